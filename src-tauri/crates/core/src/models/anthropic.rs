@@ -50,9 +50,12 @@ pub struct AnthropicTool {
     pub description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub input_schema: Option<serde_json::Value>,
+    /// Prompt Caching 断点（{"type": "ephemeral"}）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AnthropicMessagesRequest {
     pub model: String,
     pub messages: Vec<AnthropicMessage>,
@@ -68,6 +71,77 @@ pub struct AnthropicMessagesRequest {
     pub tools: Option<Vec<AnthropicTool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AnthropicMetadata>,
+    /// Extended Thinking 配置
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<AnthropicThinking>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+    /// 未建模的字段（container、mcp_servers 等），原样透传
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl AnthropicMessagesRequest {
+    /// 客户端设置了的参数名（不含 model、messages、stream）
+    ///
+    /// 转换器据此判断哪些参数无法映射到目标协议。
+    pub fn present_params(&self) -> Vec<String> {
+        let mut params: Vec<String> = [
+            ("max_tokens", self.max_tokens.is_some()),
+            ("system", self.system.is_some()),
+            ("temperature", self.temperature.is_some()),
+            ("tools", self.tools.is_some()),
+            ("tool_choice", self.tool_choice.is_some()),
+            ("top_p", self.top_p.is_some()),
+            ("top_k", self.top_k.is_some()),
+            ("stop_sequences", self.stop_sequences.is_some()),
+            ("metadata", self.metadata.is_some()),
+            ("thinking", self.thinking.is_some()),
+            ("service_tier", self.service_tier.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, present)| *present)
+        .map(|(name, _)| name.to_string())
+        .collect();
+        params.extend(self.extra.keys().cloned());
+        params
+    }
+}
+
+/// 请求元数据
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnthropicMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// 未建模的元数据字段，原样透传
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Extended Thinking 配置
+///
+/// `type` 为 enabled 时需提供 `budget_tokens`；disabled 表示关闭。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnthropicThinking {
+    #[serde(rename = "type")]
+    pub thinking_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget_tokens: Option<u32>,
+}
+
+impl AnthropicThinking {
+    /// 是否启用了思维链
+    pub fn is_enabled(&self) -> bool {
+        self.thinking_type != "disabled"
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -140,3 +214,43 @@ pub enum AnthropicDelta {
 pub struct AnthropicMessageDelta {
     pub stop_reason: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_request_keeps_all_fields() {
+        let json = serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "hi"}],
+            "top_k": 40,
+            "stop_sequences": ["\n\nHuman:"],
+            "metadata": {"user_id": "u-1", "session": "s-1"},
+            "thinking": {"type": "enabled", "budget_tokens": 2048},
+            "container": "c-1"
+        });
+
+        let request: AnthropicMessagesRequest = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(request.top_k, Some(40));
+        assert!(request.thinking.as_ref().unwrap().is_enabled());
+        assert_eq!(request.thinking.as_ref().unwrap().budget_tokens, Some(2048));
+        assert_eq!(
+            request.metadata.as_ref().unwrap().user_id.as_deref(),
+            Some("u-1")
+        );
+        assert!(request.present_params().contains(&"container".to_string()));
+
+        let round_trip = serde_json::to_value(&request).unwrap();
+        for key in [
+            "top_k",
+            "stop_sequences",
+            "metadata",
+            "thinking",
+            "container",
+        ] {
+            assert_eq!(round_trip[key], json[key], "字段 {} 丢失", key);
+        }
+    }
+}
@@ -126,7 +126,7 @@ pub enum Tool {
     WebSearch20250305,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChatCompletionRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
@@ -142,8 +142,150 @@ pub struct ChatCompletionRequest {
     pub tools: Option<Vec<Tool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_choice: Option<serde_json::Value>,
+    /// 思维链强度：none, low, medium, high
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reasoning_effort: Option<String>,
+    /// 最大输出 token 数（含推理 token，新版替代 max_tokens）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    /// 停止序列（字符串或字符串数组）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<StopSequence>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// 生成候选数量
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    /// 输出格式：text / json_object / json_schema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u32>,
+    /// token ID -> 偏置值
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logit_bias: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel_tool_calls: Option<bool>,
+    /// 终端用户标识
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+    /// 输出模态（text, audio）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<serde_json::Value>,
+    /// 预测输出（Predicted Outputs）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prediction: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_search_options: Option<serde_json::Value>,
+    /// 输出详细程度：low, medium, high
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbosity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_cache_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_identifier: Option<String>,
+    /// 未建模的字段，原样透传
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ChatCompletionRequest {
+    /// 客户端设置了的参数名（不含 model、messages、stream）
+    ///
+    /// 转换器据此判断哪些参数无法映射到目标协议。
+    pub fn present_params(&self) -> Vec<String> {
+        let mut params: Vec<String> = [
+            ("temperature", self.temperature.is_some()),
+            ("max_tokens", self.max_tokens.is_some()),
+            ("top_p", self.top_p.is_some()),
+            ("tools", self.tools.is_some()),
+            ("tool_choice", self.tool_choice.is_some()),
+            ("reasoning_effort", self.reasoning_effort.is_some()),
+            (
+                "max_completion_tokens",
+                self.max_completion_tokens.is_some(),
+            ),
+            ("frequency_penalty", self.frequency_penalty.is_some()),
+            ("presence_penalty", self.presence_penalty.is_some()),
+            ("stop", self.stop.is_some()),
+            ("seed", self.seed.is_some()),
+            ("n", self.n.is_some()),
+            ("response_format", self.response_format.is_some()),
+            ("logprobs", self.logprobs.is_some()),
+            ("top_logprobs", self.top_logprobs.is_some()),
+            ("logit_bias", self.logit_bias.is_some()),
+            ("stream_options", self.stream_options.is_some()),
+            ("parallel_tool_calls", self.parallel_tool_calls.is_some()),
+            ("user", self.user.is_some()),
+            ("metadata", self.metadata.is_some()),
+            ("store", self.store.is_some()),
+            ("service_tier", self.service_tier.is_some()),
+            ("modalities", self.modalities.is_some()),
+            ("audio", self.audio.is_some()),
+            ("prediction", self.prediction.is_some()),
+            ("web_search_options", self.web_search_options.is_some()),
+            ("verbosity", self.verbosity.is_some()),
+            ("prompt_cache_key", self.prompt_cache_key.is_some()),
+            ("safety_identifier", self.safety_identifier.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, present)| *present)
+        .map(|(name, _)| name.to_string())
+        .collect();
+        params.extend(self.extra.keys().cloned());
+        params
+    }
+
+    /// 有效的最大输出 token 数（max_completion_tokens 优先）
+    pub fn effective_max_tokens(&self) -> Option<u32> {
+        self.max_completion_tokens.or(self.max_tokens)
+    }
+}
+
+/// 停止序列
+///
+/// OpenAI 允许传单个字符串或字符串数组。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StopSequence {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl StopSequence {
+    /// 统一转换为字符串列表
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            StopSequence::Single(s) => vec![s.clone()],
+            StopSequence::Multiple(v) => v.clone(),
+        }
+    }
+}
+
+/// 流式选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamOptions {
+    /// 是否在最后一个 chunk 中返回 usage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_usage: Option<bool>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -257,3 +399,46 @@ pub struct ImageData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub revised_prompt: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_completion_request_keeps_all_fields() {
+        let json = serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}],
+            "stop": "END",
+            "seed": 42,
+            "n": 2,
+            "response_format": {"type": "json_object"},
+            "stream_options": {"include_usage": true},
+            "parallel_tool_calls": false,
+            "user": "u-1",
+            "functions": [{"name": "legacy"}]
+        });
+
+        let request: ChatCompletionRequest = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(request.stop, Some(StopSequence::Single("END".to_string())));
+        assert_eq!(request.seed, Some(42));
+        assert_eq!(
+            request.stream_options.as_ref().unwrap().include_usage,
+            Some(true)
+        );
+        assert!(request.extra.contains_key("functions"));
+        assert!(request.present_params().contains(&"functions".to_string()));
+
+        let round_trip = serde_json::to_value(&request).unwrap();
+        for key in ["stop", "seed", "n", "response_format", "user", "functions"] {
+            assert_eq!(round_trip[key], json[key], "字段 {} 丢失", key);
+        }
+    }
+
+    #[test]
+    fn test_stop_sequence_to_vec() {
+        let multi: StopSequence = serde_json::from_value(serde_json::json!(["a", "b"])).unwrap();
+        assert_eq!(multi.to_vec(), vec!["a", "b"]);
+        assert_eq!(StopSequence::Single("x".into()).to_vec(), vec!["x"]);
+    }
+}
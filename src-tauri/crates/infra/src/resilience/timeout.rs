@@ -17,6 +17,10 @@ pub struct TimeoutConfig {
     /// 流式响应空闲超时（毫秒），0 表示无超时
     /// 当流式响应中两个 chunk 之间的间隔超过此值时触发超时
     pub stream_idle_timeout_ms: u64,
+    /// 对冲请求阈值（毫秒），0 表示不启用对冲
+    /// 超过此时间仍未收到首字节时，向备用凭证或 Provider 发起相同请求
+    #[serde(default)]
+    pub hedge_delay_ms: u64,
 }
 
 impl Default for TimeoutConfig {
@@ -24,6 +28,7 @@ impl Default for TimeoutConfig {
         Self {
             request_timeout_ms: 120_000,    // 2 分钟
             stream_idle_timeout_ms: 30_000, // 30 秒
+            hedge_delay_ms: 0,              // 默认不启用对冲
         }
     }
 }
@@ -34,6 +39,7 @@ impl TimeoutConfig {
         Self {
             request_timeout_ms,
             stream_idle_timeout_ms,
+            hedge_delay_ms: 0,
         }
     }
 
@@ -42,9 +48,16 @@ impl TimeoutConfig {
         Self {
             request_timeout_ms: 0,
             stream_idle_timeout_ms: 0,
+            hedge_delay_ms: 0,
         }
     }
 
+    /// 设置对冲请求阈值
+    pub fn with_hedge_delay(mut self, hedge_delay_ms: u64) -> Self {
+        self.hedge_delay_ms = hedge_delay_ms;
+        self
+    }
+
     /// 获取请求超时 Duration
     pub fn request_timeout(&self) -> Option<Duration> {
         if self.request_timeout_ms > 0 {
@@ -63,6 +76,20 @@ impl TimeoutConfig {
         }
     }
 
+    /// 获取对冲请求阈值 Duration
+    pub fn hedge_delay(&self) -> Option<Duration> {
+        if self.hedge_delay_ms > 0 {
+            Some(Duration::from_millis(self.hedge_delay_ms))
+        } else {
+            None
+        }
+    }
+
+    /// 检查是否启用对冲请求
+    pub fn has_hedging(&self) -> bool {
+        self.hedge_delay_ms > 0
+    }
+
     /// 检查是否启用请求超时
     pub fn has_request_timeout(&self) -> bool {
         self.request_timeout_ms > 0
@@ -445,6 +472,20 @@ mod unit_tests {
         assert_eq!(config.stream_idle_timeout_ms, 30_000);
         assert!(config.has_request_timeout());
         assert!(config.has_stream_idle_timeout());
+        assert!(!config.has_hedging());
+    }
+
+    #[test]
+    fn test_timeout_config_hedge_delay() {
+        let config = TimeoutConfig::default().with_hedge_delay(800);
+        assert!(config.has_hedging());
+        assert_eq!(config.hedge_delay(), Some(Duration::from_millis(800)));
+
+        // 旧配置缺少字段时默认不启用
+        let legacy: TimeoutConfig =
+            serde_json::from_str(r#"{"request_timeout_ms":1000,"stream_idle_timeout_ms":500}"#)
+                .unwrap();
+        assert!(!legacy.has_hedging());
     }
 
     #[test]
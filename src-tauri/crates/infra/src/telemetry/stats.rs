@@ -211,6 +211,37 @@ impl StatsAggregator {
             .collect()
     }
 
+    /// 按凭证和模型分组统计
+    ///
+    /// 未记录凭证 ID 的日志不参与统计
+    ///
+    /// # Arguments
+    /// * `range` - 可选的时间范围
+    ///
+    /// # Returns
+    /// 按 (凭证 ID, Model) 分组的统计数据
+    pub fn by_credential_and_model(
+        &self,
+        range: Option<TimeRange>,
+    ) -> HashMap<(String, String), StatsSummary> {
+        let logs = self.get_logs_in_range(range);
+
+        let mut grouped: HashMap<(String, String), Vec<RequestLog>> = HashMap::new();
+        for log in logs {
+            if let Some(credential_id) = log.credential_id.clone() {
+                grouped
+                    .entry((credential_id, log.model.clone()))
+                    .or_default()
+                    .push(log);
+            }
+        }
+
+        grouped
+            .into_iter()
+            .map(|(key, logs)| (key, StatsSummary::from_logs(&logs)))
+            .collect()
+    }
+
     /// 按状态分组统计
     ///
     /// # Arguments
@@ -798,6 +798,37 @@ fn test_stats_aggregator_by_model() {
     assert_eq!(stats["model-b"].summary.total_requests, 1);
 }
 
+#[test]
+fn test_stats_aggregator_by_credential_and_model() {
+    let aggregator = create_test_aggregator();
+
+    for (credential, success) in [(Some("cred-1"), true), (Some("cred-1"), false), (None, true)] {
+        let mut log = RequestLog::new(
+            uuid::Uuid::new_v4().to_string(),
+            ProviderType::Kiro,
+            "model-a".to_string(),
+            false,
+        );
+        if let Some(credential) = credential {
+            log.set_credential_id(credential.to_string());
+        }
+        if success {
+            log.mark_success(100, 200);
+        } else {
+            log.mark_failed(100, Some(500), "error".to_string());
+        }
+        aggregator.record(log);
+    }
+
+    let stats = aggregator.by_credential_and_model(None);
+
+    // 没有凭证 ID 的日志被忽略
+    assert_eq!(stats.len(), 1);
+    let summary = &stats[&("cred-1".to_string(), "model-a".to_string())];
+    assert_eq!(summary.total_requests, 2);
+    assert_eq!(summary.failed_requests, 1);
+}
+
 #[test]
 fn test_stats_aggregator_time_range() {
     let aggregator = create_test_aggregator();
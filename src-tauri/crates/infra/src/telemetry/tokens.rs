@@ -31,6 +31,15 @@ pub struct TokenUsageRecord {
     pub source: TokenSource,
     /// 关联的请求 ID
     pub request_id: Option<String>,
+    /// 是否为被丢弃的调用消耗（如对冲请求中落败的一方）
+    #[serde(default)]
+    pub wasted: bool,
+    /// 写入 Prompt Cache 的输入 Token 数（Anthropic cache_creation_input_tokens）
+    #[serde(default)]
+    pub cache_creation_input_tokens: u32,
+    /// 命中 Prompt Cache 的输入 Token 数（Anthropic cache_read_input_tokens）
+    #[serde(default)]
+    pub cache_read_input_tokens: u32,
 }
 
 impl TokenUsageRecord {
@@ -53,6 +62,9 @@ impl TokenUsageRecord {
             total_tokens: input_tokens + output_tokens,
             source,
             request_id: None,
+            wasted: false,
+            cache_creation_input_tokens: 0,
+            cache_read_input_tokens: 0,
         }
     }
 
@@ -61,6 +73,19 @@ impl TokenUsageRecord {
         self.request_id = Some(request_id);
         self
     }
+
+    /// 标记为被丢弃的调用消耗
+    pub fn mark_wasted(mut self) -> Self {
+        self.wasted = true;
+        self
+    }
+
+    /// 设置 Prompt Cache 写入 / 命中的 Token 数
+    pub fn with_cache_tokens(mut self, creation: u32, read: u32) -> Self {
+        self.cache_creation_input_tokens = creation;
+        self.cache_read_input_tokens = read;
+        self
+    }
 }
 
 /// Token 来源
@@ -101,6 +126,15 @@ pub struct TokenStatsSummary {
     pub avg_input_tokens: f64,
     /// 平均输出 Token 数
     pub avg_output_tokens: f64,
+    /// 被丢弃调用消耗的 Token 数（已计入总数）
+    #[serde(default)]
+    pub wasted_tokens: u64,
+    /// 写入 Prompt Cache 的 Token 数
+    #[serde(default)]
+    pub total_cache_creation_tokens: u64,
+    /// 命中 Prompt Cache 的 Token 数
+    #[serde(default)]
+    pub total_cache_read_tokens: u64,
 }
 
 impl TokenStatsSummary {
@@ -122,6 +156,19 @@ impl TokenStatsSummary {
             .iter()
             .filter(|r| r.source == TokenSource::Estimated)
             .count() as u64;
+        let wasted_tokens: u64 = records
+            .iter()
+            .filter(|r| r.wasted)
+            .map(|r| r.total_tokens as u64)
+            .sum();
+        let total_cache_creation_tokens: u64 = records
+            .iter()
+            .map(|r| r.cache_creation_input_tokens as u64)
+            .sum();
+        let total_cache_read_tokens: u64 = records
+            .iter()
+            .map(|r| r.cache_read_input_tokens as u64)
+            .sum();
 
         Self {
             total_input_tokens,
@@ -132,6 +179,9 @@ impl TokenStatsSummary {
             estimated_count,
             avg_input_tokens: total_input_tokens as f64 / record_count as f64,
             avg_output_tokens: total_output_tokens as f64 / record_count as f64,
+            wasted_tokens,
+            total_cache_creation_tokens,
+            total_cache_read_tokens,
         }
     }
 }
@@ -267,6 +317,26 @@ impl TokenTracker {
         }
     }
 
+    /// 为已记录的请求补充 Prompt Cache Token 数
+    ///
+    /// 流式响应的缓存用量在响应体发送过程中才能得到，此时请求记录已写入，
+    /// 因此按请求 ID 更新最近一条记录。未找到记录时返回 false。
+    pub fn add_cache_tokens(&self, request_id: &str, creation: u32, read: u32) -> bool {
+        let mut records = self.records.write();
+        match records
+            .iter_mut()
+            .rev()
+            .find(|r| r.request_id.as_deref() == Some(request_id))
+        {
+            Some(record) => {
+                record.cache_creation_input_tokens = creation;
+                record.cache_read_input_tokens = read;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// 获取所有记录
     pub fn get_all(&self) -> Vec<TokenUsageRecord> {
         self.records.read().iter().cloned().collect()
@@ -640,6 +710,72 @@ mod token_tests {
         assert_eq!(summary.estimated_count, 1);
         assert!((summary.avg_input_tokens - 150.0).abs() < 0.001);
         assert!((summary.avg_output_tokens - 75.0).abs() < 0.001);
+        assert_eq!(summary.wasted_tokens, 0);
+    }
+
+    #[test]
+    fn test_token_stats_summary_wasted_tokens() {
+        let records = vec![
+            TokenUsageRecord::new(
+                "1".to_string(),
+                ProviderType::Kiro,
+                "model".to_string(),
+                100,
+                50,
+                TokenSource::Actual,
+            ),
+            TokenUsageRecord::new(
+                "2".to_string(),
+                ProviderType::Gemini,
+                "model".to_string(),
+                100,
+                10,
+                TokenSource::Estimated,
+            )
+            .mark_wasted(),
+        ];
+
+        let summary = TokenStatsSummary::from_records(&records);
+
+        assert_eq!(summary.total_tokens, 260);
+        assert_eq!(summary.wasted_tokens, 110);
+    }
+
+    #[test]
+    fn test_token_tracker_add_cache_tokens() {
+        let tracker = TokenTracker::with_defaults();
+        tracker.record(
+            TokenUsageRecord::new(
+                "1".to_string(),
+                ProviderType::Claude,
+                "claude-sonnet".to_string(),
+                100,
+                20,
+                TokenSource::Actual,
+            )
+            .with_request_id("req-1".to_string())
+            .with_cache_tokens(500, 0),
+        );
+        tracker.record(
+            TokenUsageRecord::new(
+                "2".to_string(),
+                ProviderType::Claude,
+                "claude-sonnet".to_string(),
+                100,
+                20,
+                TokenSource::Estimated,
+            )
+            .with_request_id("req-2".to_string()),
+        );
+
+        assert!(tracker.add_cache_tokens("req-2", 0, 480));
+        assert!(!tracker.add_cache_tokens("req-missing", 1, 1));
+
+        let summary = TokenStatsSummary::from_records(&tracker.get_all());
+        assert_eq!(summary.total_cache_creation_tokens, 500);
+        assert_eq!(summary.total_cache_read_tokens, 480);
+        // 缓存 Token 不计入输入 / 总数
+        assert_eq!(summary.total_tokens, 240);
     }
 
     #[test]
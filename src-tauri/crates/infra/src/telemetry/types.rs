@@ -67,6 +67,12 @@ pub struct RequestLog {
     pub credential_id: Option<String>,
     /// 重试次数
     pub retry_count: u32,
+    /// 等待凭证的排队时间（毫秒，未排队为 0）
+    #[serde(default)]
+    pub queue_wait_ms: u64,
+    /// 进入队列时的排队深度（未排队为 0）
+    #[serde(default)]
+    pub queue_depth: u32,
 }
 
 impl RequestLog {
@@ -87,6 +93,8 @@ impl RequestLog {
             is_streaming,
             credential_id: None,
             retry_count: 0,
+            queue_wait_ms: 0,
+            queue_depth: 0,
         }
     }
 
@@ -133,6 +141,12 @@ impl RequestLog {
         self.credential_id = Some(id);
     }
 
+    /// 设置排队信息
+    pub fn set_queue_info(&mut self, wait_ms: u64, depth: u32) {
+        self.queue_wait_ms = wait_ms;
+        self.queue_depth = depth;
+    }
+
     /// 增加重试次数
     pub fn increment_retry(&mut self) {
         self.retry_count += 1;
@@ -204,6 +218,15 @@ pub struct StatsSummary {
     pub total_output_tokens: u64,
     /// 总 Token 数
     pub total_tokens: u64,
+    /// 排队请求数
+    #[serde(default)]
+    pub queued_requests: u64,
+    /// 排队请求的平均等待时间（毫秒）
+    #[serde(default)]
+    pub avg_queue_wait_ms: f64,
+    /// 最大排队等待时间（毫秒）
+    #[serde(default)]
+    pub max_queue_wait_ms: u64,
 }
 
 impl StatsSummary {
@@ -252,6 +275,19 @@ impl StatsSummary {
             .sum();
         let total_tokens = total_input_tokens + total_output_tokens;
 
+        let queue_waits: Vec<u64> = logs
+            .iter()
+            .filter(|l| l.queue_depth > 0)
+            .map(|l| l.queue_wait_ms)
+            .collect();
+        let queued_requests = queue_waits.len() as u64;
+        let avg_queue_wait_ms = if !queue_waits.is_empty() {
+            queue_waits.iter().sum::<u64>() as f64 / queue_waits.len() as f64
+        } else {
+            0.0
+        };
+        let max_queue_wait_ms = queue_waits.iter().max().copied().unwrap_or(0);
+
         Self {
             total_requests,
             successful_requests,
@@ -264,6 +300,9 @@ impl StatsSummary {
             total_input_tokens,
             total_output_tokens,
             total_tokens,
+            queued_requests,
+            avg_queue_wait_ms,
+            max_queue_wait_ms,
         }
     }
 }
@@ -443,4 +482,29 @@ mod type_tests {
         assert_eq!(summary.total_input_tokens, 150);
         assert_eq!(summary.total_output_tokens, 75);
     }
+
+    #[test]
+    fn test_stats_summary_queue_wait() {
+        let mut queued = RequestLog::new(
+            "1".to_string(),
+            ProviderType::Kiro,
+            "model".to_string(),
+            false,
+        );
+        queued.mark_success(500, 200);
+        queued.set_queue_info(400, 2);
+
+        let mut direct = RequestLog::new(
+            "2".to_string(),
+            ProviderType::Kiro,
+            "model".to_string(),
+            false,
+        );
+        direct.mark_success(100, 200);
+
+        let summary = StatsSummary::from_logs(&[queued, direct]);
+        assert_eq!(summary.queued_requests, 1);
+        assert_eq!(summary.avg_queue_wait_ms, 400.0);
+        assert_eq!(summary.max_queue_wait_ms, 400);
+    }
 }
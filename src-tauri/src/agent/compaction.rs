@@ -0,0 +1,577 @@
+//! 上下文自动压缩
+//!
+//! 会话接近模型上下文上限时压缩历史消息，避免请求超出上下文窗口
+//!
+//! ## 压缩步骤
+//! 1. 按 token 估算会话大小，未超过阈值时不处理
+//! 2. 截断较早的工具输出，完整内容可通过检索句柄取回
+//! 3. 仍超过目标大小时，由模型对较早的消息生成摘要；置顶消息和最近的消息原样保留
+//!
+//! 摘要的生成由 `NativeAgent` 完成，本模块只负责规划和应用压缩。
+
+use crate::agent::types::{
+    AgentCompaction, AgentMessage, AgentMessageNode, CompactedToolOutput, MessageContent,
+};
+use crate::config::CompactionConfig;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+/// 检索被截断工具输出的工具名称
+pub const RETRIEVE_TOOL_NAME: &str = "retrieve_tool_output";
+
+/// 每条消息的固定开销（角色、分隔符等）
+const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+/// 摘要输入中单条消息保留的最大字符数
+const SUMMARY_INPUT_MAX_CHARS: usize = 2000;
+
+/// 生成摘要的系统提示词
+pub const SUMMARY_SYSTEM_PROMPT: &str =
+    "You summarize the earlier part of a conversation between a user and an AI agent so the \
+     agent can continue working with a smaller context. Preserve the user's goals and \
+     requirements, decisions that were made, files, commands and identifiers that were touched, \
+     important tool findings, and open questions or next steps. Be concise and factual. Reply \
+     with the summary only.";
+
+/// 摘要消息前缀
+const SUMMARY_PREFIX: &str = "[Summary of the earlier conversation]";
+
+/// 摘要之后插入的 assistant 确认消息
+const SUMMARY_ACK: &str = "Understood. I will continue from the summary above.";
+
+/// 估算文本的 token 数
+pub fn estimate_tokens(text: &str) -> u32 {
+    if text.is_empty() {
+        return 0;
+    }
+    tiktoken_rs::cl100k_base_singleton()
+        .lock()
+        .encode_ordinary(text)
+        .len() as u32
+}
+
+/// 估算单条消息的 token 数
+pub fn estimate_message_tokens(message: &AgentMessage) -> u32 {
+    let mut tokens = MESSAGE_OVERHEAD_TOKENS + estimate_tokens(&message.content.as_text());
+    if let Some(tool_calls) = &message.tool_calls {
+        for call in tool_calls {
+            tokens += estimate_tokens(&call.function.name);
+            tokens += estimate_tokens(&call.function.arguments);
+        }
+    }
+    if let Some(reasoning) = &message.reasoning_content {
+        tokens += estimate_tokens(reasoning);
+    }
+    tokens
+}
+
+/// 估算消息列表的 token 数
+pub fn estimate_messages_tokens(messages: &[AgentMessage]) -> u32 {
+    messages.iter().map(estimate_message_tokens).sum()
+}
+
+/// 最近消息的起始位置
+///
+/// 至少保留 `keep_recent` 条消息，并向前移动到最近的非工具结果消息，
+/// 避免把工具调用和对应的工具结果拆开。没有可压缩的消息时返回 0。
+fn tail_start(messages: &[AgentMessage], keep_recent: usize) -> usize {
+    let candidate = messages.len().saturating_sub(keep_recent.max(1));
+    messages[..=candidate.min(messages.len().saturating_sub(1))]
+        .iter()
+        .rposition(|m| m.role != "tool")
+        .unwrap_or(0)
+}
+
+/// 压缩计划
+#[derive(Debug, Clone)]
+pub struct CompactionPlan {
+    /// 截断工具输出后的消息
+    messages: Vec<AgentMessage>,
+    /// 需要摘要的消息范围 `[0, summarize_end)`，0 表示只截断工具输出
+    summarize_end: usize,
+    /// 被截断的工具输出
+    truncated_outputs: Vec<CompactedToolOutput>,
+    /// 压缩前估算的 token 数
+    tokens_before: u32,
+}
+
+impl CompactionPlan {
+    /// 是否需要生成摘要
+    pub fn needs_summary(&self) -> bool {
+        self.summarize_end > 0
+    }
+
+    /// 构建生成摘要的输入（对话记录）
+    pub fn summary_input(&self) -> String {
+        let mut transcript = String::new();
+        for message in &self.messages[..self.summarize_end] {
+            let mut text = message.content.as_text();
+            if let Some(tool_calls) = &message.tool_calls {
+                for call in tool_calls {
+                    text.push_str(&format!(
+                        "\n[calls {}({})]",
+                        call.function.name, call.function.arguments
+                    ));
+                }
+            }
+            if text.chars().count() > SUMMARY_INPUT_MAX_CHARS {
+                text = text.chars().take(SUMMARY_INPUT_MAX_CHARS).collect();
+                text.push_str(" ...");
+            }
+            transcript.push_str(&format!("[{}] {}\n\n", message.role, text.trim()));
+        }
+        transcript
+    }
+}
+
+/// 压缩结果
+#[derive(Debug, Clone)]
+pub struct CompactionOutcome {
+    /// 压缩后的消息
+    pub messages: Vec<AgentMessage>,
+    /// 摘要内容
+    pub summary: Option<String>,
+    /// 被摘要的消息数量
+    pub summarized_messages: usize,
+    /// 原样保留的最近消息数量
+    pub kept_messages: usize,
+    /// 被截断的工具输出
+    pub truncated_outputs: Vec<CompactedToolOutput>,
+    /// 压缩前估算的 token 数
+    pub tokens_before: u32,
+    /// 压缩后估算的 token 数
+    pub tokens_after: u32,
+}
+
+/// 上下文压缩器
+///
+/// 由工具循环持有，同时保存被截断的工具输出以供 `retrieve_tool_output` 工具检索
+pub struct ContextCompactor {
+    /// 配置
+    config: CompactionConfig,
+    /// 模型上下文长度（token）
+    context_window: u32,
+    /// 检索句柄 -> 完整工具输出
+    outputs: Mutex<HashMap<String, String>>,
+}
+
+impl ContextCompactor {
+    /// 创建压缩器，`context_window` 为 None 时使用配置中的默认值
+    pub fn new(config: CompactionConfig, context_window: Option<u32>) -> Self {
+        let context_window = context_window
+            .filter(|w| *w > 0)
+            .unwrap_or(config.default_context_window);
+        Self {
+            config,
+            context_window,
+            outputs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 载入已持久化的工具输出
+    pub fn with_outputs(self, outputs: impl IntoIterator<Item = CompactedToolOutput>) -> Self {
+        self.outputs
+            .lock()
+            .extend(outputs.into_iter().map(|o| (o.handle, o.content)));
+        self
+    }
+
+    /// 模型上下文长度
+    pub fn context_window(&self) -> u32 {
+        self.context_window
+    }
+
+    /// 生成摘要使用的模型
+    pub fn summary_model(&self) -> Option<&str> {
+        self.config.summary_model.as_deref()
+    }
+
+    fn threshold_tokens(&self) -> u32 {
+        (self.context_window as f32 * self.config.threshold_ratio) as u32
+    }
+
+    fn target_tokens(&self) -> u32 {
+        (self.context_window as f32 * self.config.target_ratio) as u32
+    }
+
+    /// 规划压缩
+    ///
+    /// `pending_tokens` 为即将追加的内容（如新的用户消息）的 token 数。
+    /// 未超过阈值时返回 None。
+    pub fn plan(&self, messages: &[AgentMessage], pending_tokens: u32) -> Option<CompactionPlan> {
+        let tokens_before = estimate_messages_tokens(messages);
+        if messages.is_empty() || tokens_before + pending_tokens < self.threshold_tokens() {
+            return None;
+        }
+
+        let tail = tail_start(messages, self.config.keep_recent_messages);
+        let mut messages = messages.to_vec();
+        let mut truncated_outputs = Vec::new();
+        for message in &mut messages[..tail] {
+            if let Some(output) = self.truncate_tool_output(message) {
+                truncated_outputs.push(output);
+            }
+        }
+
+        let tokens = estimate_messages_tokens(&messages) + pending_tokens;
+        let summarize_end = if tokens > self.target_tokens() {
+            tail
+        } else {
+            0
+        };
+        if summarize_end == 0 && truncated_outputs.is_empty() {
+            return None;
+        }
+
+        Some(CompactionPlan {
+            messages,
+            summarize_end,
+            truncated_outputs,
+            tokens_before,
+        })
+    }
+
+    /// 截断过长的工具输出，返回被截断的完整内容
+    fn truncate_tool_output(&self, message: &mut AgentMessage) -> Option<CompactedToolOutput> {
+        if message.role != "tool" || message.pinned {
+            return None;
+        }
+        let content = message.content.as_text();
+        let total_chars = content.chars().count();
+        if total_chars <= self.config.tool_result_max_chars {
+            return None;
+        }
+
+        let handle = format!("out_{}", &uuid::Uuid::new_v4().simple().to_string()[..12]);
+        let preview: String = content
+            .chars()
+            .take(self.config.tool_result_max_chars)
+            .collect();
+        message.content = MessageContent::Text(format!(
+            "{}\n\n[Output truncated: {} characters in total. Call {} with handle \"{}\" to read the full output.]",
+            preview, total_chars, RETRIEVE_TOOL_NAME, handle
+        ));
+        Some(CompactedToolOutput {
+            handle,
+            tool_call_id: message.tool_call_id.clone(),
+            content,
+        })
+    }
+
+    /// 应用压缩计划
+    ///
+    /// `summary` 为 None 时（无需摘要或摘要失败）只截断工具输出。
+    pub fn apply(&self, plan: CompactionPlan, summary: Option<String>) -> CompactionOutcome {
+        {
+            let mut outputs = self.outputs.lock();
+            for output in &plan.truncated_outputs {
+                outputs.insert(output.handle.clone(), output.content.clone());
+            }
+        }
+
+        let summary = summary
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty() && plan.needs_summary());
+        let summarize_end = if summary.is_some() {
+            plan.summarize_end
+        } else {
+            0
+        };
+
+        let mut messages = plan.messages;
+        let tail = messages.split_off(summarize_end);
+        let kept_messages = tail.len();
+        let mut summarized_messages = 0;
+        if let Some(summary) = &summary {
+            let head = std::mem::take(&mut messages);
+            messages.push(text_message(
+                "user",
+                format!("{}\n{}", SUMMARY_PREFIX, summary),
+            ));
+            for message in head {
+                if message.pinned {
+                    messages.push(AgentMessage {
+                        tool_calls: None,
+                        tool_call_id: None,
+                        role: if message.role == "tool" {
+                            "user".to_string()
+                        } else {
+                            message.role.clone()
+                        },
+                        ..message
+                    });
+                } else {
+                    summarized_messages += 1;
+                }
+            }
+            let needs_ack = messages.last().is_some_and(|m| m.role == "user")
+                && tail.first().is_none_or(|m| m.role == "user");
+            if needs_ack {
+                messages.push(text_message("assistant", SUMMARY_ACK.to_string()));
+            }
+        }
+        messages.extend(tail);
+
+        let tokens_after = estimate_messages_tokens(&messages);
+        CompactionOutcome {
+            messages,
+            summary,
+            summarized_messages,
+            kept_messages,
+            truncated_outputs: plan.truncated_outputs,
+            tokens_before: plan.tokens_before,
+            tokens_after,
+        }
+    }
+
+    /// 按句柄取回被截断的工具输出
+    pub fn retrieve(&self, handle: &str) -> Option<String> {
+        self.outputs.lock().get(handle).cloned()
+    }
+
+    /// retrieve_tool_output 工具定义（OpenAI 格式）
+    pub fn tool_definition() -> crate::models::openai::Tool {
+        crate::models::openai::Tool::Function {
+            function: crate::models::openai::FunctionDef {
+                name: RETRIEVE_TOOL_NAME.to_string(),
+                description: Some(
+                    "Read the full content of a tool output that was truncated to save context. \
+                     Use the handle shown in the truncation notice."
+                        .to_string(),
+                ),
+                parameters: Some(serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "handle": {
+                            "type": "string",
+                            "description": "Handle from the truncation notice"
+                        }
+                    },
+                    "required": ["handle"]
+                })),
+            },
+        }
+    }
+}
+
+/// 用持久化的压缩记录恢复会话消息
+///
+/// `branch` 为激活分支。`compaction.message_id` 及之前的消息替换为摘要，
+/// 其中的置顶消息和最后 `kept_messages` 条消息原样保留；
+/// 压缩点不在该分支上时返回完整分支。
+///
+/// 数据库中不保存工具消息，按条数保留时恢复的历史可能比压缩时多，不会丢失消息。
+pub fn restore_compacted(
+    branch: &[AgentMessageNode],
+    compaction: &AgentCompaction,
+) -> Vec<AgentMessage> {
+    let position = compaction
+        .message_id
+        .and_then(|id| branch.iter().position(|n| n.id == id));
+    let Some(position) = position else {
+        return branch.iter().map(|n| n.message.clone()).collect();
+    };
+
+    let (head, after) = branch.split_at(position + 1);
+    let head: Vec<AgentMessage> = head.iter().map(|n| n.message.clone()).collect();
+    let keep_from = tail_start(&head, compaction.kept_messages as usize);
+    let plan = CompactionPlan {
+        messages: head,
+        summarize_end: keep_from,
+        truncated_outputs: Vec::new(),
+        tokens_before: 0,
+    };
+    let compactor = ContextCompactor::new(CompactionConfig::default(), None);
+    let mut messages = compactor
+        .apply(plan, Some(compaction.summary.clone()))
+        .messages;
+    messages.extend(after.iter().map(|n| n.message.clone()));
+    messages
+}
+
+fn text_message(role: &str, text: String) -> AgentMessage {
+    AgentMessage {
+        role: role.to_string(),
+        content: MessageContent::Text(text),
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        tool_calls: None,
+        tool_call_id: None,
+        reasoning_content: None,
+        pinned: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::types::{FunctionCall, ToolCall};
+
+    fn message(role: &str, text: &str) -> AgentMessage {
+        text_message(role, text.to_string())
+    }
+
+    /// 一轮对话：用户消息、带工具调用的 assistant 消息、工具结果、最终回复
+    fn turn(i: usize, output: &str) -> Vec<AgentMessage> {
+        let call_id = format!("call_{}", i);
+        vec![
+            message("user", &format!("question {}", i)),
+            AgentMessage {
+                tool_calls: Some(vec![ToolCall {
+                    id: call_id.clone(),
+                    call_type: "function".to_string(),
+                    function: FunctionCall {
+                        name: "read_file".to_string(),
+                        arguments: "{}".to_string(),
+                    },
+                }]),
+                ..message("assistant", "")
+            },
+            AgentMessage {
+                tool_call_id: Some(call_id),
+                ..message("tool", output)
+            },
+            message("assistant", &format!("answer {}", i)),
+        ]
+    }
+
+    fn config() -> CompactionConfig {
+        CompactionConfig {
+            keep_recent_messages: 3,
+            tool_result_max_chars: 20,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_under_threshold_is_untouched() {
+        let compactor = ContextCompactor::new(config(), Some(100_000));
+        let messages: Vec<_> = (0..3).flat_map(|i| turn(i, "short")).collect();
+        assert!(compactor.plan(&messages, 0).is_none());
+    }
+
+    #[test]
+    fn test_truncates_old_tool_outputs_with_handle() {
+        let big = "x ".repeat(500);
+        let messages: Vec<_> = (0..3).flat_map(|i| turn(i, &big)).collect();
+        let tokens = estimate_messages_tokens(&messages);
+        let compactor = ContextCompactor::new(
+            CompactionConfig {
+                threshold_ratio: 0.5,
+                target_ratio: 0.9,
+                ..config()
+            },
+            Some(tokens),
+        );
+
+        let plan = compactor.plan(&messages, 0).expect("应触发压缩");
+        assert!(!plan.needs_summary());
+        let outcome = compactor.apply(plan, None);
+
+        // 最后一轮在保留范围内，不截断
+        assert_eq!(outcome.truncated_outputs.len(), 2);
+        assert_eq!(outcome.messages.len(), messages.len());
+        assert!(outcome.tokens_after < outcome.tokens_before);
+        let output = &outcome.truncated_outputs[0];
+        assert_eq!(output.tool_call_id.as_deref(), Some("call_0"));
+        assert_eq!(compactor.retrieve(&output.handle), Some(big.clone()));
+        assert!(outcome.messages[2]
+            .content
+            .as_text()
+            .contains(&output.handle));
+        assert_eq!(outcome.messages[10].content.as_text(), big);
+    }
+
+    #[test]
+    fn test_summarizes_old_turns_and_keeps_pinned() {
+        let mut messages: Vec<_> = (0..4).flat_map(|i| turn(i, "result")).collect();
+        messages[3].pinned = true; // answer 0
+        let tokens = estimate_messages_tokens(&messages);
+        let compactor = ContextCompactor::new(
+            CompactionConfig {
+                threshold_ratio: 0.5,
+                target_ratio: 0.5,
+                ..config()
+            },
+            Some(tokens),
+        );
+
+        let plan = compactor.plan(&messages, 0).expect("应触发压缩");
+        assert!(plan.needs_summary());
+        assert!(plan.summary_input().contains("[calls read_file({})]"));
+        let outcome = compactor.apply(plan, Some("用户在排查问题".to_string()));
+
+        // 保留最后一次工具调用及之后的消息
+        assert_eq!(outcome.kept_messages, 3);
+        assert_eq!(outcome.summarized_messages, 12);
+        let roles: Vec<_> = outcome.messages.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(
+            roles,
+            vec!["user", "assistant", "assistant", "tool", "assistant"]
+        );
+        assert!(outcome.messages[0]
+            .content
+            .as_text()
+            .contains("用户在排查问题"));
+        assert_eq!(outcome.messages[1].content.as_text(), "answer 0");
+        assert!(outcome.messages[1].pinned);
+        assert!(outcome.messages[2].tool_calls.is_some());
+        assert!(outcome.tokens_after < outcome.tokens_before);
+    }
+
+    #[test]
+    fn test_failed_summary_keeps_history() {
+        let messages: Vec<_> = (0..4).flat_map(|i| turn(i, "result")).collect();
+        let tokens = estimate_messages_tokens(&messages);
+        let compactor = ContextCompactor::new(
+            CompactionConfig {
+                threshold_ratio: 0.5,
+                target_ratio: 0.5,
+                ..config()
+            },
+            Some(tokens),
+        );
+
+        let plan = compactor.plan(&messages, 0).unwrap();
+        let outcome = compactor.apply(plan, None);
+        assert_eq!(outcome.messages.len(), messages.len());
+        assert_eq!(outcome.summarized_messages, 0);
+    }
+
+    #[test]
+    fn test_restore_compacted_branch() {
+        let branch: Vec<AgentMessageNode> = (0..4)
+            .flat_map(|i| turn(i, "result"))
+            .enumerate()
+            .map(|(i, message)| AgentMessageNode {
+                id: i as i64 + 1,
+                parent_id: (i > 0).then_some(i as i64),
+                message,
+            })
+            .collect();
+        let compaction = AgentCompaction {
+            id: "c1".to_string(),
+            session_id: "s1".to_string(),
+            message_id: Some(12),
+            summary: "摘要".to_string(),
+            summarized_messages: 8,
+            kept_messages: 4,
+            tokens_before: 0,
+            tokens_after: 0,
+            created_at: String::new(),
+        };
+
+        let messages = restore_compacted(&branch, &compaction);
+        let texts: Vec<_> = messages.iter().map(|m| m.content.as_text()).collect();
+        assert!(texts[0].ends_with("摘要"));
+        assert_eq!(texts[1], SUMMARY_ACK);
+        assert_eq!(texts[2], "question 2");
+        assert_eq!(texts.last().unwrap(), "answer 3");
+        assert_eq!(messages.len(), 2 + 4 + 4);
+
+        let elsewhere = AgentCompaction {
+            message_id: Some(999),
+            ..compaction
+        };
+        assert_eq!(restore_compacted(&branch, &elsewhere).len(), branch.len());
+    }
+}
@@ -3,6 +3,7 @@
 //! 将 Aster AgentEvent 转换为 Tauri 可用的事件格式
 //! 用于前端实时显示流式响应
 
+use crate::agent::types::{StreamEvent, TokenUsage};
 use aster::agents::AgentEvent;
 use aster::conversation::message::{ActionRequiredData, Message, MessageContent};
 use serde::{Deserialize, Serialize};
@@ -32,7 +33,7 @@ fn extract_tool_result_text<T: serde::Serialize>(result: &T) -> String {
 /// Tauri Agent 事件
 ///
 /// 用于前端消费的事件格式，与现有的 StreamEvent 兼容
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type")]
 pub enum TauriAgentEvent {
     /// 文本增量
@@ -92,10 +93,30 @@ pub enum TauriAgentEvent {
     /// 完整消息（用于历史记录）
     #[serde(rename = "message")]
     Message { message: TauriMessage },
+
+    /// 上下文已压缩
+    #[serde(rename = "context_compacted")]
+    ContextCompacted {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        summary: Option<String>,
+        summarized_messages: usize,
+        kept_messages: usize,
+        tokens_before: u32,
+        tokens_after: u32,
+    },
+
+    /// 子 Agent 事件（task 工具启动的子 Agent 的嵌套事件）
+    #[serde(rename = "sub_agent")]
+    SubAgent {
+        parent_tool_id: String,
+        agent_id: String,
+        description: String,
+        event: Box<TauriAgentEvent>,
+    },
 }
 
 /// 工具执行结果
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TauriToolResult {
     pub success: bool,
     pub output: String,
@@ -104,14 +125,23 @@ pub struct TauriToolResult {
 }
 
 /// Token 使用量
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TauriTokenUsage {
     pub input_tokens: u32,
     pub output_tokens: u32,
 }
 
+impl From<&TokenUsage> for TauriTokenUsage {
+    fn from(usage: &TokenUsage) -> Self {
+        Self {
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+        }
+    }
+}
+
 /// 简化的消息结构
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TauriMessage {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub id: Option<String>,
@@ -121,7 +151,7 @@ pub struct TauriMessage {
 }
 
 /// 简化的消息内容
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type")]
 pub enum TauriMessageContent {
     #[serde(rename = "text")]
@@ -315,6 +345,84 @@ fn convert_message(message: Message) -> Vec<TauriAgentEvent> {
     events
 }
 
+/// 将原生 Agent 的 StreamEvent 转换为 TauriAgentEvent
+///
+/// 用于把子 Agent 的进度以统一格式嵌套进父 Agent 的事件流
+pub fn convert_stream_event(event: StreamEvent) -> TauriAgentEvent {
+    match event {
+        StreamEvent::TextDelta { text } => TauriAgentEvent::TextDelta { text },
+        StreamEvent::ReasoningDelta { text } => TauriAgentEvent::ThinkingDelta { text },
+        StreamEvent::ToolStart {
+            tool_name,
+            tool_id,
+            arguments,
+        } => TauriAgentEvent::ToolStart {
+            tool_name,
+            tool_id,
+            arguments,
+        },
+        StreamEvent::ToolEnd { tool_id, result } => TauriAgentEvent::ToolEnd {
+            tool_id,
+            result: TauriToolResult {
+                success: result.success,
+                output: result.output,
+                error: result.error,
+            },
+        },
+        StreamEvent::ActionRequired {
+            request_id,
+            action_type,
+            tool_name,
+            arguments,
+            prompt,
+            questions,
+            requested_schema,
+        } => TauriAgentEvent::ActionRequired {
+            request_id,
+            action_type,
+            data: serde_json::json!({
+                "tool_name": tool_name,
+                "arguments": arguments,
+                "prompt": prompt,
+                "questions": questions,
+                "requested_schema": requested_schema,
+            }),
+        },
+        StreamEvent::Done { usage } => TauriAgentEvent::Done {
+            usage: usage.as_ref().map(TauriTokenUsage::from),
+        },
+        StreamEvent::FinalDone { usage } => TauriAgentEvent::FinalDone {
+            usage: usage.as_ref().map(TauriTokenUsage::from),
+        },
+        StreamEvent::Error { message } => TauriAgentEvent::Error { message },
+        StreamEvent::ContextCompacted {
+            summary,
+            summarized_messages,
+            kept_messages,
+            tokens_before,
+            tokens_after,
+            ..
+        } => TauriAgentEvent::ContextCompacted {
+            summary,
+            summarized_messages,
+            kept_messages,
+            tokens_before,
+            tokens_after,
+        },
+        StreamEvent::SubAgent {
+            parent_tool_id,
+            agent_id,
+            description,
+            event,
+        } => TauriAgentEvent::SubAgent {
+            parent_tool_id,
+            agent_id,
+            description,
+            event,
+        },
+    }
+}
+
 /// 将 Aster Message 转换为 TauriMessage
 pub fn convert_to_tauri_message(message: &Message) -> TauriMessage {
     let content = message
@@ -448,4 +556,36 @@ mod tests {
             _ => panic!("Expected ModelChange event"),
         }
     }
+
+    #[test]
+    fn test_convert_stream_event_nested() {
+        let event = StreamEvent::SubAgent {
+            parent_tool_id: "call_1".to_string(),
+            agent_id: "child".to_string(),
+            description: "search".to_string(),
+            event: Box::new(convert_stream_event(StreamEvent::FinalDone {
+                usage: Some(TokenUsage::new(10, 5)),
+            })),
+        };
+
+        match convert_stream_event(event) {
+            TauriAgentEvent::SubAgent {
+                parent_tool_id,
+                event,
+                ..
+            } => {
+                assert_eq!(parent_tool_id, "call_1");
+                assert_eq!(
+                    *event,
+                    TauriAgentEvent::FinalDone {
+                        usage: Some(TauriTokenUsage {
+                            input_tokens: 10,
+                            output_tokens: 5,
+                        }),
+                    }
+                );
+            }
+            _ => panic!("Expected SubAgent event"),
+        }
+    }
 }
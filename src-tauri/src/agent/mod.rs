@@ -8,6 +8,8 @@
 //! - parsers/ - SSE 流解析器
 //! - native_agent - 核心 Agent 逻辑
 //! - tool_loop - 工具调用循环
+//! - subagent - 子 Agent 编排（task 工具）
+//! - compaction - 上下文自动压缩
 //! - tools/ - 工具实现
 //! - aster_state - Aster Agent 状态管理（新）
 //! - aster_agent - Aster Agent 包装器（新）
@@ -15,17 +17,20 @@
 
 pub mod aster_agent;
 pub mod aster_state;
+pub mod compaction;
 pub mod event_converter;
 pub mod native_agent;
 pub mod parsers;
 pub mod protocols;
+pub mod subagent;
 pub mod tool_loop;
 pub mod tools;
 pub mod types;
 
 pub use aster_agent::{AsterAgentWrapper, SessionDetail, SessionInfo};
 pub use aster_state::AsterAgentState;
-pub use event_converter::{convert_agent_event, TauriAgentEvent};
+pub use compaction::ContextCompactor;
+pub use event_converter::{convert_agent_event, convert_stream_event, TauriAgentEvent};
 pub use native_agent::{NativeAgent, NativeAgentState};
 pub use parsers::{AnthropicSSEParser, OpenAISSEParser};
 pub use protocols::{create_protocol, AnthropicProtocol, OpenAIProtocol, Protocol};
@@ -17,6 +17,7 @@
 
 #![allow(dead_code)]
 
+use crate::agent::compaction::{self, ContextCompactor, SUMMARY_SYSTEM_PROMPT};
 use crate::agent::protocols::{create_protocol, Protocol};
 use crate::agent::tool_loop::{ToolCallResult, ToolLoopEngine, ToolLoopState};
 use crate::agent::tools::{create_default_registry, ToolRegistry};
@@ -31,6 +32,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
 /// 原生 Agent 实现
@@ -125,6 +127,17 @@ impl NativeAgent {
         }
     }
 
+    /// 非流式请求的 chat/completions 地址
+    ///
+    /// 对于自定义 Provider，使用 provider 特定路由
+    fn chat_completions_url(&self) -> String {
+        if self.is_custom_provider() {
+            format!("{}/chat/completions", self.get_effective_base_url())
+        } else {
+            format!("{}/v1/chat/completions", self.base_url)
+        }
+    }
+
     /// 发送聊天请求（非流式，用于简单场景）
     pub async fn chat(&self, request: NativeChatRequest) -> Result<NativeChatResponse, String> {
         let model = request.model.unwrap_or_else(|| self.config.model.clone());
@@ -160,14 +173,10 @@ impl NativeAgent {
             tools: None,
             tool_choice: None,
             reasoning_effort: None,
+            ..Default::default()
         };
 
-        // 对于自定义 Provider，使用 provider 特定路由
-        let url = if self.is_custom_provider() {
-            format!("{}/chat/completions", self.get_effective_base_url())
-        } else {
-            format!("{}/v1/chat/completions", self.base_url)
-        };
+        let url = self.chat_completions_url();
 
         let response = self
             .client
@@ -332,28 +341,28 @@ impl NativeAgent {
         let session_id = request.session_id.clone();
         let mut state = ToolLoopState::new();
 
-        // 获取工具定义并转换为 OpenAI 格式
-        let aster_definitions = tool_loop_engine.registry().get_definitions();
-        let tools: Vec<crate::models::openai::Tool> = aster_definitions
-            .into_iter()
-            .map(|def| crate::models::openai::Tool::Function {
-                function: crate::models::openai::FunctionDef {
-                    name: def.name,
-                    description: Some(def.description),
-                    parameters: Some(def.input_schema),
-                },
-            })
-            .collect();
+        // 获取工具定义（OpenAI 格式）
+        let tools = tool_loop_engine.tool_definitions();
         let tools_ref = if tools.is_empty() {
             None
         } else {
             Some(tools.as_slice())
         };
 
+        // 首次请求前检查上下文大小，新消息也计入
+        if let (Some(sid), Some(compactor)) = (&session_id, tool_loop_engine.compactor()) {
+            let pending = compaction::estimate_tokens(&request.message);
+            let usage = self
+                .compact_if_needed(sid, compactor, request.model.as_deref(), pending, &tx)
+                .await;
+            state.add_usage(usage.as_ref());
+        }
+
         // 首次请求
         let mut current_result = self
             .chat_stream(request.clone(), tools_ref, tx.clone())
             .await?;
+        state.add_usage(current_result.usage.as_ref());
 
         // 工具调用循环
         // Requirements: 7.3 - THE Tool_Loop SHALL continue until the Agent produces a final response without tool_calls
@@ -380,6 +389,17 @@ impl NativeAgent {
                     self.add_tool_result_to_session(sid, result);
                 }
             }
+            for result in &tool_results {
+                state.add_usage(result.usage.as_ref());
+            }
+
+            // 工具输出可能很大，继续对话前再次检查上下文大小
+            if let (Some(sid), Some(compactor)) = (&session_id, tool_loop_engine.compactor()) {
+                let usage = self
+                    .compact_if_needed(sid, compactor, request.model.as_deref(), 0, &tx)
+                    .await;
+                state.add_usage(usage.as_ref());
+            }
 
             // 继续对话
             let continue_request = NativeChatRequest {
@@ -393,6 +413,7 @@ impl NativeAgent {
             current_result = self
                 .chat_stream_continue(continue_request, tools_ref, tx.clone())
                 .await?;
+            state.add_usage(current_result.usage.as_ref());
         }
 
         // 检查是否因为达到最大迭代次数而停止
@@ -414,10 +435,17 @@ impl NativeAgent {
         state.mark_completed(current_result.content.clone());
 
         info!(
-            "[NativeAgent] 工具循环完成: {} 次迭代, {} 个工具调用",
-            state.iteration, state.total_tool_calls
+            "[NativeAgent] 工具循环完成: {} 次迭代, {} 个工具调用, {} tokens",
+            state.iteration,
+            state.total_tool_calls,
+            state.usage.total()
         );
 
+        // 整个循环（包括子 Agent）的累计用量
+        if state.usage != TokenUsage::default() {
+            current_result.usage = Some(state.usage.clone());
+        }
+
         // 发送 FinalDone 事件，通知前端整个对话（包括工具循环）已完成
         let _ = tx
             .send(StreamEvent::FinalDone {
@@ -428,6 +456,134 @@ impl NativeAgent {
         Ok(current_result)
     }
 
+    /// 会话接近上下文上限时压缩历史消息
+    ///
+    /// 压缩后替换会话消息并发送 `ContextCompacted` 事件，返回生成摘要消耗的 token。
+    /// 摘要生成失败时只截断工具输出。
+    async fn compact_if_needed(
+        &self,
+        session_id: &str,
+        compactor: &ContextCompactor,
+        model: Option<&str>,
+        pending_tokens: u32,
+        tx: &mpsc::Sender<StreamEvent>,
+    ) -> Option<TokenUsage> {
+        let messages = self.sessions.read().get(session_id)?.messages.clone();
+        let plan = compactor.plan(&messages, pending_tokens)?;
+
+        let mut usage = None;
+        let summary = if plan.needs_summary() {
+            let model = compactor
+                .summary_model()
+                .or(model)
+                .unwrap_or(&self.config.model)
+                .to_string();
+            match self.summarize(&model, plan.summary_input()).await {
+                Ok((summary, summary_usage)) => {
+                    usage = summary_usage;
+                    Some(summary)
+                }
+                Err(e) => {
+                    warn!("[NativeAgent] 生成上下文摘要失败，仅截断工具输出: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let outcome = compactor.apply(plan, summary);
+        if outcome.summary.is_none() && outcome.truncated_outputs.is_empty() {
+            return usage;
+        }
+        info!(
+            "[NativeAgent] 上下文已压缩: session={}, tokens {} -> {}, 摘要 {} 条消息, 截断 {} 个工具输出",
+            session_id,
+            outcome.tokens_before,
+            outcome.tokens_after,
+            outcome.summarized_messages,
+            outcome.truncated_outputs.len()
+        );
+
+        if let Some(session) = self.sessions.write().get_mut(session_id) {
+            session.messages = outcome.messages;
+            session.updated_at = chrono::Utc::now().to_rfc3339();
+        }
+
+        let _ = tx
+            .send(StreamEvent::ContextCompacted {
+                summary: outcome.summary,
+                summarized_messages: outcome.summarized_messages,
+                kept_messages: outcome.kept_messages,
+                tokens_before: outcome.tokens_before,
+                tokens_after: outcome.tokens_after,
+                truncated_outputs: outcome.truncated_outputs,
+            })
+            .await;
+
+        usage
+    }
+
+    /// 生成对话摘要（非流式）
+    async fn summarize(
+        &self,
+        model: &str,
+        transcript: String,
+    ) -> Result<(String, Option<TokenUsage>), String> {
+        let text_message = |role: &str, text: String| ChatMessage {
+            role: role.to_string(),
+            content: Some(OpenAIMessageContent::Text(text)),
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_content: None,
+        };
+        let chat_request = ChatCompletionRequest {
+            model: model.to_string(),
+            messages: vec![
+                text_message("system", SUMMARY_SYSTEM_PROMPT.to_string()),
+                text_message("user", transcript),
+            ],
+            stream: false,
+            temperature: Some(0.2),
+            ..Default::default()
+        };
+
+        let response = self
+            .client
+            .post(self.chat_completions_url())
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Content-Type", "application/json")
+            .json(&chat_request)
+            .send()
+            .await
+            .map_err(|e| format!("请求失败: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(format!("API 错误 ({}): {}", status, body));
+        }
+
+        let body: ChatCompletionResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("解析响应失败: {}", e))?;
+        let summary = body
+            .choices
+            .first()
+            .and_then(|c| c.message.content.clone())
+            .filter(|c| !c.trim().is_empty())
+            .ok_or("摘要为空")?;
+
+        Ok((
+            summary,
+            Some(TokenUsage::new(
+                body.usage.prompt_tokens,
+                body.usage.completion_tokens,
+            )),
+        ))
+    }
+
     /// 继续流式对话（使用会话历史）
     async fn chat_stream_continue(
         &self,
@@ -635,6 +791,7 @@ impl NativeAgent {
                 tool_calls: None,
                 tool_call_id: None,
                 reasoning_content: None,
+                pinned: false,
             });
             session.updated_at = chrono::Utc::now().to_rfc3339();
         }
@@ -657,6 +814,7 @@ impl NativeAgent {
                 tool_calls,
                 tool_call_id: None,
                 reasoning_content,
+                pinned: false,
             });
             session.updated_at = chrono::Utc::now().to_rfc3339();
         }
@@ -720,6 +878,16 @@ impl NativeAgent {
             .get(session_id)
             .map(|s| s.messages.clone())
     }
+
+    /// 用持久化的会话（切换分支、回退后）替换内存中的会话
+    pub fn restore_session(&self, session: AgentSession) {
+        info!(
+            "[NativeAgent] 恢复会话: {} ({} 条消息)",
+            session.id,
+            session.messages.len()
+        );
+        self.sessions.write().insert(session.id.clone(), session);
+    }
 }
 
 // ==================== Tauri 状态管理 ====================
@@ -728,15 +896,29 @@ impl NativeAgent {
 #[derive(Clone, Default)]
 pub struct NativeAgentState {
     agent: Arc<RwLock<Option<NativeAgent>>>,
+    /// 进行中的对话共享的取消令牌，停止 Agent 时取消并替换
+    stop_token: Arc<RwLock<CancellationToken>>,
 }
 
 impl NativeAgentState {
     pub fn new() -> Self {
         Self {
             agent: Arc::new(RwLock::new(None)),
+            stop_token: Arc::new(RwLock::new(CancellationToken::new())),
         }
     }
 
+    /// 为新的对话创建取消令牌，调用 [`Self::cancel_running`] 时一并取消
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.stop_token.read().child_token()
+    }
+
+    /// 取消所有进行中的对话（中止正在执行的工具调用并结束工具循环）
+    pub fn cancel_running(&self) {
+        let token = std::mem::take(&mut *self.stop_token.write());
+        token.cancel();
+    }
+
     pub fn init(
         &self,
         base_url: String,
@@ -992,6 +1174,16 @@ impl NativeAgentState {
             .as_ref()
             .and_then(|a| a.get_session_messages(session_id))
     }
+
+    pub fn restore_session(&self, session: AgentSession) -> bool {
+        let guard = self.agent.read();
+        if let Some(agent) = guard.as_ref() {
+            agent.restore_session(session);
+            true
+        } else {
+            false
+        }
+    }
 }
 
 #[cfg(test)]
@@ -344,6 +344,7 @@ impl AnthropicProtocol {
                     tool_calls: None,
                     tool_call_id: Some(tool_call_id.clone()),
                     reasoning_content: None,
+                    pinned: false,
                 };
                 validated_messages.push(default_result);
             }
@@ -446,6 +446,7 @@ impl Protocol for OpenAIProtocol {
                 None
             },
             reasoning_effort: None,
+            ..Default::default()
         };
 
         let url = format!("{}{}", base_url, self.endpoint());
@@ -522,6 +523,7 @@ impl Protocol for OpenAIProtocol {
                 None
             },
             reasoning_effort: None,
+            ..Default::default()
         };
 
         let url = format!("{}{}", base_url, self.endpoint());
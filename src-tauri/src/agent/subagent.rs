@@ -0,0 +1,435 @@
+//! 子 Agent 编排
+//!
+//! 提供 `task` 工具：父 Agent 可以启动拥有独立系统提示词、模型和工具子集的子 Agent，
+//! 子 Agent 的进度以嵌套事件的形式转发给前端，完成后将总结结果返回给父 Agent。
+//!
+//! ## 设计
+//! - 模型通过 `ModelOrchestrator` 按服务等级选择，也可以由调用方直接指定
+//! - 子 Agent 使用临时会话，完成后删除
+//! - 嵌套深度受 `SubAgentConfig::max_depth` 限制
+//! - 子 Agent 的 token 用量计入父 Agent 的工具结果，由工具循环汇总
+
+use crate::agent::event_converter::convert_stream_event;
+use crate::agent::native_agent::NativeAgentState;
+use crate::agent::tool_loop::{ToolLoopConfig, ToolLoopEngine};
+use crate::agent::tools::security::SecurityManager;
+use crate::agent::tools::ToolRegistry;
+use crate::agent::types::{NativeChatRequest, StreamEvent, TokenUsage};
+use crate::config::SubAgentConfig;
+use crate::orchestrator::{ModelOrchestrator, SelectionContext, ServiceTier, TaskHint};
+use crate::services::tool_hooks_service::ToolHooksService;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+/// task 工具名称
+pub const TASK_TOOL_NAME: &str = "task";
+
+/// 子 Agent 默认系统提示词
+const DEFAULT_SUB_AGENT_PROMPT: &str =
+    "You are a sub-agent working on a focused task delegated by another agent. \
+     Use the available tools as needed and work autonomously.";
+
+/// 附加在子 Agent 系统提示词后的总结要求
+const SUMMARY_INSTRUCTION: &str =
+    "When you are done, reply with a concise summary of your findings or changes. \
+     The summary is the only thing the delegating agent will see.";
+
+/// task 工具参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubAgentTask {
+    /// 任务简述（用于前端显示）
+    pub description: String,
+    /// 交给子 Agent 的完整任务说明
+    pub prompt: String,
+    /// 自定义系统提示词
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// 服务等级（未指定时使用配置中的默认等级）
+    #[serde(default)]
+    pub tier: Option<ServiceTier>,
+    /// 任务类型，用于模型选择
+    #[serde(default)]
+    pub task_type: Option<TaskHint>,
+    /// 直接指定模型，优先于服务等级
+    #[serde(default)]
+    pub model: Option<String>,
+    /// 允许子 Agent 使用的工具（未指定时继承父 Agent 的全部工具）
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
+}
+
+/// 子 Agent 执行结果
+#[derive(Debug, Clone)]
+pub struct SubAgentOutcome {
+    /// 子 Agent 会话 ID
+    pub agent_id: String,
+    /// 使用的模型（None 表示 Agent 默认模型）
+    pub model: Option<String>,
+    /// 总结内容（已截断）
+    pub content: String,
+    /// 子 Agent 及其后代的 token 用量
+    pub usage: TokenUsage,
+}
+
+/// 子 Agent 启动器
+///
+/// 由父 Agent 的工具循环持有，每一层嵌套对应一个启动器
+pub struct SubAgentRunner {
+    /// Agent 状态（共享会话存储）
+    agent_state: NativeAgentState,
+    /// 父 Agent 的工具注册表
+    registry: Arc<ToolRegistry>,
+    /// 模型编排器
+    orchestrator: Option<Arc<ModelOrchestrator>>,
+    /// 工具钩子，子 Agent 同样受钩子约束
+    hooks: Option<Arc<ToolHooksService>>,
+    /// 命令审批的工作目录校验，子 Agent 同样受审批策略约束
+    command_security: Option<SecurityManager>,
+    /// 无法选择模型时使用的模型
+    fallback_model: Option<String>,
+    /// 配置
+    config: SubAgentConfig,
+    /// 当前嵌套深度（父 Agent 为 0）
+    depth: usize,
+}
+
+impl SubAgentRunner {
+    /// 创建启动器
+    pub fn new(
+        agent_state: NativeAgentState,
+        registry: Arc<ToolRegistry>,
+        config: SubAgentConfig,
+    ) -> Self {
+        Self {
+            agent_state,
+            registry,
+            orchestrator: None,
+            hooks: None,
+            command_security: None,
+            fallback_model: None,
+            config,
+            depth: 0,
+        }
+    }
+
+    /// 设置模型编排器
+    pub fn with_orchestrator(mut self, orchestrator: Arc<ModelOrchestrator>) -> Self {
+        self.orchestrator = Some(orchestrator);
+        self
+    }
+
+    /// 设置工具钩子
+    pub fn with_hooks(mut self, hooks: Arc<ToolHooksService>) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    /// 终端类工具按命令审批策略执行
+    ///
+    /// 子 Agent 无法向用户请求审批，需要审批的命令直接拒绝。
+    pub fn with_command_policy(mut self, security: SecurityManager) -> Self {
+        self.command_security = Some(security);
+        self
+    }
+
+    /// 设置回退模型
+    pub fn with_fallback_model(mut self, model: Option<String>) -> Self {
+        self.fallback_model = model;
+        self
+    }
+
+    /// task 工具定义（OpenAI 格式）
+    pub fn tool_definition() -> crate::models::openai::Tool {
+        crate::models::openai::Tool::Function {
+            function: crate::models::openai::FunctionDef {
+                name: TASK_TOOL_NAME.to_string(),
+                description: Some(
+                    "Launch a sub-agent to handle an independent task. Multiple task calls in \
+                     the same turn run concurrently. The sub-agent only sees the prompt you \
+                     give it and returns a summary of its work."
+                        .to_string(),
+                ),
+                parameters: Some(serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "description": {
+                            "type": "string",
+                            "description": "A short (3-5 words) description of the task"
+                        },
+                        "prompt": {
+                            "type": "string",
+                            "description": "Complete, self-contained instructions for the sub-agent"
+                        },
+                        "system_prompt": {
+                            "type": "string",
+                            "description": "Optional system prompt for the sub-agent"
+                        },
+                        "tier": {
+                            "type": "string",
+                            "enum": ["mini", "pro", "max"],
+                            "description": "Service tier used to pick the sub-agent model"
+                        },
+                        "task_type": {
+                            "type": "string",
+                            "enum": [
+                                "coding", "writing", "analysis", "chat",
+                                "translation", "summarization", "math", "other"
+                            ],
+                            "description": "Kind of work, used to pick the sub-agent model"
+                        },
+                        "model": {
+                            "type": "string",
+                            "description": "Explicit model id, overrides tier selection"
+                        },
+                        "tools": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Tool names the sub-agent may use (default: all)"
+                        }
+                    },
+                    "required": ["description", "prompt"]
+                })),
+            },
+        }
+    }
+
+    /// 子 Agent 使用的启动器（达到最大深度时为 None）
+    fn child_runner(&self) -> Option<Self> {
+        if self.depth + 1 >= self.config.max_depth {
+            return None;
+        }
+        Some(Self {
+            agent_state: self.agent_state.clone(),
+            registry: self.registry.clone(),
+            orchestrator: self.orchestrator.clone(),
+            hooks: self.hooks.clone(),
+            command_security: self.command_security.clone(),
+            fallback_model: self.fallback_model.clone(),
+            config: self.config.clone(),
+            depth: self.depth + 1,
+        })
+    }
+
+    /// 选择子 Agent 模型
+    async fn select_model(&self, task: &SubAgentTask) -> Option<String> {
+        if let Some(model) = &task.model {
+            return Some(model.clone());
+        }
+
+        if let Some(orchestrator) = &self.orchestrator {
+            let tier = task.tier.unwrap_or(self.config.default_tier);
+            let mut ctx = SelectionContext::new(tier).with_tools(true);
+            if let Some(hint) = task.task_type {
+                ctx = ctx.with_task_hint(hint);
+            }
+            match orchestrator.select(&ctx).await {
+                Ok(selection) => return Some(selection.model.id),
+                Err(e) => warn!("[SubAgent] 模型选择失败，使用回退模型: {}", e),
+            }
+        }
+
+        self.fallback_model.clone()
+    }
+
+    /// 运行子 Agent
+    ///
+    /// 子 Agent 的事件包装为 `StreamEvent::SubAgent` 发送到 `event_tx`。
+    /// 返回装箱的 Future 以支持子 Agent 再次启动子 Agent。
+    pub fn run<'a>(
+        &'a self,
+        task: SubAgentTask,
+        parent_tool_id: &'a str,
+        cancel_token: CancellationToken,
+        event_tx: Option<mpsc::Sender<StreamEvent>>,
+    ) -> BoxFuture<'a, Result<SubAgentOutcome, String>> {
+        Box::pin(async move {
+            let model = self.select_model(&task).await;
+            let system_prompt = format!(
+                "{}\n\n{}",
+                task.system_prompt
+                    .as_deref()
+                    .unwrap_or(DEFAULT_SUB_AGENT_PROMPT),
+                SUMMARY_INSTRUCTION
+            );
+            let agent_id = self
+                .agent_state
+                .create_session(model.clone(), Some(system_prompt))?;
+            // 取消或提前返回时 Future 被丢弃，由守卫负责清理子会话
+            let _session = SessionGuard {
+                agent_state: &self.agent_state,
+                session_id: &agent_id,
+            };
+
+            info!(
+                "[SubAgent] 启动子 Agent: id={}, depth={}, model={:?}, task={}",
+                agent_id,
+                self.depth + 1,
+                model,
+                task.description
+            );
+
+            let mut config = ToolLoopConfig::new(self.config.max_iterations);
+            if let Some(tools) = &task.tools {
+                config = config.with_allowed_tools(tools.iter());
+            }
+            let mut engine = ToolLoopEngine::with_config(self.registry.clone(), config)
+                .with_cancel_token(cancel_token);
+            if let Some(hooks) = &self.hooks {
+                engine = engine.with_hooks(hooks.clone(), agent_id.clone());
+            }
+            if let Some(security) = &self.command_security {
+                engine = engine
+                    .with_command_policy(security.clone())
+                    .with_command_approval(false);
+            }
+            if let Some(child) = self.child_runner() {
+                engine = engine.with_sub_agents(Arc::new(child));
+            }
+
+            let request = NativeChatRequest {
+                session_id: Some(agent_id.clone()),
+                message: task.prompt.clone(),
+                model: model.clone(),
+                images: None,
+                stream: true,
+            };
+
+            let (tx, mut rx) = mpsc::channel::<StreamEvent>(100);
+            let forward = async {
+                while let Some(event) = rx.recv().await {
+                    let Some(parent_tx) = &event_tx else {
+                        continue;
+                    };
+                    let _ = parent_tx
+                        .send(StreamEvent::SubAgent {
+                            parent_tool_id: parent_tool_id.to_string(),
+                            agent_id: agent_id.clone(),
+                            description: task.description.clone(),
+                            event: Box::new(convert_stream_event(event)),
+                        })
+                        .await;
+                }
+            };
+            // 子 Agent 结束时发送端随之释放，转发循环随即结束
+            let run = self
+                .agent_state
+                .chat_stream_with_tools(request, tx, &engine);
+            let (result, _) = tokio::join!(run, forward);
+
+            let result = result?;
+            let usage = result.usage.unwrap_or_default();
+            debug!(
+                "[SubAgent] 子 Agent 完成: id={}, tokens={}",
+                agent_id,
+                usage.total()
+            );
+
+            Ok(SubAgentOutcome {
+                agent_id: agent_id.clone(),
+                model,
+                content: truncate_result(&result.content, self.config.max_result_chars),
+                usage,
+            })
+        })
+    }
+}
+
+/// 子 Agent 会话守卫，离开作用域时删除会话
+struct SessionGuard<'a> {
+    agent_state: &'a NativeAgentState,
+    session_id: &'a str,
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        self.agent_state.delete_session(self.session_id);
+    }
+}
+
+/// 截断子 Agent 结果，避免占用父 Agent 过多上下文
+fn truncate_result(content: &str, max_chars: usize) -> String {
+    let content = content.trim();
+    match content.char_indices().nth(max_chars) {
+        Some((end, _)) => format!(
+            "{}\n\n[结果已截断，共 {} 字符]",
+            &content[..end],
+            content.chars().count()
+        ),
+        None => content.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::types::ProviderType;
+
+    #[test]
+    fn test_task_arguments_parse() {
+        let task: SubAgentTask = serde_json::from_value(serde_json::json!({
+            "description": "查找配置",
+            "prompt": "找出所有读取 config.yaml 的位置",
+            "tier": "mini",
+            "task_type": "coding",
+            "tools": ["read_file", "grep"]
+        }))
+        .unwrap();
+
+        assert_eq!(task.tier, Some(ServiceTier::Mini));
+        assert_eq!(task.task_type, Some(TaskHint::Coding));
+        assert_eq!(task.tools.unwrap(), vec!["read_file", "grep"]);
+        assert!(task.model.is_none());
+    }
+
+    #[test]
+    fn test_truncate_result() {
+        assert_eq!(truncate_result("  done  ", 10), "done");
+        let truncated = truncate_result("一二三四五", 3);
+        assert!(truncated.starts_with("一二三\n\n"));
+        assert!(truncated.contains("共 5 字符"));
+    }
+
+    #[test]
+    fn test_child_runner_respects_max_depth() {
+        let config = SubAgentConfig {
+            max_depth: 2,
+            ..Default::default()
+        };
+        let runner = SubAgentRunner::new(
+            NativeAgentState::new(),
+            Arc::new(ToolRegistry::new()),
+            config,
+        );
+
+        let child = runner.child_runner().expect("深度 1 应允许子 Agent");
+        assert_eq!(child.depth, 1);
+        assert!(child.child_runner().is_none());
+    }
+
+    #[test]
+    fn test_session_guard_deletes_session() {
+        let state = NativeAgentState::new();
+        state
+            .init(
+                "http://127.0.0.1:1".to_string(),
+                "test-key".to_string(),
+                ProviderType::OpenAI,
+                None,
+            )
+            .unwrap();
+        let session_id = state.create_session(None, None).unwrap();
+
+        {
+            let _guard = SessionGuard {
+                agent_state: &state,
+                session_id: &session_id,
+            };
+            assert_eq!(state.list_sessions().len(), 1);
+        }
+        assert!(state.list_sessions().is_empty());
+    }
+}
@@ -1,26 +1,31 @@
 //! Browser 工具模块
 //!
-//! 提供浏览器自动化功能，基于 Playwright
+//! 提供浏览器自动化功能，基于 Chrome DevTools Protocol（见 `browser_cdp`）
 //! 专为 AI Agent 设计，提供结构化的页面快照
+//!
+//! 每个 Agent 会话使用独立的浏览器上下文（Cookie 隔离），
+//! 下载文件和截图保存到会话目录。
 
 #![allow(dead_code)]
 
+use super::browser_cdp::{shared_browser, BrowserError, BrowserSession, DownloadState};
 use super::registry::Tool;
 use super::types::{JsonSchema, PropertySchema, ToolDefinition, ToolError, ToolResult};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::process::Stdio;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::process::Command;
 use tokio::time::timeout;
 use tracing::info;
 
+pub use super::browser_cdp::ScrollDirection;
+
 /// 默认超时时间（秒）
 const DEFAULT_TIMEOUT_SECS: u64 = 30;
 
-/// Playwright 脚本目录
-const PLAYWRIGHT_SCRIPTS_DIR: &str = "scripts/playwright";
+/// 未指定会话时使用的会话 ID
+const DEFAULT_SESSION_ID: &str = "default";
 
 /// 浏览器操作类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,6 +68,16 @@ pub enum BrowserAction {
     GetText { selector: Option<String> },
     /// 执行 JavaScript
     Evaluate { script: String },
+    /// 打开新标签页
+    NewTab { url: Option<String> },
+    /// 列出标签页
+    ListTabs,
+    /// 切换标签页
+    SwitchTab { tab_id: String },
+    /// 关闭标签页（未指定时关闭当前标签页）
+    CloseTab { tab_id: Option<String> },
+    /// 列出下载记录
+    Downloads,
     /// 关闭浏览器
     Close,
 }
@@ -75,15 +90,6 @@ fn default_wait_timeout() -> u64 {
     5000
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum ScrollDirection {
-    Up,
-    Down,
-    Left,
-    Right,
-}
-
 /// 浏览器操作结果
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BrowserResult {
@@ -95,43 +101,66 @@ pub struct BrowserResult {
     pub url: Option<String>,
     /// 页面标题
     pub title: Option<String>,
-    /// 截图 base64（如果有）
-    pub screenshot: Option<String>,
     /// 错误信息
     pub error: Option<String>,
 }
 
+impl BrowserResult {
+    fn ok(output: impl Into<String>) -> Self {
+        Self {
+            success: true,
+            output: output.into(),
+            url: None,
+            title: None,
+            error: None,
+        }
+    }
+
+    fn failed(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            output: String::new(),
+            url: None,
+            title: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
 /// Browser 工具
 ///
 /// 提供浏览器自动化功能，专为 AI Agent 设计
 pub struct BrowserTool {
-    /// Playwright 脚本路径
-    script_path: PathBuf,
+    /// Agent 会话 ID（决定浏览器上下文）
+    session_id: String,
+    /// 会话目录（下载文件和截图保存位置）
+    session_dir: PathBuf,
     /// 超时时间（秒）
     timeout_secs: u64,
-    /// 是否使用 headless 模式
-    headless: bool,
 }
 
 impl BrowserTool {
     /// 创建新的 Browser 工具
     pub fn new() -> Self {
-        // 获取脚本路径（相对于项目根目录）
-        let script_path = std::env::current_dir()
-            .unwrap_or_default()
-            .join(PLAYWRIGHT_SCRIPTS_DIR)
-            .join("browser-tool.mjs");
-
         Self {
-            script_path,
+            session_id: DEFAULT_SESSION_ID.to_string(),
+            session_dir: default_session_dir(DEFAULT_SESSION_ID),
             timeout_secs: DEFAULT_TIMEOUT_SECS,
-            headless: true,
         }
     }
 
-    /// 设置脚本路径
-    pub fn with_script_path(mut self, path: PathBuf) -> Self {
-        self.script_path = path;
+    /// 设置 Agent 会话
+    ///
+    /// # 参数
+    /// - `session_id`: Agent 会话 ID，不同会话的 Cookie 和标签页互相隔离
+    /// - `session_dir`: 会话目录，未指定时使用 `~/.proxycast/sessions/{session_id}`
+    pub fn with_session(
+        mut self,
+        session_id: impl Into<String>,
+        session_dir: Option<PathBuf>,
+    ) -> Self {
+        self.session_id = session_id.into();
+        self.session_dir = session_dir.unwrap_or_else(|| default_session_dir(&self.session_id));
         self
     }
 
@@ -141,63 +170,180 @@ impl BrowserTool {
         self
     }
 
-    /// 设置是否 headless
-    pub fn with_headless(mut self, headless: bool) -> Self {
-        self.headless = headless;
-        self
+    /// 获取当前会话对应的浏览器会话
+    async fn browser_session(&self) -> Result<Arc<BrowserSession>, BrowserError> {
+        shared_browser()
+            .session(&self.session_id, &self.session_dir.join("downloads"))
+            .await
     }
 
     /// 执行浏览器操作
     async fn execute_action(&self, action: &BrowserAction) -> Result<BrowserResult, ToolError> {
-        let action_json = serde_json::to_string(action)
-            .map_err(|e| ToolError::ExecutionFailed(format!("序列化操作失败: {}", e)))?;
-
         info!("[BrowserTool] 执行操作: {:?}", action);
 
-        // 构建命令
-        let mut cmd = Command::new("node");
-        cmd.arg(&self.script_path);
-        cmd.arg("--action");
-        cmd.arg(&action_json);
-
-        if self.headless {
-            cmd.arg("--headless");
+        let timeout_duration = Duration::from_secs(self.timeout_secs);
+        match timeout(timeout_duration, self.run_action(action)).await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(e)) => match e {
+                BrowserError::Timeout(_) => Err(ToolError::Timeout),
+                BrowserError::InvalidArgument(msg) => Err(ToolError::InvalidArguments(msg)),
+                BrowserError::BrowserNotFound
+                | BrowserError::LaunchFailed(_)
+                | BrowserError::Connection(_)
+                | BrowserError::Io(_) => Err(ToolError::ExecutionFailed(e.to_string())),
+                // 页面相关错误交给 Agent 处理（如重新快照后重试）
+                _ => Ok(BrowserResult::failed(e.to_string())),
+            },
+            Err(_) => Err(ToolError::Timeout),
         }
+    }
 
-        cmd.stdin(Stdio::null());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+    /// 在浏览器会话中执行操作
+    async fn run_action(&self, action: &BrowserAction) -> Result<BrowserResult, BrowserError> {
+        if let BrowserAction::Close = action {
+            shared_browser().close_session(&self.session_id).await?;
+            return Ok(BrowserResult::ok("浏览器会话已关闭"));
+        }
 
-        // 执行命令
-        let timeout_duration = Duration::from_secs(self.timeout_secs);
-        let result = timeout(timeout_duration, cmd.output()).await;
-
-        match result {
-            Ok(Ok(output)) => {
-                let stdout = String::from_utf8_lossy(&output.stdout);
-                let stderr = String::from_utf8_lossy(&output.stderr);
-
-                if output.status.success() {
-                    // 解析 JSON 输出
-                    serde_json::from_str(&stdout).map_err(|e| {
-                        ToolError::ExecutionFailed(format!(
-                            "解析输出失败: {}\nstdout: {}\nstderr: {}",
-                            e, stdout, stderr
-                        ))
+        let session = self.browser_session().await?;
+        let mut result = match action {
+            BrowserAction::Open { url } => {
+                session.navigate(url).await?;
+                BrowserResult::ok("页面已打开")
+            }
+            BrowserAction::Snapshot { interactive_only } => {
+                BrowserResult::ok(session.snapshot(*interactive_only).await?)
+            }
+            BrowserAction::Click { selector } => {
+                session.click(selector).await?;
+                BrowserResult::ok(format!("已点击 {}", selector))
+            }
+            BrowserAction::Fill { selector, value } => {
+                session.fill(selector, value).await?;
+                BrowserResult::ok(format!("已填充 {}", selector))
+            }
+            BrowserAction::Type { selector, text } => {
+                session.type_text(selector, text).await?;
+                BrowserResult::ok(format!("已在 {} 中输入文本", selector))
+            }
+            BrowserAction::Press { key } => {
+                session.press(key).await?;
+                BrowserResult::ok(format!("已按下 {}", key))
+            }
+            BrowserAction::Scroll { direction, amount } => {
+                session.scroll(*direction, *amount).await?;
+                BrowserResult::ok(format!("已滚动 {:?} {}px", direction, amount))
+            }
+            BrowserAction::WaitFor {
+                selector,
+                timeout_ms,
+            } => {
+                session.wait_for(selector, *timeout_ms).await?;
+                BrowserResult::ok(format!("元素 {} 已出现", selector))
+            }
+            BrowserAction::Screenshot { full_page, path } => {
+                let data = session.screenshot(*full_page).await?;
+                // 相对路径基于会话目录，未指定时按时间命名
+                let path = self.session_dir.join(path.clone().unwrap_or_else(|| {
+                    format!(
+                        "screenshots/{}.png",
+                        chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+                    )
+                }));
+                if let Some(parent) = path.parent() {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                tokio::fs::write(&path, &data).await?;
+                BrowserResult::ok(format!("截图已保存到 {}", path.display()))
+            }
+            BrowserAction::GetText { selector } => {
+                BrowserResult::ok(session.get_text(selector.as_deref()).await?)
+            }
+            BrowserAction::Evaluate { script } => {
+                let value = session.evaluate(script).await?;
+                let output = match value {
+                    serde_json::Value::String(s) => s,
+                    other => serde_json::to_string_pretty(&other).unwrap_or_default(),
+                };
+                BrowserResult::ok(output)
+            }
+            BrowserAction::NewTab { url } => {
+                let tab = session.new_tab(url.as_deref()).await?;
+                BrowserResult::ok(format!("已打开新标签页 {}", tab.id))
+            }
+            BrowserAction::ListTabs => {
+                let tabs = session.list_tabs().await?;
+                let output = tabs
+                    .iter()
+                    .map(|tab| {
+                        format!(
+                            "{} {} \"{}\" {}",
+                            if tab.active { "*" } else { "-" },
+                            tab.id,
+                            tab.title,
+                            tab.url
+                        )
                     })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                BrowserResult::ok(output)
+            }
+            BrowserAction::SwitchTab { tab_id } => {
+                let tab = session.switch_tab(tab_id).await?;
+                BrowserResult::ok(format!("已切换到标签页 {}", tab.id))
+            }
+            BrowserAction::CloseTab { tab_id } => match session.close_tab(tab_id.as_deref()).await?
+            {
+                Some(active) => BrowserResult::ok(format!("标签页已关闭，当前标签页 {}", active)),
+                None => BrowserResult::ok("标签页已关闭，没有剩余标签页"),
+            },
+            BrowserAction::Downloads => {
+                let downloads = session.downloads();
+                if downloads.is_empty() {
+                    BrowserResult::ok("没有下载记录")
                 } else {
-                    Err(ToolError::ExecutionFailed(format!(
-                        "浏览器操作失败: {}",
-                        stderr
-                    )))
+                    let output = downloads
+                        .iter()
+                        .map(|d| match d.state {
+                            DownloadState::Completed => format!(
+                                "- {} [completed] {}",
+                                d.filename,
+                                d.path.as_deref().unwrap_or(Path::new("")).display()
+                            ),
+                            DownloadState::InProgress => format!(
+                                "- {} [in_progress] {}/{} 字节",
+                                d.filename, d.received_bytes, d.total_bytes
+                            ),
+                            DownloadState::Canceled => {
+                                format!("- {} [canceled] {}", d.filename, d.url)
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    BrowserResult::ok(output)
                 }
             }
-            Ok(Err(e)) => Err(ToolError::ExecutionFailed(format!("执行命令失败: {}", e))),
-            Err(_) => Err(ToolError::Timeout),
+            BrowserAction::Close => unreachable!(),
+        };
+
+        // 附带当前页面信息（没有标签页时忽略）
+        if let Ok((url, title)) = session.page_info().await {
+            result.url = Some(url);
+            result.title = Some(title);
         }
+        Ok(result)
     }
 }
 
+/// 默认会话目录：~/.proxycast/sessions/{session_id}
+fn default_session_dir(session_id: &str) -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(".proxycast")
+        .join("sessions")
+        .join(session_id)
+}
+
 impl Default for BrowserTool {
     fn default() -> Self {
         Self::new()
@@ -212,7 +358,8 @@ impl Tool for BrowserTool {
             "Control a web browser for automation tasks. Use this to navigate websites, \
              interact with elements, fill forms, and extract information. The 'snapshot' \
              action returns an accessibility tree with element references (like @e1, @e2) \
-             that can be used in subsequent actions.",
+             that can be used in subsequent actions. References stay valid until the page \
+             navigates. Downloads are saved to the session's downloads directory.",
         )
         .with_parameters(
             JsonSchema::new()
@@ -220,13 +367,14 @@ impl Tool for BrowserTool {
                     "action",
                     PropertySchema::string(
                         "The browser action to perform. One of: open, snapshot, click, fill, \
-                         type, press, scroll, wait_for, screenshot, get_text, evaluate, close",
+                         type, press, scroll, wait_for, screenshot, get_text, evaluate, \
+                         new_tab, list_tabs, switch_tab, close_tab, downloads, close",
                     ),
                     true,
                 )
                 .add_property(
                     "url",
-                    PropertySchema::string("URL to open (for 'open' action)"),
+                    PropertySchema::string("URL to open (for 'open' and 'new_tab' actions)"),
                     false,
                 )
                 .add_property(
@@ -250,7 +398,7 @@ impl Tool for BrowserTool {
                 .add_property(
                     "key",
                     PropertySchema::string(
-                        "Key to press (for 'press' action), e.g., 'Enter', 'Tab', 'Escape'",
+                        "Key to press (for 'press' action), e.g., 'Enter', 'Tab', 'Control+a'",
                     ),
                     false,
                 )
@@ -259,6 +407,33 @@ impl Tool for BrowserTool {
                     PropertySchema::string("Scroll direction: up, down, left, right"),
                     false,
                 )
+                .add_property(
+                    "amount",
+                    PropertySchema::integer("Scroll distance in pixels (default: 500)"),
+                    false,
+                )
+                .add_property(
+                    "timeout_ms",
+                    PropertySchema::integer(
+                        "Timeout in milliseconds (for 'wait_for' action, default: 5000)",
+                    ),
+                    false,
+                )
+                .add_property(
+                    "tab_id",
+                    PropertySchema::string(
+                        "Tab ID from 'list_tabs' (for 'switch_tab' and 'close_tab' actions)",
+                    ),
+                    false,
+                )
+                .add_property(
+                    "path",
+                    PropertySchema::string(
+                        "Screenshot file path, relative to the session directory \
+                         (default: screenshots/<timestamp>.png)",
+                    ),
+                    false,
+                )
                 .add_property(
                     "script",
                     PropertySchema::string("JavaScript code to evaluate (for 'evaluate' action)"),
@@ -356,13 +531,10 @@ impl Tool for BrowserTool {
                     .get("direction")
                     .and_then(|v| v.as_str())
                     .unwrap_or("down");
-                let direction = match direction {
-                    "up" => ScrollDirection::Up,
-                    "down" => ScrollDirection::Down,
-                    "left" => ScrollDirection::Left,
-                    "right" => ScrollDirection::Right,
-                    _ => ScrollDirection::Down,
-                };
+                let direction =
+                    serde_json::from_value(serde_json::json!(direction)).map_err(|_| {
+                        ToolError::InvalidArguments(format!("无效的滚动方向: {}", direction))
+                    })?;
                 let amount = args
                     .get("amount")
                     .and_then(|v| v.as_i64())
@@ -409,6 +581,27 @@ impl Tool for BrowserTool {
                     script: script.to_string(),
                 }
             }
+            "new_tab" => {
+                let url = args.get("url").and_then(|v| v.as_str()).map(String::from);
+                BrowserAction::NewTab { url }
+            }
+            "list_tabs" => BrowserAction::ListTabs,
+            "switch_tab" => {
+                let tab_id = args.get("tab_id").and_then(|v| v.as_str()).ok_or_else(|| {
+                    ToolError::InvalidArguments("switch_tab 操作需要 tab_id 参数".to_string())
+                })?;
+                BrowserAction::SwitchTab {
+                    tab_id: tab_id.to_string(),
+                }
+            }
+            "close_tab" => {
+                let tab_id = args
+                    .get("tab_id")
+                    .and_then(|v| v.as_str())
+                    .map(String::from);
+                BrowserAction::CloseTab { tab_id }
+            }
+            "downloads" => BrowserAction::Downloads,
             "close" => BrowserAction::Close,
             _ => {
                 return Err(ToolError::InvalidArguments(format!(
@@ -456,6 +649,31 @@ mod tests {
         assert!(def.parameters.required.contains(&"action".to_string()));
     }
 
+    #[test]
+    fn test_with_session() {
+        let tool = BrowserTool::new().with_session("abc", None);
+        assert_eq!(tool.session_id, "abc");
+        assert!(tool.session_dir.ends_with(".proxycast/sessions/abc"));
+
+        let tool = BrowserTool::new().with_session("abc", Some(PathBuf::from("/tmp/s")));
+        assert_eq!(tool.session_dir, PathBuf::from("/tmp/s"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_arguments() {
+        let tool = BrowserTool::new();
+        for args in [
+            serde_json::json!({ "action": "unknown" }),
+            serde_json::json!({ "action": "switch_tab" }),
+            serde_json::json!({ "action": "scroll", "direction": "sideways" }),
+        ] {
+            assert!(matches!(
+                tool.execute(args).await,
+                Err(ToolError::InvalidArguments(_))
+            ));
+        }
+    }
+
     #[test]
     fn test_action_serialization() {
         let action = BrowserAction::Open {
@@ -464,5 +682,15 @@ mod tests {
         let json = serde_json::to_string(&action).unwrap();
         assert!(json.contains("open"));
         assert!(json.contains("https://example.com"));
+
+        let action: BrowserAction =
+            serde_json::from_str(r#"{"scroll":{"direction":"up"}}"#).unwrap();
+        assert!(matches!(
+            action,
+            BrowserAction::Scroll {
+                direction: ScrollDirection::Up,
+                amount: 500
+            }
+        ));
     }
 }
@@ -0,0 +1,2497 @@
+//! 基于 Chrome DevTools Protocol 的无头浏览器驱动
+//!
+//! 直接通过 CDP（WebSocket）驱动本地 Chrome/Chromium，不依赖 Node/Playwright。
+//! 由 `browser` 工具使用。
+//!
+//! ## 设计
+//! - 所有 Agent 会话共享一个浏览器进程，按需启动，最后一个会话关闭后退出
+//! - 每个 Agent 会话对应一个独立的 BrowserContext，Cookie 和存储互相隔离
+//! - 每个会话可以有多个标签页，操作作用于当前标签页
+//! - 快照基于可访问性树，元素引用（`e1`、`e2`…）在标签页生命周期内保持稳定
+//! - 下载文件保存到会话目录，按建议文件名命名
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures::{SinkExt, StreamExt};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Instant};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info, warn};
+
+/// 默认超时时间（秒）
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// 等待浏览器启动的超时时间（秒）
+const LAUNCH_TIMEOUT_SECS: u64 = 20;
+
+/// 交互操作后等待页面开始导航的时间（毫秒）
+const NAVIGATION_SETTLE_MS: u64 = 300;
+
+/// 轮询元素的间隔（毫秒）
+const POLL_INTERVAL_MS: u64 = 100;
+
+/// 快照中名称的最大字符数
+const MAX_NAME_CHARS: usize = 100;
+
+/// 事件通道容量
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// 可交互的可访问性角色
+const INTERACTIVE_ROLES: &[&str] = &[
+    "button",
+    "checkbox",
+    "combobox",
+    "link",
+    "listbox",
+    "menuitem",
+    "menuitemcheckbox",
+    "menuitemradio",
+    "option",
+    "radio",
+    "searchbox",
+    "slider",
+    "spinbutton",
+    "switch",
+    "tab",
+    "textbox",
+    "treeitem",
+];
+
+/// 快照中展开子节点、自身不输出的角色（无名称时）
+const TRANSPARENT_ROLES: &[&str] = &["generic", "none", "presentation", "group", "Section"];
+
+/// 快照中忽略的角色
+const SKIPPED_ROLES: &[&str] = &["InlineTextBox", "LineBreak"];
+
+/// 快照中输出的布尔属性
+const SNAPSHOT_STATES: &[&str] = &[
+    "checked", "disabled", "expanded", "pressed", "required", "selected",
+];
+
+/// 浏览器驱动错误
+#[derive(Debug, Error)]
+pub enum BrowserError {
+    #[error("未找到可用的浏览器，请安装 Google Chrome 或 Chromium")]
+    BrowserNotFound,
+
+    #[error("启动浏览器失败: {0}")]
+    LaunchFailed(String),
+
+    #[error("CDP 连接错误: {0}")]
+    Connection(String),
+
+    #[error("CDP 调用 {method} 失败: {message}")]
+    Protocol { method: String, message: String },
+
+    #[error("操作超时: {0}")]
+    Timeout(String),
+
+    #[error("未找到元素: {0}")]
+    ElementNotFound(String),
+
+    #[error("标签页不存在: {0}")]
+    TabNotFound(String),
+
+    #[error("页面导航失败: {0}")]
+    Navigation(String),
+
+    #[error("页面脚本错误: {0}")]
+    Script(String),
+
+    #[error("无效参数: {0}")]
+    InvalidArgument(String),
+
+    #[error("IO 错误: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// 浏览器配置
+#[derive(Debug, Clone)]
+pub struct BrowserConfig {
+    /// 浏览器可执行文件（未指定时自动查找系统 Chrome 或 Playwright Chromium）
+    pub executable: Option<PathBuf>,
+    /// 是否使用 headless 模式
+    pub headless: bool,
+    /// 单次 CDP 调用和页面加载的超时时间
+    pub timeout: Duration,
+    /// 额外的启动参数
+    pub extra_args: Vec<String>,
+}
+
+impl Default for BrowserConfig {
+    fn default() -> Self {
+        Self {
+            executable: None,
+            headless: true,
+            timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+/// 查找可用的浏览器可执行文件
+pub fn find_browser_executable() -> Option<PathBuf> {
+    crate::commands::provider_pool_cmd::get_available_browser_path()
+        .map(|(path, _source)| PathBuf::from(path))
+}
+
+// ============================================================================
+// CDP 连接
+// ============================================================================
+
+/// CDP 事件
+#[derive(Debug, Clone)]
+pub struct CdpEvent {
+    /// 事件名称，如 `Page.loadEventFired`
+    pub method: String,
+    /// 事件参数
+    pub params: Value,
+    /// 来源会话 ID（浏览器级事件为 None）
+    pub session_id: Option<String>,
+}
+
+/// 等待响应的调用
+type PendingCall = (String, oneshot::Sender<Result<Value, BrowserError>>);
+
+/// CDP WebSocket 连接
+///
+/// 使用扁平会话模式（`flatten: true`），所有标签页共享一个连接，
+/// 通过 `sessionId` 区分。
+pub struct CdpConnection {
+    /// 下一个请求 ID
+    next_id: AtomicU64,
+    /// 发送队列
+    outgoing: mpsc::UnboundedSender<String>,
+    /// 等待响应的调用
+    pending: Arc<Mutex<HashMap<u64, PendingCall>>>,
+    /// 事件接收端模板（用于创建新的订阅）
+    events: broadcast::Receiver<CdpEvent>,
+    /// 连接是否已关闭
+    closed: Arc<AtomicBool>,
+    /// 调用超时
+    timeout: Duration,
+}
+
+impl CdpConnection {
+    /// 连接到 CDP WebSocket 端点
+    ///
+    /// # 参数
+    /// - `ws_url`: WebSocket 地址（`ws://127.0.0.1:PORT/devtools/browser/ID`）
+    /// - `call_timeout`: 单次调用超时
+    pub async fn connect(ws_url: &str, call_timeout: Duration) -> Result<Self, BrowserError> {
+        let (stream, _) = tokio_tungstenite::connect_async(ws_url)
+            .await
+            .map_err(|e| BrowserError::Connection(format!("连接 {} 失败: {}", ws_url, e)))?;
+        let (mut sink, mut source) = stream.split();
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            while let Some(text) = outgoing_rx.recv().await {
+                if sink.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            let _ = sink.close().await;
+        });
+
+        let pending: Arc<Mutex<HashMap<u64, PendingCall>>> = Arc::new(Mutex::new(HashMap::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+        let (events_tx, events) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
+        let reader_pending = pending.clone();
+        let reader_closed = closed.clone();
+        tokio::spawn(async move {
+            while let Some(message) = source.next().await {
+                let text = match message {
+                    Ok(Message::Text(text)) => text,
+                    Ok(Message::Close(_)) | Err(_) => break,
+                    Ok(_) => continue,
+                };
+                let Ok(value) = serde_json::from_str::<Value>(&text) else {
+                    continue;
+                };
+                dispatch_message(value, &reader_pending, &events_tx);
+            }
+
+            reader_closed.store(true, Ordering::SeqCst);
+            for (_, (method, tx)) in reader_pending.lock().drain() {
+                let _ = tx.send(Err(BrowserError::Connection(format!(
+                    "等待 {} 响应时连接已关闭",
+                    method
+                ))));
+            }
+            debug!("[CDP] 连接已关闭");
+        });
+
+        Ok(Self {
+            next_id: AtomicU64::new(1),
+            outgoing,
+            pending,
+            events,
+            closed,
+            timeout: call_timeout,
+        })
+    }
+
+    /// 连接是否已关闭
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// 订阅事件
+    ///
+    /// 只能收到订阅之后的事件，需要等待的事件应在发起调用之前订阅。
+    pub fn subscribe(&self) -> broadcast::Receiver<CdpEvent> {
+        self.events.resubscribe()
+    }
+
+    /// 调用 CDP 方法
+    ///
+    /// # 参数
+    /// - `method`: 方法名，如 `Page.navigate`
+    /// - `params`: 参数
+    /// - `session_id`: 目标会话 ID（浏览器级调用为 None）
+    pub async fn call(
+        &self,
+        method: &str,
+        params: Value,
+        session_id: Option<&str>,
+    ) -> Result<Value, BrowserError> {
+        if self.is_closed() {
+            return Err(BrowserError::Connection("连接已关闭".to_string()));
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut message = json!({ "id": id, "method": method, "params": params });
+        if let Some(session_id) = session_id {
+            message["sessionId"] = json!(session_id);
+        }
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().insert(id, (method.to_string(), tx));
+        // 读取任务先标记关闭再清理等待队列，插入后再检查一次可避免请求永久挂起
+        if self.is_closed() || self.outgoing.send(message.to_string()).is_err() {
+            self.pending.lock().remove(&id);
+            return Err(BrowserError::Connection("连接已关闭".to_string()));
+        }
+
+        match timeout(self.timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(BrowserError::Connection("连接已关闭".to_string())),
+            Err(_) => {
+                self.pending.lock().remove(&id);
+                Err(BrowserError::Timeout(method.to_string()))
+            }
+        }
+    }
+}
+
+/// 分发收到的 CDP 消息（响应或事件）
+fn dispatch_message(
+    value: Value,
+    pending: &Mutex<HashMap<u64, PendingCall>>,
+    events: &broadcast::Sender<CdpEvent>,
+) {
+    if let Some(id) = value.get("id").and_then(Value::as_u64) {
+        let Some((method, tx)) = pending.lock().remove(&id) else {
+            return;
+        };
+        let result = match value.get("error") {
+            Some(error) => Err(BrowserError::Protocol {
+                method,
+                message: error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or("未知错误")
+                    .to_string(),
+            }),
+            None => Ok(value.get("result").cloned().unwrap_or(Value::Null)),
+        };
+        let _ = tx.send(result);
+        return;
+    }
+
+    if let Some(method) = value.get("method").and_then(Value::as_str) {
+        let _ = events.send(CdpEvent {
+            method: method.to_string(),
+            params: value.get("params").cloned().unwrap_or(Value::Null),
+            session_id: value
+                .get("sessionId")
+                .and_then(Value::as_str)
+                .map(String::from),
+        });
+    }
+}
+
+/// 等待指定会话的事件
+///
+/// # 返回
+/// 事件参数（超时或连接关闭时为 None）
+async fn wait_for_event(
+    rx: &mut broadcast::Receiver<CdpEvent>,
+    session_id: &str,
+    method: &str,
+    wait: Duration,
+) -> Option<Value> {
+    let deadline = Instant::now() + wait;
+    loop {
+        let remaining = deadline.checked_duration_since(Instant::now())?;
+        match timeout(remaining, rx.recv()).await {
+            Ok(Ok(event)) => {
+                if event.method == method && event.session_id.as_deref() == Some(session_id) {
+                    return Some(event.params);
+                }
+            }
+            Ok(Err(broadcast::error::RecvError::Lagged(_))) => continue,
+            Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => return None,
+        }
+    }
+}
+
+// ============================================================================
+// 浏览器进程
+// ============================================================================
+
+/// 浏览器进程
+struct ChromeProcess {
+    /// 子进程（drop 时终止）
+    _child: Child,
+    /// 临时用户数据目录
+    user_data_dir: PathBuf,
+}
+
+impl ChromeProcess {
+    /// 启动浏览器
+    ///
+    /// # 返回
+    /// 进程和浏览器级 WebSocket 地址
+    async fn launch(config: &BrowserConfig) -> Result<(Self, String), BrowserError> {
+        let executable = config
+            .executable
+            .clone()
+            .or_else(find_browser_executable)
+            .ok_or(BrowserError::BrowserNotFound)?;
+        let user_data_dir =
+            std::env::temp_dir().join(format!("proxycast-browser-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&user_data_dir)?;
+
+        let mut cmd = Command::new(&executable);
+        if config.headless {
+            cmd.arg("--headless=new");
+        }
+        cmd.arg("--remote-debugging-port=0")
+            .arg(format!("--user-data-dir={}", user_data_dir.display()))
+            .args([
+                "--no-first-run",
+                "--no-default-browser-check",
+                "--disable-background-networking",
+                "--disable-component-update",
+                "--disable-extensions",
+                "--disable-sync",
+                "--disable-gpu",
+                "--mute-audio",
+                "--hide-scrollbars",
+                "--window-size=1280,800",
+            ]);
+        // 以 root 运行时（如容器内）Chrome 要求关闭沙箱
+        #[cfg(target_os = "linux")]
+        if whoami::username() == "root" {
+            cmd.arg("--no-sandbox");
+        }
+        cmd.args(&config.extra_args)
+            .arg("about:blank")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        info!("[Browser] 启动浏览器: {:?}", executable);
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| BrowserError::LaunchFailed(format!("{:?}: {}", executable, e)))?;
+        let stderr = child.stderr.take();
+        // 之后的错误返回会 drop 进程，终止浏览器并清理用户数据目录
+        let process = Self {
+            _child: child,
+            user_data_dir,
+        };
+        let stderr =
+            stderr.ok_or_else(|| BrowserError::LaunchFailed("无法读取浏览器输出".to_string()))?;
+
+        let mut lines = BufReader::new(stderr).lines();
+        let mut output = Vec::new();
+        let ws_url = timeout(Duration::from_secs(LAUNCH_TIMEOUT_SECS), async {
+            while let Ok(Some(line)) = lines.next_line().await {
+                if let Some(url) = line.trim().strip_prefix("DevTools listening on ") {
+                    return Some(url.to_string());
+                }
+                output.push(line);
+            }
+            None
+        })
+        .await
+        .map_err(|_| BrowserError::Timeout("等待浏览器启动".to_string()))?
+        .ok_or_else(|| {
+            BrowserError::LaunchFailed(format!(
+                "浏览器已退出: {}",
+                output[output.len().saturating_sub(5)..].join("\n")
+            ))
+        })?;
+
+        // 持续读取输出，避免管道写满阻塞浏览器
+        tokio::spawn(async move {
+            while let Ok(Some(line)) = lines.next_line().await {
+                debug!("[Browser] {}", line);
+            }
+        });
+
+        Ok((process, ws_url))
+    }
+}
+
+impl Drop for ChromeProcess {
+    fn drop(&mut self) {
+        // 子进程由 kill_on_drop 终止，这里只清理用户数据目录
+        let _ = std::fs::remove_dir_all(&self.user_data_dir);
+    }
+}
+
+// ============================================================================
+// 可访问性快照
+// ============================================================================
+
+/// 元素引用表
+///
+/// 将 DOM 节点（backendNodeId）映射为 `e1`、`e2` 形式的引用。同一节点在
+/// 多次快照中保持相同引用；导航后清空映射，但编号继续递增，旧引用不会指向新元素。
+#[derive(Debug, Default)]
+pub struct RefMap {
+    by_node: HashMap<i64, String>,
+    by_ref: HashMap<String, i64>,
+    next: usize,
+}
+
+impl RefMap {
+    /// 获取节点引用（不存在时分配新引用）
+    pub fn assign(&mut self, backend_node_id: i64) -> String {
+        if let Some(existing) = self.by_node.get(&backend_node_id) {
+            return existing.clone();
+        }
+        self.next += 1;
+        let element_ref = format!("e{}", self.next);
+        self.by_node.insert(backend_node_id, element_ref.clone());
+        self.by_ref.insert(element_ref.clone(), backend_node_id);
+        element_ref
+    }
+
+    /// 根据引用查找节点
+    pub fn resolve(&self, element_ref: &str) -> Option<i64> {
+        self.by_ref.get(element_ref).copied()
+    }
+
+    /// 清空映射（保留编号）
+    pub fn clear(&mut self) {
+        self.by_node.clear();
+        self.by_ref.clear();
+    }
+}
+
+/// 解析元素引用选择器
+///
+/// 支持 `@e3`、`ref=e3` 和 `e3` 三种写法。
+pub fn parse_element_ref(selector: &str) -> Option<&str> {
+    let candidate = selector.trim();
+    let candidate = candidate
+        .strip_prefix('@')
+        .or_else(|| candidate.strip_prefix("ref="))
+        .unwrap_or(candidate);
+    let digits = candidate.strip_prefix('e')?;
+    (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())).then_some(candidate)
+}
+
+/// 将 `Accessibility.getFullAXTree` 返回的节点渲染为快照文本
+///
+/// 输出形如：
+/// ```text
+/// - heading "Welcome" [level=1] [ref=e1]
+/// - textbox "Email" [ref=e2]: me@example.com
+/// - button "Submit" [ref=e3]
+/// ```
+///
+/// # 参数
+/// - `nodes`: 可访问性节点列表
+/// - `refs`: 元素引用表（为输出的元素分配引用）
+/// - `interactive_only`: 只输出可交互元素（不缩进）
+pub fn render_ax_tree(nodes: &[Value], refs: &mut RefMap, interactive_only: bool) -> String {
+    let by_id: HashMap<&str, &Value> = nodes
+        .iter()
+        .filter_map(|node| Some((node.get("nodeId")?.as_str()?, node)))
+        .collect();
+    let root = nodes
+        .iter()
+        .find(|node| node.get("parentId").is_none())
+        .or_else(|| nodes.first());
+
+    let mut lines = Vec::new();
+    if let Some(root) = root {
+        let mut renderer = SnapshotRenderer {
+            by_id: &by_id,
+            refs,
+            interactive_only,
+            lines: &mut lines,
+        };
+        // 根节点（RootWebArea）本身不输出
+        renderer.children(root, 0, "");
+    }
+    lines.join("\n")
+}
+
+/// 快照渲染器
+struct SnapshotRenderer<'a> {
+    by_id: &'a HashMap<&'a str, &'a Value>,
+    refs: &'a mut RefMap,
+    interactive_only: bool,
+    lines: &'a mut Vec<String>,
+}
+
+impl SnapshotRenderer<'_> {
+    fn children(&mut self, node: &Value, depth: usize, parent_name: &str) {
+        let by_id = self.by_id;
+        for child_id in node
+            .get("childIds")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            if let Some(child) = child_id.as_str().and_then(|id| by_id.get(id)) {
+                self.node(child, depth, parent_name);
+            }
+        }
+    }
+
+    fn node(&mut self, node: &Value, depth: usize, parent_name: &str) {
+        let role = ax_string(node, "role");
+        let name = ax_string(node, "name");
+        let name = name.trim();
+
+        if node
+            .get("ignored")
+            .and_then(Value::as_bool)
+            .unwrap_or(false)
+            || SKIPPED_ROLES.contains(&role.as_str())
+        {
+            if !SKIPPED_ROLES.contains(&role.as_str()) {
+                self.children(node, depth, parent_name);
+            }
+            return;
+        }
+
+        if role == "StaticText" {
+            // 与父节点名称相同的文本（如链接文字）不重复输出
+            if !self.interactive_only && !name.is_empty() && name != parent_name {
+                self.push(
+                    depth,
+                    format!("- text: {}", truncate_chars(name, MAX_NAME_CHARS)),
+                );
+            }
+            return;
+        }
+
+        if name.is_empty() && TRANSPARENT_ROLES.contains(&role.as_str()) {
+            self.children(node, depth, parent_name);
+            return;
+        }
+
+        let interactive = INTERACTIVE_ROLES.contains(&role.as_str());
+        if self.interactive_only && !interactive {
+            self.children(node, depth, parent_name);
+            return;
+        }
+
+        let mut line = format!("- {}", role);
+        if !name.is_empty() {
+            line.push(' ');
+            line.push_str(&Value::from(truncate_chars(name, MAX_NAME_CHARS)).to_string());
+        }
+        for property in node
+            .get("properties")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let prop_name = property.get("name").and_then(Value::as_str).unwrap_or("");
+            let value = property.pointer("/value/value");
+            if prop_name == "level" {
+                if let Some(level) = value.and_then(Value::as_i64) {
+                    line.push_str(&format!(" [level={}]", level));
+                }
+            } else if SNAPSHOT_STATES.contains(&prop_name) {
+                match value {
+                    Some(Value::Bool(true)) => line.push_str(&format!(" [{}]", prop_name)),
+                    Some(Value::String(s)) if s == "true" || s == "mixed" => {
+                        line.push_str(&format!(" [{}={}]", prop_name, s))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if let Some(backend_id) = node.get("backendDOMNodeId").and_then(Value::as_i64) {
+            if interactive || !name.is_empty() {
+                line.push_str(&format!(" [ref={}]", self.refs.assign(backend_id)));
+            }
+        }
+        let value = ax_string(node, "value");
+        if !value.is_empty() {
+            line.push_str(": ");
+            line.push_str(&truncate_chars(&value, MAX_NAME_CHARS));
+        }
+
+        self.push(depth, line);
+        let child_depth = if self.interactive_only {
+            depth
+        } else {
+            depth + 1
+        };
+        self.children(node, child_depth, name);
+    }
+
+    fn push(&mut self, depth: usize, line: String) {
+        let depth = if self.interactive_only { 0 } else { depth };
+        self.lines.push(format!("{}{}", "  ".repeat(depth), line));
+    }
+}
+
+/// 读取可访问性节点的字符串字段（`role`、`name`、`value`）
+fn ax_string(node: &Value, field: &str) -> String {
+    match node.get(field).and_then(|v| v.get("value")) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// 按字符截断文本
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars).collect();
+    truncated.push('…');
+    truncated
+}
+
+// ============================================================================
+// 键盘
+// ============================================================================
+
+/// 按键定义
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDefinition {
+    /// DOM `key`
+    pub key: String,
+    /// DOM `code`
+    pub code: String,
+    /// Windows 虚拟键码
+    pub key_code: i64,
+    /// 输入的文本（不产生文本的按键为 None）
+    pub text: Option<String>,
+}
+
+/// 解析按键组合，如 `Enter`、`Control+a`、`Shift+Tab`
+///
+/// # 返回
+/// CDP 修饰键位掩码（Alt=1, Control=2, Meta=4, Shift=8）和按键定义
+pub fn parse_key_combo(combo: &str) -> Result<(i64, KeyDefinition), BrowserError> {
+    let parts: Vec<&str> = combo.split('+').collect();
+    // 允许单独的 "+" 键
+    let (modifier_parts, key_name) = match parts.as_slice() {
+        [.., "", ""] => (&parts[..parts.len() - 2], "+"),
+        [modifiers @ .., key] => (modifiers, *key),
+        [] => (&parts[..0], ""),
+    };
+
+    let mut modifiers = 0;
+    for modifier in modifier_parts {
+        modifiers |= match modifier.to_ascii_lowercase().as_str() {
+            "alt" | "option" => 1,
+            "control" | "ctrl" => 2,
+            "meta" | "cmd" | "command" => 4,
+            "shift" => 8,
+            _ => {
+                return Err(BrowserError::InvalidArgument(format!(
+                    "未知的修饰键: {}",
+                    modifier
+                )))
+            }
+        };
+    }
+
+    let definition = key_definition(key_name)
+        .ok_or_else(|| BrowserError::InvalidArgument(format!("未知的按键: {}", combo)))?;
+    Ok((modifiers, definition))
+}
+
+/// 查找按键定义
+fn key_definition(name: &str) -> Option<KeyDefinition> {
+    let named = |key: &str, key_code: i64, text: Option<&str>| KeyDefinition {
+        key: key.to_string(),
+        code: key.to_string(),
+        key_code,
+        text: text.map(String::from),
+    };
+
+    let definition = match name {
+        "Enter" => named("Enter", 13, Some("\r")),
+        "Tab" => named("Tab", 9, None),
+        "Escape" | "Esc" => named("Escape", 27, None),
+        "Backspace" => named("Backspace", 8, None),
+        "Delete" => named("Delete", 46, None),
+        "ArrowUp" => named("ArrowUp", 38, None),
+        "ArrowDown" => named("ArrowDown", 40, None),
+        "ArrowLeft" => named("ArrowLeft", 37, None),
+        "ArrowRight" => named("ArrowRight", 39, None),
+        "Home" => named("Home", 36, None),
+        "End" => named("End", 35, None),
+        "PageUp" => named("PageUp", 33, None),
+        "PageDown" => named("PageDown", 34, None),
+        "Space" | " " => KeyDefinition {
+            key: " ".to_string(),
+            code: "Space".to_string(),
+            key_code: 32,
+            text: Some(" ".to_string()),
+        },
+        _ => {
+            if let Some(n) = name
+                .strip_prefix('F')
+                .and_then(|n| n.parse::<i64>().ok())
+                .filter(|n| (1..=12).contains(n))
+            {
+                return Some(named(name, 111 + n, None));
+            }
+
+            let mut chars = name.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return None;
+            };
+            let (code, key_code) = if c.is_ascii_alphabetic() {
+                (
+                    format!("Key{}", c.to_ascii_uppercase()),
+                    c.to_ascii_uppercase() as i64,
+                )
+            } else if c.is_ascii_digit() {
+                (format!("Digit{}", c), c as i64)
+            } else {
+                (String::new(), 0)
+            };
+            KeyDefinition {
+                key: c.to_string(),
+                code,
+                key_code,
+                text: Some(c.to_string()),
+            }
+        }
+    };
+    Some(definition)
+}
+
+// ============================================================================
+// 浏览器会话
+// ============================================================================
+
+/// 滚动方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrollDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// 标签页信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabInfo {
+    /// 标签页 ID（CDP targetId）
+    pub id: String,
+    /// 页面 URL
+    pub url: String,
+    /// 页面标题
+    pub title: String,
+    /// 是否为当前标签页
+    pub active: bool,
+}
+
+/// 下载状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadState {
+    InProgress,
+    Completed,
+    Canceled,
+}
+
+/// 下载信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadInfo {
+    /// 下载 ID
+    pub guid: String,
+    /// 下载地址
+    pub url: String,
+    /// 文件名
+    pub filename: String,
+    /// 保存路径（完成后存在）
+    pub path: Option<PathBuf>,
+    /// 状态
+    pub state: DownloadState,
+    /// 已接收字节数
+    pub received_bytes: u64,
+    /// 总字节数（未知时为 0）
+    pub total_bytes: u64,
+}
+
+/// 标签页状态
+struct TabState {
+    /// CDP 会话 ID
+    cdp_session: String,
+    /// 元素引用表
+    refs: RefMap,
+}
+
+/// 会话内部状态
+#[derive(Default)]
+struct SessionState {
+    /// 已附加的标签页（targetId -> 状态）
+    tabs: HashMap<String, TabState>,
+    /// 当前标签页
+    active: Option<String>,
+}
+
+/// 浏览器会话
+///
+/// 对应一个 Agent 会话，拥有独立的 BrowserContext（Cookie、存储、缓存隔离）
+/// 和下载目录。同一会话内的操作串行执行。
+pub struct BrowserSession {
+    /// Agent 会话 ID
+    id: String,
+    /// CDP 连接
+    conn: Arc<CdpConnection>,
+    /// BrowserContext ID
+    context_id: String,
+    /// 下载目录
+    download_dir: PathBuf,
+    /// 页面加载超时
+    timeout: Duration,
+    /// 标签页状态
+    state: tokio::sync::Mutex<SessionState>,
+    /// 本会话标签页的 targetId（供下载跟踪使用）
+    tab_ids: Arc<Mutex<HashSet<String>>>,
+    /// 下载记录
+    downloads: Arc<Mutex<Vec<DownloadInfo>>>,
+    /// 下载跟踪任务
+    download_task: JoinHandle<()>,
+}
+
+impl BrowserSession {
+    /// 创建会话（新建 BrowserContext 并设置下载目录）
+    async fn create(
+        conn: Arc<CdpConnection>,
+        id: &str,
+        download_dir: &Path,
+        page_timeout: Duration,
+    ) -> Result<Self, BrowserError> {
+        std::fs::create_dir_all(download_dir)?;
+        let download_dir = download_dir.canonicalize()?;
+
+        let result = conn
+            .call(
+                "Target.createBrowserContext",
+                json!({ "disposeOnDetach": false }),
+                None,
+            )
+            .await?;
+        let context_id = result
+            .get("browserContextId")
+            .and_then(Value::as_str)
+            .ok_or_else(|| BrowserError::Connection("未返回 browserContextId".to_string()))?
+            .to_string();
+
+        // 下载先以 guid 命名，完成后由跟踪任务重命名，guid 文件所在目录即归属会话
+        conn.call(
+            "Browser.setDownloadBehavior",
+            json!({
+                "behavior": "allowAndName",
+                "browserContextId": context_id,
+                "downloadPath": download_dir.to_string_lossy(),
+                "eventsEnabled": true,
+            }),
+            None,
+        )
+        .await?;
+
+        let tab_ids = Arc::new(Mutex::new(HashSet::new()));
+        let downloads = Arc::new(Mutex::new(Vec::new()));
+        let download_task = tokio::spawn(track_downloads(
+            conn.subscribe(),
+            download_dir.clone(),
+            tab_ids.clone(),
+            downloads.clone(),
+        ));
+
+        info!(
+            "[Browser] 创建会话 {} (context: {}, 下载目录: {:?})",
+            id, context_id, download_dir
+        );
+
+        Ok(Self {
+            id: id.to_string(),
+            conn,
+            context_id,
+            download_dir,
+            timeout: page_timeout,
+            state: tokio::sync::Mutex::new(SessionState::default()),
+            tab_ids,
+            downloads,
+            download_task,
+        })
+    }
+
+    /// Agent 会话 ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// 下载目录
+    pub fn download_dir(&self) -> &Path {
+        &self.download_dir
+    }
+
+    /// 会话是否可用（浏览器连接未断开）
+    pub fn is_alive(&self) -> bool {
+        !self.conn.is_closed()
+    }
+
+    // ------------------------------------------------------------------------
+    // 标签页
+    // ------------------------------------------------------------------------
+
+    /// 新建标签页并设为当前标签页
+    ///
+    /// # 参数
+    /// - `url`: 打开的地址（None 为空白页）
+    pub async fn new_tab(&self, url: Option<&str>) -> Result<TabInfo, BrowserError> {
+        let mut state = self.state.lock().await;
+        let target_id = self.create_tab(&mut state).await?;
+        if let Some(url) = url {
+            self.navigate_in(&mut state, url).await?;
+        }
+        self.tab_info(&target_id, true).await
+    }
+
+    /// 列出本会话的所有标签页（包括页面打开的新窗口）
+    pub async fn list_tabs(&self) -> Result<Vec<TabInfo>, BrowserError> {
+        let mut state = self.state.lock().await;
+        let targets = self.page_targets().await?;
+
+        // 清理已关闭的标签页
+        let live: HashSet<&str> = targets
+            .iter()
+            .filter_map(|t| t.get("targetId").and_then(Value::as_str))
+            .collect();
+        state.tabs.retain(|id, _| live.contains(id.as_str()));
+        self.tab_ids.lock().retain(|id| live.contains(id.as_str()));
+        if state.active.as_deref().is_some_and(|id| !live.contains(id)) {
+            state.active = None;
+        }
+
+        Ok(targets
+            .iter()
+            .map(|target| {
+                let id = target
+                    .get("targetId")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+                TabInfo {
+                    active: state.active.as_deref() == Some(id.as_str()),
+                    url: target
+                        .get("url")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    title: target
+                        .get("title")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    id,
+                }
+            })
+            .collect())
+    }
+
+    /// 切换当前标签页
+    pub async fn switch_tab(&self, tab_id: &str) -> Result<TabInfo, BrowserError> {
+        let mut state = self.state.lock().await;
+        let known = self
+            .page_targets()
+            .await?
+            .iter()
+            .any(|target| target.get("targetId").and_then(Value::as_str) == Some(tab_id));
+        if !known {
+            return Err(BrowserError::TabNotFound(tab_id.to_string()));
+        }
+
+        if !state.tabs.contains_key(tab_id) {
+            self.attach(&mut state, tab_id).await?;
+        }
+        state.active = Some(tab_id.to_string());
+        self.conn
+            .call("Target.activateTarget", json!({ "targetId": tab_id }), None)
+            .await?;
+        self.tab_info(tab_id, true).await
+    }
+
+    /// 关闭标签页（默认关闭当前标签页）
+    ///
+    /// # 返回
+    /// 关闭后的当前标签页 ID
+    pub async fn close_tab(&self, tab_id: Option<&str>) -> Result<Option<String>, BrowserError> {
+        let mut state = self.state.lock().await;
+        let tab_id = match tab_id.map(String::from).or_else(|| state.active.clone()) {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+
+        self.conn
+            .call("Target.closeTarget", json!({ "targetId": tab_id }), None)
+            .await
+            .map_err(|_| BrowserError::TabNotFound(tab_id.clone()))?;
+        state.tabs.remove(&tab_id);
+        self.tab_ids.lock().remove(&tab_id);
+
+        if state.active.as_deref() == Some(tab_id.as_str()) {
+            state.active = state.tabs.keys().next().cloned();
+        }
+        Ok(state.active.clone())
+    }
+
+    // ------------------------------------------------------------------------
+    // 页面操作（作用于当前标签页）
+    // ------------------------------------------------------------------------
+
+    /// 打开页面（没有标签页时新建）
+    pub async fn navigate(&self, url: &str) -> Result<(), BrowserError> {
+        let mut state = self.state.lock().await;
+        self.navigate_in(&mut state, url).await
+    }
+
+    /// 获取当前页面的 URL 和标题
+    pub async fn page_info(&self) -> Result<(String, String), BrowserError> {
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+        let value = self
+            .evaluate_in(&sid, "[location.href, document.title]")
+            .await?;
+        Ok((
+            value[0].as_str().unwrap_or_default().to_string(),
+            value[1].as_str().unwrap_or_default().to_string(),
+        ))
+    }
+
+    /// 获取可访问性快照
+    ///
+    /// # 参数
+    /// - `interactive_only`: 只输出可交互元素
+    pub async fn snapshot(&self, interactive_only: bool) -> Result<String, BrowserError> {
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+        let result = self
+            .conn
+            .call("Accessibility.getFullAXTree", json!({}), Some(&sid))
+            .await?;
+        let nodes = result
+            .get("nodes")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+
+        let tab = self.active_tab(&mut state)?;
+        Ok(render_ax_tree(&nodes, &mut tab.refs, interactive_only))
+    }
+
+    /// 点击元素
+    pub async fn click(&self, selector: &str) -> Result<(), BrowserError> {
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+        let object_id = self.resolve(&mut state, &sid, selector).await?;
+        let mut events = self.conn.subscribe();
+
+        self.call_on(
+            &sid,
+            &object_id,
+            "function() { this.scrollIntoView({ block: 'center', inline: 'center' }); }",
+            vec![],
+        )
+        .await?;
+
+        match self.element_center(&sid, &object_id).await {
+            Some((x, y)) => {
+                for (event_type, buttons) in
+                    [("mouseMoved", 0), ("mousePressed", 1), ("mouseReleased", 0)]
+                {
+                    self.conn
+                        .call(
+                            "Input.dispatchMouseEvent",
+                            json!({
+                                "type": event_type,
+                                "x": x,
+                                "y": y,
+                                "button": if event_type == "mouseMoved" { "none" } else { "left" },
+                                "buttons": buttons,
+                                "clickCount": 1,
+                            }),
+                            Some(&sid),
+                        )
+                        .await?;
+                }
+            }
+            // 元素没有可见区域时退回到 DOM click
+            None => {
+                self.call_on(&sid, &object_id, "function() { this.click(); }", vec![])
+                    .await?;
+            }
+        }
+
+        self.settle(&mut state, &sid, &mut events).await;
+        Ok(())
+    }
+
+    /// 填充表单元素（替换原有内容并触发 input/change 事件）
+    pub async fn fill(&self, selector: &str, value: &str) -> Result<(), BrowserError> {
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+        let object_id = self.resolve(&mut state, &sid, selector).await?;
+
+        self.call_on(
+            &sid,
+            &object_id,
+            "function(value) {
+                this.scrollIntoView({ block: 'center' });
+                this.focus();
+                if (this.isContentEditable) {
+                    this.textContent = value;
+                } else {
+                    const descriptor = Object.getOwnPropertyDescriptor(Object.getPrototypeOf(this), 'value');
+                    if (descriptor && descriptor.set) { descriptor.set.call(this, value); } else { this.value = value; }
+                }
+                this.dispatchEvent(new Event('input', { bubbles: true }));
+                this.dispatchEvent(new Event('change', { bubbles: true }));
+            }",
+            vec![json!(value)],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// 逐字符输入文本
+    pub async fn type_text(&self, selector: &str, text: &str) -> Result<(), BrowserError> {
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+        let object_id = self.resolve(&mut state, &sid, selector).await?;
+        self.call_on(&sid, &object_id, "function() { this.focus(); }", vec![])
+            .await?;
+
+        for c in text.chars() {
+            let key = if c == '\n' {
+                "Enter".to_string()
+            } else {
+                c.to_string()
+            };
+            let (_, definition) = parse_key_combo(&key).unwrap_or((
+                0,
+                KeyDefinition {
+                    key: key.clone(),
+                    code: String::new(),
+                    key_code: 0,
+                    text: Some(key.clone()),
+                },
+            ));
+            self.dispatch_key(&sid, 0, &definition).await?;
+        }
+        Ok(())
+    }
+
+    /// 按键（支持组合键，如 `Control+a`）
+    pub async fn press(&self, key: &str) -> Result<(), BrowserError> {
+        let (modifiers, definition) = parse_key_combo(key)?;
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+        let mut events = self.conn.subscribe();
+
+        self.dispatch_key(&sid, modifiers, &definition).await?;
+        self.settle(&mut state, &sid, &mut events).await;
+        Ok(())
+    }
+
+    /// 滚动页面
+    pub async fn scroll(
+        &self,
+        direction: ScrollDirection,
+        amount: i32,
+    ) -> Result<(), BrowserError> {
+        let (dx, dy) = match direction {
+            ScrollDirection::Up => (0, -amount),
+            ScrollDirection::Down => (0, amount),
+            ScrollDirection::Left => (-amount, 0),
+            ScrollDirection::Right => (amount, 0),
+        };
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+        self.evaluate_in(&sid, &format!("window.scrollBy({}, {})", dx, dy))
+            .await?;
+        Ok(())
+    }
+
+    /// 等待元素出现
+    pub async fn wait_for(&self, selector: &str, timeout_ms: u64) -> Result<(), BrowserError> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+        loop {
+            {
+                let mut state = self.state.lock().await;
+                let sid = self.active_session(&mut state).await?;
+                match self.resolve(&mut state, &sid, selector).await {
+                    Ok(_) => return Ok(()),
+                    Err(BrowserError::ElementNotFound(_)) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(BrowserError::Timeout(format!("等待元素 {}", selector)));
+            }
+            tokio::time::sleep(Duration::from_millis(POLL_INTERVAL_MS)).await;
+        }
+    }
+
+    /// 截图
+    ///
+    /// # 返回
+    /// PNG 数据
+    pub async fn screenshot(&self, full_page: bool) -> Result<Vec<u8>, BrowserError> {
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+
+        let mut params = json!({ "format": "png" });
+        if full_page {
+            let metrics = self
+                .conn
+                .call("Page.getLayoutMetrics", json!({}), Some(&sid))
+                .await?;
+            let size = metrics
+                .get("cssContentSize")
+                .or_else(|| metrics.get("contentSize"))
+                .cloned()
+                .unwrap_or(Value::Null);
+            params["captureBeyondViewport"] = json!(true);
+            params["clip"] = json!({
+                "x": 0,
+                "y": 0,
+                "width": size.get("width").and_then(Value::as_f64).unwrap_or(1280.0),
+                "height": size.get("height").and_then(Value::as_f64).unwrap_or(800.0),
+                "scale": 1,
+            });
+        }
+
+        let result = self
+            .conn
+            .call("Page.captureScreenshot", params, Some(&sid))
+            .await?;
+        let data = result.get("data").and_then(Value::as_str).unwrap_or("");
+        BASE64
+            .decode(data)
+            .map_err(|e| BrowserError::Connection(format!("截图数据无效: {}", e)))
+    }
+
+    /// 获取文本内容（未指定选择器时为整个页面）
+    pub async fn get_text(&self, selector: Option<&str>) -> Result<String, BrowserError> {
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+        let value = match selector {
+            Some(selector) => {
+                let object_id = self.resolve(&mut state, &sid, selector).await?;
+                self.call_on(
+                    &sid,
+                    &object_id,
+                    "function() { return this.innerText ?? this.textContent ?? ''; }",
+                    vec![],
+                )
+                .await?
+            }
+            None => {
+                self.evaluate_in(&sid, "document.body ? document.body.innerText : ''")
+                    .await?
+            }
+        };
+        Ok(value.as_str().unwrap_or_default().to_string())
+    }
+
+    /// 执行 JavaScript 表达式（支持 Promise）
+    ///
+    /// # 返回
+    /// 表达式结果（JSON 值，undefined 为 Null）
+    pub async fn evaluate(&self, script: &str) -> Result<Value, BrowserError> {
+        let mut state = self.state.lock().await;
+        let sid = self.active_session(&mut state).await?;
+        self.evaluate_in(&sid, script).await
+    }
+
+    /// 获取下载记录
+    pub fn downloads(&self) -> Vec<DownloadInfo> {
+        self.downloads.lock().clone()
+    }
+
+    /// 关闭会话（销毁 BrowserContext，关闭所有标签页）
+    pub async fn close(&self) -> Result<(), BrowserError> {
+        self.download_task.abort();
+        self.conn
+            .call(
+                "Target.disposeBrowserContext",
+                json!({ "browserContextId": self.context_id }),
+                None,
+            )
+            .await?;
+        info!("[Browser] 会话 {} 已关闭", self.id);
+        Ok(())
+    }
+
+    // ------------------------------------------------------------------------
+    // 内部实现
+    // ------------------------------------------------------------------------
+
+    /// 本会话的页面 target
+    async fn page_targets(&self) -> Result<Vec<Value>, BrowserError> {
+        let result = self.conn.call("Target.getTargets", json!({}), None).await?;
+        Ok(result
+            .get("targetInfos")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter(|target| {
+                target.get("type").and_then(Value::as_str) == Some("page")
+                    && target.get("browserContextId").and_then(Value::as_str)
+                        == Some(self.context_id.as_str())
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// 新建标签页并设为当前标签页
+    async fn create_tab(&self, state: &mut SessionState) -> Result<String, BrowserError> {
+        let result = self
+            .conn
+            .call(
+                "Target.createTarget",
+                json!({ "url": "about:blank", "browserContextId": self.context_id }),
+                None,
+            )
+            .await?;
+        let target_id = result
+            .get("targetId")
+            .and_then(Value::as_str)
+            .ok_or_else(|| BrowserError::Connection("未返回 targetId".to_string()))?
+            .to_string();
+
+        self.attach(state, &target_id).await?;
+        state.active = Some(target_id.clone());
+        debug!("[Browser] 会话 {} 新建标签页 {}", self.id, target_id);
+        Ok(target_id)
+    }
+
+    /// 附加到标签页并启用所需的域
+    async fn attach(&self, state: &mut SessionState, target_id: &str) -> Result<(), BrowserError> {
+        let result = self
+            .conn
+            .call(
+                "Target.attachToTarget",
+                json!({ "targetId": target_id, "flatten": true }),
+                None,
+            )
+            .await?;
+        let cdp_session = result
+            .get("sessionId")
+            .and_then(Value::as_str)
+            .ok_or_else(|| BrowserError::Connection("未返回 sessionId".to_string()))?
+            .to_string();
+
+        for domain in ["Page.enable", "DOM.enable", "Accessibility.enable"] {
+            self.conn
+                .call(domain, json!({}), Some(&cdp_session))
+                .await?;
+        }
+
+        state.tabs.insert(
+            target_id.to_string(),
+            TabState {
+                cdp_session,
+                refs: RefMap::default(),
+            },
+        );
+        self.tab_ids.lock().insert(target_id.to_string());
+        Ok(())
+    }
+
+    /// 当前标签页状态
+    fn active_tab<'a>(
+        &self,
+        state: &'a mut SessionState,
+    ) -> Result<&'a mut TabState, BrowserError> {
+        let active = state
+            .active
+            .clone()
+            .ok_or_else(|| BrowserError::TabNotFound("当前没有打开的标签页".to_string()))?;
+        state
+            .tabs
+            .get_mut(&active)
+            .ok_or(BrowserError::TabNotFound(active))
+    }
+
+    /// 当前标签页的 CDP 会话 ID（没有标签页时新建）
+    async fn active_session(&self, state: &mut SessionState) -> Result<String, BrowserError> {
+        if !state
+            .active
+            .as_ref()
+            .is_some_and(|id| state.tabs.contains_key(id))
+        {
+            self.create_tab(state).await?;
+        }
+        Ok(self.active_tab(state)?.cdp_session.clone())
+    }
+
+    /// 在当前标签页打开页面并等待加载完成
+    async fn navigate_in(&self, state: &mut SessionState, url: &str) -> Result<(), BrowserError> {
+        let sid = self.active_session(state).await?;
+        let mut events = self.conn.subscribe();
+
+        let result = self
+            .conn
+            .call("Page.navigate", json!({ "url": url }), Some(&sid))
+            .await?;
+        if let Some(error) = result
+            .get("errorText")
+            .and_then(Value::as_str)
+            .filter(|e| !e.is_empty())
+        {
+            return Err(BrowserError::Navigation(format!("{}: {}", url, error)));
+        }
+
+        // 没有 loaderId 表示同文档导航（如锚点），无需等待
+        if result.get("loaderId").is_some()
+            && wait_for_event(&mut events, &sid, "Page.loadEventFired", self.timeout)
+                .await
+                .is_none()
+        {
+            warn!("[Browser] 等待页面加载超时: {}", url);
+        }
+
+        self.active_tab(state)?.refs.clear();
+        Ok(())
+    }
+
+    /// 交互操作后，如果页面开始导航则等待加载完成
+    async fn settle(
+        &self,
+        state: &mut SessionState,
+        sid: &str,
+        events: &mut broadcast::Receiver<CdpEvent>,
+    ) {
+        let settle = Duration::from_millis(NAVIGATION_SETTLE_MS);
+        if wait_for_event(events, sid, "Page.frameStartedLoading", settle)
+            .await
+            .is_some()
+        {
+            wait_for_event(events, sid, "Page.loadEventFired", self.timeout).await;
+            if let Ok(tab) = self.active_tab(state) {
+                tab.refs.clear();
+            }
+        }
+    }
+
+    /// 在页面中求值
+    async fn evaluate_in(&self, sid: &str, expression: &str) -> Result<Value, BrowserError> {
+        let result = self
+            .conn
+            .call(
+                "Runtime.evaluate",
+                json!({
+                    "expression": expression,
+                    "returnByValue": true,
+                    "awaitPromise": true,
+                    "userGesture": true,
+                }),
+                Some(sid),
+            )
+            .await?;
+        check_exception(&result)?;
+        Ok(result
+            .pointer("/result/value")
+            .cloned()
+            .unwrap_or(Value::Null))
+    }
+
+    /// 以元素为 this 调用函数
+    async fn call_on(
+        &self,
+        sid: &str,
+        object_id: &str,
+        function: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, BrowserError> {
+        let arguments: Vec<Value> = args.into_iter().map(|v| json!({ "value": v })).collect();
+        let result = self
+            .conn
+            .call(
+                "Runtime.callFunctionOn",
+                json!({
+                    "objectId": object_id,
+                    "functionDeclaration": function,
+                    "arguments": arguments,
+                    "returnByValue": true,
+                    "awaitPromise": true,
+                    "userGesture": true,
+                }),
+                Some(sid),
+            )
+            .await?;
+        check_exception(&result)?;
+        Ok(result
+            .pointer("/result/value")
+            .cloned()
+            .unwrap_or(Value::Null))
+    }
+
+    /// 解析选择器为元素对象 ID
+    ///
+    /// 支持元素引用（`@e3`）、XPath（`xpath=` 或以 `/` 开头）和 CSS 选择器。
+    async fn resolve(
+        &self,
+        state: &mut SessionState,
+        sid: &str,
+        selector: &str,
+    ) -> Result<String, BrowserError> {
+        if let Some(element_ref) = parse_element_ref(selector) {
+            let backend_id = self
+                .active_tab(state)?
+                .refs
+                .resolve(element_ref)
+                .ok_or_else(|| {
+                    BrowserError::ElementNotFound(format!(
+                        "{}（引用已失效，请重新获取快照）",
+                        selector
+                    ))
+                })?;
+            let result = self
+                .conn
+                .call(
+                    "DOM.resolveNode",
+                    json!({ "backendNodeId": backend_id }),
+                    Some(sid),
+                )
+                .await
+                .map_err(|_| {
+                    BrowserError::ElementNotFound(format!(
+                        "{}（元素已从页面移除，请重新获取快照）",
+                        selector
+                    ))
+                })?;
+            return result
+                .pointer("/object/objectId")
+                .and_then(Value::as_str)
+                .map(String::from)
+                .ok_or_else(|| BrowserError::ElementNotFound(selector.to_string()));
+        }
+
+        let selector = selector.trim();
+        let expression = if let Some(xpath) = selector
+            .strip_prefix("xpath=")
+            .or_else(|| selector.starts_with('/').then_some(selector))
+        {
+            format!(
+                "document.evaluate({}, document, null, XPathResult.FIRST_ORDERED_NODE_TYPE, null).singleNodeValue",
+                Value::from(xpath)
+            )
+        } else {
+            let css = selector.strip_prefix("css=").unwrap_or(selector);
+            format!("document.querySelector({})", Value::from(css))
+        };
+
+        let result = self
+            .conn
+            .call(
+                "Runtime.evaluate",
+                json!({ "expression": expression, "returnByValue": false }),
+                Some(sid),
+            )
+            .await?;
+        check_exception(&result)?;
+        result
+            .pointer("/result/objectId")
+            .and_then(Value::as_str)
+            .map(String::from)
+            .ok_or_else(|| BrowserError::ElementNotFound(selector.to_string()))
+    }
+
+    /// 元素可见区域的中心点（视口坐标）
+    async fn element_center(&self, sid: &str, object_id: &str) -> Option<(f64, f64)> {
+        let result = self
+            .conn
+            .call(
+                "DOM.getContentQuads",
+                json!({ "objectId": object_id }),
+                Some(sid),
+            )
+            .await
+            .ok()?;
+        let quad = result.get("quads")?.as_array()?.first()?.as_array()?;
+        let points: Vec<f64> = quad.iter().filter_map(Value::as_f64).collect();
+        if points.len() != 8 {
+            return None;
+        }
+        let x = (points[0] + points[2] + points[4] + points[6]) / 4.0;
+        let y = (points[1] + points[3] + points[5] + points[7]) / 4.0;
+        Some((x, y))
+    }
+
+    /// 发送按键事件
+    async fn dispatch_key(
+        &self,
+        sid: &str,
+        modifiers: i64,
+        definition: &KeyDefinition,
+    ) -> Result<(), BrowserError> {
+        // 带 Control/Alt/Meta 的组合键不输入文本
+        let text = definition
+            .text
+            .as_deref()
+            .filter(|_| modifiers & 0b0111 == 0);
+        let mut key_down = json!({
+            "type": if text.is_some() { "keyDown" } else { "rawKeyDown" },
+            "modifiers": modifiers,
+            "key": definition.key,
+            "code": definition.code,
+            "windowsVirtualKeyCode": definition.key_code,
+        });
+        if let Some(text) = text {
+            key_down["text"] = json!(text);
+            key_down["unmodifiedText"] = json!(text);
+        }
+        self.conn
+            .call("Input.dispatchKeyEvent", key_down, Some(sid))
+            .await?;
+        self.conn
+            .call(
+                "Input.dispatchKeyEvent",
+                json!({
+                    "type": "keyUp",
+                    "modifiers": modifiers,
+                    "key": definition.key,
+                    "code": definition.code,
+                    "windowsVirtualKeyCode": definition.key_code,
+                }),
+                Some(sid),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 获取标签页信息
+    async fn tab_info(&self, target_id: &str, active: bool) -> Result<TabInfo, BrowserError> {
+        let result = self
+            .conn
+            .call(
+                "Target.getTargetInfo",
+                json!({ "targetId": target_id }),
+                None,
+            )
+            .await?;
+        let info = result.get("targetInfo").cloned().unwrap_or(Value::Null);
+        Ok(TabInfo {
+            id: target_id.to_string(),
+            url: info
+                .get("url")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            title: info
+                .get("title")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            active,
+        })
+    }
+}
+
+impl Drop for BrowserSession {
+    fn drop(&mut self) {
+        self.download_task.abort();
+    }
+}
+
+/// 检查 Runtime 调用结果中的异常
+fn check_exception(result: &Value) -> Result<(), BrowserError> {
+    let Some(details) = result.get("exceptionDetails") else {
+        return Ok(());
+    };
+    let message = details
+        .pointer("/exception/description")
+        .and_then(Value::as_str)
+        .or_else(|| details.get("text").and_then(Value::as_str))
+        .unwrap_or("未知错误");
+    Err(BrowserError::Script(message.to_string()))
+}
+
+/// 跟踪下载事件
+///
+/// 下载开始时按标签页判断归属（记录进行中的下载）；完成时以下载目录中
+/// 是否存在 guid 文件判断归属，并重命名为建议文件名。
+async fn track_downloads(
+    mut events: broadcast::Receiver<CdpEvent>,
+    download_dir: PathBuf,
+    tab_ids: Arc<Mutex<HashSet<String>>>,
+    downloads: Arc<Mutex<Vec<DownloadInfo>>>,
+) {
+    // guid -> (url, 建议文件名)
+    let mut started: HashMap<String, (String, String)> = HashMap::new();
+
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let params = &event.params;
+        let guid = params
+            .get("guid")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        match event.method.as_str() {
+            "Browser.downloadWillBegin" => {
+                let url = params
+                    .get("url")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_string();
+                let filename = params
+                    .get("suggestedFilename")
+                    .and_then(Value::as_str)
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or("download")
+                    .to_string();
+                let frame_id = params.get("frameId").and_then(Value::as_str).unwrap_or("");
+                if tab_ids.lock().contains(frame_id) {
+                    downloads.lock().push(DownloadInfo {
+                        guid: guid.clone(),
+                        url: url.clone(),
+                        filename: filename.clone(),
+                        path: None,
+                        state: DownloadState::InProgress,
+                        received_bytes: 0,
+                        total_bytes: 0,
+                    });
+                }
+                started.insert(guid, (url, filename));
+            }
+            "Browser.downloadProgress" => {
+                let state = match params.get("state").and_then(Value::as_str) {
+                    Some("completed") => DownloadState::Completed,
+                    Some("canceled") => DownloadState::Canceled,
+                    _ => DownloadState::InProgress,
+                };
+                let received = params
+                    .get("receivedBytes")
+                    .and_then(Value::as_f64)
+                    .unwrap_or(0.0) as u64;
+                let total = params
+                    .get("totalBytes")
+                    .and_then(Value::as_f64)
+                    .unwrap_or(0.0) as u64;
+
+                let mut path = None;
+                if state == DownloadState::Completed {
+                    let temp_path = download_dir.join(&guid);
+                    if !temp_path.exists() {
+                        // 其他会话的下载
+                        started.remove(&guid);
+                        continue;
+                    }
+                    let filename = started
+                        .get(&guid)
+                        .map(|(_, name)| name.clone())
+                        .unwrap_or_else(|| guid.clone());
+                    let target = unique_download_path(&download_dir, &filename);
+                    match std::fs::rename(&temp_path, &target) {
+                        Ok(()) => path = Some(target),
+                        Err(e) => {
+                            warn!("[Browser] 重命名下载文件失败: {}", e);
+                            path = Some(temp_path);
+                        }
+                    }
+                }
+
+                let mut downloads = downloads.lock();
+                match downloads.iter_mut().find(|d| d.guid == guid) {
+                    Some(download) => {
+                        download.state = state;
+                        download.received_bytes = received;
+                        download.total_bytes = total;
+                        if path.is_some() {
+                            download.path = path;
+                        }
+                    }
+                    // 子框架发起的下载在完成时才能确认归属
+                    None if path.is_some() => {
+                        let (url, filename) = started.get(&guid).cloned().unwrap_or_default();
+                        downloads.push(DownloadInfo {
+                            guid: guid.clone(),
+                            url,
+                            filename,
+                            path,
+                            state,
+                            received_bytes: received,
+                            total_bytes: total,
+                        });
+                    }
+                    None => {}
+                }
+                if state != DownloadState::InProgress {
+                    started.remove(&guid);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 生成不与已有文件冲突的下载路径（`report.pdf` → `report (1).pdf`）
+fn unique_download_path(dir: &Path, filename: &str) -> PathBuf {
+    // 只保留文件名部分，防止路径穿越
+    let filename = Path::new(filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "download".to_string());
+    let candidate = dir.join(&filename);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let path = Path::new(&filename);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(candidate)
+}
+
+// ============================================================================
+// 浏览器
+// ============================================================================
+
+/// 运行中的浏览器实例
+struct BrowserInstance {
+    /// 浏览器进程
+    _process: ChromeProcess,
+    /// CDP 连接
+    conn: Arc<CdpConnection>,
+}
+
+/// CDP 浏览器
+///
+/// 管理共享的浏览器进程和各 Agent 会话的 BrowserSession。
+pub struct CdpBrowser {
+    /// 浏览器配置
+    config: BrowserConfig,
+    /// 浏览器实例（按需启动）
+    instance: tokio::sync::Mutex<Option<BrowserInstance>>,
+    /// Agent 会话 ID -> 浏览器会话
+    sessions: tokio::sync::Mutex<HashMap<String, Arc<BrowserSession>>>,
+}
+
+impl CdpBrowser {
+    /// 创建浏览器（不会立即启动进程）
+    pub fn new(config: BrowserConfig) -> Self {
+        Self {
+            config,
+            instance: tokio::sync::Mutex::new(None),
+            sessions: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 获取或创建 Agent 会话对应的浏览器会话
+    ///
+    /// # 参数
+    /// - `session_id`: Agent 会话 ID
+    /// - `download_dir`: 下载目录（首次创建会话时使用）
+    pub async fn session(
+        &self,
+        session_id: &str,
+        download_dir: &Path,
+    ) -> Result<Arc<BrowserSession>, BrowserError> {
+        let mut sessions = self.sessions.lock().await;
+        if let Some(session) = sessions.get(session_id) {
+            if session.is_alive() {
+                return Ok(session.clone());
+            }
+        }
+
+        let conn = self.connection().await?;
+        let session = Arc::new(
+            BrowserSession::create(conn, session_id, download_dir, self.config.timeout).await?,
+        );
+        sessions.insert(session_id.to_string(), session.clone());
+        Ok(session)
+    }
+
+    /// 关闭 Agent 会话对应的浏览器会话
+    ///
+    /// 最后一个会话关闭后浏览器进程退出。
+    pub async fn close_session(&self, session_id: &str) -> Result<(), BrowserError> {
+        let mut sessions = self.sessions.lock().await;
+        let Some(session) = sessions.remove(session_id) else {
+            return Ok(());
+        };
+        let result = if session.is_alive() {
+            session.close().await
+        } else {
+            Ok(())
+        };
+
+        if sessions.is_empty() {
+            self.shutdown().await;
+        }
+        result
+    }
+
+    /// 关闭浏览器进程
+    pub async fn shutdown(&self) {
+        if let Some(instance) = self.instance.lock().await.take() {
+            let _ = instance.conn.call("Browser.close", json!({}), None).await;
+            info!("[Browser] 浏览器已关闭");
+        }
+    }
+
+    /// 获取浏览器连接（未启动或已断开时启动新进程）
+    async fn connection(&self) -> Result<Arc<CdpConnection>, BrowserError> {
+        let mut instance = self.instance.lock().await;
+        if let Some(existing) = instance.as_ref() {
+            if !existing.conn.is_closed() {
+                return Ok(existing.conn.clone());
+            }
+        }
+
+        let (process, ws_url) = ChromeProcess::launch(&self.config).await?;
+        let conn = Arc::new(CdpConnection::connect(&ws_url, self.config.timeout).await?);
+        tokio::spawn(dismiss_dialogs(conn.subscribe(), Arc::downgrade(&conn)));
+        info!("[Browser] 浏览器已启动: {}", ws_url);
+
+        *instance = Some(BrowserInstance {
+            _process: process,
+            conn: conn.clone(),
+        });
+        Ok(conn)
+    }
+}
+
+/// 自动接受页面对话框（alert/confirm/prompt），避免阻塞后续操作
+async fn dismiss_dialogs(mut events: broadcast::Receiver<CdpEvent>, conn: Weak<CdpConnection>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if event.method != "Page.javascriptDialogOpening" {
+            continue;
+        }
+        let Some(conn) = conn.upgrade() else {
+            break;
+        };
+        let message = event.params.get("message").and_then(Value::as_str);
+        debug!("[Browser] 自动接受对话框: {}", message.unwrap_or(""));
+        let _ = conn
+            .call(
+                "Page.handleJavaScriptDialog",
+                json!({ "accept": true }),
+                event.session_id.as_deref(),
+            )
+            .await;
+    }
+}
+
+/// 全局浏览器实例
+static SHARED_BROWSER: once_cell::sync::Lazy<Arc<CdpBrowser>> =
+    once_cell::sync::Lazy::new(|| Arc::new(CdpBrowser::new(BrowserConfig::default())));
+
+/// 获取全局浏览器实例
+pub fn shared_browser() -> Arc<CdpBrowser> {
+    Arc::clone(&SHARED_BROWSER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// 构造可访问性节点
+    fn ax_node(
+        id: &str,
+        role: &str,
+        name: &str,
+        backend_id: Option<i64>,
+        children: &[&str],
+    ) -> Value {
+        let mut node = json!({
+            "nodeId": id,
+            "ignored": false,
+            "role": { "type": "role", "value": role },
+            "name": { "type": "computedString", "value": name },
+            "childIds": children,
+        });
+        if id != "1" {
+            node["parentId"] = json!("1");
+        }
+        if let Some(backend_id) = backend_id {
+            node["backendDOMNodeId"] = json!(backend_id);
+        }
+        node
+    }
+
+    fn sample_tree() -> Vec<Value> {
+        let mut heading = ax_node("2", "heading", "Welcome", Some(10), &["3"]);
+        heading["properties"] =
+            json!([{ "name": "level", "value": { "type": "integer", "value": 1 } }]);
+        let mut checkbox = ax_node("6", "checkbox", "Agree", Some(14), &[]);
+        checkbox["properties"] =
+            json!([{ "name": "checked", "value": { "type": "tristate", "value": "true" } }]);
+        let mut textbox = ax_node("5", "textbox", "Email", Some(13), &[]);
+        textbox["value"] = json!({ "type": "string", "value": "me@example.com" });
+
+        vec![
+            ax_node("1", "RootWebArea", "Fixture", Some(1), &["2", "4"]),
+            heading,
+            ax_node("3", "StaticText", "Welcome", None, &[]),
+            ax_node("4", "generic", "", Some(12), &["5", "6", "7", "8"]),
+            textbox,
+            checkbox,
+            ax_node("7", "button", "Submit", Some(15), &[]),
+            ax_node("8", "StaticText", "Some text", None, &[]),
+        ]
+    }
+
+    #[test]
+    fn test_render_snapshot() {
+        let mut refs = RefMap::default();
+        let snapshot = render_ax_tree(&sample_tree(), &mut refs, false);
+        assert_eq!(
+            snapshot,
+            "- heading \"Welcome\" [level=1] [ref=e1]\n\
+             - textbox \"Email\" [ref=e2]: me@example.com\n\
+             - checkbox \"Agree\" [checked=true] [ref=e3]\n\
+             - button \"Submit\" [ref=e4]\n\
+             - text: Some text"
+        );
+
+        // 再次快照时引用保持不变
+        assert_eq!(render_ax_tree(&sample_tree(), &mut refs, false), snapshot);
+        assert_eq!(refs.resolve("e4"), Some(15));
+
+        let interactive = render_ax_tree(&sample_tree(), &mut refs, true);
+        assert_eq!(
+            interactive,
+            "- textbox \"Email\" [ref=e2]: me@example.com\n\
+             - checkbox \"Agree\" [checked=true] [ref=e3]\n\
+             - button \"Submit\" [ref=e4]"
+        );
+
+        // 导航后清空映射，新元素不会复用旧编号
+        refs.clear();
+        assert_eq!(refs.resolve("e1"), None);
+        assert_eq!(refs.assign(99), "e5");
+    }
+
+    #[test]
+    fn test_parse_element_ref() {
+        assert_eq!(parse_element_ref("@e12"), Some("e12"));
+        assert_eq!(parse_element_ref("ref=e3"), Some("e3"));
+        assert_eq!(parse_element_ref("e7"), Some("e7"));
+        assert_eq!(parse_element_ref("em"), None);
+        assert_eq!(parse_element_ref("#e1"), None);
+        assert_eq!(parse_element_ref("e"), None);
+    }
+
+    #[test]
+    fn test_parse_key_combo() {
+        let (modifiers, key) = parse_key_combo("Enter").unwrap();
+        assert_eq!(modifiers, 0);
+        assert_eq!(key.key_code, 13);
+        assert_eq!(key.text.as_deref(), Some("\r"));
+
+        let (modifiers, key) = parse_key_combo("Control+Shift+a").unwrap();
+        assert_eq!(modifiers, 2 | 8);
+        assert_eq!(key.code, "KeyA");
+        assert_eq!(key.key_code, 'A' as i64);
+
+        let (modifiers, key) = parse_key_combo("Shift++").unwrap();
+        assert_eq!(modifiers, 8);
+        assert_eq!(key.key, "+");
+
+        assert_eq!(parse_key_combo("F5").unwrap().1.key_code, 116);
+        assert!(parse_key_combo("Hyper+a").is_err());
+        assert!(parse_key_combo("NotAKey").is_err());
+    }
+
+    #[test]
+    fn test_unique_download_path() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            unique_download_path(dir.path(), "report.txt"),
+            dir.path().join("report.txt")
+        );
+
+        std::fs::write(dir.path().join("report.txt"), "1").unwrap();
+        std::fs::write(dir.path().join("report (1).txt"), "2").unwrap();
+        assert_eq!(
+            unique_download_path(dir.path(), "report.txt"),
+            dir.path().join("report (2).txt")
+        );
+        assert_eq!(
+            unique_download_path(dir.path(), "../../etc/passwd"),
+            dir.path().join("passwd")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_routes_responses_and_events() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            // 处理两个请求后断开
+            for _ in 0..2 {
+                let Some(Ok(Message::Text(text))) = ws.next().await else {
+                    return;
+                };
+                let request: Value = serde_json::from_str(&text).unwrap();
+                let event = json!({
+                    "method": "Page.loadEventFired",
+                    "params": { "timestamp": 1 },
+                    "sessionId": request["sessionId"],
+                });
+                ws.send(Message::Text(event.to_string())).await.unwrap();
+
+                let reply = if request["method"] == "Fail.method" {
+                    json!({ "id": request["id"], "error": { "code": -32000, "message": "boom" } })
+                } else {
+                    json!({ "id": request["id"], "result": { "echo": request["params"] } })
+                };
+                ws.send(Message::Text(reply.to_string())).await.unwrap();
+            }
+        });
+
+        let conn = CdpConnection::connect(&format!("ws://{}", addr), Duration::from_secs(5))
+            .await
+            .unwrap();
+        let mut events = conn.subscribe();
+
+        let result = conn
+            .call("Echo.method", json!({ "x": 1 }), Some("S1"))
+            .await
+            .unwrap();
+        assert_eq!(result["echo"]["x"], 1);
+        assert!(wait_for_event(
+            &mut events,
+            "S1",
+            "Page.loadEventFired",
+            Duration::from_secs(1)
+        )
+        .await
+        .is_some());
+
+        match conn.call("Fail.method", json!({}), None).await {
+            Err(BrowserError::Protocol { method, message }) => {
+                assert_eq!(method, "Fail.method");
+                assert_eq!(message, "boom");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // 服务端断开后调用立即失败
+        assert!(matches!(
+            conn.call("Echo.method", json!({}), None).await,
+            Err(BrowserError::Connection(_))
+        ));
+    }
+
+    // ------------------------------------------------------------------------
+    // 真实浏览器测试（需要 Chrome/Chromium，使用 `cargo test -- --ignored` 运行）
+    // ------------------------------------------------------------------------
+
+    /// 静态 HTML 测试页面目录
+    fn fixture_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/browser")
+    }
+
+    /// 启动本地静态文件服务器
+    ///
+    /// `/files/` 下的文件以附件形式返回，用于触发下载。
+    async fn serve_fixtures() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_fixture(stream));
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    async fn serve_fixture(mut stream: TcpStream) {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+        }
+
+        let request = String::from_utf8_lossy(&request);
+        let path = request.split_whitespace().nth(1).unwrap_or("/");
+        let path = path.split('?').next().unwrap_or("/");
+        let path = if path == "/" { "/index.html" } else { path };
+
+        let file = fixture_dir().join(path.trim_start_matches('/'));
+        let (status, body) = match std::fs::read(&file) {
+            Ok(body) => ("200 OK", body),
+            Err(_) => ("404 Not Found", b"not found".to_vec()),
+        };
+        let content_type = match file.extension().and_then(|e| e.to_str()) {
+            Some("html") => "text/html; charset=utf-8",
+            _ => "text/plain; charset=utf-8",
+        };
+        let disposition = if path.starts_with("/files/") {
+            "Content-Disposition: attachment\r\n"
+        } else {
+            ""
+        };
+        let header = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+            status,
+            content_type,
+            body.len(),
+            disposition
+        );
+
+        let _ = stream.write_all(header.as_bytes()).await;
+        let _ = stream.write_all(&body).await;
+        let _ = stream.shutdown().await;
+    }
+
+    /// 创建测试用浏览器
+    fn test_browser() -> CdpBrowser {
+        let executable = find_browser_executable().expect("未找到 Chrome/Chromium");
+        CdpBrowser::new(BrowserConfig {
+            executable: Some(executable),
+            timeout: Duration::from_secs(15),
+            ..Default::default()
+        })
+    }
+
+    /// 从快照中找到包含指定文本的行的元素引用
+    fn find_ref(snapshot: &str, needle: &str) -> String {
+        let line = snapshot
+            .lines()
+            .find(|line| line.contains(needle))
+            .unwrap_or_else(|| panic!("快照中没有 {}:\n{}", needle, snapshot));
+        let start = line.find("[ref=").unwrap() + 5;
+        let end = start + line[start..].find(']').unwrap();
+        format!("@{}", &line[start..end])
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Chrome/Chromium"]
+    async fn test_browser_snapshot_and_interaction() {
+        let browser = test_browser();
+        let base_url = serve_fixtures().await;
+        let dir = tempfile::tempdir().unwrap();
+        let session = browser.session("snapshot", dir.path()).await.unwrap();
+
+        session
+            .navigate(&format!("{}/index.html", base_url))
+            .await
+            .unwrap();
+        let (_, title) = session.page_info().await.unwrap();
+        assert_eq!(title, "Fixture Home");
+
+        let snapshot = session.snapshot(false).await.unwrap();
+        assert!(snapshot.contains("heading \"Browser Fixture\" [level=1]"));
+        let name_ref = find_ref(&snapshot, "textbox \"Name\"");
+        let button_ref = find_ref(&snapshot, "button \"Greet\"");
+        assert_eq!(session.snapshot(false).await.unwrap(), snapshot);
+
+        session.fill(&name_ref, "Ada").await.unwrap();
+        session.click(&button_ref).await.unwrap();
+        assert_eq!(
+            session.get_text(Some("#result")).await.unwrap(),
+            "Hello, Ada"
+        );
+
+        session.type_text("#name", " L").await.unwrap();
+        assert_eq!(
+            session
+                .evaluate("document.getElementById('name').value")
+                .await
+                .unwrap(),
+            json!("Ada L")
+        );
+
+        let interactive = session.snapshot(true).await.unwrap();
+        assert!(interactive.contains("checkbox \"Agree\""));
+        assert!(!interactive.contains("heading"));
+
+        session.click("#second").await.unwrap();
+        let (url, title) = session.page_info().await.unwrap();
+        assert!(url.ends_with("/second.html"));
+        assert_eq!(title, "Second Page");
+        assert!(matches!(
+            session.click(&button_ref).await,
+            Err(BrowserError::ElementNotFound(_))
+        ));
+
+        browser.close_session("snapshot").await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Chrome/Chromium"]
+    async fn test_browser_tabs_and_cookie_isolation() {
+        let browser = test_browser();
+        let base_url = serve_fixtures().await;
+        let index = format!("{}/index.html", base_url);
+        let dir = tempfile::tempdir().unwrap();
+
+        let alpha = browser
+            .session("alpha", &dir.path().join("alpha"))
+            .await
+            .unwrap();
+        let beta = browser
+            .session("beta", &dir.path().join("beta"))
+            .await
+            .unwrap();
+
+        alpha.navigate(&index).await.unwrap();
+        alpha
+            .evaluate("document.cookie = 'token=alpha; path=/'")
+            .await
+            .unwrap();
+        beta.navigate(&index).await.unwrap();
+        assert_eq!(beta.evaluate("document.cookie").await.unwrap(), json!(""));
+
+        // 同一会话的新标签页共享 Cookie
+        let first_tab = alpha.list_tabs().await.unwrap()[0].id.clone();
+        let tab = alpha.new_tab(Some(&index)).await.unwrap();
+        assert!(tab.active);
+        assert_eq!(
+            alpha.evaluate("document.cookie").await.unwrap(),
+            json!("token=alpha")
+        );
+
+        // 页面打开的新窗口出现在标签页列表中
+        alpha.switch_tab(&first_tab).await.unwrap();
+        alpha.click("#popup").await.unwrap();
+        let mut tabs = Vec::new();
+        for _ in 0..50 {
+            tabs = alpha.list_tabs().await.unwrap();
+            if tabs.len() == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(tabs.len(), 3);
+        assert_eq!(tabs.iter().filter(|t| t.active).count(), 1);
+        assert_eq!(beta.list_tabs().await.unwrap().len(), 1);
+
+        let popup = tabs
+            .iter()
+            .find(|t| t.id != first_tab && t.id != tab.id)
+            .unwrap();
+        alpha.switch_tab(&popup.id).await.unwrap();
+        alpha.wait_for("h1", 5000).await.unwrap();
+        assert_eq!(alpha.get_text(Some("h1")).await.unwrap(), "Second");
+
+        alpha.close_tab(None).await.unwrap();
+        assert_eq!(alpha.list_tabs().await.unwrap().len(), 2);
+
+        browser.close_session("alpha").await.unwrap();
+        browser.close_session("beta").await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "需要 Chrome/Chromium"]
+    async fn test_browser_downloads_into_session_dir() {
+        let browser = test_browser();
+        let base_url = serve_fixtures().await;
+        let dir = tempfile::tempdir().unwrap();
+        let session = browser
+            .session("download", &dir.path().join("downloads"))
+            .await
+            .unwrap();
+
+        session
+            .navigate(&format!("{}/index.html", base_url))
+            .await
+            .unwrap();
+        session.click("#download").await.unwrap();
+
+        let mut completed = None;
+        for _ in 0..100 {
+            completed = session
+                .downloads()
+                .into_iter()
+                .find(|d| d.state == DownloadState::Completed);
+            if completed.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let download = completed.expect("下载未完成");
+        assert_eq!(download.filename, "report.txt");
+        let path = download.path.unwrap();
+        assert_eq!(path, session.download_dir().join("report.txt"));
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "quarterly report\n");
+
+        browser.close_session("download").await.unwrap();
+    }
+}
@@ -23,10 +23,20 @@ pub use aster::tools::ToolResult;
 pub type JsonSchema = serde_json::Value;
 pub type PropertySchema = serde_json::Value;
 
+pub mod browser_cdp;
+pub mod command_policy;
+pub mod security;
+
+pub use browser_cdp::{shared_browser, BrowserConfig, BrowserError, BrowserSession, CdpBrowser};
+pub use command_policy::{
+    reload_shared_policy, resolve_approval, shared_policy, ApprovalRequest, CommandPolicy,
+    PolicyDecision,
+};
+pub use security::{SecurityError, SecurityManager};
+
 // 保留现有的特殊工具（暂时注释掉，需要适配 aster-rust 接口）
 // pub mod browser;
 // pub mod prompt;
-// pub mod security;
 // pub mod term_scrollback;
 // pub mod terminal;
 
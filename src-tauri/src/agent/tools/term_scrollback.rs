@@ -2,6 +2,9 @@
 //!
 //! 提供只读访问终端输出历史的功能
 //! 参考 Waveterm 的 term_get_scrollback 工具设计
+//!
+//! 传入 `history_query`、`host`、`cwd` 或 `exit_status` 时改为查询命令历史
+//! （跨本地、SSH、WSL 会话，见 `terminal::persistence::command_history`）。
 
 use super::registry::Tool;
 use super::types::{JsonSchema, PropertySchema, ToolDefinition, ToolError, ToolResult};
@@ -11,13 +14,23 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
+use tauri::Manager;
 use tokio::sync::oneshot;
 use tokio::time::timeout;
 use tracing::{debug, info, warn};
 
+use crate::commands::terminal_cmd::TerminalManagerState;
+use crate::terminal::persistence::{CommandHistoryHit, CommandHistoryQuery, ExitStatusFilter};
+
 /// 默认超时时间（秒）
 const DEFAULT_TIMEOUT_SECS: u64 = 30;
 
+/// 命令历史默认返回条数
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+/// 命令历史结果中每条输出的最大字符数
+const MAX_HISTORY_OUTPUT_CHARS: usize = 500;
+
 /// 获取滚动缓冲区的请求
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetScrollbackRequest {
@@ -213,6 +226,72 @@ impl TermScrollbackTool {
             }
         }
     }
+
+    /// 查询命令历史
+    async fn search_history(
+        &self,
+        query: &CommandHistoryQuery,
+    ) -> Result<Vec<CommandHistoryHit>, ToolError> {
+        let app_handle = self.app_handle.read().clone().ok_or_else(|| {
+            ToolError::ExecutionFailed("TermScrollbackTool 未正确初始化".to_string())
+        })?;
+        let state = app_handle
+            .try_state::<TerminalManagerState>()
+            .ok_or_else(|| ToolError::ExecutionFailed("终端管理器未初始化".to_string()))?;
+
+        let guard = state.inner().0.read().await;
+        let manager = guard
+            .as_ref()
+            .ok_or_else(|| ToolError::ExecutionFailed("终端管理器未初始化".to_string()))?;
+
+        manager
+            .search_command_history(query)
+            .map_err(|e| ToolError::ExecutionFailed(e.to_string()))
+    }
+}
+
+/// 格式化命令历史结果
+fn format_history(hits: &[CommandHistoryHit]) -> String {
+    if hits.is_empty() {
+        return "No matching commands in terminal history.".to_string();
+    }
+
+    let mut output = format!("Found {} command(s) in terminal history:\n", hits.len());
+    for hit in hits {
+        let entry = &hit.entry;
+        let started = chrono::DateTime::from_timestamp_millis(entry.started_at)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        let exit = entry
+            .exit_code
+            .map(|c| c.to_string())
+            .unwrap_or_else(|| "?".to_string());
+
+        output.push_str(&format!(
+            "\n#{} [{}] {}@{} {} (exit {})\n$ {}\n",
+            entry.id,
+            started,
+            entry.connection_type,
+            entry.host,
+            entry.cwd.as_deref().unwrap_or("?"),
+            exit,
+            entry.command
+        ));
+
+        let count = entry.output.chars().count();
+        if count > MAX_HISTORY_OUTPUT_CHARS {
+            let tail: String = entry
+                .output
+                .chars()
+                .skip(count - MAX_HISTORY_OUTPUT_CHARS)
+                .collect();
+            output.push_str(&format!("...{}\n", tail));
+        } else if !entry.output.is_empty() {
+            output.push_str(&entry.output);
+            output.push('\n');
+        }
+    }
+    output
 }
 
 impl Default for TermScrollbackTool {
@@ -234,16 +313,20 @@ impl Tool for TermScrollbackTool {
              - View error messages and logs\n\
              - Understand the current state of the terminal\n\n\
              The user must manually execute commands in their terminal. You can suggest commands \
-             for the user to run, but you cannot execute them directly with this tool.",
+             for the user to run, but you cannot execute them directly with this tool.\n\n\
+             To search the command history across all terminal sessions (local, SSH and WSL) \
+             instead, pass any of history_query, host, cwd or exit_status. Each result contains \
+             the command line, working directory, exit code and an output snippet.",
         )
         .with_parameters(
             JsonSchema::new()
                 .add_property(
                     "session_id",
                     PropertySchema::string(
-                        "The terminal session ID to read from. This is provided by the system.",
+                        "The terminal session ID to read from. This is provided by the system. \
+                         Required unless searching command history.",
                     ),
-                    true,
+                    false,
                 )
                 .add_property(
                     "line_start",
@@ -259,11 +342,88 @@ impl Tool for TermScrollbackTool {
                     )
                     .with_default(serde_json::json!(200)),
                     false,
+                )
+                .add_property(
+                    "history_query",
+                    PropertySchema::string(
+                        "Optional full-text search over past command lines and their output.",
+                    ),
+                    false,
+                )
+                .add_property(
+                    "host",
+                    PropertySchema::string(
+                        "Optional host filter for command history (local hostname, SSH host or WSL distribution).",
+                    ),
+                    false,
+                )
+                .add_property(
+                    "cwd",
+                    PropertySchema::string(
+                        "Optional working directory filter for command history (includes subdirectories).",
+                    ),
+                    false,
+                )
+                .add_property(
+                    "exit_status",
+                    PropertySchema::string(
+                        "Optional exit status filter for command history: \"success\" or \"failure\".",
+                    ),
+                    false,
+                )
+                .add_property(
+                    "limit",
+                    PropertySchema::integer(
+                        "Optional maximum number of history entries to return. Defaults to 20.",
+                    )
+                    .with_default(serde_json::json!(DEFAULT_HISTORY_LIMIT)),
+                    false,
                 ),
         )
     }
 
     async fn execute(&self, args: serde_json::Value) -> Result<ToolResult, ToolError> {
+        let arg_str = |key: &str| {
+            args.get(key)
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(String::from)
+        };
+
+        // 命令历史查询
+        let history_query = arg_str("history_query");
+        let host = arg_str("host");
+        let cwd = arg_str("cwd");
+        let exit_status = match arg_str("exit_status").as_deref() {
+            None => None,
+            Some("success") => Some(ExitStatusFilter::Success),
+            Some("failure") => Some(ExitStatusFilter::Failure),
+            Some(other) => {
+                return Err(ToolError::InvalidArguments(format!(
+                    "exit_status 只能是 success 或 failure: {}",
+                    other
+                )))
+            }
+        };
+        if history_query.is_some() || host.is_some() || cwd.is_some() || exit_status.is_some() {
+            let query = CommandHistoryQuery {
+                text: history_query,
+                host,
+                cwd,
+                exit_status,
+                limit: Some(
+                    args.get("limit")
+                        .and_then(|v| v.as_u64())
+                        .map(|v| v as usize)
+                        .unwrap_or(DEFAULT_HISTORY_LIMIT),
+                ),
+                ..Default::default()
+            };
+            let hits = self.search_history(&query).await?;
+            return Ok(ToolResult::success(format_history(&hits)));
+        }
+
         // 解析参数
         let session_id = args
             .get("session_id")
@@ -9,8 +9,13 @@
 //! 3. 前端显示审批 UI
 //! 4. 用户批准后，命令发送到实际终端
 //! 5. 终端执行结果返回给 AI
+//!
+//! 发送前先经过 `CommandPolicy` 判定：危险命令和 deny 规则直接拒绝，
+//! allow 规则与会话授权命中的命令标记为 `auto_approved`，前端无需审批直接执行。
 
+use super::command_policy::{CommandPolicy, PolicyDecision};
 use super::registry::Tool;
+use super::security::SecurityManager;
 use super::types::{JsonSchema, PropertySchema, ToolDefinition, ToolError, ToolResult};
 use async_trait::async_trait;
 use parking_lot::RwLock;
@@ -22,6 +27,8 @@ use tokio::sync::oneshot;
 use tokio::time::timeout;
 use tracing::{debug, info, warn};
 
+use crate::config::CommandPolicyConfig;
+
 /// 默认超时时间（秒）
 const DEFAULT_TIMEOUT_SECS: u64 = 120;
 
@@ -36,6 +43,12 @@ pub struct TerminalCommandRequest {
     pub working_dir: Option<String>,
     /// 超时时间（秒）
     pub timeout_secs: u64,
+    /// 是否已由审批策略自动批准
+    #[serde(default)]
+    pub auto_approved: bool,
+    /// 审批策略的判定原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy_reason: Option<String>,
 }
 
 /// 命令执行响应
@@ -53,6 +66,9 @@ pub struct TerminalCommandResponse {
     pub exit_code: Option<i32>,
     /// 是否被用户拒绝
     pub rejected: bool,
+    /// 用户选择在当前会话中始终允许该命令
+    #[serde(default)]
+    pub always_allow: bool,
 }
 
 /// 命令执行状态
@@ -74,6 +90,10 @@ pub enum CommandStatus {
 pub(crate) struct PendingCommand {
     /// 响应发送器
     response_tx: oneshot::Sender<TerminalCommandResponse>,
+    /// 命令内容（用于会话授权）
+    command: String,
+    /// 发起命令的会话
+    session_id: Option<String>,
 }
 
 /// 已执行命令的记录
@@ -102,6 +122,12 @@ pub struct TerminalTool {
     timeout_secs: u64,
     /// Tauri AppHandle（用于发送事件）
     app_handle: Arc<RwLock<Option<tauri::AppHandle>>>,
+    /// 命令审批策略
+    policy: Arc<RwLock<Arc<CommandPolicy>>>,
+    /// 工作目录校验（自动执行的命令必须在基础目录内）
+    security: Option<SecurityManager>,
+    /// 当前 Agent 会话 ID（会话授权的作用域）
+    session_id: Arc<RwLock<Option<String>>>,
 }
 
 impl TerminalTool {
@@ -112,6 +138,36 @@ impl TerminalTool {
             executed_commands: Arc::new(RwLock::new(Vec::new())),
             timeout_secs: DEFAULT_TIMEOUT_SECS,
             app_handle: Arc::new(RwLock::new(None)),
+            policy: Arc::new(RwLock::new(Arc::new(CommandPolicy::default()))),
+            security: None,
+            session_id: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// 设置工作目录校验
+    pub fn with_security_manager(mut self, security: SecurityManager) -> Self {
+        self.security = Some(security);
+        self
+    }
+
+    /// 更新命令审批策略（会话授权随之清空）
+    pub fn set_policy(&self, config: &CommandPolicyConfig) {
+        *self.policy.write() = Arc::new(CommandPolicy::from_config(config));
+    }
+
+    /// 设置当前 Agent 会话
+    pub fn set_session_id(&self, session_id: Option<String>) {
+        *self.session_id.write() = session_id;
+    }
+
+    /// 判定命令，自动执行的命令还需通过工作目录校验
+    fn evaluate_policy(&self, command: &str, working_dir: Option<&str>) -> PolicyDecision {
+        let session_id = self.session_id.read().clone();
+        let policy = self.policy.read().clone();
+        let decision = policy.evaluate(session_id.as_deref(), command, working_dir);
+        match &self.security {
+            Some(security) => decision.require_working_dir(security, working_dir),
+            None => decision,
         }
     }
 
@@ -145,6 +201,13 @@ impl TerminalTool {
         };
 
         if let Some(pending) = pending {
+            if response.always_allow && !response.rejected {
+                if let Some(session_id) = &pending.session_id {
+                    self.policy
+                        .read()
+                        .grant_session(session_id, &pending.command);
+                }
+            }
             if pending.response_tx.send(response).is_err() {
                 warn!("[TerminalTool] 发送响应失败，接收端已关闭: {}", request_id);
             }
@@ -200,6 +263,7 @@ impl TerminalTool {
         command: &str,
         working_dir: Option<&str>,
         timeout_secs: u64,
+        decision: &PolicyDecision,
     ) -> Result<TerminalCommandResponse, ToolError> {
         let request_id = uuid::Uuid::new_v4().to_string();
 
@@ -214,7 +278,14 @@ impl TerminalTool {
         // 添加到待处理列表
         {
             let mut commands = self.pending_commands.write();
-            commands.insert(request_id.clone(), PendingCommand { response_tx });
+            commands.insert(
+                request_id.clone(),
+                PendingCommand {
+                    response_tx,
+                    command: command.to_string(),
+                    session_id: self.session_id.read().clone(),
+                },
+            );
         }
 
         // 构建请求
@@ -223,6 +294,8 @@ impl TerminalTool {
             command: command.to_string(),
             working_dir: working_dir.map(|s| s.to_string()),
             timeout_secs,
+            auto_approved: decision.is_allowed(),
+            policy_reason: Some(decision.reason.clone()),
         };
 
         // 发送事件到前端
@@ -250,6 +323,7 @@ impl TerminalTool {
                         error: Some(format!("无法发送命令到终端：{}。请检查应用配置。", e)),
                         exit_code: Some(-1),
                         rejected: false,
+                        always_allow: false,
                     });
                 }
                 debug!("[TerminalTool] 已发送命令请求到前端: {}", request_id);
@@ -272,6 +346,7 @@ impl TerminalTool {
                     ),
                     exit_code: Some(-1),
                     rejected: false,
+                    always_allow: false,
                 });
             }
         }
@@ -376,9 +451,21 @@ impl Tool for TerminalTool {
             )));
         }
 
+        // 审批策略
+        let decision = self.evaluate_policy(command, working_dir);
+        if decision.is_denied() {
+            return Ok(ToolResult::failure_with_output(
+                format!(
+                    "[COMMAND BLOCKED BY POLICY]\n{}\n\nCommand: {}",
+                    decision.reason, command
+                ),
+                "命令被审批策略拒绝".to_string(),
+            ));
+        }
+
         // 执行命令
         let response = self
-            .execute_command(command, working_dir, timeout_secs)
+            .execute_command(command, working_dir, timeout_secs, &decision)
             .await?;
 
         // 记录已执行的命令
@@ -409,7 +496,12 @@ impl Tool for TerminalTool {
 /// 全局 TerminalTool 实例
 static TERMINAL_TOOL: once_cell::sync::Lazy<Arc<TerminalTool>> = once_cell::sync::Lazy::new(|| {
     eprintln!("[TerminalTool] 创建全局实例");
-    Arc::new(TerminalTool::new())
+    let tool = TerminalTool::new();
+    let tool = match dirs::home_dir() {
+        Some(home) => tool.with_security_manager(SecurityManager::new(home)),
+        None => tool,
+    };
+    Arc::new(tool)
 });
 
 /// 获取全局 TerminalTool 实例
@@ -439,6 +531,16 @@ pub fn set_terminal_tool_app_handle(handle: tauri::AppHandle) {
     }
 }
 
+/// 更新全局 TerminalTool 的命令审批策略
+pub fn set_terminal_tool_policy(config: &CommandPolicyConfig) {
+    TERMINAL_TOOL.set_policy(config);
+}
+
+/// 设置全局 TerminalTool 的当前 Agent 会话
+pub fn set_terminal_tool_session(session_id: Option<String>) {
+    TERMINAL_TOOL.set_session_id(session_id);
+}
+
 /// 处理终端命令响应（由 Tauri 命令调用）
 pub fn handle_terminal_command_response(response: TerminalCommandResponse) {
     TERMINAL_TOOL.handle_response(response);
@@ -465,6 +567,8 @@ mod tests {
             command: "echo hello".to_string(),
             working_dir: Some("/home/user".to_string()),
             timeout_secs: 60,
+            auto_approved: true,
+            policy_reason: None,
         };
 
         let json = serde_json::to_string(&request).unwrap();
@@ -474,6 +578,7 @@ mod tests {
         assert_eq!(parsed.command, "echo hello");
         assert_eq!(parsed.working_dir, Some("/home/user".to_string()));
         assert_eq!(parsed.timeout_secs, 60);
+        assert!(parsed.auto_approved);
     }
 
     #[test]
@@ -485,6 +590,7 @@ mod tests {
             error: None,
             exit_code: Some(0),
             rejected: false,
+            always_allow: false,
         };
 
         let json = serde_json::to_string(&response).unwrap();
@@ -496,4 +602,33 @@ mod tests {
         assert_eq!(parsed.exit_code, Some(0));
         assert!(!parsed.rejected);
     }
+
+    #[test]
+    fn test_policy_decision_for_tool() {
+        let tool = TerminalTool::new();
+        assert!(tool.evaluate_policy("rm -rf /", None).is_denied());
+
+        tool.set_policy(&CommandPolicyConfig {
+            rules: vec![crate::config::CommandPolicyRule {
+                name: "ls".to_string(),
+                action: crate::config::CommandPolicyAction::Allow,
+                prefix: Some("ls".to_string()),
+                regex: None,
+                argv: None,
+                working_dir: None,
+            }],
+            ..CommandPolicyConfig::default()
+        });
+        assert!(tool.evaluate_policy("ls -la", None).is_allowed());
+
+        // 工作目录超出基础目录时改为人工审批
+        let base = tempfile::tempdir().unwrap();
+        let tool = tool.with_security_manager(SecurityManager::new(base.path()));
+        assert!(tool
+            .evaluate_policy("ls", Some(base.path().to_str().unwrap()))
+            .is_allowed());
+        assert!(!tool.evaluate_policy("ls", Some("/")).is_allowed());
+        // 未指定工作目录时无法校验，同样需要审批
+        assert!(!tool.evaluate_policy("ls", None).is_allowed());
+    }
 }
@@ -3,6 +3,7 @@
 //! 定义 Agent 模块使用的核心类型
 //! 参考 aster 项目的 Conversation 设计，支持连续对话和工具调用
 
+use crate::agent::event_converter::TauriAgentEvent;
 use serde::{Deserialize, Serialize};
 
 /// Provider 类型枚举
@@ -136,6 +137,87 @@ pub struct AgentSession {
     pub updated_at: String,
 }
 
+/// 消息树节点（持久化的消息及其父消息）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentMessageNode {
+    /// 消息 ID
+    pub id: i64,
+    /// 父消息 ID（根消息为 None）
+    pub parent_id: Option<i64>,
+    /// 消息内容
+    #[serde(flatten)]
+    pub message: AgentMessage,
+}
+
+/// 会话分支（以叶子消息标识）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentBranch {
+    /// 分支末端消息 ID
+    pub leaf_id: i64,
+    /// 分支上的消息数量
+    pub message_count: usize,
+    /// 末端消息预览
+    pub preview: String,
+    /// 末端消息时间
+    pub updated_at: String,
+    /// 是否为当前激活分支
+    pub is_active: bool,
+}
+
+/// 会话检查点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentCheckpoint {
+    /// 检查点 ID
+    pub id: String,
+    /// 会话 ID
+    pub session_id: String,
+    /// 创建时激活分支的末端消息（空会话为 None）
+    pub message_id: Option<i64>,
+    /// 标签
+    pub label: Option<String>,
+    /// 快照中的文件数量
+    pub file_count: u32,
+    /// 创建时间
+    pub created_at: String,
+}
+
+/// 上下文压缩记录
+///
+/// 会话恢复时用摘要替换 `message_id` 及之前的消息（置顶消息和最近的
+/// `kept_messages` 条消息除外）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentCompaction {
+    /// 记录 ID
+    pub id: String,
+    /// 会话 ID
+    pub session_id: String,
+    /// 压缩时激活分支的末端消息
+    pub message_id: Option<i64>,
+    /// 摘要内容
+    pub summary: String,
+    /// 被摘要的消息数量
+    pub summarized_messages: u32,
+    /// 原样保留的最近消息数量
+    pub kept_messages: u32,
+    /// 压缩前估算的 token 数
+    pub tokens_before: u32,
+    /// 压缩后估算的 token 数
+    pub tokens_after: u32,
+    /// 创建时间
+    pub created_at: String,
+}
+
+/// 被截断的工具输出（通过句柄取回完整内容）
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompactedToolOutput {
+    /// 检索句柄
+    pub handle: String,
+    /// 工具调用 ID
+    pub tool_call_id: Option<String>,
+    /// 完整输出
+    pub content: String,
+}
+
 /// Agent 消息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentMessage {
@@ -155,6 +237,9 @@ pub struct AgentMessage {
     /// DeepSeek Reasoner 在 Tool Calls 场景下要求此字段
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reasoning_content: Option<String>,
+    /// 是否置顶（上下文压缩时原样保留）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
 }
 
 /// 消息内容类型
@@ -375,7 +460,7 @@ pub struct NativeChatResponse {
 ///
 /// 记录 API 调用的 token 消耗
 /// Requirements: 1.3 - THE Streaming_Handler SHALL emit a done event with token usage statistics
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct TokenUsage {
     /// 输入 token 数
     pub input_tokens: u32,
@@ -396,6 +481,12 @@ impl TokenUsage {
     pub fn total(&self) -> u32 {
         self.input_tokens + self.output_tokens
     }
+
+    /// 累加另一次调用的用量
+    pub fn accumulate(&mut self, other: &TokenUsage) {
+        self.input_tokens = self.input_tokens.saturating_add(other.input_tokens);
+        self.output_tokens = self.output_tokens.saturating_add(other.output_tokens);
+    }
 }
 
 /// 流式响应事件
@@ -477,6 +568,40 @@ pub enum StreamEvent {
     /// Requirements: 1.4 - IF a streaming error occurs, THEN THE Streaming_Handler SHALL emit an error event
     #[serde(rename = "error")]
     Error { message: String },
+
+    /// 上下文已压缩
+    /// 会话接近模型上下文上限时，较早的消息被摘要、工具输出被截断
+    #[serde(rename = "context_compacted")]
+    ContextCompacted {
+        /// 摘要内容（仅截断工具输出时为 None）
+        #[serde(skip_serializing_if = "Option::is_none")]
+        summary: Option<String>,
+        /// 被摘要的消息数量
+        summarized_messages: usize,
+        /// 原样保留的最近消息数量
+        kept_messages: usize,
+        /// 压缩前估算的 token 数
+        tokens_before: u32,
+        /// 压缩后估算的 token 数
+        tokens_after: u32,
+        /// 被截断的工具输出（仅用于后端持久化，不发送给前端）
+        #[serde(skip)]
+        truncated_outputs: Vec<CompactedToolOutput>,
+    },
+
+    /// 子 Agent 事件
+    /// task 工具启动的子 Agent 的进度，嵌套事件为子 Agent 自身的事件
+    #[serde(rename = "sub_agent")]
+    SubAgent {
+        /// 启动子 Agent 的 task 工具调用 ID
+        parent_tool_id: String,
+        /// 子 Agent 会话 ID
+        agent_id: String,
+        /// 子任务描述
+        description: String,
+        /// 子 Agent 事件
+        event: Box<TauriAgentEvent>,
+    },
 }
 
 /// 工具执行结果（用于 StreamEvent）
@@ -491,6 +616,12 @@ pub struct ToolExecutionResult {
     /// 错误信息（如果失败）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// 开始执行时间（RFC 3339）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    /// 执行耗时（毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
 }
 
 impl ToolExecutionResult {
@@ -500,6 +631,8 @@ impl ToolExecutionResult {
             success: true,
             output: output.into(),
             error: None,
+            started_at: None,
+            duration_ms: None,
         }
     }
 
@@ -510,6 +643,8 @@ impl ToolExecutionResult {
             success: false,
             output: String::new(),
             error: Some(error_msg),
+            started_at: None,
+            duration_ms: None,
         }
     }
 
@@ -519,6 +654,8 @@ impl ToolExecutionResult {
             success: false,
             output: output.into(),
             error: Some(error.into()),
+            started_at: None,
+            duration_ms: None,
         }
     }
 }
@@ -24,6 +24,7 @@ use crate::commands::provider_pool_cmd::{CredentialSyncServiceState, ProviderPoo
 use crate::commands::resilience_cmd::ResilienceConfigState;
 use crate::commands::session_files_cmd::SessionFilesState;
 use crate::commands::skill_cmd::SkillServiceState;
+use crate::commands::ssh_tunnel_cmd::SSHTunnelState;
 use crate::commands::terminal_cmd::TerminalManagerState;
 use crate::commands::tool_hooks::ToolHooksServiceState;
 use crate::commands::webview_cmd::{WebviewManagerState, WebviewManagerWrapper};
@@ -146,6 +147,7 @@ pub struct AppStates {
     pub model_registry: ModelRegistryState,
     pub global_config_manager: GlobalConfigManagerState,
     pub terminal_manager: TerminalManagerState,
+    pub ssh_tunnel: SSHTunnelState,
     pub webview_manager: WebviewManagerWrapper,
     pub update_check_service: UpdateCheckServiceState,
     pub session_files: SessionFilesState,
@@ -174,8 +176,16 @@ pub fn init_states(config: &Config) -> Result<AppStates, String> {
     let skill_service_state = SkillServiceState(Arc::new(skill_service));
 
     let provider_pool_service = ProviderPoolService::new();
+    provider_pool_service
+        .scheduler()
+        .set_config(config.concurrency.clone());
+    provider_pool_service
+        .scheduler()
+        .register_cooldown_source("risk", crate::credential::init_global_unified_manager());
     let provider_pool_service_state = ProviderPoolServiceState(Arc::new(provider_pool_service));
 
+    crate::agent::tools::reload_shared_policy(&config.agent.command_policy);
+
     let api_key_provider_service = ApiKeyProviderService::new();
     let api_key_provider_service_state =
         ApiKeyProviderServiceState(Arc::new(api_key_provider_service));
@@ -204,6 +214,13 @@ pub fn init_states(config: &Config) -> Result<AppStates, String> {
     // 遥测系统
     let (telemetry_state, shared_stats, shared_tokens, shared_logger) = init_telemetry(config)?;
 
+    // 自适应编排策略使用实时遥测和风控冷却状态
+    let orchestrator = crate::orchestrator::init_global_orchestrator();
+    orchestrator.adaptive().attach_stats(shared_stats.clone());
+    orchestrator
+        .adaptive()
+        .register_cooldown_source(crate::credential::init_global_unified_manager());
+
     // Flow Monitor 系统（根据插件安装状态启用/禁用）
     let (
         flow_monitor_state,
@@ -235,6 +252,9 @@ pub fn init_states(config: &Config) -> Result<AppStates, String> {
     // 初始化终端管理器状态（延迟初始化，在 setup hook 中完成）
     let terminal_manager_state = TerminalManagerState(Arc::new(RwLock::new(None)));
 
+    // 初始化 SSH 端口转发连接状态
+    let ssh_tunnel_state = SSHTunnelState::default();
+
     // 初始化 Webview 管理器状态
     let webview_manager_state =
         WebviewManagerWrapper(Arc::new(RwLock::new(WebviewManagerState::new())));
@@ -303,6 +323,7 @@ pub fn init_states(config: &Config) -> Result<AppStates, String> {
         model_registry: model_registry_state,
         global_config_manager: global_config_manager_state,
         terminal_manager: terminal_manager_state,
+        ssh_tunnel: ssh_tunnel_state,
         webview_manager: webview_manager_state,
         update_check_service: update_check_service_state,
         session_files: session_files_state,
@@ -316,6 +337,68 @@ pub fn init_states(config: &Config) -> Result<AppStates, String> {
     })
 }
 
+/// 加载启动时的凭证
+///
+/// 记录凭证池概览，并兼容加载旧版 Kiro 凭证。桌面端与无界面模式共用。
+pub async fn load_startup_credentials(
+    state: &AppState,
+    logs: &LogState,
+    pool_service: &ProviderPoolService,
+    db: &DbConnection,
+) {
+    logs.write().await.add("info", "[启动] 正在加载凭证池...");
+
+    // 获取凭证池概览信息
+    match pool_service.get_overview(db) {
+        Ok(overview) => {
+            let mut loaded_types = Vec::new();
+            let mut total_credentials = 0;
+
+            for provider_overview in overview {
+                let count = provider_overview.stats.total_count;
+                if count > 0 {
+                    total_credentials += count;
+                    let provider_name = match provider_overview.provider_type.as_str() {
+                        "kiro" => "Kiro",
+                        "gemini" => "Gemini",
+                        "antigravity" => "Antigravity",
+                        "openai" => "OpenAI",
+                        "claude" => "Claude",
+                        "codex" => "Codex",
+                        "claude_oauth" => "Claude OAuth",
+                        _ => &provider_overview.provider_type,
+                    };
+                    loaded_types.push(format!("{} ({} 个)", provider_name, count));
+                }
+            }
+
+            if loaded_types.is_empty() {
+                logs.write().await.add("warn", "[启动] 未找到任何可用凭证");
+            } else {
+                let message = format!(
+                    "[启动] 凭证已加载: {} (共 {} 个)",
+                    loaded_types.join(", "),
+                    total_credentials
+                );
+                logs.write().await.add("info", &message);
+            }
+        }
+        Err(e) => {
+            logs.write()
+                .await
+                .add("warn", &format!("[启动] 获取凭证池信息失败: {}", e));
+        }
+    }
+
+    // 兼容性：仍然尝试加载旧的 Kiro 凭证（如果存在）
+    let mut s = state.write().await;
+    if let Err(e) = s.kiro_provider.load_credentials().await {
+        logs.write()
+            .await
+            .add("debug", &format!("[启动] 旧版 Kiro 凭证加载失败: {e}"));
+    }
+}
+
 /// 初始化插件安装器
 fn init_plugin_installer() -> Result<PluginInstallerState, String> {
     let db_path = database::get_db_path().map_err(|e| format!("获取数据库路径失败: {}", e))?;
@@ -140,6 +140,7 @@ pub async fn check_api_compatibility(
                     }]),
                     tool_choice: None,
                     reasoning_effort: None,
+                    ..Default::default()
                 }
             }
             _ => {
@@ -162,6 +163,7 @@ pub async fn check_api_compatibility(
                     tools: None,
                     tool_choice: None,
                     reasoning_effort: None,
+                    ..Default::default()
                 }
             }
         };
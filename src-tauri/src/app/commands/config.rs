@@ -48,6 +48,7 @@ pub async fn save_config(
 
     let mut s = state.write().await;
     s.config = config.clone();
+    crate::agent::tools::reload_shared_policy(&config.agent.command_policy);
 
     match config::save_config(&config) {
         Ok(()) => {
@@ -0,0 +1,326 @@
+//! 无界面运行模块
+//!
+//! 不创建 Tauri 窗口和托盘，仅启动配置、数据库、凭证池、RequestProcessor、
+//! Flow Monitor 与 axum 服务器，适用于 Linux 服务器和容器部署。
+//!
+//! 原本推送给前端的事件改为写入日志；收到 SIGTERM / Ctrl+C 后停止接收新连接，
+//! 并等待进行中的请求处理完毕再退出。
+//!
+//! 带 `--mcp-stdio` 启动时同时通过标准输入输出提供内置 MCP 服务器，
+//! 日志改写到标准错误，标准输入关闭后按收到退出信号的流程关闭。
+
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::flow_monitor::FlowEvent;
+use crate::mcp_server::{self, McpScope, ProxyCastMcpServer};
+
+use super::bootstrap::{self, AppStates};
+
+/// 桌面可执行文件切换到无界面模式的命令行参数
+pub const HEADLESS_FLAG: &str = "--headless";
+
+/// 通过 stdio 提供内置 MCP 服务器的命令行参数
+pub const MCP_STDIO_FLAG: &str = "--mcp-stdio";
+
+/// 日志级别环境变量（trace / debug / info / warn / error）
+const LOG_LEVEL_ENV: &str = "PROXYCAST_LOG";
+
+/// 优雅关闭的最长等待时间
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// 命令行参数中是否请求了无界面模式
+pub fn headless_requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == HEADLESS_FLAG)
+}
+
+/// 以无界面模式运行 ProxyCast 服务器
+///
+/// 阻塞直到收到退出信号或服务器异常退出。
+pub fn run_headless() -> ExitCode {
+    let mcp_stdio = std::env::args().skip(1).any(|arg| arg == MCP_STDIO_FLAG);
+    init_tracing(mcp_stdio);
+
+    let config = match bootstrap::load_and_validate_config() {
+        Ok(cfg) => cfg,
+        Err(err) => {
+            tracing::error!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mcp_scope = mcp_stdio.then(|| {
+        McpScope::from_management_key(
+            &config.remote_management,
+            std::env::var(mcp_server::MANAGEMENT_KEY_ENV)
+                .ok()
+                .as_deref(),
+        )
+    });
+
+    // init_states 内部会使用 blocking_read，必须在 tokio 运行时之外调用
+    let states = match bootstrap::init_states(&config) {
+        Ok(s) => s,
+        Err(err) => {
+            tracing::error!("应用状态初始化失败: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) => {
+            tracing::error!("创建 tokio 运行时失败: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let exit_code = runtime.block_on(serve(states, mcp_scope));
+    if mcp_scope.is_some() {
+        // 读取标准输入的阻塞线程不会自行结束，不等待它退出
+        runtime.shutdown_timeout(Duration::from_secs(1));
+    }
+    exit_code
+}
+
+/// 初始化 tracing 输出（无界面模式下日志是唯一的观测手段）
+///
+/// stdio MCP 模式下标准输出用于 JSON-RPC 消息，日志写到标准错误。
+fn init_tracing(to_stderr: bool) {
+    let level = std::env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|v| v.parse::<tracing::Level>().ok())
+        .unwrap_or(tracing::Level::INFO);
+
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false);
+    let _ = if to_stderr {
+        builder.with_writer(std::io::stderr).try_init()
+    } else {
+        builder.try_init()
+    };
+}
+
+async fn serve(states: AppStates, mcp_scope: Option<McpScope>) -> ExitCode {
+    let AppStates {
+        state,
+        logs,
+        db,
+        provider_pool_service,
+        token_cache_service,
+        flow_query_service,
+        shared_stats,
+        shared_tokens,
+        shared_logger,
+        flow_monitor_arc,
+        flow_interceptor_arc,
+        ..
+    } = states;
+
+    // 没有前端日志面板，日志同步输出到 tracing
+    logs.write().await.set_mirror_to_tracing(true);
+
+    bootstrap::load_startup_credentials(&state, &logs, &provider_pool_service.0, &db).await;
+
+    // Flow 事件原本推送到前端，这里改为写入日志
+    let flow_events = tokio::spawn(log_flow_events(flow_monitor_arc.subscribe()));
+
+    let (server_task, mcp_stdio) = {
+        let mut s = state.write().await;
+        logs.write().await.add("info", "[启动] 正在启动服务器...");
+        if let Err(e) = s
+            .start_with_telemetry_and_flow_monitor(
+                logs.clone(),
+                provider_pool_service.0.clone(),
+                token_cache_service.0.clone(),
+                Some(db.clone()),
+                Some(shared_stats.clone()),
+                Some(shared_tokens),
+                Some(shared_logger),
+                Some(flow_monitor_arc),
+                Some(flow_interceptor_arc),
+            )
+            .await
+        {
+            logs.write()
+                .await
+                .add("error", &format!("[启动] 服务器启动失败: {e}"));
+            flow_events.abort();
+            return ExitCode::FAILURE;
+        }
+
+        let status = s.status();
+        logs.write().await.add(
+            "info",
+            &format!("[启动] 服务器已启动: {}:{}", status.host, status.port),
+        );
+        // 内置 MCP 服务器与 HTTP 服务器共享路由器、配额管理器和统计数据
+        let mcp_stdio = match (mcp_scope, &s.router_ref, &s.quota_ref) {
+            (Some(scope), Some(router), Some(quota)) => {
+                let server = Arc::new(ProxyCastMcpServer::new(
+                    Some(flow_query_service.0.clone()),
+                    provider_pool_service.0.clone(),
+                    Some(db),
+                    quota.clone(),
+                    shared_stats,
+                    s.default_provider_ref.clone(),
+                    router.clone(),
+                ));
+                tracing::info!("[启动] 内置 MCP 服务器已通过 stdio 提供 ({:?})", scope);
+                Some(tokio::spawn(mcp_server::serve_stdio(server, scope)))
+            }
+            _ => None,
+        };
+        (s.take_server_task(), mcp_stdio)
+    };
+
+    let Some(mut server_task) = server_task else {
+        flow_events.abort();
+        return ExitCode::FAILURE;
+    };
+
+    let stdio_closed = async {
+        match mcp_stdio {
+            Some(task) => {
+                if let Ok(Err(e)) = task.await {
+                    tracing::error!("[MCP] stdio 读写失败: {}", e);
+                }
+            }
+            None => std::future::pending::<()>().await,
+        }
+    };
+
+    let exit_code = tokio::select! {
+        reason = async {
+            tokio::select! {
+                signal = shutdown_signal() => format!("收到 {} 信号", signal),
+                _ = stdio_closed => "MCP 标准输入已关闭".to_string(),
+            }
+        } => {
+            tracing::info!("[关闭] {}，正在停止服务器...", reason);
+            state.write().await.stop().await;
+
+            match tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, &mut server_task).await {
+                Ok(_) => tracing::info!("[关闭] 服务器已停止"),
+                Err(_) => {
+                    tracing::warn!(
+                        "[关闭] 等待进行中的请求超时（{}s），强制退出",
+                        SHUTDOWN_GRACE_PERIOD.as_secs()
+                    );
+                    server_task.abort();
+                }
+            }
+            ExitCode::SUCCESS
+        }
+        _ = &mut server_task => {
+            // 监听失败等错误已由服务器任务记录
+            tracing::error!("[关闭] 服务器意外退出");
+            ExitCode::FAILURE
+        }
+    };
+
+    flow_events.abort();
+    exit_code
+}
+
+/// 等待退出信号，返回信号名称
+async fn shutdown_signal() -> &'static str {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("监听 Ctrl+C 信号失败: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("监听 SIGTERM 信号失败: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => "SIGINT",
+        _ = terminate => "SIGTERM",
+    }
+}
+
+/// 将 Flow Monitor 事件写入日志
+async fn log_flow_events(mut rx: tokio::sync::broadcast::Receiver<FlowEvent>) {
+    use tokio::sync::broadcast::error::RecvError;
+
+    loop {
+        match rx.recv().await {
+            Ok(event) => log_flow_event(&event),
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!("[Flow] 日志输出跟不上，跳过 {} 个事件", skipped);
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+fn log_flow_event(event: &FlowEvent) {
+    match event {
+        FlowEvent::FlowStarted { flow } => {
+            tracing::debug!(
+                "[Flow] 开始 {} provider={} model={}",
+                flow.id,
+                flow.provider,
+                flow.model
+            );
+        }
+        FlowEvent::FlowUpdated { .. } => {}
+        FlowEvent::FlowCompleted { id, summary } => {
+            let (input, output) = summary
+                .usage
+                .as_ref()
+                .map(|u| (u.input_tokens, u.output_tokens))
+                .unwrap_or_default();
+            tracing::info!(
+                "[Flow] 完成 {} provider={} model={} 耗时={}ms tokens={}/{}",
+                id,
+                summary.provider,
+                summary.model,
+                summary.duration_ms.unwrap_or_default(),
+                input,
+                output
+            );
+        }
+        FlowEvent::FlowFailed { id, error } => match error.status_code {
+            Some(code) => tracing::warn!("[Flow] 失败 {} [{}] {}", id, code, error.message),
+            None => tracing::warn!("[Flow] 失败 {} {}", id, error.message),
+        },
+        FlowEvent::ThresholdWarning { id, result } => {
+            tracing::warn!(
+                "[Flow] 超过阈值 {} 延迟={}ms tokens={}",
+                id,
+                result.actual_latency_ms,
+                result.actual_tokens
+            );
+        }
+        FlowEvent::Notification { notification } => {
+            tracing::info!(
+                "[通知] {}: {} (flow={})",
+                notification.title,
+                notification.message,
+                notification.flow_id
+            );
+        }
+        FlowEvent::RequestRateUpdate { .. } => {}
+    }
+}
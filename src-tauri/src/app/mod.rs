@@ -10,15 +10,18 @@
 //! - `utils` - 辅助函数
 //! - `bootstrap` - 应用启动引导（配置验证、状态初始化）
 //! - `runner` - 应用运行器（Tauri Builder 配置和命令注册）
+//! - `headless` - 无界面运行模式（服务器/容器部署）
 
 pub mod bootstrap;
 pub mod commands;
+pub mod headless;
 pub mod runner;
 mod setup;
 mod state;
 mod types;
 mod utils;
 
+pub use headless::{headless_requested, run_headless};
 pub use runner::run;
 pub use setup::setup_app;
 pub use state::*;
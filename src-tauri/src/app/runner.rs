@@ -78,6 +78,7 @@ pub fn run() {
         model_registry: model_registry_state,
         global_config_manager: global_config_manager_state,
         terminal_manager: terminal_manager_state,
+        ssh_tunnel: ssh_tunnel_state,
         webview_manager: webview_manager_state,
         update_check_service: update_check_service_state,
         session_files: session_files_state,
@@ -163,6 +164,7 @@ pub fn run() {
         .manage(model_registry_state)
         .manage(global_config_manager_state)
         .manage(terminal_manager_state)
+        .manage(ssh_tunnel_state)
         .manage(webview_manager_state)
         .manage(update_check_service_state)
         .manage(session_files_state)
@@ -336,7 +338,10 @@ pub fn run() {
             // 初始化终端会话管理器
             {
                 let app_handle = app.handle().clone();
-                let terminal_manager = crate::terminal::TerminalSessionManager::new(app_handle.clone());
+                let mut terminal_manager = crate::terminal::TerminalSessionManager::new(app_handle.clone());
+                if let Err(e) = terminal_manager.enable_command_history(db_clone.clone()) {
+                    tracing::warn!("[启动] 终端命令历史初始化失败: {}", e);
+                }
                 if let Some(state) = app_handle.try_state::<crate::commands::terminal_cmd::TerminalManagerState>() {
                     let mut guard = state.inner().0.blocking_write();
                     *guard = Some(terminal_manager);
@@ -449,60 +454,7 @@ pub fn run() {
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
                 // 先加载凭证池中的凭证
-                {
-                    logs.write().await.add("info", "[启动] 正在加载凭证池...");
-
-                    // 获取凭证池概览信息
-                    match pool_service.get_overview(&db) {
-                        Ok(overview) => {
-                            let mut loaded_types = Vec::new();
-                            let mut total_credentials = 0;
-
-                            for provider_overview in overview {
-                                let count = provider_overview.stats.total_count;
-                                if count > 0 {
-                                    total_credentials += count;
-                                    let provider_name =
-                                        match provider_overview.provider_type.as_str() {
-                                            "kiro" => "Kiro",
-                                            "gemini" => "Gemini",
-                                            "antigravity" => "Antigravity",
-                                            "openai" => "OpenAI",
-                                            "claude" => "Claude",
-                                            "codex" => "Codex",
-                                            "claude_oauth" => "Claude OAuth",
-                                            _ => &provider_overview.provider_type,
-                                        };
-                                    loaded_types.push(format!("{} ({} 个)", provider_name, count));
-                                }
-                            }
-
-                            if loaded_types.is_empty() {
-                                logs.write().await.add("warn", "[启动] 未找到任何可用凭证");
-                            } else {
-                                let message = format!(
-                                    "[启动] 凭证已加载: {} (共 {} 个)",
-                                    loaded_types.join(", "),
-                                    total_credentials
-                                );
-                                logs.write().await.add("info", &message);
-                            }
-                        }
-                        Err(e) => {
-                            logs.write()
-                                .await
-                                .add("warn", &format!("[启动] 获取凭证池信息失败: {}", e));
-                        }
-                    }
-
-                    // 兼容性：仍然尝试加载旧的 Kiro 凭证（如果存在）
-                    let mut s = state.write().await;
-                    if let Err(e) = s.kiro_provider.load_credentials().await {
-                        logs.write()
-                            .await
-                            .add("debug", &format!("[启动] 旧版 Kiro 凭证加载失败: {e}"));
-                    }
-                }
+                bootstrap::load_startup_credentials(&state, &logs, &pool_service, &db).await;
                 // 启动服务器（使用共享的遥测实例和 Flow Monitor）
                 let server_started;
                 let server_address;
@@ -1051,6 +1003,18 @@ pub fn run() {
             commands::agent_cmd::agent_get_session,
             commands::agent_cmd::agent_delete_session,
             commands::agent_cmd::agent_get_session_messages,
+            commands::agent_cmd::agent_get_message_tree,
+            commands::agent_cmd::agent_list_branches,
+            commands::agent_cmd::agent_switch_branch,
+            commands::agent_cmd::agent_edit_message,
+            commands::agent_cmd::agent_fork_session,
+            commands::agent_cmd::agent_create_checkpoint,
+            commands::agent_cmd::agent_list_checkpoints,
+            commands::agent_cmd::agent_rewind_to_checkpoint,
+            commands::agent_cmd::agent_delete_checkpoint,
+            commands::agent_cmd::agent_pin_message,
+            commands::agent_cmd::agent_list_compactions,
+            commands::agent_cmd::agent_get_tool_output,
             // TODO: 重新启用这些命令，适配 aster-rust 工具系统
             // commands::agent_cmd::agent_terminal_command_response,
             // commands::agent_cmd::agent_term_scrollback_response,
@@ -1141,6 +1105,8 @@ pub fn run() {
             commands::orchestrator_cmd::quick_select_model,
             commands::orchestrator_cmd::select_model_for_task,
             commands::orchestrator_cmd::list_strategies,
+            commands::orchestrator_cmd::get_adaptive_decisions,
+            commands::orchestrator_cmd::explain_adaptive_selection,
             commands::orchestrator_cmd::list_service_tiers,
             commands::orchestrator_cmd::list_task_hints,
             // Connect commands
@@ -1180,6 +1146,14 @@ pub fn run() {
             commands::terminal_cmd::terminal_close,
             commands::terminal_cmd::terminal_list_sessions,
             commands::terminal_cmd::terminal_get_session,
+            commands::terminal_cmd::terminal_start_recording,
+            commands::terminal_cmd::terminal_stop_recording,
+            commands::terminal_cmd::terminal_export_recording,
+            commands::terminal_cmd::terminal_list_recording_chapters,
+            commands::terminal_cmd::terminal_replay_recording,
+            commands::terminal_cmd::terminal_search_command_history,
+            commands::terminal_cmd::terminal_rerun_command,
+            commands::terminal_cmd::terminal_delete_command_history,
             // Connection commands
             commands::connection_cmd::connection_list,
             commands::connection_cmd::connection_add,
@@ -1191,6 +1165,12 @@ pub fn run() {
             commands::connection_cmd::connection_save_raw_config,
             commands::connection_cmd::connection_test,
             commands::connection_cmd::connection_import_ssh_host,
+            // SSH tunnel commands
+            commands::ssh_tunnel_cmd::ssh_tunnel_connect,
+            commands::ssh_tunnel_cmd::ssh_tunnel_disconnect,
+            commands::ssh_tunnel_cmd::ssh_tunnel_list,
+            commands::ssh_tunnel_cmd::ssh_tunnel_add,
+            commands::ssh_tunnel_cmd::ssh_tunnel_remove,
             // Sysinfo commands
             crate::services::sysinfo_service::get_sysinfo,
             crate::services::sysinfo_service::subscribe_sysinfo,
@@ -0,0 +1,769 @@
+//! Batch API
+//!
+//! 兼容 OpenAI `/v1/files` + `/v1/batches` 与 Anthropic Message Batches：
+//! - 输入文件、任务与逐条请求持久化在 SQLite，重启后继续执行未完成的请求
+//! - 后台执行器按任务创建顺序逐个处理，单个任务内按 `concurrency` 并发
+//! - 按 `requests_per_minute` 限速；429 / 5xx 按退避重试，429 会暂停整个队列
+//! - 结束后生成 OpenAI 格式的结果 / 错误文件，Anthropic 结果按需生成
+
+pub mod models;
+pub mod multipart;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use parking_lot::Mutex;
+use rusqlite::Connection;
+use serde_json::Value;
+use thiserror::Error;
+use tokio::sync::{Notify, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::config::BatchConfig;
+use crate::database::dao::batch::BatchDao;
+use crate::database::DbConnection;
+
+use models::{
+    BatchApiFormat, BatchFile, BatchJob, BatchRequest, BatchStatus, RequestCounts, RequestStatus,
+};
+
+/// 没有任务时的轮询间隔（新任务会立即唤醒执行器）
+const IDLE_POLL: Duration = Duration::from_secs(30);
+/// 重试退避上限
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// 可重试的上游状态码
+const RETRYABLE_STATUS: &[u16] = &[408, 429, 500, 502, 503, 504, 529];
+
+/// Batch API 错误
+#[derive(Debug, Error)]
+pub enum BatchError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Invalid(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("Batch API 未启用")]
+    Disabled,
+    #[error("数据库错误: {0}")]
+    Database(String),
+}
+
+impl BatchError {
+    /// 对应的 HTTP 状态码
+    pub fn status_code(&self) -> u16 {
+        match self {
+            BatchError::NotFound(_) => 404,
+            BatchError::Invalid(_) => 400,
+            BatchError::Conflict(_) => 409,
+            BatchError::Disabled => 503,
+            BatchError::Database(_) => 500,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for BatchError {
+    fn from(e: rusqlite::Error) -> Self {
+        BatchError::Database(e.to_string())
+    }
+}
+
+/// 单次调用的结果
+#[derive(Debug, Clone)]
+pub struct BatchCallResult {
+    pub status: u16,
+    pub body: Value,
+    /// 上游返回的 Retry-After
+    pub retry_after: Option<Duration>,
+}
+
+/// 请求执行器
+///
+/// 由服务器层实现，经由常规的请求处理流程（路由、凭证池、协议转换）调用上游。
+#[async_trait]
+pub trait BatchExecutor: Send + Sync {
+    async fn execute(&self, url: &str, body: Value) -> BatchCallResult;
+}
+
+/// 限速状态
+#[derive(Debug)]
+struct Pacing {
+    /// 下一个可派发的时间点
+    next_slot: Instant,
+    /// 收到 429 后暂停到此时间点
+    paused_until: Option<Instant>,
+}
+
+/// 批处理队列
+pub struct BatchQueue {
+    config: BatchConfig,
+    db: Option<DbConnection>,
+    wake: Notify,
+    pacing: Mutex<Pacing>,
+    /// 执行器正在处理的任务
+    active: Mutex<Option<String>>,
+}
+
+impl BatchQueue {
+    pub fn new(config: BatchConfig, db: Option<DbConnection>) -> Self {
+        Self {
+            config,
+            db,
+            wake: Notify::new(),
+            pacing: Mutex::new(Pacing {
+                next_slot: Instant::now(),
+                paused_until: None,
+            }),
+            active: Mutex::new(None),
+        }
+    }
+
+    /// 是否可用（已启用且有数据库）
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled && self.db.is_some()
+    }
+
+    fn with_db<T>(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<T, rusqlite::Error>,
+    ) -> Result<T, BatchError> {
+        if !self.config.enabled {
+            return Err(BatchError::Disabled);
+        }
+        let db = self.db.as_ref().ok_or(BatchError::Disabled)?;
+        let conn = db.lock().map_err(|e| BatchError::Database(e.to_string()))?;
+        Ok(f(&conn)?)
+    }
+
+    // ------------------------------------------------------------------------
+    // 文件
+    // ------------------------------------------------------------------------
+
+    /// 保存上传的文件
+    pub fn upload_file(
+        &self,
+        filename: &str,
+        purpose: &str,
+        content: Vec<u8>,
+    ) -> Result<BatchFile, BatchError> {
+        if purpose != "batch" {
+            return Err(BatchError::Invalid(format!(
+                "Unsupported purpose '{}', only 'batch' is supported",
+                purpose
+            )));
+        }
+        self.store_file(filename, purpose, &content)
+    }
+
+    fn store_file(
+        &self,
+        filename: &str,
+        purpose: &str,
+        content: &[u8],
+    ) -> Result<BatchFile, BatchError> {
+        let file = BatchFile {
+            id: format!("file-{}", uuid::Uuid::new_v4().simple()),
+            purpose: purpose.to_string(),
+            filename: filename.to_string(),
+            bytes: content.len() as i64,
+            created_at: Utc::now().timestamp(),
+        };
+        self.with_db(|conn| BatchDao::insert_file(conn, &file, content))?;
+        Ok(file)
+    }
+
+    pub fn get_file(&self, id: &str) -> Result<BatchFile, BatchError> {
+        self.with_db(|conn| BatchDao::get_file(conn, id))?
+            .ok_or_else(|| BatchError::NotFound(format!("No such File object: {}", id)))
+    }
+
+    pub fn file_content(&self, id: &str) -> Result<Vec<u8>, BatchError> {
+        self.with_db(|conn| BatchDao::get_file_content(conn, id))?
+            .ok_or_else(|| BatchError::NotFound(format!("No such File object: {}", id)))
+    }
+
+    pub fn list_files(&self, purpose: Option<&str>) -> Result<Vec<BatchFile>, BatchError> {
+        self.with_db(|conn| BatchDao::list_files(conn, purpose))
+    }
+
+    pub fn delete_file(&self, id: &str) -> Result<(), BatchError> {
+        if self.with_db(|conn| BatchDao::delete_file(conn, id))? {
+            Ok(())
+        } else {
+            Err(BatchError::NotFound(format!("No such File object: {}", id)))
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // 任务
+    // ------------------------------------------------------------------------
+
+    /// 创建 OpenAI 格式的任务
+    ///
+    /// 输入文件校验失败时任务直接进入 `failed`，错误记录在 `errors` 中。
+    pub fn create_openai(
+        &self,
+        input_file_id: &str,
+        endpoint: &str,
+        completion_window: &str,
+        metadata: Option<Value>,
+    ) -> Result<(BatchJob, RequestCounts), BatchError> {
+        if !models::SUPPORTED_ENDPOINTS.contains(&endpoint) {
+            return Err(BatchError::Invalid(format!(
+                "Unsupported endpoint '{}', supported: {}",
+                endpoint,
+                models::SUPPORTED_ENDPOINTS.join(", ")
+            )));
+        }
+        let window = models::parse_completion_window(completion_window).ok_or_else(|| {
+            BatchError::Invalid(format!("Invalid completion_window '{}'", completion_window))
+        })?;
+        let file = self.get_file(input_file_id)?;
+        if file.purpose != "batch" {
+            return Err(BatchError::Invalid(format!(
+                "File {} does not have purpose 'batch'",
+                input_file_id
+            )));
+        }
+        let content = self.file_content(input_file_id)?;
+
+        let mut job = BatchJob::new(
+            BatchApiFormat::OpenAI,
+            endpoint,
+            Some(input_file_id.to_string()),
+            completion_window,
+            window,
+            metadata,
+        );
+        match models::parse_openai_input(&content, endpoint, self.config.max_requests) {
+            Ok(requests) => {
+                job.transition(BatchStatus::InProgress);
+                self.insert_job(&job, &requests)?;
+            }
+            Err(errors) => {
+                job.errors = errors;
+                job.transition(BatchStatus::Failed);
+                self.with_db(|conn| BatchDao::insert_job(conn, &job))?;
+            }
+        }
+        self.get(&job.id, BatchApiFormat::OpenAI)
+    }
+
+    /// 创建 Anthropic 格式的任务（完成时限固定 24 小时）
+    pub fn create_anthropic(
+        &self,
+        payload: &Value,
+    ) -> Result<(BatchJob, RequestCounts), BatchError> {
+        let requests = models::parse_anthropic_requests(payload, self.config.max_requests)
+            .map_err(BatchError::Invalid)?;
+        let mut job = BatchJob::new(
+            BatchApiFormat::Anthropic,
+            "/v1/messages",
+            None,
+            "24h",
+            24 * 3600,
+            None,
+        );
+        job.transition(BatchStatus::InProgress);
+        self.insert_job(&job, &requests)?;
+        self.get(&job.id, BatchApiFormat::Anthropic)
+    }
+
+    fn insert_job(
+        &self,
+        job: &BatchJob,
+        requests: &[models::BatchRequestInput],
+    ) -> Result<(), BatchError> {
+        self.with_db(|conn| {
+            BatchDao::insert_job(conn, job)?;
+            BatchDao::insert_requests(conn, &job.id, requests)
+        })?;
+        tracing::info!("[BATCH] 创建任务 {} ({} 条请求)", job.id, requests.len());
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// 获取任务及请求统计（格式不符视为不存在）
+    pub fn get(
+        &self,
+        id: &str,
+        format: BatchApiFormat,
+    ) -> Result<(BatchJob, RequestCounts), BatchError> {
+        self.with_db(|conn| {
+            let Some(job) = BatchDao::get_job(conn, id)? else {
+                return Ok(None);
+            };
+            let counts = BatchDao::request_counts(conn, id)?;
+            Ok(Some((job, counts)))
+        })?
+        .filter(|(job, _)| job.api_format == format)
+        .ok_or_else(|| BatchError::NotFound(format!("No batch found with id '{}'", id)))
+    }
+
+    /// 分页列出任务
+    pub fn list(
+        &self,
+        format: BatchApiFormat,
+        limit: usize,
+        after: Option<&str>,
+    ) -> Result<(Vec<(BatchJob, RequestCounts)>, bool), BatchError> {
+        self.with_db(|conn| {
+            let (jobs, has_more) = BatchDao::list_jobs(conn, format, limit.clamp(1, 100), after)?;
+            let jobs = jobs
+                .into_iter()
+                .map(|job| {
+                    let counts = BatchDao::request_counts(conn, &job.id)?;
+                    Ok((job, counts))
+                })
+                .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+            Ok((jobs, has_more))
+        })
+    }
+
+    /// 取消任务
+    ///
+    /// 执行中的任务进入 `cancelling`，等待进行中的请求结束后由执行器收尾；
+    /// 排队中的任务直接收尾。
+    pub fn cancel(
+        &self,
+        id: &str,
+        format: BatchApiFormat,
+    ) -> Result<(BatchJob, RequestCounts), BatchError> {
+        let (mut job, _) = self.get(id, format)?;
+        match job.status {
+            BatchStatus::InProgress | BatchStatus::Validating => {}
+            BatchStatus::Cancelling => return self.get(id, format),
+            status => {
+                return Err(BatchError::Conflict(format!(
+                    "Cannot cancel a batch with status '{}'",
+                    status.as_str()
+                )))
+            }
+        }
+
+        // 持有 active 锁，避免执行器在此期间领取该任务
+        let active = self.active.lock();
+        job.transition(BatchStatus::Cancelling);
+        self.with_db(|conn| BatchDao::update_job(conn, &job))?;
+        if active.as_deref() != Some(id) {
+            self.finalize(id, BatchStatus::Cancelled)?;
+        }
+        drop(active);
+        tracing::info!("[BATCH] 取消任务 {}", id);
+        self.get(id, format)
+    }
+
+    /// 删除已结束的 Anthropic 任务
+    pub fn delete(&self, id: &str, format: BatchApiFormat) -> Result<(), BatchError> {
+        let (job, _) = self.get(id, format)?;
+        if !job.status.is_terminal() {
+            return Err(BatchError::Conflict(format!(
+                "Batch {} is still in progress, cancel it before deleting",
+                id
+            )));
+        }
+        self.with_db(|conn| BatchDao::delete_job(conn, id))?;
+        Ok(())
+    }
+
+    /// Anthropic 结果（JSONL，按输入顺序）
+    pub fn anthropic_results(&self, id: &str) -> Result<Vec<u8>, BatchError> {
+        let (job, _) = self.get(id, BatchApiFormat::Anthropic)?;
+        if !job.status.is_terminal() {
+            return Err(BatchError::Conflict(format!(
+                "Batch {} has not ended yet",
+                id
+            )));
+        }
+        let requests = self.with_db(|conn| BatchDao::get_requests(conn, id))?;
+        let lines: Vec<Value> = requests
+            .iter()
+            .map(BatchRequest::anthropic_result_line)
+            .collect();
+        Ok(models::to_jsonl(&lines))
+    }
+
+    // ------------------------------------------------------------------------
+    // 执行
+    // ------------------------------------------------------------------------
+
+    /// 启动后台执行器
+    pub fn start(self: &Arc<Self>, executor: Arc<dyn BatchExecutor>) -> JoinHandle<()> {
+        let queue = self.clone();
+        tokio::spawn(async move {
+            match queue.with_db(BatchDao::reset_running) {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("[BATCH] 恢复 {} 条中断的请求", n),
+                Err(e) => tracing::warn!("[BATCH] 恢复中断的请求失败: {}", e),
+            }
+            loop {
+                match queue.claim_job() {
+                    Ok(Some(job)) => {
+                        let id = job.id.clone();
+                        if let Err(e) = queue.process(job, &executor).await {
+                            tracing::error!("[BATCH] 处理任务 {} 失败: {}", id, e);
+                        }
+                        *queue.active.lock() = None;
+                    }
+                    Ok(None) => {
+                        let _ = tokio::time::timeout(IDLE_POLL, queue.wake.notified()).await;
+                    }
+                    Err(e) => {
+                        tracing::error!("[BATCH] 读取任务失败: {}", e);
+                        tokio::time::sleep(IDLE_POLL).await;
+                    }
+                }
+            }
+        })
+    }
+
+    fn claim_job(&self) -> Result<Option<BatchJob>, BatchError> {
+        let mut active = self.active.lock();
+        let job = self.with_db(BatchDao::next_active_job)?;
+        *active = job.as_ref().map(|j| j.id.clone());
+        Ok(job)
+    }
+
+    async fn process(
+        self: &Arc<Self>,
+        job: BatchJob,
+        executor: &Arc<dyn BatchExecutor>,
+    ) -> Result<(), BatchError> {
+        let semaphore = Arc::new(Semaphore::new(self.config.concurrency.max(1)));
+        let mut tasks = JoinSet::new();
+
+        let outcome = loop {
+            let permit = semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore closed");
+            self.pace().await;
+
+            let status = self
+                .with_db(|conn| BatchDao::get_job(conn, &job.id))?
+                .map(|j| j.status);
+            if status != Some(BatchStatus::InProgress) {
+                break BatchStatus::Cancelled;
+            }
+            if Utc::now().timestamp() >= job.expires_at {
+                break BatchStatus::Expired;
+            }
+            let Some(request) = self.with_db(|conn| BatchDao::claim_next_request(conn, &job.id))?
+            else {
+                break BatchStatus::Completed;
+            };
+
+            let queue = self.clone();
+            let executor = executor.clone();
+            tasks.spawn(async move {
+                queue.execute(request, executor.as_ref()).await;
+                drop(permit);
+            });
+            while tasks.try_join_next().is_some() {}
+        };
+
+        while tasks.join_next().await.is_some() {}
+        self.finalize(&job.id, outcome)
+    }
+
+    /// 执行单条请求（含重试）并写回结果
+    async fn execute(&self, mut request: BatchRequest, executor: &dyn BatchExecutor) {
+        let result = loop {
+            request.attempts += 1;
+            let result = executor.execute(&request.url, request.body.clone()).await;
+            let retryable = RETRYABLE_STATUS.contains(&result.status);
+            if !retryable || request.attempts > self.config.max_retries {
+                break result;
+            }
+
+            let delay = result
+                .retry_after
+                .unwrap_or_else(|| self.backoff(request.attempts));
+            if result.status == 429 {
+                self.pause(delay);
+            }
+            tracing::debug!(
+                "[BATCH] {} 第 {} 次请求返回 {}，{:?} 后重试",
+                request.custom_id,
+                request.attempts,
+                result.status,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+        };
+
+        request.status_code = Some(result.status);
+        if (200..300).contains(&result.status) {
+            request.status = RequestStatus::Succeeded;
+        } else {
+            request.status = RequestStatus::Failed;
+            request.error = Some(format!("upstream returned HTTP {}", result.status));
+        }
+        request.response = Some(result.body);
+
+        let completed_at = Utc::now().timestamp();
+        if let Err(e) =
+            self.with_db(|conn| BatchDao::complete_request(conn, &request, completed_at))
+        {
+            tracing::error!("[BATCH] 保存请求 {} 结果失败: {}", request.custom_id, e);
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let base = Duration::from_millis(self.config.retry_backoff_ms);
+        base.saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+            .min(MAX_BACKOFF)
+    }
+
+    /// 暂停整个队列的派发
+    fn pause(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+        let mut pacing = self.pacing.lock();
+        if pacing.paused_until.is_none_or(|current| current < until) {
+            pacing.paused_until = Some(until);
+        }
+    }
+
+    /// 等待到下一个可派发的时间点
+    async fn pace(&self) {
+        let wait = {
+            let mut pacing = self.pacing.lock();
+            let now = Instant::now();
+            let mut at = now.max(pacing.next_slot);
+            if let Some(until) = pacing.paused_until {
+                at = at.max(until);
+            }
+            if self.config.requests_per_minute > 0 {
+                pacing.next_slot = at + Duration::from_secs(60) / self.config.requests_per_minute;
+            }
+            at - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// 收尾：关闭未执行的请求，生成结果文件并进入终态
+    fn finalize(&self, id: &str, outcome: BatchStatus) -> Result<(), BatchError> {
+        let Some(mut job) = self.with_db(|conn| BatchDao::get_job(conn, id))? else {
+            return Ok(());
+        };
+        match outcome {
+            BatchStatus::Cancelled => {
+                self.with_db(|conn| BatchDao::close_pending(conn, id, RequestStatus::Cancelled))?;
+            }
+            BatchStatus::Expired => {
+                self.with_db(|conn| BatchDao::close_pending(conn, id, RequestStatus::Expired))?;
+            }
+            _ => {}
+        }
+
+        if job.api_format == BatchApiFormat::OpenAI {
+            job.transition(BatchStatus::Finalizing);
+            self.with_db(|conn| BatchDao::update_job(conn, &job))?;
+
+            let requests = self.with_db(|conn| BatchDao::get_requests(conn, id))?;
+            let (succeeded, errored): (Vec<_>, Vec<_>) = requests
+                .iter()
+                .partition(|r| r.status == RequestStatus::Succeeded);
+            if !succeeded.is_empty() {
+                let lines: Vec<Value> = succeeded.iter().map(|r| r.openai_result_line()).collect();
+                let file = self.store_file(
+                    &format!("{}_output.jsonl", id),
+                    "batch_output",
+                    &models::to_jsonl(&lines),
+                )?;
+                job.output_file_id = Some(file.id);
+            }
+            if !errored.is_empty() {
+                let lines: Vec<Value> = errored.iter().map(|r| r.openai_result_line()).collect();
+                let file = self.store_file(
+                    &format!("{}_error.jsonl", id),
+                    "batch_output",
+                    &models::to_jsonl(&lines),
+                )?;
+                job.error_file_id = Some(file.id);
+            }
+        }
+
+        job.transition(outcome);
+        self.with_db(|conn| BatchDao::update_job(conn, &job))?;
+        tracing::info!("[BATCH] 任务 {} 结束: {}", id, outcome.as_str());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::schema::create_tables;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 第一次调用返回 429，之后按请求体中的 `fail` 字段返回 400 或 200
+    struct MockExecutor {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl BatchExecutor for MockExecutor {
+        async fn execute(&self, _url: &str, body: Value) -> BatchCallResult {
+            if self.calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                return BatchCallResult {
+                    status: 429,
+                    body: json!({"error": {"message": "rate limited"}}),
+                    retry_after: Some(Duration::from_millis(1)),
+                };
+            }
+            if body.get("fail").is_some() {
+                BatchCallResult {
+                    status: 400,
+                    body: json!({"error": {"message": "bad request"}}),
+                    retry_after: None,
+                }
+            } else {
+                BatchCallResult {
+                    status: 200,
+                    body: json!({"id": "chatcmpl-1", "object": "chat.completion"}),
+                    retry_after: None,
+                }
+            }
+        }
+    }
+
+    fn test_queue() -> Arc<BatchQueue> {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        let config = BatchConfig {
+            retry_backoff_ms: 1,
+            ..BatchConfig::default()
+        };
+        Arc::new(BatchQueue::new(
+            config,
+            Some(Arc::new(std::sync::Mutex::new(conn))),
+        ))
+    }
+
+    async fn wait_for_terminal(queue: &BatchQueue, id: &str, format: BatchApiFormat) -> BatchJob {
+        for _ in 0..200 {
+            let (job, _) = queue.get(id, format).unwrap();
+            if job.status.is_terminal() {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("batch {} did not finish", id);
+    }
+
+    #[tokio::test]
+    async fn test_openai_batch_runs_to_completion() {
+        let queue = test_queue();
+        let input = concat!(
+            r#"{"custom_id":"ok","method":"POST","url":"/v1/chat/completions","body":{"model":"gpt-4o"}}"#,
+            "\n",
+            r#"{"custom_id":"bad","method":"POST","url":"/v1/chat/completions","body":{"model":"gpt-4o","fail":true}}"#,
+            "\n"
+        );
+        let file = queue
+            .upload_file("input.jsonl", "batch", input.as_bytes().to_vec())
+            .unwrap();
+        let (job, counts) = queue
+            .create_openai(&file.id, "/v1/chat/completions", "24h", None)
+            .unwrap();
+        assert_eq!(job.status, BatchStatus::InProgress);
+        assert_eq!(counts.pending, 2);
+
+        let runner = queue.start(Arc::new(MockExecutor {
+            calls: AtomicUsize::new(0),
+        }));
+        let job = wait_for_terminal(&queue, &job.id, BatchApiFormat::OpenAI).await;
+        runner.abort();
+
+        assert_eq!(job.status, BatchStatus::Completed);
+        let output = queue
+            .file_content(job.output_file_id.as_ref().unwrap())
+            .unwrap();
+        let output: Value =
+            serde_json::from_slice(output.split(|b| *b == b'\n').next().unwrap()).unwrap();
+        assert_eq!(output["custom_id"], "ok");
+        assert_eq!(output["response"]["status_code"], 200);
+
+        let errors = queue
+            .file_content(job.error_file_id.as_ref().unwrap())
+            .unwrap();
+        let errors: Value =
+            serde_json::from_slice(errors.split(|b| *b == b'\n').next().unwrap()).unwrap();
+        assert_eq!(errors["custom_id"], "bad");
+        assert_eq!(errors["response"]["status_code"], 400);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_input_fails_batch() {
+        let queue = test_queue();
+        let file = queue
+            .upload_file("input.jsonl", "batch", b"not json\n".to_vec())
+            .unwrap();
+        let (job, counts) = queue
+            .create_openai(&file.id, "/v1/chat/completions", "24h", None)
+            .unwrap();
+        assert_eq!(job.status, BatchStatus::Failed);
+        assert_eq!(job.errors[0].code, "invalid_json_line");
+        assert_eq!(counts.total(), 0);
+
+        assert!(matches!(
+            queue.create_openai(&file.id, "/v1/embeddings", "24h", None),
+            Err(BatchError::Invalid(_))
+        ));
+        assert!(matches!(
+            queue.upload_file("a.jsonl", "fine-tune", Vec::new()),
+            Err(BatchError::Invalid(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_queued_anthropic_batch() {
+        let queue = test_queue();
+        let payload = json!({"requests": [
+            {"custom_id": "a", "params": {"model": "claude-sonnet-4-5", "max_tokens": 16, "messages": []}}
+        ]});
+        let (job, _) = queue.create_anthropic(&payload).unwrap();
+        assert!(matches!(
+            queue.anthropic_results(&job.id),
+            Err(BatchError::Conflict(_))
+        ));
+
+        // 执行器未启动，任务直接收尾
+        let (job, counts) = queue.cancel(&job.id, BatchApiFormat::Anthropic).unwrap();
+        assert_eq!(job.status, BatchStatus::Cancelled);
+        assert_eq!(counts.cancelled, 1);
+
+        let results = queue.anthropic_results(&job.id).unwrap();
+        let line: Value = serde_json::from_slice(results.trim_ascii_end()).unwrap();
+        assert_eq!(line["result"]["type"], "canceled");
+
+        assert!(queue.get(&job.id, BatchApiFormat::OpenAI).is_err());
+        queue.delete(&job.id, BatchApiFormat::Anthropic).unwrap();
+        assert!(matches!(
+            queue.get(&job.id, BatchApiFormat::Anthropic),
+            Err(BatchError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_disabled_queue() {
+        let queue = BatchQueue::new(BatchConfig::default(), None);
+        assert!(!queue.is_enabled());
+        assert!(matches!(queue.list_files(None), Err(BatchError::Disabled)));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let queue = BatchQueue::new(BatchConfig::default(), None);
+        assert_eq!(queue.backoff(1), Duration::from_millis(2000));
+        assert_eq!(queue.backoff(2), Duration::from_millis(4000));
+        assert_eq!(queue.backoff(10), MAX_BACKOFF);
+    }
+}
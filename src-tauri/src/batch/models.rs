@@ -0,0 +1,845 @@
+//! 批处理数据模型
+//!
+//! 任务与请求的持久化结构，以及 OpenAI / Anthropic 两种格式的输入解析与结果序列化。
+
+use std::collections::HashSet;
+
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// 支持的请求端点
+pub const SUPPORTED_ENDPOINTS: &[&str] = &["/v1/chat/completions", "/v1/messages"];
+
+/// 接口格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchApiFormat {
+    /// `/v1/batches`
+    OpenAI,
+    /// `/v1/messages/batches`
+    Anthropic,
+}
+
+impl BatchApiFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BatchApiFormat::OpenAI => "openai",
+            BatchApiFormat::Anthropic => "anthropic",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "openai" => Some(BatchApiFormat::OpenAI),
+            "anthropic" => Some(BatchApiFormat::Anthropic),
+            _ => None,
+        }
+    }
+
+    /// 任务 ID 前缀
+    pub fn id_prefix(&self) -> &'static str {
+        match self {
+            BatchApiFormat::OpenAI => "batch_",
+            BatchApiFormat::Anthropic => "msgbatch_",
+        }
+    }
+}
+
+/// 任务状态（沿用 OpenAI 命名，Anthropic 格式输出时映射为 `processing_status`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    /// 校验输入文件
+    Validating,
+    /// 输入文件校验失败
+    Failed,
+    /// 执行中
+    InProgress,
+    /// 生成结果文件
+    Finalizing,
+    /// 已完成
+    Completed,
+    /// 超过完成时限
+    Expired,
+    /// 取消中（等待进行中的请求结束）
+    Cancelling,
+    /// 已取消
+    Cancelled,
+}
+
+impl BatchStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BatchStatus::Validating => "validating",
+            BatchStatus::Failed => "failed",
+            BatchStatus::InProgress => "in_progress",
+            BatchStatus::Finalizing => "finalizing",
+            BatchStatus::Completed => "completed",
+            BatchStatus::Expired => "expired",
+            BatchStatus::Cancelling => "cancelling",
+            BatchStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "validating" => Some(BatchStatus::Validating),
+            "failed" => Some(BatchStatus::Failed),
+            "in_progress" => Some(BatchStatus::InProgress),
+            "finalizing" => Some(BatchStatus::Finalizing),
+            "completed" => Some(BatchStatus::Completed),
+            "expired" => Some(BatchStatus::Expired),
+            "cancelling" => Some(BatchStatus::Cancelling),
+            "cancelled" => Some(BatchStatus::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// 是否为终态
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            BatchStatus::Failed
+                | BatchStatus::Completed
+                | BatchStatus::Expired
+                | BatchStatus::Cancelled
+        )
+    }
+}
+
+/// 单条请求状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    Expired,
+}
+
+impl RequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestStatus::Pending => "pending",
+            RequestStatus::Running => "running",
+            RequestStatus::Succeeded => "succeeded",
+            RequestStatus::Failed => "failed",
+            RequestStatus::Cancelled => "cancelled",
+            RequestStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(RequestStatus::Pending),
+            "running" => Some(RequestStatus::Running),
+            "succeeded" => Some(RequestStatus::Succeeded),
+            "failed" => Some(RequestStatus::Failed),
+            "cancelled" => Some(RequestStatus::Cancelled),
+            "expired" => Some(RequestStatus::Expired),
+            _ => None,
+        }
+    }
+}
+
+/// 批处理文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchFile {
+    pub id: String,
+    /// `batch`（输入）或 `batch_output`（结果 / 错误文件）
+    pub purpose: String,
+    pub filename: String,
+    pub bytes: i64,
+    /// Unix 时间戳（秒）
+    pub created_at: i64,
+}
+
+impl BatchFile {
+    /// OpenAI File 对象
+    pub fn to_openai(&self) -> Value {
+        json!({
+            "id": self.id,
+            "object": "file",
+            "bytes": self.bytes,
+            "created_at": self.created_at,
+            "filename": self.filename,
+            "purpose": self.purpose,
+        })
+    }
+}
+
+/// 输入校验错误（OpenAI `errors.data` 条目）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchValidationError {
+    pub code: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param: Option<String>,
+}
+
+impl BatchValidationError {
+    fn at_line(line: usize, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.into(),
+            line: Some(line),
+            param: None,
+        }
+    }
+}
+
+/// 批处理任务
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchJob {
+    pub id: String,
+    pub api_format: BatchApiFormat,
+    pub endpoint: String,
+    pub input_file_id: Option<String>,
+    pub completion_window: String,
+    pub status: BatchStatus,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    pub errors: Vec<BatchValidationError>,
+    pub metadata: Option<Value>,
+    pub created_at: i64,
+    pub in_progress_at: Option<i64>,
+    pub expires_at: i64,
+    pub finalizing_at: Option<i64>,
+    pub completed_at: Option<i64>,
+    pub failed_at: Option<i64>,
+    pub expired_at: Option<i64>,
+    pub cancelling_at: Option<i64>,
+    pub cancelled_at: Option<i64>,
+}
+
+impl BatchJob {
+    /// 创建新任务
+    pub fn new(
+        api_format: BatchApiFormat,
+        endpoint: &str,
+        input_file_id: Option<String>,
+        completion_window: &str,
+        window_secs: i64,
+        metadata: Option<Value>,
+    ) -> Self {
+        let now = Utc::now().timestamp();
+        Self {
+            id: format!(
+                "{}{}",
+                api_format.id_prefix(),
+                uuid::Uuid::new_v4().simple()
+            ),
+            api_format,
+            endpoint: endpoint.to_string(),
+            input_file_id,
+            completion_window: completion_window.to_string(),
+            status: BatchStatus::Validating,
+            output_file_id: None,
+            error_file_id: None,
+            errors: Vec::new(),
+            metadata,
+            created_at: now,
+            in_progress_at: None,
+            expires_at: now + window_secs,
+            finalizing_at: None,
+            completed_at: None,
+            failed_at: None,
+            expired_at: None,
+            cancelling_at: None,
+            cancelled_at: None,
+        }
+    }
+
+    /// 切换状态并记录对应的时间戳
+    pub fn transition(&mut self, status: BatchStatus) {
+        let now = Some(Utc::now().timestamp());
+        match status {
+            BatchStatus::Validating => {}
+            BatchStatus::Failed => self.failed_at = now,
+            BatchStatus::InProgress => self.in_progress_at = now,
+            BatchStatus::Finalizing => self.finalizing_at = now,
+            BatchStatus::Completed => self.completed_at = now,
+            BatchStatus::Expired => self.expired_at = now,
+            BatchStatus::Cancelling => self.cancelling_at = now,
+            BatchStatus::Cancelled => self.cancelled_at = now,
+        }
+        self.status = status;
+    }
+
+    /// 结束时间（任一终态时间戳）
+    pub fn ended_at(&self) -> Option<i64> {
+        self.completed_at
+            .or(self.failed_at)
+            .or(self.expired_at)
+            .or(self.cancelled_at)
+    }
+
+    /// OpenAI Batch 对象
+    pub fn to_openai(&self, counts: &RequestCounts) -> Value {
+        let errors = if self.errors.is_empty() {
+            Value::Null
+        } else {
+            json!({ "object": "list", "data": self.errors })
+        };
+        json!({
+            "id": self.id,
+            "object": "batch",
+            "endpoint": self.endpoint,
+            "errors": errors,
+            "input_file_id": self.input_file_id,
+            "completion_window": self.completion_window,
+            "status": self.status.as_str(),
+            "output_file_id": self.output_file_id,
+            "error_file_id": self.error_file_id,
+            "created_at": self.created_at,
+            "in_progress_at": self.in_progress_at,
+            "expires_at": self.expires_at,
+            "finalizing_at": self.finalizing_at,
+            "completed_at": self.completed_at,
+            "failed_at": self.failed_at,
+            "expired_at": self.expired_at,
+            "cancelling_at": self.cancelling_at,
+            "cancelled_at": self.cancelled_at,
+            "request_counts": {
+                "total": counts.total(),
+                "completed": counts.succeeded,
+                "failed": counts.failed + counts.expired,
+            },
+            "metadata": self.metadata,
+        })
+    }
+
+    /// Anthropic Message Batch 对象
+    ///
+    /// `results_url` 为相对路径，仅在任务结束后给出。
+    pub fn to_anthropic(&self, counts: &RequestCounts) -> Value {
+        let processing_status = match self.status {
+            BatchStatus::Cancelling => "canceling",
+            status if status.is_terminal() => "ended",
+            _ => "in_progress",
+        };
+        let ended_at = self.ended_at();
+        json!({
+            "id": self.id,
+            "type": "message_batch",
+            "processing_status": processing_status,
+            "request_counts": {
+                "processing": counts.pending + counts.running,
+                "succeeded": counts.succeeded,
+                "errored": counts.failed,
+                "canceled": counts.cancelled,
+                "expired": counts.expired,
+            },
+            "ended_at": ended_at.map(rfc3339),
+            "created_at": rfc3339(self.created_at),
+            "expires_at": rfc3339(self.expires_at),
+            "archived_at": Value::Null,
+            "cancel_initiated_at": self.cancelling_at.map(rfc3339),
+            "results_url": ended_at.map(|_| format!("/v1/messages/batches/{}/results", self.id)),
+        })
+    }
+}
+
+fn rfc3339(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .unwrap_or_default()
+        .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// 各状态的请求数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestCounts {
+    pub pending: u64,
+    pub running: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub cancelled: u64,
+    pub expired: u64,
+}
+
+impl RequestCounts {
+    pub fn total(&self) -> u64 {
+        self.pending + self.running + self.succeeded + self.failed + self.cancelled + self.expired
+    }
+
+    /// 累加某个状态的数量
+    pub fn add(&mut self, status: RequestStatus, count: u64) {
+        match status {
+            RequestStatus::Pending => self.pending += count,
+            RequestStatus::Running => self.running += count,
+            RequestStatus::Succeeded => self.succeeded += count,
+            RequestStatus::Failed => self.failed += count,
+            RequestStatus::Cancelled => self.cancelled += count,
+            RequestStatus::Expired => self.expired += count,
+        }
+    }
+}
+
+/// 待入队的请求
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRequestInput {
+    pub custom_id: String,
+    pub url: String,
+    pub body: Value,
+}
+
+/// 已入队的请求
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRequest {
+    pub batch_id: String,
+    /// 在输入中的序号（从 0 开始）
+    pub line_no: i64,
+    pub custom_id: String,
+    pub url: String,
+    pub body: Value,
+    pub status: RequestStatus,
+    pub attempts: u32,
+    pub status_code: Option<u16>,
+    pub response: Option<Value>,
+    pub error: Option<String>,
+}
+
+impl BatchRequest {
+    /// OpenAI 结果 / 错误文件中的一行
+    pub fn openai_result_line(&self) -> Value {
+        let response = self.status_code.map(|status_code| {
+            let body = self.response.clone().unwrap_or(Value::Null);
+            json!({
+                "status_code": status_code,
+                "request_id": body.get("id").and_then(|v| v.as_str()).unwrap_or_default(),
+                "body": body,
+            })
+        });
+        let error = match self.status {
+            RequestStatus::Expired => Some(json!({
+                "code": "batch_expired",
+                "message": "This request could not be executed before the completion window expired.",
+            })),
+            RequestStatus::Cancelled => Some(json!({
+                "code": "batch_cancelled",
+                "message": "This request was cancelled before it was executed.",
+            })),
+            _ if response.is_none() => Some(json!({
+                "code": "request_failed",
+                "message": self.error.clone().unwrap_or_default(),
+            })),
+            _ => None,
+        };
+        json!({
+            "id": format!("batch_req_{}_{}", self.batch_id.trim_start_matches("batch_"), self.line_no),
+            "custom_id": self.custom_id,
+            "response": response,
+            "error": error,
+        })
+    }
+
+    /// Anthropic 结果文件中的一行
+    pub fn anthropic_result_line(&self) -> Value {
+        let result = match self.status {
+            RequestStatus::Succeeded => json!({
+                "type": "succeeded",
+                "message": self.response.clone().unwrap_or(Value::Null),
+            }),
+            RequestStatus::Cancelled => json!({ "type": "canceled" }),
+            RequestStatus::Expired => json!({ "type": "expired" }),
+            _ => {
+                // 上游已返回 Anthropic 错误结构时原样透传
+                let error = match &self.response {
+                    Some(body) if body.get("type").and_then(|v| v.as_str()) == Some("error") => {
+                        body.clone()
+                    }
+                    body => json!({
+                        "type": "error",
+                        "error": {
+                            "type": "api_error",
+                            "message": body
+                                .as_ref()
+                                .and_then(error_message)
+                                .or_else(|| self.error.clone())
+                                .unwrap_or_else(|| "request failed".to_string()),
+                        },
+                    }),
+                };
+                json!({ "type": "errored", "error": error })
+            }
+        };
+        json!({ "custom_id": self.custom_id, "result": result })
+    }
+}
+
+/// 从 OpenAI 风格的错误体中提取消息
+fn error_message(body: &Value) -> Option<String> {
+    body.pointer("/error/message")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+}
+
+/// 解析完成时限（如 `24h`），返回秒数
+pub fn parse_completion_window(window: &str) -> Option<i64> {
+    let hours: i64 = window.trim().strip_suffix('h')?.parse().ok()?;
+    (hours > 0).then_some(hours * 3600)
+}
+
+/// 解析 OpenAI 批处理输入文件（JSONL）
+///
+/// 每行形如 `{"custom_id": "...", "method": "POST", "url": "/v1/chat/completions", "body": {...}}`，
+/// `url` 必须与任务的 `endpoint` 一致，`custom_id` 不可重复。
+pub fn parse_openai_input(
+    content: &[u8],
+    endpoint: &str,
+    max_requests: usize,
+) -> Result<Vec<BatchRequestInput>, Vec<BatchValidationError>> {
+    let Ok(text) = std::str::from_utf8(content) else {
+        return Err(vec![BatchValidationError {
+            code: "invalid_file_format".to_string(),
+            message: "File is not valid UTF-8".to_string(),
+            line: None,
+            param: None,
+        }]);
+    };
+
+    let mut requests = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_no = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(e) => {
+                errors.push(BatchValidationError::at_line(
+                    line_no,
+                    "invalid_json_line",
+                    format!("Invalid JSON: {}", e),
+                ));
+                continue;
+            }
+        };
+        let custom_id = value.get("custom_id").and_then(|v| v.as_str());
+        let method = value.get("method").and_then(|v| v.as_str());
+        let url = value.get("url").and_then(|v| v.as_str());
+        let body = value.get("body").filter(|b| b.is_object());
+
+        let Some(custom_id) = custom_id else {
+            errors.push(BatchValidationError::at_line(
+                line_no,
+                "missing_required_parameter",
+                "Missing custom_id",
+            ));
+            continue;
+        };
+        if !seen.insert(custom_id.to_string()) {
+            errors.push(BatchValidationError::at_line(
+                line_no,
+                "duplicate_custom_id",
+                format!("Duplicate custom_id '{}'", custom_id),
+            ));
+            continue;
+        }
+        if method
+            .map(|m| !m.eq_ignore_ascii_case("POST"))
+            .unwrap_or(true)
+        {
+            errors.push(BatchValidationError::at_line(
+                line_no,
+                "invalid_method",
+                "Only POST is supported",
+            ));
+            continue;
+        }
+        if url != Some(endpoint) {
+            errors.push(BatchValidationError::at_line(
+                line_no,
+                "mismatched_endpoint",
+                format!("url must match the batch endpoint '{}'", endpoint),
+            ));
+            continue;
+        }
+        let Some(body) = body else {
+            errors.push(BatchValidationError::at_line(
+                line_no,
+                "missing_required_parameter",
+                "Missing body",
+            ));
+            continue;
+        };
+        requests.push(BatchRequestInput {
+            custom_id: custom_id.to_string(),
+            url: endpoint.to_string(),
+            body: body.clone(),
+        });
+    }
+
+    if requests.is_empty() && errors.is_empty() {
+        errors.push(BatchValidationError {
+            code: "empty_file".to_string(),
+            message: "The input file contains no requests".to_string(),
+            line: None,
+            param: None,
+        });
+    }
+    if requests.len() > max_requests {
+        errors.push(BatchValidationError {
+            code: "too_many_requests".to_string(),
+            message: format!("A batch may contain at most {} requests", max_requests),
+            line: None,
+            param: None,
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(requests)
+    } else {
+        Err(errors)
+    }
+}
+
+/// 解析 Anthropic Message Batches 创建请求中的 `requests`
+///
+/// 每项形如 `{"custom_id": "...", "params": {...}}`。
+pub fn parse_anthropic_requests(
+    payload: &Value,
+    max_requests: usize,
+) -> Result<Vec<BatchRequestInput>, String> {
+    let items = payload
+        .get("requests")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| "requests: field required".to_string())?;
+    if items.is_empty() {
+        return Err("requests: must contain at least one request".to_string());
+    }
+    if items.len() > max_requests {
+        return Err(format!(
+            "requests: a batch may contain at most {} requests",
+            max_requests
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let custom_id = item
+                .get("custom_id")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| format!("requests.{}.custom_id: field required", index))?;
+            if !seen.insert(custom_id) {
+                return Err(format!(
+                    "requests.{}.custom_id: duplicate custom_id '{}'",
+                    index, custom_id
+                ));
+            }
+            let params = item
+                .get("params")
+                .filter(|p| p.is_object())
+                .ok_or_else(|| format!("requests.{}.params: field required", index))?;
+            Ok(BatchRequestInput {
+                custom_id: custom_id.to_string(),
+                url: "/v1/messages".to_string(),
+                body: params.clone(),
+            })
+        })
+        .collect()
+}
+
+/// 将多行 JSON 拼接为 JSONL
+pub fn to_jsonl<'a>(lines: impl IntoIterator<Item = &'a Value>) -> Vec<u8> {
+    let mut out = Vec::new();
+    for line in lines {
+        out.extend_from_slice(line.to_string().as_bytes());
+        out.push(b'\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(
+        status: RequestStatus,
+        status_code: Option<u16>,
+        response: Option<Value>,
+    ) -> BatchRequest {
+        BatchRequest {
+            batch_id: "batch_abc".to_string(),
+            line_no: 3,
+            custom_id: "req-3".to_string(),
+            url: "/v1/chat/completions".to_string(),
+            body: json!({}),
+            status,
+            attempts: 1,
+            status_code,
+            response,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_openai_input() {
+        let content = br#"{"custom_id":"a","method":"POST","url":"/v1/chat/completions","body":{"model":"gpt-4o","messages":[]}}
+
+{"custom_id":"b","method":"POST","url":"/v1/chat/completions","body":{"model":"gpt-4o","messages":[]}}
+"#;
+        let requests = parse_openai_input(content, "/v1/chat/completions", 10).unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].custom_id, "b");
+        assert_eq!(requests[0].body["model"], "gpt-4o");
+    }
+
+    #[test]
+    fn test_parse_openai_input_reports_line_errors() {
+        let content = br#"{"custom_id":"a","method":"POST","url":"/v1/chat/completions","body":{}}
+not json
+{"custom_id":"a","method":"POST","url":"/v1/chat/completions","body":{}}
+{"custom_id":"c","method":"POST","url":"/v1/embeddings","body":{}}
+"#;
+        let errors = parse_openai_input(content, "/v1/chat/completions", 10).unwrap_err();
+        let codes: Vec<_> = errors.iter().map(|e| (e.line, e.code.as_str())).collect();
+        assert_eq!(
+            codes,
+            vec![
+                (Some(2), "invalid_json_line"),
+                (Some(3), "duplicate_custom_id"),
+                (Some(4), "mismatched_endpoint"),
+            ]
+        );
+
+        let errors = parse_openai_input(
+            content.split(|b| *b == b'\n').next().unwrap(),
+            "/v1/chat/completions",
+            0,
+        )
+        .unwrap_err();
+        assert_eq!(errors[0].code, "too_many_requests");
+        assert_eq!(
+            parse_openai_input(b"\n", "/v1/chat/completions", 10).unwrap_err()[0].code,
+            "empty_file"
+        );
+    }
+
+    #[test]
+    fn test_parse_anthropic_requests() {
+        let payload = json!({
+            "requests": [
+                {"custom_id": "a", "params": {"model": "claude-sonnet-4-5", "max_tokens": 16, "messages": []}},
+                {"custom_id": "b", "params": {"model": "claude-sonnet-4-5", "max_tokens": 16, "messages": []}}
+            ]
+        });
+        let requests = parse_anthropic_requests(&payload, 10).unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|r| r.url == "/v1/messages"));
+
+        let duplicate = json!({"requests": [
+            {"custom_id": "a", "params": {}},
+            {"custom_id": "a", "params": {}}
+        ]});
+        assert!(parse_anthropic_requests(&duplicate, 10)
+            .unwrap_err()
+            .contains("duplicate"));
+        assert!(parse_anthropic_requests(&json!({}), 10).is_err());
+    }
+
+    #[test]
+    fn test_parse_completion_window() {
+        assert_eq!(parse_completion_window("24h"), Some(86_400));
+        assert_eq!(parse_completion_window("0h"), None);
+        assert_eq!(parse_completion_window("1d"), None);
+    }
+
+    #[test]
+    fn test_openai_result_lines() {
+        let ok = request(
+            RequestStatus::Succeeded,
+            Some(200),
+            Some(json!({"id": "chatcmpl-1", "choices": []})),
+        )
+        .openai_result_line();
+        assert_eq!(ok["custom_id"], "req-3");
+        assert_eq!(ok["response"]["status_code"], 200);
+        assert_eq!(ok["response"]["request_id"], "chatcmpl-1");
+        assert!(ok["error"].is_null());
+
+        let expired = request(RequestStatus::Expired, None, None).openai_result_line();
+        assert!(expired["response"].is_null());
+        assert_eq!(expired["error"]["code"], "batch_expired");
+    }
+
+    #[test]
+    fn test_anthropic_result_lines() {
+        let ok = request(
+            RequestStatus::Succeeded,
+            Some(200),
+            Some(json!({"id": "msg_1", "type": "message"})),
+        )
+        .anthropic_result_line();
+        assert_eq!(ok["result"]["type"], "succeeded");
+        assert_eq!(ok["result"]["message"]["id"], "msg_1");
+
+        let upstream_error =
+            json!({"type": "error", "error": {"type": "overloaded_error", "message": "busy"}});
+        let failed = request(
+            RequestStatus::Failed,
+            Some(529),
+            Some(upstream_error.clone()),
+        )
+        .anthropic_result_line();
+        assert_eq!(failed["result"]["type"], "errored");
+        assert_eq!(failed["result"]["error"], upstream_error);
+
+        let openai_error = json!({"error": {"message": "no credential"}});
+        let failed =
+            request(RequestStatus::Failed, Some(503), Some(openai_error)).anthropic_result_line();
+        assert_eq!(
+            failed["result"]["error"]["error"]["message"],
+            "no credential"
+        );
+
+        let canceled = request(RequestStatus::Cancelled, None, None).anthropic_result_line();
+        assert_eq!(canceled["result"]["type"], "canceled");
+    }
+
+    #[test]
+    fn test_batch_objects() {
+        let mut job = BatchJob::new(
+            BatchApiFormat::Anthropic,
+            "/v1/messages",
+            None,
+            "24h",
+            86_400,
+            None,
+        );
+        assert!(job.id.starts_with("msgbatch_"));
+        let mut counts = RequestCounts::default();
+        counts.add(RequestStatus::Pending, 2);
+        counts.add(RequestStatus::Succeeded, 1);
+
+        let value = job.to_anthropic(&counts);
+        assert_eq!(value["processing_status"], "in_progress");
+        assert_eq!(value["request_counts"]["processing"], 2);
+        assert!(value["results_url"].is_null());
+
+        job.transition(BatchStatus::Completed);
+        let value = job.to_anthropic(&counts);
+        assert_eq!(value["processing_status"], "ended");
+        assert_eq!(
+            value["results_url"],
+            format!("/v1/messages/batches/{}/results", job.id)
+        );
+
+        let value = job.to_openai(&counts);
+        assert_eq!(value["object"], "batch");
+        assert_eq!(value["status"], "completed");
+        assert_eq!(value["request_counts"]["total"], 3);
+        assert!(value["errors"].is_null());
+    }
+}
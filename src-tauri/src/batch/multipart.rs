@@ -0,0 +1,150 @@
+//! 最小化的 multipart/form-data 解析
+//!
+//! 仅用于 `POST /v1/files`：读取文本字段与单个文件字段，整个请求体已在内存中。
+
+/// 表单字段
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormPart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// 从 Content-Type 中提取 boundary
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let mime = params.next()?.trim();
+    if !mime.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params.find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+            .filter(|b| !b.is_empty())
+    })
+}
+
+/// 解析请求体
+pub fn parse(body: &[u8], boundary: &str) -> Result<Vec<FormPart>, String> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+
+    let mut pos = find(body, &delimiter, 0).ok_or("missing multipart boundary")?;
+    loop {
+        pos += delimiter.len();
+        if body[pos..].starts_with(b"--") {
+            return Ok(parts);
+        }
+        pos = skip_crlf(body, pos);
+
+        let header_end = find(body, b"\r\n\r\n", pos).ok_or("malformed part headers")?;
+        let headers = std::str::from_utf8(&body[pos..header_end])
+            .map_err(|_| "part headers are not valid UTF-8")?;
+        let data_start = header_end + 4;
+
+        let mut close = delimiter.clone();
+        close.splice(0..0, *b"\r\n");
+        let data_end = find(body, &close, data_start).ok_or("unterminated multipart body")?;
+
+        let (name, filename) = content_disposition(headers)?;
+        parts.push(FormPart {
+            name,
+            filename,
+            data: body[data_start..data_end].to_vec(),
+        });
+        pos = data_end + 2;
+    }
+}
+
+fn content_disposition(headers: &str) -> Result<(String, Option<String>), String> {
+    let value = headers
+        .split("\r\n")
+        .find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case("content-disposition")
+                .then_some(value)
+        })
+        .ok_or("part is missing Content-Disposition")?;
+
+    let mut name = None;
+    let mut filename = None;
+    for param in value.split(';').skip(1) {
+        let Some((key, value)) = param.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match key.trim() {
+            "name" => name = Some(value),
+            "filename" => filename = Some(value),
+            _ => {}
+        }
+    }
+    Ok((name.ok_or("part is missing a name")?, filename))
+}
+
+fn skip_crlf(body: &[u8], pos: usize) -> usize {
+    if body[pos..].starts_with(b"\r\n") {
+        pos + 2
+    } else {
+        pos
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|i| i + from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(
+            boundary("multipart/form-data; boundary=\"abc123\"").as_deref(),
+            Some("abc123")
+        );
+        assert_eq!(
+            boundary("Multipart/Form-Data;boundary=xyz").as_deref(),
+            Some("xyz")
+        );
+        assert!(boundary("application/json").is_none());
+        assert!(boundary("multipart/form-data").is_none());
+    }
+
+    #[test]
+    fn test_parse() {
+        let body = b"--XX\r\n\
+Content-Disposition: form-data; name=\"purpose\"\r\n\r\n\
+batch\r\n\
+--XX\r\n\
+Content-Disposition: form-data; name=\"file\"; filename=\"input.jsonl\"\r\n\
+Content-Type: application/jsonl\r\n\r\n\
+{\"a\":1}\n{\"b\":2}\n\r\n\
+--XX--\r\n";
+        let parts = parse(body, "XX").unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "purpose");
+        assert_eq!(parts[0].data, b"batch");
+        assert!(parts[0].filename.is_none());
+        assert_eq!(parts[1].filename.as_deref(), Some("input.jsonl"));
+        assert_eq!(parts[1].data, b"{\"a\":1}\n{\"b\":2}\n");
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        assert!(parse(b"no boundary here", "XX").is_err());
+        assert!(parse(
+            b"--XX\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nvalue",
+            "XX"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,9 @@
+//! ProxyCast 无界面服务器
+//!
+//! 不依赖桌面环境，适用于 Linux 服务器和容器部署。
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    proxycast_lib::run_headless()
+}
@@ -7,10 +7,14 @@
 //     handle_term_scrollback_response, handle_terminal_command_response, GetScrollbackResponse,
 //     TerminalCommandResponse,
 // };
+use crate::agent::compaction::restore_compacted;
+use crate::agent::tools::resolve_approval;
 use crate::agent::{
-    AgentMessage, AgentSession, ImageData, NativeAgentState, NativeChatRequest, ProviderType,
+    AgentBranch, AgentCheckpoint, AgentCompaction, AgentMessage, AgentMessageNode, AgentSession,
+    CompactedToolOutput, ImageData, NativeAgentState, NativeChatRequest, ProviderType,
 };
 use crate::commands::network_cmd::get_local_url;
+use crate::commands::session_files_cmd::SessionFilesState;
 use crate::database::dao::agent::AgentDao;
 use crate::database::DbConnection;
 use crate::AppState;
@@ -81,6 +85,7 @@ pub async fn agent_start_process(
 #[tauri::command]
 pub async fn agent_stop_process(agent_state: State<'_, NativeAgentState>) -> Result<(), String> {
     tracing::info!("[Agent] 停止原生 Agent");
+    agent_state.cancel_running();
     agent_state.reset();
     Ok(())
 }
@@ -417,6 +422,287 @@ pub async fn agent_get_session_messages(
     Ok(messages)
 }
 
+// ============================================================================
+// 会话分支与检查点
+// ============================================================================
+
+/// 用数据库中的激活分支同步内存会话，返回激活分支
+///
+/// 分支上有压缩记录时，内存会话使用压缩后的消息；返回的分支始终是完整的。
+fn sync_active_branch(
+    agent_state: &NativeAgentState,
+    conn: &rusqlite::Connection,
+    session_id: &str,
+) -> Result<Vec<AgentMessageNode>, String> {
+    let branch = AgentDao::get_active_branch(conn, session_id)
+        .map_err(|e| format!("获取分支失败: {}", e))?;
+    let mut session = AgentDao::get_session(conn, session_id)
+        .map_err(|e| format!("获取会话失败: {}", e))?
+        .ok_or_else(|| "会话不存在".to_string())?;
+    let branch_ids = branch.iter().map(|n| n.id).collect();
+    let compaction = AgentDao::get_branch_compaction(conn, session_id, &branch_ids)
+        .map_err(|e| format!("获取压缩记录失败: {}", e))?;
+    session.messages = match compaction {
+        Some(compaction) => restore_compacted(&branch, &compaction),
+        None => branch.iter().map(|n| n.message.clone()).collect(),
+    };
+    agent_state.restore_session(session);
+    Ok(branch)
+}
+
+/// 获取会话的完整消息树
+#[tauri::command]
+pub async fn agent_get_message_tree(
+    db: State<'_, DbConnection>,
+    session_id: String,
+) -> Result<Vec<AgentMessageNode>, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    AgentDao::get_message_tree(&conn, &session_id).map_err(|e| format!("获取消息树失败: {}", e))
+}
+
+/// 列出会话的所有分支
+#[tauri::command]
+pub async fn agent_list_branches(
+    db: State<'_, DbConnection>,
+    session_id: String,
+) -> Result<Vec<AgentBranch>, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    AgentDao::list_branches(&conn, &session_id).map_err(|e| format!("获取分支列表失败: {}", e))
+}
+
+/// 切换激活分支
+///
+/// `message_id` 可以是任意消息：切换到中间消息即回退到该位置，之后的消息保留为其他分支。
+#[tauri::command]
+pub async fn agent_switch_branch(
+    agent_state: State<'_, NativeAgentState>,
+    db: State<'_, DbConnection>,
+    session_id: String,
+    message_id: i64,
+) -> Result<Vec<AgentMessageNode>, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    AgentDao::get_message_node(&conn, &session_id, message_id)
+        .map_err(|e| format!("获取消息失败: {}", e))?
+        .ok_or_else(|| format!("消息不存在: {}", message_id))?;
+    AgentDao::set_active_leaf(&conn, &session_id, Some(message_id))
+        .map_err(|e| format!("切换分支失败: {}", e))?;
+
+    tracing::info!(
+        "[Agent] 切换分支: session={}, leaf={}",
+        session_id,
+        message_id
+    );
+    sync_active_branch(&agent_state, &conn, &session_id)
+}
+
+/// 编辑并重发用户消息
+///
+/// 将激活分支回退到该消息的父消息，随后发送的新消息会成为原消息的兄弟分支。
+#[tauri::command]
+pub async fn agent_edit_message(
+    agent_state: State<'_, NativeAgentState>,
+    db: State<'_, DbConnection>,
+    session_id: String,
+    message_id: i64,
+) -> Result<Vec<AgentMessageNode>, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    let node = AgentDao::get_message_node(&conn, &session_id, message_id)
+        .map_err(|e| format!("获取消息失败: {}", e))?
+        .ok_or_else(|| format!("消息不存在: {}", message_id))?;
+    if node.message.role != "user" {
+        return Err("只能编辑用户消息".to_string());
+    }
+    AgentDao::set_active_leaf(&conn, &session_id, node.parent_id)
+        .map_err(|e| format!("回退分支失败: {}", e))?;
+
+    tracing::info!(
+        "[Agent] 编辑消息: session={}, message={}",
+        session_id,
+        message_id
+    );
+    sync_active_branch(&agent_state, &conn, &session_id)
+}
+
+/// 在指定消息处分叉出新会话，返回新会话 ID
+#[tauri::command]
+pub async fn agent_fork_session(
+    agent_state: State<'_, NativeAgentState>,
+    db: State<'_, DbConnection>,
+    session_id: String,
+    message_id: i64,
+) -> Result<String, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    let source = AgentDao::get_session(&conn, &session_id)
+        .map_err(|e| format!("获取会话失败: {}", e))?
+        .ok_or_else(|| "会话不存在".to_string())?;
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let new_session = AgentSession {
+        id: uuid::Uuid::new_v4().to_string(),
+        model: source.model,
+        messages: Vec::new(),
+        system_prompt: source.system_prompt,
+        created_at: now.clone(),
+        updated_at: now,
+    };
+    let copied = AgentDao::fork_session(&conn, &session_id, message_id, &new_session)
+        .map_err(|e| format!("分叉会话失败: {}", e))?;
+
+    tracing::info!(
+        "[Agent] 分叉会话: {} -> {} ({} 条消息)",
+        session_id,
+        new_session.id,
+        copied
+    );
+    sync_active_branch(&agent_state, &conn, &new_session.id)?;
+    Ok(new_session.id)
+}
+
+/// 创建检查点（记录激活分支位置并快照会话文件）
+#[tauri::command]
+pub async fn agent_create_checkpoint(
+    db: State<'_, DbConnection>,
+    session_files: State<'_, SessionFilesState>,
+    session_id: String,
+    label: Option<String>,
+) -> Result<AgentCheckpoint, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    let message_id = AgentDao::get_active_leaf(&conn, &session_id)
+        .map_err(|e| format!("获取分支失败: {}", e))?;
+
+    let checkpoint_id = uuid::Uuid::new_v4().to_string();
+    let file_count = {
+        let storage = session_files
+            .0
+            .lock()
+            .map_err(|e| format!("锁定失败: {}", e))?;
+        storage.create_snapshot(&session_id, &checkpoint_id)?
+    };
+
+    let checkpoint = AgentCheckpoint {
+        id: checkpoint_id,
+        session_id,
+        message_id,
+        label,
+        file_count,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    AgentDao::create_checkpoint(&conn, &checkpoint)
+        .map_err(|e| format!("保存检查点失败: {}", e))?;
+
+    tracing::info!(
+        "[Agent] 创建检查点: session={}, checkpoint={}",
+        checkpoint.session_id,
+        checkpoint.id
+    );
+    Ok(checkpoint)
+}
+
+/// 列出会话的检查点
+#[tauri::command]
+pub async fn agent_list_checkpoints(
+    db: State<'_, DbConnection>,
+    session_id: String,
+) -> Result<Vec<AgentCheckpoint>, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    AgentDao::list_checkpoints(&conn, &session_id).map_err(|e| format!("获取检查点失败: {}", e))
+}
+
+/// 回退到检查点，同时恢复对话分支和会话文件
+#[tauri::command]
+pub async fn agent_rewind_to_checkpoint(
+    agent_state: State<'_, NativeAgentState>,
+    db: State<'_, DbConnection>,
+    session_files: State<'_, SessionFilesState>,
+    session_id: String,
+    checkpoint_id: String,
+) -> Result<Vec<AgentMessageNode>, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    let checkpoint = AgentDao::get_checkpoint(&conn, &session_id, &checkpoint_id)
+        .map_err(|e| format!("获取检查点失败: {}", e))?
+        .ok_or_else(|| format!("检查点不存在: {}", checkpoint_id))?;
+
+    {
+        let storage = session_files
+            .0
+            .lock()
+            .map_err(|e| format!("锁定失败: {}", e))?;
+        storage.restore_snapshot(&session_id, &checkpoint.id)?;
+    }
+    AgentDao::set_active_leaf(&conn, &session_id, checkpoint.message_id)
+        .map_err(|e| format!("回退分支失败: {}", e))?;
+
+    tracing::info!(
+        "[Agent] 回退到检查点: session={}, checkpoint={}",
+        session_id,
+        checkpoint_id
+    );
+    sync_active_branch(&agent_state, &conn, &session_id)
+}
+
+/// 删除检查点
+#[tauri::command]
+pub async fn agent_delete_checkpoint(
+    db: State<'_, DbConnection>,
+    session_files: State<'_, SessionFilesState>,
+    session_id: String,
+    checkpoint_id: String,
+) -> Result<(), String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    AgentDao::delete_checkpoint(&conn, &session_id, &checkpoint_id)
+        .map_err(|e| format!("删除检查点失败: {}", e))?;
+
+    let storage = session_files
+        .0
+        .lock()
+        .map_err(|e| format!("锁定失败: {}", e))?;
+    storage.delete_snapshot(&session_id, &checkpoint_id)
+}
+
+// ============================================================================
+// 上下文压缩
+// ============================================================================
+
+/// 置顶或取消置顶消息（置顶消息在上下文压缩时原样保留）
+#[tauri::command]
+pub async fn agent_pin_message(
+    agent_state: State<'_, NativeAgentState>,
+    db: State<'_, DbConnection>,
+    session_id: String,
+    message_id: i64,
+    pinned: bool,
+) -> Result<Vec<AgentMessageNode>, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    if !AgentDao::set_message_pinned(&conn, &session_id, message_id, pinned)
+        .map_err(|e| format!("更新消息失败: {}", e))?
+    {
+        return Err(format!("消息不存在: {}", message_id));
+    }
+    sync_active_branch(&agent_state, &conn, &session_id)
+}
+
+/// 列出会话的上下文压缩记录
+#[tauri::command]
+pub async fn agent_list_compactions(
+    db: State<'_, DbConnection>,
+    session_id: String,
+) -> Result<Vec<AgentCompaction>, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    AgentDao::list_compactions(&conn, &session_id).map_err(|e| format!("获取压缩记录失败: {}", e))
+}
+
+/// 获取被截断的工具输出
+#[tauri::command]
+pub async fn agent_get_tool_output(
+    db: State<'_, DbConnection>,
+    session_id: String,
+    handle: String,
+) -> Result<Option<CompactedToolOutput>, String> {
+    let conn = db.lock().map_err(|e| format!("数据库锁定失败: {}", e))?;
+    AgentDao::get_tool_output(&conn, &session_id, &handle)
+        .map_err(|e| format!("获取工具输出失败: {}", e))
+}
+
 /// 处理终端命令响应
 ///
 /// 前端在用户批准/拒绝命令后调用此命令，将结果传递给 TerminalTool
@@ -428,14 +714,21 @@ pub async fn agent_terminal_command_response(
     error: Option<String>,
     exit_code: Option<i32>,
     rejected: bool,
+    always_allow: Option<bool>,
 ) -> Result<(), String> {
     tracing::info!(
-        "[Agent] 收到终端命令响应: request_id={}, success={}, rejected={}",
+        "[Agent] 收到终端命令响应: request_id={}, success={}, rejected={}, always_allow={:?}",
         request_id,
         success,
-        rejected
+        rejected,
+        always_allow
     );
 
+    // 审批结果交给等待中的命令，"始终允许"在会话内授权该命令
+    if !resolve_approval(&request_id, !rejected, always_allow.unwrap_or(false)) {
+        tracing::warn!("[Agent] 未找到待审批的终端命令: {}", request_id);
+    }
+
     // TODO: 重新实现终端命令响应处理，适配 aster-rust 工具系统
     // let response = TerminalCommandResponse {
     //     request_id,
@@ -444,12 +737,11 @@ pub async fn agent_terminal_command_response(
     //     error,
     //     exit_code,
     //     rejected,
+    //     always_allow: always_allow.unwrap_or(false),
     // };
 
     // handle_terminal_command_response(response);
 
-    tracing::warn!("[Agent] 终端命令响应处理暂时禁用，等待适配 aster-rust 工具系统");
-
     Ok(())
 }
 
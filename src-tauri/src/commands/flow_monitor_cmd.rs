@@ -804,9 +804,14 @@ pub async fn create_test_flows(
                 target_url: Some("https://api.openai.com".to_string()),
                 route_rule: None,
                 load_balance_strategy: None,
+                attempts: Vec::new(),
+                trace: None,
             },
             injected_params: None,
             context_usage_percentage: Some(50.0),
+            unsupported_params: Vec::new(),
+            structured_output: None,
+            guardrail_events: Vec::new(),
         };
 
         // 启动 Flow
@@ -32,6 +32,7 @@ pub mod route_cmd;
 pub mod screenshot_cmd;
 pub mod session_files_cmd;
 pub mod skill_cmd;
+pub mod ssh_tunnel_cmd;
 pub mod switch_cmd;
 pub mod telemetry_cmd;
 pub mod terminal_cmd;
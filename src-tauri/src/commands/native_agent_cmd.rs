@@ -2,14 +2,20 @@
 //!
 //! 提供原生 Rust Agent 的 Tauri 命令，替代 aster sidecar 方案
 
+use crate::agent::subagent::SubAgentRunner;
+use crate::agent::tools::{resolve_approval, shared_policy, SecurityManager};
 use crate::agent::{
-    AgentMessage, AgentSession, ImageData, MessageContent, NativeAgentState, NativeChatRequest,
-    NativeChatResponse, ProviderType, StreamEvent, ToolLoopEngine,
+    AgentCompaction, AgentMessage, AgentSession, CompactedToolOutput, ContextCompactor, ImageData,
+    MessageContent, NativeAgentState, NativeChatRequest, NativeChatResponse, ProviderType,
+    StreamEvent, ToolLoopConfig, ToolLoopEngine,
 };
+use crate::commands::model_registry_cmd::ModelRegistryState;
 use crate::commands::network_cmd::get_local_url;
+use crate::commands::tool_hooks::ToolHooksServiceState;
 use crate::database::dao::agent::AgentDao;
 use crate::database::dao::api_key_provider::ApiKeyProviderDao;
 use crate::database::DbConnection;
+use crate::orchestrator::get_global_orchestrator;
 use crate::AppState;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -216,6 +222,8 @@ pub async fn native_agent_chat_stream(
     agent_state: State<'_, NativeAgentState>,
     app_state: State<'_, AppState>,
     db: State<'_, DbConnection>,
+    hooks_service: State<'_, ToolHooksServiceState>,
+    model_registry: State<'_, ModelRegistryState>,
     message: String,
     event_name: String,
     session_id: Option<String>,
@@ -236,14 +244,29 @@ pub async fn native_agent_chat_stream(
     );
 
     // 获取配置信息
-    let (host, port, api_key, running, default_provider) = {
+    let (
+        host,
+        port,
+        api_key,
+        running,
+        default_provider,
+        sub_agent_config,
+        compaction_config,
+        tool_loop_config,
+    ) = {
         let state = app_state.read().await;
+        let agent_config = &state.config.agent;
         (
             state.config.server.host.clone(),
             state.config.server.port,
             state.running_api_key.clone(),
             state.running,
             state.config.routing.default_provider.clone(),
+            agent_config.sub_agents.clone(),
+            agent_config.compaction.clone(),
+            ToolLoopConfig::default()
+                .with_max_parallel_tools(agent_config.max_parallel_tools)
+                .with_exclusive_tools(&agent_config.exclusive_tools),
         )
     };
 
@@ -325,6 +348,7 @@ pub async fn native_agent_chat_stream(
             tool_calls: None,
             tool_call_id: None,
             reasoning_content: None,
+            pinned: false,
         };
         if let Err(e) = AgentDao::add_message(&conn, sid, &user_message) {
             tracing::warn!("[NativeAgent] 保存用户消息到数据库失败: {}", e);
@@ -346,9 +370,60 @@ pub async fn native_agent_chat_stream(
         stream: true,
     };
 
+    // 上下文压缩：上下文长度取自模型注册表，载入会话中已截断的工具输出
+    let compactor = if compaction_config.enabled {
+        let context_window = match &request.model {
+            Some(model) => match model_registry.read().await.as_ref() {
+                Some(service) => service.get_context_length(model).await,
+                None => None,
+            },
+            None => None,
+        };
+        let outputs = match &session_id_for_db {
+            Some(sid) => {
+                let conn = db_clone
+                    .lock()
+                    .map_err(|e| format!("数据库锁定失败: {}", e))?;
+                AgentDao::list_tool_outputs(&conn, sid).unwrap_or_else(|e| {
+                    tracing::warn!("[NativeAgent] 读取已截断的工具输出失败: {}", e);
+                    Vec::new()
+                })
+            }
+            None => Vec::new(),
+        };
+        Some(Arc::new(
+            ContextCompactor::new(compaction_config, context_window).with_outputs(outputs),
+        ))
+    } else {
+        None
+    };
+
     // 克隆 agent_state 用于后台任务（共享 sessions）
     let agent_state_clone = agent_state.inner().clone();
+    // 停止 Agent 时取消本次对话
+    let cancel_token = agent_state.cancel_token();
     let session_id_for_task = session_id_for_db.clone();
+    let tool_hooks = hooks_service.0.clone();
+    // 终端命令按审批策略执行，自动执行的命令须在用户目录内
+    let command_security = dirs::home_dir().map(SecurityManager::new);
+
+    // task 工具：启动子 Agent，模型由编排器按服务等级选择
+    let sub_agents = (sub_agent_config.enabled && sub_agent_config.max_depth > 0).then(|| {
+        let mut runner = SubAgentRunner::new(
+            agent_state_clone.clone(),
+            tool_registry.clone(),
+            sub_agent_config,
+        )
+        .with_hooks(tool_hooks.clone())
+        .with_fallback_model(request.model.clone());
+        if let Some(security) = &command_security {
+            runner = runner.with_command_policy(security.clone());
+        }
+        if let Some(orchestrator) = get_global_orchestrator() {
+            runner = runner.with_orchestrator(orchestrator);
+        }
+        Arc::new(runner)
+    });
 
     // 在后台任务中处理流式响应
     let event_name_clone = event_name.clone();
@@ -360,7 +435,18 @@ pub async fn native_agent_chat_stream(
         eprintln!("[native_agent_chat_stream] 后台任务开始执行");
 
         // 创建工具循环引擎（使用共享的 tool_registry）
-        let tool_loop_engine = ToolLoopEngine::new(tool_registry);
+        let mut tool_loop_engine = ToolLoopEngine::with_config(tool_registry, tool_loop_config)
+            .with_cancel_token(cancel_token.clone())
+            .with_hooks(tool_hooks, session_id_for_task.clone().unwrap_or_default());
+        if let Some(security) = command_security {
+            tool_loop_engine = tool_loop_engine.with_command_policy(security);
+        }
+        if let Some(sub_agents) = sub_agents {
+            tool_loop_engine = tool_loop_engine.with_sub_agents(sub_agents);
+        }
+        if let Some(compactor) = compactor {
+            tool_loop_engine = tool_loop_engine.with_compactor(compactor);
+        }
         eprintln!("[native_agent_chat_stream] 工具循环引擎创建成功");
 
         let (tx, mut rx) = mpsc::channel::<StreamEvent>(100);
@@ -395,6 +481,32 @@ pub async fn native_agent_chat_stream(
                 full_content.push_str(text);
             }
 
+            // 持久化压缩记录和被截断的工具输出，以便恢复会话时使用
+            if let (
+                Some(sid),
+                StreamEvent::ContextCompacted {
+                    summary,
+                    summarized_messages,
+                    kept_messages,
+                    tokens_before,
+                    tokens_after,
+                    truncated_outputs,
+                },
+            ) = (&session_id_for_task, &event)
+            {
+                if let Ok(conn) = db_clone.lock() {
+                    save_compaction(
+                        &conn,
+                        sid,
+                        summary.as_deref(),
+                        *summarized_messages,
+                        *kept_messages,
+                        (*tokens_before, *tokens_after),
+                        truncated_outputs,
+                    );
+                }
+            }
+
             if let Err(e) = app_handle.emit(&event_name_clone, &event) {
                 tracing::error!("[NativeAgent] 发送事件失败: {}", e);
                 eprintln!("[native_agent_chat_stream] 发送事件失败: {}", e);
@@ -410,6 +522,8 @@ pub async fn native_agent_chat_stream(
             }
         }
         eprintln!("[native_agent_chat_stream] channel 关闭，事件接收完成");
+        // 提前停止接收时（前端不可达或出错），中止仍在执行的工具调用
+        cancel_token.cancel();
 
         eprintln!("[native_agent_chat_stream] 等待 stream_task 完成...");
         match stream_task.await {
@@ -427,6 +541,7 @@ pub async fn native_agent_chat_stream(
                                 tool_calls: None,
                                 tool_call_id: None,
                                 reasoning_content: None,
+                                pinned: false,
                             };
                             if let Err(e) = AgentDao::add_message(&conn, sid, &assistant_message) {
                                 tracing::warn!("[NativeAgent] 保存助手消息到数据库失败: {}", e);
@@ -447,6 +562,49 @@ pub async fn native_agent_chat_stream(
     Ok(())
 }
 
+/// 保存上下文压缩记录（压缩点为当前激活分支的末端消息）
+fn save_compaction(
+    conn: &rusqlite::Connection,
+    session_id: &str,
+    summary: Option<&str>,
+    summarized_messages: usize,
+    kept_messages: usize,
+    (tokens_before, tokens_after): (u32, u32),
+    truncated_outputs: &[CompactedToolOutput],
+) {
+    for output in truncated_outputs {
+        if let Err(e) = AgentDao::save_tool_output(conn, session_id, output) {
+            tracing::warn!("[NativeAgent] 保存工具输出失败: {}", e);
+        }
+    }
+
+    // 仅截断工具输出时没有摘要，无需记录
+    let Some(summary) = summary else {
+        return;
+    };
+    let message_id = match AgentDao::get_active_leaf(conn, session_id) {
+        Ok(message_id) => message_id,
+        Err(e) => {
+            tracing::warn!("[NativeAgent] 获取激活分支失败: {}", e);
+            return;
+        }
+    };
+    let compaction = AgentCompaction {
+        id: uuid::Uuid::new_v4().to_string(),
+        session_id: session_id.to_string(),
+        message_id,
+        summary: summary.to_string(),
+        summarized_messages: summarized_messages as u32,
+        kept_messages: kept_messages as u32,
+        tokens_before,
+        tokens_after,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    if let Err(e) = AgentDao::create_compaction(conn, &compaction) {
+        tracing::warn!("[NativeAgent] 保存压缩记录失败: {}", e);
+    }
+}
+
 #[tauri::command]
 pub async fn native_agent_create_session(
     agent_state: State<'_, NativeAgentState>,
@@ -469,6 +627,7 @@ pub async fn native_agent_delete_session(
     agent_state: State<'_, NativeAgentState>,
     session_id: String,
 ) -> Result<bool, String> {
+    shared_policy().revoke_session(&session_id);
     Ok(agent_state.delete_session(&session_id))
 }
 
@@ -479,6 +638,9 @@ pub async fn native_agent_list_sessions(
     Ok(agent_state.list_sessions())
 }
 
+/// 权限确认时选择"始终允许"的响应值
+const ALWAYS_ALLOW_RESPONSE: &str = "always_allow";
+
 /// 权限确认响应请求
 #[derive(Debug, Deserialize)]
 pub struct PermissionResponseRequest {
@@ -502,9 +664,11 @@ pub async fn agent_permission_response(
         response
     );
 
-    // TODO: 实现权限确认响应逻辑
-    // 这需要与工具循环引擎集成，将用户的确认响应传递给等待中的工具
-    // 目前先记录日志并返回成功
+    // 终端命令审批：response 为 "always_allow" 时在会话内始终允许该命令
+    let always_allow = response.as_deref() == Some(ALWAYS_ALLOW_RESPONSE);
+    if !resolve_approval(&request_id, confirmed, always_allow) {
+        tracing::warn!("[NativeAgent] 未找到待确认的请求: {}", request_id);
+    }
 
     Ok(())
 }
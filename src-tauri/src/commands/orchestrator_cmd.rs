@@ -5,9 +5,9 @@
 use crate::database::dao::provider_pool::ProviderPoolDao;
 use crate::database::DbConnection;
 use crate::orchestrator::{
-    get_global_orchestrator, init_global_orchestrator, AvailableModel, CredentialInfo,
-    OrchestratorConfig, PoolStats, ProviderType, SelectionContext, SelectionResult, ServiceTier,
-    StrategyInfo, TaskHint,
+    get_global_orchestrator, init_global_orchestrator, AdaptiveDecision, AvailableModel,
+    CredentialInfo, OrchestratorConfig, PoolStats, ProviderType, SelectionContext, SelectionResult,
+    ServiceTier, StrategyInfo, TaskHint,
 };
 use serde::{Deserialize, Serialize};
 use tauri::State;
@@ -346,11 +346,8 @@ pub struct SelectionRequest {
     pub strategy_id: Option<String>,
 }
 
-/// 选择模型
-#[tauri::command]
-pub async fn select_model(request: SelectionRequest) -> Result<SelectionResult, String> {
-    let orchestrator = get_global_orchestrator().ok_or("编排器未初始化")?;
-
+/// 根据选择请求构建选择上下文
+fn build_selection_context(request: &SelectionRequest) -> Result<SelectionContext, String> {
     let tier = ServiceTier::from_str(&request.tier)
         .ok_or_else(|| format!("无效的服务等级: {}", request.tier))?;
 
@@ -377,14 +374,24 @@ pub async fn select_model(request: SelectionRequest) -> Result<SelectionResult,
         ctx.requires_tools = tools;
     }
 
-    if let Some(provider) = request.preferred_provider {
-        ctx.preferred_provider = Some(provider);
+    if let Some(provider) = &request.preferred_provider {
+        ctx.preferred_provider = Some(provider.clone());
     }
 
-    if let Some(excluded) = request.excluded_models {
-        ctx.excluded_models = excluded;
+    if let Some(excluded) = &request.excluded_models {
+        ctx.excluded_models = excluded.clone();
     }
 
+    Ok(ctx)
+}
+
+/// 选择模型
+#[tauri::command]
+pub async fn select_model(request: SelectionRequest) -> Result<SelectionResult, String> {
+    let orchestrator = get_global_orchestrator().ok_or("编排器未初始化")?;
+
+    let ctx = build_selection_context(&request)?;
+
     let result = if let Some(strategy_id) = &request.strategy_id {
         orchestrator.select_with_strategy(strategy_id, &ctx).await
     } else {
@@ -431,6 +438,27 @@ pub async fn select_model_for_task(tier: String, task: String) -> Result<Selecti
 // 策略命令
 // ============================================================================
 
+/// 获取自适应策略最近的决策记录（最新的在前）
+#[tauri::command]
+pub async fn get_adaptive_decisions(limit: Option<usize>) -> Result<Vec<AdaptiveDecision>, String> {
+    let orchestrator = get_global_orchestrator().ok_or("编排器未初始化")?;
+
+    Ok(orchestrator
+        .adaptive()
+        .recent_decisions(limit.unwrap_or(20)))
+}
+
+/// 解释自适应策略对请求的评分和选择结果
+#[tauri::command]
+pub async fn explain_adaptive_selection(
+    request: SelectionRequest,
+) -> Result<AdaptiveDecision, String> {
+    let orchestrator = get_global_orchestrator().ok_or("编排器未初始化")?;
+
+    let ctx = build_selection_context(&request)?;
+    Ok(orchestrator.explain_adaptive(&ctx).await)
+}
+
 /// 列出所有可用策略
 #[tauri::command]
 pub async fn list_strategies() -> Result<Vec<StrategyInfo>, String> {
@@ -2799,7 +2799,7 @@ fn get_playwright_browser_path() -> Option<String> {
 }
 
 /// 获取可用的浏览器路径（优先系统 Chrome）
-fn get_available_browser_path() -> Option<(String, String)> {
+pub(crate) fn get_available_browser_path() -> Option<(String, String)> {
     // 优先使用系统 Chrome
     if let Some(path) = get_system_chrome_path() {
         return Some((path, "system".to_string()));
@@ -0,0 +1,153 @@
+//! SSH 端口转发 Tauri 命令
+//!
+//! 维护用于端口转发的 SSH 连接，并提供运行时增删隧道的接口。
+//!
+//! ## 命令列表
+//! - `ssh_tunnel_connect` - 建立 SSH 连接并启动 ssh_config 中配置的端口转发
+//! - `ssh_tunnel_disconnect` - 断开连接并停止全部端口转发
+//! - `ssh_tunnel_list` - 获取连接上的隧道
+//! - `ssh_tunnel_add` - 添加隧道
+//! - `ssh_tunnel_remove` - 移除隧道
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tauri::State;
+use tokio::sync::RwLock;
+
+use crate::terminal::connections::{
+    build_default_auth_methods, ConnKeywords, ConnStatus, ForwardKind, ForwardSpec,
+    NoOpAuthCallback, SSHConfigParser, SSHConn, SSHOpts, TunnelStatus,
+};
+
+/// 端口转发连接状态（连接名 → SSH 连接）
+#[derive(Default)]
+pub struct SSHTunnelState(pub Arc<RwLock<HashMap<String, Arc<SSHConn>>>>);
+
+/// 获取已建立的连接
+async fn get_connection(
+    state: &State<'_, SSHTunnelState>,
+    connection: &str,
+) -> Result<Arc<SSHConn>, String> {
+    state
+        .0
+        .read()
+        .await
+        .get(connection)
+        .filter(|conn| conn.is_connected())
+        .cloned()
+        .ok_or_else(|| format!("连接 '{}' 未建立", connection))
+}
+
+/// 解析连接名，合并 ssh_config 中对应 Host 的配置
+fn resolve_connection(connection: &str) -> Result<(SSHOpts, ConnKeywords), String> {
+    let alias = SSHOpts::parse(connection)?;
+    let keywords = SSHConfigParser::get_host_config(&alias.ssh_host).unwrap_or_default();
+
+    let opts = SSHOpts {
+        ssh_host: keywords.host.clone().unwrap_or(alias.ssh_host),
+        ssh_user: alias.ssh_user.or_else(|| keywords.user.clone()),
+        ssh_port: alias.ssh_port.or(keywords.port),
+    };
+    Ok((opts, keywords))
+}
+
+/// 建立 SSH 连接并启动 ssh_config 中配置的端口转发
+///
+/// # 参数
+/// - `connection`: 连接字符串或 ssh_config 中的 Host 别名
+/// - `password`: 密码（可选）
+///
+/// 主机密钥需已在 known_hosts 中。
+#[tauri::command]
+pub async fn ssh_tunnel_connect(
+    app_handle: tauri::AppHandle,
+    state: State<'_, SSHTunnelState>,
+    connection: String,
+    password: Option<String>,
+) -> Result<ConnStatus, String> {
+    if let Ok(conn) = get_connection(&state, &connection).await {
+        return Ok(conn.derive_conn_status());
+    }
+
+    let (opts, keywords) = resolve_connection(&connection)?;
+    let auth_methods = build_default_auth_methods(&keywords, password);
+    let conn = Arc::new(SSHConn::with_app_handle(opts, app_handle));
+    conn.connect_and_authenticate(&keywords, &auth_methods, &NoOpAuthCallback)
+        .await?;
+
+    tokio::spawn(conn.clone().watch_forward_changes());
+    let status = conn.derive_conn_status();
+    tracing::info!(
+        "[SSHTunnel] 连接已建立: {}，隧道数: {}",
+        connection,
+        status.tunnels.len()
+    );
+
+    let previous = state.0.write().await.insert(connection, conn);
+    if let Some(previous) = previous {
+        let _ = previous.close().await;
+    }
+    Ok(status)
+}
+
+/// 断开连接并停止全部端口转发
+#[tauri::command]
+pub async fn ssh_tunnel_disconnect(
+    state: State<'_, SSHTunnelState>,
+    connection: String,
+) -> Result<(), String> {
+    let conn = state
+        .0
+        .write()
+        .await
+        .remove(&connection)
+        .ok_or_else(|| format!("连接 '{}' 未建立", connection))?;
+    conn.close().await?;
+    Ok(())
+}
+
+/// 获取连接上的隧道
+#[tauri::command]
+pub async fn ssh_tunnel_list(
+    state: State<'_, SSHTunnelState>,
+    connection: String,
+) -> Result<Vec<TunnelStatus>, String> {
+    Ok(get_connection(&state, &connection).await?.list_forwards())
+}
+
+/// 添加隧道
+///
+/// # 参数
+/// - `kind`: 转发类型（local / remote / dynamic）
+/// - `spec`: ssh_config 格式的规则，如 `8080 localhost:80` 或 `1080`
+#[tauri::command]
+pub async fn ssh_tunnel_add(
+    state: State<'_, SSHTunnelState>,
+    connection: String,
+    kind: ForwardKind,
+    spec: String,
+) -> Result<TunnelStatus, String> {
+    let conn = get_connection(&state, &connection).await?;
+    let spec = ForwardSpec::parse(kind, &spec)?;
+
+    // 远程转发需要等待服务器响应
+    let status = tokio::task::spawn_blocking(move || conn.add_forward(spec))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(status)
+}
+
+/// 移除隧道
+#[tauri::command]
+pub async fn ssh_tunnel_remove(
+    state: State<'_, SSHTunnelState>,
+    connection: String,
+    id: String,
+) -> Result<(), String> {
+    let conn = get_connection(&state, &connection).await?;
+    tokio::task::spawn_blocking(move || conn.remove_forward(&id))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(())
+}
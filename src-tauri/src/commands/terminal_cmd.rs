@@ -11,6 +11,13 @@
 //! - `terminal_resize` - 调整终端大小
 //! - `terminal_close` - 关闭终端会话
 //! - `terminal_list_sessions` - 获取所有会话列表
+//! - `terminal_start_recording` / `terminal_stop_recording` - 开始/停止会话录制
+//! - `terminal_export_recording` - 导出录制（asciicast v2 或纯文本）
+//! - `terminal_list_recording_chapters` - 获取录制章节（命令边界）
+//! - `terminal_replay_recording` - 将录制回放到新的块
+//! - `terminal_search_command_history` - 搜索命令历史
+//! - `terminal_rerun_command` - 在会话中重新执行历史命令
+//! - `terminal_delete_command_history` - 删除命令历史记录
 
 use std::sync::Arc;
 
@@ -18,6 +25,9 @@ use serde::Serialize;
 use tauri::State;
 use tokio::sync::RwLock;
 
+use crate::terminal::persistence::{
+    CommandHistoryEntry, CommandHistoryHit, CommandHistoryQuery, RecordingChapter,
+};
 use crate::terminal::{SessionMetadata, TerminalSessionManager};
 
 /// 终端会话管理器状态包装
@@ -153,3 +163,189 @@ pub async fn terminal_get_session(
 
     Ok(manager.get_session(&session_id).await)
 }
+
+/// 开始录制终端会话
+///
+/// # 参数
+/// - `session_id`: 会话 ID
+///
+/// # 返回
+/// 录制文件路径
+#[tauri::command]
+pub async fn terminal_start_recording(
+    state: State<'_, TerminalManagerState>,
+    session_id: String,
+) -> Result<String, String> {
+    let guard = state.inner().0.read().await;
+    let manager = guard
+        .as_ref()
+        .ok_or_else(|| "终端管理器未初始化".to_string())?;
+
+    manager
+        .start_recording(&session_id)
+        .await
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// 停止录制终端会话
+///
+/// # 参数
+/// - `session_id`: 会话 ID
+///
+/// # 返回
+/// 录制文件路径（未在录制时为 None）
+#[tauri::command]
+pub async fn terminal_stop_recording(
+    state: State<'_, TerminalManagerState>,
+    session_id: String,
+) -> Result<Option<String>, String> {
+    let guard = state.inner().0.read().await;
+    let manager = guard
+        .as_ref()
+        .ok_or_else(|| "终端管理器未初始化".to_string())?;
+
+    manager
+        .stop_recording(&session_id)
+        .await
+        .map(|path| path.map(|p| p.to_string_lossy().to_string()))
+        .map_err(|e| e.to_string())
+}
+
+/// 导出终端会话录制
+///
+/// # 参数
+/// - `session_id`: 会话 ID
+/// - `format`: `asciicast`（默认）或 `transcript`
+/// - `include_input`: asciicast 是否包含输入事件（默认 false）
+///
+/// # 返回
+/// 导出内容
+#[tauri::command]
+pub async fn terminal_export_recording(
+    state: State<'_, TerminalManagerState>,
+    session_id: String,
+    format: Option<String>,
+    include_input: Option<bool>,
+) -> Result<String, String> {
+    let guard = state.inner().0.read().await;
+    let manager = guard
+        .as_ref()
+        .ok_or_else(|| "终端管理器未初始化".to_string())?;
+
+    let result = match format.as_deref().unwrap_or("asciicast") {
+        "asciicast" => {
+            manager
+                .export_recording_asciicast(&session_id, include_input.unwrap_or(false))
+                .await
+        }
+        "transcript" => manager.export_recording_transcript(&session_id).await,
+        other => return Err(format!("不支持的导出格式: {}", other)),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+/// 获取终端会话录制的章节列表
+///
+/// # 参数
+/// - `session_id`: 会话 ID
+#[tauri::command]
+pub async fn terminal_list_recording_chapters(
+    state: State<'_, TerminalManagerState>,
+    session_id: String,
+) -> Result<Vec<RecordingChapter>, String> {
+    let guard = state.inner().0.read().await;
+    let manager = guard
+        .as_ref()
+        .ok_or_else(|| "终端管理器未初始化".to_string())?;
+
+    manager
+        .list_recording_chapters(&session_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 将终端会话录制回放到新的块
+///
+/// # 参数
+/// - `session_id`: 会话 ID
+/// - `speed`: 回放速度倍率（默认 1.0）
+///
+/// # 返回
+/// 新块 ID（前端按该 ID 监听输出事件）
+#[tauri::command]
+pub async fn terminal_replay_recording(
+    state: State<'_, TerminalManagerState>,
+    session_id: String,
+    speed: Option<f64>,
+) -> Result<String, String> {
+    let guard = state.inner().0.read().await;
+    let manager = guard
+        .as_ref()
+        .ok_or_else(|| "终端管理器未初始化".to_string())?;
+
+    manager
+        .replay_recording(&session_id, speed)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 搜索命令历史
+///
+/// # 参数
+/// - `query`: 查询条件（全文、主机、工作目录、退出状态、时间范围）
+#[tauri::command]
+pub async fn terminal_search_command_history(
+    state: State<'_, TerminalManagerState>,
+    query: CommandHistoryQuery,
+) -> Result<Vec<CommandHistoryHit>, String> {
+    let guard = state.inner().0.read().await;
+    let manager = guard
+        .as_ref()
+        .ok_or_else(|| "终端管理器未初始化".to_string())?;
+
+    manager
+        .search_command_history(&query)
+        .map_err(|e| e.to_string())
+}
+
+/// 在会话中重新执行历史命令
+///
+/// # 参数
+/// - `entry_id`: 命令历史记录 ID
+/// - `session_id`: 目标会话 ID
+#[tauri::command]
+pub async fn terminal_rerun_command(
+    state: State<'_, TerminalManagerState>,
+    entry_id: i64,
+    session_id: String,
+) -> Result<CommandHistoryEntry, String> {
+    let guard = state.inner().0.read().await;
+    let manager = guard
+        .as_ref()
+        .ok_or_else(|| "终端管理器未初始化".to_string())?;
+
+    manager
+        .rerun_command(entry_id, &session_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 删除命令历史记录
+///
+/// # 参数
+/// - `entry_id`: 命令历史记录 ID
+#[tauri::command]
+pub async fn terminal_delete_command_history(
+    state: State<'_, TerminalManagerState>,
+    entry_id: i64,
+) -> Result<(), String> {
+    let guard = state.inner().0.read().await;
+    let manager = guard
+        .as_ref()
+        .ok_or_else(|| "终端管理器未初始化".to_string())?;
+
+    manager
+        .delete_command_history(entry_id)
+        .map_err(|e| e.to_string())
+}
@@ -1,8 +1,8 @@
 //! 工具钩子管理相关的 Tauri 命令
 
 use crate::services::tool_hooks_service::{
-    HookAction, HookCondition, HookContext, HookExecutionStats, HookRule, HookTrigger,
-    ToolHooksService,
+    HookAction, HookCondition, HookContext, HookExecutionStats, HookOutcome, HookRule,
+    HookTrigger, ToolHooksService,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -23,6 +23,8 @@ pub struct HookContextData {
     pub session_id: String,
     pub tool_name: Option<String>,
     pub tool_parameters: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub tool_input: Option<serde_json::Value>,
     pub tool_result: Option<String>,
     pub message_content: Option<String>,
     pub message_count: usize,
@@ -36,6 +38,7 @@ impl From<HookContextData> for HookContext {
             session_id: data.session_id,
             tool_name: data.tool_name,
             tool_parameters: data.tool_parameters,
+            tool_input: data.tool_input,
             tool_result: data.tool_result,
             message_content: data.message_content,
             message_count: data.message_count,
@@ -49,15 +52,15 @@ impl From<HookContextData> for HookContext {
 pub async fn execute_hooks(
     hooks_service: State<'_, ToolHooksServiceState>,
     request: ExecuteHooksRequest,
-) -> Result<(), String> {
+) -> Result<HookOutcome, String> {
     debug!(
         "执行钩子: {:?} (会话: {})",
         request.trigger, request.context.session_id
     );
     let context: HookContext = request.context.into();
-    hooks_service.0.execute_hooks(request.trigger, &context)?;
+    let outcome = hooks_service.0.run_hooks(request.trigger, &context).await?;
     info!("钩子执行完成");
-    Ok(())
+    Ok(outcome)
 }
 
 #[tauri::command]
@@ -21,13 +21,16 @@ pub use hot_reload::{
 pub use import::{ImportOptions, ImportService, ValidationResult};
 pub use path_utils::{collapse_tilde, contains_tilde, expand_tilde};
 pub use types::{
-    generate_secure_api_key, AmpConfig, AmpModelMapping, ApiKeyEntry, Config, CredentialEntry,
-    CredentialPoolConfig, CustomProviderConfig, EndpointProvidersConfig, ExperimentalFeatures,
-    GeminiApiKeyEntry, InjectionRuleConfig, InjectionSettings, LoggingConfig, ModelInfo,
-    ModelsConfig, NativeAgentConfig, ProviderConfig, ProviderModelsConfig, ProvidersConfig,
-    QuotaExceededConfig, RemoteManagementConfig, RetrySettings, RoutingConfig,
-    ScreenshotChatConfig, ServerConfig, TlsConfig, VertexApiKeyEntry, VertexModelAlias,
-    DEFAULT_API_KEY,
+    generate_secure_api_key, AmpConfig, AmpModelMapping, ApiKeyEntry, BatchConfig,
+    CommandPolicyAction, CommandPolicyConfig, CommandPolicyRule, CompactionConfig,
+    ConcurrencyConfig, Config, CredentialEntry, CredentialPoolConfig, CustomProviderConfig,
+    EndpointProvidersConfig, ExperimentalFeatures, ForwardProxyConfig, GeminiApiKeyEntry,
+    GuardrailAction, GuardrailRule, GuardrailsConfig, InjectionRuleConfig, InjectionSettings,
+    LoggingConfig, McpGatewayConfig, ModelInfo, ModelsConfig, NativeAgentConfig, PromptCacheConfig,
+    ProviderConfig, ProviderModelsConfig, ProvidersConfig, QuotaExceededConfig,
+    RemoteManagementConfig, RetrySettings, RoutingConfig, ScreenshotChatConfig, ServerConfig,
+    StructuredOutputConfig, SubAgentConfig, TlsConfig, ToolCallFormat, ToolEmulationConfig,
+    VertexApiKeyEntry, VertexModelAlias, DEFAULT_API_KEY,
 };
 pub use yaml::{load_config, save_config, ConfigError, ConfigManager, YamlService};
 
@@ -75,6 +75,7 @@ impl GlobalConfigManager {
         let router_observer = Arc::new(RouterObserver::new(
             processor.router.clone(),
             processor.mapper.clone(),
+            processor.fallback.clone(),
         ));
         self.subject.register(router_observer);
 
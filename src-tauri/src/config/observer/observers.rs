@@ -6,7 +6,7 @@ use super::events::ConfigChangeEvent;
 use super::traits::ConfigObserver;
 use crate::config::{Config, EndpointProvidersConfig};
 use crate::injection::Injector;
-use crate::router::{ModelMapper, Router};
+use crate::router::{FallbackResolver, ModelMapper, Router};
 use async_trait::async_trait;
 use std::sync::Arc;
 use tauri::{AppHandle, Emitter};
@@ -14,15 +14,24 @@ use tokio::sync::RwLock;
 
 /// 路由器观察者
 ///
-/// 监听路由配置变更，更新 Router 和 ModelMapper
+/// 监听路由配置变更，更新 Router、ModelMapper 和 FallbackResolver
 pub struct RouterObserver {
     router: Arc<RwLock<Router>>,
     mapper: Arc<RwLock<ModelMapper>>,
+    fallback: Arc<RwLock<FallbackResolver>>,
 }
 
 impl RouterObserver {
-    pub fn new(router: Arc<RwLock<Router>>, mapper: Arc<RwLock<ModelMapper>>) -> Self {
-        Self { router, mapper }
+    pub fn new(
+        router: Arc<RwLock<Router>>,
+        mapper: Arc<RwLock<ModelMapper>>,
+        fallback: Arc<RwLock<FallbackResolver>>,
+    ) -> Self {
+        Self {
+            router,
+            mapper,
+            fallback,
+        }
     }
 }
 
@@ -75,6 +84,16 @@ impl ConfigObserver for RouterObserver {
             );
         }
 
+        // 更新回退链
+        {
+            let mut fallback = self.fallback.write().await;
+            fallback.set_chains(config.routing.fallback_chains.clone());
+            tracing::debug!(
+                "[RouterObserver] 更新回退链: {} 条",
+                config.routing.fallback_chains.len()
+            );
+        }
+
         Ok(())
     }
 }
@@ -313,12 +332,35 @@ mod tests {
     async fn test_router_observer_priority() {
         let router = Arc::new(RwLock::new(Router::default()));
         let mapper = Arc::new(RwLock::new(ModelMapper::new()));
-        let observer = RouterObserver::new(router, mapper);
+        let fallback = Arc::new(RwLock::new(FallbackResolver::new()));
+        let observer = RouterObserver::new(router, mapper, fallback);
 
         assert_eq!(observer.name(), "RouterObserver");
         assert_eq!(observer.priority(), 10);
     }
 
+    #[tokio::test]
+    async fn test_router_observer_updates_fallback_chains() {
+        let router = Arc::new(RwLock::new(Router::default()));
+        let mapper = Arc::new(RwLock::new(ModelMapper::new()));
+        let fallback = Arc::new(RwLock::new(FallbackResolver::new()));
+        let observer = RouterObserver::new(router, mapper, fallback.clone());
+
+        let mut config = Config::default();
+        config.routing.fallback_chains.insert(
+            "claude-sonnet-4-5".to_string(),
+            vec!["gpt-5".to_string(), "gemini-2.5-pro".to_string()],
+        );
+        let event = ConfigChangeEvent::FullReload(FullReloadEvent {
+            timestamp_ms: 0,
+            source: ConfigChangeSource::ApiCall,
+        });
+        observer.on_config_changed(&event, &config).await.unwrap();
+
+        let chain = fallback.read().await.resolve("claude-sonnet-4-5", None);
+        assert_eq!(chain.len(), 3);
+    }
+
     #[tokio::test]
     async fn test_injector_observer_interest() {
         let injector = Arc::new(RwLock::new(Injector::new()));
@@ -116,6 +116,7 @@ fn arb_routing_config() -> impl Strategy<Value = RoutingConfig> {
         .prop_map(|(default_provider, model_aliases)| RoutingConfig {
             default_provider,
             model_aliases,
+            fallback_chains: std::collections::HashMap::new(),
         })
 }
 
@@ -126,13 +127,17 @@ fn arb_retry_settings() -> impl Strategy<Value = RetrySettings> {
         100u64..5000u64,
         5000u64..60000u64,
         any::<bool>(),
+        0u64..5000u64,
     )
         .prop_map(
-            |(max_retries, base_delay_ms, max_delay_ms, auto_switch_provider)| RetrySettings {
-                max_retries,
-                base_delay_ms,
-                max_delay_ms,
-                auto_switch_provider,
+            |(max_retries, base_delay_ms, max_delay_ms, auto_switch_provider, hedge_delay_ms)| {
+                RetrySettings {
+                    max_retries,
+                    base_delay_ms,
+                    max_delay_ms,
+                    auto_switch_provider,
+                    hedge_delay_ms,
+                }
             },
         )
 }
@@ -181,6 +186,14 @@ fn arb_config() -> impl Strategy<Value = Config> {
             credential_pool: crate::config::CredentialPoolConfig::default(),
             remote_management: crate::config::RemoteManagementConfig::default(),
             quota_exceeded: crate::config::QuotaExceededConfig::default(),
+            concurrency: crate::config::ConcurrencyConfig::default(),
+            structured_output: crate::config::StructuredOutputConfig::default(),
+            prompt_cache: crate::config::PromptCacheConfig::default(),
+            tool_emulation: crate::config::ToolEmulationConfig::default(),
+            guardrails: crate::config::GuardrailsConfig::default(),
+            mcp_gateway: crate::config::McpGatewayConfig::default(),
+            batch: crate::config::BatchConfig::default(),
+            forward_proxy: crate::config::ForwardProxyConfig::default(),
             proxy_url: None,
             ampcode: crate::config::AmpConfig::default(),
             endpoint_providers: crate::config::EndpointProvidersConfig::default(),
@@ -364,13 +377,17 @@ fn arb_valid_retry_settings() -> impl Strategy<Value = RetrySettings> {
         1u64..5000u64,     // base_delay_ms > 0
         5000u64..60000u64, // max_delay_ms
         any::<bool>(),
+        0u64..5000u64,
     )
         .prop_map(
-            |(max_retries, base_delay_ms, max_delay_ms, auto_switch_provider)| RetrySettings {
-                max_retries,
-                base_delay_ms,
-                max_delay_ms,
-                auto_switch_provider,
+            |(max_retries, base_delay_ms, max_delay_ms, auto_switch_provider, hedge_delay_ms)| {
+                RetrySettings {
+                    max_retries,
+                    base_delay_ms,
+                    max_delay_ms,
+                    auto_switch_provider,
+                    hedge_delay_ms,
+                }
             },
         )
 }
@@ -419,6 +436,14 @@ fn arb_valid_config() -> impl Strategy<Value = Config> {
             credential_pool: crate::config::CredentialPoolConfig::default(),
             remote_management: crate::config::RemoteManagementConfig::default(),
             quota_exceeded: crate::config::QuotaExceededConfig::default(),
+            concurrency: crate::config::ConcurrencyConfig::default(),
+            structured_output: crate::config::StructuredOutputConfig::default(),
+            prompt_cache: crate::config::PromptCacheConfig::default(),
+            tool_emulation: crate::config::ToolEmulationConfig::default(),
+            guardrails: crate::config::GuardrailsConfig::default(),
+            mcp_gateway: crate::config::McpGatewayConfig::default(),
+            batch: crate::config::BatchConfig::default(),
+            forward_proxy: crate::config::ForwardProxyConfig::default(),
             proxy_url: None,
             ampcode: crate::config::AmpConfig::default(),
             endpoint_providers: crate::config::EndpointProvidersConfig::default(),
@@ -468,6 +493,14 @@ fn arb_invalid_config() -> impl Strategy<Value = Config> {
                     credential_pool: crate::config::CredentialPoolConfig::default(),
                     remote_management: crate::config::RemoteManagementConfig::default(),
                     quota_exceeded: crate::config::QuotaExceededConfig::default(),
+                    concurrency: crate::config::ConcurrencyConfig::default(),
+                    structured_output: crate::config::StructuredOutputConfig::default(),
+                    prompt_cache: crate::config::PromptCacheConfig::default(),
+                    tool_emulation: crate::config::ToolEmulationConfig::default(),
+                    guardrails: crate::config::GuardrailsConfig::default(),
+                    mcp_gateway: crate::config::McpGatewayConfig::default(),
+                    batch: crate::config::BatchConfig::default(),
+                    forward_proxy: crate::config::ForwardProxyConfig::default(),
                     proxy_url: None,
                     ampcode: crate::config::AmpConfig::default(),
                     endpoint_providers: crate::config::EndpointProvidersConfig::default(),
@@ -4,6 +4,7 @@
 //! 保持与旧版 JSON 配置的向后兼容性
 
 use crate::injection::{InjectionMode, InjectionRule};
+use crate::orchestrator::ServiceTier;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -271,6 +272,30 @@ pub struct Config {
     /// 配额超限配置
     #[serde(default)]
     pub quota_exceeded: QuotaExceededConfig,
+    /// 凭证并发与排队配置
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
+    /// 结构化输出配置
+    #[serde(default)]
+    pub structured_output: StructuredOutputConfig,
+    /// Anthropic Prompt Caching 断点配置
+    #[serde(default)]
+    pub prompt_cache: PromptCacheConfig,
+    /// 工具调用模拟配置
+    #[serde(default)]
+    pub tool_emulation: ToolEmulationConfig,
+    /// 出站防护配置
+    #[serde(default)]
+    pub guardrails: GuardrailsConfig,
+    /// MCP 网关配置
+    #[serde(default)]
+    pub mcp_gateway: McpGatewayConfig,
+    /// 批处理 API 配置
+    #[serde(default)]
+    pub batch: BatchConfig,
+    /// 正向代理（MITM）配置
+    #[serde(default)]
+    pub forward_proxy: ForwardProxyConfig,
     /// 全局代理 URL
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub proxy_url: Option<String>,
@@ -327,6 +352,33 @@ pub struct NativeAgentConfig {
     /// 默认最大 token 数
     #[serde(default = "default_max_tokens")]
     pub max_tokens: u32,
+    /// 终端命令审批策略
+    #[serde(default)]
+    pub command_policy: CommandPolicyConfig,
+    /// 子 Agent（task 工具）配置
+    #[serde(default)]
+    pub sub_agents: SubAgentConfig,
+    /// 上下文自动压缩配置
+    #[serde(default)]
+    pub compaction: CompactionConfig,
+    /// 同一轮中同时执行的工具调用上限（1 表示顺序执行）
+    #[serde(default = "default_agent_max_parallel_tools")]
+    pub max_parallel_tools: usize,
+    /// 独占执行的工具（不与其他工具并发，如共享终端会话或浏览器页面的工具）
+    #[serde(default = "default_agent_exclusive_tools")]
+    pub exclusive_tools: Vec<String>,
+}
+
+fn default_agent_max_parallel_tools() -> usize {
+    4
+}
+
+fn default_agent_exclusive_tools() -> Vec<String> {
+    vec![
+        "bash".to_string(),
+        "terminal".to_string(),
+        "browser".to_string(),
+    ]
 }
 
 fn default_use_default_prompt() -> bool {
@@ -354,10 +406,204 @@ impl Default for NativeAgentConfig {
             default_model: default_agent_model(),
             temperature: default_temperature(),
             max_tokens: default_max_tokens(),
+            command_policy: CommandPolicyConfig::default(),
+            sub_agents: SubAgentConfig::default(),
+            compaction: CompactionConfig::default(),
+            max_parallel_tools: default_agent_max_parallel_tools(),
+            exclusive_tools: default_agent_exclusive_tools(),
         }
     }
 }
 
+/// 子 Agent 配置
+///
+/// Agent 通过 task 工具启动子 Agent 处理独立的子任务，
+/// 同一轮中的多个 task 调用按 `max_parallel_tools` 并发执行。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubAgentConfig {
+    /// 是否启用 task 工具
+    #[serde(default = "default_sub_agents_enabled")]
+    pub enabled: bool,
+    /// 最大嵌套深度（1 表示子 Agent 不能再启动子 Agent）
+    #[serde(default = "default_sub_agent_max_depth")]
+    pub max_depth: usize,
+    /// 未指定等级时使用的服务等级
+    #[serde(default)]
+    pub default_tier: ServiceTier,
+    /// 子 Agent 最大工具迭代次数
+    #[serde(default = "default_sub_agent_max_iterations")]
+    pub max_iterations: usize,
+    /// 返回给父 Agent 的结果最大字符数
+    #[serde(default = "default_sub_agent_max_result_chars")]
+    pub max_result_chars: usize,
+}
+
+fn default_sub_agents_enabled() -> bool {
+    true
+}
+
+fn default_sub_agent_max_depth() -> usize {
+    1
+}
+
+fn default_sub_agent_max_iterations() -> usize {
+    20
+}
+
+fn default_sub_agent_max_result_chars() -> usize {
+    4000
+}
+
+impl Default for SubAgentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_sub_agents_enabled(),
+            max_depth: default_sub_agent_max_depth(),
+            default_tier: ServiceTier::default(),
+            max_iterations: default_sub_agent_max_iterations(),
+            max_result_chars: default_sub_agent_max_result_chars(),
+        }
+    }
+}
+
+/// 上下文自动压缩配置
+///
+/// 会话估算的 token 数超过模型上下文的 `threshold_ratio` 时，先截断较早的工具输出，
+/// 仍超过 `target_ratio` 时由模型对较早的消息生成摘要。置顶消息始终原样保留。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CompactionConfig {
+    /// 是否启用
+    #[serde(default = "default_compaction_enabled")]
+    pub enabled: bool,
+    /// 触发压缩的上下文占用比例
+    #[serde(default = "default_compaction_threshold_ratio")]
+    pub threshold_ratio: f32,
+    /// 压缩后的目标占用比例
+    #[serde(default = "default_compaction_target_ratio")]
+    pub target_ratio: f32,
+    /// 原样保留的最近消息数量（不拆分工具调用和工具结果）
+    #[serde(default = "default_compaction_keep_recent_messages")]
+    pub keep_recent_messages: usize,
+    /// 工具输出截断后保留的字符数
+    #[serde(default = "default_compaction_tool_result_max_chars")]
+    pub tool_result_max_chars: usize,
+    /// 模型注册表中没有上下文长度时使用的值
+    #[serde(default = "default_compaction_context_window")]
+    pub default_context_window: u32,
+    /// 生成摘要使用的模型（None 表示使用当前会话模型）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary_model: Option<String>,
+}
+
+fn default_compaction_enabled() -> bool {
+    true
+}
+
+fn default_compaction_threshold_ratio() -> f32 {
+    0.8
+}
+
+fn default_compaction_target_ratio() -> f32 {
+    0.5
+}
+
+fn default_compaction_keep_recent_messages() -> usize {
+    6
+}
+
+fn default_compaction_tool_result_max_chars() -> usize {
+    2000
+}
+
+fn default_compaction_context_window() -> u32 {
+    128_000
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_compaction_enabled(),
+            threshold_ratio: default_compaction_threshold_ratio(),
+            target_ratio: default_compaction_target_ratio(),
+            keep_recent_messages: default_compaction_keep_recent_messages(),
+            tool_result_max_chars: default_compaction_tool_result_max_chars(),
+            default_context_window: default_compaction_context_window(),
+            summary_model: None,
+        }
+    }
+}
+
+/// 终端命令审批策略配置
+///
+/// Agent 通过 terminal 工具执行命令前按规则决定自动执行、拒绝或交由用户审批。
+/// 优先级：内置危险命令 > deny 规则 > 会话授权 > ask 规则 > allow 规则 > 默认动作。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommandPolicyConfig {
+    /// 是否启用（关闭后所有命令都需审批，内置危险命令仍会被拒绝）
+    #[serde(default = "default_command_policy_enabled")]
+    pub enabled: bool,
+    /// 是否启用内置危险命令拒绝列表（rm -rf /、curl | sh、强制推送等）
+    #[serde(default = "default_command_policy_enabled")]
+    pub builtin_denylist: bool,
+    /// 未命中任何规则时的动作
+    #[serde(default)]
+    pub default_action: CommandPolicyAction,
+    /// 自定义规则
+    #[serde(default)]
+    pub rules: Vec<CommandPolicyRule>,
+}
+
+fn default_command_policy_enabled() -> bool {
+    true
+}
+
+impl Default for CommandPolicyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_command_policy_enabled(),
+            builtin_denylist: default_command_policy_enabled(),
+            default_action: CommandPolicyAction::default(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+/// 命令审批规则
+///
+/// 已设置的匹配条件需全部满足。复合命令（`&&`、`|`、`;` 等）按子命令分别匹配。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommandPolicyRule {
+    /// 规则名称（用于日志）
+    pub name: String,
+    /// 命中后的动作
+    pub action: CommandPolicyAction,
+    /// 命令前缀（按词匹配，`git status` 不匹配 `git statuses`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// 正则表达式
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+    /// 参数模式，`*` 匹配任意单个参数，末尾的 `**` 匹配其余参数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub argv: Option<Vec<String>>,
+    /// 工作目录（支持 ~，匹配该目录及其子目录）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+}
+
+/// 命令审批动作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandPolicyAction {
+    /// 自动执行
+    Allow,
+    /// 拒绝执行
+    Deny,
+    /// 交由用户审批
+    #[default]
+    Ask,
+}
+
 // ============ 实验室功能配置类型 ============
 
 /// 截图对话功能配置
@@ -573,6 +819,388 @@ impl Default for QuotaExceededConfig {
     }
 }
 
+/// 凭证并发与排队配置
+///
+/// 限制每个凭证、每个 Provider 的同时在途请求数，超出的请求按优先级排队等待
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConcurrencyConfig {
+    /// 每个凭证的最大在途请求数（0 表示不限制）
+    #[serde(default)]
+    pub max_in_flight_per_credential: u32,
+    /// 每个 Provider 的最大在途请求数（0 表示不限制）
+    #[serde(default)]
+    pub max_in_flight_per_provider: u32,
+    /// 排队等待超时（毫秒）
+    #[serde(default = "default_queue_timeout_ms")]
+    pub queue_timeout_ms: u64,
+    /// 每个 Provider 的最大排队请求数
+    #[serde(default = "default_max_queue_depth")]
+    pub max_queue_depth: usize,
+    /// 视为批处理优先级的客户端（客户端类型配置键，如 `codex`）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub batch_clients: Vec<String>,
+}
+
+fn default_queue_timeout_ms() -> u64 {
+    30_000
+}
+
+fn default_max_queue_depth() -> usize {
+    100
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight_per_credential: 0,
+            max_in_flight_per_provider: 0,
+            queue_timeout_ms: default_queue_timeout_ms(),
+            max_queue_depth: default_max_queue_depth(),
+            batch_clients: Vec::new(),
+        }
+    }
+}
+
+/// 结构化输出配置
+///
+/// 对不支持 `response_format` 的后端，由代理注入 schema 说明并校验输出
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StructuredOutputConfig {
+    /// 是否启用代理侧兜底（注入说明 + 校验 + 修复）
+    #[serde(default = "default_structured_fallback_enabled")]
+    pub fallback_enabled: bool,
+    /// 校验失败后的最大修复重试次数
+    #[serde(default = "default_max_repair_attempts")]
+    pub max_repair_attempts: u32,
+    /// 是否也校验原生支持后端的输出（仅记录，不重试）
+    #[serde(default = "default_validate_native")]
+    pub validate_native: bool,
+}
+
+fn default_structured_fallback_enabled() -> bool {
+    true
+}
+
+fn default_max_repair_attempts() -> u32 {
+    2
+}
+
+fn default_validate_native() -> bool {
+    true
+}
+
+impl Default for StructuredOutputConfig {
+    fn default() -> Self {
+        Self {
+            fallback_enabled: true,
+            max_repair_attempts: default_max_repair_attempts(),
+            validate_native: true,
+        }
+    }
+}
+
+/// Anthropic Prompt Caching 配置
+///
+/// 转发到 Claude API Key / Anthropic API Key 凭证时自动插入 `cache_control` 断点
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptCacheConfig {
+    /// 是否启用（默认关闭）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 在工具定义末尾插入断点
+    #[serde(default = "default_cache_breakpoint_enabled")]
+    pub cache_tools: bool,
+    /// 在系统提示词末尾插入断点
+    #[serde(default = "default_cache_breakpoint_enabled")]
+    pub cache_system: bool,
+    /// 在对话的稳定前缀末尾插入断点
+    #[serde(default = "default_cache_breakpoint_enabled")]
+    pub cache_conversation: bool,
+}
+
+fn default_cache_breakpoint_enabled() -> bool {
+    true
+}
+
+impl Default for PromptCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cache_tools: true,
+            cache_system: true,
+            cache_conversation: true,
+        }
+    }
+}
+
+/// 工具调用模拟配置
+///
+/// 部分模型不支持原生函数调用（或会直接拒绝带 tools 的请求），命中的模型由代理
+/// 把工具定义写入提示词，再从输出文本中解析工具调用并还原为标准格式
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ToolEmulationConfig {
+    /// 需要模拟工具调用的模型（支持通配符，如 `deepseek-r1*`）
+    #[serde(default)]
+    pub models: Vec<String>,
+    /// 要求模型输出工具调用时使用的格式
+    #[serde(default)]
+    pub format: ToolCallFormat,
+}
+
+/// 模拟工具调用的输出格式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolCallFormat {
+    /// `<tool_call><name>..</name><arguments>{..}</arguments></tool_call>`
+    #[default]
+    Xml,
+    /// `<tool_call>{"name": .., "arguments": {..}}</tool_call>`
+    Json,
+}
+
+/// 出站防护配置
+///
+/// 请求发往上游前检测消息内容与工具结果中的密钥、邮箱、手机号等敏感数据，
+/// 按规则拦截、打码或替换为可还原的占位符
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GuardrailsConfig {
+    /// 是否启用（默认关闭）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 启用的内置检测器：api_key、private_key、email、phone、credit_card、id_card
+    #[serde(default = "default_guardrail_detectors")]
+    pub detectors: Vec<String>,
+    /// 默认处理动作
+    #[serde(default)]
+    pub action: GuardrailAction,
+    /// 按检测器覆盖处理动作
+    #[serde(default)]
+    pub actions: HashMap<String, GuardrailAction>,
+    /// 自定义正则规则
+    #[serde(default)]
+    pub custom_rules: Vec<GuardrailRule>,
+}
+
+fn default_guardrail_detectors() -> Vec<String> {
+    ["api_key", "private_key", "email", "phone"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+impl Default for GuardrailsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            detectors: default_guardrail_detectors(),
+            action: GuardrailAction::default(),
+            actions: HashMap::new(),
+            custom_rules: Vec::new(),
+        }
+    }
+}
+
+/// 自定义防护规则
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GuardrailRule {
+    /// 规则名称（用于审计记录和占位符）
+    pub name: String,
+    /// 匹配模式（正则表达式）
+    pub pattern: String,
+    /// 处理动作（未设置时使用默认动作）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<GuardrailAction>,
+}
+
+/// 检测到敏感数据时的处理动作
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GuardrailAction {
+    /// 拒绝请求
+    Block,
+    /// 替换为 `[REDACTED_*]`，不可还原
+    #[default]
+    Mask,
+    /// 替换为占位符，响应中还原为原值
+    Tokenize,
+}
+
+/// MCP 网关配置
+///
+/// 启用后由 ProxyCast 托管 `mcp_servers` 表中启用了 ProxyCast 的 MCP 服务器，
+/// 并通过 `/mcp` 端点统一对外提供。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct McpGatewayConfig {
+    /// 是否启用
+    #[serde(default)]
+    pub enabled: bool,
+    /// 单次调用超时（秒）
+    #[serde(default = "default_mcp_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// 客户端 API Key
+    ///
+    /// 键为客户端标识，值为该客户端连接 `/mcp` 时使用的 API Key。
+    /// 使用服务器 API Key 认证的请求视为所有者（`default`）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub client_keys: HashMap<String, String>,
+    /// 客户端白名单
+    ///
+    /// 键为客户端标识（`client_keys` 中的键或 `default`），
+    /// 值为允许访问的带命名空间名称模式（如 `github__*`）。
+    /// `client_keys` 中的客户端没有白名单时不能访问任何上游；所有者没有白名单时不受限制
+    #[serde(default)]
+    pub allowlists: HashMap<String, Vec<String>>,
+    /// 会话空闲超时（秒）
+    #[serde(default = "default_mcp_session_idle_secs")]
+    pub session_idle_secs: u64,
+    /// 最大会话数（超出时淘汰最久未使用的会话）
+    #[serde(default = "default_mcp_max_sessions")]
+    pub max_sessions: usize,
+}
+
+fn default_mcp_request_timeout_secs() -> u64 {
+    60
+}
+
+fn default_mcp_session_idle_secs() -> u64 {
+    1800
+}
+
+fn default_mcp_max_sessions() -> usize {
+    256
+}
+
+impl Default for McpGatewayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            request_timeout_secs: default_mcp_request_timeout_secs(),
+            client_keys: HashMap::new(),
+            allowlists: HashMap::new(),
+            session_idle_secs: default_mcp_session_idle_secs(),
+            max_sessions: default_mcp_max_sessions(),
+        }
+    }
+}
+
+/// 批处理 API 配置
+///
+/// `/v1/batches` 与 `/v1/messages/batches` 的任务持久化在 SQLite 中，
+/// 由后台执行器以批处理优先级逐条调用本地请求处理流程。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BatchConfig {
+    /// 是否启用
+    #[serde(default = "default_batch_enabled")]
+    pub enabled: bool,
+    /// 单个任务的并发请求数
+    #[serde(default = "default_batch_concurrency")]
+    pub concurrency: usize,
+    /// 每分钟最多发出的请求数（0 表示不限制）
+    #[serde(default)]
+    pub requests_per_minute: u32,
+    /// 单条请求的最大重试次数（429、5xx 与网络错误）
+    #[serde(default = "default_batch_max_retries")]
+    pub max_retries: u32,
+    /// 重试基础退避（毫秒），按指数增长，上游返回 Retry-After 时以其为准
+    #[serde(default = "default_batch_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    /// 单个任务的最大请求数
+    #[serde(default = "default_batch_max_requests")]
+    pub max_requests: usize,
+}
+
+fn default_batch_enabled() -> bool {
+    true
+}
+
+fn default_batch_concurrency() -> usize {
+    4
+}
+
+fn default_batch_max_retries() -> u32 {
+    3
+}
+
+fn default_batch_retry_backoff_ms() -> u64 {
+    2000
+}
+
+fn default_batch_max_requests() -> usize {
+    50_000
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            concurrency: default_batch_concurrency(),
+            requests_per_minute: 0,
+            max_retries: default_batch_max_retries(),
+            retry_backoff_ms: default_batch_retry_backoff_ms(),
+            max_requests: default_batch_max_requests(),
+        }
+    }
+}
+
+/// 正向代理（MITM）配置
+///
+/// 供无法修改 Base URL 的客户端使用：通过 HTTP CONNECT 代理访问时，
+/// `intercept_hosts` 中的主机由本地 CA 签发证书解密，请求交给常规处理流程；
+/// 其他主机原样隧道转发；配置了 `allowed_hosts` 时只放行列表中的主机。
+/// 监听非回环地址时必须配置 `auth_token`。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ForwardProxyConfig {
+    /// 是否启用
+    #[serde(default)]
+    pub enabled: bool,
+    /// 监听地址
+    #[serde(default = "default_forward_proxy_host")]
+    pub host: String,
+    /// 监听端口
+    #[serde(default = "default_forward_proxy_port")]
+    pub port: u16,
+    /// 解密并交给常规处理流程的主机（支持通配符）
+    #[serde(default = "default_forward_proxy_intercept_hosts")]
+    pub intercept_hosts: Vec<String>,
+    /// 允许隧道转发的主机（支持通配符）；为空时放行所有主机，
+    /// 非空时不在列表和 `intercept_hosts` 中的主机返回 403
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    /// 代理认证凭证，客户端通过 `Proxy-Authorization` 携带
+    /// （`Basic` 的密码或 `Bearer` 令牌）；未配置时只允许监听回环地址
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+}
+
+fn default_forward_proxy_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_forward_proxy_port() -> u16 {
+    8998
+}
+
+fn default_forward_proxy_intercept_hosts() -> Vec<String> {
+    vec![
+        "api.anthropic.com".to_string(),
+        "api.openai.com".to_string(),
+        "generativelanguage.googleapis.com".to_string(),
+    ]
+}
+
+impl Default for ForwardProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            host: default_forward_proxy_host(),
+            port: default_forward_proxy_port(),
+            intercept_hosts: default_forward_proxy_intercept_hosts(),
+            allowed_hosts: Vec::new(),
+            auth_token: None,
+        }
+    }
+}
+
 /// Amp CLI 模型映射
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AmpModelMapping {
@@ -736,6 +1364,9 @@ pub struct RoutingConfig {
     /// 模型别名映射
     #[serde(default)]
     pub model_aliases: HashMap<String, String>,
+    /// 跨协议回退链（模型名 -> 有序回退列表，元素格式为 `model` 或 `provider:model`）
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub fallback_chains: HashMap<String, Vec<String>>,
 }
 
 fn default_provider() -> String {
@@ -747,6 +1378,7 @@ impl Default for RoutingConfig {
         Self {
             default_provider: default_provider(),
             model_aliases: HashMap::new(),
+            fallback_chains: HashMap::new(),
         }
     }
 }
@@ -766,6 +1398,11 @@ pub struct RetrySettings {
     /// 是否自动切换 Provider
     #[serde(default = "default_auto_switch")]
     pub auto_switch_provider: bool,
+    /// 对冲请求延迟（毫秒，0 表示不启用）
+    ///
+    /// 首选凭证超过该时间未返回时，向同 Provider 的另一个凭证发起相同请求
+    #[serde(default)]
+    pub hedge_delay_ms: u64,
 }
 
 fn default_max_retries() -> u32 {
@@ -791,6 +1428,7 @@ impl Default for RetrySettings {
             base_delay_ms: default_base_delay_ms(),
             max_delay_ms: default_max_delay_ms(),
             auto_switch_provider: default_auto_switch(),
+            hedge_delay_ms: 0,
         }
     }
 }
@@ -1297,6 +1935,14 @@ impl Default for Config {
             credential_pool: CredentialPoolConfig::default(),
             remote_management: RemoteManagementConfig::default(),
             quota_exceeded: QuotaExceededConfig::default(),
+            concurrency: ConcurrencyConfig::default(),
+            structured_output: StructuredOutputConfig::default(),
+            prompt_cache: PromptCacheConfig::default(),
+            tool_emulation: ToolEmulationConfig::default(),
+            guardrails: GuardrailsConfig::default(),
+            mcp_gateway: McpGatewayConfig::default(),
+            batch: BatchConfig::default(),
+            forward_proxy: ForwardProxyConfig::default(),
             proxy_url: None,
             ampcode: AmpConfig::default(),
             endpoint_providers: EndpointProvidersConfig::default(),
@@ -46,11 +46,86 @@ pub fn convert_anthropic_to_openai(request: &AnthropicMessagesRequest) -> ChatCo
         messages: openai_messages,
         temperature: request.temperature,
         max_tokens: request.max_tokens,
-        top_p: None,
+        top_p: request.top_p,
         stream: request.stream,
         tools,
-        tool_choice: request.tool_choice.clone(),
-        reasoning_effort: None,
+        tool_choice: request.tool_choice.as_ref().map(convert_tool_choice),
+        reasoning_effort: request
+            .thinking
+            .as_ref()
+            .and_then(thinking_to_reasoning_effort),
+        stop: request
+            .stop_sequences
+            .as_ref()
+            .filter(|s| !s.is_empty())
+            .map(|s| StopSequence::Multiple(s.clone())),
+        user: request.metadata.as_ref().and_then(|m| m.user_id.clone()),
+        service_tier: request
+            .service_tier
+            .as_deref()
+            .and_then(convert_service_tier),
+        ..Default::default()
+    }
+}
+
+/// 返回转换到 OpenAI 格式时会被丢弃的 Anthropic 参数
+pub fn anthropic_to_openai_unsupported_params(request: &AnthropicMessagesRequest) -> Vec<String> {
+    let mut params = Vec::new();
+    if request.top_k.is_some() {
+        params.push("top_k".to_string());
+    }
+    if let Some(tier) = &request.service_tier {
+        if convert_service_tier(tier).is_none() {
+            params.push("service_tier".to_string());
+        }
+    }
+    params.extend(request.extra.keys().cloned());
+    params
+}
+
+/// 转换 tool_choice
+///
+/// Anthropic: {"type": "auto" | "any" | "none"} 或 {"type": "tool", "name": "xxx"}
+/// OpenAI: "auto" | "required" | "none" 或 {"type": "function", "function": {"name": "xxx"}}
+fn convert_tool_choice(choice: &serde_json::Value) -> serde_json::Value {
+    let choice_type = choice.get("type").and_then(|t| t.as_str());
+    match choice_type {
+        Some("auto") => serde_json::json!("auto"),
+        Some("any") => serde_json::json!("required"),
+        Some("none") => serde_json::json!("none"),
+        Some("tool") => match choice.get("name").and_then(|n| n.as_str()) {
+            Some(name) => serde_json::json!({
+                "type": "function",
+                "function": { "name": name }
+            }),
+            None => serde_json::json!("required"),
+        },
+        _ => choice.clone(),
+    }
+}
+
+/// 将 thinking 预算映射为 reasoning_effort
+fn thinking_to_reasoning_effort(thinking: &AnthropicThinking) -> Option<String> {
+    if !thinking.is_enabled() {
+        return None;
+    }
+    let effort = match thinking.budget_tokens {
+        Some(budget) if budget <= 4096 => "low",
+        Some(budget) if budget <= 16384 => "medium",
+        Some(_) => "high",
+        None => "medium",
+    };
+    Some(effort.to_string())
+}
+
+/// 转换 service_tier
+///
+/// Anthropic 只有 auto 和 standard_only，分别对应 OpenAI 的 auto 和 default。
+fn convert_service_tier(tier: &str) -> Option<String> {
+    match tier {
+        "auto" => Some("auto".to_string()),
+        "standard_only" => Some("default".to_string()),
+        _ => None,
     }
 }
 
@@ -202,3 +277,43 @@ fn extract_tool_result_content(content: Option<&serde_json::Value>) -> String {
         _ => String::new(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_sampling_and_thinking_params() {
+        let request: AnthropicMessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "messages": [{"role": "user", "content": "Hello"}],
+            "top_p": 0.9,
+            "top_k": 40,
+            "stop_sequences": ["END"],
+            "metadata": {"user_id": "u-1"},
+            "thinking": {"type": "enabled", "budget_tokens": 8000},
+            "tool_choice": {"type": "tool", "name": "get_weather"},
+            "container": "c-1"
+        }))
+        .unwrap();
+
+        let openai = convert_anthropic_to_openai(&request);
+        assert_eq!(openai.top_p, Some(0.9));
+        assert_eq!(
+            openai.stop,
+            Some(StopSequence::Multiple(vec!["END".into()]))
+        );
+        assert_eq!(openai.user.as_deref(), Some("u-1"));
+        assert_eq!(openai.reasoning_effort.as_deref(), Some("medium"));
+        assert_eq!(
+            openai.tool_choice,
+            Some(serde_json::json!({"type": "function", "function": {"name": "get_weather"}}))
+        );
+
+        assert_eq!(
+            anthropic_to_openai_unsupported_params(&request),
+            vec!["top_k", "container"]
+        );
+    }
+}
@@ -129,10 +129,27 @@ pub struct GeminiGenerationConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub candidate_count: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_logprobs: Option<bool>,
+    /// 返回的候选 token 数量（对应 OpenAI top_logprobs）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub thinking_config: Option<ThinkingConfig>,
     /// 响应模态（TEXT, IMAGE）
     #[serde(skip_serializing_if = "Option::is_none")]
     pub response_modalities: Option<Vec<String>>,
+    /// 结构化输出 MIME 类型（application/json）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_mime_type: Option<String>,
+    /// 结构化输出 JSON Schema（对应 OpenAI json_schema）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -559,15 +576,26 @@ pub fn convert_openai_to_antigravity_with_context(
     // 构建生成配置
     let mut generation_config = GeminiGenerationConfig {
         temperature: request.temperature,
-        max_output_tokens: request.max_tokens.map(|t| t as i32),
+        max_output_tokens: request.effective_max_tokens().map(|t| t as i32),
         top_p: request.top_p,
         top_k: None,
-        stop_sequences: None,
-        candidate_count: None,
+        stop_sequences: request.stop.as_ref().map(StopSequence::to_vec),
+        candidate_count: request.n.map(|n| n as i32),
+        presence_penalty: request.presence_penalty,
+        frequency_penalty: request.frequency_penalty,
+        seed: request.seed,
+        response_logprobs: request.logprobs,
+        logprobs: request.top_logprobs.map(|n| n as i32),
         thinking_config: None,
         response_modalities: None,
+        response_mime_type: None,
+        response_schema: None,
     };
 
+    if let Some(format) = &request.response_format {
+        apply_response_format(&mut generation_config, format);
+    }
+
     // 为图片生成模型设置 response_modalities
     if is_image_generation_model(actual_model) {
         generation_config.response_modalities = Some(vec!["TEXT".to_string(), "IMAGE".to_string()]);
@@ -679,11 +707,7 @@ pub fn convert_openai_to_antigravity_with_context(
 
     // 构建 toolConfig（如果有工具定义）
     let tool_config: Option<serde_json::Value> = if tools.is_some() {
-        Some(serde_json::json!({
-            "functionCallingConfig": {
-                "mode": "AUTO"
-            }
-        }))
+        Some(convert_tool_choice(request.tool_choice.as_ref()))
     } else {
         None
     };
@@ -768,11 +792,101 @@ fn clean_value(value: serde_json::Value) -> serde_json::Value {
     }
 }
 
+/// 将 OpenAI response_format 映射为 responseMimeType / responseSchema
+///
+/// - {"type": "json_object"} → application/json
+/// - {"type": "json_schema", "json_schema": {"schema": ...}} → application/json + 清理后的 schema
+/// - {"type": "text"} → 不设置
+fn apply_response_format(config: &mut GeminiGenerationConfig, format: &serde_json::Value) {
+    match format.get("type").and_then(|t| t.as_str()) {
+        Some("json_object") => {
+            config.response_mime_type = Some("application/json".to_string());
+        }
+        Some("json_schema") => {
+            config.response_mime_type = Some("application/json".to_string());
+            config.response_schema = clean_parameters(format["json_schema"].get("schema").cloned());
+        }
+        _ => {}
+    }
+}
+
 /// 兼容旧接口
 pub fn convert_openai_to_antigravity(request: &ChatCompletionRequest) -> serde_json::Value {
     convert_openai_to_antigravity_with_context(request, "")
 }
 
+/// Antigravity 能映射的 OpenAI 参数
+const ANTIGRAVITY_SUPPORTED_PARAMS: &[&str] = &[
+    "temperature",
+    "max_tokens",
+    "max_completion_tokens",
+    "top_p",
+    "tools",
+    "tool_choice",
+    "reasoning_effort",
+    "stop",
+    "n",
+    "presence_penalty",
+    "frequency_penalty",
+    "seed",
+    "logprobs",
+    "top_logprobs",
+    "response_format",
+];
+
+/// 返回转换到 Antigravity 时会被丢弃的 OpenAI 参数
+pub fn openai_to_antigravity_unsupported_params(request: &ChatCompletionRequest) -> Vec<String> {
+    request
+        .present_params()
+        .into_iter()
+        .filter(|p| !ANTIGRAVITY_SUPPORTED_PARAMS.contains(&p.as_str()))
+        .collect()
+}
+
+/// 将 tool_choice 转换为 functionCallingConfig
+///
+/// - "auto" / 未设置 → AUTO
+/// - "none" → NONE
+/// - "required" / {"type": "any"} → ANY
+/// - {"type": "function", "function": {"name": "xxx"}} / {"type": "tool", "name": "xxx"}
+///   → ANY + allowedFunctionNames
+fn convert_tool_choice(tool_choice: Option<&serde_json::Value>) -> serde_json::Value {
+    let (mode, allowed): (&str, Option<&str>) = match tool_choice {
+        Some(serde_json::Value::String(s)) => match s.as_str() {
+            "none" => ("NONE", None),
+            "required" | "any" => ("ANY", None),
+            _ => ("AUTO", None),
+        },
+        Some(serde_json::Value::Object(obj)) => match obj.get("type").and_then(|t| t.as_str()) {
+            Some("none") => ("NONE", None),
+            Some("any") => ("ANY", None),
+            Some("function") => (
+                "ANY",
+                obj.get("function")
+                    .and_then(|f| f.get("name"))
+                    .and_then(|n| n.as_str()),
+            ),
+            Some("tool") => ("ANY", obj.get("name").and_then(|n| n.as_str())),
+            _ => ("AUTO", None),
+        },
+        _ => ("AUTO", None),
+    };
+
+    match allowed {
+        Some(name) => serde_json::json!({
+            "functionCallingConfig": {
+                "mode": mode,
+                "allowedFunctionNames": [name]
+            }
+        }),
+        None => serde_json::json!({
+            "functionCallingConfig": {
+                "mode": mode
+            }
+        }),
+    }
+}
+
 /// 转换用户消息内容
 fn convert_user_content(msg: &ChatMessage) -> Vec<GeminiPart> {
     let mut parts = Vec::new();
@@ -1677,3 +1791,103 @@ mod image_property_tests {
         }
     }
 }
+
+#[cfg(test)]
+mod param_tests {
+    use super::*;
+
+    fn request_with(extra: serde_json::Value) -> ChatCompletionRequest {
+        let mut json = serde_json::json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "user", "content": "Hello"}]
+        });
+        for (k, v) in extra.as_object().unwrap() {
+            json[k] = v.clone();
+        }
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_generation_config_maps_sampling_params() {
+        let request = request_with(serde_json::json!({
+            "stop": ["END"],
+            "seed": 7,
+            "n": 2,
+            "presence_penalty": 0.5,
+            "max_completion_tokens": 512,
+            "user": "u-1"
+        }));
+
+        let body = convert_openai_to_antigravity_with_context(&request, "proj");
+        let config = &body["request"]["generationConfig"];
+        assert_eq!(config["stopSequences"], serde_json::json!(["END"]));
+        assert_eq!(config["seed"], 7);
+        assert_eq!(config["candidateCount"], 2);
+        assert_eq!(config["presencePenalty"], 0.5);
+        assert_eq!(config["maxOutputTokens"], 512);
+
+        assert_eq!(
+            openai_to_antigravity_unsupported_params(&request),
+            vec!["user"]
+        );
+    }
+
+    #[test]
+    fn test_tool_choice_maps_to_function_calling_config() {
+        let named = serde_json::json!({"type": "function", "function": {"name": "f"}});
+        assert_eq!(
+            convert_tool_choice(Some(&named)),
+            serde_json::json!({
+                "functionCallingConfig": {"mode": "ANY", "allowedFunctionNames": ["f"]}
+            })
+        );
+        assert_eq!(
+            convert_tool_choice(Some(&serde_json::json!("none")))["functionCallingConfig"]["mode"],
+            "NONE"
+        );
+        assert_eq!(
+            convert_tool_choice(None)["functionCallingConfig"]["mode"],
+            "AUTO"
+        );
+    }
+
+    #[test]
+    fn test_response_format_maps_to_response_schema() {
+        let request = request_with(serde_json::json!({
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "person",
+                    "strict": true,
+                    "schema": {
+                        "type": "object",
+                        "properties": {"name": {"type": "string"}},
+                        "required": ["name"],
+                        "additionalProperties": false
+                    }
+                }
+            }
+        }));
+
+        let body = convert_openai_to_antigravity_with_context(&request, "proj");
+        let config = &body["request"]["generationConfig"];
+        assert_eq!(config["responseMimeType"], "application/json");
+        assert_eq!(
+            config["responseSchema"],
+            serde_json::json!({
+                "type": "object",
+                "properties": {"name": {"type": "string"}},
+                "required": ["name"]
+            })
+        );
+        assert!(openai_to_antigravity_unsupported_params(&request).is_empty());
+
+        let json_object = request_with(serde_json::json!({
+            "response_format": {"type": "json_object"}
+        }));
+        let body = convert_openai_to_antigravity_with_context(&json_object, "proj");
+        let config = &body["request"]["generationConfig"];
+        assert_eq!(config["responseMimeType"], "application/json");
+        assert!(config.get("responseSchema").is_none());
+    }
+}
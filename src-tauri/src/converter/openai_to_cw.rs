@@ -177,6 +177,11 @@ struct ProcessedMessage {
     tool_results: Option<Vec<CWToolResult>>,
 }
 
+/// 返回转换到 CodeWhisperer 时会被丢弃的 OpenAI 参数
+pub fn openai_to_cw_unsupported_params(request: &ChatCompletionRequest) -> Vec<String> {
+    crate::translator::kiro::openai::request::unsupported_openai_params(request)
+}
+
 /// 将 OpenAI ChatCompletionRequest 转换为 CodeWhisperer 请求
 pub fn convert_openai_to_codewhisperer(
     request: &ChatCompletionRequest,
@@ -15,6 +15,7 @@
 //! - `oauth_plugin_loader` - OAuth Provider 插件加载器
 //! - `sdk` - ProxyCast Plugin SDK
 //! - `risk` - 风控模块（限流检测、冷却期管理）
+//! - `scheduler` - 凭证并发调度器（并发限制、优先级排队）
 //! - `unified` - 统一凭证管理器
 
 mod balancer;
@@ -25,6 +26,7 @@ mod pool;
 mod quota;
 pub mod registry;
 pub mod risk;
+mod scheduler;
 pub mod sdk;
 mod sync;
 mod types;
@@ -52,6 +54,10 @@ pub use registry::{
     PluginState, PluginUpdate,
 };
 pub use risk::{CooldownConfig, RateLimitEvent, RateLimitStats, RiskController, RiskLevel};
+pub use scheduler::{
+    CooldownSource, CredentialPermit, CredentialScheduler, PriorityClass, ProviderScheduleStats,
+    ScheduleError, SchedulerSnapshot, PRIORITY_HEADER,
+};
 pub use sdk::{
     DatabaseCallback, HttpRequestOptions, HttpResponse, JsonRpcError, JsonRpcRequest,
     JsonRpcResponse, PluginPermission, PluginSdkContext, QueryResult, SdkError, SdkMethodHandler,
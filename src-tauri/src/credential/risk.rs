@@ -39,6 +39,16 @@ impl RiskLevel {
         }
     }
 
+    /// 序列化名称（与 serde 一致）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+            RiskLevel::Critical => "critical",
+        }
+    }
+
     /// 获取风险等级描述
     pub fn description(&self) -> &'static str {
         match self {
@@ -293,6 +303,20 @@ impl RiskController {
             .collect()
     }
 
+    /// 获取最早的冷却结束时间
+    ///
+    /// # 返回
+    /// - `Some(DateTime)`: 最早的冷却结束时间
+    /// - `None`: 没有凭证处于冷却期
+    pub fn earliest_recovery(&self) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        self.states
+            .iter()
+            .filter_map(|entry| entry.value().cooldown_until)
+            .filter(|until| now < *until)
+            .min()
+    }
+
     /// 获取凭证的限流事件统计
     pub fn get_event_stats(&self, credential_id: &str) -> Option<RateLimitStats> {
         self.states.get(credential_id).map(|state| {
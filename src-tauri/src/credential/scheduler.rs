@@ -0,0 +1,1082 @@
+//! 凭证并发调度器
+//!
+//! 位于 `ProviderPoolService::select_credential` 之前，提供：
+//! - 每个凭证、每个 Provider 的最大在途请求数限制
+//! - 无空闲凭证时按优先级排队（同优先级先进先出），超过截止时间返回错误
+//! - 在途请求结束或冷却（配额超限、风控、限流）到期时唤醒排队请求
+//! - 排队深度和等待时间统计
+
+use super::quota::QuotaManager;
+use super::risk::RiskController;
+use super::unified::UnifiedCredentialManager;
+use crate::config::ConcurrencyConfig;
+use crate::models::provider_pool_model::ProviderCredential;
+use crate::session::RateLimitTracker;
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// 优先级请求头名称
+pub const PRIORITY_HEADER: &str = "x-proxycast-priority";
+
+/// 冷却到期后额外等待的时间，避免在边界时刻重复唤醒
+const COOLDOWN_WAKE_SLACK: Duration = Duration::from_millis(10);
+
+/// 乐观选择的最大重试次数（选择期间计数被其他请求占满时重试）
+const MAX_SELECT_ATTEMPTS: usize = 3;
+
+/// 请求优先级
+///
+/// 排序时 Interactive 在 Batch 之前
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriorityClass {
+    /// 交互式请求（默认）
+    Interactive,
+    /// 批处理请求
+    Batch,
+}
+
+impl PriorityClass {
+    /// 从字符串解析优先级
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "interactive" | "high" => Some(Self::Interactive),
+            "batch" | "low" => Some(Self::Batch),
+            _ => None,
+        }
+    }
+
+    /// 获取字符串表示
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Interactive => "interactive",
+            Self::Batch => "batch",
+        }
+    }
+}
+
+/// 冷却状态来源
+///
+/// 调度器通过它跳过冷却中的凭证，并在最早的冷却到期时唤醒排队请求
+pub trait CooldownSource: Send + Sync {
+    /// 凭证是否处于冷却期
+    fn is_cooling(&self, credential_id: &str) -> bool;
+
+    /// 最早的冷却结束时间
+    fn earliest_recovery(&self) -> Option<DateTime<Utc>>;
+}
+
+impl CooldownSource for QuotaManager {
+    fn is_cooling(&self, credential_id: &str) -> bool {
+        !self.is_available(credential_id)
+    }
+
+    fn earliest_recovery(&self) -> Option<DateTime<Utc>> {
+        QuotaManager::earliest_recovery(self)
+    }
+}
+
+impl CooldownSource for RiskController {
+    fn is_cooling(&self, credential_id: &str) -> bool {
+        self.is_in_cooldown(credential_id)
+    }
+
+    fn earliest_recovery(&self) -> Option<DateTime<Utc>> {
+        RiskController::earliest_recovery(self)
+    }
+}
+
+impl CooldownSource for RateLimitTracker {
+    fn is_cooling(&self, credential_id: &str) -> bool {
+        self.is_rate_limited(credential_id)
+    }
+
+    fn earliest_recovery(&self) -> Option<DateTime<Utc>> {
+        RateLimitTracker::earliest_recovery(self)
+    }
+}
+
+impl CooldownSource for UnifiedCredentialManager {
+    fn is_cooling(&self, credential_id: &str) -> bool {
+        self.risk_controller().is_in_cooldown(credential_id)
+    }
+
+    fn earliest_recovery(&self) -> Option<DateTime<Utc>> {
+        self.risk_controller().earliest_recovery()
+    }
+}
+
+/// 调度错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// 排队已满
+    QueueFull { provider: String, depth: usize },
+    /// 排队超时
+    Timeout { provider: String, waited_ms: u64 },
+    /// 凭证选择失败
+    Selection(String),
+}
+
+impl ScheduleError {
+    /// 对外返回的错误码
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::QueueFull { .. } => "queue_full",
+            Self::Timeout { .. } => "queue_timeout",
+            Self::Selection(_) => "selection_failed",
+        }
+    }
+}
+
+impl std::fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::QueueFull { provider, depth } => {
+                write!(f, "Request queue for '{}' is full ({})", provider, depth)
+            }
+            Self::Timeout {
+                provider,
+                waited_ms,
+            } => write!(
+                f,
+                "Timed out after {}ms waiting for a credential of '{}'",
+                waited_ms, provider
+            ),
+            Self::Selection(e) => write!(f, "Credential selection failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// 单个 Provider 的调度状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderScheduleStats {
+    /// Provider 类型
+    pub provider: String,
+    /// 在途请求数
+    pub in_flight: u32,
+    /// 排队请求数
+    pub queue_depth: usize,
+}
+
+/// 调度器统计快照
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchedulerSnapshot {
+    /// 各 Provider 状态
+    pub providers: Vec<ProviderScheduleStats>,
+    /// 累计排队请求数
+    pub queued_total: u64,
+    /// 累计排队超时数
+    pub timed_out_total: u64,
+    /// 累计因排队已满被拒绝数
+    pub rejected_total: u64,
+    /// 排队请求的平均等待时间（毫秒）
+    pub avg_wait_ms: f64,
+    /// 排队请求的最大等待时间（毫秒）
+    pub max_wait_ms: u64,
+}
+
+/// 凭证占用许可
+///
+/// 持有期间计入在途请求数，Drop 时释放并唤醒排队请求
+#[derive(Debug)]
+pub struct CredentialPermit {
+    scheduler: Arc<CredentialScheduler>,
+    provider: String,
+    credential_id: String,
+    queue_wait_ms: u64,
+    queue_depth: usize,
+}
+
+impl CredentialPermit {
+    /// 凭证 ID
+    pub fn credential_id(&self) -> &str {
+        &self.credential_id
+    }
+
+    /// 排队等待时间（毫秒，未排队为 0）
+    pub fn queue_wait_ms(&self) -> u64 {
+        self.queue_wait_ms
+    }
+
+    /// 进入队列时的排队深度（未排队为 0）
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth
+    }
+}
+
+impl Drop for CredentialPermit {
+    fn drop(&mut self) {
+        self.scheduler.release(&self.provider, &self.credential_id);
+    }
+}
+
+/// 排队位置：(优先级, 入队序号)
+type Ticket = (PriorityClass, u64);
+
+/// 队列键：(Provider, 模型)
+///
+/// 按模型分别排队：某个模型可用的凭证全部占满时，
+/// 不会阻塞同一 Provider 下仍有空闲凭证的其他模型请求
+type QueueKey = (String, Option<String>);
+
+/// 排队位置守卫
+///
+/// Drop 时出队并唤醒新的队首。`acquire` 的 Future 在等待期间被取消
+/// （客户端断开、对冲或降级请求被中止）时也会出队，不会阻塞后续请求
+struct QueuedTicket<'a> {
+    scheduler: &'a CredentialScheduler,
+    key: QueueKey,
+    ticket: Ticket,
+}
+
+impl Drop for QueuedTicket<'_> {
+    fn drop(&mut self) {
+        let mut state = self.scheduler.state.lock();
+        state.dequeue(&self.key, self.ticket);
+        state.wake_head(&self.key);
+    }
+}
+
+#[derive(Debug, Default)]
+struct SchedulerState {
+    credential_in_flight: HashMap<String, u32>,
+    provider_in_flight: HashMap<String, u32>,
+    queues: HashMap<QueueKey, BTreeMap<Ticket, Arc<Notify>>>,
+    next_seq: u64,
+}
+
+impl SchedulerState {
+    /// 是否轮到该请求尝试获取凭证（只与同一模型队列中的请求比较）
+    fn is_turn(&self, key: &QueueKey, ticket: Option<Ticket>) -> bool {
+        match self.queues.get(key).and_then(|q| q.keys().next()) {
+            None => true,
+            Some(head) => ticket == Some(*head),
+        }
+    }
+
+    /// Provider 下所有模型队列的排队总数
+    fn queue_depth(&self, provider: &str) -> usize {
+        self.queues
+            .iter()
+            .filter(|((p, _), _)| p == provider)
+            .map(|(_, q)| q.len())
+            .sum()
+    }
+
+    fn dequeue(&mut self, key: &QueueKey, ticket: Ticket) {
+        if let Some(queue) = self.queues.get_mut(key) {
+            queue.remove(&ticket);
+            if queue.is_empty() {
+                self.queues.remove(key);
+            }
+        }
+    }
+
+    fn wake_head(&self, key: &QueueKey) {
+        if let Some(notify) = self.queues.get(key).and_then(|q| q.values().next()) {
+            notify.notify_one();
+        }
+    }
+
+    /// 唤醒 Provider 下每个模型队列的队首（凭证可能被多个模型共用）
+    fn wake_provider(&self, provider: &str) {
+        for key in self.queues.keys().filter(|(p, _)| p == provider) {
+            self.wake_head(key);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct WaitCounters {
+    queued_total: u64,
+    timed_out_total: u64,
+    rejected_total: u64,
+    completed_waits: u64,
+    total_wait_ms: u64,
+    max_wait_ms: u64,
+}
+
+/// 单次选择结果
+enum SelectOutcome {
+    Acquired(Box<ProviderCredential>),
+    Busy,
+    NoCredentials,
+}
+
+/// 凭证并发调度器
+pub struct CredentialScheduler {
+    config: RwLock<ConcurrencyConfig>,
+    state: Mutex<SchedulerState>,
+    counters: Mutex<WaitCounters>,
+    cooldown_sources: RwLock<Vec<(&'static str, Arc<dyn CooldownSource>)>>,
+}
+
+impl std::fmt::Debug for CredentialScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CredentialScheduler")
+            .field("config", &*self.config.read())
+            .field("cooldown_sources", &self.cooldown_sources.read().len())
+            .finish()
+    }
+}
+
+impl Default for CredentialScheduler {
+    fn default() -> Self {
+        Self::new(ConcurrencyConfig::default())
+    }
+}
+
+impl CredentialScheduler {
+    /// 创建新的调度器
+    pub fn new(config: ConcurrencyConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            state: Mutex::new(SchedulerState::default()),
+            counters: Mutex::new(WaitCounters::default()),
+            cooldown_sources: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// 获取当前配置
+    pub fn config(&self) -> ConcurrencyConfig {
+        self.config.read().clone()
+    }
+
+    /// 更新配置（放宽限制后唤醒所有排队请求）
+    pub fn set_config(&self, config: ConcurrencyConfig) {
+        *self.config.write() = config;
+        self.wake_all();
+    }
+
+    /// 注册冷却状态来源
+    ///
+    /// 同名来源会被替换（服务器重启时重新创建的配额管理器等），
+    /// 替换后唤醒排队请求，使其按新来源重新计算冷却
+    pub fn register_cooldown_source(&self, name: &'static str, source: Arc<dyn CooldownSource>) {
+        {
+            let mut sources = self.cooldown_sources.write();
+            match sources.iter_mut().find(|(existing, _)| *existing == name) {
+                Some(entry) => entry.1 = source,
+                None => sources.push((name, source)),
+            }
+        }
+        self.wake_all();
+    }
+
+    /// 唤醒所有 Provider 队首的排队请求（如冷却被手动清除后调用）
+    pub fn wake_all(&self) {
+        let state = self.state.lock();
+        for key in state.queues.keys() {
+            state.wake_head(key);
+        }
+    }
+
+    /// 根据请求头和客户端类型确定优先级
+    ///
+    /// 请求头优先；否则客户端在 `batch_clients` 中时为 Batch，其余为 Interactive
+    pub fn priority_for(&self, header: Option<&str>, client_key: &str) -> PriorityClass {
+        if let Some(priority) = header.and_then(PriorityClass::parse) {
+            return priority;
+        }
+        let config = self.config.read();
+        if config
+            .batch_clients
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(client_key))
+        {
+            PriorityClass::Batch
+        } else {
+            PriorityClass::Interactive
+        }
+    }
+
+    /// 获取统计快照
+    pub fn snapshot(&self) -> SchedulerSnapshot {
+        let state = self.state.lock();
+        let mut providers: Vec<String> = state
+            .provider_in_flight
+            .keys()
+            .chain(state.queues.keys().map(|(provider, _)| provider))
+            .cloned()
+            .collect();
+        providers.sort();
+        providers.dedup();
+
+        let providers = providers
+            .into_iter()
+            .map(|provider| ProviderScheduleStats {
+                in_flight: state
+                    .provider_in_flight
+                    .get(&provider)
+                    .copied()
+                    .unwrap_or(0),
+                queue_depth: state.queue_depth(&provider),
+                provider,
+            })
+            .collect();
+        drop(state);
+
+        let counters = self.counters.lock();
+        SchedulerSnapshot {
+            providers,
+            queued_total: counters.queued_total,
+            timed_out_total: counters.timed_out_total,
+            rejected_total: counters.rejected_total,
+            avg_wait_ms: if counters.completed_waits > 0 {
+                counters.total_wait_ms as f64 / counters.completed_waits as f64
+            } else {
+                0.0
+            },
+            max_wait_ms: counters.max_wait_ms,
+        }
+    }
+
+    /// 获取凭证占用许可
+    ///
+    /// `select` 接收一个过滤函数（跳过已满或冷却中的凭证），返回选中的凭证。
+    /// 排队按 (Provider, 模型) 分队，`model` 为请求的模型。
+    ///
+    /// # 返回
+    /// - `Ok(Some((credential, permit)))`: 获取成功
+    /// - `Ok(None)`: 该 Provider 没有任何可用凭证（调用方可继续降级）
+    /// - `Err(ScheduleError)`: 排队已满、排队超时或选择失败
+    pub async fn acquire<F>(
+        self: &Arc<Self>,
+        provider: &str,
+        model: Option<&str>,
+        priority: PriorityClass,
+        mut select: F,
+    ) -> Result<Option<(ProviderCredential, CredentialPermit)>, ScheduleError>
+    where
+        F: FnMut(
+            &dyn Fn(&ProviderCredential) -> bool,
+        ) -> Result<Option<ProviderCredential>, String>,
+    {
+        let started = Instant::now();
+        let config = self.config();
+        let deadline = started + Duration::from_millis(config.queue_timeout_ms);
+        let notify = Arc::new(Notify::new());
+        let mut queued: Option<QueuedTicket<'_>> = None;
+        let mut queue_depth = 0;
+        let key: QueueKey = (provider.to_string(), model.map(str::to_string));
+
+        loop {
+            let ticket = queued.as_ref().map(|q| q.ticket);
+            if self.state.lock().is_turn(&key, ticket) {
+                let outcome = self.try_select(provider, &config, &mut select);
+                let outcome = match outcome {
+                    Ok(outcome) => outcome,
+                    Err(e) => {
+                        self.leave_queue(&key, queued.take());
+                        return Err(e);
+                    }
+                };
+
+                match outcome {
+                    SelectOutcome::Acquired(credential) => {
+                        self.leave_queue(&key, queued.take());
+                        let queue_wait_ms = if ticket.is_some() {
+                            let waited = started.elapsed().as_millis() as u64;
+                            self.record_wait(waited);
+                            waited
+                        } else {
+                            0
+                        };
+                        let permit = CredentialPermit {
+                            scheduler: self.clone(),
+                            provider: provider.to_string(),
+                            credential_id: credential.uuid.clone(),
+                            queue_wait_ms,
+                            queue_depth,
+                        };
+                        return Ok(Some((*credential, permit)));
+                    }
+                    SelectOutcome::NoCredentials => {
+                        self.leave_queue(&key, queued.take());
+                        return Ok(None);
+                    }
+                    SelectOutcome::Busy => {}
+                }
+            }
+
+            if queued.is_none() {
+                let mut state = self.state.lock();
+                let depth = state.queue_depth(provider);
+                if depth >= config.max_queue_depth {
+                    drop(state);
+                    self.counters.lock().rejected_total += 1;
+                    return Err(ScheduleError::QueueFull {
+                        provider: provider.to_string(),
+                        depth,
+                    });
+                }
+                let new_ticket = (priority, state.next_seq);
+                state.next_seq += 1;
+                state
+                    .queues
+                    .entry(key.clone())
+                    .or_default()
+                    .insert(new_ticket, notify.clone());
+                drop(state);
+
+                queued = Some(QueuedTicket {
+                    scheduler: self,
+                    key: key.clone(),
+                    ticket: new_ticket,
+                });
+                queue_depth = depth + 1;
+                self.counters.lock().queued_total += 1;
+                tracing::debug!(
+                    "[SCHEDULER] 请求排队: provider={} priority={} depth={}",
+                    provider,
+                    priority.as_str(),
+                    queue_depth
+                );
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                self.leave_queue(&key, queued.take());
+                let waited_ms = started.elapsed().as_millis() as u64;
+                {
+                    let mut counters = self.counters.lock();
+                    counters.timed_out_total += 1;
+                }
+                self.record_wait(waited_ms);
+                return Err(ScheduleError::Timeout {
+                    provider: provider.to_string(),
+                    waited_ms,
+                });
+            }
+
+            let mut wait = deadline - now;
+            if let Some(until) = self.next_recovery_in() {
+                wait = wait.min(until + COOLDOWN_WAKE_SLACK);
+            }
+            let _ = tokio::time::timeout(wait, notify.notified()).await;
+        }
+    }
+
+    /// 尝试选择一个未满且不在冷却期的凭证
+    fn try_select<F>(
+        &self,
+        provider: &str,
+        config: &ConcurrencyConfig,
+        select: &mut F,
+    ) -> Result<SelectOutcome, ScheduleError>
+    where
+        F: FnMut(
+            &dyn Fn(&ProviderCredential) -> bool,
+        ) -> Result<Option<ProviderCredential>, String>,
+    {
+        let credential_limit = config.max_in_flight_per_credential;
+        let provider_limit = config.max_in_flight_per_provider;
+
+        for _ in 0..MAX_SELECT_ATTEMPTS {
+            let (snapshot, provider_count) = {
+                let state = self.state.lock();
+                (
+                    state.credential_in_flight.clone(),
+                    state.provider_in_flight.get(provider).copied().unwrap_or(0),
+                )
+            };
+
+            if provider_limit > 0 && provider_count >= provider_limit {
+                return self.busy_or_none(select);
+            }
+
+            let schedulable = |c: &ProviderCredential| {
+                let in_flight = snapshot.get(&c.uuid).copied().unwrap_or(0);
+                (credential_limit == 0 || in_flight < credential_limit) && !self.is_cooling(&c.uuid)
+            };
+
+            let Some(credential) = select(&schedulable).map_err(ScheduleError::Selection)? else {
+                return self.busy_or_none(select);
+            };
+
+            // 选择期间其他请求可能已占满，重新校验后再计数
+            let mut state = self.state.lock();
+            let credential_count = state
+                .credential_in_flight
+                .get(&credential.uuid)
+                .copied()
+                .unwrap_or(0);
+            let provider_count = state.provider_in_flight.get(provider).copied().unwrap_or(0);
+            if (credential_limit > 0 && credential_count >= credential_limit)
+                || (provider_limit > 0 && provider_count >= provider_limit)
+            {
+                continue;
+            }
+
+            *state
+                .credential_in_flight
+                .entry(credential.uuid.clone())
+                .or_insert(0) += 1;
+            *state
+                .provider_in_flight
+                .entry(provider.to_string())
+                .or_insert(0) += 1;
+            return Ok(SelectOutcome::Acquired(Box::new(credential)));
+        }
+
+        Ok(SelectOutcome::Busy)
+    }
+
+    /// 区分“所有凭证都忙”和“没有任何凭证”
+    fn busy_or_none<F>(&self, select: &mut F) -> Result<SelectOutcome, ScheduleError>
+    where
+        F: FnMut(
+            &dyn Fn(&ProviderCredential) -> bool,
+        ) -> Result<Option<ProviderCredential>, String>,
+    {
+        match select(&|_: &ProviderCredential| true).map_err(ScheduleError::Selection)? {
+            Some(_) => Ok(SelectOutcome::Busy),
+            None => Ok(SelectOutcome::NoCredentials),
+        }
+    }
+
+    /// 凭证是否处于任一冷却来源的冷却期
+    pub fn is_cooling(&self, credential_id: &str) -> bool {
+        self.cooldown_sources
+            .read()
+            .iter()
+            .any(|(_, source)| source.is_cooling(credential_id))
+    }
+
+    /// 凭证是否已达到单凭证并发上限
+    pub fn is_at_capacity(&self, credential_id: &str) -> bool {
+        let limit = self.config.read().max_in_flight_per_credential;
+        limit > 0
+            && self
+                .state
+                .lock()
+                .credential_in_flight
+                .get(credential_id)
+                .copied()
+                .unwrap_or(0)
+                >= limit
+    }
+
+    /// 距离最早冷却到期的时间
+    fn next_recovery_in(&self) -> Option<Duration> {
+        let now = Utc::now();
+        let earliest = self
+            .cooldown_sources
+            .read()
+            .iter()
+            .filter_map(|(_, source)| source.earliest_recovery())
+            .filter(|until| *until > now)
+            .min()?;
+        (earliest - now).to_std().ok()
+    }
+
+    /// 离开队列，并唤醒下一个排队请求（可能还有空闲容量）
+    fn leave_queue(&self, key: &QueueKey, queued: Option<QueuedTicket<'_>>) {
+        match queued {
+            // 守卫 Drop 时出队并唤醒队首
+            Some(queued) => drop(queued),
+            None => self.state.lock().wake_head(key),
+        }
+    }
+
+    fn record_wait(&self, waited_ms: u64) {
+        let mut counters = self.counters.lock();
+        counters.completed_waits += 1;
+        counters.total_wait_ms += waited_ms;
+        counters.max_wait_ms = counters.max_wait_ms.max(waited_ms);
+    }
+
+    /// 释放在途计数
+    fn release(&self, provider: &str, credential_id: &str) {
+        let mut state = self.state.lock();
+        if let Some(count) = state.credential_in_flight.get_mut(credential_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                state.credential_in_flight.remove(credential_id);
+            }
+        }
+        if let Some(count) = state.provider_in_flight.get_mut(provider) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                state.provider_in_flight.remove(provider);
+            }
+        }
+        state.wake_provider(provider);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::RateLimitEvent;
+    use crate::models::provider_pool_model::{CredentialData, PoolProviderType};
+    use crate::session::RateLimitReason;
+
+    fn credential(uuid: &str) -> ProviderCredential {
+        let mut cred = ProviderCredential::new(
+            PoolProviderType::OpenAI,
+            CredentialData::OpenAIKey {
+                api_key: "sk-test".to_string(),
+                base_url: None,
+            },
+        );
+        cred.uuid = uuid.to_string();
+        cred
+    }
+
+    type Selected = Result<Option<ProviderCredential>, String>;
+
+    fn selector(
+        pool: Vec<ProviderCredential>,
+    ) -> impl FnMut(&dyn Fn(&ProviderCredential) -> bool) -> Selected {
+        move |filter| Ok(pool.iter().find(|c| filter(c)).cloned())
+    }
+
+    fn limited(per_credential: u32, timeout_ms: u64) -> Arc<CredentialScheduler> {
+        Arc::new(CredentialScheduler::new(ConcurrencyConfig {
+            max_in_flight_per_credential: per_credential,
+            queue_timeout_ms: timeout_ms,
+            ..ConcurrencyConfig::default()
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_acquire_without_limits() {
+        let scheduler = Arc::new(CredentialScheduler::default());
+        let pool = vec![credential("a")];
+
+        let first = scheduler
+            .acquire(
+                "openai",
+                None,
+                PriorityClass::Interactive,
+                selector(pool.clone()),
+            )
+            .await
+            .unwrap();
+        let second = scheduler
+            .acquire("openai", None, PriorityClass::Interactive, selector(pool))
+            .await
+            .unwrap();
+        assert!(first.is_some());
+        assert!(second.is_some());
+        assert_eq!(scheduler.snapshot().providers[0].in_flight, 2);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_returns_none_without_credentials() {
+        let scheduler = limited(1, 1000);
+        let result = scheduler
+            .acquire(
+                "openai",
+                None,
+                PriorityClass::Interactive,
+                selector(Vec::new()),
+            )
+            .await
+            .unwrap();
+        assert!(result.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_queue_timeout_when_saturated() {
+        let scheduler = limited(1, 50);
+        let pool = vec![credential("a")];
+
+        let _held = scheduler
+            .acquire(
+                "openai",
+                None,
+                PriorityClass::Interactive,
+                selector(pool.clone()),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let err = scheduler
+            .acquire("openai", None, PriorityClass::Interactive, selector(pool))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ScheduleError::Timeout { .. }));
+        assert_eq!(scheduler.snapshot().timed_out_total, 1);
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_wakes_on_release() {
+        let scheduler = limited(1, 5000);
+        let pool = vec![credential("a")];
+
+        let (_, held) = scheduler
+            .acquire(
+                "openai",
+                None,
+                PriorityClass::Interactive,
+                selector(pool.clone()),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let waiter = {
+            let scheduler = scheduler.clone();
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                scheduler
+                    .acquire("openai", None, PriorityClass::Interactive, selector(pool))
+                    .await
+                    .map(|r| r.map(|(_, permit)| (permit.queue_depth(), permit.queue_wait_ms())))
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(scheduler.snapshot().providers[0].queue_depth, 1);
+        drop(held);
+
+        let (depth, waited) = waiter.await.unwrap().unwrap().unwrap();
+        assert_eq!(depth, 1);
+        assert!(waited >= 30);
+        assert_eq!(scheduler.snapshot().queued_total, 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_leaves_queue() {
+        let scheduler = limited(1, 5000);
+        let pool = vec![credential("a")];
+
+        let (_, held) = scheduler
+            .acquire(
+                "openai",
+                None,
+                PriorityClass::Interactive,
+                selector(pool.clone()),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        // 排队中的 Future 被丢弃（如客户端断开）
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(20),
+            scheduler.acquire(
+                "openai",
+                None,
+                PriorityClass::Interactive,
+                selector(pool.clone()),
+            ),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        assert_eq!(scheduler.snapshot().providers[0].queue_depth, 0);
+
+        let waiter = {
+            let scheduler = scheduler.clone();
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                scheduler
+                    .acquire("openai", None, PriorityClass::Interactive, selector(pool))
+                    .await
+                    .map(|r| r.is_some())
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(held);
+
+        let acquired = tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("后续请求被已取消的排队请求阻塞")
+            .unwrap();
+        assert_eq!(acquired, Ok(true));
+    }
+
+    #[tokio::test]
+    async fn test_interactive_served_before_batch() {
+        let scheduler = limited(1, 5000);
+        let pool = vec![credential("a")];
+
+        let (_, held) = scheduler
+            .acquire(
+                "openai",
+                None,
+                PriorityClass::Interactive,
+                selector(pool.clone()),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let spawn = |priority: PriorityClass| {
+            let scheduler = scheduler.clone();
+            let pool = pool.clone();
+            let order = order.clone();
+            tokio::spawn(async move {
+                let permit = scheduler
+                    .acquire("openai", None, priority, selector(pool))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                order.lock().push(priority);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                drop(permit);
+            })
+        };
+
+        let batch = spawn(PriorityClass::Batch);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let interactive = spawn(PriorityClass::Interactive);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        drop(held);
+        batch.await.unwrap();
+        interactive.await.unwrap();
+
+        assert_eq!(
+            *order.lock(),
+            vec![PriorityClass::Interactive, PriorityClass::Batch]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_busy_model_does_not_block_other_models() {
+        let scheduler = limited(1, 5000);
+        let model_a = vec![credential("a")];
+        let model_b = vec![credential("b")];
+
+        let (_, held) = scheduler
+            .acquire(
+                "openai",
+                Some("model-a"),
+                PriorityClass::Interactive,
+                selector(model_a.clone()),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        // model-a 的凭证已占满，该请求进入排队
+        let waiter = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move {
+                scheduler
+                    .acquire(
+                        "openai",
+                        Some("model-a"),
+                        PriorityClass::Interactive,
+                        selector(model_a),
+                    )
+                    .await
+                    .map(|r| r.is_some())
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(scheduler.snapshot().providers[0].queue_depth, 1);
+
+        // model-b 仍有空闲凭证，不应排在 model-a 的请求之后
+        let (cred, permit) = tokio::time::timeout(
+            Duration::from_millis(200),
+            scheduler.acquire(
+                "openai",
+                Some("model-b"),
+                PriorityClass::Interactive,
+                selector(model_b),
+            ),
+        )
+        .await
+        .expect("其他模型的请求被队首阻塞")
+        .unwrap()
+        .unwrap();
+        assert_eq!(cred.uuid, "b");
+        assert_eq!(permit.queue_depth(), 0);
+
+        drop(held);
+        assert_eq!(waiter.await.unwrap(), Ok(true));
+    }
+
+    #[tokio::test]
+    async fn test_queue_full_rejected() {
+        let scheduler = Arc::new(CredentialScheduler::new(ConcurrencyConfig {
+            max_in_flight_per_provider: 1,
+            max_queue_depth: 0,
+            ..ConcurrencyConfig::default()
+        }));
+        let pool = vec![credential("a"), credential("b")];
+
+        let _held = scheduler
+            .acquire(
+                "openai",
+                None,
+                PriorityClass::Interactive,
+                selector(pool.clone()),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let err = scheduler
+            .acquire("openai", None, PriorityClass::Batch, selector(pool))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ScheduleError::QueueFull { depth: 0, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_cooling_credential_skipped() {
+        let scheduler = limited(0, 1000);
+        let risk = Arc::new(RiskController::with_defaults());
+        risk.record_rate_limit(RateLimitEvent::new("a".to_string()));
+        scheduler.register_cooldown_source("risk", risk);
+
+        let pool = vec![credential("a"), credential("b")];
+        let (cred, _permit) = scheduler
+            .acquire("openai", None, PriorityClass::Interactive, selector(pool))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cred.uuid, "b");
+    }
+
+    #[test]
+    fn test_cooldown_source_replaced_by_name() {
+        let scheduler = limited(0, 1000);
+        let risk = Arc::new(RiskController::with_defaults());
+        risk.record_rate_limit(RateLimitEvent::new("a".to_string()));
+        scheduler.register_cooldown_source("risk", risk);
+        assert!(scheduler.is_cooling("a"));
+
+        scheduler.register_cooldown_source("risk", Arc::new(RiskController::with_defaults()));
+        assert!(!scheduler.is_cooling("a"));
+
+        let limits = Arc::new(RateLimitTracker::default());
+        limits.mark_rate_limited("b", RateLimitReason::RateLimitExceeded, None, None);
+        scheduler.register_cooldown_source("rate_limit", limits);
+        assert!(scheduler.is_cooling("b"));
+        assert_eq!(scheduler.cooldown_sources.read().len(), 2);
+    }
+
+    #[test]
+    fn test_priority_for() {
+        let scheduler = CredentialScheduler::new(ConcurrencyConfig {
+            batch_clients: vec!["codex".to_string()],
+            ..ConcurrencyConfig::default()
+        });
+        assert_eq!(
+            scheduler.priority_for(Some("batch"), "cursor"),
+            PriorityClass::Batch
+        );
+        assert_eq!(
+            scheduler.priority_for(Some("interactive"), "codex"),
+            PriorityClass::Interactive
+        );
+        assert_eq!(scheduler.priority_for(None, "codex"), PriorityClass::Batch);
+        assert_eq!(
+            scheduler.priority_for(Some("unknown"), "cursor"),
+            PriorityClass::Interactive
+        );
+    }
+}
@@ -1,9 +1,20 @@
 //! Agent 会话和消息的数据访问层
 //!
 //! 提供 Agent 会话和消息的持久化存储功能
+//!
+//! 消息以树的形式存储：每条消息记录父消息 ID，会话记录当前激活分支的末端消息。
+//! 编辑重发、回退等操作只移动激活分支，不会删除历史消息。
+
+use crate::agent::types::{
+    AgentBranch, AgentCheckpoint, AgentCompaction, AgentMessage, AgentMessageNode, AgentSession,
+    CompactedToolOutput, MessageContent, ToolCall,
+};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::collections::{HashMap, HashSet};
 
-use crate::agent::types::{AgentMessage, AgentSession, MessageContent, ToolCall};
-use rusqlite::{params, Connection};
+/// 消息查询字段（与 `row_to_node` 对应）
+const MESSAGE_COLUMNS: &str =
+    "id, parent_id, role, content_json, timestamp, tool_calls_json, tool_call_id, pinned";
 
 pub struct AgentDao;
 
@@ -88,14 +99,20 @@ impl AgentDao {
         sessions.collect()
     }
 
-    /// 获取会话的消息数量
+    /// 获取会话当前分支的消息数量
     pub fn get_message_count(
         conn: &Connection,
         session_id: &str,
     ) -> Result<usize, rusqlite::Error> {
+        let leaf_id = Self::get_active_leaf(conn, session_id)?;
         let count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM agent_messages WHERE session_id = ?",
-            [session_id],
+            "WITH RECURSIVE branch(id, parent_id) AS (
+                SELECT id, parent_id FROM agent_messages WHERE id = ?1 AND session_id = ?2
+                UNION ALL
+                SELECT m.id, m.parent_id FROM agent_messages m JOIN branch b ON m.id = b.parent_id
+             )
+             SELECT COUNT(*) FROM branch",
+            params![leaf_id, session_id],
             |row| row.get(0),
         )?;
         Ok(count as usize)
@@ -120,12 +137,23 @@ impl AgentDao {
         Ok(rows > 0)
     }
 
-    /// 添加消息到会话
+    /// 添加消息到会话当前分支的末端，返回消息 ID
     pub fn add_message(
         conn: &Connection,
         session_id: &str,
         message: &AgentMessage,
-    ) -> Result<(), rusqlite::Error> {
+    ) -> Result<i64, rusqlite::Error> {
+        let parent_id = Self::get_active_leaf(conn, session_id)?;
+        Self::add_message_with_parent(conn, session_id, parent_id, message)
+    }
+
+    /// 添加消息到指定父消息下，并将其设为激活分支的末端，返回消息 ID
+    pub fn add_message_with_parent(
+        conn: &Connection,
+        session_id: &str,
+        parent_id: Option<i64>,
+        message: &AgentMessage,
+    ) -> Result<i64, rusqlite::Error> {
         let content_json = serde_json::to_string(&message.content)
             .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
 
@@ -137,86 +165,455 @@ impl AgentDao {
             .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
 
         conn.execute(
-            "INSERT INTO agent_messages (session_id, role, content_json, timestamp, tool_calls_json, tool_call_id)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO agent_messages (session_id, parent_id, role, content_json, timestamp, tool_calls_json, tool_call_id, pinned)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
                 session_id,
+                parent_id,
                 message.role,
                 content_json,
                 message.timestamp,
                 tool_calls_json,
                 message.tool_call_id,
+                message.pinned,
             ],
         )?;
+        let message_id = conn.last_insert_rowid();
 
-        // 更新会话的 updated_at
+        // 更新会话的激活分支和 updated_at
         conn.execute(
-            "UPDATE agent_sessions SET updated_at = ? WHERE id = ?",
-            params![message.timestamp, session_id],
+            "UPDATE agent_sessions SET active_leaf_id = ?, updated_at = ? WHERE id = ?",
+            params![message_id, message.timestamp, session_id],
         )?;
 
-        Ok(())
+        Ok(message_id)
     }
 
-    /// 获取会话的所有消息
+    /// 获取会话当前分支的所有消息
     pub fn get_messages(
         conn: &Connection,
         session_id: &str,
     ) -> Result<Vec<AgentMessage>, rusqlite::Error> {
-        let mut stmt = conn.prepare(
-            "SELECT role, content_json, timestamp, tool_calls_json, tool_call_id
-             FROM agent_messages WHERE session_id = ? ORDER BY id ASC",
+        Ok(Self::get_active_branch(conn, session_id)?
+            .into_iter()
+            .map(|node| node.message)
+            .collect())
+    }
+
+    /// 删除会话的所有消息
+    pub fn delete_messages(conn: &Connection, session_id: &str) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "DELETE FROM agent_messages WHERE session_id = ?",
+            [session_id],
+        )?;
+        Self::set_active_leaf(conn, session_id, None)?;
+        Ok(())
+    }
+
+    // ==================== 消息树与分支 ====================
+
+    /// 获取会话当前激活分支的末端消息 ID
+    pub fn get_active_leaf(
+        conn: &Connection,
+        session_id: &str,
+    ) -> Result<Option<i64>, rusqlite::Error> {
+        conn.query_row(
+            "SELECT active_leaf_id FROM agent_sessions WHERE id = ?",
+            [session_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map(Option::flatten)
+    }
+
+    /// 设置会话当前激活分支的末端消息（None 表示空分支）
+    pub fn set_active_leaf(
+        conn: &Connection,
+        session_id: &str,
+        leaf_id: Option<i64>,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "UPDATE agent_sessions SET active_leaf_id = ? WHERE id = ?",
+            params![leaf_id, session_id],
         )?;
+        Ok(())
+    }
 
-        let messages = stmt.query_map([session_id], |row| {
-            let role: String = row.get(0)?;
-            let content_json: String = row.get(1)?;
-            let timestamp: String = row.get(2)?;
-            let tool_calls_json: Option<String> = row.get(3)?;
-            let tool_call_id: Option<String> = row.get(4)?;
+    /// 获取会话中的单条消息
+    pub fn get_message_node(
+        conn: &Connection,
+        session_id: &str,
+        message_id: i64,
+    ) -> Result<Option<AgentMessageNode>, rusqlite::Error> {
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM agent_messages WHERE id = ? AND session_id = ?",
+                MESSAGE_COLUMNS
+            ),
+            params![message_id, session_id],
+            Self::row_to_node,
+        )
+        .optional()
+    }
+
+    /// 获取从根消息到指定消息的分支（按时间顺序）
+    pub fn get_branch(
+        conn: &Connection,
+        session_id: &str,
+        leaf_id: Option<i64>,
+    ) -> Result<Vec<AgentMessageNode>, rusqlite::Error> {
+        let Some(leaf_id) = leaf_id else {
+            return Ok(Vec::new());
+        };
+        // 父消息总是先于子消息插入，按 ID 排序即为分支顺序
+        let mut stmt = conn.prepare(&format!(
+            "WITH RECURSIVE branch(id, parent_id) AS (
+                SELECT id, parent_id FROM agent_messages WHERE id = ?1 AND session_id = ?2
+                UNION ALL
+                SELECT m.id, m.parent_id FROM agent_messages m JOIN branch b ON m.id = b.parent_id
+             )
+             SELECT {} FROM agent_messages WHERE id IN (SELECT id FROM branch) ORDER BY id ASC",
+            MESSAGE_COLUMNS
+        ))?;
+        let nodes = stmt.query_map(params![leaf_id, session_id], Self::row_to_node)?;
+        nodes.collect()
+    }
+
+    /// 获取会话当前激活分支
+    pub fn get_active_branch(
+        conn: &Connection,
+        session_id: &str,
+    ) -> Result<Vec<AgentMessageNode>, rusqlite::Error> {
+        let leaf_id = Self::get_active_leaf(conn, session_id)?;
+        Self::get_branch(conn, session_id, leaf_id)
+    }
 
-            // 解析 JSON
-            let content: MessageContent = serde_json::from_str(&content_json).map_err(|e| {
+    /// 获取会话的完整消息树（按 ID 排序）
+    pub fn get_message_tree(
+        conn: &Connection,
+        session_id: &str,
+    ) -> Result<Vec<AgentMessageNode>, rusqlite::Error> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM agent_messages WHERE session_id = ? ORDER BY id ASC",
+            MESSAGE_COLUMNS
+        ))?;
+        let nodes = stmt.query_map([session_id], Self::row_to_node)?;
+        nodes.collect()
+    }
+
+    /// 列出会话的所有分支
+    ///
+    /// 每个没有子消息的消息对应一个分支；若激活分支已回退到中间消息，也会单独列出。
+    pub fn list_branches(
+        conn: &Connection,
+        session_id: &str,
+    ) -> Result<Vec<AgentBranch>, rusqlite::Error> {
+        let tree = Self::get_message_tree(conn, session_id)?;
+        let active_leaf = Self::get_active_leaf(conn, session_id)?;
+
+        let parents: HashSet<i64> = tree.iter().filter_map(|n| n.parent_id).collect();
+        let mut depths: HashMap<i64, usize> = HashMap::new();
+        for node in &tree {
+            let depth = node
+                .parent_id
+                .and_then(|p| depths.get(&p))
+                .map_or(1, |d| d + 1);
+            depths.insert(node.id, depth);
+        }
+
+        let branches = tree
+            .iter()
+            .filter(|n| !parents.contains(&n.id) || Some(n.id) == active_leaf)
+            .map(|n| AgentBranch {
+                leaf_id: n.id,
+                message_count: depths.get(&n.id).copied().unwrap_or(1),
+                preview: n.message.content.as_text().chars().take(80).collect(),
+                updated_at: n.message.timestamp.clone(),
+                is_active: Some(n.id) == active_leaf,
+            })
+            .collect();
+        Ok(branches)
+    }
+
+    /// 在指定消息处分叉出新会话
+    ///
+    /// 新会话包含从根消息到该消息的分支，返回复制的消息数量。
+    pub fn fork_session(
+        conn: &Connection,
+        session_id: &str,
+        message_id: i64,
+        new_session: &AgentSession,
+    ) -> Result<usize, rusqlite::Error> {
+        let branch = Self::get_branch(conn, session_id, Some(message_id))?;
+        if branch.is_empty() {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        let tx = conn.unchecked_transaction()?;
+        Self::create_session(&tx, new_session)?;
+        let mut parent_id = None;
+        for node in &branch {
+            parent_id = Some(Self::add_message_with_parent(
+                &tx,
+                &new_session.id,
+                parent_id,
+                &node.message,
+            )?);
+        }
+        tx.commit()?;
+        Ok(branch.len())
+    }
+
+    fn row_to_node(row: &Row) -> Result<AgentMessageNode, rusqlite::Error> {
+        let id: i64 = row.get(0)?;
+        let parent_id: Option<i64> = row.get(1)?;
+        let role: String = row.get(2)?;
+        let content_json: String = row.get(3)?;
+        let timestamp: String = row.get(4)?;
+        let tool_calls_json: Option<String> = row.get(5)?;
+        let tool_call_id: Option<String> = row.get(6)?;
+        let pinned: bool = row.get(7)?;
+
+        // 解析 JSON
+        let content: MessageContent = serde_json::from_str(&content_json).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+        })?;
+
+        let tool_calls: Option<Vec<ToolCall>> = tool_calls_json
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .map_err(|e| {
                 rusqlite::Error::FromSqlConversionFailure(
-                    1,
+                    5,
                     rusqlite::types::Type::Text,
                     Box::new(e),
                 )
             })?;
 
-            let tool_calls: Option<Vec<ToolCall>> = tool_calls_json
-                .map(|json| serde_json::from_str(&json))
-                .transpose()
-                .map_err(|e| {
-                    rusqlite::Error::FromSqlConversionFailure(
-                        3,
-                        rusqlite::types::Type::Text,
-                        Box::new(e),
-                    )
-                })?;
-
-            Ok(AgentMessage {
+        Ok(AgentMessageNode {
+            id,
+            parent_id,
+            message: AgentMessage {
                 role,
                 content,
                 timestamp,
                 tool_calls,
                 tool_call_id,
                 reasoning_content: None,
-            })
-        })?;
+                pinned,
+            },
+        })
+    }
+
+    // ==================== 检查点 ====================
 
-        messages.collect()
+    /// 保存检查点
+    pub fn create_checkpoint(
+        conn: &Connection,
+        checkpoint: &AgentCheckpoint,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "INSERT INTO agent_checkpoints (id, session_id, message_id, label, file_count, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                checkpoint.id,
+                checkpoint.session_id,
+                checkpoint.message_id,
+                checkpoint.label,
+                checkpoint.file_count,
+                checkpoint.created_at,
+            ],
+        )?;
+        Ok(())
     }
 
-    /// 删除会话的所有消息
-    pub fn delete_messages(conn: &Connection, session_id: &str) -> Result<(), rusqlite::Error> {
+    /// 获取检查点
+    pub fn get_checkpoint(
+        conn: &Connection,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<Option<AgentCheckpoint>, rusqlite::Error> {
+        conn.query_row(
+            "SELECT id, session_id, message_id, label, file_count, created_at
+             FROM agent_checkpoints WHERE id = ? AND session_id = ?",
+            params![checkpoint_id, session_id],
+            Self::row_to_checkpoint,
+        )
+        .optional()
+    }
+
+    /// 列出会话的检查点（按创建时间倒序）
+    pub fn list_checkpoints(
+        conn: &Connection,
+        session_id: &str,
+    ) -> Result<Vec<AgentCheckpoint>, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT id, session_id, message_id, label, file_count, created_at
+             FROM agent_checkpoints WHERE session_id = ? ORDER BY created_at DESC",
+        )?;
+        let checkpoints = stmt.query_map([session_id], Self::row_to_checkpoint)?;
+        checkpoints.collect()
+    }
+
+    /// 删除检查点
+    pub fn delete_checkpoint(
+        conn: &Connection,
+        session_id: &str,
+        checkpoint_id: &str,
+    ) -> Result<bool, rusqlite::Error> {
+        let rows = conn.execute(
+            "DELETE FROM agent_checkpoints WHERE id = ? AND session_id = ?",
+            params![checkpoint_id, session_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn row_to_checkpoint(row: &Row) -> Result<AgentCheckpoint, rusqlite::Error> {
+        Ok(AgentCheckpoint {
+            id: row.get(0)?,
+            session_id: row.get(1)?,
+            message_id: row.get(2)?,
+            label: row.get(3)?,
+            file_count: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    }
+
+    // ==================== 上下文压缩 ====================
+
+    /// 设置消息置顶状态
+    pub fn set_message_pinned(
+        conn: &Connection,
+        session_id: &str,
+        message_id: i64,
+        pinned: bool,
+    ) -> Result<bool, rusqlite::Error> {
+        let rows = conn.execute(
+            "UPDATE agent_messages SET pinned = ? WHERE id = ? AND session_id = ?",
+            params![pinned, message_id, session_id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// 保存压缩记录
+    pub fn create_compaction(
+        conn: &Connection,
+        compaction: &AgentCompaction,
+    ) -> Result<(), rusqlite::Error> {
         conn.execute(
-            "DELETE FROM agent_messages WHERE session_id = ?",
-            [session_id],
+            "INSERT INTO agent_compactions (id, session_id, message_id, summary, summarized_messages, kept_messages, tokens_before, tokens_after, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                compaction.id,
+                compaction.session_id,
+                compaction.message_id,
+                compaction.summary,
+                compaction.summarized_messages,
+                compaction.kept_messages,
+                compaction.tokens_before,
+                compaction.tokens_after,
+                compaction.created_at,
+            ],
         )?;
         Ok(())
     }
 
+    /// 列出会话的压缩记录（按创建时间倒序）
+    pub fn list_compactions(
+        conn: &Connection,
+        session_id: &str,
+    ) -> Result<Vec<AgentCompaction>, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT id, session_id, message_id, summary, summarized_messages, kept_messages, tokens_before, tokens_after, created_at
+             FROM agent_compactions WHERE session_id = ? ORDER BY created_at DESC, rowid DESC",
+        )?;
+        let compactions = stmt.query_map([session_id], Self::row_to_compaction)?;
+        compactions.collect()
+    }
+
+    /// 获取会话在指定分支上最近的压缩记录
+    ///
+    /// `branch_ids` 为分支上的消息 ID，压缩点不在该分支上的记录会被忽略
+    pub fn get_branch_compaction(
+        conn: &Connection,
+        session_id: &str,
+        branch_ids: &HashSet<i64>,
+    ) -> Result<Option<AgentCompaction>, rusqlite::Error> {
+        Ok(Self::list_compactions(conn, session_id)?
+            .into_iter()
+            .find(|c| c.message_id.is_some_and(|id| branch_ids.contains(&id))))
+    }
+
+    fn row_to_compaction(row: &Row) -> Result<AgentCompaction, rusqlite::Error> {
+        Ok(AgentCompaction {
+            id: row.get(0)?,
+            session_id: row.get(1)?,
+            message_id: row.get(2)?,
+            summary: row.get(3)?,
+            summarized_messages: row.get(4)?,
+            kept_messages: row.get(5)?,
+            tokens_before: row.get(6)?,
+            tokens_after: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    }
+
+    /// 保存被截断的工具输出
+    pub fn save_tool_output(
+        conn: &Connection,
+        session_id: &str,
+        output: &CompactedToolOutput,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "INSERT OR REPLACE INTO agent_tool_outputs (handle, session_id, tool_call_id, content, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                output.handle,
+                session_id,
+                output.tool_call_id,
+                output.content,
+                chrono::Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 按句柄获取被截断的工具输出
+    pub fn get_tool_output(
+        conn: &Connection,
+        session_id: &str,
+        handle: &str,
+    ) -> Result<Option<CompactedToolOutput>, rusqlite::Error> {
+        conn.query_row(
+            "SELECT handle, tool_call_id, content FROM agent_tool_outputs
+             WHERE handle = ? AND session_id = ?",
+            params![handle, session_id],
+            Self::row_to_tool_output,
+        )
+        .optional()
+    }
+
+    /// 列出会话中被截断的工具输出
+    pub fn list_tool_outputs(
+        conn: &Connection,
+        session_id: &str,
+    ) -> Result<Vec<CompactedToolOutput>, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT handle, tool_call_id, content FROM agent_tool_outputs
+             WHERE session_id = ? ORDER BY created_at",
+        )?;
+        let outputs = stmt.query_map([session_id], Self::row_to_tool_output)?;
+        outputs.collect()
+    }
+
+    fn row_to_tool_output(row: &Row) -> Result<CompactedToolOutput, rusqlite::Error> {
+        Ok(CompactedToolOutput {
+            handle: row.get(0)?,
+            tool_call_id: row.get(1)?,
+            content: row.get(2)?,
+        })
+    }
+
     /// 检查会话是否存在
     pub fn session_exists(conn: &Connection, session_id: &str) -> Result<bool, rusqlite::Error> {
         let count: i64 = conn.query_row(
@@ -227,3 +624,236 @@ impl AgentDao {
         Ok(count > 0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::schema::create_tables;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn
+    }
+
+    fn session(id: &str) -> AgentSession {
+        AgentSession {
+            id: id.to_string(),
+            model: "gpt-4o".to_string(),
+            messages: Vec::new(),
+            system_prompt: None,
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            updated_at: "2025-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    fn message(role: &str, text: &str) -> AgentMessage {
+        AgentMessage {
+            role: role.to_string(),
+            content: MessageContent::Text(text.to_string()),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+            tool_calls: None,
+            tool_call_id: None,
+            reasoning_content: None,
+            pinned: false,
+        }
+    }
+
+    fn texts(messages: &[AgentMessage]) -> Vec<String> {
+        messages.iter().map(|m| m.content.as_text()).collect()
+    }
+
+    #[test]
+    fn test_edit_creates_branch() {
+        let conn = setup_test_db();
+        AgentDao::create_session(&conn, &session("s1")).unwrap();
+
+        let u1 = AgentDao::add_message(&conn, "s1", &message("user", "hi")).unwrap();
+        let a1 = AgentDao::add_message(&conn, "s1", &message("assistant", "hello")).unwrap();
+        let u2 = AgentDao::add_message(&conn, "s1", &message("user", "bad idea")).unwrap();
+        AgentDao::add_message(&conn, "s1", &message("assistant", "ok")).unwrap();
+
+        // 编辑 u2：回退到其父消息后继续发送
+        let node = AgentDao::get_message_node(&conn, "s1", u2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(node.parent_id, Some(a1));
+        AgentDao::set_active_leaf(&conn, "s1", node.parent_id).unwrap();
+        AgentDao::add_message(&conn, "s1", &message("user", "good idea")).unwrap();
+
+        let messages = AgentDao::get_messages(&conn, "s1").unwrap();
+        assert_eq!(texts(&messages), vec!["hi", "hello", "good idea"]);
+        assert_eq!(AgentDao::get_message_count(&conn, "s1").unwrap(), 3);
+        assert_eq!(AgentDao::get_message_tree(&conn, "s1").unwrap().len(), 5);
+
+        let branches = AgentDao::list_branches(&conn, "s1").unwrap();
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches.iter().filter(|b| b.is_active).count(), 1);
+        assert_eq!(branches[0].message_count, 4);
+        assert_eq!(branches[1].preview, "good idea");
+
+        // 切换回原分支
+        AgentDao::set_active_leaf(&conn, "s1", Some(branches[0].leaf_id)).unwrap();
+        let messages = AgentDao::get_messages(&conn, "s1").unwrap();
+        assert_eq!(texts(&messages), vec!["hi", "hello", "bad idea", "ok"]);
+
+        // 回退到第一条消息之前
+        AgentDao::set_active_leaf(&conn, "s1", None).unwrap();
+        assert!(AgentDao::get_messages(&conn, "s1").unwrap().is_empty());
+        AgentDao::add_message(&conn, "s1", &message("user", "restart")).unwrap();
+        let root = AgentDao::get_active_branch(&conn, "s1").unwrap();
+        assert_eq!(root.len(), 1);
+        assert_eq!(root[0].parent_id, None);
+        assert_ne!(root[0].id, u1);
+    }
+
+    #[test]
+    fn test_fork_session() {
+        let conn = setup_test_db();
+        AgentDao::create_session(&conn, &session("s1")).unwrap();
+        AgentDao::add_message(&conn, "s1", &message("user", "hi")).unwrap();
+        let a1 = AgentDao::add_message(&conn, "s1", &message("assistant", "hello")).unwrap();
+        AgentDao::add_message(&conn, "s1", &message("user", "more")).unwrap();
+
+        let copied = AgentDao::fork_session(&conn, "s1", a1, &session("s2")).unwrap();
+        assert_eq!(copied, 2);
+        let messages = AgentDao::get_messages(&conn, "s2").unwrap();
+        assert_eq!(texts(&messages), vec!["hi", "hello"]);
+        assert_eq!(AgentDao::get_message_count(&conn, "s1").unwrap(), 3);
+
+        // 其他会话的消息不能作为分叉点
+        assert!(AgentDao::fork_session(&conn, "s2", a1, &session("s3")).is_err());
+        assert!(!AgentDao::session_exists(&conn, "s3").unwrap());
+    }
+
+    #[test]
+    fn test_checkpoints() {
+        let conn = setup_test_db();
+        AgentDao::create_session(&conn, &session("s1")).unwrap();
+        let u1 = AgentDao::add_message(&conn, "s1", &message("user", "hi")).unwrap();
+
+        let checkpoint = AgentCheckpoint {
+            id: "cp1".to_string(),
+            session_id: "s1".to_string(),
+            message_id: Some(u1),
+            label: Some("before refactor".to_string()),
+            file_count: 2,
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+        };
+        AgentDao::create_checkpoint(&conn, &checkpoint).unwrap();
+
+        let loaded = AgentDao::get_checkpoint(&conn, "s1", "cp1")
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.message_id, Some(u1));
+        assert_eq!(loaded.file_count, 2);
+        assert!(AgentDao::get_checkpoint(&conn, "s2", "cp1")
+            .unwrap()
+            .is_none());
+        assert_eq!(AgentDao::list_checkpoints(&conn, "s1").unwrap().len(), 1);
+
+        assert!(AgentDao::delete_checkpoint(&conn, "s1", "cp1").unwrap());
+        assert!(AgentDao::list_checkpoints(&conn, "s1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pinned_messages_and_compactions() {
+        let conn = setup_test_db();
+        AgentDao::create_session(&conn, &session("s1")).unwrap();
+        let u1 = AgentDao::add_message(&conn, "s1", &message("user", "hi")).unwrap();
+        let a1 = AgentDao::add_message(&conn, "s1", &message("assistant", "hello")).unwrap();
+
+        assert!(AgentDao::set_message_pinned(&conn, "s1", u1, true).unwrap());
+        assert!(!AgentDao::set_message_pinned(&conn, "s2", u1, true).unwrap());
+        let messages = AgentDao::get_messages(&conn, "s1").unwrap();
+        assert!(messages[0].pinned);
+        assert!(!messages[1].pinned);
+
+        let compaction = AgentCompaction {
+            id: "c1".to_string(),
+            session_id: "s1".to_string(),
+            message_id: Some(a1),
+            summary: "summary".to_string(),
+            summarized_messages: 1,
+            kept_messages: 1,
+            tokens_before: 1000,
+            tokens_after: 200,
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+        };
+        AgentDao::create_compaction(&conn, &compaction).unwrap();
+        assert_eq!(AgentDao::list_compactions(&conn, "s1").unwrap().len(), 1);
+
+        let branch: HashSet<i64> = [u1, a1].into_iter().collect();
+        let found = AgentDao::get_branch_compaction(&conn, "s1", &branch)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.tokens_after, 200);
+        let other: HashSet<i64> = [u1].into_iter().collect();
+        assert!(AgentDao::get_branch_compaction(&conn, "s1", &other)
+            .unwrap()
+            .is_none());
+
+        let output = CompactedToolOutput {
+            handle: "out_1".to_string(),
+            tool_call_id: Some("call_1".to_string()),
+            content: "full output".to_string(),
+        };
+        AgentDao::save_tool_output(&conn, "s1", &output).unwrap();
+        assert_eq!(
+            AgentDao::get_tool_output(&conn, "s1", "out_1").unwrap(),
+            Some(output.clone())
+        );
+        assert!(AgentDao::get_tool_output(&conn, "s2", "out_1")
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            AgentDao::list_tool_outputs(&conn, "s1").unwrap(),
+            vec![output]
+        );
+    }
+
+    #[test]
+    fn test_migrate_linear_history() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agent_sessions (
+                id TEXT PRIMARY KEY,
+                model TEXT NOT NULL,
+                system_prompt TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            CREATE TABLE agent_messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                role TEXT NOT NULL,
+                content_json TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                tool_calls_json TEXT,
+                tool_call_id TEXT
+            );
+            INSERT INTO agent_sessions VALUES ('s1', 'm', NULL, 't', 't');
+            INSERT INTO agent_sessions VALUES ('s2', 'm', NULL, 't', 't');
+            INSERT INTO agent_messages (session_id, role, content_json, timestamp)
+                VALUES ('s1', 'user', '\"a\"', 't');
+            INSERT INTO agent_messages (session_id, role, content_json, timestamp)
+                VALUES ('s2', 'user', '\"x\"', 't');
+            INSERT INTO agent_messages (session_id, role, content_json, timestamp)
+                VALUES ('s1', 'assistant', '\"b\"', 't');",
+        )
+        .unwrap();
+
+        create_tables(&conn).unwrap();
+
+        let messages = AgentDao::get_messages(&conn, "s1").unwrap();
+        assert_eq!(texts(&messages), vec!["a", "b"]);
+        assert_eq!(
+            texts(&AgentDao::get_messages(&conn, "s2").unwrap()),
+            vec!["x"]
+        );
+
+        // 再次执行迁移不会改变已有的消息树
+        create_tables(&conn).unwrap();
+        assert_eq!(AgentDao::get_message_count(&conn, "s1").unwrap(), 2);
+    }
+}
@@ -0,0 +1,549 @@
+//! 批处理 DAO
+//!
+//! 管理 `batch_files`、`batch_jobs`、`batch_requests` 三张表。
+
+use crate::batch::models::{
+    BatchApiFormat, BatchFile, BatchJob, BatchRequest, BatchRequestInput, BatchStatus,
+    BatchValidationError, RequestCounts, RequestStatus,
+};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+pub struct BatchDao;
+
+const JOB_COLUMNS: &str = "id, api_format, endpoint, input_file_id, completion_window, status,
+    output_file_id, error_file_id, errors, metadata, created_at, in_progress_at, expires_at,
+    finalizing_at, completed_at, failed_at, expired_at, cancelling_at, cancelled_at";
+
+const REQUEST_COLUMNS: &str =
+    "batch_id, line_no, custom_id, url, body, status, attempts, status_code, response, error";
+
+fn job_from_row(row: &Row) -> Result<BatchJob, rusqlite::Error> {
+    let api_format: String = row.get(1)?;
+    let status: String = row.get(5)?;
+    let errors: Option<String> = row.get(8)?;
+    let metadata: Option<String> = row.get(9)?;
+    Ok(BatchJob {
+        id: row.get(0)?,
+        api_format: BatchApiFormat::parse(&api_format).unwrap_or(BatchApiFormat::OpenAI),
+        endpoint: row.get(2)?,
+        input_file_id: row.get(3)?,
+        completion_window: row.get(4)?,
+        status: BatchStatus::parse(&status).unwrap_or(BatchStatus::Failed),
+        output_file_id: row.get(6)?,
+        error_file_id: row.get(7)?,
+        errors: errors
+            .and_then(|s| serde_json::from_str::<Vec<BatchValidationError>>(&s).ok())
+            .unwrap_or_default(),
+        metadata: metadata.and_then(|s| serde_json::from_str(&s).ok()),
+        created_at: row.get(10)?,
+        in_progress_at: row.get(11)?,
+        expires_at: row.get(12)?,
+        finalizing_at: row.get(13)?,
+        completed_at: row.get(14)?,
+        failed_at: row.get(15)?,
+        expired_at: row.get(16)?,
+        cancelling_at: row.get(17)?,
+        cancelled_at: row.get(18)?,
+    })
+}
+
+fn request_from_row(row: &Row) -> Result<BatchRequest, rusqlite::Error> {
+    let body: String = row.get(4)?;
+    let status: String = row.get(5)?;
+    let response: Option<String> = row.get(8)?;
+    Ok(BatchRequest {
+        batch_id: row.get(0)?,
+        line_no: row.get(1)?,
+        custom_id: row.get(2)?,
+        url: row.get(3)?,
+        body: serde_json::from_str(&body).unwrap_or_default(),
+        status: RequestStatus::parse(&status).unwrap_or(RequestStatus::Failed),
+        attempts: row.get(6)?,
+        status_code: row.get(7)?,
+        response: response.and_then(|s| serde_json::from_str(&s).ok()),
+        error: row.get(9)?,
+    })
+}
+
+fn file_from_row(row: &Row) -> Result<BatchFile, rusqlite::Error> {
+    Ok(BatchFile {
+        id: row.get(0)?,
+        purpose: row.get(1)?,
+        filename: row.get(2)?,
+        bytes: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+impl BatchDao {
+    // ------------------------------------------------------------------------
+    // 文件
+    // ------------------------------------------------------------------------
+
+    pub fn insert_file(
+        conn: &Connection,
+        file: &BatchFile,
+        content: &[u8],
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "INSERT INTO batch_files (id, purpose, filename, bytes, content, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                file.id,
+                file.purpose,
+                file.filename,
+                file.bytes,
+                content,
+                file.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get_file(conn: &Connection, id: &str) -> Result<Option<BatchFile>, rusqlite::Error> {
+        conn.query_row(
+            "SELECT id, purpose, filename, bytes, created_at FROM batch_files WHERE id = ?",
+            [id],
+            file_from_row,
+        )
+        .optional()
+    }
+
+    pub fn get_file_content(
+        conn: &Connection,
+        id: &str,
+    ) -> Result<Option<Vec<u8>>, rusqlite::Error> {
+        conn.query_row(
+            "SELECT content FROM batch_files WHERE id = ?",
+            [id],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    /// 按创建时间倒序列出文件，可按 purpose 过滤
+    pub fn list_files(
+        conn: &Connection,
+        purpose: Option<&str>,
+    ) -> Result<Vec<BatchFile>, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT id, purpose, filename, bytes, created_at FROM batch_files
+             WHERE ?1 IS NULL OR purpose = ?1
+             ORDER BY created_at DESC, id DESC",
+        )?;
+        let files = stmt.query_map([purpose], file_from_row)?;
+        files.collect()
+    }
+
+    pub fn delete_file(conn: &Connection, id: &str) -> Result<bool, rusqlite::Error> {
+        let affected = conn.execute("DELETE FROM batch_files WHERE id = ?", [id])?;
+        Ok(affected > 0)
+    }
+
+    // ------------------------------------------------------------------------
+    // 任务
+    // ------------------------------------------------------------------------
+
+    pub fn insert_job(conn: &Connection, job: &BatchJob) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            &format!(
+                "INSERT INTO batch_jobs ({})
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10,
+                         ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
+                JOB_COLUMNS
+            ),
+            params![
+                job.id,
+                job.api_format.as_str(),
+                job.endpoint,
+                job.input_file_id,
+                job.completion_window,
+                job.status.as_str(),
+                job.output_file_id,
+                job.error_file_id,
+                Self::errors_json(job),
+                job.metadata.as_ref().map(|m| m.to_string()),
+                job.created_at,
+                job.in_progress_at,
+                job.expires_at,
+                job.finalizing_at,
+                job.completed_at,
+                job.failed_at,
+                job.expired_at,
+                job.cancelling_at,
+                job.cancelled_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 更新任务的状态、结果文件与各时间戳
+    pub fn update_job(conn: &Connection, job: &BatchJob) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "UPDATE batch_jobs SET status = ?1, output_file_id = ?2, error_file_id = ?3,
+             errors = ?4, in_progress_at = ?5, finalizing_at = ?6, completed_at = ?7,
+             failed_at = ?8, expired_at = ?9, cancelling_at = ?10, cancelled_at = ?11
+             WHERE id = ?12",
+            params![
+                job.status.as_str(),
+                job.output_file_id,
+                job.error_file_id,
+                Self::errors_json(job),
+                job.in_progress_at,
+                job.finalizing_at,
+                job.completed_at,
+                job.failed_at,
+                job.expired_at,
+                job.cancelling_at,
+                job.cancelled_at,
+                job.id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn errors_json(job: &BatchJob) -> Option<String> {
+        (!job.errors.is_empty()).then(|| serde_json::to_string(&job.errors).unwrap_or_default())
+    }
+
+    pub fn get_job(conn: &Connection, id: &str) -> Result<Option<BatchJob>, rusqlite::Error> {
+        conn.query_row(
+            &format!("SELECT {} FROM batch_jobs WHERE id = ?", JOB_COLUMNS),
+            [id],
+            job_from_row,
+        )
+        .optional()
+    }
+
+    /// 分页列出任务（创建时间倒序）
+    ///
+    /// `after` 为上一页最后一个任务的 ID；返回值第二项表示是否还有更多。
+    pub fn list_jobs(
+        conn: &Connection,
+        format: BatchApiFormat,
+        limit: usize,
+        after: Option<&str>,
+    ) -> Result<(Vec<BatchJob>, bool), rusqlite::Error> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM batch_jobs
+             WHERE api_format = ?1
+               AND (?2 IS NULL OR (created_at, id) <
+                    (SELECT created_at, id FROM batch_jobs WHERE id = ?2))
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?3",
+            JOB_COLUMNS
+        ))?;
+        let mut jobs = stmt
+            .query_map(
+                params![format.as_str(), after, (limit + 1) as i64],
+                job_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        let has_more = jobs.len() > limit;
+        jobs.truncate(limit);
+        Ok((jobs, has_more))
+    }
+
+    /// 删除任务及其请求
+    pub fn delete_job(conn: &Connection, id: &str) -> Result<bool, rusqlite::Error> {
+        conn.execute("DELETE FROM batch_requests WHERE batch_id = ?", [id])?;
+        let affected = conn.execute("DELETE FROM batch_jobs WHERE id = ?", [id])?;
+        Ok(affected > 0)
+    }
+
+    /// 下一个需要处理的任务：取消中的任务优先，其次按创建时间
+    pub fn next_active_job(conn: &Connection) -> Result<Option<BatchJob>, rusqlite::Error> {
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM batch_jobs
+                 WHERE status IN ('in_progress', 'cancelling')
+                 ORDER BY status = 'cancelling' DESC, created_at, id
+                 LIMIT 1",
+                JOB_COLUMNS
+            ),
+            [],
+            job_from_row,
+        )
+        .optional()
+    }
+
+    // ------------------------------------------------------------------------
+    // 请求
+    // ------------------------------------------------------------------------
+
+    /// 批量写入请求（单个事务）
+    pub fn insert_requests(
+        conn: &Connection,
+        batch_id: &str,
+        requests: &[BatchRequestInput],
+    ) -> Result<(), rusqlite::Error> {
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO batch_requests (batch_id, line_no, custom_id, url, body, status)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 'pending')",
+            )?;
+            for (line_no, request) in requests.iter().enumerate() {
+                stmt.execute(params![
+                    batch_id,
+                    line_no as i64,
+                    request.custom_id,
+                    request.url,
+                    request.body.to_string(),
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    pub fn request_counts(
+        conn: &Connection,
+        batch_id: &str,
+    ) -> Result<RequestCounts, rusqlite::Error> {
+        let mut stmt = conn.prepare(
+            "SELECT status, COUNT(*) FROM batch_requests WHERE batch_id = ? GROUP BY status",
+        )?;
+        let rows = stmt.query_map([batch_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        let mut counts = RequestCounts::default();
+        for row in rows {
+            let (status, count) = row?;
+            if let Some(status) = RequestStatus::parse(&status) {
+                counts.add(status, count as u64);
+            }
+        }
+        Ok(counts)
+    }
+
+    /// 将中断时仍在执行的请求恢复为待执行（启动时调用）
+    pub fn reset_running(conn: &Connection) -> Result<usize, rusqlite::Error> {
+        conn.execute(
+            "UPDATE batch_requests SET status = 'pending' WHERE status = 'running'",
+            [],
+        )
+    }
+
+    /// 领取下一条待执行请求并标记为执行中
+    pub fn claim_next_request(
+        conn: &Connection,
+        batch_id: &str,
+    ) -> Result<Option<BatchRequest>, rusqlite::Error> {
+        let request = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM batch_requests
+                     WHERE batch_id = ? AND status = 'pending'
+                     ORDER BY line_no LIMIT 1",
+                    REQUEST_COLUMNS
+                ),
+                [batch_id],
+                request_from_row,
+            )
+            .optional()?;
+        let Some(mut request) = request else {
+            return Ok(None);
+        };
+        conn.execute(
+            "UPDATE batch_requests SET status = 'running'
+             WHERE batch_id = ?1 AND line_no = ?2",
+            params![batch_id, request.line_no],
+        )?;
+        request.status = RequestStatus::Running;
+        Ok(Some(request))
+    }
+
+    /// 记录请求的最终结果
+    pub fn complete_request(
+        conn: &Connection,
+        request: &BatchRequest,
+        completed_at: i64,
+    ) -> Result<(), rusqlite::Error> {
+        conn.execute(
+            "UPDATE batch_requests SET status = ?1, attempts = ?2, status_code = ?3,
+             response = ?4, error = ?5, completed_at = ?6
+             WHERE batch_id = ?7 AND line_no = ?8",
+            params![
+                request.status.as_str(),
+                request.attempts,
+                request.status_code,
+                request.response.as_ref().map(|r| r.to_string()),
+                request.error,
+                completed_at,
+                request.batch_id,
+                request.line_no,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// 将所有未执行的请求标记为指定状态（取消 / 过期）
+    pub fn close_pending(
+        conn: &Connection,
+        batch_id: &str,
+        status: RequestStatus,
+    ) -> Result<usize, rusqlite::Error> {
+        conn.execute(
+            "UPDATE batch_requests SET status = ?1
+             WHERE batch_id = ?2 AND status IN ('pending', 'running')",
+            params![status.as_str(), batch_id],
+        )
+    }
+
+    /// 按输入顺序获取任务的全部请求
+    pub fn get_requests(
+        conn: &Connection,
+        batch_id: &str,
+    ) -> Result<Vec<BatchRequest>, rusqlite::Error> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM batch_requests WHERE batch_id = ? ORDER BY line_no",
+            REQUEST_COLUMNS
+        ))?;
+        let requests = stmt.query_map([batch_id], request_from_row)?;
+        requests.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::schema::create_tables;
+    use serde_json::json;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn
+    }
+
+    fn inputs(n: usize) -> Vec<BatchRequestInput> {
+        (0..n)
+            .map(|i| BatchRequestInput {
+                custom_id: format!("req-{}", i),
+                url: "/v1/chat/completions".to_string(),
+                body: json!({"model": "gpt-4o", "messages": []}),
+            })
+            .collect()
+    }
+
+    fn insert_job(conn: &Connection, requests: usize) -> BatchJob {
+        let mut job = BatchJob::new(
+            BatchApiFormat::OpenAI,
+            "/v1/chat/completions",
+            Some("file-1".to_string()),
+            "24h",
+            86_400,
+            Some(json!({"run": "eval"})),
+        );
+        job.transition(BatchStatus::InProgress);
+        BatchDao::insert_job(conn, &job).unwrap();
+        BatchDao::insert_requests(conn, &job.id, &inputs(requests)).unwrap();
+        job
+    }
+
+    #[test]
+    fn test_file_roundtrip() {
+        let conn = setup_test_db();
+        let file = BatchFile {
+            id: "file-1".to_string(),
+            purpose: "batch".to_string(),
+            filename: "input.jsonl".to_string(),
+            bytes: 3,
+            created_at: 1,
+        };
+        BatchDao::insert_file(&conn, &file, b"abc").unwrap();
+
+        assert_eq!(BatchDao::get_file(&conn, "file-1").unwrap(), Some(file));
+        assert_eq!(
+            BatchDao::get_file_content(&conn, "file-1").unwrap(),
+            Some(b"abc".to_vec())
+        );
+        assert_eq!(BatchDao::list_files(&conn, Some("batch")).unwrap().len(), 1);
+        assert!(BatchDao::list_files(&conn, Some("batch_output"))
+            .unwrap()
+            .is_empty());
+        assert!(BatchDao::delete_file(&conn, "file-1").unwrap());
+        assert!(BatchDao::get_file(&conn, "file-1").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_job_roundtrip_and_pagination() {
+        let conn = setup_test_db();
+        let first = insert_job(&conn, 1);
+        let loaded = BatchDao::get_job(&conn, &first.id).unwrap().unwrap();
+        assert_eq!(loaded, first);
+
+        let second = insert_job(&conn, 1);
+        let (page, has_more) = BatchDao::list_jobs(&conn, BatchApiFormat::OpenAI, 1, None).unwrap();
+        assert_eq!(page.len(), 1);
+        assert!(has_more);
+        let (rest, has_more) =
+            BatchDao::list_jobs(&conn, BatchApiFormat::OpenAI, 10, Some(&page[0].id)).unwrap();
+        assert_eq!(rest.len(), 1);
+        assert!(!has_more);
+        assert_ne!(rest[0].id, page[0].id);
+        assert!([&first.id, &second.id].contains(&&rest[0].id));
+
+        assert!(
+            BatchDao::list_jobs(&conn, BatchApiFormat::Anthropic, 10, None)
+                .unwrap()
+                .0
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_request_lifecycle() {
+        let conn = setup_test_db();
+        let job = insert_job(&conn, 3);
+        assert_eq!(
+            BatchDao::next_active_job(&conn).unwrap().map(|j| j.id),
+            Some(job.id.clone())
+        );
+
+        let mut first = BatchDao::claim_next_request(&conn, &job.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.line_no, 0);
+        first.status = RequestStatus::Succeeded;
+        first.attempts = 1;
+        first.status_code = Some(200);
+        first.response = Some(json!({"id": "chatcmpl-1"}));
+        BatchDao::complete_request(&conn, &first, 10).unwrap();
+
+        let second = BatchDao::claim_next_request(&conn, &job.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.line_no, 1);
+        let counts = BatchDao::request_counts(&conn, &job.id).unwrap();
+        assert_eq!(
+            (counts.succeeded, counts.running, counts.pending),
+            (1, 1, 1)
+        );
+
+        // 重启后执行中的请求回到待执行
+        assert_eq!(BatchDao::reset_running(&conn).unwrap(), 1);
+        assert_eq!(
+            BatchDao::claim_next_request(&conn, &job.id)
+                .unwrap()
+                .unwrap()
+                .line_no,
+            1
+        );
+
+        assert_eq!(
+            BatchDao::close_pending(&conn, &job.id, RequestStatus::Cancelled).unwrap(),
+            2
+        );
+        assert!(BatchDao::claim_next_request(&conn, &job.id)
+            .unwrap()
+            .is_none());
+        let requests = BatchDao::get_requests(&conn, &job.id).unwrap();
+        assert_eq!(requests[0].response, Some(json!({"id": "chatcmpl-1"})));
+        assert_eq!(requests[2].status, RequestStatus::Cancelled);
+
+        assert!(BatchDao::delete_job(&conn, &job.id).unwrap());
+        assert!(BatchDao::get_requests(&conn, &job.id).unwrap().is_empty());
+        assert!(BatchDao::next_active_job(&conn).unwrap().is_none());
+    }
+}
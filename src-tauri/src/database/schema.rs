@@ -591,6 +591,113 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
         [],
     )?;
 
+    // Migration: 消息树（parent_id 指向上一条消息，同一父消息下的多条消息构成分支）
+    if conn
+        .execute(
+            "ALTER TABLE agent_messages ADD COLUMN parent_id INTEGER",
+            [],
+        )
+        .is_ok()
+    {
+        // 已有的线性历史串成单链
+        conn.execute(
+            "UPDATE agent_messages SET parent_id = (
+                SELECT MAX(prev.id) FROM agent_messages prev
+                WHERE prev.session_id = agent_messages.session_id
+                  AND prev.id < agent_messages.id
+            )",
+            [],
+        )?;
+    }
+
+    // Migration: 会话当前激活分支的末端消息（NULL 表示空分支）
+    if conn
+        .execute(
+            "ALTER TABLE agent_sessions ADD COLUMN active_leaf_id INTEGER",
+            [],
+        )
+        .is_ok()
+    {
+        conn.execute(
+            "UPDATE agent_sessions SET active_leaf_id = (
+                SELECT MAX(id) FROM agent_messages WHERE session_id = agent_sessions.id
+            )",
+            [],
+        )?;
+    }
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agent_messages_parent ON agent_messages(parent_id)",
+        [],
+    )?;
+
+    // Agent 检查点表
+    // 记录会话分支上的消息位置，工作文件快照保存在 SessionFileStorage 中
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_checkpoints (
+            id TEXT PRIMARY KEY,
+            session_id TEXT NOT NULL,
+            message_id INTEGER,
+            label TEXT,
+            file_count INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (session_id) REFERENCES agent_sessions(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agent_checkpoints_session ON agent_checkpoints(session_id)",
+        [],
+    )?;
+
+    // Migration: 置顶消息（上下文压缩时原样保留）
+    let _ = conn.execute(
+        "ALTER TABLE agent_messages ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // Agent 上下文压缩记录表
+    // message_id 为压缩时分支的末端消息，该消息及之前的历史由摘要替代
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_compactions (
+            id TEXT PRIMARY KEY,
+            session_id TEXT NOT NULL,
+            message_id INTEGER,
+            summary TEXT NOT NULL,
+            summarized_messages INTEGER NOT NULL DEFAULT 0,
+            kept_messages INTEGER NOT NULL DEFAULT 0,
+            tokens_before INTEGER NOT NULL DEFAULT 0,
+            tokens_after INTEGER NOT NULL DEFAULT 0,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (session_id) REFERENCES agent_sessions(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agent_compactions_session ON agent_compactions(session_id)",
+        [],
+    )?;
+
+    // 被截断的工具输出（通过 retrieve_tool_output 工具按句柄取回）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_tool_outputs (
+            handle TEXT PRIMARY KEY,
+            session_id TEXT NOT NULL,
+            tool_call_id TEXT,
+            content TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            FOREIGN KEY (session_id) REFERENCES agent_sessions(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agent_tool_outputs_session ON agent_tool_outputs(session_id)",
+        [],
+    )?;
+
     // ============================================================================
     // General Chat 相关表
     // ============================================================================
@@ -643,6 +750,79 @@ pub fn create_tables(conn: &Connection) -> Result<(), rusqlite::Error> {
         [],
     )?;
 
+    // ============================================================================
+    // Batch API 相关表
+    // ============================================================================
+
+    // 批处理文件表（输入 JSONL 与生成的结果 / 错误文件）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS batch_files (
+            id TEXT PRIMARY KEY,
+            purpose TEXT NOT NULL,
+            filename TEXT NOT NULL,
+            bytes INTEGER NOT NULL,
+            content BLOB NOT NULL,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // 批处理任务表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS batch_jobs (
+            id TEXT PRIMARY KEY,
+            api_format TEXT NOT NULL,
+            endpoint TEXT NOT NULL,
+            input_file_id TEXT,
+            completion_window TEXT NOT NULL,
+            status TEXT NOT NULL,
+            output_file_id TEXT,
+            error_file_id TEXT,
+            errors TEXT,
+            metadata TEXT,
+            created_at INTEGER NOT NULL,
+            in_progress_at INTEGER,
+            expires_at INTEGER NOT NULL,
+            finalizing_at INTEGER,
+            completed_at INTEGER,
+            failed_at INTEGER,
+            expired_at INTEGER,
+            cancelling_at INTEGER,
+            cancelled_at INTEGER
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_batch_jobs_status ON batch_jobs(status, created_at)",
+        [],
+    )?;
+
+    // 批处理请求表（每行对应输入文件中的一条请求）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS batch_requests (
+            batch_id TEXT NOT NULL,
+            line_no INTEGER NOT NULL,
+            custom_id TEXT NOT NULL,
+            url TEXT NOT NULL,
+            body TEXT NOT NULL,
+            status TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            status_code INTEGER,
+            response TEXT,
+            error TEXT,
+            completed_at INTEGER,
+            PRIMARY KEY (batch_id, line_no),
+            FOREIGN KEY (batch_id) REFERENCES batch_jobs(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_batch_requests_status ON batch_requests(batch_id, status)",
+        [],
+    )?;
+
     Ok(())
 }
 
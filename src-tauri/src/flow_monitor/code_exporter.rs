@@ -454,6 +454,8 @@ mod tests {
             target_url: Some("https://api.openai.com".to_string()),
             route_rule: None,
             load_balance_strategy: None,
+            attempts: Vec::new(),
+            trace: None,
         };
 
         LLMFlow {
@@ -730,6 +732,8 @@ mod property_tests {
                 target_url: base_url,
                 route_rule: None,
                 load_balance_strategy: None,
+                attempts: Vec::new(),
+                trace: None,
             };
 
             LLMFlow {
@@ -1276,6 +1276,9 @@ mod property_tests {
             routing_info: Default::default(),
             injected_params: None,
             context_usage_percentage: None,
+            unsupported_params: Vec::new(),
+            structured_output: None,
+            guardrail_events: Vec::new(),
         })
     }
 
@@ -1625,6 +1625,9 @@ mod property_tests {
             routing_info: RoutingInfo::default(),
             injected_params: None,
             context_usage_percentage: None,
+            unsupported_params: Vec::new(),
+            structured_output: None,
+            guardrail_events: Vec::new(),
         })
     }
 
@@ -1977,6 +1980,9 @@ mod redaction_property_tests {
                         routing_info: RoutingInfo::default(),
                         injected_params: None,
                         context_usage_percentage: None,
+                        unsupported_params: Vec::new(),
+                        structured_output: None,
+                        guardrail_events: Vec::new(),
                     };
 
                     let mut flow = LLMFlow::new(id, flow_type, request, metadata);
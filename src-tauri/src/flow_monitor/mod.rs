@@ -34,8 +34,13 @@ pub mod stream_rebuilder;
 
 // 重新导出核心类型
 pub use models::{
+    AttemptKind,
+    AttemptOutcome,
+    CandidateExclusion,
+    CandidateTrace,
     ClientInfo,
     ContentPart,
+    CredentialSource,
     FlowAnnotations,
     // 错误
     FlowError,
@@ -45,6 +50,8 @@ pub use models::{
     FlowState,
     FlowTimestamps,
     FlowType,
+    GuardrailAuditAction,
+    GuardrailEvent,
     // 核心 Flow 结构
     LLMFlow,
     // 请求相关
@@ -54,11 +61,16 @@ pub use models::{
     Message,
     MessageContent,
     MessageRole,
+    ProviderSource,
     RequestParameters,
+    RoutingAttempt,
     RoutingInfo,
+    RoutingTrace,
     StopReason,
     StreamChunk,
     StreamInfo,
+    StructuredOutputMode,
+    StructuredOutputValidation,
     ThinkingContent,
     TokenUsage,
     ToolCall,
@@ -561,6 +561,15 @@ pub struct FlowMetadata {
     /// 上下文使用百分比
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context_usage_percentage: Option<f32>,
+    /// 目标协议无法映射而被丢弃的请求参数
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unsupported_params: Vec<String>,
+    /// 结构化输出（response_format）校验结果
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_output: Option<StructuredOutputValidation>,
+    /// 出站防护审计记录
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub guardrail_events: Vec<GuardrailEvent>,
 }
 
 impl Default for FlowMetadata {
@@ -575,6 +584,9 @@ impl Default for FlowMetadata {
             routing_info: RoutingInfo::default(),
             injected_params: None,
             context_usage_percentage: None,
+            unsupported_params: Vec::new(),
+            structured_output: None,
+            guardrail_events: Vec::new(),
         }
     }
 }
@@ -605,6 +617,233 @@ pub struct RoutingInfo {
     /// 负载均衡策略
     #[serde(skip_serializing_if = "Option::is_none")]
     pub load_balance_strategy: Option<String>,
+    /// 上游调用尝试记录（对冲、故障转移等）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<RoutingAttempt>,
+    /// 路由决策过程（别名解析、Provider 选择、候选凭证筛选）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<RoutingTrace>,
+}
+
+/// Provider 的选择来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderSource {
+    /// X-Provider-Id 请求头显式指定
+    ProviderIdHeader,
+    /// 回退链首跳指定
+    FallbackHop,
+    /// 客户端类型对应的端点 Provider 配置
+    EndpointOverride,
+    /// 默认 Provider
+    Default,
+}
+
+/// 最终凭证的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialSource {
+    /// 凭证池
+    Pool,
+    /// 凭证池无可用凭证时降级到 API Key Provider
+    ApiKeyProvider,
+}
+
+/// 候选凭证被排除的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateExclusion {
+    /// 已禁用
+    Disabled,
+    /// 健康检查未通过
+    Unhealthy,
+    /// 模型在 not_supported_models / excluded_models 中
+    ModelNotSupported,
+    /// 与客户端类型不兼容
+    ClientIncompatible,
+    /// 处于限流冷却期
+    CoolingDown,
+    /// 已达到并发上限
+    ConcurrencyLimit,
+}
+
+/// 单个候选凭证的筛选结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CandidateTrace {
+    /// 凭证 UUID
+    pub credential_id: String,
+    /// 凭证名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// 凭证的 Provider 类型
+    pub provider_type: String,
+    /// 是否通过筛选
+    pub accepted: bool,
+    /// 被排除的原因（通过时为空）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excluded_by: Vec<CandidateExclusion>,
+    /// 风控风险等级
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk_level: Option<String>,
+    /// 权重分数（仅通过筛选的凭证）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+}
+
+/// 路由决策过程
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoutingTrace {
+    /// 客户端请求的模型
+    pub requested_model: String,
+    /// 回退链（`a|b|c`、请求头或配置展开后的模型列表）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_chain: Vec<String>,
+    /// 别名解析后的模型
+    pub resolved_model: String,
+    /// 根据 User-Agent 检测到的客户端类型
+    pub client_type: String,
+    /// 客户端类型对应的端点 Provider 配置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_provider: Option<String>,
+    /// 默认 Provider
+    pub default_provider: String,
+    /// X-Provider-Id 请求头
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_id_header: Option<String>,
+    /// 最终选择的 Provider
+    pub selected_provider: String,
+    /// Provider 的选择来源
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_source: Option<ProviderSource>,
+    /// 候选凭证及筛选结果
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<CandidateTrace>,
+    /// 最终选中的凭证 UUID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected_credential: Option<String>,
+    /// 最终凭证的来源
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_source: Option<CredentialSource>,
+    /// 会话指纹；凭证池选择不做会话粘滞，仅用于关联同一会话的请求
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// 协议转换路径，如 `openai->kiro`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversion: Option<String>,
+}
+
+/// 上游调用尝试类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptKind {
+    /// 首次调用
+    Primary,
+    /// 首字节超时后发起的对冲调用
+    Hedge,
+    /// 故障转移后的调用
+    Failover,
+}
+
+/// 上游调用尝试结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptOutcome {
+    /// 被采用的响应
+    Won,
+    /// 被取消（对冲竞争失败）
+    Cancelled,
+    /// 调用失败
+    Failed,
+}
+
+/// 结构化输出的实现方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StructuredOutputMode {
+    /// 后端原生支持（OpenAI json_schema、Gemini responseSchema）
+    Native,
+    /// 强制调用以 schema 为参数的工具（Claude）
+    ToolForcing,
+    /// 代理注入 schema 说明并校验、修复输出
+    PromptFallback,
+}
+
+/// 结构化输出校验结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructuredOutputValidation {
+    /// 实现方式
+    pub mode: StructuredOutputMode,
+    /// schema 名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_name: Option<String>,
+    /// 是否校验通过（流式响应无法校验时为空）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid: Option<bool>,
+    /// 上游调用次数（含修复重试）
+    pub attempts: u32,
+    /// 最后一次校验的错误
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// 出站防护动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuardrailAuditAction {
+    /// 请求被拒绝
+    Blocked,
+    /// 敏感数据被打码
+    Masked,
+    /// 敏感数据被替换为占位符
+    Tokenized,
+    /// 响应中的占位符被还原
+    Restored,
+}
+
+/// 出站防护审计记录（不包含敏感数据原文）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardrailEvent {
+    /// 检测器 / 规则名称
+    pub detector: String,
+    /// 处理动作
+    pub action: GuardrailAuditAction,
+    /// 命中位置（如 `messages[2].content`、`response`）
+    pub location: String,
+    /// 命中次数
+    pub count: u32,
+    /// 时间戳
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 单次上游调用尝试
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingAttempt {
+    /// 尝试类型
+    pub kind: AttemptKind,
+    /// 尝试结果
+    pub outcome: AttemptOutcome,
+    /// 提供商类型
+    pub provider: ProviderType,
+    /// 凭证 ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<String>,
+    /// 相对请求开始的发起时间（毫秒）
+    pub started_after_ms: u64,
+    /// 首字节时间（毫秒，相对本次尝试发起）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttfb_ms: Option<u64>,
+    /// 错误信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// 被浪费的 Token 数（被取消尝试已消耗的 Token）
+    #[serde(default)]
+    pub wasted_tokens: u32,
+    /// 本次尝试使用的模型（回退链跳转时可能与原始模型不同）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// 协议转换路径（如 `openai->gemini`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversion: Option<String>,
 }
 
 /// 时间戳集合
@@ -937,6 +1176,44 @@ mod tests {
         assert_eq!(flow.id, deserialized.id);
         assert_eq!(flow.state, deserialized.state);
     }
+
+    #[test]
+    fn test_routing_trace_serialization() {
+        let trace = RoutingTrace {
+            requested_model: "fast".to_string(),
+            resolved_model: "claude-sonnet-4-5".to_string(),
+            client_type: "claude_code".to_string(),
+            default_provider: "kiro".to_string(),
+            selected_provider: "kiro".to_string(),
+            provider_source: Some(ProviderSource::Default),
+            candidates: vec![CandidateTrace {
+                credential_id: "cred-1".to_string(),
+                name: None,
+                provider_type: "kiro".to_string(),
+                accepted: false,
+                excluded_by: vec![CandidateExclusion::CoolingDown],
+                risk_level: Some("high".to_string()),
+                score: None,
+            }],
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&trace).unwrap();
+        assert_eq!(json["provider_source"], "default");
+        assert_eq!(json["candidates"][0]["excluded_by"][0], "cooling_down");
+        assert!(json.get("endpoint_provider").is_none());
+
+        // 旧版本记录的 Flow 没有 trace 字段
+        let info: RoutingInfo = serde_json::from_str("{}").unwrap();
+        assert!(info.trace.is_none());
+        let info = RoutingInfo {
+            trace: Some(trace.clone()),
+            ..Default::default()
+        };
+        let roundtrip: RoutingInfo =
+            serde_json::from_str(&serde_json::to_string(&info).unwrap()).unwrap();
+        assert_eq!(roundtrip.trace, Some(trace));
+    }
 }
 
 // ============================================================================
@@ -1073,6 +1350,9 @@ mod property_tests {
                 routing_info: RoutingInfo::default(),
                 injected_params: None,
                 context_usage_percentage: None,
+                unsupported_params: Vec::new(),
+                structured_output: None,
+                guardrail_events: Vec::new(),
             })
     }
 
@@ -20,8 +20,8 @@ use uuid::Uuid;
 use super::file_store::FlowFileStore;
 use super::memory_store::FlowMemoryStore;
 use super::models::{
-    FlowAnnotations, FlowError, FlowMetadata, FlowState, FlowType, LLMFlow, LLMRequest,
-    LLMResponse, TokenUsage,
+    FlowAnnotations, FlowError, FlowMetadata, FlowState, FlowType, GuardrailEvent, LLMFlow,
+    LLMRequest, LLMResponse, RoutingAttempt, StructuredOutputValidation, TokenUsage,
 };
 use super::stream_rebuilder::{StreamFormat, StreamRebuilder};
 
@@ -1305,6 +1305,73 @@ impl FlowMonitor {
         }
     }
 
+    /// 记录上游调用尝试
+    ///
+    /// 用于对冲请求、故障转移等一次请求对应多次上游调用的场景。
+    ///
+    /// # 参数
+    /// - `flow_id`: Flow ID
+    /// - `attempts`: 调用尝试列表
+    pub async fn record_attempts(&self, flow_id: &str, attempts: Vec<RoutingAttempt>) {
+        let mut active = self.active_flows.write().await;
+        if let Some(active_flow) = active.get_mut(flow_id) {
+            active_flow
+                .flow
+                .metadata
+                .routing_info
+                .attempts
+                .extend(attempts);
+        }
+    }
+
+    /// 记录结构化输出校验结果
+    ///
+    /// # 参数
+    /// - `flow_id`: Flow ID
+    /// - `validation`: 校验结果
+    pub async fn record_structured_output(
+        &self,
+        flow_id: &str,
+        validation: StructuredOutputValidation,
+    ) {
+        let mut active = self.active_flows.write().await;
+        if let Some(active_flow) = active.get_mut(flow_id) {
+            active_flow.flow.metadata.structured_output = Some(validation);
+        }
+    }
+
+    /// 追加出站防护审计记录
+    ///
+    /// # 参数
+    /// - `flow_id`: Flow ID
+    /// - `events`: 审计记录
+    pub async fn record_guardrail_events(&self, flow_id: &str, events: Vec<GuardrailEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        let mut active = self.active_flows.write().await;
+        if let Some(active_flow) = active.get_mut(flow_id) {
+            active_flow.flow.metadata.guardrail_events.extend(events);
+        }
+    }
+
+    /// 记录协议转换时无法映射的请求参数
+    ///
+    /// # 参数
+    /// - `flow_id`: Flow ID
+    /// - `params`: 参数名列表
+    pub async fn record_unsupported_params(&self, flow_id: &str, params: Vec<String>) {
+        let mut active = self.active_flows.write().await;
+        if let Some(active_flow) = active.get_mut(flow_id) {
+            let recorded = &mut active_flow.flow.metadata.unsupported_params;
+            for param in params {
+                if !recorded.contains(&param) {
+                    recorded.push(param);
+                }
+            }
+        }
+    }
+
     /// 取消 Flow
     ///
     /// # 参数
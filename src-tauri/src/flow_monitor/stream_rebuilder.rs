@@ -470,6 +470,7 @@ impl StreamRebuilder {
                 if let Some(input_tokens) = usage.get("input_tokens").and_then(|v| v.as_u64()) {
                     self.usage.input_tokens = input_tokens as u32;
                 }
+                self.process_anthropic_cache_usage(usage);
             }
         }
         Ok(())
@@ -602,11 +603,28 @@ impl StreamRebuilder {
             if let Some(output_tokens) = usage.get("output_tokens").and_then(|v| v.as_u64()) {
                 self.usage.output_tokens = output_tokens as u32;
             }
+            self.process_anthropic_cache_usage(usage);
         }
 
         Ok(())
     }
 
+    /// 处理 Anthropic Prompt Caching 用量（cache_creation / cache_read）
+    fn process_anthropic_cache_usage(&mut self, usage: &serde_json::Value) {
+        if let Some(tokens) = usage
+            .get("cache_creation_input_tokens")
+            .and_then(|v| v.as_u64())
+        {
+            self.usage.cache_write_tokens = Some(tokens as u32);
+        }
+        if let Some(tokens) = usage
+            .get("cache_read_input_tokens")
+            .and_then(|v| v.as_u64())
+        {
+            self.usage.cache_read_tokens = Some(tokens as u32);
+        }
+    }
+
     /// 解析 Anthropic 停止原因
     fn parse_anthropic_stop_reason(reason: &str) -> StopReason {
         match reason {
@@ -1095,6 +1113,28 @@ mod tests {
         assert_eq!(response.usage.output_tokens, 5);
     }
 
+    #[test]
+    fn test_anthropic_stream_cache_usage() {
+        let mut rebuilder = StreamRebuilder::new(StreamFormat::Anthropic);
+
+        rebuilder
+            .process_event(
+                Some("message_start"),
+                r#"{"type":"message_start","message":{"id":"msg_123","model":"claude-3","usage":{"input_tokens":10,"cache_creation_input_tokens":1200,"cache_read_input_tokens":0}}}"#,
+            )
+            .unwrap();
+        rebuilder
+            .process_event(
+                Some("message_delta"),
+                r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":5,"cache_read_input_tokens":800}}"#,
+            )
+            .unwrap();
+
+        let response = rebuilder.finish();
+        assert_eq!(response.usage.cache_write_tokens, Some(1200));
+        assert_eq!(response.usage.cache_read_tokens, Some(800));
+    }
+
     #[test]
     fn test_anthropic_tool_use_stream() {
         let mut rebuilder = StreamRebuilder::new(StreamFormat::Anthropic);
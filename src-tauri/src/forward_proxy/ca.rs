@@ -0,0 +1,470 @@
+//! 本地证书颁发机构
+//!
+//! 首次启动时生成根证书（`~/.proxycast/mitm/`），按主机签发叶子证书并缓存 TLS 配置。
+//! 客户端需信任 `proxycast-ca.pem` 才能通过解密后的连接。
+//!
+//! 根证书带有关键的名称约束（NameConstraints），只能为 `intercept_hosts` 签发有效证书，
+//! 私钥泄露也无法冒充其他域名。约束写入证书后不可修改，拦截主机列表变化时重新生成根证书。
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use openssl::asn1::{Asn1Object, Asn1OctetString, Asn1Time};
+use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{self, AlpnError, SslAcceptor, SslMethod};
+use openssl::x509::extension::{
+    AuthorityKeyIdentifier, BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAlternativeName,
+    SubjectKeyIdentifier,
+};
+use openssl::x509::{X509Builder, X509Extension, X509Name, X509NameBuilder, X509};
+use parking_lot::Mutex;
+
+/// 根证书文件名
+pub const CA_CERT_FILE: &str = "proxycast-ca.pem";
+/// 根证书私钥文件名
+const CA_KEY_FILE: &str = "proxycast-ca-key.pem";
+/// 根证书名称约束记录，与当前拦截主机不一致时重新生成根证书
+const CA_NAMES_FILE: &str = "proxycast-ca-names.txt";
+/// NameConstraints 扩展的 OID
+const NAME_CONSTRAINTS_OID: &str = "2.5.29.30";
+/// 根证书有效期（天）
+const CA_VALIDITY_DAYS: i64 = 3650;
+/// 叶子证书有效期（天），不超过浏览器与 Apple 平台接受的 398 天上限
+const LEAF_VALIDITY_DAYS: i64 = 397;
+/// 签发时回拨的时间，容忍客户端时钟偏差
+const CLOCK_SKEW_SECS: i64 = 86_400;
+/// 协商的应用层协议（优先 HTTP/2）
+const ALPN_PROTOCOLS: &[u8] = b"\x02h2\x08http/1.1";
+
+/// 本地 CA
+pub struct CertificateAuthority {
+    cert: X509,
+    key: PKey<Private>,
+    /// 所有叶子证书共用的密钥
+    leaf_key: PKey<Private>,
+    /// 名称约束允许的主机（DNS 名称同时覆盖其子域名）
+    permitted: Vec<String>,
+    cert_path: PathBuf,
+    acceptors: Mutex<HashMap<String, Arc<SslAcceptor>>>,
+}
+
+impl CertificateAuthority {
+    /// 默认的证书目录
+    pub fn default_dir() -> Result<PathBuf, String> {
+        let home = dirs::home_dir().ok_or_else(|| "无法获取主目录".to_string())?;
+        Ok(home.join(".proxycast").join("mitm"))
+    }
+
+    /// 从目录加载根证书，不存在或名称约束与 `intercept_hosts` 不一致时生成
+    pub fn load_or_create(dir: &Path, intercept_hosts: &[String]) -> Result<Self, String> {
+        let cert_path = dir.join(CA_CERT_FILE);
+        let key_path = dir.join(CA_KEY_FILE);
+        let names_path = dir.join(CA_NAMES_FILE);
+        let permitted = permitted_names(intercept_hosts);
+        let names = permitted.join("\n");
+
+        let existing = cert_path.exists() && key_path.exists();
+        let unchanged = std::fs::read_to_string(&names_path).is_ok_and(|saved| saved == names);
+        let (cert, key) = if existing && unchanged {
+            let cert = std::fs::read(&cert_path)
+                .map_err(|e| format!("读取根证书失败 {:?}: {}", cert_path, e))?;
+            let key = std::fs::read(&key_path)
+                .map_err(|e| format!("读取根证书私钥失败 {:?}: {}", key_path, e))?;
+            (
+                X509::from_pem(&cert).map_err(|e| format!("解析根证书失败: {}", e))?,
+                PKey::private_key_from_pem(&key)
+                    .map_err(|e| format!("解析根证书私钥失败: {}", e))?,
+            )
+        } else {
+            if existing {
+                tracing::warn!(
+                    "[FORWARD_PROXY] 拦截主机列表已变化，重新生成根证书，客户端需要重新信任"
+                );
+            }
+            let (cert, key) =
+                generate_ca(&permitted).map_err(|e| format!("生成根证书失败: {}", e))?;
+            std::fs::create_dir_all(dir).map_err(|e| format!("无法创建目录 {:?}: {}", dir, e))?;
+            let cert_pem = cert.to_pem().map_err(|e| e.to_string())?;
+            let key_pem = key.private_key_to_pem_pkcs8().map_err(|e| e.to_string())?;
+            write_private(&key_path, &key_pem)?;
+            std::fs::write(&cert_path, cert_pem)
+                .map_err(|e| format!("写入根证书失败 {:?}: {}", cert_path, e))?;
+            std::fs::write(&names_path, &names)
+                .map_err(|e| format!("写入名称约束失败 {:?}: {}", names_path, e))?;
+            tracing::info!("[FORWARD_PROXY] 已生成本地根证书: {:?}", cert_path);
+            (cert, key)
+        };
+
+        Ok(Self {
+            cert,
+            key,
+            leaf_key: generate_key().map_err(|e| format!("生成叶子证书密钥失败: {}", e))?,
+            permitted,
+            cert_path,
+            acceptors: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// 根证书路径（供用户导入信任）
+    pub fn cert_path(&self) -> &Path {
+        &self.cert_path
+    }
+
+    /// 根证书的名称约束是否允许该主机（不允许的主机即使签发证书也无法通过校验）
+    pub fn permits(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.permitted.iter().any(|name| {
+            host == *name
+                || (name.parse::<IpAddr>().is_err()
+                    && host
+                        .strip_suffix(name.as_str())
+                        .is_some_and(|prefix| prefix.ends_with('.')))
+        })
+    }
+
+    /// 根证书 PEM
+    pub fn cert_pem(&self) -> Result<Vec<u8>, ErrorStack> {
+        self.cert.to_pem()
+    }
+
+    /// 为主机签发叶子证书
+    pub fn issue(&self, host: &str) -> Result<X509, ErrorStack> {
+        let serial = random_serial()?.to_asn1_integer()?;
+        let is_ip = host.parse::<IpAddr>().is_ok();
+        // 没有 DNS 类型的 SAN 时 OpenSSL 会按 DNS 名称约束校验 CN，IP 证书的 CN 不能形如主机名
+        let common_name = if is_ip {
+            "ProxyCast IP Certificate"
+        } else {
+            host
+        };
+        let subject = name(common_name)?;
+        let not_before = Asn1Time::from_unix(now() - CLOCK_SKEW_SECS)?;
+        let not_after = Asn1Time::days_from_now(LEAF_VALIDITY_DAYS as u32)?;
+
+        let mut builder = X509Builder::new()?;
+        builder.set_version(2)?;
+        builder.set_serial_number(&serial)?;
+        builder.set_subject_name(&subject)?;
+        builder.set_issuer_name(self.cert.subject_name())?;
+        builder.set_pubkey(&self.leaf_key)?;
+        builder.set_not_before(&not_before)?;
+        builder.set_not_after(&not_after)?;
+
+        builder.append_extension(BasicConstraints::new().build()?)?;
+        builder.append_extension(KeyUsage::new().critical().digital_signature().build()?)?;
+        builder.append_extension(ExtendedKeyUsage::new().server_auth().build()?)?;
+        let mut san = SubjectAlternativeName::new();
+        if is_ip {
+            san.ip(host);
+        } else {
+            san.dns(host);
+        }
+        let san = san.build(&builder.x509v3_context(Some(&self.cert), None))?;
+        builder.append_extension(san)?;
+        let authority_key = AuthorityKeyIdentifier::new()
+            .keyid(false)
+            .build(&builder.x509v3_context(Some(&self.cert), None))?;
+        builder.append_extension(authority_key)?;
+
+        builder.sign(&self.key, MessageDigest::sha256())?;
+        Ok(builder.build())
+    }
+
+    /// 获取主机对应的 TLS 接收器（按主机缓存）
+    pub fn acceptor(&self, host: &str) -> Result<Arc<SslAcceptor>, String> {
+        if let Some(acceptor) = self.acceptors.lock().get(host) {
+            return Ok(acceptor.clone());
+        }
+        let acceptor = self
+            .build_acceptor(host)
+            .map_err(|e| format!("为 {} 签发证书失败: {}", host, e))?;
+        let acceptor = Arc::new(acceptor);
+        self.acceptors
+            .lock()
+            .insert(host.to_string(), acceptor.clone());
+        Ok(acceptor)
+    }
+
+    fn build_acceptor(&self, host: &str) -> Result<SslAcceptor, ErrorStack> {
+        let leaf = self.issue(host)?;
+        let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server())?;
+        builder.set_private_key(&self.leaf_key)?;
+        builder.set_certificate(&leaf)?;
+        builder.add_extra_chain_cert(self.cert.clone())?;
+        builder.check_private_key()?;
+        builder.set_alpn_select_callback(|_, client| {
+            ssl::select_next_proto(ALPN_PROTOCOLS, client).ok_or(AlpnError::NOACK)
+        });
+        Ok(builder.build())
+    }
+}
+
+/// 生成自签名根证书，只允许为 `permitted` 中的主机签发证书
+fn generate_ca(permitted: &[String]) -> Result<(X509, PKey<Private>), ErrorStack> {
+    let key = generate_key()?;
+    let name = name("ProxyCast Local CA")?;
+    let serial = random_serial()?.to_asn1_integer()?;
+    let not_before = Asn1Time::from_unix(now() - CLOCK_SKEW_SECS)?;
+    let not_after = Asn1Time::days_from_now(CA_VALIDITY_DAYS as u32)?;
+
+    let mut builder = X509Builder::new()?;
+    builder.set_version(2)?;
+    builder.set_serial_number(&serial)?;
+    builder.set_subject_name(&name)?;
+    builder.set_issuer_name(&name)?;
+    builder.set_pubkey(&key)?;
+    builder.set_not_before(&not_before)?;
+    builder.set_not_after(&not_after)?;
+
+    builder.append_extension(BasicConstraints::new().critical().ca().pathlen(0).build()?)?;
+    builder.append_extension(
+        KeyUsage::new()
+            .critical()
+            .key_cert_sign()
+            .crl_sign()
+            .build()?,
+    )?;
+    builder.append_extension(name_constraints(permitted)?)?;
+    let subject_key = SubjectKeyIdentifier::new().build(&builder.x509v3_context(None, None))?;
+    builder.append_extension(subject_key)?;
+
+    builder.sign(&key, MessageDigest::sha256())?;
+    Ok((builder.build(), key))
+}
+
+/// 由拦截主机推导名称约束允许的名称
+///
+/// DNS 约束同时覆盖所有子域名，`*.example.com` 对应 `example.com`；
+/// 通配符不在开头的模式无法表达为约束，对应主机不解密，只做隧道转发。
+fn permitted_names(hosts: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = hosts
+        .iter()
+        .filter_map(|host| {
+            let host = host.trim().to_ascii_lowercase();
+            let name = host.strip_prefix("*.").unwrap_or(&host);
+            if name.is_empty() || name.contains('*') {
+                tracing::warn!(
+                    "[FORWARD_PROXY] 无法为 {} 设置根证书名称约束，该主机不会被解密",
+                    host
+                );
+                return None;
+            }
+            Some(name.to_string())
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// 构造关键的 NameConstraints 扩展
+///
+/// 未允许任何 IP 时排除全部 IPv4/IPv6 地址，避免签发 IP 证书绕过 DNS 约束；
+/// 没有可允许的名称时同时排除全部 DNS 名称。
+fn name_constraints(permitted: &[String]) -> Result<X509Extension, ErrorStack> {
+    const DNS_NAME: u8 = 0x82;
+    const IP_ADDRESS: u8 = 0x87;
+
+    let subtree = |tag: u8, base: &[u8]| der(0x30, &der(tag, base));
+    let mut allowed = Vec::new();
+    let mut has_ip = false;
+    for name in permitted {
+        match name.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                has_ip = true;
+                allowed.extend(subtree(
+                    IP_ADDRESS,
+                    &[&ip.octets()[..], &[0xff; 4]].concat(),
+                ));
+            }
+            Ok(IpAddr::V6(ip)) => {
+                has_ip = true;
+                allowed.extend(subtree(
+                    IP_ADDRESS,
+                    &[&ip.octets()[..], &[0xff; 16]].concat(),
+                ));
+            }
+            Err(_) => allowed.extend(subtree(DNS_NAME, name.as_bytes())),
+        }
+    }
+
+    let mut excluded = Vec::new();
+    if !has_ip {
+        excluded.extend(subtree(IP_ADDRESS, &[0; 8]));
+        excluded.extend(subtree(IP_ADDRESS, &[0; 32]));
+    }
+    if allowed.is_empty() {
+        excluded.extend(subtree(DNS_NAME, b""));
+    }
+
+    let mut value = Vec::new();
+    if !allowed.is_empty() {
+        value.extend(der(0xa0, &allowed));
+    }
+    if !excluded.is_empty() {
+        value.extend(der(0xa1, &excluded));
+    }
+    let oid = Asn1Object::from_str(NAME_CONSTRAINTS_OID)?;
+    let contents = Asn1OctetString::new_from_bytes(&der(0x30, &value))?;
+    X509Extension::new_from_der(&oid, true, &contents)
+}
+
+/// DER 编码（标签 + 长度 + 内容）
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn generate_key() -> Result<PKey<Private>, ErrorStack> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    PKey::from_ec_key(EcKey::generate(&group)?)
+}
+
+fn name(common_name: &str) -> Result<X509Name, ErrorStack> {
+    let mut builder = X509NameBuilder::new()?;
+    builder.append_entry_by_nid(Nid::ORGANIZATIONNAME, "ProxyCast")?;
+    builder.append_entry_by_nid(Nid::COMMONNAME, common_name)?;
+    Ok(builder.build())
+}
+
+fn random_serial() -> Result<BigNum, ErrorStack> {
+    let mut serial = BigNum::new()?;
+    serial.rand(127, MsbOption::MAYBE_ZERO, false)?;
+    Ok(serial)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// 写入私钥（Unix 下仅所有者可读写）
+fn write_private(path: &Path, content: &[u8]) -> Result<(), String> {
+    std::fs::write(path, content).map_err(|e| format!("写入私钥失败 {:?}: {}", path, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("设置私钥权限失败 {:?}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::stack::Stack;
+    use openssl::x509::store::X509StoreBuilder;
+    use openssl::x509::X509StoreContext;
+
+    fn hosts(hosts: &[&str]) -> Vec<String> {
+        hosts.iter().map(|h| h.to_string()).collect()
+    }
+
+    /// 以根证书为信任锚校验叶子证书链
+    fn verify_chain(ca: &CertificateAuthority, leaf: &X509) -> bool {
+        let mut store = X509StoreBuilder::new().unwrap();
+        store.add_cert(ca.cert.clone()).unwrap();
+        let store = store.build();
+        let mut context = X509StoreContext::new().unwrap();
+        context
+            .init(&store, leaf, &Stack::new().unwrap(), |c| c.verify_cert())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_issue_leaf_signed_by_ca() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = CertificateAuthority::load_or_create(dir.path(), &hosts(&["api.anthropic.com"]))
+            .unwrap();
+        let leaf = ca.issue("api.anthropic.com").unwrap();
+
+        let ca_key = ca.cert.public_key().unwrap();
+        assert!(leaf.verify(&ca_key).unwrap());
+        assert!(verify_chain(&ca, &leaf));
+        let names: Vec<_> = leaf
+            .subject_alt_names()
+            .unwrap()
+            .iter()
+            .filter_map(|n| n.dnsname().map(str::to_string))
+            .collect();
+        assert_eq!(names, vec!["api.anthropic.com"]);
+        assert!(ca.acceptor("api.anthropic.com").is_ok());
+    }
+
+    #[test]
+    fn test_name_constraints_limit_issued_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = CertificateAuthority::load_or_create(
+            dir.path(),
+            &hosts(&["API.openai.com", "*.googleapis.com", "api.*"]),
+        )
+        .unwrap();
+
+        assert!(ca.permits("api.openai.com"));
+        assert!(ca.permits("generativelanguage.googleapis.com"));
+        assert!(!ca.permits("evilapi.openai.com"));
+        assert!(!ca.permits("api.example.com"));
+
+        // 私钥泄露时为其他域名或 IP 签发的证书也无法通过校验
+        assert!(verify_chain(&ca, &ca.issue("api.openai.com").unwrap()));
+        assert!(verify_chain(
+            &ca,
+            &ca.issue("generativelanguage.googleapis.com").unwrap()
+        ));
+        assert!(!verify_chain(&ca, &ca.issue("github.com").unwrap()));
+        assert!(!verify_chain(&ca, &ca.issue("api.example.com").unwrap()));
+        assert!(!verify_chain(&ca, &ca.issue("127.0.0.1").unwrap()));
+    }
+
+    #[test]
+    fn test_load_existing_ca() {
+        let dir = tempfile::tempdir().unwrap();
+        let intercept = hosts(&["api.anthropic.com", "127.0.0.1"]);
+        let first = CertificateAuthority::load_or_create(dir.path(), &intercept).unwrap();
+        let second = CertificateAuthority::load_or_create(dir.path(), &intercept).unwrap();
+        assert_eq!(first.cert_pem().unwrap(), second.cert_pem().unwrap());
+        assert!(second.cert_path().ends_with(CA_CERT_FILE));
+
+        // 重新加载后签发的证书仍由同一根证书签名
+        let leaf = second.issue("127.0.0.1").unwrap();
+        assert!(leaf.verify(&first.cert.public_key().unwrap()).unwrap());
+        assert!(verify_chain(&first, &leaf));
+
+        // 拦截主机变化时重新生成
+        let third =
+            CertificateAuthority::load_or_create(dir.path(), &hosts(&["api.openai.com"])).unwrap();
+        assert_ne!(first.cert_pem().unwrap(), third.cert_pem().unwrap());
+        assert!(!third.permits("api.anthropic.com"));
+    }
+
+    #[test]
+    fn test_der_long_length() {
+        let encoded = der(0x04, &[0u8; 300]);
+        assert_eq!(&encoded[..4], &[0x04, 0x82, 0x01, 0x2c]);
+        assert_eq!(encoded.len(), 304);
+    }
+}
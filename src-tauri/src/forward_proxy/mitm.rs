@@ -0,0 +1,213 @@
+//! 解密后的请求处理
+//!
+//! LLM 端点改写为本地路径并替换认证信息后交给 API 服务器的 Router，
+//! 与直接访问 ProxyCast 的请求走同一套处理流程；其余路径原样转发到原主机。
+
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{header, HeaderMap, HeaderValue, Request, Response, Uri};
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tower::ServiceExt;
+
+use super::{Authorized, ForwardProxy};
+
+/// 交给本地处理流程的路径
+const PIPELINE_PATHS: &[&str] = &[
+    "/v1/messages",
+    "/v1/messages/count_tokens",
+    "/v1/chat/completions",
+    "/v1/models",
+];
+
+/// 不转发的逐跳请求头
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "host",
+    "content-length",
+];
+
+/// 在解密后的连接上提供 HTTP/1.1 与 HTTP/2 服务
+///
+/// 连接在 `CONNECT` 阶段已通过代理认证，`authorized` 随每个请求传递给 [`rewrite_auth`]。
+pub(super) async fn serve<S>(
+    stream: S,
+    host: String,
+    proxy: Arc<ForwardProxy>,
+    authorized: Authorized,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = service_fn(move |request: Request<Incoming>| {
+        let proxy = proxy.clone();
+        let host = host.clone();
+        async move { Ok::<_, Infallible>(proxy.handle_intercepted(&host, request, authorized).await) }
+    });
+    if let Err(e) = auto::Builder::new(TokioExecutor::new())
+        .serve_connection(TokioIo::new(stream), service)
+        .await
+    {
+        tracing::debug!("[FORWARD_PROXY] 解密连接结束: {}", e);
+    }
+}
+
+/// 映射到本地处理流程的路径
+///
+/// Gemini 的 OpenAI 兼容端点（`/v1beta/openai/...`）映射到对应的 `/v1/...`。
+fn pipeline_path(path: &str) -> Option<String> {
+    let path = match path.strip_prefix("/v1beta/openai/") {
+        Some(rest) => format!("/v1/{}", rest),
+        None => path.to_string(),
+    };
+    PIPELINE_PATHS.contains(&path.as_str()).then_some(path)
+}
+
+/// 以本地 API Key 替换客户端携带的官方凭证
+///
+/// 只对已通过代理认证的连接替换（`Authorized` 只能由 [`ForwardProxy::authorize`] 创建），
+/// 避免未认证的来源借用本地 API Key。
+fn rewrite_auth(headers: &mut HeaderMap, api_key: &str, _authorized: Authorized) {
+    headers.remove(header::AUTHORIZATION);
+    headers.remove("x-goog-api-key");
+    headers.remove(header::PROXY_AUTHORIZATION);
+    if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", api_key)) {
+        headers.insert(header::AUTHORIZATION, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(api_key) {
+        headers.insert("x-api-key", value);
+    }
+}
+
+impl ForwardProxy {
+    async fn handle_intercepted(
+        &self,
+        host: &str,
+        request: Request<Incoming>,
+        authorized: Authorized,
+    ) -> Response<Body> {
+        let Some(local_path) = pipeline_path(request.uri().path()) else {
+            return self.passthrough(host, request).await;
+        };
+        tracing::info!(
+            "[FORWARD_PROXY] {} https://{}{} -> {}",
+            request.method(),
+            host,
+            request.uri().path(),
+            local_path
+        );
+
+        let (mut parts, body) = request.into_parts();
+        let path_and_query = match parts.uri.query() {
+            Some(query) => format!("{}?{}", local_path, query),
+            None => local_path,
+        };
+        parts.uri = path_and_query.parse::<Uri>().unwrap_or_default();
+        rewrite_auth(&mut parts.headers, &self.api_key, authorized);
+
+        let request = Request::from_parts(parts, Body::new(body));
+        match self.pipeline.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        }
+    }
+
+    /// 原样转发到原主机
+    async fn passthrough(&self, host: &str, request: Request<Incoming>) -> Response<Body> {
+        let (parts, body) = request.into_parts();
+        let path_and_query = parts
+            .uri
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+        let url = format!("https://{}{}", host, path_and_query);
+        tracing::debug!("[FORWARD_PROXY] 透传 {} {}", parts.method, url);
+
+        let body = match axum::body::to_bytes(Body::new(body), usize::MAX).await {
+            Ok(body) => body,
+            Err(e) => return error_response(400, &format!("读取请求体失败: {}", e)),
+        };
+        let mut upstream = self.http.request(parts.method, &url).body(body);
+        for (name, value) in &parts.headers {
+            if !HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+                upstream = upstream.header(name, value);
+            }
+        }
+
+        let response = match upstream.send().await {
+            Ok(response) => response,
+            Err(e) => return error_response(502, &format!("转发到 {} 失败: {}", host, e)),
+        };
+        let mut builder = Response::builder().status(response.status());
+        for (name, value) in response.headers() {
+            if !HOP_BY_HOP_HEADERS.contains(&name.as_str()) {
+                builder = builder.header(name, value);
+            }
+        }
+        builder
+            .body(Body::from_stream(response.bytes_stream()))
+            .unwrap_or_else(|e| error_response(502, &e.to_string()))
+    }
+}
+
+fn error_response(status: u16, message: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(
+        serde_json::json!({"error": {"message": message}}).to_string(),
+    ));
+    *response.status_mut() =
+        axum::http::StatusCode::from_u16(status).unwrap_or(axum::http::StatusCode::BAD_GATEWAY);
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_path() {
+        assert_eq!(
+            pipeline_path("/v1/messages").as_deref(),
+            Some("/v1/messages")
+        );
+        assert_eq!(
+            pipeline_path("/v1beta/openai/chat/completions").as_deref(),
+            Some("/v1/chat/completions")
+        );
+        assert_eq!(pipeline_path("/v1/embeddings"), None);
+        assert_eq!(
+            pipeline_path("/v1beta/models/gemini-2.5-pro:generateContent"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rewrite_auth() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer sk-real"),
+        );
+        headers.insert("x-api-key", HeaderValue::from_static("sk-ant-real"));
+        headers.insert("x-goog-api-key", HeaderValue::from_static("AIza-real"));
+        rewrite_auth(&mut headers, "local-key", Authorized(()));
+
+        assert_eq!(headers[header::AUTHORIZATION], "Bearer local-key");
+        assert_eq!(headers["x-api-key"], "local-key");
+        assert!(headers.get("x-goog-api-key").is_none());
+    }
+}
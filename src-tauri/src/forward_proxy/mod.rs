@@ -0,0 +1,585 @@
+//! 正向代理（MITM）
+//!
+//! 供无法修改 Base URL、直接访问官方 API 的客户端使用：
+//! - `CONNECT` 到 `intercept_hosts` 中的主机（443 端口）时，以本地 CA 签发的证书终止 TLS，
+//!   LLM 端点交给常规处理流程（Flow 监控、路由、参数注入），其余路径原样转发到原主机
+//! - 其他主机原样建立隧道，不解密
+//! - 普通 HTTP 代理请求（绝对路径形式）直接转发
+//! - 配置了 `allowed_hosts` 时只放行其中的主机和 `intercept_hosts`，其余返回 403
+//!
+//! 配置了 `auth_token` 时所有请求都要携带匹配的 `Proxy-Authorization`，否则返回 407；
+//! 未配置时只允许监听回环地址，避免成为开放代理并把本地 API Key 借给任意来源。
+
+pub mod ca;
+mod mitm;
+
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::Router;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use openssl::ssl::Ssl;
+use subtle::ConstantTimeEq;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_openssl::SslStream;
+
+use crate::config::ForwardProxyConfig;
+use crate::models::provider_pool_model::pattern_matches;
+
+pub use ca::CertificateAuthority;
+
+/// 请求头最大长度
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// 对目标主机的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostAction {
+    /// 解密并交给常规处理流程
+    Intercept,
+    /// 原样隧道转发
+    Tunnel,
+    /// 拒绝
+    Deny,
+}
+
+/// 已通过代理认证的连接
+///
+/// 只能由 [`ForwardProxy::authorize`] 创建，解密后替换为本地 API Key 前必须持有。
+#[derive(Debug, Clone, Copy)]
+struct Authorized(());
+
+/// 代理请求头
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RequestHead {
+    method: String,
+    target: String,
+    version: String,
+    headers: Vec<(String, String)>,
+}
+
+/// 正向代理
+pub struct ForwardProxy {
+    config: ForwardProxyConfig,
+    ca: Option<CertificateAuthority>,
+    /// 本地处理流程（与 API 服务器共用同一个 Router）
+    pipeline: Router,
+    api_key: String,
+    http: reqwest::Client,
+}
+
+impl ForwardProxy {
+    pub fn new(
+        config: ForwardProxyConfig,
+        pipeline: Router,
+        api_key: String,
+    ) -> Result<Self, String> {
+        let ca = if config.intercept_hosts.is_empty() {
+            None
+        } else {
+            let dir = CertificateAuthority::default_dir()?;
+            Some(CertificateAuthority::load_or_create(
+                &dir,
+                &config.intercept_hosts,
+            )?)
+        };
+        let http = reqwest::Client::builder()
+            .no_proxy()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+        Ok(Self {
+            config,
+            ca,
+            pipeline,
+            api_key,
+            http,
+        })
+    }
+
+    /// 监听并在后台处理连接
+    ///
+    /// 未配置 `auth_token` 时拒绝监听非回环地址。
+    pub async fn start(self: Arc<Self>) -> Result<JoinHandle<()>, String> {
+        if self.auth_token().is_none() && !is_loopback_host(&self.config.host) {
+            return Err(format!(
+                "正向代理监听非回环地址 {} 时必须配置 auth_token",
+                self.config.host
+            ));
+        }
+        let addr = format!("{}:{}", self.config.host, self.config.port);
+        let listener = TcpListener::bind(&addr)
+            .await
+            .map_err(|e| format!("正向代理无法绑定到 {}: {}", addr, e))?;
+        match &self.ca {
+            Some(ca) => tracing::info!(
+                "[FORWARD_PROXY] 监听 {}，根证书: {:?}",
+                addr,
+                ca.cert_path()
+            ),
+            None => tracing::info!("[FORWARD_PROXY] 监听 {}（仅隧道转发）", addr),
+        }
+
+        Ok(tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        tracing::warn!("[FORWARD_PROXY] 接受连接失败: {}", e);
+                        continue;
+                    }
+                };
+                let proxy = self.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = proxy.handle(stream).await {
+                        tracing::debug!("[FORWARD_PROXY] {} 连接结束: {}", peer, e);
+                    }
+                });
+            }
+        }))
+    }
+
+    /// 判断目标主机的处理方式
+    pub fn host_action(&self, host: &str, port: u16) -> HostAction {
+        let host = host.to_ascii_lowercase();
+        let matches = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|p| pattern_matches(&p.to_ascii_lowercase(), &host))
+        };
+        let permitted = self.ca.as_ref().is_some_and(|ca| ca.permits(&host));
+        if port == 443 && permitted && matches(&self.config.intercept_hosts) {
+            HostAction::Intercept
+        } else if self.config.allowed_hosts.is_empty()
+            || matches(&self.config.allowed_hosts)
+            || matches(&self.config.intercept_hosts)
+        {
+            HostAction::Tunnel
+        } else {
+            HostAction::Deny
+        }
+    }
+
+    fn auth_token(&self) -> Option<&str> {
+        self.config
+            .auth_token
+            .as_deref()
+            .filter(|token| !token.is_empty())
+    }
+
+    /// 校验 `Proxy-Authorization`
+    ///
+    /// 接受 `Bearer <auth_token>` 或以 `auth_token` 为密码的 `Basic`（用户名任意）；
+    /// 未配置 `auth_token` 时（只能监听回环地址）直接放行。
+    fn authorize(&self, proxy_authorization: Option<&str>) -> Option<Authorized> {
+        let Some(expected) = self.auth_token() else {
+            return Some(Authorized(()));
+        };
+        let (scheme, credentials) = proxy_authorization?.trim().split_once(' ')?;
+        let credentials = credentials.trim();
+        let provided = if scheme.eq_ignore_ascii_case("bearer") {
+            credentials.to_string()
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = String::from_utf8(BASE64.decode(credentials).ok()?).ok()?;
+            decoded.split_once(':')?.1.to_string()
+        } else {
+            return None;
+        };
+        bool::from(provided.as_bytes().ct_eq(expected.as_bytes())).then_some(Authorized(()))
+    }
+
+    async fn handle(self: Arc<Self>, stream: TcpStream) -> Result<(), String> {
+        let mut reader = BufReader::new(stream);
+        let head = parse_head(&read_head(&mut reader).await?)?;
+        // 请求头之后已读入缓冲区的数据（普通 HTTP 请求的请求体）
+        let leftover = reader.buffer().to_vec();
+        let mut stream = reader.into_inner();
+
+        let Some(authorized) = self.authorize(head.header("proxy-authorization")) else {
+            tracing::info!(
+                "[FORWARD_PROXY] 代理认证失败: {} {}",
+                head.method,
+                head.target
+            );
+            return respond_auth_required(&mut stream).await;
+        };
+
+        if head.method.eq_ignore_ascii_case("CONNECT") {
+            let Some((host, port)) = split_authority(&head.target, 443) else {
+                return respond(&mut stream, "400 Bad Request").await;
+            };
+            match self.host_action(&host, port) {
+                HostAction::Deny => {
+                    tracing::info!("[FORWARD_PROXY] 拒绝 CONNECT {}:{}", host, port);
+                    respond(&mut stream, "403 Forbidden").await
+                }
+                HostAction::Tunnel => {
+                    let Ok(mut upstream) = TcpStream::connect((host.as_str(), port)).await else {
+                        return respond(&mut stream, "502 Bad Gateway").await;
+                    };
+                    write_established(&mut stream).await?;
+                    upstream
+                        .write_all(&leftover)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    tokio::io::copy_bidirectional(&mut stream, &mut upstream)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    Ok(())
+                }
+                HostAction::Intercept => {
+                    write_established(&mut stream).await?;
+                    self.intercept(stream, host, authorized).await
+                }
+            }
+        } else {
+            self.forward_plain(stream, head, leftover).await
+        }
+    }
+
+    /// 终止 TLS 并处理解密后的请求
+    async fn intercept(
+        self: Arc<Self>,
+        stream: TcpStream,
+        host: String,
+        authorized: Authorized,
+    ) -> Result<(), String> {
+        let Some(ca) = &self.ca else {
+            return Err("未加载根证书".to_string());
+        };
+        let acceptor = ca.acceptor(&host)?;
+        let ssl = Ssl::new(acceptor.context()).map_err(|e| e.to_string())?;
+        let mut tls = SslStream::new(ssl, stream).map_err(|e| e.to_string())?;
+        Pin::new(&mut tls)
+            .accept()
+            .await
+            .map_err(|e| format!("与客户端 TLS 握手失败（客户端是否信任根证书？）: {}", e))?;
+        tracing::debug!("[FORWARD_PROXY] 解密 {}", host);
+        mitm::serve(tls, host, self.clone(), authorized).await;
+        Ok(())
+    }
+
+    /// 转发普通 HTTP 代理请求（`GET http://host/path`）
+    async fn forward_plain(
+        &self,
+        mut stream: TcpStream,
+        head: RequestHead,
+        leftover: Vec<u8>,
+    ) -> Result<(), String> {
+        let Ok(url) = url::Url::parse(&head.target) else {
+            return respond(&mut stream, "400 Bad Request").await;
+        };
+        let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+            return respond(&mut stream, "400 Bad Request").await;
+        };
+        if url.scheme() != "http" {
+            return respond(&mut stream, "400 Bad Request").await;
+        }
+        if self.host_action(host, port) == HostAction::Deny {
+            tracing::info!("[FORWARD_PROXY] 拒绝 {} {}", head.method, head.target);
+            return respond(&mut stream, "403 Forbidden").await;
+        }
+        let Ok(mut upstream) = TcpStream::connect((host, port)).await else {
+            return respond(&mut stream, "502 Bad Gateway").await;
+        };
+
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        upstream
+            .write_all(origin_form_head(&head, &path).as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+        upstream
+            .write_all(&leftover)
+            .await
+            .map_err(|e| e.to_string())?;
+        tokio::io::copy_bidirectional(&mut stream, &mut upstream)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+impl RequestHead {
+    /// 按名称（不区分大小写）查找请求头
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// 是否为回环地址（`localhost`、`127.0.0.0/8`、`::1`）
+fn is_loopback_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// 读取请求头（到空行为止）
+async fn read_head(reader: &mut BufReader<TcpStream>) -> Result<String, String> {
+    let mut head = String::new();
+    loop {
+        let read = reader
+            .read_line(&mut head)
+            .await
+            .map_err(|e| e.to_string())?;
+        if read == 0 {
+            return Err("连接在请求头结束前关闭".to_string());
+        }
+        if head.len() > MAX_HEAD_BYTES {
+            return Err("请求头过长".to_string());
+        }
+        if head.ends_with("\r\n\r\n") || head.ends_with("\n\n") {
+            return Ok(head);
+        }
+    }
+}
+
+fn parse_head(raw: &str) -> Result<RequestHead, String> {
+    let mut lines = raw.lines();
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(format!("无效的请求行: {}", request_line));
+    };
+    let headers = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect();
+    Ok(RequestHead {
+        method: method.to_string(),
+        target: target.to_string(),
+        version: version.to_string(),
+        headers,
+    })
+}
+
+/// 拆分 `host:port`（支持 `[IPv6]:port`）
+fn split_authority(authority: &str, default_port: u16) -> Option<(String, u16)> {
+    let (host, port) = if let Some(rest) = authority.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']')?;
+        let port = match rest.strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None => default_port,
+        };
+        (host, port)
+    } else {
+        match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (authority, default_port),
+        }
+    };
+    (!host.is_empty()).then(|| (host.to_string(), port))
+}
+
+/// 将绝对路径形式的代理请求头改写为发往源站的形式
+fn origin_form_head(head: &RequestHead, path: &str) -> String {
+    let mut out = format!("{} {} {}\r\n", head.method, path, head.version);
+    for (name, value) in &head.headers {
+        let lower = name.to_ascii_lowercase();
+        if lower.starts_with("proxy-") || lower == "connection" || lower == "keep-alive" {
+            continue;
+        }
+        out.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    // 每个连接只转发一个请求，避免后续请求被发往错误的主机
+    out.push_str("Connection: close\r\n\r\n");
+    out
+}
+
+async fn write_established(stream: &mut TcpStream) -> Result<(), String> {
+    stream
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn respond(stream: &mut TcpStream, status: &str) -> Result<(), String> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+    stream
+        .write_all(response.as_bytes())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn respond_auth_required(stream: &mut TcpStream) -> Result<(), String> {
+    stream
+        .write_all(
+            b"HTTP/1.1 407 Proxy Authentication Required\r\nProxy-Authenticate: Basic realm=\"ProxyCast\"\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy(config: ForwardProxyConfig, with_ca: bool) -> ForwardProxy {
+        let dir = tempfile::tempdir().unwrap();
+        let ca = with_ca.then(|| {
+            CertificateAuthority::load_or_create(dir.path(), &config.intercept_hosts).unwrap()
+        });
+        ForwardProxy {
+            config,
+            ca,
+            pipeline: Router::new(),
+            api_key: "local-key".to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    #[test]
+    fn test_host_action() {
+        let config = ForwardProxyConfig {
+            allowed_hosts: vec!["*.github.com".to_string()],
+            ..ForwardProxyConfig::default()
+        };
+        let proxy = proxy(config, true);
+        assert_eq!(
+            proxy.host_action("API.Anthropic.com", 443),
+            HostAction::Intercept
+        );
+        // 非 443 端口不解密
+        assert_eq!(
+            proxy.host_action("api.openai.com", 8443),
+            HostAction::Tunnel
+        );
+        assert_eq!(proxy.host_action("api.github.com", 443), HostAction::Tunnel);
+        assert_eq!(proxy.host_action("example.com", 443), HostAction::Deny);
+    }
+
+    #[test]
+    fn test_host_action_without_ca_only_tunnels() {
+        let proxy = proxy(ForwardProxyConfig::default(), false);
+        assert_eq!(
+            proxy.host_action("api.anthropic.com", 443),
+            HostAction::Tunnel
+        );
+        // 允许列表为空时其他主机原样隧道转发
+        assert_eq!(proxy.host_action("example.com", 443), HostAction::Tunnel);
+    }
+
+    #[test]
+    fn test_authorize() {
+        let open = proxy(ForwardProxyConfig::default(), false);
+        assert!(open.authorize(None).is_some());
+
+        let config = ForwardProxyConfig {
+            auth_token: Some("secret".to_string()),
+            ..ForwardProxyConfig::default()
+        };
+        let proxy = proxy(config, false);
+        assert!(proxy.authorize(None).is_none());
+        assert!(proxy.authorize(Some("Bearer secret")).is_some());
+        assert!(proxy.authorize(Some("Bearer wrong")).is_none());
+        let basic = format!("Basic {}", BASE64.encode("user:secret"));
+        assert!(proxy.authorize(Some(&basic)).is_some());
+        let basic = format!("Basic {}", BASE64.encode("secret:wrong"));
+        assert!(proxy.authorize(Some(&basic)).is_none());
+        assert!(proxy.authorize(Some("Digest secret")).is_none());
+    }
+
+    #[test]
+    fn test_is_loopback_host() {
+        assert!(is_loopback_host("127.0.0.1"));
+        assert!(is_loopback_host("localhost"));
+        assert!(is_loopback_host("::1"));
+        assert!(is_loopback_host("[::1]"));
+        assert!(!is_loopback_host("0.0.0.0"));
+        assert!(!is_loopback_host("192.168.1.10"));
+    }
+
+    #[tokio::test]
+    async fn test_start_refuses_open_non_loopback_bind() {
+        let config = ForwardProxyConfig {
+            host: "0.0.0.0".to_string(),
+            port: 0,
+            ..ForwardProxyConfig::default()
+        };
+        let err = Arc::new(proxy(config, false)).start().await.unwrap_err();
+        assert!(err.contains("auth_token"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_requires_proxy_authorization() {
+        let config = ForwardProxyConfig {
+            auth_token: Some("secret".to_string()),
+            ..ForwardProxyConfig::default()
+        };
+        let proxy = Arc::new(proxy(config, false));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = proxy.handle(stream).await;
+        });
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"CONNECT api.openai.com:443 HTTP/1.1\r\nHost: api.openai.com:443\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut client, &mut response)
+            .await
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 407"));
+        assert!(response.contains("Proxy-Authenticate: Basic"));
+    }
+
+    #[test]
+    fn test_parse_head() {
+        let head = parse_head(
+            "CONNECT api.openai.com:443 HTTP/1.1\r\nHost: api.openai.com:443\r\nProxy-Connection: keep-alive\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(head.method, "CONNECT");
+        assert_eq!(head.target, "api.openai.com:443");
+        assert_eq!(head.headers.len(), 2);
+        assert!(parse_head("garbage\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_split_authority() {
+        assert_eq!(
+            split_authority("api.openai.com:443", 443),
+            Some(("api.openai.com".to_string(), 443))
+        );
+        assert_eq!(
+            split_authority("[::1]:8080", 443),
+            Some(("::1".to_string(), 8080))
+        );
+        assert_eq!(
+            split_authority("example.com", 80),
+            Some(("example.com".to_string(), 80))
+        );
+        assert_eq!(split_authority(":443", 443), None);
+        assert_eq!(split_authority("host:abc", 443), None);
+    }
+
+    #[test]
+    fn test_origin_form_head() {
+        let head = parse_head(
+            "GET http://example.com/a?b=1 HTTP/1.1\r\nHost: example.com\r\nProxy-Authorization: Basic x\r\nConnection: keep-alive\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            origin_form_head(&head, "/a?b=1"),
+            "GET /a?b=1 HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n"
+        );
+    }
+}
@@ -0,0 +1,555 @@
+//! 占位符还原
+//!
+//! `tokenize` 动作发出的 `[[EMAIL_1]]` 等占位符会被模型原样引用，响应返回客户端前
+//! 还原为原值。工具调用参数是 JSON 文本，原值需要先做 JSON 字符串转义。
+//!
+//! 流式响应中占位符可能被拆到多个增量里，`StreamRestorer` 会暂存末尾不完整的
+//! `[[...` 片段，直到占位符闭合或内容块结束。
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde_json::{json, Value};
+
+use super::TokenVault;
+
+/// 单个占位符的最大长度，超过后不再暂存
+const MAX_TOKEN_LEN: usize = 64;
+
+fn token_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\[\[[A-Z0-9_]+_\d+\]\]").expect("valid token regex"))
+}
+
+/// 还原文本中的占位符，返回还原数量
+fn restore_text(text: &str, vault: &TokenVault, json_escape: bool) -> (String, u32) {
+    let mut count = 0;
+    let restored = token_regex().replace_all(text, |caps: &regex::Captures| {
+        let token = &caps[0];
+        match vault.get(token) {
+            Some(original) => {
+                count += 1;
+                if json_escape {
+                    let quoted = serde_json::to_string(original).unwrap_or_default();
+                    quoted[1..quoted.len() - 1].to_string()
+                } else {
+                    original.to_string()
+                }
+            }
+            None => token.to_string(),
+        }
+    });
+    (restored.into_owned(), count)
+}
+
+fn restore_in_place(value: &mut Value, vault: &TokenVault, json_escape: bool) -> u32 {
+    match value {
+        Value::String(text) => {
+            let (restored, count) = restore_text(text, vault, json_escape);
+            if count > 0 {
+                *text = restored;
+            }
+            count
+        }
+        _ => 0,
+    }
+}
+
+/// 递归还原 JSON 值中所有字符串
+fn restore_value(value: &mut Value, vault: &TokenVault) -> u32 {
+    match value {
+        Value::String(_) => restore_in_place(value, vault, false),
+        Value::Array(items) => items.iter_mut().map(|v| restore_value(v, vault)).sum(),
+        Value::Object(map) => map.values_mut().map(|v| restore_value(v, vault)).sum(),
+        _ => 0,
+    }
+}
+
+/// 还原 OpenAI 非流式响应，返回还原数量
+pub fn restore_openai_response(json: &mut Value, vault: &TokenVault) -> u32 {
+    if vault.is_empty() {
+        return 0;
+    }
+    let mut count = 0;
+    if let Some(choices) = json["choices"].as_array_mut() {
+        for choice in choices {
+            let message = &mut choice["message"];
+            count += restore_in_place(&mut message["content"], vault, false);
+            if let Some(calls) = message["tool_calls"].as_array_mut() {
+                for call in calls {
+                    count += restore_in_place(&mut call["function"]["arguments"], vault, true);
+                }
+            }
+        }
+    }
+    count
+}
+
+/// 还原 Anthropic 非流式响应，返回还原数量
+pub fn restore_anthropic_response(json: &mut Value, vault: &TokenVault) -> u32 {
+    if vault.is_empty() {
+        return 0;
+    }
+    let mut count = 0;
+    if let Some(blocks) = json["content"].as_array_mut() {
+        for block in blocks {
+            match block["type"].as_str() {
+                Some("text") => count += restore_in_place(&mut block["text"], vault, false),
+                Some("tool_use") => count += restore_value(&mut block["input"], vault),
+                _ => {}
+            }
+        }
+    }
+    count
+}
+
+/// 流式还原的响应格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamRestorerFormat {
+    OpenAi,
+    Anthropic,
+}
+
+/// 单个增量通道（文本或某个工具调用的参数）的暂存内容
+#[derive(Debug, Default)]
+struct Channel {
+    pending: String,
+    json_escape: bool,
+}
+
+impl Channel {
+    /// 追加增量，返回可以下发的已还原文本
+    fn push(&mut self, delta: &str, vault: &TokenVault, restored: &mut u32) -> String {
+        self.pending.push_str(delta);
+        let split = hold_back_start(&self.pending);
+        let ready: String = self.pending.drain(..split).collect();
+        let (text, count) = restore_text(&ready, vault, self.json_escape);
+        *restored += count;
+        text
+    }
+
+    /// 取出全部暂存内容
+    fn flush(&mut self, vault: &TokenVault, restored: &mut u32) -> Option<String> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let (text, count) =
+            restore_text(&std::mem::take(&mut self.pending), vault, self.json_escape);
+        *restored += count;
+        Some(text)
+    }
+}
+
+/// 末尾可能是未闭合占位符的起始位置；没有时返回文本长度
+fn hold_back_start(text: &str) -> usize {
+    if text.ends_with('[') {
+        // 可能是 "[[" 的前半
+        return match text.rfind("[[") {
+            Some(pos) if pos + 2 == text.len() => pos,
+            _ => text.len() - 1,
+        };
+    }
+    let Some(pos) = text.rfind("[[") else {
+        return text.len();
+    };
+    let tail = &text[pos + 2..];
+    let partial = tail.len() <= MAX_TOKEN_LEN
+        && tail
+            .trim_end_matches(']')
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        && !tail.ends_with("]]");
+    if partial {
+        pos
+    } else {
+        text.len()
+    }
+}
+
+/// 流式响应占位符还原
+#[derive(Debug)]
+pub struct StreamRestorer {
+    format: StreamRestorerFormat,
+    vault: TokenVault,
+    buffer: Vec<u8>,
+    /// OpenAI: "<choice>:content" / "<choice>:tool:<index>"；Anthropic: 内容块 index
+    channels: HashMap<String, Channel>,
+    /// 当前 SSE 事件中位于 data 行之前的行（如 `event:`）
+    prefix: String,
+    /// 最近一个 chunk 的公共字段（OpenAI），用于构造补发的 chunk
+    envelope: Option<Value>,
+    restored: u32,
+}
+
+impl StreamRestorer {
+    pub fn new(format: StreamRestorerFormat, vault: TokenVault) -> Self {
+        Self {
+            format,
+            vault,
+            buffer: Vec::new(),
+            channels: HashMap::new(),
+            prefix: String::new(),
+            envelope: None,
+            restored: 0,
+        }
+    }
+
+    /// 已还原的占位符数量
+    pub fn restored_count(&self) -> u32 {
+        self.restored
+    }
+
+    /// 处理一段上游字节，返回改写后的字节
+    pub fn transform(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.buffer.extend_from_slice(chunk);
+        let mut out = String::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            self.process_line(line.trim_end_matches(['\r', '\n']), &mut out);
+        }
+        out.into_bytes()
+    }
+
+    /// 上游结束，输出剩余内容
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut out = String::new();
+        if !self.buffer.is_empty() {
+            let rest = String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).to_string();
+            self.process_line(rest.trim_end_matches(['\r', '\n']), &mut out);
+        }
+        let keys: Vec<String> = self.channels.keys().cloned().collect();
+        for key in keys {
+            self.flush_channel(&key, &mut out);
+        }
+        out.push_str(&std::mem::take(&mut self.prefix));
+        out.into_bytes()
+    }
+
+    fn process_line(&mut self, line: &str, out: &mut String) {
+        let parsed = line
+            .strip_prefix("data:")
+            .map(str::trim)
+            .and_then(|data| serde_json::from_str::<Value>(data).ok());
+        let Some(mut event) = parsed else {
+            if line.is_empty() || line.starts_with("data:") {
+                out.push_str(&std::mem::take(&mut self.prefix));
+                out.push_str(line);
+                out.push('\n');
+            } else {
+                self.prefix.push_str(line);
+                self.prefix.push('\n');
+            }
+            return;
+        };
+        // 补发的事件需要排在当前事件（含 event: 行）之前
+        match self.format {
+            StreamRestorerFormat::OpenAi => self.process_openai(&mut event),
+            StreamRestorerFormat::Anthropic => self.process_anthropic(&mut event, out),
+        }
+        out.push_str(&std::mem::take(&mut self.prefix));
+        out.push_str(&format!("data: {}\n", event));
+    }
+
+    fn push(&mut self, key: &str, json_escape: bool, delta: &str) -> String {
+        let channel = self
+            .channels
+            .entry(key.to_string())
+            .or_insert_with(|| Channel {
+                json_escape,
+                ..Channel::default()
+            });
+        channel.push(delta, &self.vault, &mut self.restored)
+    }
+
+    fn process_openai(&mut self, chunk: &mut Value) {
+        let mut envelope = chunk.clone();
+        envelope["choices"] = json!([]);
+        if let Some(obj) = envelope.as_object_mut() {
+            obj.remove("usage");
+        }
+        self.envelope = Some(envelope);
+
+        let Some(choices) = chunk["choices"].as_array_mut() else {
+            return;
+        };
+        // n > 1 时各 choice 的增量交错到达，按 choice index 分别暂存
+        for (position, choice) in choices.iter_mut().enumerate() {
+            let choice_index = choice["index"].as_u64().unwrap_or(position as u64);
+            let delta = &mut choice["delta"];
+            if let Some(content) = delta["content"].as_str().map(String::from) {
+                let key = format!("{}:content", choice_index);
+                delta["content"] = Value::String(self.push(&key, false, &content));
+            }
+            if let Some(calls) = delta["tool_calls"].as_array_mut() {
+                for call in calls {
+                    let index = call["index"].as_u64().unwrap_or(0);
+                    if let Some(args) = call["function"]["arguments"].as_str().map(String::from) {
+                        let key = format!("{}:tool:{}", choice_index, index);
+                        call["function"]["arguments"] = Value::String(self.push(&key, true, &args));
+                    }
+                }
+            }
+
+            // 结束前把暂存内容并入当前 chunk，排在本次已下发的文本之后
+            if choice["finish_reason"].is_string() {
+                self.flush_into_delta(choice_index, &mut choice["delta"]);
+            }
+        }
+    }
+
+    /// 把某个 choice 所有通道的暂存内容追加到增量中
+    fn flush_into_delta(&mut self, choice_index: u64, delta: &mut Value) {
+        let prefix = format!("{}:", choice_index);
+        let mut keys: Vec<String> = self
+            .channels
+            .keys()
+            .filter(|key| key.starts_with(&prefix))
+            .cloned()
+            .collect();
+        keys.sort();
+        for key in keys {
+            let Some(text) = self.take_pending(&key) else {
+                continue;
+            };
+            match key[prefix.len()..].strip_prefix("tool:") {
+                Some(index) => {
+                    let index = index.parse::<u64>().unwrap_or(0);
+                    if !delta["tool_calls"].is_array() {
+                        delta["tool_calls"] = json!([]);
+                    }
+                    let Some(calls) = delta["tool_calls"].as_array_mut() else {
+                        continue;
+                    };
+                    match calls
+                        .iter_mut()
+                        .find(|c| c["index"].as_u64() == Some(index))
+                    {
+                        Some(call) => {
+                            let arguments = &mut call["function"]["arguments"];
+                            *arguments = Value::String(format!(
+                                "{}{}",
+                                arguments.as_str().unwrap_or_default(),
+                                text
+                            ));
+                        }
+                        None => {
+                            calls.push(json!({"index": index, "function": {"arguments": text}}))
+                        }
+                    }
+                }
+                None => {
+                    let content = &mut delta["content"];
+                    *content =
+                        Value::String(format!("{}{}", content.as_str().unwrap_or_default(), text));
+                }
+            }
+        }
+    }
+
+    fn process_anthropic(&mut self, event: &mut Value, out: &mut String) {
+        let index = event["index"].as_u64().unwrap_or(0).to_string();
+        match event["type"].as_str() {
+            Some("content_block_delta") => {
+                let delta = &mut event["delta"];
+                let (field, json_escape) = match delta["type"].as_str() {
+                    Some("text_delta") => ("text", false),
+                    Some("input_json_delta") => ("partial_json", true),
+                    _ => return,
+                };
+                if let Some(text) = delta[field].as_str().map(String::from) {
+                    delta[field] = Value::String(self.push(&index, json_escape, &text));
+                }
+            }
+            Some("content_block_stop") => self.flush_channel(&index, out),
+            _ => {}
+        }
+    }
+
+    /// 取出通道中的暂存内容（已还原）
+    fn take_pending(&mut self, key: &str) -> Option<String> {
+        self.channels
+            .get_mut(key)?
+            .flush(&self.vault, &mut self.restored)
+    }
+
+    /// 以单独的增量事件补发通道中的暂存内容
+    fn flush_channel(&mut self, key: &str, out: &mut String) {
+        let json_escape = self.channels.get(key).is_some_and(|c| c.json_escape);
+        let Some(text) = self.take_pending(key) else {
+            return;
+        };
+
+        let event = match self.format {
+            StreamRestorerFormat::OpenAi => {
+                let (choice, channel) = key.split_once(':').unwrap_or(("0", key));
+                let delta = match channel.strip_prefix("tool:") {
+                    Some(index) => json!({"tool_calls": [{
+                        "index": index.parse::<u64>().unwrap_or(0),
+                        "function": {"arguments": text}
+                    }]}),
+                    None => json!({"content": text}),
+                };
+                let mut chunk = self.envelope.clone().unwrap_or_else(|| json!({}));
+                chunk["choices"] = json!([{
+                    "index": choice.parse::<u64>().unwrap_or(0),
+                    "delta": delta,
+                    "finish_reason": null
+                }]);
+                format!("data: {}\n\n", chunk)
+            }
+            StreamRestorerFormat::Anthropic => {
+                let delta = if json_escape {
+                    json!({"type": "input_json_delta", "partial_json": text})
+                } else {
+                    json!({"type": "text_delta", "text": text})
+                };
+                let event = json!({
+                    "type": "content_block_delta",
+                    "index": key.parse::<u64>().unwrap_or(0),
+                    "delta": delta
+                });
+                format!("event: content_block_delta\ndata: {}\n\n", event)
+            }
+        };
+        out.push_str(&event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault() -> TokenVault {
+        let mut vault = TokenVault::default();
+        vault.tokenize("email", "a\"b@c.com");
+        vault
+    }
+
+    #[test]
+    fn test_restore_non_stream_openai() {
+        let mut json = json!({"choices": [{"message": {
+            "content": "Mail [[EMAIL_1]] now, not [[EMAIL_9]]",
+            "tool_calls": [{"function": {"name": "send", "arguments": "{\"to\":\"[[EMAIL_1]]\"}"}}]
+        }}]});
+
+        assert_eq!(restore_openai_response(&mut json, &vault()), 2);
+        let message = &json["choices"][0]["message"];
+        assert_eq!(message["content"], "Mail a\"b@c.com now, not [[EMAIL_9]]");
+        let args: Value = serde_json::from_str(
+            message["tool_calls"][0]["function"]["arguments"]
+                .as_str()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(args["to"], "a\"b@c.com");
+    }
+
+    #[test]
+    fn test_stream_restores_split_token() {
+        let mut restorer = StreamRestorer::new(StreamRestorerFormat::Anthropic, vault());
+        let delta = |text: &str| {
+            format!(
+                "event: content_block_delta\ndata: {}\n\n",
+                json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": text}})
+            )
+        };
+
+        let mut output = String::new();
+        for text in ["Hi [", "[EMA", "IL_1]] bye [["] {
+            output
+                .push_str(&String::from_utf8(restorer.transform(delta(text).as_bytes())).unwrap());
+        }
+        output.push_str(
+            &String::from_utf8(restorer.transform(
+                b"event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+            ))
+            .unwrap(),
+        );
+
+        let text: String = output
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+            .filter_map(|event| event["delta"]["text"].as_str().map(String::from))
+            .collect();
+        assert_eq!(text, "Hi a\"b@c.com bye [[");
+        assert_eq!(restorer.restored_count(), 1);
+    }
+
+    /// 按 choice index 拼接 OpenAI 流中的文本增量
+    fn openai_contents(output: &str) -> Vec<String> {
+        let mut contents = vec![String::new(); 2];
+        for chunk in output
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<Value>(data).ok())
+        {
+            for choice in chunk["choices"].as_array().into_iter().flatten() {
+                let index = choice["index"].as_u64().unwrap() as usize;
+                if let Some(text) = choice["delta"]["content"].as_str() {
+                    contents[index].push_str(text);
+                }
+            }
+        }
+        contents
+    }
+
+    #[test]
+    fn test_stream_openai_held_tail_stays_in_order() {
+        let mut restorer = StreamRestorer::new(StreamRestorerFormat::OpenAi, vault());
+        let chunk = json!({"id": "c1", "choices": [{
+            "index": 0,
+            "delta": {"content": "x [[EM"},
+            "finish_reason": "stop"
+        }]});
+        let output =
+            String::from_utf8(restorer.transform(format!("data: {}\n\n", chunk).as_bytes()))
+                .unwrap();
+        assert_eq!(openai_contents(&output)[0], "x [[EM");
+        assert!(restorer.finish().is_empty());
+    }
+
+    #[test]
+    fn test_stream_openai_multiple_choices() {
+        let mut restorer = StreamRestorer::new(StreamRestorerFormat::OpenAi, vault());
+        let chunk = |index: u64, text: &str, finish: Option<&str>| {
+            format!(
+                "data: {}\n\n",
+                json!({"id": "c1", "choices": [{
+                    "index": index,
+                    "delta": {"content": text},
+                    "finish_reason": finish
+                }]})
+            )
+        };
+
+        let mut output = String::new();
+        for event in [
+            chunk(0, "A [[EMA", None),
+            chunk(1, "B [[EM", None),
+            chunk(0, "IL_1]] a", None),
+            chunk(1, "AIL_1]] b [[", Some("stop")),
+            chunk(0, "", Some("stop")),
+        ] {
+            output.push_str(&String::from_utf8(restorer.transform(event.as_bytes())).unwrap());
+        }
+
+        assert_eq!(
+            openai_contents(&output),
+            vec!["A a\"b@c.com a", "B a\"b@c.com b [["]
+        );
+        assert_eq!(restorer.restored_count(), 2);
+    }
+
+    #[test]
+    fn test_hold_back_start() {
+        assert_eq!(hold_back_start("abc"), 3);
+        assert_eq!(hold_back_start("abc ["), 4);
+        assert_eq!(hold_back_start("abc [[EM"), 4);
+        assert_eq!(hold_back_start("abc [[EMAIL_1]"), 4);
+        assert_eq!(hold_back_start("abc [[EMAIL_1]] x"), 17);
+        assert_eq!(hold_back_start("abc [[not a token"), 17);
+    }
+}
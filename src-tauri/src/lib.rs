@@ -47,13 +47,18 @@ pub mod translator;
 pub mod tray;
 
 // 内部模块
+mod batch;
 mod commands;
 mod config;
 mod converter;
 mod data;
 #[cfg(debug_assertions)]
 mod dev_bridge;
+mod forward_proxy;
+mod guardrails;
 mod logger;
+mod mcp_gateway;
+mod mcp_server;
 mod models;
 mod providers;
 mod server_utils;
@@ -64,6 +69,8 @@ mod processor;
 mod router;
 mod server;
 mod streaming;
+mod structured_output;
+mod tool_emulation;
 mod websocket;
 
 // 重新导出核心类型以保持向后兼容
@@ -72,3 +79,4 @@ pub use services::provider_pool_service::ProviderPoolService;
 
 // 重新导出 run 函数
 pub use app::run;
+pub use app::{headless_requested, run_headless};
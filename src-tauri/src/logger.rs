@@ -42,6 +42,8 @@ pub struct LogStore {
     max_logs: usize,
     config: LogStoreConfig,
     log_file_path: Option<PathBuf>,
+    /// 是否同步输出到 tracing（无界面模式下没有前端日志面板）
+    mirror_to_tracing: bool,
 }
 
 impl Default for LogStore {
@@ -64,6 +66,7 @@ impl Default for LogStore {
             max_logs: config.max_logs,
             config,
             log_file_path: Some(log_file),
+            mirror_to_tracing: false,
         }
     }
 }
@@ -81,8 +84,21 @@ impl LogStore {
         store
     }
 
+    /// 设置是否将日志同步输出到 tracing
+    pub fn set_mirror_to_tracing(&mut self, enabled: bool) {
+        self.mirror_to_tracing = enabled;
+    }
+
     pub fn add(&mut self, level: &str, message: &str) {
         let sanitized = sanitize_log_message(message);
+        if self.mirror_to_tracing {
+            match level {
+                "error" => tracing::error!("{}", sanitized),
+                "warn" => tracing::warn!("{}", sanitized),
+                "debug" => tracing::debug!("{}", sanitized),
+                _ => tracing::info!("{}", sanitized),
+            }
+        }
         let now = Utc::now();
         let entry = LogEntry {
             timestamp: now.to_rfc3339(),
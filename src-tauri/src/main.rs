@@ -1,5 +1,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-fn main() {
-    proxycast_lib::run()
+fn main() -> std::process::ExitCode {
+    // `proxycast --headless` 与 proxycast-server 等价
+    if proxycast_lib::headless_requested() {
+        return proxycast_lib::run_headless();
+    }
+    proxycast_lib::run();
+    std::process::ExitCode::SUCCESS
 }
@@ -0,0 +1,765 @@
+//! MCP 客户端
+//!
+//! 网关与上游 MCP 服务器之间的 JSON-RPC 连接，支持三种传输：
+//! - stdio：启动子进程，按行收发 JSON
+//! - Streamable HTTP：POST JSON-RPC，响应为 JSON 或 SSE
+//! - SSE（旧版）：GET 建立事件流，`endpoint` 事件给出 POST 地址
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::StreamExt;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// 网关声明的 MCP 协议版本
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// MCP 调用错误
+#[derive(Debug, Clone, Error)]
+pub enum McpError {
+    #[error("配置无效: {0}")]
+    InvalidConfig(String),
+    #[error("传输错误: {0}")]
+    Transport(String),
+    #[error("JSON-RPC 错误 {code}: {message}")]
+    Rpc {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+    #[error("请求超时")]
+    Timeout,
+    #[error("连接已关闭")]
+    Closed,
+}
+
+impl McpError {
+    /// 转为 JSON-RPC error 对象
+    pub fn to_rpc_error(&self) -> Value {
+        match self {
+            McpError::Rpc {
+                code,
+                message,
+                data,
+            } => {
+                let mut error = json!({"code": code, "message": message});
+                if let Some(data) = data {
+                    error["data"] = data.clone();
+                }
+                error
+            }
+            other => json!({"code": -32603, "message": other.to_string()}),
+        }
+    }
+}
+
+/// 上游服务器传输配置（解析自 `mcp_servers.server_config`）
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportConfig {
+    Stdio {
+        command: String,
+        args: Vec<String>,
+        env: HashMap<String, String>,
+        cwd: Option<String>,
+    },
+    StreamableHttp {
+        url: String,
+        headers: HashMap<String, String>,
+    },
+    Sse {
+        url: String,
+        headers: HashMap<String, String>,
+    },
+}
+
+fn string_map(value: &Value) -> HashMap<String, String> {
+    value
+        .as_object()
+        .map(|map| {
+            map.iter()
+                .filter_map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl TransportConfig {
+    /// 解析 Claude / Gemini 风格的服务器配置
+    ///
+    /// - `{"command", "args", "env", "cwd"}` → stdio
+    /// - `{"type": "http" | "streamable-http", "url", "headers"}` / `{"httpUrl"}` → Streamable HTTP
+    /// - `{"type": "sse", "url", "headers"}` → SSE
+    pub fn from_value(config: &Value) -> Result<Self, McpError> {
+        let headers = string_map(&config["headers"]);
+        let kind = config["type"].as_str().unwrap_or_default();
+
+        if let Some(url) = config["httpUrl"].as_str() {
+            return Ok(TransportConfig::StreamableHttp {
+                url: url.to_string(),
+                headers,
+            });
+        }
+        match (kind, config["command"].as_str(), config["url"].as_str()) {
+            ("" | "stdio", Some(command), _) => Ok(TransportConfig::Stdio {
+                command: command.to_string(),
+                args: config["args"]
+                    .as_array()
+                    .map(|args| {
+                        args.iter()
+                            .filter_map(|a| a.as_str().map(String::from))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                env: string_map(&config["env"]),
+                cwd: config["cwd"].as_str().map(String::from),
+            }),
+            ("sse", _, Some(url)) => Ok(TransportConfig::Sse {
+                url: url.to_string(),
+                headers,
+            }),
+            ("" | "http" | "streamable-http" | "streamableHttp", _, Some(url)) => {
+                Ok(TransportConfig::StreamableHttp {
+                    url: url.to_string(),
+                    headers,
+                })
+            }
+            _ => Err(McpError::InvalidConfig(format!(
+                "无法识别的服务器配置: {}",
+                config
+            ))),
+        }
+    }
+}
+
+/// 等待响应的请求
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Value>>>>;
+
+/// 增量 SSE 解析器
+#[derive(Debug, Default)]
+pub(crate) struct SseParser {
+    buffer: String,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// 追加字节，返回完整的 (event, data) 事件
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Vec<(String, String)> {
+        self.buffer.push_str(&String::from_utf8_lossy(chunk));
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=pos).collect();
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    let event = self.event.take().unwrap_or_else(|| "message".to_string());
+                    events.push((event, self.data.join("\n")));
+                    self.data.clear();
+                }
+                self.event = None;
+            } else if let Some(event) = line.strip_prefix("event:") {
+                self.event = Some(event.trim().to_string());
+            } else if let Some(data) = line.strip_prefix("data:") {
+                self.data
+                    .push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+        }
+        events
+    }
+}
+
+enum Transport {
+    Stdio {
+        stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+        child: tokio::sync::Mutex<Child>,
+    },
+    StreamableHttp {
+        http: reqwest::Client,
+        url: String,
+        headers: HashMap<String, String>,
+        session_id: Arc<Mutex<Option<String>>>,
+    },
+    Sse {
+        http: reqwest::Client,
+        endpoint: String,
+        headers: HashMap<String, String>,
+    },
+}
+
+/// 与单个上游 MCP 服务器的连接
+pub struct McpClient {
+    name: String,
+    transport: Transport,
+    pending: Pending,
+    next_id: AtomicU64,
+    alive: Arc<AtomicBool>,
+    timeout: Duration,
+    /// 上游 initialize 返回的 capabilities
+    capabilities: Mutex<Value>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl McpClient {
+    /// 建立连接并完成 initialize 握手
+    pub async fn connect(
+        name: &str,
+        config: &TransportConfig,
+        timeout: Duration,
+    ) -> Result<Arc<Self>, McpError> {
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let alive = Arc::new(AtomicBool::new(true));
+        let mut tasks = Vec::new();
+
+        let transport = match config {
+            TransportConfig::Stdio {
+                command,
+                args,
+                env,
+                cwd,
+            } => {
+                let mut cmd = Command::new(command);
+                cmd.args(args)
+                    .envs(env)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true);
+                if let Some(cwd) = cwd {
+                    cmd.current_dir(crate::config::expand_tilde(cwd));
+                }
+                let mut child = cmd
+                    .spawn()
+                    .map_err(|e| McpError::Transport(format!("启动 {} 失败: {}", command, e)))?;
+                let stdin = Arc::new(tokio::sync::Mutex::new(
+                    child.stdin.take().ok_or(McpError::Closed)?,
+                ));
+                let stdout = child.stdout.take().ok_or(McpError::Closed)?;
+                if let Some(stderr) = child.stderr.take() {
+                    let name = name.to_string();
+                    tasks.push(tokio::spawn(async move {
+                        let mut lines = BufReader::new(stderr).lines();
+                        while let Ok(Some(line)) = lines.next_line().await {
+                            tracing::debug!("[MCP_GATEWAY] {} stderr: {}", name, line);
+                        }
+                    }));
+                }
+
+                let reader_pending = pending.clone();
+                let reader_alive = alive.clone();
+                let reader_stdin = stdin.clone();
+                tasks.push(tokio::spawn(async move {
+                    let mut lines = BufReader::new(stdout).lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                            continue;
+                        };
+                        if let Some(reply) = route_incoming(&reader_pending, message) {
+                            let mut stdin = reader_stdin.lock().await;
+                            let _ = stdin.write_all(format!("{}\n", reply).as_bytes()).await;
+                            let _ = stdin.flush().await;
+                        }
+                    }
+                    close(&reader_alive, &reader_pending);
+                }));
+
+                Transport::Stdio {
+                    stdin,
+                    child: tokio::sync::Mutex::new(child),
+                }
+            }
+            TransportConfig::StreamableHttp { url, headers } => Transport::StreamableHttp {
+                http: reqwest::Client::new(),
+                url: url.clone(),
+                headers: headers.clone(),
+                session_id: Arc::new(Mutex::new(None)),
+            },
+            TransportConfig::Sse { url, headers } => {
+                let http = reqwest::Client::new();
+                let (endpoint, task) =
+                    open_sse(&http, url, headers, pending.clone(), alive.clone(), timeout).await?;
+                tasks.push(task);
+                Transport::Sse {
+                    http,
+                    endpoint,
+                    headers: headers.clone(),
+                }
+            }
+        };
+
+        let client = Arc::new(Self {
+            name: name.to_string(),
+            transport,
+            pending,
+            next_id: AtomicU64::new(1),
+            alive,
+            timeout,
+            capabilities: Mutex::new(Value::Null),
+            tasks: Mutex::new(tasks),
+        });
+
+        let result = client
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {"name": "proxycast", "version": env!("CARGO_PKG_VERSION")}
+                }),
+            )
+            .await;
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                client.shutdown().await;
+                return Err(e);
+            }
+        };
+        *client.capabilities.lock() = result["capabilities"].clone();
+        client
+            .notify("notifications/initialized", json!({}))
+            .await?;
+        Ok(client)
+    }
+
+    /// 服务器名称
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 连接是否仍然可用
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    /// 上游是否声明了某项能力（tools / resources / prompts）
+    pub fn supports(&self, capability: &str) -> bool {
+        !self.capabilities.lock()[capability].is_null()
+    }
+
+    /// 发送请求并等待结果
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        if !self.is_alive() {
+            return Err(McpError::Closed);
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().insert(id, tx);
+
+        let sent = match &self.transport {
+            Transport::StreamableHttp { .. } => match self.post_streamable(&message, id).await {
+                Ok(Some(response)) => {
+                    self.pending.lock().remove(&id);
+                    return into_result(response);
+                }
+                Ok(None) => Ok(()),
+                Err(e) => Err(e),
+            },
+            _ => self.send(&message).await,
+        };
+        if let Err(e) = sent {
+            self.pending.lock().remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(self.timeout, rx).await {
+            Ok(Ok(response)) => into_result(response),
+            Ok(Err(_)) => Err(McpError::Closed),
+            Err(_) => {
+                self.pending.lock().remove(&id);
+                let _ = self
+                    .notify(
+                        "notifications/cancelled",
+                        json!({"requestId": id, "reason": "timeout"}),
+                    )
+                    .await;
+                Err(McpError::Timeout)
+            }
+        }
+    }
+
+    /// 发送通知
+    pub async fn notify(&self, method: &str, params: Value) -> Result<(), McpError> {
+        let message = json!({"jsonrpc": "2.0", "method": method, "params": params});
+        match &self.transport {
+            Transport::StreamableHttp { .. } => self.post_streamable(&message, 0).await.map(|_| ()),
+            _ => self.send(&message).await,
+        }
+    }
+
+    /// 关闭连接（stdio 会结束子进程）
+    pub async fn shutdown(&self) {
+        self.alive.store(false, Ordering::SeqCst);
+        if let Transport::Stdio { child, .. } = &self.transport {
+            let _ = child.lock().await.kill().await;
+        }
+        if let Transport::StreamableHttp {
+            http,
+            url,
+            headers,
+            session_id,
+        } = &self.transport
+        {
+            // 通知服务器结束会话
+            let session = session_id.lock().take();
+            if let Some(session) = session {
+                let mut request = http.delete(url).header("Mcp-Session-Id", session);
+                for (k, v) in headers {
+                    request = request.header(k, v);
+                }
+                let _ = request.send().await;
+            }
+        }
+        for task in self.tasks.lock().drain(..) {
+            task.abort();
+        }
+        close(&self.alive, &self.pending);
+    }
+
+    /// 通过 stdio / SSE 发送一条消息
+    async fn send(&self, message: &Value) -> Result<(), McpError> {
+        match &self.transport {
+            Transport::Stdio { stdin, .. } => {
+                let mut stdin = stdin.lock().await;
+                let line = format!("{}\n", message);
+                if let Err(e) = stdin.write_all(line.as_bytes()).await {
+                    self.alive.store(false, Ordering::SeqCst);
+                    return Err(McpError::Transport(e.to_string()));
+                }
+                stdin
+                    .flush()
+                    .await
+                    .map_err(|e| McpError::Transport(e.to_string()))
+            }
+            Transport::Sse {
+                http,
+                endpoint,
+                headers,
+            } => {
+                let mut request = http.post(endpoint).json(message);
+                for (k, v) in headers {
+                    request = request.header(k, v);
+                }
+                let response = request
+                    .send()
+                    .await
+                    .map_err(|e| McpError::Transport(e.to_string()))?;
+                if !response.status().is_success() {
+                    return Err(McpError::Transport(format!(
+                        "POST {} 返回 {}",
+                        endpoint,
+                        response.status()
+                    )));
+                }
+                Ok(())
+            }
+            Transport::StreamableHttp { .. } => {
+                unreachable!("streamable HTTP 使用 post_streamable")
+            }
+        }
+    }
+
+    /// Streamable HTTP：POST 一条消息，返回 id 对应的响应（通知返回 None）
+    async fn post_streamable(&self, message: &Value, id: u64) -> Result<Option<Value>, McpError> {
+        let Transport::StreamableHttp {
+            http,
+            url,
+            headers,
+            session_id,
+        } = &self.transport
+        else {
+            return Ok(None);
+        };
+
+        let mut request = http
+            .post(url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(message);
+        for (k, v) in headers {
+            request = request.header(k, v);
+        }
+        let session = session_id.lock().clone();
+        if let Some(session) = session {
+            request = request.header("Mcp-Session-Id", session);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| McpError::Transport(e.to_string()))?;
+        if let Some(session) = response
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|v| v.to_str().ok())
+        {
+            *session_id.lock() = Some(session.to_string());
+        }
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND && session_id.lock().is_some() {
+            // 会话已失效，交由网关重建连接
+            self.alive.store(false, Ordering::SeqCst);
+            return Err(McpError::Closed);
+        }
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(McpError::Transport(format!("HTTP {}: {}", status, body)));
+        }
+        if message.get("id").is_none() {
+            return Ok(None);
+        }
+
+        let is_sse = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("text/event-stream"));
+        if !is_sse {
+            let body: Value = response
+                .json()
+                .await
+                .map_err(|e| McpError::Transport(e.to_string()))?;
+            return Ok(Some(body));
+        }
+
+        let mut parser = SseParser::default();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| McpError::Transport(e.to_string()))?;
+            for (_, data) in parser.push(&chunk) {
+                let Ok(incoming) = serde_json::from_str::<Value>(&data) else {
+                    continue;
+                };
+                if incoming["id"].as_u64() == Some(id) && incoming.get("method").is_none() {
+                    return Ok(Some(incoming));
+                }
+                if let Some(reply) = route_incoming(&self.pending, incoming) {
+                    // 服务器发起的请求：回复后继续等待响应
+                    let _ = Box::pin(self.post_streamable(&reply, 0)).await;
+                }
+            }
+        }
+        Err(McpError::Closed)
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        for task in self.tasks.get_mut().drain(..) {
+            task.abort();
+        }
+    }
+}
+
+/// 处理上游发来的消息：响应交给等待者，服务器请求返回需要回复的消息
+fn route_incoming(pending: &Pending, message: Value) -> Option<Value> {
+    match (message.get("id"), message["method"].as_str()) {
+        (Some(id), Some(method)) => {
+            // 网关不向上游暴露 sampling / roots 等客户端能力
+            let id = id.clone();
+            Some(if method == "ping" {
+                json!({"jsonrpc": "2.0", "id": id, "result": {}})
+            } else {
+                json!({"jsonrpc": "2.0", "id": id, "error": {
+                    "code": -32601,
+                    "message": format!("Method not supported by ProxyCast gateway: {}", method)
+                }})
+            })
+        }
+        (Some(id), None) => {
+            if let Some(tx) = id.as_u64().and_then(|id| pending.lock().remove(&id)) {
+                let _ = tx.send(message);
+            }
+            None
+        }
+        // 通知（日志、进度、list_changed 等）
+        _ => None,
+    }
+}
+
+fn into_result(response: Value) -> Result<Value, McpError> {
+    if let Some(error) = response.get("error") {
+        return Err(McpError::Rpc {
+            code: error["code"].as_i64().unwrap_or(-32603),
+            message: error["message"].as_str().unwrap_or_default().to_string(),
+            data: error.get("data").cloned(),
+        });
+    }
+    Ok(response.get("result").cloned().unwrap_or(Value::Null))
+}
+
+/// 标记连接关闭，唤醒所有等待者
+fn close(alive: &AtomicBool, pending: &Pending) {
+    alive.store(false, Ordering::SeqCst);
+    pending.lock().clear();
+}
+
+/// 旧版 SSE：建立事件流并等待 `endpoint` 事件
+async fn open_sse(
+    http: &reqwest::Client,
+    url: &str,
+    headers: &HashMap<String, String>,
+    pending: Pending,
+    alive: Arc<AtomicBool>,
+    timeout: Duration,
+) -> Result<(String, JoinHandle<()>), McpError> {
+    let mut request = http.get(url).header("Accept", "text/event-stream");
+    for (k, v) in headers {
+        request = request.header(k, v);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| McpError::Transport(e.to_string()))?;
+    if !response.status().is_success() {
+        return Err(McpError::Transport(format!(
+            "GET {} 返回 {}",
+            url,
+            response.status()
+        )));
+    }
+
+    let base = url::Url::parse(url).map_err(|e| McpError::InvalidConfig(e.to_string()))?;
+    let (endpoint_tx, endpoint_rx) = oneshot::channel::<String>();
+    let post_http = http.clone();
+    let post_headers = headers.clone();
+
+    let task = tokio::spawn(async move {
+        let mut endpoint_tx = Some(endpoint_tx);
+        let mut endpoint: Option<String> = None;
+        let mut parser = SseParser::default();
+        let mut stream = response.bytes_stream();
+        while let Some(Ok(chunk)) = stream.next().await {
+            for (event, data) in parser.push(&chunk) {
+                if event == "endpoint" {
+                    if let Ok(resolved) = base.join(data.trim()) {
+                        endpoint = Some(resolved.to_string());
+                        if let Some(tx) = endpoint_tx.take() {
+                            let _ = tx.send(resolved.to_string());
+                        }
+                    }
+                    continue;
+                }
+                let Ok(message) = serde_json::from_str::<Value>(&data) else {
+                    continue;
+                };
+                if let (Some(reply), Some(endpoint)) =
+                    (route_incoming(&pending, message), &endpoint)
+                {
+                    let mut request = post_http.post(endpoint).json(&reply);
+                    for (k, v) in &post_headers {
+                        request = request.header(k, v);
+                    }
+                    let _ = request.send().await;
+                }
+            }
+        }
+        close(&alive, &pending);
+    });
+
+    match tokio::time::timeout(timeout, endpoint_rx).await {
+        Ok(Ok(endpoint)) => Ok((endpoint, task)),
+        _ => {
+            task.abort();
+            Err(McpError::Transport(format!("{} 未返回 endpoint 事件", url)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_config_from_value() {
+        let stdio = TransportConfig::from_value(&json!({
+            "command": "npx",
+            "args": ["-y", "@modelcontextprotocol/server-github"],
+            "env": {"GITHUB_TOKEN": "x"}
+        }))
+        .unwrap();
+        assert!(matches!(stdio, TransportConfig::Stdio { ref args, .. } if args.len() == 2));
+
+        let http = TransportConfig::from_value(&json!({"type": "http", "url": "https://a/mcp"}));
+        assert!(matches!(http, Ok(TransportConfig::StreamableHttp { .. })));
+
+        let sse = TransportConfig::from_value(&json!({"type": "sse", "url": "https://a/sse"}));
+        assert!(matches!(sse, Ok(TransportConfig::Sse { .. })));
+
+        let gemini = TransportConfig::from_value(&json!({"httpUrl": "https://a/mcp"}));
+        assert!(matches!(gemini, Ok(TransportConfig::StreamableHttp { .. })));
+
+        assert!(TransportConfig::from_value(&json!({"type": "sse"})).is_err());
+    }
+
+    #[test]
+    fn test_sse_parser_handles_split_events() {
+        let mut parser = SseParser::default();
+        assert!(parser
+            .push(b"event: endpoint\ndata: /messages?s")
+            .is_empty());
+        let events = parser.push(b"ession=1\n\ndata: {\"id\":1}\r\n\r\n");
+        assert_eq!(
+            events,
+            vec![
+                ("endpoint".to_string(), "/messages?session=1".to_string()),
+                ("message".to_string(), "{\"id\":1}".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_route_incoming() {
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let (tx, mut rx) = oneshot::channel();
+        pending.lock().insert(7, tx);
+
+        assert!(route_incoming(&pending, json!({"id": 7, "result": {"ok": true}})).is_none());
+        assert_eq!(rx.try_recv().unwrap()["result"]["ok"], true);
+
+        let reply = route_incoming(&pending, json!({"id": "s1", "method": "roots/list"})).unwrap();
+        assert_eq!(reply["error"]["code"], -32601);
+        let pong = route_incoming(&pending, json!({"id": 2, "method": "ping"})).unwrap();
+        assert_eq!(pong["result"], json!({}));
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_stdio_client_round_trip() {
+        // 最小的 stdio MCP 服务器：逐行回显 id
+        let script = r#"while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  [ -n "$id" ] && printf '{"jsonrpc":"2.0","id":%s,"result":{"capabilities":{"tools":{}},"tools":[]}}\n' "$id"
+done"#;
+        let config = TransportConfig::Stdio {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env: HashMap::new(),
+            cwd: None,
+        };
+
+        let client = McpClient::connect("echo", &config, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(client.supports("tools"));
+        assert!(!client.supports("prompts"));
+        let result = client.request("tools/list", json!({})).await.unwrap();
+        assert_eq!(result["tools"], json!([]));
+
+        client.shutdown().await;
+        assert!(!client.is_alive());
+        assert!(matches!(
+            client.request("tools/list", json!({})).await,
+            Err(McpError::Closed)
+        ));
+    }
+}
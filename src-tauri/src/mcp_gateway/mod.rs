@@ -0,0 +1,949 @@
+//! MCP 网关
+//!
+//! 托管 `mcp_servers` 表中启用了 ProxyCast 的 MCP 服务器（stdio 子进程或远程服务），
+//! 通过单一的 `/mcp` 端点对外提供：
+//! - 工具 / 提示词以 `<命名空间>__<名称>` 命名，资源 URI 以 `<命名空间>+` 为前缀
+//! - 按认证使用的 API Key 确定客户端身份并应用白名单（未配置白名单的客户端无权访问）
+//! - 会话按空闲超时和最大数量回收
+//! - 工具调用记录到 Flow Monitor
+//! - 后台巡检：同步数据库配置，进程退出或连接断开后按退避间隔重启
+
+pub mod client;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use futures::future::join_all;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::config::McpGatewayConfig;
+use crate::database::dao::mcp::McpDao;
+use crate::database::DbConnection;
+use crate::flow_monitor::{
+    ClientInfo, FlowError, FlowErrorType, FlowMetadata, FlowMonitor, LLMRequest, LLMResponse,
+};
+use crate::models::provider_pool_model::pattern_matches;
+
+pub use client::{McpClient, McpError, TransportConfig, PROTOCOL_VERSION};
+
+/// 命名空间与名称之间的分隔符
+const SEPARATOR: &str = "__";
+/// 巡检间隔
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(5);
+/// 重启退避上限
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// 聚合列表时每个服务器最多翻页次数
+const MAX_PAGES: usize = 20;
+
+/// 使用服务器 API Key 认证的客户端标识
+pub const OWNER_CLIENT: &str = "default";
+
+/// 由服务器名称生成命名空间（仅保留字母、数字和连字符）
+pub fn namespace_of(name: &str) -> String {
+    let namespace: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    if namespace.is_empty() {
+        "mcp".to_string()
+    } else {
+        namespace
+    }
+}
+
+/// 拆分 `<命名空间>__<名称>`
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    name.split_once(SEPARATOR)
+}
+
+/// 拆分 `<命名空间>+<原始 URI>`
+fn split_uri(uri: &str) -> Option<(&str, &str)> {
+    uri.split_once('+')
+}
+
+/// 重启退避：1s、2s、4s …… 最多 60s
+fn backoff(failures: u32) -> Duration {
+    Duration::from_secs(1u64 << failures.min(6)).min(MAX_BACKOFF)
+}
+
+/// 上游服务器运行状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum McpServerState {
+    /// 等待启动
+    Starting,
+    /// 运行中
+    Running,
+    /// 启动失败或已退出，等待重试
+    Backoff,
+}
+
+/// 上游服务器状态
+#[derive(Debug, Clone, Serialize)]
+pub struct McpServerStatus {
+    pub id: String,
+    pub name: String,
+    pub namespace: String,
+    pub state: McpServerState,
+    /// 运行期间的重启次数
+    pub restarts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+struct ManagedServer {
+    name: String,
+    namespace: String,
+    config: serde_json::Value,
+    client: Option<Arc<McpClient>>,
+    /// 是否曾经成功运行（用于区分首次启动与重启）
+    started: bool,
+    restarts: u32,
+    failures: u32,
+    next_attempt: Instant,
+    last_error: Option<String>,
+}
+
+impl ManagedServer {
+    fn status(&self, id: &str) -> McpServerStatus {
+        let state = match &self.client {
+            Some(client) if client.is_alive() => McpServerState::Running,
+            _ if self.failures > 0 => McpServerState::Backoff,
+            _ => McpServerState::Starting,
+        };
+        McpServerStatus {
+            id: id.to_string(),
+            name: self.name.clone(),
+            namespace: self.namespace.clone(),
+            state,
+            restarts: self.restarts,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+/// 网关会话
+#[derive(Debug, Clone)]
+pub struct GatewaySession {
+    /// 客户端标识（由认证使用的 API Key 确定，白名单按此匹配）
+    pub client_id: String,
+    /// 客户端自报的名称（`clientInfo.name`，仅用于日志和 Flow 记录）
+    pub client_name: String,
+}
+
+struct SessionSlot {
+    session: GatewaySession,
+    last_seen: Instant,
+}
+
+/// MCP 网关
+pub struct McpGateway {
+    config: McpGatewayConfig,
+    db: Option<DbConnection>,
+    flow_monitor: Arc<FlowMonitor>,
+    /// 按服务器 ID 索引
+    servers: Mutex<HashMap<String, ManagedServer>>,
+    sessions: parking_lot::Mutex<HashMap<String, SessionSlot>>,
+    supervisor: parking_lot::Mutex<Option<JoinHandle<()>>>,
+    /// 串行化巡检，避免并发启动同一服务器
+    supervise_lock: Mutex<()>,
+}
+
+impl McpGateway {
+    pub fn new(
+        config: McpGatewayConfig,
+        db: Option<DbConnection>,
+        flow_monitor: Arc<FlowMonitor>,
+    ) -> Self {
+        Self {
+            config,
+            db,
+            flow_monitor,
+            servers: Mutex::new(HashMap::new()),
+            sessions: parking_lot::Mutex::new(HashMap::new()),
+            supervisor: parking_lot::Mutex::new(None),
+            supervise_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// 启动后台巡检（未启用时不做任何事）
+    pub fn start(self: &Arc<Self>) {
+        if !self.is_enabled() {
+            return;
+        }
+        let gateway: Weak<Self> = Arc::downgrade(self);
+        let handle = tokio::spawn(async move {
+            loop {
+                let Some(gateway) = gateway.upgrade() else {
+                    break;
+                };
+                gateway.supervise().await;
+                drop(gateway);
+                tokio::time::sleep(SUPERVISE_INTERVAL).await;
+            }
+        });
+        *self.supervisor.lock() = Some(handle);
+        tracing::info!("[MCP_GATEWAY] 已启动");
+    }
+
+    /// 停止巡检并关闭所有上游连接
+    pub async fn shutdown(&self) {
+        if let Some(handle) = self.supervisor.lock().take() {
+            handle.abort();
+        }
+        let servers: Vec<ManagedServer> =
+            self.servers.lock().await.drain().map(|(_, s)| s).collect();
+        for server in servers {
+            if let Some(client) = server.client {
+                client.shutdown().await;
+            }
+        }
+    }
+
+    /// 所有上游服务器状态
+    pub async fn status(&self) -> Vec<McpServerStatus> {
+        let servers = self.servers.lock().await;
+        let mut status: Vec<McpServerStatus> = servers.iter().map(|(id, s)| s.status(id)).collect();
+        status.sort_by(|a, b| a.namespace.cmp(&b.namespace));
+        status
+    }
+
+    /// 同步数据库配置，并启动 / 重启需要运行的服务器
+    pub async fn supervise(&self) {
+        let _guard = self.supervise_lock.lock().await;
+        let Some(desired) = self.load_servers() else {
+            return;
+        };
+
+        let mut stopped = Vec::new();
+        let mut to_connect = Vec::new();
+        {
+            let mut servers = self.servers.lock().await;
+
+            // 移除已删除、已禁用或配置变化的服务器
+            servers.retain(|id, server| {
+                let keep = desired.iter().any(|(d_id, name, config)| {
+                    d_id == id && *name == server.name && *config == server.config
+                });
+                if !keep {
+                    tracing::info!("[MCP_GATEWAY] 停止服务器: {}", server.name);
+                    if let Some(client) = server.client.take() {
+                        stopped.push(client);
+                    }
+                }
+                keep
+            });
+
+            for (id, name, config) in &desired {
+                if servers.contains_key(id) {
+                    continue;
+                }
+                let base = namespace_of(name);
+                let mut namespace = base.clone();
+                let mut n = 2;
+                while servers.values().any(|s| s.namespace == namespace) {
+                    namespace = format!("{}-{}", base, n);
+                    n += 1;
+                }
+                servers.insert(
+                    id.clone(),
+                    ManagedServer {
+                        name: name.clone(),
+                        namespace,
+                        config: config.clone(),
+                        client: None,
+                        started: false,
+                        restarts: 0,
+                        failures: 0,
+                        next_attempt: Instant::now(),
+                        last_error: None,
+                    },
+                );
+            }
+
+            let now = Instant::now();
+            for (id, server) in servers.iter_mut() {
+                if let Some(client) = &server.client {
+                    if client.is_alive() {
+                        continue;
+                    }
+                    tracing::warn!("[MCP_GATEWAY] 服务器 {} 已退出，准备重启", server.name);
+                    server.last_error = Some("连接已断开".to_string());
+                    server.client = None;
+                }
+                if server.next_attempt <= now {
+                    to_connect.push((id.clone(), server.name.clone(), server.config.clone()));
+                }
+            }
+        }
+
+        for client in stopped {
+            client.shutdown().await;
+        }
+        if to_connect.is_empty() {
+            return;
+        }
+
+        let timeout = Duration::from_secs(self.config.request_timeout_secs.max(1));
+        let results = join_all(to_connect.into_iter().map(|(id, name, config)| async move {
+            let result = match TransportConfig::from_value(&config) {
+                Ok(transport) => McpClient::connect(&name, &transport, timeout).await,
+                Err(e) => Err(e),
+            };
+            (id, config, result)
+        }))
+        .await;
+
+        let mut servers = self.servers.lock().await;
+        for (id, config, result) in results {
+            let Some(server) = servers.get_mut(&id) else {
+                // 连接期间被移除
+                if let Ok(client) = result {
+                    client.shutdown().await;
+                }
+                continue;
+            };
+            if server.config != config {
+                continue;
+            }
+            match result {
+                Ok(client) => {
+                    if server.started {
+                        server.restarts += 1;
+                    }
+                    tracing::info!(
+                        "[MCP_GATEWAY] 服务器 {} 已就绪 (命名空间: {})",
+                        server.name,
+                        server.namespace
+                    );
+                    server.client = Some(client);
+                    server.started = true;
+                    server.failures = 0;
+                    server.last_error = None;
+                }
+                Err(e) => {
+                    server.failures += 1;
+                    server.next_attempt = Instant::now() + backoff(server.failures);
+                    tracing::warn!(
+                        "[MCP_GATEWAY] 服务器 {} 启动失败（第 {} 次）: {}",
+                        server.name,
+                        server.failures,
+                        e
+                    );
+                    server.last_error = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// 读取启用了 ProxyCast 的服务器：(id, name, config)
+    fn load_servers(&self) -> Option<Vec<(String, String, Value)>> {
+        let db = self.db.as_ref()?;
+        let conn = match db.lock() {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::warn!("[MCP_GATEWAY] 数据库锁获取失败: {}", e);
+                return None;
+            }
+        };
+        match McpDao::get_all(&conn) {
+            Ok(servers) => Some(
+                servers
+                    .into_iter()
+                    .filter(|s| s.enabled_proxycast)
+                    .map(|s| (s.id, s.name, s.server_config))
+                    .collect(),
+            ),
+            Err(e) => {
+                tracing::warn!("[MCP_GATEWAY] 读取 MCP 服务器失败: {}", e);
+                None
+            }
+        }
+    }
+
+    // ========================================================================
+    // 会话
+    // ========================================================================
+
+    /// 根据认证使用的 API Key 确定客户端标识
+    ///
+    /// 服务器 API Key 对应所有者，`client_keys` 中的 Key 对应其客户端；都不匹配时返回 None
+    pub fn authenticate(&self, api_key: &str, server_api_key: &str) -> Option<String> {
+        if api_key.is_empty() {
+            return None;
+        }
+        if api_key == server_api_key {
+            return Some(OWNER_CLIENT.to_string());
+        }
+        self.config
+            .client_keys
+            .iter()
+            .find(|(_, key)| key.as_str() == api_key)
+            .map(|(client_id, _)| client_id.clone())
+    }
+
+    fn session_idle(&self) -> Duration {
+        Duration::from_secs(self.config.session_idle_secs.max(1))
+    }
+
+    /// 创建会话，返回会话 ID
+    ///
+    /// 先清理空闲超时的会话，仍达到上限时淘汰最久未使用的会话
+    pub fn create_session(&self, client_id: &str, client_name: &str) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        let idle = self.session_idle();
+        let max_sessions = self.config.max_sessions.max(1);
+
+        let mut sessions = self.sessions.lock();
+        sessions.retain(|_, slot| slot.last_seen.elapsed() < idle);
+        while sessions.len() >= max_sessions {
+            let Some(oldest) = sessions
+                .iter()
+                .min_by_key(|(_, slot)| slot.last_seen)
+                .map(|(id, _)| id.clone())
+            else {
+                break;
+            };
+            sessions.remove(&oldest);
+        }
+        sessions.insert(
+            id.clone(),
+            SessionSlot {
+                session: GatewaySession {
+                    client_id: client_id.to_string(),
+                    client_name: client_name.to_string(),
+                },
+                last_seen: Instant::now(),
+            },
+        );
+        id
+    }
+
+    /// 获取会话并刷新活跃时间
+    ///
+    /// 会话已超时或不属于该客户端时返回 None
+    pub fn session(&self, id: &str, client_id: &str) -> Option<GatewaySession> {
+        let mut sessions = self.sessions.lock();
+        let slot = sessions.get_mut(id)?;
+        if slot.last_seen.elapsed() >= self.session_idle() {
+            sessions.remove(id);
+            return None;
+        }
+        if slot.session.client_id != client_id {
+            return None;
+        }
+        slot.last_seen = Instant::now();
+        Some(slot.session.clone())
+    }
+
+    /// 结束会话（仅限会话所属的客户端）
+    pub fn close_session(&self, id: &str, client_id: &str) -> bool {
+        let mut sessions = self.sessions.lock();
+        let owned = sessions
+            .get(id)
+            .is_some_and(|slot| slot.session.client_id == client_id);
+        owned && sessions.remove(id).is_some()
+    }
+
+    // ========================================================================
+    // 请求分发
+    // ========================================================================
+
+    /// `initialize` 的结果
+    pub fn initialize_result(&self, requested_version: Option<&str>) -> Value {
+        let version = match requested_version {
+            Some(v @ ("2024-11-05" | "2025-03-26" | "2025-06-18")) => v,
+            _ => PROTOCOL_VERSION,
+        };
+        json!({
+            "protocolVersion": version,
+            "capabilities": {
+                "tools": {},
+                "resources": {},
+                "prompts": {},
+                "completions": {}
+            },
+            "serverInfo": {
+                "name": "proxycast-mcp-gateway",
+                "version": env!("CARGO_PKG_VERSION")
+            }
+        })
+    }
+
+    /// 处理会话内的请求
+    pub async fn handle_request(
+        &self,
+        session: &GatewaySession,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, McpError> {
+        match method {
+            "ping" | "logging/setLevel" => Ok(json!({})),
+            "tools/list" => Ok(json!({
+                "tools": self.aggregate(session, "tools", "tools/list", "tools").await
+            })),
+            "prompts/list" => Ok(json!({
+                "prompts": self.aggregate(session, "prompts", "prompts/list", "prompts").await
+            })),
+            "resources/list" => Ok(json!({
+                "resources": self.aggregate(session, "resources", "resources/list", "resources").await
+            })),
+            "resources/templates/list" => Ok(json!({
+                "resourceTemplates": self
+                    .aggregate(session, "resources", "resources/templates/list", "resourceTemplates")
+                    .await
+            })),
+            "tools/call" => self.call_tool(session, params).await,
+            "prompts/get" => {
+                let name = params["name"].as_str().unwrap_or_default().to_string();
+                let (client, original) = self.route_name(session, &name).await?;
+                let mut params = params;
+                params["name"] = json!(original);
+                client.request(method, params).await
+            }
+            "resources/read" | "resources/subscribe" | "resources/unsubscribe" => {
+                let uri = params["uri"].as_str().unwrap_or_default().to_string();
+                let (namespace, client, original) = self.route_uri(session, &uri).await?;
+                let mut params = params;
+                params["uri"] = json!(original);
+                let mut result = client.request(method, params).await?;
+                if let Some(contents) = result["contents"].as_array_mut() {
+                    for content in contents {
+                        prefix_field(content, "uri", &namespace, "+");
+                    }
+                }
+                Ok(result)
+            }
+            "completion/complete" => {
+                let mut params = params;
+                let reference = params["ref"].clone();
+                let client = match reference["type"].as_str() {
+                    Some("ref/prompt") => {
+                        let name = reference["name"].as_str().unwrap_or_default();
+                        let (client, original) = self.route_name(session, name).await?;
+                        params["ref"]["name"] = json!(original);
+                        client
+                    }
+                    _ => {
+                        let uri = reference["uri"].as_str().unwrap_or_default();
+                        let (_, client, original) = self.route_uri(session, uri).await?;
+                        params["ref"]["uri"] = json!(original);
+                        client
+                    }
+                };
+                client.request(method, params).await
+            }
+            _ => Err(McpError::Rpc {
+                code: -32601,
+                message: format!("Method not found: {}", method),
+                data: None,
+            }),
+        }
+    }
+
+    /// 当前可用的上游连接：(命名空间, 连接)
+    async fn clients(&self) -> Vec<(String, Arc<McpClient>)> {
+        let servers = self.servers.lock().await;
+        let mut clients: Vec<(String, Arc<McpClient>)> = servers
+            .values()
+            .filter_map(|s| {
+                s.client
+                    .as_ref()
+                    .filter(|c| c.is_alive())
+                    .map(|c| (s.namespace.clone(), c.clone()))
+            })
+            .collect();
+        clients.sort_by(|a, b| a.0.cmp(&b.0));
+        clients
+    }
+
+    async fn client_for(&self, namespace: &str) -> Option<Arc<McpClient>> {
+        self.clients()
+            .await
+            .into_iter()
+            .find(|(ns, _)| ns == namespace)
+            .map(|(_, c)| c)
+    }
+
+    /// 是否允许客户端访问某个带命名空间的名称
+    fn is_allowed(&self, session: &GatewaySession, name: &str) -> bool {
+        is_allowed(&self.config, &session.client_id, name)
+    }
+
+    /// 聚合所有上游的列表结果，加上命名空间并按白名单过滤
+    async fn aggregate(
+        &self,
+        session: &GatewaySession,
+        capability: &str,
+        method: &str,
+        key: &str,
+    ) -> Vec<Value> {
+        let clients: Vec<(String, Arc<McpClient>)> = self
+            .clients()
+            .await
+            .into_iter()
+            .filter(|(_, c)| c.supports(capability))
+            .collect();
+
+        let results = join_all(clients.iter().map(|(namespace, client)| async move {
+            let mut items = Vec::new();
+            let mut cursor: Option<Value> = None;
+            for _ in 0..MAX_PAGES {
+                let params = match &cursor {
+                    Some(cursor) => json!({"cursor": cursor}),
+                    None => json!({}),
+                };
+                match client.request(method, params).await {
+                    Ok(mut result) => {
+                        if let Some(page) = result[key].as_array_mut() {
+                            items.append(page);
+                        }
+                        cursor = result.get("nextCursor").filter(|c| !c.is_null()).cloned();
+                        if cursor.is_none() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("[MCP_GATEWAY] {} {} 失败: {}", namespace, method, e);
+                        break;
+                    }
+                }
+            }
+            (namespace.clone(), items)
+        }))
+        .await;
+
+        let mut aggregated = Vec::new();
+        for (namespace, items) in results {
+            for mut item in items {
+                let allowed_as = match key {
+                    "resources" => {
+                        let uri = item["uri"].as_str().unwrap_or_default();
+                        format!("{}{}{}", namespace, SEPARATOR, uri)
+                    }
+                    "resourceTemplates" => {
+                        let uri = item["uriTemplate"].as_str().unwrap_or_default();
+                        format!("{}{}{}", namespace, SEPARATOR, uri)
+                    }
+                    _ => format!(
+                        "{}{}{}",
+                        namespace,
+                        SEPARATOR,
+                        item["name"].as_str().unwrap_or_default()
+                    ),
+                };
+                if !self.is_allowed(session, &allowed_as) {
+                    continue;
+                }
+                match key {
+                    "resources" => prefix_field(&mut item, "uri", &namespace, "+"),
+                    "resourceTemplates" => prefix_field(&mut item, "uriTemplate", &namespace, "+"),
+                    _ => prefix_field(&mut item, "name", &namespace, SEPARATOR),
+                }
+                aggregated.push(item);
+            }
+        }
+        aggregated
+    }
+
+    /// 按 `<命名空间>__<名称>` 找到上游连接，返回连接与原始名称
+    async fn route_name(
+        &self,
+        session: &GatewaySession,
+        name: &str,
+    ) -> Result<(Arc<McpClient>, String), McpError> {
+        let invalid = || McpError::Rpc {
+            code: -32602,
+            message: format!("Unknown name: {}", name),
+            data: None,
+        };
+        let (namespace, original) = split_name(name).ok_or_else(invalid)?;
+        if !self.is_allowed(session, name) {
+            return Err(McpError::Rpc {
+                code: -32602,
+                message: format!("{} is not allowed for client {}", name, session.client_id),
+                data: None,
+            });
+        }
+        let client = self.client_for(namespace).await.ok_or_else(invalid)?;
+        Ok((client, original.to_string()))
+    }
+
+    /// 按 `<命名空间>+<URI>` 找到上游连接，返回命名空间、连接与原始 URI
+    async fn route_uri(
+        &self,
+        session: &GatewaySession,
+        uri: &str,
+    ) -> Result<(String, Arc<McpClient>, String), McpError> {
+        let invalid = || McpError::Rpc {
+            code: -32002,
+            message: format!("Resource not found: {}", uri),
+            data: None,
+        };
+        let (namespace, original) = split_uri(uri).ok_or_else(invalid)?;
+        if !self.is_allowed(session, &format!("{}{}{}", namespace, SEPARATOR, original)) {
+            return Err(invalid());
+        }
+        let client = self.client_for(namespace).await.ok_or_else(invalid)?;
+        Ok((namespace.to_string(), client, original.to_string()))
+    }
+
+    /// 转发工具调用并记录到 Flow Monitor
+    async fn call_tool(&self, session: &GatewaySession, params: Value) -> Result<Value, McpError> {
+        let name = params["name"].as_str().unwrap_or_default().to_string();
+        let (client, original) = self.route_name(session, &name).await?;
+
+        let flow_id = self
+            .start_call_flow(session, &name, client.name(), &params)
+            .await;
+        let mut upstream = params;
+        upstream["name"] = json!(original);
+        let started = Utc::now();
+        let result = client.request("tools/call", upstream).await;
+
+        if let Some(flow_id) = flow_id {
+            match &result {
+                Ok(result) => {
+                    let content = result["content"]
+                        .as_array()
+                        .map(|items| {
+                            items
+                                .iter()
+                                .filter_map(|item| item["text"].as_str())
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        })
+                        .unwrap_or_default();
+                    let is_error = result["isError"].as_bool().unwrap_or(false);
+                    let response = LLMResponse {
+                        status_code: 200,
+                        status_text: if is_error { "tool error" } else { "OK" }.to_string(),
+                        body: result.clone(),
+                        content,
+                        size_bytes: result.to_string().len(),
+                        timestamp_start: started,
+                        timestamp_end: Utc::now(),
+                        ..LLMResponse::default()
+                    };
+                    self.flow_monitor
+                        .complete_flow(&flow_id, Some(response))
+                        .await;
+                }
+                Err(e) => {
+                    let error_type = match e {
+                        McpError::Timeout => FlowErrorType::Timeout,
+                        McpError::Rpc { .. } => FlowErrorType::BadRequest,
+                        _ => FlowErrorType::Network,
+                    };
+                    self.flow_monitor
+                        .fail_flow(&flow_id, FlowError::new(error_type, e.to_string()))
+                        .await;
+                }
+            }
+        }
+        result
+    }
+
+    async fn start_call_flow(
+        &self,
+        session: &GatewaySession,
+        name: &str,
+        server: &str,
+        params: &Value,
+    ) -> Option<String> {
+        let request = LLMRequest {
+            method: "POST".to_string(),
+            path: "/mcp/tools/call".to_string(),
+            body: params.clone(),
+            model: name.to_string(),
+            size_bytes: params.to_string().len(),
+            timestamp: Utc::now(),
+            ..LLMRequest::default()
+        };
+        let metadata = FlowMetadata {
+            provider_id: Some("mcp".to_string()),
+            credential_name: Some(server.to_string()),
+            client_info: ClientInfo {
+                user_agent: Some(format!("{} ({})", session.client_name, session.client_id)),
+                ..ClientInfo::default()
+            },
+            ..FlowMetadata::default()
+        };
+        self.flow_monitor.start_flow(request, metadata).await
+    }
+}
+
+/// 白名单判断：客户端没有白名单时拒绝访问，所有者没有白名单时不受限制
+fn is_allowed(config: &McpGatewayConfig, client_id: &str, name: &str) -> bool {
+    match config.allowlists.get(client_id) {
+        Some(patterns) => patterns.iter().any(|p| pattern_matches(p, name)),
+        None => client_id == OWNER_CLIENT,
+    }
+}
+
+fn prefix_field(item: &mut Value, field: &str, namespace: &str, separator: &str) {
+    if let Some(value) = item[field].as_str() {
+        item[field] = json!(format!("{}{}{}", namespace, separator, value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_monitor::FlowMonitorConfig;
+
+    fn gateway(config: McpGatewayConfig) -> McpGateway {
+        McpGateway::new(
+            config,
+            None,
+            Arc::new(FlowMonitor::new(FlowMonitorConfig::default(), None)),
+        )
+    }
+
+    #[test]
+    fn test_namespacing() {
+        assert_eq!(namespace_of("GitHub MCP"), "GitHub-MCP");
+        assert_eq!(namespace_of("my_server"), "my-server");
+        assert_eq!(namespace_of("  "), "mcp");
+
+        assert_eq!(
+            split_name("my-server__read_file"),
+            Some(("my-server", "read_file"))
+        );
+        assert_eq!(
+            split_uri("fs+file:///tmp/a+b.txt"),
+            Some(("fs", "file:///tmp/a+b.txt"))
+        );
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let mut config = McpGatewayConfig::default();
+        config.allowlists.insert(
+            "claude-code".to_string(),
+            vec!["github__*".to_string(), "fs__read_file".to_string()],
+        );
+
+        assert!(is_allowed(&config, "claude-code", "github__create_issue"));
+        assert!(is_allowed(&config, "claude-code", "fs__read_file"));
+        assert!(!is_allowed(&config, "claude-code", "fs__write_file"));
+        // 没有白名单的客户端无权访问，所有者不受限制
+        assert!(!is_allowed(&config, "cursor-vscode", "fs__read_file"));
+        assert!(is_allowed(&config, OWNER_CLIENT, "fs__write_file"));
+
+        config
+            .allowlists
+            .insert(OWNER_CLIENT.to_string(), vec!["github__*".to_string()]);
+        assert!(!is_allowed(&config, OWNER_CLIENT, "fs__write_file"));
+    }
+
+    #[test]
+    fn test_authenticate_by_api_key() {
+        let mut config = McpGatewayConfig::default();
+        config
+            .client_keys
+            .insert("claude-code".to_string(), "client-key".to_string());
+        let gateway = gateway(config);
+
+        assert_eq!(
+            gateway.authenticate("server-key", "server-key").as_deref(),
+            Some(OWNER_CLIENT)
+        );
+        assert_eq!(
+            gateway.authenticate("client-key", "server-key").as_deref(),
+            Some("claude-code")
+        );
+        assert!(gateway.authenticate("other-key", "server-key").is_none());
+        assert!(gateway.authenticate("", "").is_none());
+    }
+
+    #[test]
+    fn test_session_bound_to_client() {
+        let gateway = gateway(McpGatewayConfig::default());
+        let id = gateway.create_session("claude-code", "Claude Code");
+
+        assert!(gateway.session(&id, "cursor").is_none());
+        assert!(!gateway.close_session(&id, "cursor"));
+        assert_eq!(
+            gateway.session(&id, "claude-code").unwrap().client_name,
+            "Claude Code"
+        );
+        assert!(gateway.close_session(&id, "claude-code"));
+    }
+
+    #[test]
+    fn test_session_limit_evicts_least_recently_used() {
+        let gateway = gateway(McpGatewayConfig {
+            max_sessions: 2,
+            ..McpGatewayConfig::default()
+        });
+        let first = gateway.create_session("a", "a");
+        let second = gateway.create_session("a", "a");
+        // 访问过的会话保留，最久未使用的被淘汰
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(gateway.session(&first, "a").is_some());
+        let third = gateway.create_session("a", "a");
+
+        assert!(gateway.session(&first, "a").is_some());
+        assert!(gateway.session(&second, "a").is_none());
+        assert!(gateway.session(&third, "a").is_some());
+    }
+
+    #[test]
+    fn test_session_idle_timeout() {
+        let gateway = gateway(McpGatewayConfig {
+            session_idle_secs: 1,
+            ..McpGatewayConfig::default()
+        });
+        let id = gateway.create_session("a", "a");
+        gateway.sessions.lock().get_mut(&id).unwrap().last_seen -= Duration::from_secs(2);
+
+        assert!(gateway.session(&id, "a").is_none());
+        assert!(gateway.sessions.lock().is_empty());
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), Duration::from_secs(2));
+        assert_eq!(backoff(3), Duration::from_secs(8));
+        assert_eq!(backoff(20), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_handle_request_without_servers() {
+        let gateway = gateway(McpGatewayConfig::default());
+        let session_id = gateway.create_session(OWNER_CLIENT, "test");
+        let session = gateway.session(&session_id, OWNER_CLIENT).unwrap();
+
+        let tools = gateway
+            .handle_request(&session, "tools/list", json!({}))
+            .await
+            .unwrap();
+        assert_eq!(tools, json!({"tools": []}));
+
+        let err = gateway
+            .handle_request(&session, "tools/call", json!({"name": "fs__read_file"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, McpError::Rpc { code: -32602, .. }));
+
+        let err = gateway
+            .handle_request(&session, "sampling/createMessage", json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, McpError::Rpc { code: -32601, .. }));
+
+        assert!(gateway.close_session(&session_id, OWNER_CLIENT));
+        assert!(gateway.session(&session_id, OWNER_CLIENT).is_none());
+    }
+}
@@ -0,0 +1,680 @@
+//! 内置 MCP 服务器
+//!
+//! 将 ProxyCast 自身作为 MCP 服务器提供给 Agent：查询 Flow、查看凭证健康与配额、
+//! 读取用量统计、切换默认 Provider。
+//!
+//! 工具按作用域划分：
+//! - 只读：`query_flows`、`get_flow`、`credential_health`、`usage_stats`、`get_default_provider`
+//! - 管理：在只读工具之外提供 `set_default_provider`、`reset_credential`
+//!
+//! 传输与作用域：
+//! - `POST /mcp/proxycast`：管理 API 认证（`remote_management`），只读作用域
+//! - `POST /v0/management/mcp`：管理 API 认证，管理作用域
+//! - stdio（`proxycast-server --mcp-stdio`）：环境变量 `PROXYCAST_MANAGEMENT_KEY`
+//!   与 `remote_management.secret_key` 一致时为管理作用域，否则只读
+
+use std::sync::Arc;
+
+use parking_lot::RwLock as ParkingLotRwLock;
+use serde_json::{json, Value};
+use subtle::ConstantTimeEq;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::RwLock;
+
+use crate::config::RemoteManagementConfig;
+use crate::credential::QuotaManager;
+use crate::database::DbConnection;
+use crate::flow_monitor::{FlowFilter, FlowQueryService, FlowSortBy, FlowSummary};
+use crate::mcp_gateway::PROTOCOL_VERSION;
+use crate::router::Router;
+use crate::services::provider_pool_service::ProviderPoolService;
+use crate::telemetry::{StatsAggregator, TimeRange};
+use crate::ProviderType;
+
+/// stdio 模式下提供管理密钥的环境变量
+pub const MANAGEMENT_KEY_ENV: &str = "PROXYCAST_MANAGEMENT_KEY";
+
+/// `query_flows` 默认每页数量
+const DEFAULT_PAGE_SIZE: u64 = 20;
+/// `query_flows` 每页数量上限
+const MAX_PAGE_SIZE: u64 = 100;
+/// `usage_stats` 默认统计窗口（小时）
+const DEFAULT_STATS_HOURS: i64 = 24;
+/// `usage_stats` 统计窗口上限（小时）
+const MAX_STATS_HOURS: i64 = 24 * 30;
+
+/// 工具作用域
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum McpScope {
+    /// 只读工具
+    ReadOnly,
+    /// 只读工具 + 管理工具
+    Admin,
+}
+
+impl McpScope {
+    /// 根据管理密钥确定作用域
+    ///
+    /// 未配置 `secret_key`（管理 API 禁用）或密钥不匹配时只授予只读作用域。
+    pub fn from_management_key(config: &RemoteManagementConfig, provided: Option<&str>) -> Self {
+        match (config.secret_key.as_deref(), provided) {
+            (Some(expected), Some(provided))
+                if !expected.is_empty()
+                    && bool::from(expected.as_bytes().ct_eq(provided.as_bytes())) =>
+            {
+                McpScope::Admin
+            }
+            _ => McpScope::ReadOnly,
+        }
+    }
+
+    fn allows(self, tool: &ToolSpec) -> bool {
+        !tool.admin || self == McpScope::Admin
+    }
+}
+
+/// 工具定义
+struct ToolSpec {
+    name: &'static str,
+    description: &'static str,
+    admin: bool,
+    input_schema: fn() -> Value,
+}
+
+const TOOLS: &[ToolSpec] = &[
+    ToolSpec {
+        name: "query_flows",
+        description: "Query recent LLM flows using the flow filter syntax \
+                      (e.g. `~m claude & ~s 5xx`, `~p kiro | ~latency >5s`). \
+                      Returns flow summaries, newest first.",
+        admin: false,
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {
+                    "filter": {"type": "string", "description": "Filter expression; empty returns all flows"},
+                    "page": {"type": "integer", "minimum": 1, "default": 1},
+                    "page_size": {"type": "integer", "minimum": 1, "maximum": MAX_PAGE_SIZE, "default": DEFAULT_PAGE_SIZE}
+                }
+            })
+        },
+    },
+    ToolSpec {
+        name: "get_flow",
+        description: "Fetch a single flow including its request, response, error and metadata.",
+        admin: false,
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {"id": {"type": "string"}},
+                "required": ["id"]
+            })
+        },
+    },
+    ToolSpec {
+        name: "credential_health",
+        description: "List pool credentials with health status and quota cooldowns.",
+        admin: false,
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {
+                    "provider_type": {"type": "string", "description": "Only return credentials of this provider type"}
+                }
+            })
+        },
+    },
+    ToolSpec {
+        name: "usage_stats",
+        description:
+            "Request statistics (totals, latency, tokens) overall, per provider and per model.",
+        admin: false,
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {
+                    "hours": {"type": "integer", "minimum": 1, "maximum": MAX_STATS_HOURS, "default": DEFAULT_STATS_HOURS}
+                }
+            })
+        },
+    },
+    ToolSpec {
+        name: "get_default_provider",
+        description: "Return the provider used when a request does not select one.",
+        admin: false,
+        input_schema: || json!({"type": "object", "properties": {}}),
+    },
+    ToolSpec {
+        name: "set_default_provider",
+        description:
+            "Switch the default provider of the running proxy (not persisted to the config file).",
+        admin: true,
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {"provider": {"type": "string"}},
+                "required": ["provider"]
+            })
+        },
+    },
+    ToolSpec {
+        name: "reset_credential",
+        description: "Mark a credential healthy again and clear its quota cooldown.",
+        admin: true,
+        input_schema: || {
+            json!({
+                "type": "object",
+                "properties": {"uuid": {"type": "string"}},
+                "required": ["uuid"]
+            })
+        },
+    },
+];
+
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn tool_result(value: Value) -> Value {
+    let text = serde_json::to_string_pretty(&value).unwrap_or_default();
+    json!({
+        "content": [{"type": "text", "text": text}],
+        "structuredContent": value,
+        "isError": false
+    })
+}
+
+fn tool_error(message: &str) -> Value {
+    json!({
+        "content": [{"type": "text", "text": message}],
+        "isError": true
+    })
+}
+
+fn required_str<'a>(args: &'a Value, key: &str) -> Result<&'a str, String> {
+    args[key]
+        .as_str()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .ok_or_else(|| format!("missing required argument '{}'", key))
+}
+
+/// 内置 MCP 服务器
+pub struct ProxyCastMcpServer {
+    flow_query: Option<Arc<FlowQueryService>>,
+    pool_service: Arc<ProviderPoolService>,
+    db: Option<DbConnection>,
+    quota: Arc<QuotaManager>,
+    stats: Arc<ParkingLotRwLock<StatsAggregator>>,
+    default_provider: Arc<RwLock<String>>,
+    router: Arc<RwLock<Router>>,
+}
+
+impl ProxyCastMcpServer {
+    /// 创建内置 MCP 服务器
+    ///
+    /// `flow_query` 为空时（Flow 未持久化）Flow 相关工具返回错误。
+    pub fn new(
+        flow_query: Option<Arc<FlowQueryService>>,
+        pool_service: Arc<ProviderPoolService>,
+        db: Option<DbConnection>,
+        quota: Arc<QuotaManager>,
+        stats: Arc<ParkingLotRwLock<StatsAggregator>>,
+        default_provider: Arc<RwLock<String>>,
+        router: Arc<RwLock<Router>>,
+    ) -> Self {
+        Self {
+            flow_query,
+            pool_service,
+            db,
+            quota,
+            stats,
+            default_provider,
+            router,
+        }
+    }
+
+    /// 构造 initialize 结果
+    pub fn initialize_result(&self, requested_version: Option<&str>) -> Value {
+        let version = match requested_version {
+            Some(v @ ("2024-11-05" | "2025-03-26" | "2025-06-18")) => v,
+            _ => PROTOCOL_VERSION,
+        };
+        json!({
+            "protocolVersion": version,
+            "capabilities": {"tools": {}},
+            "serverInfo": {
+                "name": "proxycast",
+                "version": env!("CARGO_PKG_VERSION")
+            }
+        })
+    }
+
+    /// 当前作用域可见的工具
+    pub fn tools(&self, scope: McpScope) -> Vec<Value> {
+        TOOLS
+            .iter()
+            .filter(|tool| scope.allows(tool))
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "inputSchema": (tool.input_schema)()
+                })
+            })
+            .collect()
+    }
+
+    /// 处理单条 JSON-RPC 消息
+    ///
+    /// 通知和客户端发来的响应没有返回值。
+    pub async fn handle_message(&self, scope: McpScope, message: &Value) -> Option<Value> {
+        let method = message["method"].as_str()?;
+        let id = message.get("id").cloned()?;
+        let params = message.get("params").cloned().unwrap_or_else(|| json!({}));
+
+        let result = match method {
+            "initialize" => Ok(self.initialize_result(params["protocolVersion"].as_str())),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({"tools": self.tools(scope)})),
+            "tools/call" => self.call_tool(scope, &params).await,
+            _ => Err((-32601, format!("Method not found: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => rpc_error(id, code, &message),
+        })
+    }
+
+    /// 处理单条消息或批量消息，全部为通知时返回 `None`
+    pub async fn handle_payload(&self, scope: McpScope, payload: &Value) -> Option<Value> {
+        match payload {
+            Value::Array(messages) => {
+                let mut responses = Vec::new();
+                for message in messages {
+                    if let Some(response) = self.handle_message(scope, message).await {
+                        responses.push(response);
+                    }
+                }
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            message => self.handle_message(scope, message).await,
+        }
+    }
+
+    async fn call_tool(&self, scope: McpScope, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params["name"]
+            .as_str()
+            .ok_or_else(|| (-32602, "Missing tool name".to_string()))?;
+        let tool = TOOLS
+            .iter()
+            .find(|tool| tool.name == name)
+            .ok_or_else(|| (-32602, format!("Unknown tool: {}", name)))?;
+        if !scope.allows(tool) {
+            return Ok(tool_error(&format!(
+                "tool '{}' requires the admin scope (management API key)",
+                name
+            )));
+        }
+
+        let args = params
+            .get("arguments")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        let result = match name {
+            "query_flows" => self.query_flows(&args).await,
+            "get_flow" => self.get_flow(&args).await,
+            "credential_health" => self.credential_health(&args),
+            "usage_stats" => Ok(self.usage_stats(&args)),
+            "get_default_provider" => Ok(json!({
+                "default_provider": self.default_provider.read().await.clone()
+            })),
+            "set_default_provider" => self.set_default_provider(&args).await,
+            "reset_credential" => self.reset_credential(&args),
+            _ => unreachable!("tool listed in TOOLS without handler"),
+        };
+
+        Ok(match result {
+            Ok(value) => tool_result(value),
+            Err(message) => tool_error(&message),
+        })
+    }
+
+    fn flow_query(&self) -> Result<&FlowQueryService, String> {
+        self.flow_query
+            .as_deref()
+            .ok_or_else(|| "flow storage is not available".to_string())
+    }
+
+    fn db(&self) -> Result<&DbConnection, String> {
+        self.db
+            .as_ref()
+            .ok_or_else(|| "database is not available".to_string())
+    }
+
+    async fn query_flows(&self, args: &Value) -> Result<Value, String> {
+        let flow_query = self.flow_query()?;
+        let filter = args["filter"].as_str().unwrap_or("").trim();
+        let page = args["page"].as_u64().unwrap_or(1).max(1) as usize;
+        let page_size = args["page_size"]
+            .as_u64()
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE) as usize;
+
+        let result = if filter.is_empty() {
+            flow_query
+                .query(
+                    FlowFilter::default(),
+                    FlowSortBy::CreatedAt,
+                    true,
+                    page,
+                    page_size,
+                )
+                .await
+                .map_err(|e| e.to_string())?
+        } else {
+            flow_query
+                .query_with_expression(filter, FlowSortBy::CreatedAt, true, page, page_size)
+                .await
+                .map_err(|e| e.to_string())?
+        };
+
+        let flows: Vec<FlowSummary> = result.flows.iter().map(FlowSummary::from).collect();
+        Ok(json!({
+            "total": result.total,
+            "page": result.page,
+            "page_size": result.page_size,
+            "has_next": result.has_next,
+            "flows": flows
+        }))
+    }
+
+    async fn get_flow(&self, args: &Value) -> Result<Value, String> {
+        let id = required_str(args, "id")?;
+        match self
+            .flow_query()?
+            .get_flow(id)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            Some(flow) => Ok(json!({ "flow": flow })),
+            None => Err(format!("flow '{}' not found", id)),
+        }
+    }
+
+    fn credential_health(&self, args: &Value) -> Result<Value, String> {
+        let provider_type = args["provider_type"].as_str().map(str::trim);
+        let credentials: Vec<Value> = self
+            .pool_service
+            .get_all_credential_health(self.db()?)?
+            .into_iter()
+            .filter(|info| provider_type.is_none_or(|p| info.provider_type.eq_ignore_ascii_case(p)))
+            .map(|info| {
+                let mut value = serde_json::to_value(&info).unwrap_or_default();
+                // 只展示仍在冷却中的配额记录
+                if let (Some(record), Some(remaining)) = (
+                    self.quota.get_record(&info.uuid),
+                    self.quota.remaining_cooldown_seconds(&info.uuid),
+                ) {
+                    value["quota"] = json!({
+                        "exceeded_at": record.exceeded_at,
+                        "cooldown_until": record.cooldown_until,
+                        "remaining_cooldown_seconds": remaining,
+                        "reason": record.reason
+                    });
+                }
+                value
+            })
+            .collect();
+
+        Ok(json!({
+            "credentials": credentials,
+            "quota_exceeded_count": self.quota.exceeded_count(),
+            "earliest_recovery": self.quota.earliest_recovery()
+        }))
+    }
+
+    fn usage_stats(&self, args: &Value) -> Value {
+        let hours = args["hours"]
+            .as_i64()
+            .unwrap_or(DEFAULT_STATS_HOURS)
+            .clamp(1, MAX_STATS_HOURS);
+        let range = TimeRange::last_hours(hours);
+        let stats = self.stats.read();
+        json!({
+            "hours": hours,
+            "summary": stats.summary(Some(range)),
+            "by_provider": stats.by_provider(Some(range)),
+            "by_model": stats.by_model(Some(range))
+        })
+    }
+
+    async fn set_default_provider(&self, args: &Value) -> Result<Value, String> {
+        let provider = required_str(args, "provider")?;
+        let provider_type: ProviderType = provider
+            .parse()
+            .map_err(|_| format!("invalid provider type: {}", provider))?;
+
+        let previous = {
+            let mut current = self.default_provider.write().await;
+            std::mem::replace(&mut *current, provider.to_string())
+        };
+        self.router
+            .write()
+            .await
+            .set_default_provider(provider_type);
+        tracing::info!(
+            "[MCP_SERVER] 默认 Provider 已切换: {} -> {}",
+            previous,
+            provider
+        );
+
+        Ok(json!({"default_provider": provider, "previous": previous}))
+    }
+
+    fn reset_credential(&self, args: &Value) -> Result<Value, String> {
+        let uuid = required_str(args, "uuid")?;
+        let db = self.db()?;
+        if self.pool_service.get_credential_health(db, uuid)?.is_none() {
+            return Err(format!("credential '{}' not found", uuid));
+        }
+        self.pool_service.mark_healthy(db, uuid, None)?;
+        let quota_restored = self.quota.restore_credential(uuid);
+        // 冷却解除后让排队中的请求重新选择凭证
+        self.pool_service.scheduler().wake_all();
+        tracing::info!(
+            "[MCP_SERVER] 凭证已重置: {} (quota_restored={})",
+            uuid,
+            quota_restored
+        );
+
+        Ok(json!({"uuid": uuid, "healthy": true, "quota_restored": quota_restored}))
+    }
+}
+
+/// 通过 stdio 提供服务（每行一条 JSON-RPC 消息），直到标准输入关闭
+pub async fn serve_stdio(server: Arc<ProxyCastMcpServer>, scope: McpScope) -> std::io::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(line) {
+            Ok(payload) => server.handle_payload(scope, &payload).await,
+            Err(e) => Some(rpc_error(
+                Value::Null,
+                -32700,
+                &format!("Parse error: {}", e),
+            )),
+        };
+        if let Some(response) = response {
+            let mut out = serde_json::to_vec(&response)?;
+            out.push(b'\n');
+            stdout.write_all(&out).await?;
+            stdout.flush().await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> ProxyCastMcpServer {
+        ProxyCastMcpServer::new(
+            None,
+            Arc::new(ProviderPoolService::new()),
+            None,
+            Arc::new(QuotaManager::with_defaults()),
+            Arc::new(ParkingLotRwLock::new(StatsAggregator::with_defaults())),
+            Arc::new(RwLock::new("kiro".to_string())),
+            Arc::new(RwLock::new(Router::new_empty())),
+        )
+    }
+
+    fn call(name: &str, arguments: Value) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": {"name": name, "arguments": arguments}
+        })
+    }
+
+    #[test]
+    fn test_scope_from_management_key() {
+        let mut config = RemoteManagementConfig::default();
+        assert_eq!(
+            McpScope::from_management_key(&config, Some("")),
+            McpScope::ReadOnly
+        );
+
+        config.secret_key = Some("secret".to_string());
+        assert_eq!(
+            McpScope::from_management_key(&config, Some("secret")),
+            McpScope::Admin
+        );
+        assert_eq!(
+            McpScope::from_management_key(&config, Some("wrong")),
+            McpScope::ReadOnly
+        );
+        assert_eq!(
+            McpScope::from_management_key(&config, None),
+            McpScope::ReadOnly
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tools_list_respects_scope() {
+        let server = server();
+        let list = json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"});
+
+        let read_only = server
+            .handle_message(McpScope::ReadOnly, &list)
+            .await
+            .unwrap();
+        let names: Vec<&str> = read_only["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect();
+        assert!(names.contains(&"query_flows"));
+        assert!(!names.contains(&"set_default_provider"));
+
+        let admin = server.handle_message(McpScope::Admin, &list).await.unwrap();
+        assert_eq!(
+            admin["result"]["tools"].as_array().unwrap().len(),
+            TOOLS.len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_admin_tool_requires_admin_scope() {
+        let server = server();
+        let message = call("set_default_provider", json!({"provider": "gemini"}));
+
+        let denied = server
+            .handle_message(McpScope::ReadOnly, &message)
+            .await
+            .unwrap();
+        assert_eq!(denied["result"]["isError"], true);
+        assert_eq!(*server.default_provider.read().await, "kiro");
+
+        let allowed = server
+            .handle_message(McpScope::Admin, &message)
+            .await
+            .unwrap();
+        assert_eq!(allowed["result"]["isError"], false);
+        assert_eq!(
+            allowed["result"]["structuredContent"]["previous"],
+            json!("kiro")
+        );
+        assert_eq!(*server.default_provider.read().await, "gemini");
+        assert_eq!(
+            server.router.read().await.default_provider(),
+            Some(ProviderType::Gemini)
+        );
+
+        let invalid = server
+            .handle_message(
+                McpScope::Admin,
+                &call("set_default_provider", json!({"provider": "nope"})),
+            )
+            .await
+            .unwrap();
+        assert_eq!(invalid["result"]["isError"], true);
+    }
+
+    #[tokio::test]
+    async fn test_notifications_and_unknown_methods() {
+        let server = server();
+        let batch = json!([
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "id": 2, "method": "resources/list"},
+            {"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "missing"}}
+        ]);
+        let responses = server
+            .handle_payload(McpScope::ReadOnly, &batch)
+            .await
+            .unwrap();
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["error"]["code"], -32601);
+        assert_eq!(responses[1]["error"]["code"], -32602);
+
+        let notification = json!({"jsonrpc": "2.0", "method": "notifications/initialized"});
+        assert!(server
+            .handle_payload(McpScope::ReadOnly, &notification)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_usage_stats_and_missing_flow_store() {
+        let server = server();
+        let stats = server
+            .handle_message(
+                McpScope::ReadOnly,
+                &call("usage_stats", json!({"hours": 1})),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            stats["result"]["structuredContent"]["summary"]["total_requests"],
+            0
+        );
+
+        let flows = server
+            .handle_message(McpScope::ReadOnly, &call("query_flows", json!({})))
+            .await
+            .unwrap();
+        assert_eq!(flows["result"]["isError"], true);
+    }
+}
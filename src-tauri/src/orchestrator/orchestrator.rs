@@ -5,8 +5,11 @@
 use super::fallback::{FallbackHandler, FallbackPolicy};
 use super::pool_builder::{CredentialInfo, DynamicPoolBuilder};
 use super::selector::{ModelSelector, SelectionResult};
-use super::strategies::create_default_registry;
-use super::strategy::{SelectionContext, StrategyInfo, StrategyResult, TaskHint};
+use super::strategies::{
+    create_default_registry, AdaptiveConfig, AdaptiveDecision, AdaptiveStrategy,
+    ADAPTIVE_STRATEGY_ID,
+};
+use super::strategy::{SelectionContext, StrategyError, StrategyInfo, StrategyResult, TaskHint};
 use super::tier::{AvailableModel, ServiceTier, TierPool};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -26,6 +29,9 @@ pub struct OrchestratorConfig {
     pub load_balancing: bool,
     /// 模型池刷新间隔（秒）
     pub pool_refresh_interval: u64,
+    /// 自适应策略配置
+    #[serde(default)]
+    pub adaptive: AdaptiveConfig,
 }
 
 impl Default for OrchestratorConfig {
@@ -36,6 +42,7 @@ impl Default for OrchestratorConfig {
             fallback_policy: FallbackPolicy::NextTier,
             load_balancing: true,
             pool_refresh_interval: 60,
+            adaptive: AdaptiveConfig::default(),
         }
     }
 }
@@ -55,26 +62,21 @@ pub struct ModelOrchestrator {
     fallback_handler: FallbackHandler,
     /// 当前凭证列表
     credentials: RwLock<Vec<CredentialInfo>>,
+    /// 自适应策略（与注册表中的实例共享）
+    adaptive: Arc<AdaptiveStrategy>,
 }
 
 impl ModelOrchestrator {
     /// 创建新的编排器
     pub fn new() -> Self {
-        let registry = create_default_registry();
-        let config = OrchestratorConfig::default();
-
-        Self {
-            fallback_handler: FallbackHandler::new(config.fallback_policy),
-            config: RwLock::new(config),
-            selector: ModelSelector::new(registry),
-            pool_builder: DynamicPoolBuilder::new(),
-            credentials: RwLock::new(Vec::new()),
-        }
+        Self::with_config(OrchestratorConfig::default())
     }
 
     /// 使用自定义配置创建
     pub fn with_config(config: OrchestratorConfig) -> Self {
-        let registry = create_default_registry();
+        let mut registry = create_default_registry();
+        let adaptive = Arc::new(AdaptiveStrategy::with_config(config.adaptive.clone()));
+        registry.register(adaptive.clone());
 
         Self {
             fallback_handler: FallbackHandler::new(config.fallback_policy),
@@ -82,16 +84,23 @@ impl ModelOrchestrator {
             selector: ModelSelector::new(registry),
             pool_builder: DynamicPoolBuilder::new(),
             credentials: RwLock::new(Vec::new()),
+            adaptive,
         }
     }
 
     /// 更新配置
     pub async fn update_config(&self, config: OrchestratorConfig) {
+        self.adaptive.set_config(config.adaptive.clone());
         let mut current = self.config.write().await;
         *current = config;
         info!("编排器配置已更新");
     }
 
+    /// 获取自适应策略
+    pub fn adaptive(&self) -> &Arc<AdaptiveStrategy> {
+        &self.adaptive
+    }
+
     /// 获取配置
     pub async fn get_config(&self) -> OrchestratorConfig {
         self.config.read().await.clone()
@@ -114,6 +123,10 @@ impl ModelOrchestrator {
         // 更新选择器的模型池
         self.selector.update_pool(pool).await;
 
+        // 丢弃已不存在的凭证的实时观测值
+        self.adaptive
+            .retain_credentials(credentials.iter().map(|c| c.id.as_str()));
+
         // 保存凭证列表
         let mut creds = self.credentials.write().await;
         *creds = credentials;
@@ -141,12 +154,25 @@ impl ModelOrchestrator {
         drop(creds);
 
         self.selector.update_pool(pool).await;
+        self.adaptive.forget_credential(credential_id);
     }
 
     /// 选择模型
     pub async fn select(&self, ctx: &SelectionContext) -> StrategyResult<SelectionResult> {
         debug!("选择模型: 等级={}, 任务={:?}", ctx.tier, ctx.task_hint);
 
+        // 启用自适应策略的等级优先使用实时遥测选择，本等级无可用模型时按原逻辑降级
+        if self.adaptive.is_enabled_for(ctx.tier) {
+            match self
+                .selector
+                .select_with_strategy(ADAPTIVE_STRATEGY_ID, ctx)
+                .await
+            {
+                Err(StrategyError::NoAvailableModels) => {}
+                result => return result,
+            }
+        }
+
         self.selector.select(ctx).await
     }
 
@@ -178,6 +204,12 @@ impl ModelOrchestrator {
         self.select(&ctx).await
     }
 
+    /// 解释自适应策略在当前模型池上的选择（不探索、不记录）
+    pub async fn explain_adaptive(&self, ctx: &SelectionContext) -> AdaptiveDecision {
+        let pool = self.selector.get_pool().await;
+        self.adaptive.explain(pool.get(ctx.tier), ctx)
+    }
+
     /// 获取当前模型池
     pub async fn get_pool(&self) -> TierPool {
         self.selector.get_pool().await
@@ -370,4 +402,20 @@ mod tests {
             .await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_adaptive_strategy_registered_once() {
+        let orchestrator = ModelOrchestrator::new();
+
+        // 注册表中的自适应策略即编排器接收反馈的实例
+        let registry = orchestrator.selector.get_registry().await;
+        let registered = registry.read().await.get(ADAPTIVE_STRATEGY_ID).unwrap();
+        assert!(std::ptr::eq(
+            Arc::as_ptr(&registered) as *const (),
+            Arc::as_ptr(orchestrator.adaptive()) as *const (),
+        ));
+        assert!(create_default_registry()
+            .get(ADAPTIVE_STRATEGY_ID)
+            .is_none());
+    }
 }
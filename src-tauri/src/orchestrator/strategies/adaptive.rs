@@ -0,0 +1,853 @@
+//! 自适应策略
+//!
+//! 基于代理实际观测到的遥测数据选择模型和凭证，而不是 `ModelMetadata` 中的静态数据：
+//! - `StatsAggregator`: 滚动窗口内每个 (凭证, 模型) 的错误率、延迟和输出吞吐
+//! - 上游响应: 调用成功率
+//! - 响应体的 `StreamMetrics`: 首字节时间 (TTFB) 和流式吞吐
+//! - `CooldownSource`（如 `RiskController`）: 冷却中的凭证不参与选择
+//!
+//! 为了让冷却结束后恢复的凭证重新获得流量，支持两种探索方式：
+//! - ε-greedy: 以概率 ε 选择样本最少的候选
+//! - UCB: 得分加上 `c * sqrt(ln(N) / n)` 的置信上界奖励
+//!
+//! 每次选择都会记录一条 `AdaptiveDecision`，用于解释选择原因。
+
+use crate::credential::CooldownSource;
+use crate::orchestrator::strategy::{
+    ModelSelection, SelectionContext, SelectionStrategy, StrategyError, StrategyResult,
+};
+use crate::orchestrator::tier::{AvailableModel, ServiceTier};
+use crate::streaming::StreamMetrics;
+use crate::telemetry::{StatsAggregator, StatsSummary, TimeRange};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+
+/// 自适应策略 ID
+pub const ADAPTIVE_STRATEGY_ID: &str = "adaptive";
+
+/// 实时观测值的指数加权系数
+const EWMA_ALPHA: f64 = 0.3;
+
+/// 流式字节数换算 token 数的近似系数
+const BYTES_PER_TOKEN: f64 = 4.0;
+
+/// 实时观测表的容量上限，写入新条目时超出则先清理窗口外的条目，
+/// 仍超出时淘汰最久未观测的条目
+const MAX_LIVE_ARMS: usize = 1024;
+
+/// 探索方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExplorationMode {
+    /// ε-greedy
+    #[default]
+    EpsilonGreedy,
+    /// 置信上界 (UCB1)
+    Ucb,
+}
+
+/// 自适应策略配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveConfig {
+    /// 使用自适应策略的服务等级（为空时不替换各等级的默认策略）
+    pub tiers: Vec<ServiceTier>,
+    /// 探索方式
+    pub exploration: ExplorationMode,
+    /// ε-greedy 的探索概率 (0.0 - 1.0)
+    pub epsilon: f64,
+    /// UCB 探索系数
+    pub ucb_c: f64,
+    /// 遥测滚动窗口（秒）
+    pub window_secs: u64,
+    /// 错误率权重
+    pub error_weight: f64,
+    /// TTFB 权重
+    pub ttfb_weight: f64,
+    /// 吞吐权重
+    pub throughput_weight: f64,
+    /// TTFB 参考值（毫秒），TTFB 等于该值时得分为 0.5
+    pub ttfb_reference_ms: f64,
+    /// 吞吐参考值（tokens/s），吞吐等于该值时得分为 0.5
+    pub throughput_reference_tps: f64,
+    /// 保留的决策记录数
+    pub max_decisions: usize,
+}
+
+impl Default for AdaptiveConfig {
+    fn default() -> Self {
+        Self {
+            tiers: Vec::new(),
+            exploration: ExplorationMode::EpsilonGreedy,
+            epsilon: 0.1,
+            ucb_c: 0.5,
+            window_secs: 600,
+            error_weight: 0.5,
+            ttfb_weight: 0.3,
+            throughput_weight: 0.2,
+            ttfb_reference_ms: 2000.0,
+            throughput_reference_tps: 50.0,
+            max_decisions: 100,
+        }
+    }
+}
+
+/// 单个候选 (模型, 凭证) 的评分
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArmScore {
+    /// 模型 ID
+    pub model_id: String,
+    /// 凭证 ID
+    pub credential_id: String,
+    /// Provider 类型
+    pub provider_type: String,
+    /// 窗口内样本数
+    pub samples: u64,
+    /// 错误率（无样本时为 None）
+    pub error_rate: Option<f64>,
+    /// TTFB（毫秒）
+    pub ttfb_ms: Option<f64>,
+    /// 输出吞吐（tokens/s）
+    pub throughput_tps: Option<f64>,
+    /// 是否处于冷却期
+    pub cooling: bool,
+    /// 利用得分 (0.0 - 1.0)
+    pub score: f64,
+    /// 探索奖励（仅 UCB）
+    pub bonus: f64,
+    /// 排除原因（为 None 表示参与选择）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub excluded: Option<String>,
+}
+
+impl ArmScore {
+    /// 最终得分
+    pub fn total(&self) -> f64 {
+        self.score + self.bonus
+    }
+
+    fn is_eligible(&self) -> bool {
+        self.excluded.is_none()
+    }
+}
+
+/// 一次自适应选择的解释
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdaptiveDecision {
+    /// 决策时间
+    pub timestamp: DateTime<Utc>,
+    /// 服务等级
+    pub tier: ServiceTier,
+    /// 探索方式
+    pub mode: ExplorationMode,
+    /// 是否为探索选择
+    pub explored: bool,
+    /// 选中的模型
+    pub selected_model: Option<String>,
+    /// 选中的凭证
+    pub selected_credential: Option<String>,
+    /// 选择原因
+    pub reason: String,
+    /// 所有候选的评分
+    pub candidates: Vec<ArmScore>,
+}
+
+/// 实时观测值
+#[derive(Debug, Clone, Default)]
+struct LiveArm {
+    ttfb_ms: Option<f64>,
+    success_rate: Option<f64>,
+    throughput_tps: Option<f64>,
+    samples: u64,
+    last_seen: Option<DateTime<Utc>>,
+}
+
+impl LiveArm {
+    /// 最近一次观测是否在窗口内
+    fn is_fresh(&self, window_secs: u64) -> bool {
+        self.last_seen
+            .is_some_and(|seen| Utc::now() - seen <= chrono::Duration::seconds(window_secs as i64))
+    }
+}
+
+/// 取出（必要时创建）指定凭证和模型的实时观测值
+fn live_arm<'a>(
+    live: &'a mut HashMap<(String, String), LiveArm>,
+    credential_id: &str,
+    model: &str,
+    window_secs: u64,
+) -> &'a mut LiveArm {
+    let key = (credential_id.to_string(), model.to_string());
+    if !live.contains_key(&key) && live.len() >= MAX_LIVE_ARMS {
+        live.retain(|_, arm| arm.is_fresh(window_secs));
+        while live.len() >= MAX_LIVE_ARMS {
+            let Some(oldest) = live
+                .iter()
+                .min_by_key(|(_, arm)| arm.last_seen)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            live.remove(&oldest);
+        }
+    }
+    live.entry(key).or_default()
+}
+
+fn ewma(current: Option<f64>, value: f64) -> Option<f64> {
+    Some(match current {
+        Some(current) => current + EWMA_ALPHA * (value - current),
+        None => value,
+    })
+}
+
+/// 自适应策略
+pub struct AdaptiveStrategy {
+    config: RwLock<AdaptiveConfig>,
+    live: RwLock<HashMap<(String, String), LiveArm>>,
+    stats: RwLock<Option<Arc<RwLock<StatsAggregator>>>>,
+    cooldown_sources: RwLock<Vec<Arc<dyn CooldownSource>>>,
+    decisions: Mutex<VecDeque<AdaptiveDecision>>,
+}
+
+impl AdaptiveStrategy {
+    /// 创建新的自适应策略
+    pub fn new() -> Self {
+        Self::with_config(AdaptiveConfig::default())
+    }
+
+    /// 使用自定义配置创建
+    pub fn with_config(config: AdaptiveConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            live: RwLock::new(HashMap::new()),
+            stats: RwLock::new(None),
+            cooldown_sources: RwLock::new(Vec::new()),
+            decisions: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// 获取配置
+    pub fn config(&self) -> AdaptiveConfig {
+        self.config.read().clone()
+    }
+
+    /// 更新配置
+    pub fn set_config(&self, config: AdaptiveConfig) {
+        *self.config.write() = config;
+    }
+
+    /// 关联统计聚合器
+    pub fn attach_stats(&self, stats: Arc<RwLock<StatsAggregator>>) {
+        *self.stats.write() = Some(stats);
+    }
+
+    /// 注册冷却状态来源
+    pub fn register_cooldown_source(&self, source: Arc<dyn CooldownSource>) {
+        self.cooldown_sources.write().push(source);
+    }
+
+    /// 是否对指定等级启用
+    pub fn is_enabled_for(&self, tier: ServiceTier) -> bool {
+        self.config.read().tiers.contains(&tier)
+    }
+
+    /// 记录上游响应是否成功
+    ///
+    /// 响应头到达时响应体可能尚未开始发送，TTFB 和吞吐由响应体结束时的
+    /// `observe_stream` 记录
+    pub fn observe_response(&self, credential_id: &str, model: &str, success: bool) {
+        let window_secs = self.config.read().window_secs;
+        let mut live = self.live.write();
+        let arm = live_arm(&mut live, credential_id, model, window_secs);
+        arm.success_rate = ewma(arm.success_rate, if success { 1.0 } else { 0.0 });
+        arm.samples += 1;
+        arm.last_seen = Some(Utc::now());
+    }
+
+    /// 记录流式指标（TTFB 和流式吞吐）
+    pub fn observe_stream(&self, credential_id: &str, model: &str, metrics: &StreamMetrics) {
+        let window_secs = self.config.read().window_secs;
+        let mut live = self.live.write();
+        let arm = live_arm(&mut live, credential_id, model, window_secs);
+        if let Some(ttfb) = metrics.ttfb_ms {
+            arm.ttfb_ms = ewma(arm.ttfb_ms, ttfb as f64);
+        }
+        // 没有收到数据的响应体不计入吞吐
+        if let Some(bps) = metrics
+            .throughput_bytes_per_sec()
+            .filter(|_| metrics.total_bytes > 0)
+        {
+            arm.throughput_tps = ewma(arm.throughput_tps, bps / BYTES_PER_TOKEN);
+        }
+        arm.last_seen = Some(Utc::now());
+    }
+
+    /// 清除指定凭证的实时观测值
+    pub fn forget_credential(&self, credential_id: &str) {
+        self.live.write().retain(|(id, _), _| id != credential_id);
+    }
+
+    /// 只保留仍在凭证列表中的实时观测值
+    pub fn retain_credentials<'a>(&self, credential_ids: impl IntoIterator<Item = &'a str>) {
+        let keep: HashSet<&str> = credential_ids.into_iter().collect();
+        self.live
+            .write()
+            .retain(|(id, _), _| keep.contains(id.as_str()));
+    }
+
+    /// 最近的决策记录（最新的在前）
+    pub fn recent_decisions(&self, limit: usize) -> Vec<AdaptiveDecision> {
+        self.decisions
+            .lock()
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// 解释当前的选择结果（不探索、不记录）
+    pub fn explain(&self, pool: &[AvailableModel], ctx: &SelectionContext) -> AdaptiveDecision {
+        let config = self.config();
+        let candidates = self.score_candidates(pool, ctx, &config);
+        let best = best_index(&candidates, ArmScore::total);
+        build_decision(ctx.tier, &config, candidates, best, false)
+    }
+
+    /// 为所有候选计算评分
+    fn score_candidates(
+        &self,
+        pool: &[AvailableModel],
+        ctx: &SelectionContext,
+        config: &AdaptiveConfig,
+    ) -> Vec<ArmScore> {
+        let windowed = self.windowed_stats(config.window_secs);
+        let live = self.live.read();
+        let sources = self.cooldown_sources.read();
+
+        let mut candidates: Vec<ArmScore> = pool
+            .iter()
+            .map(|model| {
+                let key = (model.credential_id.clone(), model.id.clone());
+                let summary = windowed.get(&key);
+                let arm = live.get(&key).filter(|a| a.is_fresh(config.window_secs));
+                let cooling = sources.iter().any(|s| s.is_cooling(&model.credential_id));
+
+                let samples = summary
+                    .map(|s| s.total_requests)
+                    .unwrap_or(0)
+                    .max(arm.map(|a| a.samples).unwrap_or(0));
+                let error_rate = match summary.filter(|s| s.total_requests > 0) {
+                    Some(s) => Some(1.0 - s.success_rate),
+                    None => arm.and_then(|a| a.success_rate).map(|r| 1.0 - r),
+                };
+                let ttfb_ms = arm.and_then(|a| a.ttfb_ms).or_else(|| {
+                    summary
+                        .map(|s| s.avg_latency_ms)
+                        .filter(|latency| *latency > 0.0)
+                });
+                let throughput_tps = summary
+                    .and_then(summary_throughput)
+                    .or_else(|| arm.and_then(|a| a.throughput_tps));
+
+                let excluded = if !model.is_healthy {
+                    Some("unhealthy".to_string())
+                } else if cooling {
+                    Some("cooling down".to_string())
+                } else if ctx.excluded_models.contains(&model.id) {
+                    Some("excluded by request".to_string())
+                } else if ctx.requires_vision && !model.supports_vision {
+                    Some("vision not supported".to_string())
+                } else if ctx.requires_tools && !model.supports_tools {
+                    Some("tools not supported".to_string())
+                } else {
+                    None
+                };
+
+                ArmScore {
+                    model_id: model.id.clone(),
+                    credential_id: model.credential_id.clone(),
+                    provider_type: model.provider_type.clone(),
+                    samples,
+                    error_rate,
+                    ttfb_ms,
+                    throughput_tps,
+                    cooling,
+                    score: exploit_score(config, error_rate, ttfb_ms, throughput_tps),
+                    bonus: 0.0,
+                    excluded,
+                }
+            })
+            .collect();
+
+        if config.exploration == ExplorationMode::Ucb {
+            let total_samples: u64 = candidates
+                .iter()
+                .filter(|c| c.is_eligible())
+                .map(|c| c.samples)
+                .sum();
+            let ln_total = ((total_samples + 1) as f64).ln();
+            for candidate in candidates.iter_mut().filter(|c| c.is_eligible()) {
+                candidate.bonus = config.ucb_c * (ln_total / (candidate.samples + 1) as f64).sqrt();
+            }
+        }
+
+        candidates
+    }
+
+    /// 滚动窗口内按 (凭证, 模型) 分组的统计
+    fn windowed_stats(&self, window_secs: u64) -> HashMap<(String, String), StatsSummary> {
+        let Some(stats) = self.stats.read().clone() else {
+            return HashMap::new();
+        };
+        let end = Utc::now();
+        let start = end - chrono::Duration::seconds(window_secs as i64);
+        let aggregator = stats.read();
+        aggregator.by_credential_and_model(Some(TimeRange::new(start, end)))
+    }
+
+    /// 执行选择并记录决策
+    fn decide(&self, pool: &[AvailableModel], ctx: &SelectionContext) -> AdaptiveDecision {
+        let config = self.config();
+        let candidates = self.score_candidates(pool, ctx, &config);
+
+        let best = best_index(&candidates, ArmScore::total);
+        let explore = match (config.exploration, best) {
+            (ExplorationMode::EpsilonGreedy, Some(best)) => {
+                if rand::random::<f64>() < config.epsilon {
+                    least_sampled_index(&candidates, best)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        // UCB 的探索体现在奖励项上：选中的不是利用得分最高的候选即为探索
+        let explored = match config.exploration {
+            ExplorationMode::EpsilonGreedy => explore.is_some(),
+            ExplorationMode::Ucb => best.is_some() && best != best_index(&candidates, |c| c.score),
+        };
+
+        let decision = build_decision(ctx.tier, &config, candidates, explore.or(best), explored);
+
+        let mut decisions = self.decisions.lock();
+        decisions.push_back(decision.clone());
+        while decisions.len() > config.max_decisions.max(1) {
+            decisions.pop_front();
+        }
+
+        decision
+    }
+}
+
+impl Default for AdaptiveStrategy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 根据窗口统计估算输出吞吐（tokens/s）
+fn summary_throughput(summary: &StatsSummary) -> Option<f64> {
+    let total_ms = summary.avg_latency_ms * summary.total_requests as f64;
+    if summary.total_output_tokens == 0 || total_ms <= 0.0 {
+        return None;
+    }
+    Some(summary.total_output_tokens as f64 * 1000.0 / total_ms)
+}
+
+/// 计算利用得分
+///
+/// 缺少数据的维度按中性值计算，没有错误率样本时视为无错误，
+/// 使新的候选有机会获得流量
+fn exploit_score(
+    config: &AdaptiveConfig,
+    error_rate: Option<f64>,
+    ttfb_ms: Option<f64>,
+    throughput_tps: Option<f64>,
+) -> f64 {
+    let success = 1.0 - error_rate.unwrap_or(0.0).clamp(0.0, 1.0);
+    let latency = ttfb_ms
+        .map(|t| config.ttfb_reference_ms / (config.ttfb_reference_ms + t.max(0.0)))
+        .unwrap_or(0.5);
+    let throughput = throughput_tps
+        .map(|t| t / (t + config.throughput_reference_tps))
+        .unwrap_or(0.5);
+
+    let weight_sum = config.error_weight + config.ttfb_weight + config.throughput_weight;
+    if weight_sum <= 0.0 {
+        return success;
+    }
+    (config.error_weight * success
+        + config.ttfb_weight * latency
+        + config.throughput_weight * throughput)
+        / weight_sum
+}
+
+/// 参与选择的候选中得分最高的下标（得分相同时取靠前的）
+fn best_index(candidates: &[ArmScore], key: impl Fn(&ArmScore) -> f64) -> Option<usize> {
+    candidates
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.is_eligible())
+        .fold(None, |best: Option<(usize, f64)>, (i, c)| {
+            let value = key(c);
+            match best {
+                Some((_, best_value)) if best_value >= value => best,
+                _ => Some((i, value)),
+            }
+        })
+        .map(|(i, _)| i)
+}
+
+/// 除最优候选外样本最少的候选
+fn least_sampled_index(candidates: &[ArmScore], best: usize) -> Option<usize> {
+    candidates
+        .iter()
+        .enumerate()
+        .filter(|(i, c)| *i != best && c.is_eligible())
+        .min_by_key(|(_, c)| c.samples)
+        .map(|(i, _)| i)
+}
+
+fn build_decision(
+    tier: ServiceTier,
+    config: &AdaptiveConfig,
+    candidates: Vec<ArmScore>,
+    selected: Option<usize>,
+    explored: bool,
+) -> AdaptiveDecision {
+    let reason = match selected.map(|i| &candidates[i]) {
+        None => "没有可用候选".to_string(),
+        Some(c) if explored => format!(
+            "探索选择 (样本数: {}, 得分: {:.2}, 奖励: {:.2})",
+            c.samples, c.score, c.bonus
+        ),
+        Some(c) => {
+            let mut parts = vec![format!("得分: {:.2}", c.total())];
+            if let Some(rate) = c.error_rate {
+                parts.push(format!("错误率: {:.1}%", rate * 100.0));
+            }
+            if let Some(ttfb) = c.ttfb_ms {
+                parts.push(format!("TTFB: {:.0}ms", ttfb));
+            }
+            if let Some(tps) = c.throughput_tps {
+                parts.push(format!("吞吐: {:.1} tokens/s", tps));
+            }
+            format!("自适应选择 ({})", parts.join(", "))
+        }
+    };
+
+    AdaptiveDecision {
+        timestamp: Utc::now(),
+        tier,
+        mode: config.exploration,
+        explored,
+        selected_model: selected.map(|i| candidates[i].model_id.clone()),
+        selected_credential: selected.map(|i| candidates[i].credential_id.clone()),
+        reason,
+        candidates,
+    }
+}
+
+#[async_trait]
+impl SelectionStrategy for AdaptiveStrategy {
+    fn id(&self) -> &str {
+        ADAPTIVE_STRATEGY_ID
+    }
+
+    fn display_name(&self) -> &str {
+        "自适应"
+    }
+
+    fn description(&self) -> &str {
+        "根据实时错误率、TTFB、吞吐和冷却状态选择模型和凭证，并保留探索流量"
+    }
+
+    async fn select(
+        &self,
+        pool: &[AvailableModel],
+        ctx: &SelectionContext,
+    ) -> StrategyResult<ModelSelection> {
+        let decision = self.decide(pool, ctx);
+        let (Some(model_id), Some(credential_id)) =
+            (&decision.selected_model, &decision.selected_credential)
+        else {
+            return Err(StrategyError::NoAvailableModels);
+        };
+
+        let selected = pool
+            .iter()
+            .find(|m| &m.id == model_id && &m.credential_id == credential_id)
+            .cloned()
+            .ok_or(StrategyError::NoAvailableModels)?;
+
+        let mut ranked: Vec<&ArmScore> = decision
+            .candidates
+            .iter()
+            .filter(|c| {
+                c.is_eligible() && !(&c.model_id == model_id && &c.credential_id == credential_id)
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.total()
+                .partial_cmp(&a.total())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let alternatives = ranked
+            .into_iter()
+            .filter_map(|c| {
+                pool.iter()
+                    .find(|m| m.id == c.model_id && m.credential_id == c.credential_id)
+                    .cloned()
+            })
+            .collect();
+
+        let score = decision
+            .candidates
+            .iter()
+            .find(|c| &c.model_id == model_id && &c.credential_id == credential_id)
+            .map(|c| c.score)
+            .unwrap_or(0.0);
+        let confidence = if decision.explored {
+            50
+        } else {
+            (score.clamp(0.0, 1.0) * 100.0).round() as u8
+        };
+
+        Ok(ModelSelection {
+            model: selected,
+            reason: decision.reason,
+            confidence,
+            alternatives,
+        })
+    }
+
+    fn config_schema(&self) -> serde_json::Value {
+        serde_json::to_value(self.config()).unwrap_or_default()
+    }
+
+    fn update_config(&mut self, config: serde_json::Value) -> StrategyResult<()> {
+        let config: AdaptiveConfig = serde_json::from_value(config)
+            .map_err(|e| StrategyError::ConfigError(e.to_string()))?;
+        self.set_config(config);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CoolingSet(Vec<String>);
+
+    impl CooldownSource for CoolingSet {
+        fn is_cooling(&self, credential_id: &str) -> bool {
+            self.0.iter().any(|id| id == credential_id)
+        }
+
+        fn earliest_recovery(&self) -> Option<DateTime<Utc>> {
+            None
+        }
+    }
+
+    fn model(id: &str, credential_id: &str) -> AvailableModel {
+        AvailableModel {
+            id: id.to_string(),
+            display_name: id.to_string(),
+            provider_type: "test".to_string(),
+            family: None,
+            credential_id: credential_id.to_string(),
+            context_length: None,
+            supports_vision: false,
+            supports_tools: false,
+            input_cost_per_million: None,
+            output_cost_per_million: None,
+            is_healthy: true,
+            current_load: None,
+        }
+    }
+
+    /// 记录一次上游调用，成功时按给定的首字节时间记录流式指标
+    fn observe(strategy: &AdaptiveStrategy, credential_id: &str, ttfb_ms: u64, success: bool) {
+        strategy.observe_response(credential_id, "sonnet", success);
+        if success {
+            let metrics = StreamMetrics {
+                ttfb_ms: Some(ttfb_ms),
+                ..StreamMetrics::new()
+            };
+            strategy.observe_stream(credential_id, "sonnet", &metrics);
+        }
+    }
+
+    fn greedy() -> AdaptiveConfig {
+        AdaptiveConfig {
+            epsilon: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prefers_fast_and_reliable_arm() {
+        let strategy = AdaptiveStrategy::with_config(greedy());
+        let pool = vec![model("sonnet", "cred-slow"), model("sonnet", "cred-fast")];
+
+        for _ in 0..5 {
+            observe(&strategy, "cred-slow", 4000, true);
+            observe(&strategy, "cred-fast", 300, true);
+        }
+        observe(&strategy, "cred-slow", 0, false);
+
+        let ctx = SelectionContext::new(ServiceTier::Pro);
+        let result = strategy.select(&pool, &ctx).await.unwrap();
+        assert_eq!(result.model.credential_id, "cred-fast");
+        assert_eq!(result.alternatives.len(), 1);
+
+        let decisions = strategy.recent_decisions(10);
+        assert_eq!(decisions.len(), 1);
+        assert!(!decisions[0].explored);
+        assert_eq!(decisions[0].candidates.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cooling_credential_excluded() {
+        let strategy = AdaptiveStrategy::with_config(greedy());
+        strategy.register_cooldown_source(Arc::new(CoolingSet(vec!["cred-a".to_string()])));
+        observe(&strategy, "cred-a", 100, true);
+        observe(&strategy, "cred-b", 5000, true);
+
+        let pool = vec![model("sonnet", "cred-a"), model("sonnet", "cred-b")];
+        let ctx = SelectionContext::new(ServiceTier::Pro);
+        let result = strategy.select(&pool, &ctx).await.unwrap();
+        assert_eq!(result.model.credential_id, "cred-b");
+
+        let explained = strategy.explain(&pool, &ctx);
+        assert!(explained.candidates[0].cooling);
+        assert_eq!(
+            explained.candidates[0].excluded.as_deref(),
+            Some("cooling down")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_epsilon_explores_least_sampled() {
+        let strategy = AdaptiveStrategy::with_config(AdaptiveConfig {
+            epsilon: 1.0,
+            ..Default::default()
+        });
+        for _ in 0..20 {
+            observe(&strategy, "cred-busy", 200, true);
+        }
+        // 刚从冷却恢复的凭证有过失败记录
+        observe(&strategy, "cred-recovered", 0, false);
+
+        let pool = vec![
+            model("sonnet", "cred-busy"),
+            model("sonnet", "cred-recovered"),
+        ];
+        let ctx = SelectionContext::new(ServiceTier::Pro);
+        let result = strategy.select(&pool, &ctx).await.unwrap();
+        assert_eq!(result.model.credential_id, "cred-recovered");
+        assert!(strategy.recent_decisions(1)[0].explored);
+    }
+
+    #[test]
+    fn test_ucb_bonus_favors_unexplored() {
+        let strategy = AdaptiveStrategy::with_config(AdaptiveConfig {
+            exploration: ExplorationMode::Ucb,
+            ucb_c: 1.0,
+            ..Default::default()
+        });
+        for _ in 0..50 {
+            observe(&strategy, "cred-a", 500, true);
+        }
+        observe(&strategy, "cred-b", 500, true);
+
+        let pool = vec![model("sonnet", "cred-a"), model("sonnet", "cred-b")];
+        let decision = strategy.decide(&pool, &SelectionContext::new(ServiceTier::Pro));
+        assert!(decision.candidates[1].bonus > decision.candidates[0].bonus);
+        assert_eq!(decision.selected_credential.as_deref(), Some("cred-b"));
+    }
+
+    #[test]
+    fn test_stats_aggregator_error_rate() {
+        use crate::telemetry::RequestLog;
+        use crate::ProviderType;
+
+        let stats = Arc::new(RwLock::new(StatsAggregator::with_defaults()));
+        for success in [true, false, false, true] {
+            let mut log = RequestLog::new(
+                uuid::Uuid::new_v4().to_string(),
+                ProviderType::Kiro,
+                "sonnet".to_string(),
+                false,
+            );
+            log.set_credential_id("cred-a".to_string());
+            if success {
+                log.mark_success(1000, 200);
+            } else {
+                log.mark_failed(1000, Some(500), "error".to_string());
+            }
+            stats.read().record(log);
+        }
+
+        let strategy = AdaptiveStrategy::with_config(greedy());
+        strategy.attach_stats(stats);
+        let decision = strategy.explain(
+            &[model("sonnet", "cred-a")],
+            &SelectionContext::new(ServiceTier::Pro),
+        );
+        assert_eq!(decision.candidates[0].samples, 4);
+        assert_eq!(decision.candidates[0].error_rate, Some(0.5));
+    }
+
+    #[test]
+    fn test_forget_and_retain_credentials() {
+        let strategy = AdaptiveStrategy::with_config(greedy());
+        for id in ["cred-a", "cred-b", "cred-c"] {
+            observe(&strategy, id, 100, true);
+        }
+
+        strategy.forget_credential("cred-a");
+        assert!(!strategy.live.read().keys().any(|(id, _)| id == "cred-a"));
+
+        strategy.retain_credentials(["cred-c"]);
+        let live = strategy.live.read();
+        assert_eq!(live.len(), 1);
+        assert!(live.contains_key(&("cred-c".to_string(), "sonnet".to_string())));
+    }
+
+    #[test]
+    fn test_live_arms_capped() {
+        let strategy = AdaptiveStrategy::with_config(greedy());
+        for i in 0..MAX_LIVE_ARMS {
+            strategy.observe_response(&format!("cred-{i}"), "sonnet", true);
+        }
+        // 窗口外的条目优先被清理
+        strategy
+            .live
+            .write()
+            .get_mut(&("cred-7".to_string(), "sonnet".to_string()))
+            .unwrap()
+            .last_seen = Some(Utc::now() - chrono::Duration::hours(1));
+
+        strategy.observe_response("cred-new", "sonnet", true);
+        {
+            let live = strategy.live.read();
+            assert_eq!(live.len(), MAX_LIVE_ARMS);
+            assert!(!live.contains_key(&("cred-7".to_string(), "sonnet".to_string())));
+        }
+
+        // 全部在窗口内时淘汰最久未观测的条目，已有条目的更新不触发淘汰
+        strategy.observe_response("cred-new", "sonnet", false);
+        strategy.observe_response("cred-another", "sonnet", true);
+        let live = strategy.live.read();
+        assert_eq!(live.len(), MAX_LIVE_ARMS);
+        assert!(live.contains_key(&("cred-new".to_string(), "sonnet".to_string())));
+        assert!(live.contains_key(&("cred-another".to_string(), "sonnet".to_string())));
+    }
+}
@@ -2,12 +2,17 @@
 //!
 //! 提供多种模型选择策略实现。
 
+mod adaptive;
 mod cost_optimized;
 mod load_balanced;
 mod round_robin;
 mod speed_optimized;
 mod task_based;
 
+pub use adaptive::{
+    AdaptiveConfig, AdaptiveDecision, AdaptiveStrategy, ArmScore, ExplorationMode,
+    ADAPTIVE_STRATEGY_ID,
+};
 pub use cost_optimized::CostOptimizedStrategy;
 pub use load_balanced::LoadBalancedStrategy;
 pub use round_robin::RoundRobinStrategy;
@@ -17,7 +22,10 @@ pub use task_based::TaskBasedStrategy;
 use super::strategy::StrategyRegistry;
 use std::sync::Arc;
 
-/// 注册所有内置策略
+/// 注册所有无状态的内置策略
+///
+/// 自适应策略持有实时观测数据，需要与上游响应的反馈方共享同一实例，
+/// 由 `ModelOrchestrator` 创建并注册
 pub fn register_builtin_strategies(registry: &mut StrategyRegistry) {
     registry.register(Arc::new(RoundRobinStrategy::new()));
     registry.register(Arc::new(TaskBasedStrategy::new()));
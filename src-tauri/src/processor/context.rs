@@ -2,6 +2,7 @@
 //!
 //! 定义请求处理过程中的上下文信息
 
+use crate::flow_monitor::RoutingAttempt;
 use crate::plugin::PluginContext;
 use crate::ProviderType;
 use chrono::{DateTime, Utc};
@@ -34,6 +35,12 @@ pub struct RequestContext {
     pub plugin_ctx: Option<PluginContext>,
     /// 元数据
     pub metadata: std::collections::HashMap<String, serde_json::Value>,
+    /// 上游调用尝试记录（对冲、故障转移等）
+    pub attempts: Vec<RoutingAttempt>,
+    /// 等待凭证的排队时间（毫秒）
+    pub queue_wait_ms: u64,
+    /// 进入队列时的排队深度
+    pub queue_depth: u32,
 }
 
 impl RequestContext {
@@ -52,6 +59,9 @@ impl RequestContext {
             is_stream: false,
             plugin_ctx: None,
             metadata: std::collections::HashMap::new(),
+            attempts: Vec::new(),
+            queue_wait_ms: 0,
+            queue_depth: 0,
         }
     }
 
@@ -71,6 +81,12 @@ impl RequestContext {
         self.credential_id = Some(credential_id);
     }
 
+    /// 设置排队信息
+    pub fn set_queue_info(&mut self, wait_ms: u64, depth: u32) {
+        self.queue_wait_ms = wait_ms;
+        self.queue_depth = depth;
+    }
+
     /// 设置解析后的模型名称
     pub fn set_resolved_model(&mut self, model: String) {
         self.resolved_model = model;
@@ -109,6 +125,11 @@ impl RequestContext {
     pub fn get_metadata(&self, key: &str) -> Option<&serde_json::Value> {
         self.metadata.get(key)
     }
+
+    /// 记录上游调用尝试
+    pub fn record_attempt(&mut self, attempt: RoutingAttempt) {
+        self.attempts.push(attempt);
+    }
 }
 
 impl Default for RequestContext {
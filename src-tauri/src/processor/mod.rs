@@ -7,11 +7,12 @@
 //! 请求处理流程：
 //! 1. 认证 (AuthStep)
 //! 2. 参数注入 (InjectionStep)
-//! 3. 路由解析 (RoutingStep)
-//! 4. 插件前置钩子 (PluginPreStep)
-//! 5. Provider 调用 (ProviderStep) - 包含重试和故障转移
-//! 6. 插件后置钩子 (PluginPostStep)
-//! 7. 统计记录 (TelemetryStep)
+//! 3. Prompt Caching 断点 (PromptCacheStep) - 仅 Anthropic 原生凭证
+//! 4. 路由解析 (RoutingStep)
+//! 5. 插件前置钩子 (PluginPreStep)
+//! 6. Provider 调用 (ProviderStep) - 包含重试、故障转移和对冲请求
+//! 7. 插件后置钩子 (PluginPostStep)
+//! 8. 统计记录 (TelemetryStep)
 
 mod context;
 mod error;
@@ -20,15 +21,18 @@ mod steps;
 pub use context::RequestContext;
 pub use error::ProcessError;
 pub use steps::{
-    AuthStep, InjectionStep, PipelineStep, PluginPostStep, PluginPreStep, ProviderStep,
-    RoutingStep, TelemetryStep,
+    AuthStep, CacheBreakpoints, HedgePlan, HedgeTarget, InjectionStep, PipelineStep,
+    PluginPostStep, PluginPreStep, PromptCacheSseTap, PromptCacheStep, PromptCacheUsage,
+    PromptCacheUsageSlot, ProviderCallError, ProviderStep, RoutingStep, TelemetryStep,
 };
 
+use crate::credential::QuotaManager;
 use crate::injection::Injector;
 use crate::plugin::PluginManager;
 use crate::resilience::{Failover, Retrier, TimeoutController};
-use crate::router::{ModelMapper, Router};
+use crate::router::{FallbackResolver, ModelMapper, Router};
 use crate::services::provider_pool_service::ProviderPoolService;
+use crate::session::RateLimitTracker;
 use crate::telemetry::{StatsAggregator, TokenTracker};
 use parking_lot::RwLock as ParkingLotRwLock;
 use std::sync::Arc;
@@ -42,8 +46,12 @@ pub struct RequestProcessor {
     pub router: Arc<RwLock<Router>>,
     /// 模型映射器
     pub mapper: Arc<RwLock<ModelMapper>>,
+    /// 回退链解析器
+    pub fallback: Arc<RwLock<FallbackResolver>>,
     /// 参数注入器
     pub injector: Arc<RwLock<Injector>>,
+    /// Prompt Caching 断点步骤
+    pub prompt_cache: Arc<PromptCacheStep>,
     /// 重试器
     pub retrier: Arc<Retrier>,
     /// 故障转移器
@@ -58,6 +66,12 @@ pub struct RequestProcessor {
     pub tokens: Arc<ParkingLotRwLock<TokenTracker>>,
     /// 凭证池服务
     pub pool_service: Arc<ProviderPoolService>,
+    /// Provider 调用步骤（对冲请求与首字节统计）
+    pub provider_step: Arc<ProviderStep>,
+    /// 凭证配额管理器（记录配额超限的凭证及冷却时间）
+    pub quota: Arc<QuotaManager>,
+    /// 限流追踪器（记录上游 429 响应给出的重试等待时间）
+    pub rate_limits: Arc<RateLimitTracker>,
     /// 热重载协调锁（避免配置更新期间请求读取不一致的配置）
     pub reload_lock: Arc<RwLock<()>>,
 }
@@ -76,10 +90,18 @@ impl RequestProcessor {
         tokens: Arc<ParkingLotRwLock<TokenTracker>>,
         pool_service: Arc<ProviderPoolService>,
     ) -> Self {
+        let provider_step = Arc::new(ProviderStep::new(
+            retrier.clone(),
+            failover.clone(),
+            timeout.clone(),
+            pool_service.clone(),
+        ));
         Self {
             router,
             mapper,
+            fallback: Arc::new(RwLock::new(FallbackResolver::new())),
             injector,
+            prompt_cache: Arc::new(PromptCacheStep::default()),
             retrier,
             failover,
             timeout,
@@ -87,25 +109,20 @@ impl RequestProcessor {
             stats,
             tokens,
             pool_service,
+            provider_step,
+            quota: Arc::new(QuotaManager::with_defaults()),
+            rate_limits: Arc::new(RateLimitTracker::default()),
             reload_lock: Arc::new(RwLock::new(())),
         }
     }
 
     /// 使用默认配置创建请求处理器
     pub fn with_defaults(pool_service: Arc<ProviderPoolService>) -> Self {
-        Self {
-            router: Arc::new(RwLock::new(Self::create_router_with_defaults())),
-            mapper: Arc::new(RwLock::new(ModelMapper::new())),
-            injector: Arc::new(RwLock::new(Injector::new())),
-            retrier: Arc::new(Retrier::with_defaults()),
-            failover: Arc::new(Failover::with_defaults()),
-            timeout: Arc::new(TimeoutController::with_defaults()),
-            plugins: Arc::new(PluginManager::with_defaults()),
-            stats: Arc::new(ParkingLotRwLock::new(StatsAggregator::with_defaults())),
-            tokens: Arc::new(ParkingLotRwLock::new(TokenTracker::with_defaults())),
+        Self::with_shared_telemetry(
             pool_service,
-            reload_lock: Arc::new(RwLock::new(())),
-        }
+            Arc::new(ParkingLotRwLock::new(StatsAggregator::with_defaults())),
+            Arc::new(ParkingLotRwLock::new(TokenTracker::with_defaults())),
+        )
     }
 
     /// 创建带默认路由规则的路由器
@@ -131,19 +148,18 @@ impl RequestProcessor {
         stats: Arc<ParkingLotRwLock<StatsAggregator>>,
         tokens: Arc<ParkingLotRwLock<TokenTracker>>,
     ) -> Self {
-        Self {
-            router: Arc::new(RwLock::new(Self::create_router_with_defaults())),
-            mapper: Arc::new(RwLock::new(ModelMapper::new())),
-            injector: Arc::new(RwLock::new(Injector::new())),
-            retrier: Arc::new(Retrier::with_defaults()),
-            failover: Arc::new(Failover::with_defaults()),
-            timeout: Arc::new(TimeoutController::with_defaults()),
-            plugins: Arc::new(PluginManager::with_defaults()),
+        Self::new(
+            Arc::new(RwLock::new(Self::create_router_with_defaults())),
+            Arc::new(RwLock::new(ModelMapper::new())),
+            Arc::new(RwLock::new(Injector::new())),
+            Arc::new(Retrier::with_defaults()),
+            Arc::new(Failover::with_defaults()),
+            Arc::new(TimeoutController::with_defaults()),
+            Arc::new(PluginManager::with_defaults()),
             stats,
             tokens,
             pool_service,
-            reload_lock: Arc::new(RwLock::new(())),
-        }
+        )
     }
 
     /// 解析模型别名
@@ -181,6 +197,20 @@ impl RequestProcessor {
         resolved
     }
 
+    /// 解析请求的回退链
+    ///
+    /// # Arguments
+    /// * `model` - 请求中的 `model` 字段（可能是 `|` 分隔的内联回退链）
+    /// * `header` - `X-ProxyCast-Fallback` 请求头的值
+    pub async fn resolve_fallback_chain(
+        &self,
+        model: &str,
+        header: Option<&str>,
+    ) -> crate::router::FallbackChain {
+        let fallback = self.fallback.read().await;
+        fallback.resolve(model, header)
+    }
+
     /// 根据模型选择 Provider
     ///
     /// 使用 Router 根据路由规则选择合适的 Provider
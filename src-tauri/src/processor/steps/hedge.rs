@@ -0,0 +1,83 @@
+//! 对冲请求类型
+//!
+//! 首字节超时后向备用凭证或 Provider 发起相同请求，采用先返回首字节的一方，
+//! 另一方通过 CancellationToken 取消。
+
+use crate::flow_monitor::{AttemptKind, AttemptOutcome, RoutingAttempt};
+use crate::ProviderType;
+
+/// 对冲调用目标
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HedgeTarget {
+    /// Provider 类型
+    pub provider: ProviderType,
+    /// 凭证 ID
+    pub credential_id: Option<String>,
+}
+
+impl HedgeTarget {
+    /// 创建新的调用目标
+    pub fn new(provider: ProviderType, credential_id: Option<String>) -> Self {
+        Self {
+            provider,
+            credential_id,
+        }
+    }
+}
+
+/// 对冲调用计划
+#[derive(Debug, Clone)]
+pub struct HedgePlan {
+    /// 首选目标
+    pub primary: HedgeTarget,
+    /// 备用目标（为 None 时不对冲）
+    pub backup: Option<HedgeTarget>,
+    /// 估算的输入 Token 数（用于统计被取消调用浪费的 Token）
+    pub estimated_input_tokens: u32,
+}
+
+impl HedgePlan {
+    /// 创建不带备用目标的计划
+    pub fn new(primary: HedgeTarget) -> Self {
+        Self {
+            primary,
+            backup: None,
+            estimated_input_tokens: 0,
+        }
+    }
+
+    /// 设置备用目标
+    pub fn with_backup(mut self, backup: HedgeTarget) -> Self {
+        self.backup = Some(backup);
+        self
+    }
+
+    /// 设置估算的输入 Token 数
+    pub fn with_estimated_input_tokens(mut self, tokens: u32) -> Self {
+        self.estimated_input_tokens = tokens;
+        self
+    }
+}
+
+/// 构建调用尝试记录
+pub(crate) fn build_attempt(
+    kind: AttemptKind,
+    outcome: AttemptOutcome,
+    target: &HedgeTarget,
+    started_after_ms: u64,
+    ttfb_ms: Option<u64>,
+    error: Option<String>,
+) -> RoutingAttempt {
+    RoutingAttempt {
+        kind,
+        outcome,
+        provider: target.provider,
+        credential_id: target.credential_id.clone(),
+        started_after_ms,
+        ttfb_ms,
+        error,
+        wasted_tokens: 0,
+        model: None,
+        conversion: None,
+    }
+}
@@ -3,17 +3,24 @@
 //! 定义请求处理管道中的各个步骤
 
 mod auth;
+mod hedge;
 mod injection;
 mod plugin;
+mod prompt_cache;
 mod provider;
 mod routing;
 mod telemetry;
 mod traits;
 
 pub use auth::AuthStep;
+pub use hedge::{HedgePlan, HedgeTarget};
 pub use injection::InjectionStep;
 pub use plugin::{PluginPostStep, PluginPreStep};
-pub use provider::ProviderStep;
+pub use prompt_cache::{
+    CacheBreakpoints, PromptCacheSseTap, PromptCacheStep, PromptCacheUsage, PromptCacheUsageSlot,
+    MAX_CACHE_BREAKPOINTS,
+};
+pub use provider::{ProviderCallError, ProviderStep};
 pub use routing::RoutingStep;
 pub use telemetry::TelemetryStep;
 pub use traits::PipelineStep;
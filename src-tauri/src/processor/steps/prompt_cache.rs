@@ -0,0 +1,585 @@
+//! Prompt Caching 断点步骤
+//!
+//! 为发往 Anthropic 原生接口的请求自动插入 `cache_control: ephemeral` 断点：
+//! 1. 工具定义末尾
+//! 2. 系统提示词末尾
+//! 3. 对话的稳定前缀末尾（与同一会话上一次请求相同的消息前缀）
+//! 4. 最后一条消息（供下一轮请求命中）
+//!
+//! 会话由 `SessionManager` 的请求指纹识别，每个会话保存上一次请求的消息前缀哈希，
+//! 用于判断本次请求中哪些消息是稳定的。断点总数（含客户端已有的断点）不超过 4 个。
+
+use super::traits::{PipelineStep, StepError};
+use crate::config::PromptCacheConfig;
+use crate::processor::RequestContext;
+use crate::session::SessionManager;
+use crate::telemetry::TokenTracker;
+use async_trait::async_trait;
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Anthropic 单个请求允许的最大断点数
+pub const MAX_CACHE_BREAKPOINTS: usize = 4;
+
+/// 最多跟踪的会话数
+const MAX_TRACKED_SESSIONS: usize = 1024;
+
+/// 会话前缀记录的保留时长
+const SESSION_TTL: Duration = Duration::from_secs(3600);
+
+/// 本次请求插入的断点
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CacheBreakpoints {
+    /// 会话指纹
+    pub session_id: String,
+    /// 是否在工具定义末尾插入
+    pub tools: bool,
+    /// 是否在系统提示词末尾插入
+    pub system: bool,
+    /// 插入断点的消息下标
+    pub messages: Vec<usize>,
+    /// 与上一次请求相同的前缀消息数
+    pub stable_prefix_len: usize,
+}
+
+impl CacheBreakpoints {
+    /// 插入的断点数量
+    pub fn count(&self) -> usize {
+        self.tools as usize + self.system as usize + self.messages.len()
+    }
+}
+
+/// 会话上一次请求的消息前缀
+struct SessionPrefix {
+    /// 第 i 项为前 i+1 条消息的累积哈希
+    prefix_hashes: Vec<u64>,
+    last_seen: Instant,
+}
+
+/// Prompt Caching 断点步骤
+pub struct PromptCacheStep {
+    config: RwLock<PromptCacheConfig>,
+    sessions: Mutex<HashMap<String, SessionPrefix>>,
+}
+
+impl Default for PromptCacheStep {
+    fn default() -> Self {
+        Self::new(PromptCacheConfig::default())
+    }
+}
+
+impl PromptCacheStep {
+    /// 创建新的断点步骤
+    pub fn new(config: PromptCacheConfig) -> Self {
+        Self {
+            config: RwLock::new(config),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 更新配置
+    pub fn set_config(&self, config: PromptCacheConfig) {
+        *self.config.write() = config;
+    }
+
+    /// 检查是否启用
+    pub fn is_enabled(&self) -> bool {
+        self.config.read().enabled
+    }
+
+    /// 为 Anthropic Messages 请求插入断点
+    ///
+    /// 未启用时返回 None；否则返回实际插入的断点（可能为空）。
+    pub fn apply(&self, payload: &mut Value) -> Option<CacheBreakpoints> {
+        let config = self.config.read().clone();
+        if !config.enabled {
+            return None;
+        }
+
+        let model = payload["model"].as_str().unwrap_or_default().to_string();
+        let session_id = SessionManager::extract_session_id_from_json(payload, &model);
+        let prefix_hashes = message_prefix_hashes(payload);
+        let (seen_before, stable_prefix_len) = self.update_session(&session_id, prefix_hashes);
+
+        let mut result = CacheBreakpoints {
+            session_id,
+            stable_prefix_len,
+            ..Default::default()
+        };
+        let mut budget = MAX_CACHE_BREAKPOINTS.saturating_sub(count_breakpoints(payload));
+
+        if config.cache_tools && budget > 0 && mark_last_tool(payload) {
+            result.tools = true;
+            budget -= 1;
+        }
+        if config.cache_system && budget > 0 && mark_system(payload) {
+            result.system = true;
+            budget -= 1;
+        }
+        if config.cache_conversation && seen_before {
+            let message_count = payload["messages"].as_array().map_or(0, |m| m.len());
+            // 稳定前缀用于命中上一轮写入的缓存，最后一条消息为下一轮写入缓存
+            let candidates = [
+                stable_prefix_len.checked_sub(1),
+                message_count.checked_sub(1),
+            ];
+            for index in candidates.into_iter().flatten() {
+                if budget == 0 || result.messages.contains(&index) {
+                    continue;
+                }
+                if mark_message(payload, index) {
+                    result.messages.push(index);
+                    budget -= 1;
+                }
+            }
+        }
+
+        Some(result)
+    }
+
+    /// 记录会话本次的消息前缀，返回（会话是否出现过，稳定前缀长度）
+    fn update_session(&self, session_id: &str, prefix_hashes: Vec<u64>) -> (bool, usize) {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock();
+
+        let previous = sessions
+            .get(session_id)
+            .filter(|s| now.duration_since(s.last_seen) < SESSION_TTL);
+        let seen_before = previous.is_some();
+        let stable_len = previous.map_or(0, |prev| {
+            prev.prefix_hashes
+                .iter()
+                .zip(&prefix_hashes)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+
+        if !sessions.contains_key(session_id) && sessions.len() >= MAX_TRACKED_SESSIONS {
+            sessions.retain(|_, s| now.duration_since(s.last_seen) < SESSION_TTL);
+            if sessions.len() >= MAX_TRACKED_SESSIONS {
+                let oldest = sessions
+                    .iter()
+                    .min_by_key(|(_, s)| s.last_seen)
+                    .map(|(id, _)| id.clone());
+                if let Some(id) = oldest {
+                    sessions.remove(&id);
+                }
+            }
+        }
+        sessions.insert(
+            session_id.to_string(),
+            SessionPrefix {
+                prefix_hashes,
+                last_seen: now,
+            },
+        );
+
+        (seen_before, stable_len)
+    }
+}
+
+#[async_trait]
+impl PipelineStep for PromptCacheStep {
+    async fn execute(
+        &self,
+        ctx: &mut RequestContext,
+        payload: &mut Value,
+    ) -> Result<(), StepError> {
+        if let Some(breakpoints) = self.apply(payload) {
+            if breakpoints.count() > 0 {
+                tracing::debug!(
+                    "[PROMPT_CACHE] request_id={} session={} breakpoints={} stable_prefix={}",
+                    ctx.request_id,
+                    breakpoints.session_id,
+                    breakpoints.count(),
+                    breakpoints.stable_prefix_len
+                );
+                ctx.set_metadata(
+                    "prompt_cache",
+                    serde_json::to_value(&breakpoints).unwrap_or_default(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "prompt_cache"
+    }
+
+    fn is_enabled(&self) -> bool {
+        PromptCacheStep::is_enabled(self)
+    }
+}
+
+/// 计算消息的累积前缀哈希
+///
+/// 忽略已有的 cache_control，并把字符串内容视为单个文本块，
+/// 因为客户端在后续轮次中常把同一条消息改写为带断点的块数组。
+fn message_prefix_hashes(payload: &Value) -> Vec<u64> {
+    let Some(messages) = payload["messages"].as_array() else {
+        return Vec::new();
+    };
+    let mut hasher = DefaultHasher::new();
+    messages
+        .iter()
+        .map(|message| {
+            let mut message = message.clone();
+            if let Some(Value::String(text)) = message.get("content") {
+                message["content"] = serde_json::json!([{"type": "text", "text": text}]);
+            }
+            strip_cache_control(&mut message);
+            message.to_string().hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+fn strip_cache_control(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("cache_control");
+            map.values_mut().for_each(strip_cache_control);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_cache_control),
+        _ => {}
+    }
+}
+
+/// 统计请求中已有的断点数
+fn count_breakpoints(value: &Value) -> usize {
+    match value {
+        Value::Object(map) => {
+            map.contains_key("cache_control") as usize
+                + map.values().map(count_breakpoints).sum::<usize>()
+        }
+        Value::Array(items) => items.iter().map(count_breakpoints).sum(),
+        _ => 0,
+    }
+}
+
+fn ephemeral() -> Value {
+    serde_json::json!({"type": "ephemeral"})
+}
+
+/// 在最后一个工具定义上插入断点
+fn mark_last_tool(payload: &mut Value) -> bool {
+    match payload["tools"].as_array_mut().and_then(|t| t.last_mut()) {
+        Some(Value::Object(tool)) if !tool.contains_key("cache_control") => {
+            tool.insert("cache_control".to_string(), ephemeral());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// 在系统提示词的最后一个块上插入断点（字符串形式会转换为块数组）
+fn mark_system(payload: &mut Value) -> bool {
+    let Some(system) = payload.get_mut("system") else {
+        return false;
+    };
+    if let Value::String(text) = system {
+        if text.is_empty() {
+            return false;
+        }
+        *system = serde_json::json!([{"type": "text", "text": text}]);
+    }
+    system
+        .as_array_mut()
+        .is_some_and(|blocks| mark_last_block(blocks))
+}
+
+/// 在指定消息的最后一个可缓存内容块上插入断点
+fn mark_message(payload: &mut Value, index: usize) -> bool {
+    let Some(content) = payload["messages"]
+        .get_mut(index)
+        .and_then(|m| m.get_mut("content"))
+    else {
+        return false;
+    };
+    if let Value::String(text) = content {
+        if text.is_empty() {
+            return false;
+        }
+        *content = serde_json::json!([{"type": "text", "text": text}]);
+    }
+    content
+        .as_array_mut()
+        .is_some_and(|blocks| mark_last_block(blocks))
+}
+
+/// 思维链块不能携带 cache_control，跳过它们选择最后一个普通块
+fn mark_last_block(blocks: &mut [Value]) -> bool {
+    let target = blocks.iter_mut().rev().find(|block| {
+        !matches!(
+            block["type"].as_str(),
+            Some("thinking") | Some("redacted_thinking")
+        )
+    });
+    match target {
+        Some(Value::Object(block)) if !block.contains_key("cache_control") => {
+            block.insert("cache_control".to_string(), ephemeral());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// 上游返回的 Prompt Cache 用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PromptCacheUsage {
+    /// cache_creation_input_tokens
+    pub creation: u32,
+    /// cache_read_input_tokens
+    pub read: u32,
+}
+
+impl PromptCacheUsage {
+    /// 从 Anthropic usage 对象提取缓存用量，没有缓存字段时返回 None
+    pub fn from_usage(usage: &Value) -> Option<Self> {
+        let creation = usage.get("cache_creation_input_tokens");
+        let read = usage.get("cache_read_input_tokens");
+        if creation.is_none() && read.is_none() {
+            return None;
+        }
+        let tokens = |v: Option<&Value>| v.and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        Some(Self {
+            creation: tokens(creation),
+            read: tokens(read),
+        })
+    }
+}
+
+/// 缓存用量回填槽
+///
+/// 作为响应扩展在 Provider 调用与请求处理器之间传递。流式响应的用量在响应体发送时
+/// 才能解析到，因此用量与 Token 记录目标哪一方先到都可以，两者齐备时写入 TokenTracker。
+#[derive(Clone, Default)]
+pub struct PromptCacheUsageSlot(Arc<Mutex<SlotState>>);
+
+#[derive(Default)]
+struct SlotState {
+    usage: Option<PromptCacheUsage>,
+    sink: Option<(Arc<RwLock<TokenTracker>>, String)>,
+}
+
+impl PromptCacheUsageSlot {
+    /// 上报解析到的缓存用量
+    pub fn report(&self, usage: PromptCacheUsage) {
+        let mut state = self.0.lock();
+        state.usage = Some(usage);
+        Self::flush(&state);
+    }
+
+    /// 指定写入的 TokenTracker 与请求 ID
+    pub fn bind(&self, tokens: Arc<RwLock<TokenTracker>>, request_id: String) {
+        let mut state = self.0.lock();
+        state.sink = Some((tokens, request_id));
+        Self::flush(&state);
+    }
+
+    /// 已上报的缓存用量
+    pub fn usage(&self) -> Option<PromptCacheUsage> {
+        self.0.lock().usage
+    }
+
+    fn flush(state: &SlotState) {
+        if let (Some(usage), Some((tokens, request_id))) = (&state.usage, &state.sink) {
+            tokens
+                .read()
+                .add_cache_tokens(request_id, usage.creation, usage.read);
+        }
+    }
+}
+
+/// 从 Anthropic SSE 流中提取缓存用量
+///
+/// 用量出现在 message_start（message.usage）以及 message_delta（usage）事件中。
+#[derive(Default)]
+pub struct PromptCacheSseTap {
+    buffer: String,
+}
+
+impl PromptCacheSseTap {
+    /// 处理一段 SSE 数据，返回其中最新的缓存用量
+    pub fn observe(&mut self, chunk: &[u8]) -> Option<PromptCacheUsage> {
+        self.buffer.push_str(&String::from_utf8_lossy(chunk));
+        let mut found = None;
+        while let Some(pos) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=pos).collect();
+            let Some(data) = line.trim().strip_prefix("data:") else {
+                continue;
+            };
+            if !data.contains("cache_") {
+                continue;
+            }
+            if let Ok(event) = serde_json::from_str::<Value>(data.trim()) {
+                let usage = event
+                    .get("usage")
+                    .or_else(|| event.get("message").and_then(|m| m.get("usage")));
+                if let Some(usage) = usage.and_then(PromptCacheUsage::from_usage) {
+                    found = Some(usage);
+                }
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn enabled_step() -> PromptCacheStep {
+        PromptCacheStep::new(PromptCacheConfig {
+            enabled: true,
+            ..Default::default()
+        })
+    }
+
+    fn conversation(turns: usize) -> Value {
+        let mut messages = Vec::new();
+        for i in 0..turns {
+            messages.push(json!({"role": "user", "content": format!("question number {i} about the project")}));
+            if i + 1 < turns {
+                messages.push(json!({"role": "assistant", "content": [{"type": "text", "text": format!("answer {i}")}]}));
+            }
+        }
+        json!({
+            "model": "claude-sonnet-4-5",
+            "system": "You are a helpful coding assistant.",
+            "tools": [{"name": "read"}, {"name": "write"}],
+            "messages": messages
+        })
+    }
+
+    #[test]
+    fn test_disabled_step_leaves_payload_untouched() {
+        let step = PromptCacheStep::default();
+        let mut payload = conversation(1);
+        let original = payload.clone();
+        assert!(step.apply(&mut payload).is_none());
+        assert_eq!(payload, original);
+    }
+
+    #[test]
+    fn test_first_turn_marks_tools_and_system_only() {
+        let step = enabled_step();
+        let mut payload = conversation(1);
+        let result = step.apply(&mut payload).unwrap();
+
+        assert!(result.tools && result.system);
+        assert!(result.messages.is_empty());
+        assert_eq!(payload["tools"][1]["cache_control"]["type"], "ephemeral");
+        assert!(payload["tools"][0].get("cache_control").is_none());
+        assert_eq!(
+            payload["system"][0]["text"],
+            "You are a helpful coding assistant."
+        );
+        assert_eq!(payload["system"][0]["cache_control"]["type"], "ephemeral");
+    }
+
+    #[test]
+    fn test_follow_up_turn_marks_stable_prefix_and_tail() {
+        let step = enabled_step();
+        step.apply(&mut conversation(2));
+
+        let mut payload = conversation(3);
+        let result = step.apply(&mut payload).unwrap();
+
+        // 上一轮的 3 条消息保持不变
+        assert_eq!(result.stable_prefix_len, 3);
+        assert_eq!(result.messages, vec![2, 4]);
+        assert_eq!(result.count(), 4);
+        assert_eq!(
+            payload["messages"][2]["content"][0]["cache_control"]["type"],
+            "ephemeral"
+        );
+        assert_eq!(
+            payload["messages"][4]["content"][0]["cache_control"]["type"],
+            "ephemeral"
+        );
+    }
+
+    #[test]
+    fn test_respects_existing_breakpoints() {
+        let step = enabled_step();
+        step.apply(&mut conversation(2));
+
+        let mut payload = conversation(3);
+        payload["messages"][0]["content"] = json!([
+            {"type": "text", "text": "question number 0 about the project", "cache_control": {"type": "ephemeral"}}
+        ]);
+        payload["tools"][1]["cache_control"] = json!({"type": "ephemeral"});
+        let result = step.apply(&mut payload).unwrap();
+
+        // 客户端已占用 2 个断点，且已有的 cache_control 不影响前缀判断
+        assert_eq!(result.stable_prefix_len, 3);
+        assert!(!result.tools);
+        assert_eq!(result.count(), 2);
+        assert_eq!(count_breakpoints(&payload), MAX_CACHE_BREAKPOINTS);
+    }
+
+    #[test]
+    fn test_skips_thinking_blocks() {
+        let mut blocks = vec![
+            json!({"type": "text", "text": "a"}),
+            json!({"type": "thinking", "thinking": "b", "signature": "s"}),
+        ];
+        assert!(mark_last_block(&mut blocks));
+        assert!(blocks[0].get("cache_control").is_some());
+        assert!(blocks[1].get("cache_control").is_none());
+    }
+
+    #[test]
+    fn test_sse_tap_extracts_usage_across_chunks() {
+        let mut tap = PromptCacheSseTap::default();
+        let event = "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":5,\"cache_creation_input_tokens\":120,\"cache_read_input_tokens\":900}}}\n\n";
+        let (head, tail) = event.split_at(40);
+
+        assert_eq!(tap.observe(head.as_bytes()), None);
+        assert_eq!(
+            tap.observe(tail.as_bytes()),
+            Some(PromptCacheUsage {
+                creation: 120,
+                read: 900
+            })
+        );
+        assert_eq!(tap.observe(b"data: {\"type\":\"ping\"}\n"), None);
+    }
+
+    #[test]
+    fn test_usage_slot_records_in_either_order() {
+        use crate::telemetry::{TokenSource, TokenUsageRecord};
+
+        let tokens = Arc::new(RwLock::new(TokenTracker::with_defaults()));
+        tokens.read().record(
+            TokenUsageRecord::new(
+                "1".to_string(),
+                crate::ProviderType::Claude,
+                "claude".to_string(),
+                10,
+                5,
+                TokenSource::Estimated,
+            )
+            .with_request_id("req-1".to_string()),
+        );
+
+        let slot = PromptCacheUsageSlot::default();
+        slot.bind(tokens.clone(), "req-1".to_string());
+        slot.report(PromptCacheUsage {
+            creation: 0,
+            read: 300,
+        });
+
+        let records = tokens.read().get_all();
+        assert_eq!(records[0].cache_read_input_tokens, 300);
+    }
+}
@@ -2,17 +2,22 @@
 //!
 //! 集成重试、故障转移和超时控制
 
+use super::hedge::{build_attempt, HedgePlan, HedgeTarget};
 use super::traits::{PipelineStep, StepError};
+use crate::flow_monitor::{AttemptKind, AttemptOutcome};
 use crate::processor::RequestContext;
 use crate::resilience::{
-    Failover, FailoverConfig, FailoverManager, Retrier, RetryConfig, TimeoutConfig,
-    TimeoutController, TimeoutError,
+    CancellationToken, Failover, FailoverConfig, FailoverManager, Retrier, RetryConfig,
+    TimeoutConfig, TimeoutController, TimeoutError,
 };
 use crate::services::provider_pool_service::ProviderPoolService;
+use crate::streaming::TtfbWindow;
 use crate::ProviderType;
 use async_trait::async_trait;
 use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Provider 调用结果
 #[derive(Debug, Clone)]
@@ -89,6 +94,10 @@ pub struct ProviderStep {
     timeout: Arc<TimeoutController>,
     /// 凭证池服务
     pool_service: Arc<ProviderPoolService>,
+    /// 首字节时间滑动窗口（用于计算对冲阈值）
+    ttfb_window: Arc<TtfbWindow>,
+    /// 对冲延迟（毫秒，0 表示不启用，支持热更新）
+    hedge_delay_ms: AtomicU64,
 }
 
 impl ProviderStep {
@@ -99,11 +108,14 @@ impl ProviderStep {
         timeout: Arc<TimeoutController>,
         pool_service: Arc<ProviderPoolService>,
     ) -> Self {
+        let hedge_delay_ms = AtomicU64::new(timeout.config().hedge_delay_ms);
         Self {
             retrier,
             failover,
             timeout,
             pool_service,
+            ttfb_window: Arc::new(TtfbWindow::default()),
+            hedge_delay_ms,
         }
     }
 
@@ -114,6 +126,8 @@ impl ProviderStep {
             failover: Arc::new(Failover::new(FailoverConfig::default())),
             timeout: Arc::new(TimeoutController::with_defaults()),
             pool_service,
+            ttfb_window: Arc::new(TtfbWindow::default()),
+            hedge_delay_ms: AtomicU64::new(0),
         }
     }
 
@@ -127,11 +141,21 @@ impl ProviderStep {
         Self {
             retrier: Arc::new(Retrier::new(retry_config)),
             failover: Arc::new(Failover::new(failover_config)),
+            hedge_delay_ms: AtomicU64::new(timeout_config.hedge_delay_ms),
             timeout: Arc::new(TimeoutController::new(timeout_config)),
             pool_service,
+            ttfb_window: Arc::new(TtfbWindow::default()),
         }
     }
 
+    /// 使用共享的 TTFB 窗口
+    ///
+    /// 允许多个 ProviderStep 基于同一份首字节统计计算对冲阈值
+    pub fn with_ttfb_window(mut self, ttfb_window: Arc<TtfbWindow>) -> Self {
+        self.ttfb_window = ttfb_window;
+        self
+    }
+
     /// 获取重试器
     pub fn retrier(&self) -> &Retrier {
         &self.retrier
@@ -152,6 +176,230 @@ impl ProviderStep {
         &self.pool_service
     }
 
+    /// 获取 TTFB 滑动窗口
+    pub fn ttfb_window(&self) -> &Arc<TtfbWindow> {
+        &self.ttfb_window
+    }
+
+    /// 设置对冲延迟（配置热更新时调用，0 表示不启用对冲）
+    pub fn set_hedge_delay_ms(&self, hedge_delay_ms: u64) {
+        self.hedge_delay_ms.store(hedge_delay_ms, Ordering::Relaxed);
+    }
+
+    /// 计算对冲阈值
+    ///
+    /// 未配置 `hedge_delay_ms` 时不启用对冲；样本充足时使用 p95 TTFB，
+    /// 否则回退到配置的固定阈值
+    pub fn hedge_threshold(&self) -> Option<Duration> {
+        let fixed = match self.hedge_delay_ms.load(Ordering::Relaxed) {
+            0 => return None,
+            ms => Duration::from_millis(ms),
+        };
+        Some(
+            self.ttfb_window
+                .p95()
+                .map(Duration::from_millis)
+                .unwrap_or(fixed),
+        )
+    }
+
+    /// 带对冲执行 Provider 调用
+    ///
+    /// 首选目标在阈值内未返回首字节时，向备用目标发起相同请求，
+    /// 采用先返回的一方并取消另一方。`operation_factory` 返回的 Future
+    /// 应在收到首字节（流式）或完整响应（非流式）时完成，并在令牌取消时尽快退出。
+    /// 所有尝试都会记录到 `ctx.attempts`。
+    ///
+    /// # Arguments
+    /// * `ctx` - 请求上下文
+    /// * `plan` - 对冲调用计划
+    /// * `operation_factory` - Provider 调用操作工厂
+    ///
+    /// # Returns
+    /// 成功返回胜出方的结果；两方都失败时返回首选目标的错误
+    pub async fn execute_with_hedging<F, Fut, T>(
+        &self,
+        ctx: &mut RequestContext,
+        plan: HedgePlan,
+        mut operation_factory: F,
+    ) -> Result<T, ProviderCallError>
+    where
+        F: FnMut(HedgeTarget, CancellationToken) -> Fut,
+        Fut: Future<Output = Result<T, ProviderCallError>>,
+    {
+        let start = Instant::now();
+        let primary = plan.primary;
+
+        let primary_token = CancellationToken::new();
+        let primary_fut = operation_factory(primary.clone(), primary_token.clone());
+        tokio::pin!(primary_fut);
+
+        let (backup, threshold) = match (plan.backup, self.hedge_threshold()) {
+            (Some(backup), Some(threshold)) => (backup, threshold),
+            _ => {
+                let result = primary_fut.await;
+                self.record_result(ctx, AttemptKind::Primary, &primary, 0, start, &result);
+                return result;
+            }
+        };
+
+        // 阈值内首选目标返回则不对冲
+        tokio::select! {
+            result = &mut primary_fut => {
+                self.record_result(ctx, AttemptKind::Primary, &primary, 0, start, &result);
+                return result;
+            }
+            _ = tokio::time::sleep(threshold) => {}
+        }
+
+        let hedge_started_ms = start.elapsed().as_millis() as u64;
+        let hedge_start = Instant::now();
+        tracing::info!(
+            "[HEDGE] request_id={} no_first_byte_after={}ms primary={} hedge={} credential={:?}",
+            ctx.request_id,
+            hedge_started_ms,
+            primary.provider,
+            backup.provider,
+            backup.credential_id
+        );
+
+        let hedge_token = CancellationToken::new();
+        let hedge_fut = operation_factory(backup.clone(), hedge_token.clone());
+        tokio::pin!(hedge_fut);
+
+        let mut primary_error: Option<ProviderCallError> = None;
+        let mut hedge_error: Option<ProviderCallError> = None;
+
+        loop {
+            tokio::select! {
+                result = &mut primary_fut, if primary_error.is_none() => {
+                    self.record_result(ctx, AttemptKind::Primary, &primary, 0, start, &result);
+                    match result {
+                        Ok(value) => {
+                            hedge_token.cancel();
+                            if hedge_error.is_none() {
+                                self.record_cancelled(
+                                    ctx,
+                                    AttemptKind::Hedge,
+                                    &backup,
+                                    hedge_started_ms,
+                                    plan.estimated_input_tokens,
+                                );
+                            }
+                            return Ok(value);
+                        }
+                        Err(err) => primary_error = Some(err),
+                    }
+                }
+                result = &mut hedge_fut, if hedge_error.is_none() => {
+                    self.record_result(
+                        ctx,
+                        AttemptKind::Hedge,
+                        &backup,
+                        hedge_started_ms,
+                        hedge_start,
+                        &result,
+                    );
+                    match result {
+                        Ok(value) => {
+                            primary_token.cancel();
+                            if primary_error.is_none() {
+                                self.record_cancelled(
+                                    ctx,
+                                    AttemptKind::Primary,
+                                    &primary,
+                                    0,
+                                    plan.estimated_input_tokens,
+                                );
+                            }
+                            return Ok(value);
+                        }
+                        Err(err) => hedge_error = Some(err),
+                    }
+                }
+            }
+
+            if let (Some(err), Some(_)) = (&primary_error, &hedge_error) {
+                tracing::warn!(
+                    "[HEDGE] request_id={} both_attempts_failed error={}",
+                    ctx.request_id,
+                    err.message
+                );
+                return Err(err.clone());
+            }
+        }
+    }
+
+    /// 记录已完成（成功或失败）的调用尝试
+    ///
+    /// 成功时同时更新上下文中的 Provider/凭证。TTFB 样本由调用方在
+    /// 响应体收到首个 chunk 时通过 `ttfb_window().record_metrics` 记录
+    fn record_result<T>(
+        &self,
+        ctx: &mut RequestContext,
+        kind: AttemptKind,
+        target: &HedgeTarget,
+        started_after_ms: u64,
+        attempt_start: Instant,
+        result: &Result<T, ProviderCallError>,
+    ) {
+        let attempt = match result {
+            Ok(_) => {
+                let ttfb_ms = attempt_start.elapsed().as_millis() as u64;
+                ctx.set_provider(target.provider);
+                if let Some(credential_id) = &target.credential_id {
+                    ctx.set_credential_id(credential_id.clone());
+                }
+                build_attempt(
+                    kind,
+                    AttemptOutcome::Won,
+                    target,
+                    started_after_ms,
+                    Some(ttfb_ms),
+                    None,
+                )
+            }
+            Err(err) => build_attempt(
+                kind,
+                AttemptOutcome::Failed,
+                target,
+                started_after_ms,
+                None,
+                Some(err.message.clone()),
+            ),
+        };
+        ctx.record_attempt(attempt);
+    }
+
+    /// 记录被取消的调用尝试
+    ///
+    /// 被取消的一方已将请求发往上游，按估算输入 Token 计入浪费
+    fn record_cancelled(
+        &self,
+        ctx: &mut RequestContext,
+        kind: AttemptKind,
+        target: &HedgeTarget,
+        started_after_ms: u64,
+        estimated_input_tokens: u32,
+    ) {
+        tracing::info!(
+            "[HEDGE] request_id={} cancel_loser provider={} credential={:?}",
+            ctx.request_id,
+            target.provider,
+            target.credential_id
+        );
+        let mut attempt = build_attempt(
+            kind,
+            AttemptOutcome::Cancelled,
+            target,
+            started_after_ms,
+            None,
+            None,
+        );
+        attempt.wasted_tokens = estimated_input_tokens;
+        ctx.record_attempt(attempt);
+    }
+
     /// 带重试执行 Provider 调用
     ///
     /// 使用 Retrier 包装 Provider 调用，自动处理可重试错误
@@ -730,4 +978,145 @@ mod tests {
         assert_eq!(err.status_code, Some(408));
         assert!(err.retryable);
     }
+
+    fn hedging_step(hedge_delay_ms: u64) -> ProviderStep {
+        ProviderStep::with_config(
+            RetryConfig::default(),
+            FailoverConfig::default(),
+            TimeoutConfig::default().with_hedge_delay(hedge_delay_ms),
+            Arc::new(ProviderPoolService::new()),
+        )
+    }
+
+    fn hedge_plan() -> HedgePlan {
+        HedgePlan::new(HedgeTarget::new(
+            ProviderType::Kiro,
+            Some("cred-primary".to_string()),
+        ))
+        .with_backup(HedgeTarget::new(
+            ProviderType::Gemini,
+            Some("cred-backup".to_string()),
+        ))
+        .with_estimated_input_tokens(200)
+    }
+
+    #[test]
+    fn test_hedge_threshold() {
+        assert!(hedging_step(0).hedge_threshold().is_none());
+
+        let step = hedging_step(800);
+        assert_eq!(step.hedge_threshold(), Some(Duration::from_millis(800)));
+
+        // 样本充足时使用 p95 TTFB
+        for _ in 0..TtfbWindow::MIN_SAMPLES {
+            step.ttfb_window().record(300);
+        }
+        assert_eq!(step.hedge_threshold(), Some(Duration::from_millis(300)));
+
+        // 热更新为 0 后关闭对冲
+        step.set_hedge_delay_ms(0);
+        assert!(step.hedge_threshold().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hedging_primary_wins_before_threshold() {
+        let step = hedging_step(200);
+        let mut ctx = RequestContext::new("test-model".to_string());
+
+        let result = step
+            .execute_with_hedging(&mut ctx, hedge_plan(), |target, _token| async move {
+                Ok::<_, ProviderCallError>(target.provider)
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), ProviderType::Kiro);
+        assert_eq!(ctx.attempts.len(), 1);
+        assert_eq!(ctx.attempts[0].kind, AttemptKind::Primary);
+        assert_eq!(ctx.attempts[0].outcome, AttemptOutcome::Won);
+        assert_eq!(ctx.credential_id.as_deref(), Some("cred-primary"));
+    }
+
+    #[tokio::test]
+    async fn test_hedging_backup_wins_and_cancels_primary() {
+        let step = hedging_step(50);
+        let mut ctx = RequestContext::new("test-model".to_string());
+        let primary_token = Arc::new(parking_lot::Mutex::new(None));
+        let captured = primary_token.clone();
+
+        let result = step
+            .execute_with_hedging(&mut ctx, hedge_plan(), move |target, token| {
+                if target.provider == ProviderType::Kiro {
+                    *captured.lock() = Some(token.clone());
+                }
+                async move {
+                    if target.provider == ProviderType::Kiro {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                    Ok::<_, ProviderCallError>(target.provider)
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), ProviderType::Gemini);
+        assert!(primary_token.lock().as_ref().unwrap().is_cancelled());
+        assert_eq!(ctx.provider, Some(ProviderType::Gemini));
+        assert_eq!(ctx.credential_id.as_deref(), Some("cred-backup"));
+
+        let hedge = ctx
+            .attempts
+            .iter()
+            .find(|a| a.kind == AttemptKind::Hedge)
+            .unwrap();
+        assert_eq!(hedge.outcome, AttemptOutcome::Won);
+        assert!(hedge.started_after_ms >= 50);
+
+        let primary = ctx
+            .attempts
+            .iter()
+            .find(|a| a.kind == AttemptKind::Primary)
+            .unwrap();
+        assert_eq!(primary.outcome, AttemptOutcome::Cancelled);
+        assert_eq!(primary.wasted_tokens, 200);
+    }
+
+    #[tokio::test]
+    async fn test_hedging_both_fail_returns_primary_error() {
+        let step = hedging_step(20);
+        let mut ctx = RequestContext::new("test-model".to_string());
+
+        let result = step
+            .execute_with_hedging(&mut ctx, hedge_plan(), |target, _token| async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Err::<(), _>(ProviderCallError::retryable(
+                    format!("{} failed", target.provider),
+                    Some(503),
+                ))
+            })
+            .await;
+
+        let err = result.unwrap_err();
+        assert_eq!(err.message, "kiro failed");
+        assert_eq!(ctx.attempts.len(), 2);
+        assert!(ctx
+            .attempts
+            .iter()
+            .all(|a| a.outcome == AttemptOutcome::Failed));
+    }
+
+    #[tokio::test]
+    async fn test_hedging_disabled_without_backup() {
+        let step = hedging_step(10);
+        let mut ctx = RequestContext::new("test-model".to_string());
+        let plan = HedgePlan::new(HedgeTarget::new(ProviderType::Kiro, None));
+
+        let result = step
+            .execute_with_hedging(&mut ctx, plan, |target, _token| async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok::<_, ProviderCallError>(target.provider)
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), ProviderType::Kiro);
+        assert_eq!(ctx.attempts.len(), 1);
+    }
 }
@@ -3,6 +3,7 @@
 //! 记录请求统计和 Token 使用
 
 use super::traits::{PipelineStep, StepError};
+use crate::flow_monitor::AttemptOutcome;
 use crate::processor::RequestContext;
 use crate::telemetry::{
     RequestLog, RequestStatus, StatsAggregator, TokenSource, TokenTracker, TokenUsageRecord,
@@ -140,6 +141,43 @@ impl TelemetryStep {
             }
         }
     }
+
+    /// 记录被取消的上游调用（对冲请求中落败的一方）
+    ///
+    /// 每个被取消的尝试记为一条 Cancelled 请求日志，其消耗的 Token 标记为浪费
+    pub fn record_cancelled_attempts(&self, ctx: &RequestContext) {
+        for attempt in ctx
+            .attempts
+            .iter()
+            .filter(|a| a.outcome == AttemptOutcome::Cancelled)
+        {
+            let mut log = RequestLog::new(
+                ctx.request_id.clone(),
+                attempt.provider,
+                ctx.resolved_model.clone(),
+                ctx.is_stream,
+            );
+            log.mark_cancelled(ctx.elapsed_ms().saturating_sub(attempt.started_after_ms));
+            if let Some(cred_id) = &attempt.credential_id {
+                log.set_credential_id(cred_id.clone());
+            }
+            self.stats.write().record(log);
+
+            if attempt.wasted_tokens > 0 {
+                let record = TokenUsageRecord::new(
+                    uuid::Uuid::new_v4().to_string(),
+                    attempt.provider,
+                    ctx.resolved_model.clone(),
+                    attempt.wasted_tokens,
+                    0,
+                    TokenSource::Estimated,
+                )
+                .with_request_id(ctx.request_id.clone())
+                .mark_wasted();
+                self.tokens.write().record(record);
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -152,6 +190,9 @@ impl PipelineStep for TelemetryStep {
         // 记录成功的请求（同步方法，使用 parking_lot::RwLock）
         self.record_request(ctx, RequestStatus::Success, None);
 
+        // 记录被取消的对冲调用
+        self.record_cancelled_attempts(ctx);
+
         // 从响应中提取并记录 Token（同步方法）
         self.record_tokens_from_response(ctx, payload);
 
@@ -244,4 +285,49 @@ mod tests {
         let tokens_guard = tokens.read();
         assert_eq!(tokens_guard.len(), 1);
     }
+
+    #[test]
+    fn test_telemetry_step_record_cancelled_attempts() {
+        use crate::flow_monitor::{AttemptKind, RoutingAttempt};
+
+        let stats = Arc::new(RwLock::new(StatsAggregator::with_defaults()));
+        let tokens = Arc::new(RwLock::new(TokenTracker::with_defaults()));
+        let step = TelemetryStep::new(stats.clone(), tokens.clone());
+
+        let mut ctx = RequestContext::new("claude-sonnet-4-5".to_string());
+        ctx.record_attempt(RoutingAttempt {
+            kind: AttemptKind::Primary,
+            outcome: AttemptOutcome::Cancelled,
+            provider: ProviderType::Kiro,
+            credential_id: Some("cred-1".to_string()),
+            started_after_ms: 0,
+            ttfb_ms: None,
+            error: None,
+            wasted_tokens: 120,
+            model: None,
+            conversion: None,
+        });
+        ctx.record_attempt(RoutingAttempt {
+            kind: AttemptKind::Hedge,
+            outcome: AttemptOutcome::Won,
+            provider: ProviderType::Gemini,
+            credential_id: Some("cred-2".to_string()),
+            started_after_ms: 800,
+            ttfb_ms: Some(300),
+            error: None,
+            wasted_tokens: 0,
+            model: None,
+            conversion: None,
+        });
+
+        step.record_cancelled_attempts(&ctx);
+
+        let logs = stats.read().get_all();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].status, RequestStatus::Cancelled);
+        assert_eq!(logs[0].provider, ProviderType::Kiro);
+
+        let summary = tokens.read().summary(None, None);
+        assert_eq!(summary.wasted_tokens, 120);
+    }
 }
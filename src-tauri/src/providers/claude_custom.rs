@@ -245,6 +245,17 @@ impl ClaudeCustomProvider {
             anthropic_body["system"] = serde_json::json!(sys);
         }
 
+        // 结构化输出：通过强制调用单个工具让模型按 schema 输出
+        let forced_tool = request
+            .response_format
+            .as_ref()
+            .and_then(Self::response_format_to_tool);
+        if let Some(tool) = &forced_tool {
+            anthropic_body["tools"] = serde_json::json!([tool]);
+            anthropic_body["tool_choice"] =
+                serde_json::json!({"type": "tool", "name": tool["name"]});
+        }
+
         let api_key = self
             .config
             .api_key
@@ -298,11 +309,21 @@ impl ClaudeCustomProvider {
         let anthropic_resp: serde_json::Value = resp.json().await?;
 
         // 转换回 OpenAI 格式
-        let content = anthropic_resp["content"]
-            .as_array()
-            .and_then(|arr| arr.first())
-            .and_then(|block| block["text"].as_str())
-            .unwrap_or("");
+        let forced_output = forced_tool.as_ref().and_then(|tool| {
+            anthropic_resp["content"]
+                .as_array()?
+                .iter()
+                .find(|block| block["type"] == "tool_use" && block["name"] == tool["name"])
+                .map(|block| block["input"].to_string())
+        });
+        let content = match &forced_output {
+            Some(json) => json.as_str(),
+            None => anthropic_resp["content"]
+                .as_array()
+                .and_then(|arr| arr.first())
+                .and_then(|block| block["text"].as_str())
+                .unwrap_or(""),
+        };
 
         Ok(serde_json::json!({
             "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
@@ -328,6 +349,35 @@ impl ClaudeCustomProvider {
         }))
     }
 
+    /// 将 OpenAI response_format 转换为强制调用的 Anthropic 工具定义
+    ///
+    /// json_schema 使用其 schema 作为 input_schema，json_object 使用任意对象。
+    fn response_format_to_tool(format: &serde_json::Value) -> Option<serde_json::Value> {
+        match format.get("type").and_then(|t| t.as_str()) {
+            Some("json_schema") => {
+                let spec = &format["json_schema"];
+                let name = spec["name"].as_str().unwrap_or("structured_output");
+                let schema = spec
+                    .get("schema")
+                    .cloned()
+                    .unwrap_or_else(|| serde_json::json!({"type": "object"}));
+                Some(serde_json::json!({
+                    "name": name,
+                    "description": spec["description"]
+                        .as_str()
+                        .unwrap_or("Respond with output matching this schema"),
+                    "input_schema": schema
+                }))
+            }
+            Some("json_object") => Some(serde_json::json!({
+                "name": "json_output",
+                "description": "Respond with a JSON object",
+                "input_schema": {"type": "object"}
+            })),
+            _ => None,
+        }
+    }
+
     pub async fn messages(
         &self,
         request: &serde_json::Value,
@@ -0,0 +1,314 @@
+//! 跨协议回退链
+//!
+//! 支持为单个请求定义有序的回退链，当前一跳失败时按顺序切换到下一跳。
+//! 每一跳可以指向不同的 Provider（如 Kiro -> OpenAI Key -> Gemini），
+//! 由调用方在每一跳重新选择凭证并重新执行协议转换。
+//!
+//! 回退链来源（优先级从高到低）：
+//! - 请求 `model` 字段中以 `|` 分隔的链：`claude-sonnet-4-5|gpt-5|gemini-2.5-pro`
+//! - `X-ProxyCast-Fallback` 请求头：`gpt-5, gemini:gemini-2.5-pro`
+//! - 配置 `routing.fallback_chains`
+//!
+//! 每一跳的格式为 `model` 或 `provider:model`，仅当前缀是合法的 Provider 类型时才会拆分，
+//! 因此 `llama3:8b` 这类模型名会保持原样。
+
+use crate::ProviderType;
+use std::collections::HashMap;
+
+/// 回退链请求头名称
+pub const FALLBACK_HEADER: &str = "x-proxycast-fallback";
+
+/// `model` 字段中回退链的分隔符
+pub const MODEL_CHAIN_SEPARATOR: char = '|';
+
+/// 回退链中的单跳
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackHop {
+    /// 显式指定的 Provider（为 None 时由调用方推断）
+    pub provider: Option<ProviderType>,
+    /// 模型名
+    pub model: String,
+}
+
+impl FallbackHop {
+    /// 创建新的回退跳
+    pub fn new(provider: Option<ProviderType>, model: impl Into<String>) -> Self {
+        Self {
+            provider,
+            model: model.into(),
+        }
+    }
+
+    /// 解析单跳（`model` 或 `provider:model`）
+    ///
+    /// 空字符串返回 None
+    pub fn parse(spec: &str) -> Option<Self> {
+        let spec = spec.trim();
+        if spec.is_empty() {
+            return None;
+        }
+
+        if let Some((prefix, model)) = spec.split_once(':') {
+            if let Ok(provider) = prefix.trim().parse::<ProviderType>() {
+                let model = model.trim();
+                if model.is_empty() {
+                    return None;
+                }
+                return Some(Self::new(Some(provider), model));
+            }
+        }
+
+        Some(Self::new(None, spec))
+    }
+
+    /// 获取本跳的 Provider（显式指定优先，否则按模型名推断）
+    pub fn provider_hint(&self) -> Option<ProviderType> {
+        self.provider.or_else(|| infer_provider(&self.model))
+    }
+}
+
+impl std::fmt::Display for FallbackHop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.provider {
+            Some(provider) => write!(f, "{}:{}", provider, self.model),
+            None => write!(f, "{}", self.model),
+        }
+    }
+}
+
+/// 有序回退链
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FallbackChain {
+    hops: Vec<FallbackHop>,
+}
+
+impl FallbackChain {
+    /// 创建只有一跳的链（不回退）
+    pub fn single(model: &str) -> Self {
+        Self {
+            hops: vec![FallbackHop::new(None, model)],
+        }
+    }
+
+    /// 从多跳创建回退链（自动去除重复的跳）
+    pub fn from_hops(hops: Vec<FallbackHop>) -> Self {
+        let mut deduped: Vec<FallbackHop> = Vec::with_capacity(hops.len());
+        for hop in hops {
+            if !deduped.contains(&hop) {
+                deduped.push(hop);
+            }
+        }
+        Self { hops: deduped }
+    }
+
+    /// 解析以 `|` 或 `,` 分隔的回退链
+    pub fn parse(spec: &str) -> Self {
+        Self::from_hops(
+            spec.split([MODEL_CHAIN_SEPARATOR, ','])
+                .filter_map(FallbackHop::parse)
+                .collect(),
+        )
+    }
+
+    /// 首跳
+    pub fn primary(&self) -> Option<&FallbackHop> {
+        self.hops.first()
+    }
+
+    /// 所有跳（包含首跳）
+    pub fn hops(&self) -> &[FallbackHop] {
+        &self.hops
+    }
+
+    /// 跳数
+    pub fn len(&self) -> usize {
+        self.hops.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.hops.is_empty()
+    }
+
+    /// 是否包含回退跳
+    pub fn has_fallback(&self) -> bool {
+        self.hops.len() > 1
+    }
+}
+
+/// 回退链解析器
+///
+/// 持有配置中定义的回退链，并结合请求中的 `model` 字段和请求头得到最终回退链
+#[derive(Debug, Clone, Default)]
+pub struct FallbackResolver {
+    /// 模型名 -> 回退列表（不含模型本身）
+    chains: HashMap<String, Vec<String>>,
+}
+
+impl FallbackResolver {
+    /// 创建空的解析器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从配置创建解析器
+    pub fn from_chains(chains: HashMap<String, Vec<String>>) -> Self {
+        Self { chains }
+    }
+
+    /// 替换配置中的回退链
+    pub fn set_chains(&mut self, chains: HashMap<String, Vec<String>>) {
+        self.chains = chains;
+    }
+
+    /// 配置中的回退链数量
+    pub fn len(&self) -> usize {
+        self.chains.len()
+    }
+
+    /// 是否没有配置回退链
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+
+    /// 判断模型字段是否为内联回退链
+    pub fn is_inline_chain(model: &str) -> bool {
+        model.contains(MODEL_CHAIN_SEPARATOR)
+    }
+
+    /// 解析请求的回退链
+    ///
+    /// # Arguments
+    /// * `model` - 请求中的 `model` 字段（可能是内联回退链）
+    /// * `header` - `X-ProxyCast-Fallback` 请求头的值
+    pub fn resolve(&self, model: &str, header: Option<&str>) -> FallbackChain {
+        if Self::is_inline_chain(model) {
+            return FallbackChain::parse(model);
+        }
+
+        let mut hops = vec![FallbackHop::new(None, model)];
+
+        let extra: Vec<FallbackHop> = match header.map(str::trim).filter(|h| !h.is_empty()) {
+            Some(header) => FallbackChain::parse(header).hops,
+            None => self
+                .chains
+                .get(model)
+                .map(|list| list.iter().filter_map(|s| FallbackHop::parse(s)).collect())
+                .unwrap_or_default(),
+        };
+        hops.extend(extra);
+
+        FallbackChain::from_hops(hops)
+    }
+}
+
+/// 根据模型名推断 Provider
+///
+/// 只推断家族特征明显的模型，其余返回 None，由调用方沿用当前选择的 Provider
+pub fn infer_provider(model: &str) -> Option<ProviderType> {
+    let model = model.to_lowercase();
+    let is_openai_reasoning = model.len() > 1
+        && model.starts_with('o')
+        && model[1..].starts_with(|c: char| c.is_ascii_digit());
+
+    if model.starts_with("gpt-") || model.starts_with("chatgpt-") || is_openai_reasoning {
+        Some(ProviderType::OpenAI)
+    } else if model.starts_with("gemini-") {
+        Some(ProviderType::Gemini)
+    } else {
+        None
+    }
+}
+
+/// 判断上游响应状态码是否应触发回退
+///
+/// 认证失败、模型不存在、超时、限流和服务端错误都会切换到下一跳；
+/// 其余 4xx 视为请求本身的问题，不再回退
+pub fn should_fallback(status: u16) -> bool {
+    matches!(status, 401 | 403 | 404 | 408 | 429) || status >= 500
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hop_parse() {
+        assert_eq!(
+            FallbackHop::parse("gpt-5"),
+            Some(FallbackHop::new(None, "gpt-5"))
+        );
+        assert_eq!(
+            FallbackHop::parse(" gemini:gemini-2.5-pro "),
+            Some(FallbackHop::new(
+                Some(ProviderType::Gemini),
+                "gemini-2.5-pro"
+            ))
+        );
+        // 前缀不是 Provider 时保持原样
+        assert_eq!(
+            FallbackHop::parse("llama3:8b"),
+            Some(FallbackHop::new(None, "llama3:8b"))
+        );
+        assert_eq!(FallbackHop::parse("  "), None);
+        assert_eq!(FallbackHop::parse("openai:"), None);
+    }
+
+    #[test]
+    fn test_chain_parse_dedup() {
+        let chain = FallbackChain::parse("claude-sonnet-4-5|gpt-5|gemini-2.5-pro|gpt-5");
+        assert_eq!(chain.len(), 3);
+        assert!(chain.has_fallback());
+        assert_eq!(chain.primary().unwrap().model, "claude-sonnet-4-5");
+        assert_eq!(chain.hops()[2].model, "gemini-2.5-pro");
+    }
+
+    #[test]
+    fn test_resolve_priority() {
+        let mut chains = HashMap::new();
+        chains.insert(
+            "claude-sonnet-4-5".to_string(),
+            vec!["openai:gpt-5".to_string()],
+        );
+        let resolver = FallbackResolver::from_chains(chains);
+
+        // 内联链优先
+        let chain = resolver.resolve("claude-opus-4-5|gpt-5", Some("gemini-2.5-pro"));
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain.hops()[1].model, "gpt-5");
+
+        // 请求头次之
+        let chain = resolver.resolve("claude-sonnet-4-5", Some("gemini-2.5-pro"));
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain.hops()[1].model, "gemini-2.5-pro");
+
+        // 最后使用配置
+        let chain = resolver.resolve("claude-sonnet-4-5", None);
+        assert_eq!(
+            chain.hops()[1],
+            FallbackHop::new(Some(ProviderType::OpenAI), "gpt-5")
+        );
+
+        // 没有任何回退
+        let chain = resolver.resolve("claude-haiku-4-5", Some(" "));
+        assert!(!chain.has_fallback());
+    }
+
+    #[test]
+    fn test_infer_provider() {
+        assert_eq!(infer_provider("gpt-5"), Some(ProviderType::OpenAI));
+        assert_eq!(infer_provider("o3-mini"), Some(ProviderType::OpenAI));
+        assert_eq!(infer_provider("gemini-2.5-pro"), Some(ProviderType::Gemini));
+        assert_eq!(infer_provider("claude-sonnet-4-5"), None);
+        assert_eq!(infer_provider("ollama"), None);
+    }
+
+    #[test]
+    fn test_should_fallback() {
+        assert!(should_fallback(429));
+        assert!(should_fallback(503));
+        assert!(should_fallback(401));
+        assert!(!should_fallback(400));
+        assert!(!should_fallback(200));
+    }
+}
@@ -10,14 +10,22 @@
 //!
 //! 模型映射：
 //! - 支持模型别名映射（如 `gpt-4` -> `claude-sonnet-4-5-20250514`）
+//!
+//! 回退链：
+//! - 支持跨协议回退链（如 `claude-sonnet-4-5|gpt-5|gemini-2.5-pro`）
 
 mod amp_router;
+mod fallback;
 mod mapper;
 mod provider_router;
 mod route_registry;
 mod rules;
 
 pub use amp_router::{AmpRouteMatch, AmpRouter};
+pub use fallback::{
+    infer_provider, should_fallback, FallbackChain, FallbackHop, FallbackResolver, FALLBACK_HEADER,
+    MODEL_CHAIN_SEPARATOR,
+};
 pub use mapper::{ModelInfo, ModelMapper};
 pub use provider_router::ProviderRouter;
 pub use route_registry::{RegisteredRoute, RouteRegistry, RouteType};
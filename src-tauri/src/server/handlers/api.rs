@@ -27,24 +27,136 @@ use serde_json::json;
 use std::collections::HashMap;
 
 use crate::converter::anthropic_to_openai::convert_anthropic_to_openai;
+use crate::converter::protocol_selector::Protocol;
+use crate::credential::{CredentialPermit, PriorityClass, ScheduleError, PRIORITY_HEADER};
+use crate::database::DbConnection;
 use crate::flow_monitor::{
-    ClientInfo, FlowError, FlowErrorType, FlowMetadata, FlowType, InterceptAction, InterceptType,
-    LLMFlow, LLMRequest, LLMResponse, Message, MessageContent, MessageRole, RequestParameters,
-    RoutingInfo, TokenUsage,
+    ClientInfo, CredentialSource, FlowError, FlowErrorType, FlowMetadata, FlowType,
+    InterceptAction, InterceptType, LLMFlow, LLMRequest, LLMResponse, Message, MessageContent,
+    MessageRole, RequestParameters, RoutingInfo, TokenUsage,
 };
 use crate::models::anthropic::AnthropicMessagesRequest;
 use crate::models::openai::ChatCompletionRequest;
+use crate::models::provider_pool_model::ProviderCredential;
+use crate::processor::PromptCacheUsageSlot;
 use crate::processor::RequestContext;
+use crate::router::FALLBACK_HEADER;
 use crate::server::client_detector::ClientType;
 use crate::server::{record_request_telemetry, record_token_usage, AppState};
 use crate::server_utils::{
     build_anthropic_response, build_anthropic_stream_response, message_content_len,
     parse_cw_response, safe_truncate,
 };
+use crate::session::SessionManager;
 use crate::streaming::StreamFormat as StreamingFormat;
 use crate::ProviderType;
 
-use super::{call_provider_anthropic, call_provider_openai};
+use super::{
+    call_provider_anthropic_with_fallback, call_provider_openai_with_fallback, record_credential,
+    trace_routing, FallbackCallContext,
+};
+
+// ============================================================================
+// 并发调度辅助函数
+// ============================================================================
+
+/// 从凭证池获取凭证并占用并发槽位
+///
+/// 获取成功时许可写入 `permit`，由调用方在响应结束后释放
+async fn acquire_pool_credential(
+    state: &AppState,
+    db: &DbConnection,
+    provider: &str,
+    model: &str,
+    client_type: &ClientType,
+    priority: PriorityClass,
+    permit: &mut Option<CredentialPermit>,
+) -> Result<Option<ProviderCredential>, ScheduleError> {
+    let acquired = state
+        .pool_service
+        .acquire_credential(db, provider, Some(model), Some(client_type), priority)
+        .await?;
+
+    Ok(acquired.map(|(cred, acquired_permit)| {
+        if acquired_permit.queue_wait_ms() > 0 {
+            tracing::info!(
+                "[SCHEDULER] provider={} credential={} priority={} queued {}ms",
+                provider,
+                acquired_permit.credential_id(),
+                priority.as_str(),
+                acquired_permit.queue_wait_ms()
+            );
+        }
+        *permit = Some(acquired_permit);
+        cred
+    }))
+}
+
+/// 排队失败时返回 OpenAI 格式的 503 错误
+fn openai_schedule_error(e: &ScheduleError) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({
+            "error": {
+                "message": e.to_string(),
+                "type": "provider_busy",
+                "code": e.code()
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 排队失败时返回 Anthropic 格式的 503 错误
+fn anthropic_schedule_error(e: &ScheduleError) -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({
+            "type": "error",
+            "error": {
+                "type": "provider_busy",
+                "message": e.to_string(),
+                "code": e.code()
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 估算 OpenAI 格式请求的输入 Token 数（约 4 字符 = 1 token）
+fn estimate_openai_input_tokens(request: &ChatCompletionRequest) -> u32 {
+    request
+        .messages
+        .iter()
+        .map(|m| {
+            let content_len = match &m.content {
+                Some(c) => message_content_len(c),
+                None => 0,
+            };
+            content_len / 4
+        })
+        .sum::<usize>() as u32
+}
+
+/// 估算 Anthropic 格式请求的输入 Token 数（约 4 字符 = 1 token）
+fn estimate_anthropic_input_tokens(request: &AnthropicMessagesRequest) -> u32 {
+    request
+        .messages
+        .iter()
+        .map(|m| {
+            let content_len = match &m.content {
+                serde_json::Value::String(s) => s.len(),
+                serde_json::Value::Array(arr) => arr
+                    .iter()
+                    .filter_map(|v| v.get("text").and_then(|t| t.as_str()))
+                    .map(|s| s.len())
+                    .sum(),
+                _ => 0,
+            };
+            content_len / 4
+        })
+        .sum::<usize>() as u32
+}
 
 // ============================================================================
 // Flow 捕获辅助函数
@@ -300,6 +412,9 @@ fn build_flow_metadata(
         routing_info: RoutingInfo::default(),
         injected_params: None,
         context_usage_percentage: None,
+        unsupported_params: Vec::new(),
+        structured_output: None,
+        guardrail_events: Vec::new(),
     }
 }
 
@@ -643,6 +758,18 @@ pub async fn chat_completions(
     }
     eprintln!("[CHAT_COMPLETIONS] 认证成功");
 
+    // 解析回退链（内联 `a|b|c`、X-ProxyCast-Fallback 请求头或配置）
+    let requested_model = request.model.clone();
+    let fallback_header = headers.get(FALLBACK_HEADER).and_then(|v| v.to_str().ok());
+    let fallback_chain = state
+        .processor
+        .resolve_fallback_chain(&request.model, fallback_header)
+        .await;
+    let primary_hop = fallback_chain.primary().cloned();
+    if let Some(hop) = &primary_hop {
+        request.model = hop.model.clone();
+    }
+
     // 创建请求上下文
     let mut ctx = RequestContext::new(request.model.clone()).with_stream(request.stream);
     eprintln!("[CHAT_COMPLETIONS] 请求ID: {}", ctx.request_id);
@@ -699,7 +826,11 @@ pub async fn chat_completions(
 
     // 根据客户端类型选择 Provider
     // **Validates: Requirements 3.1, 3.3, 3.4**
-    let (selected_provider, client_type) = select_provider_for_client(&headers, &state).await;
+    let (mut selected_provider, client_type) = select_provider_for_client(&headers, &state).await;
+    // 回退链首跳显式指定了 Provider 时优先使用
+    if let Some(provider) = primary_hop.as_ref().and_then(|hop| hop.provider) {
+        selected_provider = provider.to_string();
+    }
     eprintln!(
         "[CHAT_COMPLETIONS] 客户端类型: {}, 选择的Provider: {}",
         client_type, selected_provider
@@ -729,6 +860,24 @@ pub async fn chat_completions(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_lowercase());
 
+    // 路由决策追踪：选定凭证后补全并附加到 Flow
+    let mut routing_trace = trace_routing(
+        &state,
+        &headers,
+        &requested_model,
+        &fallback_chain,
+        &request.model,
+        Some(SessionManager::extract_session_id(&request)),
+    )
+    .await;
+
+    // 并发调度：按请求头或客户端类型确定优先级，许可在响应结束时释放
+    let priority = state.pool_service.scheduler().priority_for(
+        headers.get(PRIORITY_HEADER).and_then(|v| v.to_str().ok()),
+        client_type.config_key(),
+    );
+    let mut permit: Option<CredentialPermit> = None;
+
     // 尝试从凭证池中选择凭证（带客户端兼容性检查）
     // 如果指定了 X-Provider-Id，优先使用它（不降级）
     // 否则使用 selected_provider
@@ -741,16 +890,26 @@ pub async fn chat_completions(
                     "[CHAT_COMPLETIONS] 使用 X-Provider-Id 指定的 provider: {}",
                     explicit_provider_id
                 );
-                let cred = state
-                    .pool_service
-                    .select_credential_with_client_check(
-                        db,
-                        explicit_provider_id,
-                        Some(&request.model),
-                        Some(&client_type),
-                    )
-                    .ok()
-                    .flatten();
+                let cred = match acquire_pool_credential(
+                    &state,
+                    db,
+                    explicit_provider_id,
+                    &request.model,
+                    &client_type,
+                    priority,
+                    &mut permit,
+                )
+                .await
+                {
+                    Ok(cred) => cred,
+                    Err(e) => {
+                        state.logs.write().await.add(
+                            "warn",
+                            &format!("[SCHEDULER] provider={} {}", explicit_provider_id, e),
+                        );
+                        return openai_schedule_error(&e);
+                    }
+                };
 
                 if cred.is_none() {
                     eprintln!(
@@ -784,16 +943,27 @@ pub async fn chat_completions(
                     "[CHAT_COMPLETIONS] 尝试从凭证池选择: provider={}, model={}",
                     selected_provider, request.model
                 );
-                let cred = state
-                    .pool_service
-                    .select_credential_with_client_check(
-                        db,
-                        &selected_provider,
-                        Some(&request.model),
-                        Some(&client_type),
-                    )
-                    .ok()
-                    .flatten();
+                let cred = match acquire_pool_credential(
+                    &state,
+                    db,
+                    &selected_provider,
+                    &request.model,
+                    &client_type,
+                    priority,
+                    &mut permit,
+                )
+                .await
+                {
+                    Ok(cred) => cred,
+                    Err(e) => {
+                        // 排队失败时降级到 API Key Provider
+                        state.logs.write().await.add(
+                            "warn",
+                            &format!("[SCHEDULER] provider={} {}", selected_provider, e),
+                        );
+                        None
+                    }
+                };
 
                 if cred.is_some() {
                     eprintln!(
@@ -816,7 +986,16 @@ pub async fn chat_completions(
         }
     };
 
+    if let Some(ref p) = permit {
+        ctx.set_queue_info(p.queue_wait_ms(), p.queue_depth() as u32);
+    }
+
     // 如果 Provider Pool 中没有找到凭证，尝试从 API Key Provider 获取（智能降级）
+    let credential_source = if credential.is_some() {
+        CredentialSource::Pool
+    } else {
+        CredentialSource::ApiKeyProvider
+    };
     let credential = if credential.is_none() {
         eprintln!("[CHAT_COMPLETIONS] Provider Pool 中未找到凭证，尝试 API Key Provider...");
 
@@ -1005,7 +1184,13 @@ pub async fn chat_completions(
             }
         });
 
-        let flow_metadata = build_flow_metadata(
+        record_credential(
+            &mut routing_trace,
+            &cred,
+            credential_source,
+            Protocol::OpenAI,
+        );
+        let mut flow_metadata = build_flow_metadata(
             provider_type,
             provider_display_name, // 使用 Provider 显示名称（如 "DeepSeek"）
             Some(&cred.uuid),
@@ -1013,6 +1198,7 @@ pub async fn chat_completions(
             &headers,
             &ctx.request_id,
         );
+        flow_metadata.routing_info.trace = Some(routing_trace);
         let flow_id = state
             .flow_monitor
             .start_flow(llm_request.clone(), flow_metadata.clone())
@@ -1047,7 +1233,22 @@ pub async fn chat_completions(
         }
 
         eprintln!("[CHAT_COMPLETIONS] 调用 Provider: {}", cred.provider_type);
-        let response = call_provider_openai(&state, &cred, &request, flow_id.as_deref()).await;
+        let response = call_provider_openai_with_fallback(
+            &state,
+            &cred,
+            &request,
+            FallbackCallContext {
+                chain: &fallback_chain,
+                default_provider: &selected_provider,
+                client_type: &client_type,
+                flow_id: flow_id.as_deref(),
+                priority,
+                permit,
+                estimated_input_tokens: estimate_openai_input_tokens(&request),
+                request_ctx: &mut ctx,
+            },
+        )
+        .await;
         eprintln!(
             "[CHAT_COMPLETIONS] Provider 响应状态: {}",
             response.status()
@@ -1226,17 +1427,7 @@ pub async fn chat_completions(
         } else {
             // 流式响应或没有 Flow 捕获，直接返回
             // 估算 Token 使用量（用于统计）
-            let estimated_input_tokens = request
-                .messages
-                .iter()
-                .map(|m| {
-                    let content_len = match &m.content {
-                        Some(c) => message_content_len(c),
-                        None => 0,
-                    };
-                    content_len / 4
-                })
-                .sum::<usize>() as u32;
+            let estimated_input_tokens = estimate_openai_input_tokens(&request);
             let estimated_output_tokens = if is_success { 100u32 } else { 0u32 };
 
             if is_success {
@@ -1304,7 +1495,7 @@ pub async fn chat_completions(
         .parse::<ProviderType>()
         .unwrap_or(ProviderType::OpenAI);
 
-    let flow_metadata = build_flow_metadata(
+    let mut flow_metadata = build_flow_metadata(
         provider_type,
         Some(&selected_provider),
         None,
@@ -1312,6 +1503,7 @@ pub async fn chat_completions(
         &headers,
         &ctx.request_id,
     );
+    flow_metadata.routing_info.trace = Some(routing_trace);
     let flow_id = state
         .flow_monitor
         .start_flow(llm_request.clone(), flow_metadata.clone())
@@ -1417,18 +1609,7 @@ pub async fn chat_completions(
                         // 估算 Token 数量（基于字符数，约 4 字符 = 1 token）
                         let estimated_output_tokens = (parsed.content.len() / 4) as u32;
                         // 估算输入 Token（基于请求消息）
-                        let estimated_input_tokens = request
-                            .messages
-                            .iter()
-                            .map(|m| {
-                                let content_len = match &m.content {
-                                    Some(c) => message_content_len(c),
-                                    None => 0,
-                                };
-                                content_len / 4
-                            })
-                            .sum::<usize>()
-                            as u32;
+                        let estimated_input_tokens = estimate_openai_input_tokens(&request);
 
                         let response = serde_json::json!({
                             "id": format!("chatcmpl-{}", uuid::Uuid::new_v4()),
@@ -1827,6 +2008,18 @@ pub async fn anthropic_messages(
         return e.into_response();
     }
 
+    // 解析回退链（内联 `a|b|c`、X-ProxyCast-Fallback 请求头或配置）
+    let requested_model = request.model.clone();
+    let fallback_header = headers.get(FALLBACK_HEADER).and_then(|v| v.to_str().ok());
+    let fallback_chain = state
+        .processor
+        .resolve_fallback_chain(&request.model, fallback_header)
+        .await;
+    let primary_hop = fallback_chain.primary().cloned();
+    if let Some(hop) = &primary_hop {
+        request.model = hop.model.clone();
+    }
+
     // 创建请求上下文
     let mut ctx = RequestContext::new(request.model.clone()).with_stream(request.stream);
 
@@ -1907,7 +2100,11 @@ pub async fn anthropic_messages(
 
     // 根据客户端类型选择 Provider
     // **Validates: Requirements 3.1, 3.3, 3.4**
-    let (selected_provider, client_type) = select_provider_for_client(&headers, &state).await;
+    let (mut selected_provider, client_type) = select_provider_for_client(&headers, &state).await;
+    // 回退链首跳显式指定了 Provider 时优先使用
+    if let Some(provider) = primary_hop.as_ref().and_then(|hop| hop.provider) {
+        selected_provider = provider.to_string();
+    }
 
     // 记录客户端检测和 Provider 选择结果
     state.logs.write().await.add(
@@ -1933,6 +2130,28 @@ pub async fn anthropic_messages(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_lowercase());
 
+    // 路由决策追踪：选定凭证后补全并附加到 Flow
+    let session_id = SessionManager::extract_session_id_from_json(
+        &serde_json::to_value(&request).unwrap_or_default(),
+        &request.model,
+    );
+    let mut routing_trace = trace_routing(
+        &state,
+        &headers,
+        &requested_model,
+        &fallback_chain,
+        &request.model,
+        Some(session_id),
+    )
+    .await;
+
+    // 并发调度：按请求头或客户端类型确定优先级，许可在响应结束时释放
+    let priority = state.pool_service.scheduler().priority_for(
+        headers.get(PRIORITY_HEADER).and_then(|v| v.to_str().ok()),
+        client_type.config_key(),
+    );
+    let mut permit: Option<CredentialPermit> = None;
+
     // 尝试从凭证池中选择凭证（带客户端兼容性检查）
     // 如果指定了 X-Provider-Id，优先使用它（不降级）
     // 否则使用 selected_provider
@@ -1944,16 +2163,26 @@ pub async fn anthropic_messages(
                     "[ANTHROPIC_MESSAGES] 使用 X-Provider-Id 指定的 provider: {}",
                     explicit_provider_id
                 );
-                let cred = state
-                    .pool_service
-                    .select_credential_with_client_check(
-                        db,
-                        explicit_provider_id,
-                        Some(&request.model),
-                        Some(&client_type),
-                    )
-                    .ok()
-                    .flatten();
+                let cred = match acquire_pool_credential(
+                    &state,
+                    db,
+                    explicit_provider_id,
+                    &request.model,
+                    &client_type,
+                    priority,
+                    &mut permit,
+                )
+                .await
+                {
+                    Ok(cred) => cred,
+                    Err(e) => {
+                        state.logs.write().await.add(
+                            "warn",
+                            &format!("[SCHEDULER] provider={} {}", explicit_provider_id, e),
+                        );
+                        return anthropic_schedule_error(&e);
+                    }
+                };
 
                 if cred.is_none() {
                     eprintln!(
@@ -1986,16 +2215,27 @@ pub async fn anthropic_messages(
                     "[ANTHROPIC_MESSAGES] 尝试从凭证池选择: provider={}, model={}",
                     selected_provider, request.model
                 );
-                let cred = state
-                    .pool_service
-                    .select_credential_with_client_check(
-                        db,
-                        &selected_provider,
-                        Some(&request.model),
-                        Some(&client_type),
-                    )
-                    .ok()
-                    .flatten();
+                let cred = match acquire_pool_credential(
+                    &state,
+                    db,
+                    &selected_provider,
+                    &request.model,
+                    &client_type,
+                    priority,
+                    &mut permit,
+                )
+                .await
+                {
+                    Ok(cred) => cred,
+                    Err(e) => {
+                        // 排队失败时降级到 API Key Provider
+                        state.logs.write().await.add(
+                            "warn",
+                            &format!("[SCHEDULER] provider={} {}", selected_provider, e),
+                        );
+                        None
+                    }
+                };
 
                 if cred.is_some() {
                     eprintln!(
@@ -2018,7 +2258,16 @@ pub async fn anthropic_messages(
         }
     };
 
+    if let Some(ref p) = permit {
+        ctx.set_queue_info(p.queue_wait_ms(), p.queue_depth() as u32);
+    }
+
     // 如果 Provider Pool 中没有找到凭证，尝试从 API Key Provider 获取（智能降级）
+    let credential_source = if credential.is_some() {
+        CredentialSource::Pool
+    } else {
+        CredentialSource::ApiKeyProvider
+    };
     let credential = if credential.is_none() {
         eprintln!("[ANTHROPIC_MESSAGES] Provider Pool 中未找到凭证，尝试 API Key Provider...");
 
@@ -2096,7 +2345,13 @@ pub async fn anthropic_messages(
             }
         });
 
-        let flow_metadata = build_flow_metadata(
+        record_credential(
+            &mut routing_trace,
+            &cred,
+            credential_source,
+            Protocol::Anthropic,
+        );
+        let mut flow_metadata = build_flow_metadata(
             provider_type,
             provider_display_name, // 使用 Provider 显示名称（如 "DeepSeek"）
             Some(&cred.uuid),
@@ -2104,6 +2359,7 @@ pub async fn anthropic_messages(
             &headers,
             &ctx.request_id,
         );
+        flow_metadata.routing_info.trace = Some(routing_trace);
         let flow_id = state
             .flow_monitor
             .start_flow(llm_request.clone(), flow_metadata.clone())
@@ -2141,7 +2397,23 @@ pub async fn anthropic_messages(
             }
         }
 
-        let response = call_provider_anthropic(&state, &cred, &request, flow_id.as_deref()).await;
+        let response = call_provider_anthropic_with_fallback(
+            &state,
+            &cred,
+            &request,
+            FallbackCallContext {
+                chain: &fallback_chain,
+                default_provider: &selected_provider,
+                client_type: &client_type,
+                flow_id: flow_id.as_deref(),
+                priority,
+                permit,
+                estimated_input_tokens: estimate_anthropic_input_tokens(&request),
+                request_ctx: &mut ctx,
+            },
+        )
+        .await;
+        let cache_usage = response.extensions().get::<PromptCacheUsageSlot>().cloned();
 
         // 记录请求统计
         let is_success = response.status().is_success();
@@ -2153,22 +2425,7 @@ pub async fn anthropic_messages(
         record_request_telemetry(&state, &ctx, status, None);
 
         // 估算 Token 使用量
-        let estimated_input_tokens = request
-            .messages
-            .iter()
-            .map(|m| {
-                let content_len = match &m.content {
-                    serde_json::Value::String(s) => s.len(),
-                    serde_json::Value::Array(arr) => arr
-                        .iter()
-                        .filter_map(|v| v.get("text").and_then(|t| t.as_str()))
-                        .map(|s| s.len())
-                        .sum(),
-                    _ => 0,
-                };
-                content_len / 4
-            })
-            .sum::<usize>() as u32;
+        let estimated_input_tokens = estimate_anthropic_input_tokens(&request);
         let estimated_output_tokens = if is_success { 100u32 } else { 0u32 };
 
         if is_success {
@@ -2178,17 +2435,25 @@ pub async fn anthropic_messages(
                 Some(estimated_input_tokens),
                 Some(estimated_output_tokens),
             );
+            // 缓存用量在响应体读完后才可知，由槽位在两者都就绪时写入
+            if let Some(slot) = &cache_usage {
+                slot.bind(state.processor.tokens.clone(), ctx.request_id.clone());
+            }
         }
 
         // 完成 Flow 捕获并检查响应拦截
         // **Validates: Requirements 2.1, 2.5**
         if let Some(fid) = flow_id {
             if is_success {
-                let llm_response = build_llm_response(
+                let mut llm_response = build_llm_response(
                     200,
                     "",
                     Some((estimated_input_tokens, estimated_output_tokens)),
                 );
+                if let Some(usage) = cache_usage.as_ref().and_then(|slot| slot.usage()) {
+                    llm_response.usage.cache_write_tokens = Some(usage.creation);
+                    llm_response.usage.cache_read_tokens = Some(usage.read);
+                }
 
                 // 检查是否需要拦截响应
                 if let Some(modified_response) = check_response_intercept(
@@ -2293,7 +2558,7 @@ pub async fn anthropic_messages(
         .parse::<ProviderType>()
         .unwrap_or(ProviderType::OpenAI);
 
-    let flow_metadata = build_flow_metadata(
+    let mut flow_metadata = build_flow_metadata(
         provider_type,
         Some(&selected_provider),
         None,
@@ -2301,6 +2566,7 @@ pub async fn anthropic_messages(
         &headers,
         &ctx.request_id,
     );
+    flow_metadata.routing_info.trace = Some(routing_trace);
     let flow_id = state
         .flow_monitor
         .start_flow(llm_request.clone(), flow_metadata.clone())
@@ -0,0 +1,473 @@
+//! Batch API 处理器
+//!
+//! - OpenAI：`/v1/files`、`/v1/batches`
+//! - Anthropic：`/v1/messages/batches`
+//!
+//! 队列中的请求通过 `HandlerBatchExecutor` 在进程内调用 `/v1/chat/completions`
+//! 与 `/v1/messages` 的处理器，与普通请求共用路由、凭证池与协议转换，
+//! 并以 `batch` 优先级参与凭证调度。
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::batch::models::{BatchApiFormat, BatchFile};
+use crate::batch::{multipart, BatchCallResult, BatchError, BatchExecutor};
+use crate::credential::PRIORITY_HEADER;
+use crate::models::anthropic::AnthropicMessagesRequest;
+use crate::models::openai::ChatCompletionRequest;
+use crate::server::AppState;
+
+use super::{anthropic_messages, chat_completions, verify_api_key, verify_api_key_anthropic};
+
+/// 经由常规处理器执行批处理请求
+pub struct HandlerBatchExecutor {
+    state: AppState,
+}
+
+impl HandlerBatchExecutor {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", self.state.api_key)) {
+            headers.insert(header::AUTHORIZATION, value);
+        }
+        if let Ok(value) = HeaderValue::from_str(&self.state.api_key) {
+            headers.insert("x-api-key", value);
+        }
+        headers.insert(PRIORITY_HEADER, HeaderValue::from_static("batch"));
+        headers.insert(
+            header::USER_AGENT,
+            HeaderValue::from_static("proxycast-batch"),
+        );
+        headers
+    }
+}
+
+#[async_trait]
+impl BatchExecutor for HandlerBatchExecutor {
+    async fn execute(&self, url: &str, mut body: Value) -> BatchCallResult {
+        if let Some(object) = body.as_object_mut() {
+            object.insert("stream".to_string(), Value::Bool(false));
+        }
+        let state = self.state.clone();
+        let headers = self.headers();
+        let response = if url == "/v1/messages" {
+            match serde_json::from_value::<AnthropicMessagesRequest>(body) {
+                Ok(request) => anthropic_messages(State(state), headers, Json(request)).await,
+                Err(e) => return invalid_body(e),
+            }
+        } else {
+            match serde_json::from_value::<ChatCompletionRequest>(body) {
+                Ok(request) => chat_completions(State(state), headers, Json(request)).await,
+                Err(e) => return invalid_body(e),
+            }
+        };
+
+        let status = response.status().as_u16();
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let body = match axum::body::to_bytes(response.into_body(), usize::MAX).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned())),
+            Err(e) => json!({"error": {"message": format!("读取响应失败: {}", e)}}),
+        };
+        BatchCallResult {
+            status,
+            body,
+            retry_after,
+        }
+    }
+}
+
+fn invalid_body(e: serde_json::Error) -> BatchCallResult {
+    BatchCallResult {
+        status: 400,
+        body: json!({
+            "error": {
+                "message": format!("Invalid request body: {}", e),
+                "type": "invalid_request_error",
+            }
+        }),
+        retry_after: None,
+    }
+}
+
+fn status_of(e: &BatchError) -> StatusCode {
+    StatusCode::from_u16(e.status_code()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn openai_error(e: BatchError) -> Response {
+    let error_type = match e {
+        BatchError::Database(_) => "server_error",
+        _ => "invalid_request_error",
+    };
+    (
+        status_of(&e),
+        Json(json!({
+            "error": {
+                "message": e.to_string(),
+                "type": error_type,
+                "param": null,
+                "code": null,
+            }
+        })),
+    )
+        .into_response()
+}
+
+fn anthropic_error(e: BatchError) -> Response {
+    let error_type = match e {
+        BatchError::NotFound(_) => "not_found_error",
+        BatchError::Invalid(_) | BatchError::Conflict(_) => "invalid_request_error",
+        BatchError::Disabled | BatchError::Database(_) => "api_error",
+    };
+    (
+        status_of(&e),
+        Json(json!({
+            "type": "error",
+            "error": {"type": error_type, "message": e.to_string()},
+        })),
+    )
+        .into_response()
+}
+
+fn jsonl_response(content: Vec<u8>) -> Response {
+    ([(header::CONTENT_TYPE, "application/jsonl")], content).into_response()
+}
+
+// ============================================================================
+// Files
+// ============================================================================
+
+/// 文件列表查询参数
+#[derive(Debug, Deserialize)]
+pub struct ListFilesQuery {
+    pub purpose: Option<String>,
+}
+
+/// POST /v1/files（multipart/form-data，字段 `purpose` 与 `file`）
+pub async fn batch_files_create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    let Some(boundary) = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(multipart::boundary)
+    else {
+        return openai_error(BatchError::Invalid(
+            "Content-Type must be multipart/form-data".to_string(),
+        ));
+    };
+    let parts = match multipart::parse(&body, &boundary) {
+        Ok(parts) => parts,
+        Err(e) => return openai_error(BatchError::Invalid(e)),
+    };
+
+    let purpose = parts
+        .iter()
+        .find(|p| p.name == "purpose")
+        .map(|p| String::from_utf8_lossy(&p.data).trim().to_string());
+    let file = parts.into_iter().find(|p| p.name == "file");
+    let (Some(purpose), Some(file)) = (purpose, file) else {
+        return openai_error(BatchError::Invalid(
+            "Both 'purpose' and 'file' fields are required".to_string(),
+        ));
+    };
+    let filename = file.filename.unwrap_or_else(|| "upload.jsonl".to_string());
+
+    match state
+        .batch_queue
+        .upload_file(&filename, &purpose, file.data)
+    {
+        Ok(file) => Json(file.to_openai()).into_response(),
+        Err(e) => openai_error(e),
+    }
+}
+
+/// GET /v1/files
+pub async fn batch_files_list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ListFilesQuery>,
+) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.list_files(query.purpose.as_deref()) {
+        Ok(files) => Json(json!({
+            "object": "list",
+            "data": files.iter().map(BatchFile::to_openai).collect::<Vec<_>>(),
+        }))
+        .into_response(),
+        Err(e) => openai_error(e),
+    }
+}
+
+/// GET /v1/files/{id}
+pub async fn batch_files_get(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.get_file(&id) {
+        Ok(file) => Json(file.to_openai()).into_response(),
+        Err(e) => openai_error(e),
+    }
+}
+
+/// DELETE /v1/files/{id}
+pub async fn batch_files_delete(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.delete_file(&id) {
+        Ok(()) => Json(json!({"id": id, "object": "file", "deleted": true})).into_response(),
+        Err(e) => openai_error(e),
+    }
+}
+
+/// GET /v1/files/{id}/content
+pub async fn batch_files_content(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.file_content(&id) {
+        Ok(content) => jsonl_response(content),
+        Err(e) => openai_error(e),
+    }
+}
+
+// ============================================================================
+// OpenAI Batches
+// ============================================================================
+
+/// 创建任务请求
+#[derive(Debug, Deserialize)]
+pub struct CreateBatchRequest {
+    pub input_file_id: String,
+    pub endpoint: String,
+    pub completion_window: String,
+    #[serde(default)]
+    pub metadata: Option<Value>,
+}
+
+/// 任务列表查询参数
+#[derive(Debug, Deserialize)]
+pub struct ListBatchesQuery {
+    pub limit: Option<usize>,
+    pub after: Option<String>,
+    pub after_id: Option<String>,
+}
+
+/// POST /v1/batches
+pub async fn batches_create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<CreateBatchRequest>,
+) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.create_openai(
+        &request.input_file_id,
+        &request.endpoint,
+        &request.completion_window,
+        request.metadata,
+    ) {
+        Ok((job, counts)) => Json(job.to_openai(&counts)).into_response(),
+        Err(e) => openai_error(e),
+    }
+}
+
+/// GET /v1/batches
+pub async fn batches_list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ListBatchesQuery>,
+) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.list(
+        BatchApiFormat::OpenAI,
+        query.limit.unwrap_or(20),
+        query.after.as_deref(),
+    ) {
+        Ok((jobs, has_more)) => Json(json!({
+            "object": "list",
+            "data": jobs.iter().map(|(job, counts)| job.to_openai(counts)).collect::<Vec<_>>(),
+            "first_id": jobs.first().map(|(job, _)| &job.id),
+            "last_id": jobs.last().map(|(job, _)| &job.id),
+            "has_more": has_more,
+        }))
+        .into_response(),
+        Err(e) => openai_error(e),
+    }
+}
+
+/// GET /v1/batches/{id}
+pub async fn batches_get(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.get(&id, BatchApiFormat::OpenAI) {
+        Ok((job, counts)) => Json(job.to_openai(&counts)).into_response(),
+        Err(e) => openai_error(e),
+    }
+}
+
+/// POST /v1/batches/{id}/cancel
+pub async fn batches_cancel(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.cancel(&id, BatchApiFormat::OpenAI) {
+        Ok((job, counts)) => Json(job.to_openai(&counts)).into_response(),
+        Err(e) => openai_error(e),
+    }
+}
+
+// ============================================================================
+// Anthropic Message Batches
+// ============================================================================
+
+/// POST /v1/messages/batches
+pub async fn message_batches_create(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> Response {
+    if let Err(e) = verify_api_key_anthropic(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.create_anthropic(&payload) {
+        Ok((job, counts)) => Json(job.to_anthropic(&counts)).into_response(),
+        Err(e) => anthropic_error(e),
+    }
+}
+
+/// GET /v1/messages/batches
+pub async fn message_batches_list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ListBatchesQuery>,
+) -> Response {
+    if let Err(e) = verify_api_key_anthropic(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.list(
+        BatchApiFormat::Anthropic,
+        query.limit.unwrap_or(20),
+        query.after_id.as_deref(),
+    ) {
+        Ok((jobs, has_more)) => Json(json!({
+            "data": jobs.iter().map(|(job, counts)| job.to_anthropic(counts)).collect::<Vec<_>>(),
+            "first_id": jobs.first().map(|(job, _)| &job.id),
+            "last_id": jobs.last().map(|(job, _)| &job.id),
+            "has_more": has_more,
+        }))
+        .into_response(),
+        Err(e) => anthropic_error(e),
+    }
+}
+
+/// GET /v1/messages/batches/{id}
+pub async fn message_batches_get(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = verify_api_key_anthropic(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.get(&id, BatchApiFormat::Anthropic) {
+        Ok((job, counts)) => Json(job.to_anthropic(&counts)).into_response(),
+        Err(e) => anthropic_error(e),
+    }
+}
+
+/// DELETE /v1/messages/batches/{id}
+pub async fn message_batches_delete(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = verify_api_key_anthropic(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.delete(&id, BatchApiFormat::Anthropic) {
+        Ok(()) => Json(json!({"id": id, "type": "message_batch_deleted"})).into_response(),
+        Err(e) => anthropic_error(e),
+    }
+}
+
+/// POST /v1/messages/batches/{id}/cancel
+pub async fn message_batches_cancel(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = verify_api_key_anthropic(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.cancel(&id, BatchApiFormat::Anthropic) {
+        Ok((job, counts)) => Json(job.to_anthropic(&counts)).into_response(),
+        Err(e) => anthropic_error(e),
+    }
+}
+
+/// GET /v1/messages/batches/{id}/results
+pub async fn message_batches_results(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Response {
+    if let Err(e) = verify_api_key_anthropic(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    match state.batch_queue.anthropic_results(&id) {
+        Ok(content) => jsonl_response(content),
+        Err(e) => anthropic_error(e),
+    }
+}
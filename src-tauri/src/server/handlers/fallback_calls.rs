@@ -0,0 +1,953 @@
+//! 跨协议回退调用
+//!
+//! 按回退链依次调用上游 Provider。每一跳都会通过并发调度器重新获取凭证和许可
+//! （与首跳共用请求优先级），并通过 `ProtocolSelector::select_path` 重新确定协议转换路径，
+//! 因此可以从 Kiro 回退到 OpenAI Key 或 Gemini 凭证。
+//!
+//! # 对冲请求
+//!
+//! 配置了 `retry.hedge_delay_ms` 时，首跳在阈值内未返回则向同 Provider 的
+//! 另一个凭证发起相同请求，采用先返回的一方（见 `ProviderStep::execute_with_hedging`）。
+//! 最终返回的响应体会统计首字节时间，用于按 p95 调整对冲阈值。
+//!
+//! # 流式请求
+//!
+//! 上游调用返回 `Response` 时只确定了状态码，响应体尚未发送给客户端。
+//! 因此仅在状态码表示失败时回退，即流式响应只会在首字节之前回退；
+//! 一旦开始返回成功响应，后续错误由流式处理自身负责。
+
+use axum::body::Body;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use parking_lot::Mutex;
+use std::future::Future;
+use std::time::Instant;
+
+use crate::converter::protocol_selector::{Protocol, ProtocolSelector};
+use crate::credential::{CredentialPermit, PriorityClass, QuotaManager, ScheduleError};
+use crate::database::DbConnection;
+use crate::flow_monitor::{AttemptKind, AttemptOutcome, RoutingAttempt};
+use crate::models::anthropic::AnthropicMessagesRequest;
+use crate::models::openai::ChatCompletionRequest;
+use crate::models::provider_pool_model::{PoolProviderType, ProviderCredential};
+use crate::orchestrator::get_global_orchestrator;
+use crate::processor::{HedgePlan, HedgeTarget, ProviderCallError, RequestContext, TelemetryStep};
+use crate::router::{should_fallback, FallbackChain};
+use crate::server::client_detector::ClientType;
+use crate::server::AppState;
+use crate::session::{extract_retry_delay, RateLimitReason};
+use crate::streaming::StreamMetrics;
+
+use super::{call_provider_anthropic_guarded, call_provider_openai_guarded};
+
+/// 回退调用上下文
+pub struct FallbackCallContext<'a> {
+    /// 回退链（首跳已由调用方选好凭证）
+    pub chain: &'a FallbackChain,
+    /// 未指定 Provider 的跳使用的 Provider
+    pub default_provider: &'a str,
+    /// 客户端类型（用于凭证兼容性检查）
+    pub client_type: &'a ClientType,
+    /// Flow ID
+    pub flow_id: Option<&'a str>,
+    /// 请求优先级（回退跳和对冲请求通过调度器获取凭证时使用）
+    pub priority: PriorityClass,
+    /// 首跳凭证的并发许可（随最终采用的响应体释放）
+    pub permit: Option<CredentialPermit>,
+    /// 估算的输入 Token 数（统计被取消的对冲调用）
+    pub estimated_input_tokens: u32,
+    /// 请求上下文（记录调用尝试，对冲胜出时更新 Provider 和凭证）
+    pub request_ctx: &'a mut RequestContext,
+}
+
+/// 单次上游调用的结果
+struct CallOutcome {
+    response: Response,
+    permit: Option<CredentialPermit>,
+    /// 发起调用的凭证和模型（响应体结束时反馈给自适应策略）
+    credential_id: String,
+    model: String,
+    /// 本次调用的发起时间（计算首字节时间）
+    started_at: DateTime<Utc>,
+}
+
+/// 按回退链调用 Provider (OpenAI 格式)
+pub async fn call_provider_openai_with_fallback(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &ChatCompletionRequest,
+    fallback: FallbackCallContext<'_>,
+) -> Response {
+    let flow_id = fallback.flow_id;
+    run_fallback_chain(
+        state,
+        credential,
+        request,
+        Protocol::OpenAI,
+        fallback,
+        |req, model| req.model = model.to_string(),
+        |cred, req| async move {
+            let response = call_provider_openai_guarded(state, &cred, &req, flow_id).await;
+            observe_adaptive(&cred, &req.model, &response);
+            observe_cooldown(state, &cred, &response);
+            response
+        },
+    )
+    .await
+}
+
+/// 按回退链调用 Provider (Anthropic 格式)
+pub async fn call_provider_anthropic_with_fallback(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &AnthropicMessagesRequest,
+    fallback: FallbackCallContext<'_>,
+) -> Response {
+    let flow_id = fallback.flow_id;
+    run_fallback_chain(
+        state,
+        credential,
+        request,
+        Protocol::Anthropic,
+        fallback,
+        |req, model| req.model = model.to_string(),
+        |cred, req| async move {
+            let response = call_provider_anthropic_guarded(state, &cred, &req, flow_id).await;
+            observe_adaptive(&cred, &req.model, &response);
+            observe_cooldown(state, &cred, &response);
+            response
+        },
+    )
+    .await
+}
+
+/// 依次执行回退链中的每一跳，直到成功或链耗尽
+async fn run_fallback_chain<R, S, F, Fut>(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &R,
+    source: Protocol,
+    fallback: FallbackCallContext<'_>,
+    set_model: S,
+    call: F,
+) -> Response
+where
+    R: Clone,
+    S: Fn(&mut R, &str),
+    F: Fn(ProviderCredential, R) -> Fut,
+    Fut: Future<Output = Response>,
+{
+    let FallbackCallContext {
+        chain,
+        default_provider,
+        client_type,
+        flow_id,
+        priority,
+        permit,
+        estimated_input_tokens,
+        request_ctx: ctx,
+    } = fallback;
+
+    let started = Instant::now();
+    let first_attempt = ctx.attempts.len();
+
+    let hedge = HedgeCall {
+        state,
+        credential,
+        request,
+        source,
+        client_type,
+        priority,
+        estimated_input_tokens,
+    };
+    let mut outcome = hedge.call_primary(ctx, permit, &call).await;
+
+    for hop in chain.hops().iter().skip(1) {
+        let status = outcome.response.status().as_u16();
+        if !should_fallback(status) {
+            break;
+        }
+        // 先释放上一跳的并发许可，避免回退到同一凭证时排队等待自己
+        drop(outcome.permit.take());
+
+        let model = state.processor.resolve_model(&hop.model).await;
+        let provider = hop
+            .provider_hint()
+            .map(|p| p.to_string())
+            .unwrap_or_else(|| default_provider.to_string());
+
+        let started_after_ms = started.elapsed().as_millis() as u64;
+        let (hop_cred, hop_permit) =
+            match acquire_hop_credential(state, &provider, &model, client_type, priority).await {
+                Ok(Some(acquired)) => acquired,
+                failed => {
+                    let error = match failed {
+                        Err(e) => e.to_string(),
+                        Ok(_) => format!("no credentials for provider '{}'", provider),
+                    };
+                    tracing::warn!(
+                        "[FALLBACK] 回退跳 {} 无法获取凭证 (provider={}): {}",
+                        hop,
+                        provider,
+                        error
+                    );
+                    ctx.record_attempt(RoutingAttempt {
+                        kind: AttemptKind::Failover,
+                        outcome: AttemptOutcome::Failed,
+                        provider: hop.provider_hint().unwrap_or(credential.provider_type),
+                        credential_id: None,
+                        started_after_ms,
+                        ttfb_ms: None,
+                        error: Some(error),
+                        wasted_tokens: 0,
+                        model: Some(model),
+                        conversion: None,
+                    });
+                    continue;
+                }
+            };
+
+        tracing::info!(
+            "[FALLBACK] 上游返回 {}，回退到 {} (provider={}, credential={})",
+            status,
+            hop,
+            hop_cred.provider_type,
+            hop_cred.uuid
+        );
+        state.logs.write().await.add(
+            "warn",
+            &format!(
+                "[FALLBACK] status={} next_hop={} provider={}",
+                status, hop, hop_cred.provider_type
+            ),
+        );
+
+        let mut hop_request = request.clone();
+        set_model(&mut hop_request, &model);
+
+        let hop_started = Instant::now();
+        let started_at = Utc::now();
+        let response = call(hop_cred.clone(), hop_request).await;
+        ctx.record_attempt(build_hop_attempt(
+            AttemptKind::Failover,
+            &hop_cred,
+            Some(model.clone()),
+            source,
+            started_after_ms,
+            &response,
+            hop_started,
+        ));
+        outcome = CallOutcome {
+            response,
+            permit: hop_permit,
+            credential_id: hop_cred.uuid,
+            model,
+            started_at,
+        };
+    }
+
+    if ctx.attempts.iter().any(|a| a.kind == AttemptKind::Hedge) {
+        TelemetryStep::new(
+            state.processor.stats.clone(),
+            state.processor.tokens.clone(),
+        )
+        .record_cancelled_attempts(ctx);
+    }
+
+    let attempts = &ctx.attempts[first_attempt..];
+    if chain.has_fallback() || attempts.len() > 1 {
+        if let Some(fid) = flow_id {
+            state
+                .flow_monitor
+                .record_attempts(fid, attempts.to_vec())
+                .await;
+        }
+    }
+
+    let CallOutcome {
+        response,
+        permit,
+        credential_id,
+        model,
+        started_at,
+    } = outcome;
+    let response = if response.status().is_success() {
+        let ttfb_window = state.processor.provider_step.ttfb_window().clone();
+        observe_stream(response, started_at, move |metrics| {
+            ttfb_window.record_metrics(metrics);
+            if let Some(orchestrator) = get_global_orchestrator() {
+                orchestrator
+                    .adaptive()
+                    .observe_stream(&credential_id, &model, metrics);
+            }
+        })
+    } else {
+        response
+    };
+    hold_permit(response, permit)
+}
+
+/// 首跳调用（可能带对冲）所需的参数
+struct HedgeCall<'a, R> {
+    state: &'a AppState,
+    credential: &'a ProviderCredential,
+    request: &'a R,
+    source: Protocol,
+    client_type: &'a ClientType,
+    priority: PriorityClass,
+    estimated_input_tokens: u32,
+}
+
+impl<R: Clone> HedgeCall<'_, R> {
+    /// 调用首跳
+    ///
+    /// 启用对冲且同 Provider 存在其他可用凭证时交给 `ProviderStep::execute_with_hedging`，
+    /// 备用凭证在对冲发起时才通过调度器获取并发许可
+    async fn call_primary<F, Fut>(
+        &self,
+        ctx: &mut RequestContext,
+        permit: Option<CredentialPermit>,
+        call: &F,
+    ) -> CallOutcome
+    where
+        F: Fn(ProviderCredential, R) -> Fut,
+        Fut: Future<Output = Response>,
+    {
+        let step = &self.state.processor.provider_step;
+        let backup = match (step.hedge_threshold(), self.state.db.as_ref()) {
+            (Some(_), Some(db)) => self.select_backup(db, &ctx.resolved_model),
+            _ => None,
+        };
+
+        let Some(backup) = backup else {
+            let attempt_start = Instant::now();
+            let started_at = Utc::now();
+            let response = call(self.credential.clone(), self.request.clone()).await;
+            ctx.record_attempt(build_hop_attempt(
+                AttemptKind::Primary,
+                self.credential,
+                None,
+                self.source,
+                0,
+                &response,
+                attempt_start,
+            ));
+            return CallOutcome {
+                response,
+                permit,
+                credential_id: self.credential.uuid.clone(),
+                model: ctx.resolved_model.clone(),
+                started_at,
+            };
+        };
+
+        let plan = HedgePlan::new(HedgeTarget::new(
+            self.credential.provider_type,
+            Some(self.credential.uuid.clone()),
+        ))
+        .with_backup(HedgeTarget::new(
+            backup.provider_type,
+            Some(backup.uuid.clone()),
+        ))
+        .with_estimated_input_tokens(self.estimated_input_tokens);
+
+        // 两方共用同一个工厂：首选凭证使用调用方的许可，备用凭证在发起时获取许可
+        let mut primary_permit = permit;
+        let failed_primary: Mutex<Option<CallOutcome>> = Mutex::new(None);
+        let first_attempt = ctx.attempts.len();
+        let model = ctx.resolved_model.clone();
+
+        let failed = &failed_primary;
+        let model = model.as_str();
+        let backup_id = backup.uuid.as_str();
+        let result = step
+            .execute_with_hedging(ctx, plan, move |target, token| {
+                let is_primary =
+                    target.credential_id.as_deref() == Some(self.credential.uuid.as_str());
+                let permit = if is_primary {
+                    primary_permit.take()
+                } else {
+                    None
+                };
+                let attempt = async move {
+                    let (credential, permit) = if is_primary {
+                        (self.credential.clone(), permit)
+                    } else {
+                        let (credential, permit) = self.acquire_backup(model, backup_id).await?;
+                        (credential, Some(permit))
+                    };
+
+                    let credential_id = credential.uuid.clone();
+                    let started_at = Utc::now();
+                    let response = call(credential, self.request.clone()).await;
+                    let status = response.status().as_u16();
+                    let outcome = CallOutcome {
+                        response,
+                        permit,
+                        credential_id,
+                        model: model.to_string(),
+                        started_at,
+                    };
+                    if !should_fallback(status) {
+                        return Ok(outcome);
+                    }
+
+                    if is_primary {
+                        *failed.lock() = Some(outcome);
+                    }
+                    Err(ProviderCallError::failover(
+                        format!("upstream status {}", status),
+                        Some(status),
+                    ))
+                };
+                // 竞速落败时立即中止上游调用（包括仍在排队等待许可的备用凭证）
+                async move {
+                    tokio::select! {
+                        biased;
+                        _ = token.cancelled() => {
+                            Err(ProviderCallError::fatal("hedge race lost", None))
+                        }
+                        result = attempt => result,
+                    }
+                }
+            })
+            .await;
+
+        for attempt in &mut ctx.attempts[first_attempt..] {
+            attempt.conversion = Some(conversion_label(self.source, attempt.provider));
+        }
+
+        match result {
+            Ok(outcome) => outcome,
+            Err(err) => failed_primary.into_inner().unwrap_or_else(|| CallOutcome {
+                response: (
+                    StatusCode::BAD_GATEWAY,
+                    Json(serde_json::json!({"error": {"message": err.message}})),
+                )
+                    .into_response(),
+                permit: None,
+                credential_id: self.credential.uuid.clone(),
+                model: model.to_string(),
+                started_at: Utc::now(),
+            }),
+        }
+    }
+
+    /// 选择对冲用的备用凭证：同 Provider、不同于首选凭证、未冷却且未达到并发上限
+    fn select_backup(&self, db: &DbConnection, model: &str) -> Option<ProviderCredential> {
+        let scheduler = self.state.pool_service.scheduler();
+        let provider = self.credential.provider_type.to_string();
+        self.state
+            .pool_service
+            .select_credential_filtered(
+                db,
+                &provider,
+                Some(model),
+                Some(self.client_type),
+                &|c: &ProviderCredential| {
+                    c.uuid != self.credential.uuid
+                        && !scheduler.is_cooling(&c.uuid)
+                        && !scheduler.is_at_capacity(&c.uuid)
+                },
+            )
+            .ok()
+            .flatten()
+    }
+
+    /// 通过调度器获取备用凭证的并发许可（凭证已满时排队）
+    async fn acquire_backup(
+        &self,
+        model: &str,
+        backup_id: &str,
+    ) -> Result<(ProviderCredential, CredentialPermit), ProviderCallError> {
+        let db = self
+            .state
+            .db
+            .as_ref()
+            .ok_or_else(|| ProviderCallError::fatal("database unavailable", None))?;
+        let provider = self.credential.provider_type.to_string();
+        self.state
+            .pool_service
+            .acquire_credential_filtered(
+                db,
+                &provider,
+                Some(model),
+                Some(self.client_type),
+                self.priority,
+                &|c: &ProviderCredential| c.uuid == backup_id,
+            )
+            .await
+            .map_err(|e| ProviderCallError::failover(e.to_string(), None))?
+            .ok_or_else(|| {
+                ProviderCallError::failover(
+                    format!("hedge credential '{}' unavailable", backup_id),
+                    None,
+                )
+            })
+    }
+}
+
+/// 将并发许可绑定到响应体
+///
+/// 流式响应在最后一个 chunk 发送完毕（或客户端断开）时才释放槽位
+fn hold_permit(response: Response, permit: Option<CredentialPermit>) -> Response {
+    let Some(permit) = permit else {
+        return response;
+    };
+    let (parts, body) = response.into_parts();
+    let stream = body.into_data_stream().map(move |chunk| {
+        let _held = &permit;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+/// 响应体流式指标观察器
+///
+/// 随响应体一起释放（发送完毕或客户端断开），释放时回调一次
+struct StreamObserver<F: FnOnce(&StreamMetrics)> {
+    metrics: StreamMetrics,
+    on_finish: Option<F>,
+}
+
+impl<F: FnOnce(&StreamMetrics)> Drop for StreamObserver<F> {
+    fn drop(&mut self) {
+        self.metrics.finish();
+        if let Some(on_finish) = self.on_finish.take() {
+            on_finish(&self.metrics);
+        }
+    }
+}
+
+/// 统计响应体的流式指标（首字节时间从 `started_at` 起算）
+fn observe_stream<F>(response: Response, started_at: DateTime<Utc>, on_finish: F) -> Response
+where
+    F: FnOnce(&StreamMetrics) + Send + 'static,
+{
+    let mut observer = StreamObserver {
+        metrics: StreamMetrics {
+            start_time: started_at,
+            ..StreamMetrics::new()
+        },
+        on_finish: Some(on_finish),
+    };
+    let (parts, body) = response.into_parts();
+    let stream = body.into_data_stream().map(move |chunk| {
+        if let Ok(bytes) = &chunk {
+            if !bytes.is_empty() {
+                observer.metrics.record_chunk(bytes.len());
+            }
+        }
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+/// 将上游响应结果反馈给自适应编排策略
+///
+/// 首字节时间和吞吐在响应体结束时由 `observe_stream` 反馈
+fn observe_adaptive(credential: &ProviderCredential, model: &str, response: &Response) {
+    if let Some(orchestrator) = get_global_orchestrator() {
+        orchestrator.adaptive().observe_response(
+            &credential.uuid,
+            model,
+            response.status().is_success(),
+        );
+    }
+}
+
+/// 将上游限流响应反馈给调度器的冷却来源
+///
+/// 上游给出重试等待时间时按限流记录，否则按配额超限进入配置的冷却期；
+/// 冷却到期时调度器唤醒排队的请求
+fn observe_cooldown(state: &AppState, credential: &ProviderCredential, response: &Response) {
+    let status = response.status();
+    if status.is_success() {
+        state
+            .processor
+            .rate_limits
+            .clear_rate_limit(&credential.uuid);
+        return;
+    }
+    if !QuotaManager::is_quota_exceeded_error(Some(status.as_u16()), "") {
+        return;
+    }
+
+    match extract_retry_delay(Some(response.headers()), None) {
+        Some(delay) => {
+            state.processor.rate_limits.mark_rate_limited(
+                &credential.uuid,
+                RateLimitReason::RateLimitExceeded,
+                Some(delay),
+                None,
+            );
+        }
+        None => {
+            state
+                .processor
+                .quota
+                .mark_quota_exceeded(&credential.uuid, &format!("upstream status {}", status));
+        }
+    }
+}
+
+/// 为回退跳获取凭证
+///
+/// 先通过调度器从凭证池获取（占用并发槽位，凭证已满时按优先级排队），
+/// 凭证池没有可用凭证时再尝试 API Key Provider（不受调度器管理，没有许可）
+async fn acquire_hop_credential(
+    state: &AppState,
+    provider: &str,
+    model: &str,
+    client_type: &ClientType,
+    priority: PriorityClass,
+) -> Result<Option<(ProviderCredential, Option<CredentialPermit>)>, ScheduleError> {
+    let Some(db) = state.db.as_ref() else {
+        return Ok(None);
+    };
+
+    if let Some((cred, permit)) = state
+        .pool_service
+        .acquire_credential(db, provider, Some(model), Some(client_type), priority)
+        .await?
+    {
+        return Ok(Some((cred, Some(permit))));
+    }
+
+    let pool_type = provider
+        .parse::<PoolProviderType>()
+        .unwrap_or(PoolProviderType::OpenAI);
+    match state
+        .api_key_service
+        .get_fallback_credential(db, &pool_type, Some(provider), Some(client_type))
+        .await
+    {
+        Ok(cred) => Ok(cred.map(|cred| (cred, None))),
+        Err(e) => {
+            tracing::warn!("[FALLBACK] 获取 API Key Provider 凭证失败: {}", e);
+            Ok(None)
+        }
+    }
+}
+
+/// 协议转换路径描述（如 `openai->anthropic`）
+fn conversion_label(source: Protocol, provider: PoolProviderType) -> String {
+    let path = ProtocolSelector::select_path(source, provider);
+    format!("{}->{}", path.source.as_str(), path.target.as_str())
+}
+
+/// 根据上游响应构建回退尝试记录
+fn build_hop_attempt(
+    kind: AttemptKind,
+    credential: &ProviderCredential,
+    model: Option<String>,
+    source: Protocol,
+    started_after_ms: u64,
+    response: &Response,
+    hop_started: Instant,
+) -> RoutingAttempt {
+    let status = response.status();
+    let (outcome, error) = if status.is_success() {
+        (AttemptOutcome::Won, None)
+    } else {
+        (
+            AttemptOutcome::Failed,
+            Some(format!("upstream status {}", status.as_u16())),
+        )
+    };
+
+    RoutingAttempt {
+        kind,
+        outcome,
+        provider: credential.provider_type,
+        credential_id: Some(credential.uuid.clone()),
+        started_after_ms,
+        ttfb_ms: Some(hop_started.elapsed().as_millis() as u64),
+        error,
+        wasted_tokens: 0,
+        model,
+        conversion: Some(conversion_label(source, credential.provider_type)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConcurrencyConfig;
+    use crate::database::schema::create_tables;
+    use crate::models::provider_pool_model::CredentialData;
+    use crate::server::handlers::chat_completions;
+    use crate::telemetry::RequestStatus;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use futures::future::BoxFuture;
+    use futures::FutureExt;
+    use rusqlite::Connection;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    /// 模拟 OpenAI 上游：记录每次请求使用的 API Key，按请求序号决定返回的状态码，
+    /// 成功时响应内容为所用的 API Key
+    async fn spawn_upstream<F>(keys: Arc<Mutex<Vec<String>>>, respond: F) -> String
+    where
+        F: Fn(usize) -> BoxFuture<'static, StatusCode> + Clone + Send + Sync + 'static,
+    {
+        let app = axum::Router::new().route(
+            "/v1/chat/completions",
+            axum::routing::post(move |headers: HeaderMap| {
+                let keys = keys.clone();
+                let respond = respond.clone();
+                async move {
+                    let key = headers
+                        .get("authorization")
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or_default()
+                        .trim_start_matches("Bearer ")
+                        .to_string();
+                    let index = {
+                        let mut keys = keys.lock();
+                        keys.push(key.clone());
+                        keys.len() - 1
+                    };
+                    let status = respond(index).await;
+                    if !status.is_success() {
+                        return (status, Json(serde_json::json!({"error": "upstream"})))
+                            .into_response();
+                    }
+                    Json(serde_json::json!({
+                        "id": "chatcmpl-test",
+                        "object": "chat.completion",
+                        "choices": [{
+                            "index": 0,
+                            "message": {"role": "assistant", "content": key},
+                            "finish_reason": "stop"
+                        }],
+                        "usage": {"prompt_tokens": 3, "completion_tokens": 1, "total_tokens": 4}
+                    }))
+                    .into_response()
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        format!("http://{}", addr)
+    }
+
+    /// 构建带内存数据库的状态，并为每个 Key 添加指向模拟上游的 OpenAI 凭证
+    fn setup_state(base_url: &str, keys: &[&str]) -> AppState {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        let db: DbConnection = Arc::new(std::sync::Mutex::new(conn));
+        let state = AppState::for_tests("test-key", "openai", Some(db.clone()));
+        for key in keys {
+            state
+                .pool_service
+                .add_credential(
+                    &db,
+                    "openai",
+                    CredentialData::OpenAIKey {
+                        api_key: key.to_string(),
+                        base_url: Some(base_url.to_string()),
+                    },
+                    Some(key.to_string()),
+                    Some(false),
+                    None,
+                )
+                .unwrap();
+        }
+        state
+    }
+
+    fn chat_request(state: &AppState, model: &str) -> impl Future<Output = Response> {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer test-key".parse().unwrap());
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": model,
+            "messages": [{"role": "user", "content": "hello"}]
+        }))
+        .unwrap();
+        chat_completions(State(state.clone()), headers, Json(request))
+    }
+
+    fn openai_in_flight(state: &AppState) -> u32 {
+        state
+            .pool_service
+            .scheduler()
+            .snapshot()
+            .providers
+            .iter()
+            .find(|p| p.provider == "openai")
+            .map(|p| p.in_flight)
+            .unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn test_chat_completions_hedges_slow_primary() {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let base_url = spawn_upstream(keys.clone(), |index| {
+            async move {
+                if index == 0 {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                }
+                StatusCode::OK
+            }
+            .boxed()
+        })
+        .await;
+        let state = setup_state(&base_url, &["key-a", "key-b"]);
+        state.processor.provider_step.set_hedge_delay_ms(50);
+
+        let response = tokio::time::timeout(Duration::from_secs(5), chat_request(&state, "gpt-4o"))
+            .await
+            .expect("对冲请求应在首选凭证挂起时返回");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        // 两个凭证各被调用一次，采用后发起的对冲调用
+        let keys = keys.lock().clone();
+        assert_eq!(keys.len(), 2);
+        assert_ne!(keys[0], keys[1]);
+        assert_eq!(body["choices"][0]["message"]["content"], keys[1]);
+
+        // 被取消的首选调用计入统计和浪费的 Token
+        let stats = state.processor.stats.read().get_all();
+        assert!(stats
+            .iter()
+            .any(|log| log.status == RequestStatus::Cancelled));
+        let tokens = state.processor.tokens.read().get_all();
+        assert!(tokens.iter().any(|record| record.wasted));
+
+        // 响应体发送完毕后记录首字节时间
+        assert_eq!(state.processor.provider_step.ttfb_window().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hedge_cancels_losing_upstream() {
+        /// 上游调用被取消（未完成即释放）时置位
+        struct CancelGuard(Arc<std::sync::atomic::AtomicBool>);
+
+        impl Drop for CancelGuard {
+            fn drop(&mut self) {
+                self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        let state = setup_state("http://127.0.0.1:1", &["key-a", "key-b"]);
+        state.processor.provider_step.set_hedge_delay_ms(20);
+        let db = state.db.clone().unwrap();
+        let api_key = |credential: &ProviderCredential| match &credential.credential {
+            CredentialData::OpenAIKey { api_key, .. } => api_key.clone(),
+            _ => String::new(),
+        };
+        let primary = state
+            .pool_service
+            .select_credential_filtered(&db, "openai", Some("gpt-4o"), None, &|c| {
+                api_key(c) == "key-a"
+            })
+            .unwrap()
+            .unwrap();
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hello"}]
+        }))
+        .unwrap();
+        let hedge = HedgeCall {
+            state: &state,
+            credential: &primary,
+            request: &request,
+            source: Protocol::OpenAI,
+            client_type: &ClientType::Other,
+            priority: PriorityClass::Interactive,
+            estimated_input_tokens: 0,
+        };
+
+        // 首选凭证的上游一直挂起，备用凭证立即返回
+        let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let call = |credential: ProviderCredential, _request: ChatCompletionRequest| {
+            let guard = (api_key(&credential) == "key-a").then(|| CancelGuard(cancelled.clone()));
+            async move {
+                if let Some(_guard) = guard {
+                    std::future::pending::<()>().await;
+                }
+                StatusCode::OK.into_response()
+            }
+        };
+
+        let mut ctx = RequestContext::new("gpt-4o".to_string());
+        let outcome = tokio::time::timeout(
+            Duration::from_secs(5),
+            hedge.call_primary(&mut ctx, None, &call),
+        )
+        .await
+        .expect("对冲调用应在首选凭证挂起时返回");
+        assert_eq!(outcome.response.status(), StatusCode::OK);
+        assert_ne!(outcome.credential_id, primary.uuid);
+
+        // 落败的首选调用在返回前已被取消
+        assert!(cancelled.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(ctx.attempts.iter().any(|attempt| {
+            attempt.kind == AttemptKind::Primary && attempt.outcome == AttemptOutcome::Cancelled
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_fallback_hop_holds_scheduler_permit() {
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        let gate = Arc::new(Notify::new());
+        let upstream_gate = gate.clone();
+        let base_url = spawn_upstream(keys.clone(), move |index| {
+            let gate = upstream_gate.clone();
+            async move {
+                if index == 0 {
+                    return StatusCode::INTERNAL_SERVER_ERROR;
+                }
+                gate.notified().await;
+                StatusCode::OK
+            }
+            .boxed()
+        })
+        .await;
+        // 只有一个凭证且并发上限为 1：回退跳必须先释放首跳许可再排队
+        let state = setup_state(&base_url, &["key-a"]);
+        state
+            .pool_service
+            .scheduler()
+            .set_config(ConcurrencyConfig {
+                max_in_flight_per_credential: 1,
+                queue_timeout_ms: 1_000,
+                ..ConcurrencyConfig::default()
+            });
+
+        let handle = tokio::spawn(chat_request(&state, "gpt-4o|gpt-4o-mini"));
+
+        // 回退跳的上游调用进行中时占用调度器槽位
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while keys.lock().len() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("应回退到第二跳");
+        assert_eq!(openai_in_flight(&state), 1);
+
+        gate.notify_one();
+        let response = tokio::time::timeout(Duration::from_secs(5), handle)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        drop(response);
+        assert_eq!(openai_in_flight(&state), 0);
+    }
+}
@@ -0,0 +1,193 @@
+//! 出站防护调用
+//!
+//! 在所有调用包装的最外层执行 `guardrails`：
+//! - 请求发出前检测并处理消息内容与工具结果中的敏感数据
+//! - `block` 命中时直接返回 400，不调用上游
+//! - `tokenize` 生成的占位符在响应（流式 / 非流式）中还原
+//! - 审计记录写入 Flow 元数据
+
+use std::sync::Arc;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use parking_lot::Mutex;
+
+use crate::flow_monitor::GuardrailEvent;
+use crate::guardrails::{
+    restore::{restore_anthropic_response, restore_openai_response},
+    GuardrailScan, GuardrailViolation, StreamRestorer, StreamRestorerFormat,
+};
+use crate::models::anthropic::AnthropicMessagesRequest;
+use crate::models::openai::ChatCompletionRequest;
+use crate::models::provider_pool_model::ProviderCredential;
+use crate::server::AppState;
+
+use super::structured_calls::{buffer_json, rebuild_response};
+use super::tool_emulation_calls::rewrite_stream;
+use super::{call_provider_anthropic_emulated, call_provider_openai_emulated};
+
+/// 调用 Provider (OpenAI 格式)，发出前执行出站防护
+pub async fn call_provider_openai_guarded(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &ChatCompletionRequest,
+    flow_id: Option<&str>,
+) -> Response {
+    let guardrails = state.guardrails.read().await.clone();
+    if guardrails.is_empty() {
+        return call_provider_openai_emulated(state, credential, request, flow_id).await;
+    }
+
+    let mut guarded = request.clone();
+    let mut scan = GuardrailScan::default();
+    let result = guardrails.apply_openai(&mut guarded, &mut scan);
+    record_events(state, flow_id, std::mem::take(&mut scan.events)).await;
+    if let Err(violation) = result {
+        log_violation(state, &violation).await;
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": {
+                "message": violation.to_string(),
+                "type": "guardrail_violation",
+                "param": violation.location,
+            }})),
+        )
+            .into_response();
+    }
+
+    let response = call_provider_openai_emulated(state, credential, &guarded, flow_id).await;
+    if scan.vault.is_empty() || !response.status().is_success() {
+        return response;
+    }
+
+    if request.stream {
+        return restore_stream(state, flow_id, response, StreamRestorerFormat::OpenAi, scan);
+    }
+
+    let (parts, mut json) = match buffer_json(response).await {
+        Ok(buffered) => buffered,
+        Err(response) => return response,
+    };
+    scan.record_restored(restore_openai_response(&mut json, &scan.vault));
+    record_events(state, flow_id, scan.events).await;
+    rebuild_response(parts, &json)
+}
+
+/// 调用 Provider (Anthropic 格式)，发出前执行出站防护
+pub async fn call_provider_anthropic_guarded(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &AnthropicMessagesRequest,
+    flow_id: Option<&str>,
+) -> Response {
+    let guardrails = state.guardrails.read().await.clone();
+    if guardrails.is_empty() {
+        return call_provider_anthropic_emulated(state, credential, request, flow_id).await;
+    }
+
+    let mut guarded = request.clone();
+    let mut scan = GuardrailScan::default();
+    let result = guardrails.apply_anthropic(&mut guarded, &mut scan);
+    record_events(state, flow_id, std::mem::take(&mut scan.events)).await;
+    if let Err(violation) = result {
+        log_violation(state, &violation).await;
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "type": "error",
+                "error": {
+                    "type": "guardrail_violation",
+                    "message": violation.to_string()
+                }
+            })),
+        )
+            .into_response();
+    }
+
+    let response = call_provider_anthropic_emulated(state, credential, &guarded, flow_id).await;
+    if scan.vault.is_empty() || !response.status().is_success() {
+        return response;
+    }
+
+    if request.stream {
+        return restore_stream(
+            state,
+            flow_id,
+            response,
+            StreamRestorerFormat::Anthropic,
+            scan,
+        );
+    }
+
+    let (parts, mut json) = match buffer_json(response).await {
+        Ok(buffered) => buffered,
+        Err(response) => return response,
+    };
+    scan.record_restored(restore_anthropic_response(&mut json, &scan.vault));
+    record_events(state, flow_id, scan.events).await;
+    rebuild_response(parts, &json)
+}
+
+/// 流式还原占位符，流结束时补记还原数量
+fn restore_stream(
+    state: &AppState,
+    flow_id: Option<&str>,
+    response: Response,
+    format: StreamRestorerFormat,
+    scan: GuardrailScan,
+) -> Response {
+    let restorer = Arc::new(Mutex::new(StreamRestorer::new(format, scan.vault)));
+    let flow_monitor = state.flow_monitor.clone();
+    let flow_id = flow_id.map(String::from);
+
+    rewrite_stream(response, move |chunk| match chunk {
+        Some(bytes) => restorer.lock().transform(bytes),
+        None => {
+            let mut restorer = restorer.lock();
+            let output = restorer.finish();
+            let mut scan = GuardrailScan::default();
+            scan.record_restored(restorer.restored_count());
+            if let (Some(flow_id), false) = (flow_id.clone(), scan.events.is_empty()) {
+                let flow_monitor = flow_monitor.clone();
+                tokio::spawn(async move {
+                    flow_monitor
+                        .record_guardrail_events(&flow_id, scan.events)
+                        .await;
+                });
+            }
+            output
+        }
+    })
+}
+
+async fn record_events(state: &AppState, flow_id: Option<&str>, events: Vec<GuardrailEvent>) {
+    if events.is_empty() {
+        return;
+    }
+    for event in &events {
+        state.logs.write().await.add(
+            "info",
+            &format!(
+                "[GUARDRAILS] {:?} {} x{} @ {}",
+                event.action, event.detector, event.count, event.location
+            ),
+        );
+    }
+    if let Some(flow_id) = flow_id {
+        state
+            .flow_monitor
+            .record_guardrail_events(flow_id, events)
+            .await;
+    }
+}
+
+async fn log_violation(state: &AppState, violation: &GuardrailViolation) {
+    state
+        .logs
+        .write()
+        .await
+        .add("warn", &format!("[GUARDRAILS] {}", violation));
+}
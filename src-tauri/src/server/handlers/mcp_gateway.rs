@@ -0,0 +1,208 @@
+//! MCP 网关端点
+//!
+//! Streamable HTTP 传输：
+//! - `POST /mcp`：JSON-RPC 请求 / 通知 / 批量消息，响应为 `application/json`
+//! - `GET /mcp`：网关不提供服务器主动推送的 SSE 流，返回 405
+//! - `DELETE /mcp`：结束会话
+//! - `GET /mcp/status`：上游服务器运行状态（仅服务器 API Key）
+//!
+//! 客户端身份由认证使用的 API Key 确定（服务器 API Key 或 `mcp_gateway.client_keys`），
+//! 会话只能由创建它的客户端继续使用。
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+use crate::mcp_gateway::GatewaySession;
+use crate::server::AppState;
+
+use super::verify_api_key;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn disabled() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({"error": {"message": "MCP gateway is disabled"}})),
+    )
+        .into_response()
+}
+
+/// 按 API Key 认证并返回客户端标识
+fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<String, Response> {
+    let api_key = headers
+        .get(header::AUTHORIZATION)
+        .or_else(|| headers.get("x-api-key"))
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.strip_prefix("Bearer ").unwrap_or(s))
+        .unwrap_or_default();
+    state
+        .mcp_gateway
+        .authenticate(api_key, &state.api_key)
+        .ok_or_else(|| {
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({"error": {"message": "Invalid API key"}})),
+            )
+                .into_response()
+        })
+}
+
+fn session_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
+/// POST /mcp
+pub async fn mcp_gateway_post(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let client_id = match authenticate(&state, &headers) {
+        Ok(client_id) => client_id,
+        Err(response) => return response,
+    };
+    let gateway = &state.mcp_gateway;
+    if !gateway.is_enabled() {
+        return disabled();
+    }
+
+    let (messages, batch) = match body {
+        Value::Array(messages) => (messages, true),
+        message => (vec![message], false),
+    };
+
+    // initialize 创建新会话，其余消息必须携带有效会话
+    let initialize = messages
+        .iter()
+        .find(|m| m["method"].as_str() == Some("initialize"));
+    let (session_id, session, mut responses) = if let Some(message) = initialize {
+        // 自报的名称只用于记录，白名单按认证得到的客户端标识匹配
+        let client_name = message["params"]["clientInfo"]["name"]
+            .as_str()
+            .unwrap_or("unknown")
+            .to_string();
+        let session_id = gateway.create_session(&client_id, &client_name);
+        state.logs.write().await.add(
+            "info",
+            &format!(
+                "[MCP_GATEWAY] 新会话: client_id={} client_name={}",
+                client_id, client_name
+            ),
+        );
+        let result = gateway.initialize_result(message["params"]["protocolVersion"].as_str());
+        let response = json!({"jsonrpc": "2.0", "id": message["id"], "result": result});
+        let session = GatewaySession {
+            client_id,
+            client_name,
+        };
+        (session_id, session, vec![response])
+    } else {
+        let Some(session_id) = session_id(&headers) else {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(rpc_error(
+                    Value::Null,
+                    -32600,
+                    "Missing Mcp-Session-Id header",
+                )),
+            )
+                .into_response();
+        };
+        let Some(session) = gateway.session(&session_id, &client_id) else {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(rpc_error(Value::Null, -32001, "Session not found")),
+            )
+                .into_response();
+        };
+        (session_id, session, Vec::new())
+    };
+
+    for message in &messages {
+        let Some(method) = message["method"].as_str() else {
+            // 客户端对服务器请求的响应，网关不会发起此类请求
+            continue;
+        };
+        if method == "initialize" {
+            continue;
+        }
+        let Some(id) = message.get("id").cloned() else {
+            // 通知（notifications/initialized、notifications/cancelled 等）
+            continue;
+        };
+        let params = message.get("params").cloned().unwrap_or_else(|| json!({}));
+        let response = match gateway.handle_request(&session, method, params).await {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => json!({"jsonrpc": "2.0", "id": id, "error": e.to_rpc_error()}),
+        };
+        responses.push(response);
+    }
+
+    let mut response = if responses.is_empty() {
+        StatusCode::ACCEPTED.into_response()
+    } else if batch {
+        Json(Value::Array(responses)).into_response()
+    } else {
+        Json(responses.remove(0)).into_response()
+    };
+    if let Ok(value) = HeaderValue::from_str(&session_id) {
+        response.headers_mut().insert(SESSION_HEADER, value);
+    }
+    response
+}
+
+/// GET /mcp
+pub async fn mcp_gateway_get(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(response) = authenticate(&state, &headers) {
+        return response;
+    }
+    if !state.mcp_gateway.is_enabled() {
+        return disabled();
+    }
+    (
+        StatusCode::METHOD_NOT_ALLOWED,
+        [(header::ALLOW, "POST, DELETE")],
+    )
+        .into_response()
+}
+
+/// DELETE /mcp
+pub async fn mcp_gateway_delete(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let client_id = match authenticate(&state, &headers) {
+        Ok(client_id) => client_id,
+        Err(response) => return response,
+    };
+    if !state.mcp_gateway.is_enabled() {
+        return disabled();
+    }
+    match session_id(&headers) {
+        Some(id) if state.mcp_gateway.close_session(&id, &client_id) => {
+            StatusCode::OK.into_response()
+        }
+        _ => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// GET /mcp/status
+pub async fn mcp_gateway_status(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(e) = verify_api_key(&headers, &state.api_key).await {
+        return e.into_response();
+    }
+    Json(json!({
+        "enabled": state.mcp_gateway.is_enabled(),
+        "servers": state.mcp_gateway.status().await
+    }))
+    .into_response()
+}
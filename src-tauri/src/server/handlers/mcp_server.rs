@@ -0,0 +1,46 @@
+//! 内置 MCP 服务器端点
+//!
+//! 无状态的 Streamable HTTP 传输，不下发会话 ID。Flow 内容与凭证状态属于管理数据，
+//! 两个端点都由管理 API 认证层保护，按端点区分作用域：
+//! - `POST /mcp/proxycast`：只读作用域
+//! - `GET /mcp/proxycast`：不提供服务器主动推送的 SSE 流，返回 405
+//! - `POST /v0/management/mcp`：管理作用域
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::Value;
+
+use crate::mcp_server::McpScope;
+use crate::server::AppState;
+
+async fn dispatch(state: &AppState, scope: McpScope, body: Value) -> Response {
+    match state.mcp_server.handle_payload(scope, &body).await {
+        Some(response) => Json(response).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+/// POST /mcp/proxycast
+pub async fn proxycast_mcp_post(
+    State(state): State<AppState>,
+    Json(body): Json<Value>,
+) -> Response {
+    dispatch(&state, McpScope::ReadOnly, body).await
+}
+
+/// GET /mcp/proxycast
+pub async fn proxycast_mcp_get() -> Response {
+    (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, "POST")]).into_response()
+}
+
+/// POST /v0/management/mcp
+pub async fn management_mcp_post(
+    State(state): State<AppState>,
+    Json(body): Json<Value>,
+) -> Response {
+    dispatch(&state, McpScope::Admin, body).await
+}
@@ -3,17 +3,35 @@
 //! 将 server 中的各类处理器拆分到独立文件
 
 pub mod api;
+pub mod batch_api;
 pub mod credentials_api;
+pub mod fallback_calls;
+pub mod guardrail_calls;
 pub mod image_handler;
 pub mod kiro_credential;
 pub mod management;
+pub mod mcp_gateway;
+pub mod mcp_server;
+pub mod prompt_cache_calls;
 pub mod provider_calls;
+pub mod routing_explain;
+pub mod structured_calls;
+pub mod tool_emulation_calls;
 pub mod websocket;
 
 pub use api::*;
+pub use batch_api::*;
 pub use credentials_api::*;
+pub use fallback_calls::*;
+pub use guardrail_calls::*;
 pub use image_handler::*;
 pub use kiro_credential::*;
 pub use management::*;
+pub use mcp_gateway::*;
+pub use mcp_server::*;
+pub use prompt_cache_calls::*;
 pub use provider_calls::*;
+pub use routing_explain::*;
+pub use structured_calls::*;
+pub use tool_emulation_calls::*;
 pub use websocket::*;
@@ -0,0 +1,134 @@
+//! Prompt Caching 调用
+//!
+//! 在 `call_provider_anthropic` 外层处理 Anthropic Prompt Caching：
+//! - 启用 `prompt_cache` 时为 Anthropic 原生凭证自动插入 cache_control 断点
+//! - 从响应（流式 / 非流式）中提取 cache_creation / cache_read token 用量，
+//!   通过响应扩展中的 `PromptCacheUsageSlot` 交给处理器写入 TokenTracker
+
+use axum::{
+    body::Body,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::StreamExt;
+
+use crate::models::anthropic::AnthropicMessagesRequest;
+use crate::models::provider_pool_model::{CredentialData, ProviderCredential};
+use crate::processor::{PromptCacheSseTap, PromptCacheUsage, PromptCacheUsageSlot};
+use crate::server::AppState;
+
+use super::call_provider_anthropic;
+
+/// 调用 Provider (Anthropic 格式)，并处理 Prompt Caching 断点与缓存用量
+pub async fn call_provider_anthropic_cached(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &AnthropicMessagesRequest,
+    flow_id: Option<&str>,
+) -> Response {
+    // 仅 Anthropic 原生后端支持 cache_control
+    if !matches!(
+        credential.credential,
+        CredentialData::ClaudeKey { .. } | CredentialData::AnthropicKey { .. }
+    ) {
+        return call_provider_anthropic(state, credential, request, flow_id).await;
+    }
+
+    let cached_request = apply_breakpoints(state, request).await;
+    let response = call_provider_anthropic(
+        state,
+        credential,
+        cached_request.as_ref().unwrap_or(request),
+        flow_id,
+    )
+    .await;
+
+    // 客户端自行设置 cache_control 时同样需要统计缓存用量
+    if !response.status().is_success() {
+        return response;
+    }
+    attach_usage_slot(response, request.stream).await
+}
+
+/// 按配置插入缓存断点，未启用或未插入任何断点时返回 None
+async fn apply_breakpoints(
+    state: &AppState,
+    request: &AnthropicMessagesRequest,
+) -> Option<AnthropicMessagesRequest> {
+    let step = &state.processor.prompt_cache;
+    if !step.is_enabled() {
+        return None;
+    }
+
+    let mut payload = serde_json::to_value(request).ok()?;
+    let breakpoints = step.apply(&mut payload)?;
+    if breakpoints.count() == 0 {
+        return None;
+    }
+
+    match serde_json::from_value(payload) {
+        Ok(cached) => {
+            state.logs.write().await.add(
+                "debug",
+                &format!(
+                    "[PROMPT_CACHE] 插入 {} 个缓存断点: tools={} system={} messages={:?} stable_prefix={}",
+                    breakpoints.count(),
+                    breakpoints.tools,
+                    breakpoints.system,
+                    breakpoints.messages,
+                    breakpoints.stable_prefix_len
+                ),
+            );
+            Some(cached)
+        }
+        Err(e) => {
+            tracing::warn!("[PROMPT_CACHE] 插入断点后请求无法解析，使用原始请求: {}", e);
+            None
+        }
+    }
+}
+
+/// 为响应挂载缓存用量槽位
+///
+/// 非流式响应直接解析 usage；流式响应在 SSE 经过时提取 message_start /
+/// message_delta 中的用量。
+async fn attach_usage_slot(response: Response, stream: bool) -> Response {
+    let slot = PromptCacheUsageSlot::default();
+    let (parts, body) = response.into_parts();
+
+    let mut response = if stream {
+        let reporter = slot.clone();
+        let mut tap = PromptCacheSseTap::default();
+        let data = body.into_data_stream().map(move |chunk| {
+            if let Ok(bytes) = &chunk {
+                if let Some(usage) = tap.observe(bytes) {
+                    reporter.report(usage);
+                }
+            }
+            chunk
+        });
+        Response::from_parts(parts, Body::from_stream(data))
+    } else {
+        let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({"error": {"message": format!("Failed to read response body: {}", e)}})),
+                )
+                    .into_response();
+            }
+        };
+        if let Some(usage) = serde_json::from_slice::<serde_json::Value>(&bytes)
+            .ok()
+            .and_then(|json| PromptCacheUsage::from_usage(&json["usage"]))
+        {
+            slot.report(usage);
+        }
+        Response::from_parts(parts, Body::from(bytes))
+    };
+
+    response.extensions_mut().insert(slot);
+    response
+}
@@ -49,10 +49,14 @@ use axum::{
 };
 use futures::StreamExt;
 
-use crate::converter::anthropic_to_openai::convert_anthropic_to_openai;
+use crate::converter::anthropic_to_openai::{
+    anthropic_to_openai_unsupported_params, convert_anthropic_to_openai,
+};
 use crate::converter::openai_to_antigravity::{
     convert_antigravity_to_openai_response, convert_openai_to_antigravity_with_context,
+    openai_to_antigravity_unsupported_params,
 };
+use crate::converter::openai_to_cw::openai_to_cw_unsupported_params;
 use crate::flow_monitor::models::{FlowError, FlowErrorType};
 use crate::flow_monitor::stream_rebuilder::StreamFormat;
 use crate::models::anthropic::AnthropicMessagesRequest;
@@ -74,6 +78,7 @@ use crate::streaming::{
     StreamConfig, StreamContext, StreamError, StreamFormat as StreamingFormat, StreamManager,
     StreamResponse,
 };
+use crate::translator::kiro::anthropic::request::unsupported_anthropic_params as anthropic_to_cw_unsupported_params;
 
 /// 根据凭证调用 Provider (Anthropic 格式)
 ///
@@ -103,6 +108,13 @@ pub async fn call_provider_anthropic(
         }
     }
 
+    record_unsupported_params(
+        state,
+        flow_id,
+        unsupported_anthropic_params(credential, request),
+    )
+    .await;
+
     match &credential.credential {
         CredentialData::KiroOAuth { creds_file_path } => {
             // 如果是流式请求，使用真正的流式处理（需求 1.1, 6.1）
@@ -930,10 +942,17 @@ pub async fn call_provider_openai(
     state: &AppState,
     credential: &ProviderCredential,
     request: &ChatCompletionRequest,
-    _flow_id: Option<&str>,
+    flow_id: Option<&str>,
 ) -> Response {
     let _start_time = std::time::Instant::now();
 
+    record_unsupported_params(
+        state,
+        flow_id,
+        unsupported_openai_params(credential, request),
+    )
+    .await;
+
     // 调试：打印凭证类型
     let cred_type = match &credential.credential {
         CredentialData::KiroOAuth { .. } => "KiroOAuth",
@@ -2129,6 +2148,59 @@ pub async fn call_provider_openai(
     }
 }
 
+/// 记录协议转换时被丢弃的请求参数
+async fn record_unsupported_params(state: &AppState, flow_id: Option<&str>, params: Vec<String>) {
+    if params.is_empty() {
+        return;
+    }
+    tracing::debug!("[PARAMS] 目标协议不支持的参数: {:?}", params);
+    if let Some(fid) = flow_id {
+        state
+            .flow_monitor
+            .record_unsupported_params(fid, params)
+            .await;
+    }
+}
+
+/// Anthropic 请求在目标凭证上无法映射的参数
+fn unsupported_anthropic_params(
+    credential: &ProviderCredential,
+    request: &AnthropicMessagesRequest,
+) -> Vec<String> {
+    match &credential.credential {
+        CredentialData::KiroOAuth { .. } => anthropic_to_cw_unsupported_params(request),
+        CredentialData::AntigravityOAuth { .. } => {
+            // 先转为 OpenAI 格式，再转为 Antigravity 格式，两步的丢弃参数都要记录
+            let mut params = anthropic_to_openai_unsupported_params(request);
+            let openai_request = convert_anthropic_to_openai(request);
+            for param in openai_to_antigravity_unsupported_params(&openai_request) {
+                if !params.contains(&param) {
+                    params.push(param);
+                }
+            }
+            params
+        }
+        CredentialData::OpenAIKey { .. } | CredentialData::VertexKey { .. } => {
+            anthropic_to_openai_unsupported_params(request)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// OpenAI 请求在目标凭证上无法映射的参数
+fn unsupported_openai_params(
+    credential: &ProviderCredential,
+    request: &ChatCompletionRequest,
+) -> Vec<String> {
+    match &credential.credential {
+        CredentialData::KiroOAuth { .. } => openai_to_cw_unsupported_params(request),
+        CredentialData::AntigravityOAuth { .. } => {
+            openai_to_antigravity_unsupported_params(request)
+        }
+        _ => Vec::new(),
+    }
+}
+
 // ============================================================================
 // 流式传输支持
 // ============================================================================
@@ -0,0 +1,312 @@
+//! 路由决策追踪
+//!
+//! 复现 `/v1/chat/completions` 与 `/v1/messages` 的路由决策：回退链与别名解析、
+//! 客户端类型检测、端点 Provider 覆盖、候选凭证筛选以及协议转换路径。
+//!
+//! - 真实请求在获取凭证前生成 `RoutingTrace`，选定凭证后补全并附加到 Flow 上
+//! - `POST /v0/management/routing/explain` 只做决策不调用上游，也不占用并发许可、
+//!   不推进 API Key 轮询
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::converter::protocol_selector::{Protocol, ProtocolSelector};
+use crate::flow_monitor::{CredentialSource, ProviderSource, RoutingTrace};
+use crate::models::provider_pool_model::ProviderCredential;
+use crate::router::{FallbackChain, FALLBACK_HEADER};
+use crate::server::client_detector::ClientType;
+use crate::server::AppState;
+use crate::session::SessionManager;
+use crate::ProviderType;
+
+/// 入口协议
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExplainProtocol {
+    /// `/v1/chat/completions`
+    #[default]
+    Openai,
+    /// `/v1/messages`
+    Anthropic,
+}
+
+impl ExplainProtocol {
+    fn source(self) -> Protocol {
+        match self {
+            ExplainProtocol::Openai => Protocol::OpenAI,
+            ExplainProtocol::Anthropic => Protocol::Anthropic,
+        }
+    }
+}
+
+/// 路由解释请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoutingExplainRequest {
+    /// 入口协议
+    #[serde(default)]
+    pub protocol: ExplainProtocol,
+    /// 模拟的客户端请求头（User-Agent、X-Provider-Id、X-ProxyCast-Fallback 等）
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// 客户端请求体
+    pub body: serde_json::Value,
+}
+
+/// 生成获取凭证前的路由决策
+///
+/// `fallback_chain` 与 `resolved_model` 由调用方按请求处理顺序解析后传入，
+/// 候选凭证的筛选结果来自 `ProviderPoolService::explain_selection`。
+pub(crate) async fn trace_routing(
+    state: &AppState,
+    headers: &HeaderMap,
+    requested_model: &str,
+    fallback_chain: &FallbackChain,
+    resolved_model: &str,
+    session_id: Option<String>,
+) -> RoutingTrace {
+    let user_agent = headers
+        .get("user-agent")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let client_type = ClientType::from_user_agent(user_agent);
+    let endpoint_provider = state
+        .endpoint_providers
+        .read()
+        .await
+        .get_provider(client_type.config_key())
+        .cloned();
+    let default_provider = state.default_provider.read().await.clone();
+    let provider_id_header = headers
+        .get("x-provider-id")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_lowercase());
+    let hop_provider = fallback_chain.primary().and_then(|hop| hop.provider);
+
+    // 与请求处理器的优先级一致：X-Provider-Id > 回退链首跳 > 端点配置 > 默认
+    let (selected_provider, provider_source) = if let Some(id) = &provider_id_header {
+        (id.clone(), ProviderSource::ProviderIdHeader)
+    } else if let Some(provider) = hop_provider {
+        (provider.to_string(), ProviderSource::FallbackHop)
+    } else if let Some(provider) = &endpoint_provider {
+        (provider.clone(), ProviderSource::EndpointOverride)
+    } else {
+        (default_provider.clone(), ProviderSource::Default)
+    };
+
+    let candidates = match &state.db {
+        Some(db) => state
+            .pool_service
+            .explain_selection(
+                db,
+                &selected_provider,
+                Some(resolved_model),
+                Some(&client_type),
+            )
+            .unwrap_or_else(|e| {
+                tracing::warn!("[ROUTING_TRACE] 解释凭证选择失败: {}", e);
+                Vec::new()
+            }),
+        None => Vec::new(),
+    };
+
+    RoutingTrace {
+        requested_model: requested_model.to_string(),
+        fallback_chain: if fallback_chain.has_fallback() {
+            fallback_chain
+                .hops()
+                .iter()
+                .map(|hop| hop.to_string())
+                .collect()
+        } else {
+            Vec::new()
+        },
+        resolved_model: resolved_model.to_string(),
+        client_type: client_type.config_key().to_string(),
+        endpoint_provider,
+        default_provider,
+        provider_id_header,
+        selected_provider,
+        provider_source: Some(provider_source),
+        candidates,
+        selected_credential: None,
+        credential_source: None,
+        session_id,
+        conversion: None,
+    }
+}
+
+/// 记录最终使用的凭证及协议转换路径
+pub(crate) fn record_credential(
+    trace: &mut RoutingTrace,
+    credential: &ProviderCredential,
+    source: CredentialSource,
+    protocol: Protocol,
+) {
+    trace.selected_credential = Some(credential.uuid.clone());
+    trace.credential_source = Some(source);
+    trace.conversion = Some(conversion_path(protocol, credential.provider_type));
+}
+
+fn conversion_path(source: Protocol, provider: ProviderType) -> String {
+    let path = ProtocolSelector::select_path(source, provider);
+    format!("{}->{}", path.source.as_str(), path.target.as_str())
+}
+
+/// 预测凭证池会选中的凭证（权重分数最高者，同分取靠前者）
+fn predict_credential(trace: &mut RoutingTrace, protocol: Protocol) {
+    let best = trace
+        .candidates
+        .iter()
+        .filter(|c| c.accepted)
+        .fold(None, |best: Option<&_>, c| match best {
+            Some(b) if b.score.unwrap_or(f64::MIN) >= c.score.unwrap_or(f64::MIN) => Some(b),
+            _ => Some(c),
+        })
+        .map(|c| (c.credential_id.clone(), c.provider_type.clone()));
+
+    match best {
+        Some((id, provider_type)) => {
+            trace.selected_credential = Some(id);
+            trace.credential_source = Some(CredentialSource::Pool);
+            trace.conversion = provider_type
+                .parse::<ProviderType>()
+                .ok()
+                .map(|provider| conversion_path(protocol, provider));
+        }
+        // 凭证池无可用凭证时请求会降级到 API Key Provider；
+        // 具体 Key 取决于轮询位置，dry-run 不推进轮询因此不给出
+        None => trace.credential_source = Some(CredentialSource::ApiKeyProvider),
+    }
+}
+
+/// POST /v0/management/routing/explain
+///
+/// 按真实请求的处理顺序生成路由决策，不调用上游。
+pub async fn management_routing_explain(
+    State(state): State<AppState>,
+    Json(request): Json<RoutingExplainRequest>,
+) -> Response {
+    let Some(requested_model) = request
+        .body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .map(str::to_string)
+    else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "body.model is required"})),
+        )
+            .into_response();
+    };
+
+    let mut headers = HeaderMap::new();
+    for (name, value) in &request.headers {
+        match (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
+            }
+            _ => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": format!("invalid header: {}", name)})),
+                )
+                    .into_response();
+            }
+        }
+    }
+
+    let fallback_header = headers.get(FALLBACK_HEADER).and_then(|v| v.to_str().ok());
+    let fallback_chain = state
+        .processor
+        .resolve_fallback_chain(&requested_model, fallback_header)
+        .await;
+    let model = fallback_chain
+        .primary()
+        .map(|hop| hop.model.clone())
+        .unwrap_or_else(|| requested_model.clone());
+    let resolved_model = state.processor.resolve_model(&model).await;
+    let session_id = SessionManager::extract_session_id_from_json(&request.body, &model);
+
+    let mut trace = trace_routing(
+        &state,
+        &headers,
+        &requested_model,
+        &fallback_chain,
+        &resolved_model,
+        Some(session_id),
+    )
+    .await;
+    predict_credential(&mut trace, request.protocol.source());
+
+    Json(trace).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_monitor::CandidateTrace;
+
+    fn candidate(id: &str, provider_type: &str, score: Option<f64>) -> CandidateTrace {
+        CandidateTrace {
+            credential_id: id.to_string(),
+            name: None,
+            provider_type: provider_type.to_string(),
+            accepted: score.is_some(),
+            excluded_by: Vec::new(),
+            risk_level: None,
+            score,
+        }
+    }
+
+    #[test]
+    fn test_predict_credential_picks_highest_score() {
+        let mut trace = RoutingTrace {
+            candidates: vec![
+                candidate("a", "kiro", Some(50.0)),
+                candidate("b", "kiro", Some(80.0)),
+                candidate("c", "kiro", Some(80.0)),
+                candidate("d", "kiro", None),
+            ],
+            ..Default::default()
+        };
+        predict_credential(&mut trace, Protocol::OpenAI);
+
+        assert_eq!(trace.selected_credential.as_deref(), Some("b"));
+        assert_eq!(trace.credential_source, Some(CredentialSource::Pool));
+        assert_eq!(trace.conversion.as_deref(), Some("openai->codewhisperer"));
+    }
+
+    #[test]
+    fn test_predict_credential_falls_back_to_api_key_provider() {
+        let mut trace = RoutingTrace {
+            candidates: vec![candidate("a", "claude", None)],
+            ..Default::default()
+        };
+        predict_credential(&mut trace, Protocol::Anthropic);
+
+        assert!(trace.selected_credential.is_none());
+        assert_eq!(
+            trace.credential_source,
+            Some(CredentialSource::ApiKeyProvider)
+        );
+        assert!(trace.conversion.is_none());
+    }
+
+    #[test]
+    fn test_explain_request_defaults_to_openai() {
+        let request: RoutingExplainRequest =
+            serde_json::from_value(json!({"body": {"model": "gpt-4o"}})).unwrap();
+        assert_eq!(request.protocol, ExplainProtocol::Openai);
+        assert!(request.headers.is_empty());
+    }
+}
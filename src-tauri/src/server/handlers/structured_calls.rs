@@ -0,0 +1,211 @@
+//! 结构化输出调用
+//!
+//! 在 `call_provider_openai` 外层处理 `response_format`：
+//! - 原生支持 / 工具强制的后端直接调用，非流式响应按 schema 校验
+//! - 其余后端注入 schema 指令，校验失败时把错误反馈给模型重试，
+//!   重试次数受 `structured_output.max_repair_attempts` 限制
+//!
+//! 校验结果记录到 Flow 的 `metadata.structured_output`。
+
+use axum::{
+    body::Body,
+    http::{header, response::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::flow_monitor::{StructuredOutputMode, StructuredOutputValidation};
+use crate::models::openai::ChatCompletionRequest;
+use crate::models::provider_pool_model::ProviderCredential;
+use crate::server::AppState;
+use crate::structured_output::{
+    inject_instructions, mode_for_credential, push_repair_messages, StructuredOutputSpec,
+};
+
+use super::call_provider_openai;
+
+/// 调用 Provider (OpenAI 格式)，并保证 response_format 的输出约束
+pub async fn call_provider_openai_structured(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &ChatCompletionRequest,
+    flow_id: Option<&str>,
+) -> Response {
+    let Some(spec) = StructuredOutputSpec::from_request(request) else {
+        return call_provider_openai(state, credential, request, flow_id).await;
+    };
+    let config = state.structured_output.read().await.clone();
+    let mode = mode_for_credential(&credential.credential, request.stream);
+
+    let mut validation = StructuredOutputValidation {
+        mode,
+        schema_name: spec.name.clone(),
+        valid: None,
+        attempts: 1,
+        errors: Vec::new(),
+    };
+
+    if mode != StructuredOutputMode::PromptFallback {
+        let response = call_provider_openai(state, credential, request, flow_id).await;
+        if request.stream || !config.validate_native || !response.status().is_success() {
+            record_validation(state, flow_id, validation).await;
+            return response;
+        }
+
+        let (parts, json) = match buffer_json(response).await {
+            Ok(buffered) => buffered,
+            Err(response) => return response,
+        };
+        match spec.check(&completion_text(&json)) {
+            Ok(_) => validation.valid = Some(true),
+            Err(errors) => {
+                tracing::warn!("[STRUCTURED] 原生结构化输出未通过校验: {:?}", errors);
+                validation.valid = Some(false);
+                validation.errors = errors;
+            }
+        }
+        record_validation(state, flow_id, validation).await;
+        return rebuild_response(parts, &json);
+    }
+
+    if !config.fallback_enabled {
+        return call_provider_openai(state, credential, request, flow_id).await;
+    }
+
+    let mut request = request.clone();
+    inject_instructions(&mut request, &spec);
+
+    // 流式响应已逐块发给客户端，无法校验和重试
+    if request.stream {
+        let response = call_provider_openai(state, credential, &request, flow_id).await;
+        record_validation(state, flow_id, validation).await;
+        return response;
+    }
+
+    let max_attempts = config.max_repair_attempts + 1;
+    let mut usage = UsageTotals::default();
+    validation.attempts = 0;
+
+    loop {
+        validation.attempts += 1;
+        let response = call_provider_openai(state, credential, &request, flow_id).await;
+        if !response.status().is_success() {
+            record_validation(state, flow_id, validation).await;
+            return response;
+        }
+
+        let (parts, mut json) = match buffer_json(response).await {
+            Ok(buffered) => buffered,
+            Err(response) => return response,
+        };
+        usage.add(&json);
+
+        let content = completion_text(&json);
+        match spec.check(&content) {
+            Ok(value) => {
+                // 去掉代码块等包裹，只返回 JSON 本身
+                json["choices"][0]["message"]["content"] = serde_json::json!(value.to_string());
+                validation.valid = Some(true);
+                validation.errors.clear();
+            }
+            Err(errors) if validation.attempts < max_attempts => {
+                tracing::info!(
+                    "[STRUCTURED] 第 {} 次输出未通过校验，要求模型修正: {:?}",
+                    validation.attempts,
+                    errors
+                );
+                push_repair_messages(&mut request, &content, &errors);
+                continue;
+            }
+            Err(errors) => {
+                tracing::warn!(
+                    "[STRUCTURED] {} 次尝试后输出仍未通过校验: {:?}",
+                    validation.attempts,
+                    errors
+                );
+                validation.valid = Some(false);
+                validation.errors = errors;
+            }
+        }
+
+        usage.apply(&mut json);
+        record_validation(state, flow_id, validation).await;
+        return rebuild_response(parts, &json);
+    }
+}
+
+/// 多次修复调用的 token 用量合计
+#[derive(Default)]
+struct UsageTotals {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, json: &serde_json::Value) {
+        self.prompt_tokens += json["usage"]["prompt_tokens"].as_u64().unwrap_or(0);
+        self.completion_tokens += json["usage"]["completion_tokens"].as_u64().unwrap_or(0);
+    }
+
+    fn apply(&self, json: &mut serde_json::Value) {
+        if json.get("usage").is_some_and(|u| u.is_object()) {
+            json["usage"]["prompt_tokens"] = self.prompt_tokens.into();
+            json["usage"]["completion_tokens"] = self.completion_tokens.into();
+            json["usage"]["total_tokens"] = (self.prompt_tokens + self.completion_tokens).into();
+        }
+    }
+}
+
+async fn record_validation(
+    state: &AppState,
+    flow_id: Option<&str>,
+    validation: StructuredOutputValidation,
+) {
+    if let Some(fid) = flow_id {
+        state
+            .flow_monitor
+            .record_structured_output(fid, validation)
+            .await;
+    }
+}
+
+/// 读取非流式响应体并解析为 JSON
+///
+/// 读取失败或响应体不是 JSON 时返回可直接发给客户端的响应。
+pub(super) async fn buffer_json(
+    response: Response,
+) -> Result<(Parts, serde_json::Value), Response> {
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": {"message": format!("Failed to read response body: {}", e)}})),
+            )
+                .into_response())
+        }
+    };
+    match serde_json::from_slice(&bytes) {
+        Ok(json) => Ok((parts, json)),
+        Err(_) => Err(Response::from_parts(parts, Body::from(bytes))),
+    }
+}
+
+pub(super) fn rebuild_response(mut parts: Parts, json: &serde_json::Value) -> Response {
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(json.to_string()))
+}
+
+/// 取出第一个候选的文本内容
+fn completion_text(json: &serde_json::Value) -> String {
+    match &json["choices"][0]["message"]["content"] {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p["text"].as_str())
+            .collect::<Vec<_>>()
+            .join(""),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,133 @@
+//! 工具调用模拟
+//!
+//! 对 `tool_emulation.models` 命中的模型，在调用上游前把 tools 改写进提示词，
+//! 并从响应文本中还原工具调用：
+//! - OpenAI 格式：`tool_calls` + `finish_reason: tool_calls`
+//! - Anthropic 格式：`tool_use` 内容块 + `stop_reason: tool_use`
+
+use std::sync::Arc;
+
+use axum::{body::Body, response::Response};
+use futures::StreamExt;
+use parking_lot::Mutex;
+
+use crate::models::anthropic::AnthropicMessagesRequest;
+use crate::models::openai::ChatCompletionRequest;
+use crate::models::provider_pool_model::ProviderCredential;
+use crate::server::AppState;
+use crate::tool_emulation::{
+    emulate_anthropic_request, emulate_openai_request, requires_emulation,
+    rewrite_anthropic_response, rewrite_openai_response,
+    stream::{AnthropicToolStream, OpenAiToolStream},
+};
+
+use super::structured_calls::{buffer_json, rebuild_response};
+use super::{call_provider_anthropic_cached, call_provider_openai_structured};
+
+/// 调用 Provider (OpenAI 格式)，必要时模拟工具调用
+pub async fn call_provider_openai_emulated(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &ChatCompletionRequest,
+    flow_id: Option<&str>,
+) -> Response {
+    let config = state.tool_emulation.read().await.clone();
+    if request.tools.is_none() || !requires_emulation(&config, &request.model) {
+        return call_provider_openai_structured(state, credential, request, flow_id).await;
+    }
+
+    let mut emulated = request.clone();
+    emulate_openai_request(&mut emulated, config.format);
+    log_emulation(state, &request.model).await;
+
+    let response = call_provider_openai_structured(state, credential, &emulated, flow_id).await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    if request.stream {
+        let rewriter = Arc::new(Mutex::new(OpenAiToolStream::new()));
+        return rewrite_stream(response, move |chunk| match chunk {
+            Some(bytes) => rewriter.lock().transform(bytes),
+            None => rewriter.lock().finish(),
+        });
+    }
+
+    let (parts, mut json) = match buffer_json(response).await {
+        Ok(buffered) => buffered,
+        Err(response) => return response,
+    };
+    let calls = rewrite_openai_response(&mut json);
+    tracing::debug!("[TOOL_EMULATION] 从响应中还原 {} 个工具调用", calls);
+    rebuild_response(parts, &json)
+}
+
+/// 调用 Provider (Anthropic 格式)，必要时模拟工具调用
+pub async fn call_provider_anthropic_emulated(
+    state: &AppState,
+    credential: &ProviderCredential,
+    request: &AnthropicMessagesRequest,
+    flow_id: Option<&str>,
+) -> Response {
+    let config = state.tool_emulation.read().await.clone();
+    if request.tools.is_none() || !requires_emulation(&config, &request.model) {
+        return call_provider_anthropic_cached(state, credential, request, flow_id).await;
+    }
+
+    let mut emulated = request.clone();
+    emulate_anthropic_request(&mut emulated, config.format);
+    log_emulation(state, &request.model).await;
+
+    let response = call_provider_anthropic_cached(state, credential, &emulated, flow_id).await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    if request.stream {
+        let rewriter = Arc::new(Mutex::new(AnthropicToolStream::new()));
+        return rewrite_stream(response, move |chunk| match chunk {
+            Some(bytes) => rewriter.lock().transform(bytes),
+            None => rewriter.lock().finish(),
+        });
+    }
+
+    let (parts, mut json) = match buffer_json(response).await {
+        Ok(buffered) => buffered,
+        Err(response) => return response,
+    };
+    let calls = rewrite_anthropic_response(&mut json);
+    tracing::debug!("[TOOL_EMULATION] 从响应中还原 {} 个工具调用", calls);
+    rebuild_response(parts, &json)
+}
+
+async fn log_emulation(state: &AppState, model: &str) {
+    state.logs.write().await.add(
+        "info",
+        &format!(
+            "[TOOL_EMULATION] 模型 {} 不支持原生工具调用，改用提示词模拟",
+            model
+        ),
+    );
+}
+
+/// 逐块改写流式响应体；上游结束时以 `None` 调用一次以输出剩余内容
+pub(super) fn rewrite_stream<F>(response: Response, rewrite: F) -> Response
+where
+    F: Fn(Option<&[u8]>) -> Vec<u8> + Clone + Send + Sync + 'static,
+{
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+
+    let finish = rewrite.clone();
+    let rewritten = body
+        .into_data_stream()
+        .map(move |chunk| chunk.map(|bytes| bytes::Bytes::from(rewrite(Some(&bytes[..])))))
+        .chain(futures::stream::once(async move {
+            Ok(bytes::Bytes::from(finish(None)))
+        }))
+        .filter(|chunk| {
+            let keep = !matches!(chunk, Ok(bytes) if bytes.is_empty());
+            async move { keep }
+        });
+    Response::from_parts(parts, Body::from_stream(rewritten))
+}
@@ -2,17 +2,24 @@
 
 pub mod client_detector;
 
+use crate::batch::BatchQueue;
 use crate::config::{
     Config, ConfigChangeKind, ConfigManager, EndpointProvidersConfig, FileChangeEvent, FileWatcher,
-    HotReloadManager, ReloadResult,
+    HotReloadManager, ReloadResult, StructuredOutputConfig, ToolEmulationConfig,
 };
 use crate::converter::anthropic_to_openai::convert_anthropic_to_openai;
-use crate::credential::CredentialSyncService;
+use crate::credential::{
+    create_shared_quota_manager, start_quota_cleanup_task, CredentialSyncService, QuotaManager,
+};
 use crate::database::dao::provider_pool::ProviderPoolDao;
 use crate::database::DbConnection;
-use crate::flow_monitor::{FlowInterceptor, FlowMonitor, FlowMonitorConfig};
+use crate::flow_monitor::{FlowInterceptor, FlowMonitor, FlowMonitorConfig, FlowQueryService};
+use crate::forward_proxy::ForwardProxy;
+use crate::guardrails::Guardrails;
 use crate::injection::Injector;
 use crate::logger::LogStore;
+use crate::mcp_gateway::McpGateway;
+use crate::mcp_server::ProxyCastMcpServer;
 use crate::models::anthropic::*;
 use crate::models::openai::*;
 use crate::models::provider_pool_model::CredentialData;
@@ -85,6 +92,9 @@ pub fn record_request_telemetry(
     // 设置重试次数
     log.retry_count = ctx.retry_count;
 
+    // 设置排队信息
+    log.set_queue_info(ctx.queue_wait_ms, ctx.queue_depth);
+
     // 记录到统计聚合器
     {
         let stats = state.processor.stats.write();
@@ -166,7 +176,11 @@ pub struct ServerState {
     pub default_provider_ref: Arc<RwLock<String>>,
     /// 路由器引用（用于动态更新默认 Provider）
     pub router_ref: Option<Arc<RwLock<crate::router::Router>>>,
+    /// 配额管理器引用（供 stdio 模式的内置 MCP 服务器使用）
+    pub quota_ref: Option<Arc<QuotaManager>>,
     shutdown_tx: Option<oneshot::Sender<()>>,
+    /// 服务器任务句柄（用于等待优雅关闭完成）
+    server_task: Option<tokio::task::JoinHandle<()>>,
     /// 服务器运行时使用的 API key（启动时从配置复制）
     /// 用于 test_api 命令，确保测试使用的 API key 和服务器一致
     pub running_api_key: Option<String>,
@@ -193,7 +207,9 @@ impl ServerState {
             claude_custom_provider: claude_custom,
             default_provider_ref,
             router_ref: None,
+            quota_ref: None,
             shutdown_tx: None,
+            server_task: None,
             running_api_key: None,
             running_host: None,
         }
@@ -330,14 +346,23 @@ impl ServerState {
         let config_path = crate::config::ConfigManager::default_config_path();
 
         // 创建请求处理器（在 spawn 之前创建，以便保存 router_ref）
-        let processor = match (&shared_stats, &shared_tokens) {
-            (Some(stats), Some(tokens)) => Arc::new(RequestProcessor::with_shared_telemetry(
+        let mut processor = match (&shared_stats, &shared_tokens) {
+            (Some(stats), Some(tokens)) => RequestProcessor::with_shared_telemetry(
                 pool_service.clone(),
                 stats.clone(),
                 tokens.clone(),
-            )),
-            _ => Arc::new(RequestProcessor::with_defaults(pool_service.clone())),
+            ),
+            _ => RequestProcessor::with_defaults(pool_service.clone()),
         };
+        // 从配置初始化配额管理器
+        processor.quota = create_shared_quota_manager(config.quota_exceeded.clone());
+        let processor = Arc::new(processor);
+
+        // 配额超限与上游限流冷却到期时唤醒调度器中排队的请求
+        // （按名称注册，重启服务器时替换上一次的实例）
+        let scheduler = processor.pool_service.scheduler();
+        scheduler.register_cooldown_source("quota", processor.quota.clone());
+        scheduler.register_cooldown_source("rate_limit", processor.rate_limits.clone());
 
         // 从配置初始化 Router 的默认 Provider
         {
@@ -370,13 +395,31 @@ impl ServerState {
             }
         }
 
+        // 从配置初始化回退链
+        processor
+            .fallback
+            .write()
+            .await
+            .set_chains(config.routing.fallback_chains.clone());
+
+        // 从配置初始化 Prompt Caching 断点
+        processor
+            .prompt_cache
+            .set_config(config.prompt_cache.clone());
+
+        // 从配置初始化对冲请求延迟
+        processor
+            .provider_step
+            .set_hedge_delay_ms(config.retry.hedge_delay_ms);
+
         // 保存 router_ref 以便后续动态更新
         self.router_ref = Some(processor.router.clone());
+        self.quota_ref = Some(processor.quota.clone());
 
         // 保存实际使用的 host（在移动到 spawn 之前克隆）
         let running_host = host.clone();
 
-        tokio::spawn(async move {
+        let server_task = tokio::spawn(async move {
             if let Err(e) = run_server(
                 &host,
                 port,
@@ -405,6 +448,7 @@ impl ServerState {
             }
         });
 
+        self.server_task = Some(server_task);
         self.running = true;
         self.start_time = Some(std::time::Instant::now());
         // 保存服务器运行时使用的 API key，用于 test_api 命令
@@ -423,6 +467,14 @@ impl ServerState {
         self.running_api_key = None;
         self.running_host = None;
         self.router_ref = None;
+        self.quota_ref = None;
+    }
+
+    /// 取出服务器任务句柄
+    ///
+    /// 调用方可在 `stop()` 之后等待该句柄，确保进行中的请求处理完毕再退出进程。
+    pub fn take_server_task(&mut self) -> Option<tokio::task::JoinHandle<()>> {
+        self.server_task.take()
     }
 }
 
@@ -473,12 +525,87 @@ pub struct AppState {
     pub flow_interceptor: Arc<FlowInterceptor>,
     /// 端点 Provider 配置
     pub endpoint_providers: Arc<RwLock<EndpointProvidersConfig>>,
+    /// 结构化输出配置
+    pub structured_output: Arc<RwLock<StructuredOutputConfig>>,
+    /// 工具调用模拟配置
+    pub tool_emulation: Arc<RwLock<ToolEmulationConfig>>,
+    /// 出站防护
+    pub guardrails: Arc<RwLock<Guardrails>>,
+    /// MCP 网关
+    pub mcp_gateway: Arc<McpGateway>,
+    /// 内置 MCP 服务器
+    pub mcp_server: Arc<ProxyCastMcpServer>,
+    /// 批处理队列
+    pub batch_queue: Arc<BatchQueue>,
     /// Kiro 事件服务
     pub kiro_event_service: Arc<KiroEventService>,
     /// API Key Provider 服务（用于智能降级）
     pub api_key_service: Arc<crate::services::api_key_provider_service::ApiKeyProviderService>,
 }
 
+#[cfg(test)]
+impl AppState {
+    /// 构建测试用的最小状态（默认配置，不启动后台任务）
+    pub(crate) fn for_tests(
+        api_key: &str,
+        default_provider: &str,
+        db: Option<DbConnection>,
+    ) -> Self {
+        let pool_service = Arc::new(ProviderPoolService::new());
+        let processor = Arc::new(RequestProcessor::with_defaults(pool_service.clone()));
+        let flow_monitor = Arc::new(FlowMonitor::new(FlowMonitorConfig::default(), None));
+        let default_provider = Arc::new(RwLock::new(default_provider.to_string()));
+        let ws_manager = Arc::new(WsConnectionManager::new(WsConfig::default()));
+        let mcp_server = Arc::new(ProxyCastMcpServer::new(
+            None,
+            pool_service.clone(),
+            db.clone(),
+            processor.quota.clone(),
+            processor.stats.clone(),
+            default_provider.clone(),
+            processor.router.clone(),
+        ));
+
+        Self {
+            api_key: api_key.to_string(),
+            base_url: "http://127.0.0.1:0".to_string(),
+            default_provider,
+            kiro: Arc::new(RwLock::new(KiroProvider::new())),
+            logs: Arc::new(RwLock::new(LogStore::new())),
+            kiro_refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
+            gemini_refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
+            pool_service,
+            token_cache: Arc::new(TokenCacheService::new()),
+            db: db.clone(),
+            injector: Arc::new(RwLock::new(Injector::new())),
+            injection_enabled: Arc::new(RwLock::new(false)),
+            processor: processor.clone(),
+            ws_stats: ws_manager.stats().clone(),
+            ws_manager,
+            hot_reload_manager: None,
+            request_logger: None,
+            amp_router: Arc::new(crate::router::AmpRouter::new(Default::default())),
+            flow_interceptor: Arc::new(FlowInterceptor::default()),
+            endpoint_providers: Arc::new(RwLock::new(EndpointProvidersConfig::default())),
+            structured_output: Arc::new(RwLock::new(StructuredOutputConfig::default())),
+            tool_emulation: Arc::new(RwLock::new(ToolEmulationConfig::default())),
+            guardrails: Arc::new(RwLock::new(Guardrails::new(&Default::default()))),
+            mcp_gateway: Arc::new(McpGateway::new(
+                Default::default(),
+                db.clone(),
+                flow_monitor.clone(),
+            )),
+            flow_monitor,
+            mcp_server,
+            batch_queue: Arc::new(BatchQueue::new(Default::default(), db)),
+            kiro_event_service: Arc::new(KiroEventService::new()),
+            api_key_service: Arc::new(
+                crate::services::api_key_provider_service::ApiKeyProviderService::new(),
+            ),
+        }
+    }
+}
+
 /// 启动配置文件监控
 ///
 /// 监控配置文件变化并触发热重载。
@@ -551,6 +678,7 @@ async fn start_config_watcher(
                         // 更新处理器中的组件
                         let new_config = manager.config();
                         update_processor_config(&processor_clone, &new_config).await;
+                        crate::agent::tools::reload_shared_policy(&new_config.agent.command_policy);
 
                         // 同步凭证池
                         if let (Some(ref db), Some(ref cfg_manager)) =
@@ -681,6 +809,32 @@ async fn update_processor_config(processor: &RequestProcessor, config: &Config)
         );
     }
 
+    // 更新回退链
+    {
+        let mut fallback = processor.fallback.write().await;
+        fallback.set_chains(config.routing.fallback_chains.clone());
+        tracing::debug!(
+            "[HOT_RELOAD] 回退链已更新: {} 条",
+            config.routing.fallback_chains.len()
+        );
+    }
+
+    // 更新凭证并发调度配置
+    processor
+        .pool_service
+        .scheduler()
+        .set_config(config.concurrency.clone());
+
+    // 更新 Prompt Caching 断点配置
+    processor
+        .prompt_cache
+        .set_config(config.prompt_cache.clone());
+
+    // 更新对冲请求延迟
+    processor
+        .provider_step
+        .set_hedge_delay_ms(config.retry.hedge_delay_ms);
+
     // 注意：重试配置目前不支持热更新，因为 Retrier 是不可变的
     // 如果需要更新重试配置，需要重启服务器
     tracing::debug!(
@@ -816,6 +970,16 @@ async fn run_server(
                 );
             }
         }
+
+        // 从配置初始化回退链
+        processor
+            .fallback
+            .write()
+            .await
+            .set_chains(cfg.routing.fallback_chains.clone());
+
+        // 从配置初始化 Prompt Caching 断点
+        processor.prompt_cache.set_config(cfg.prompt_cache.clone());
     }
 
     // 初始化 WebSocket 管理器
@@ -864,6 +1028,67 @@ async fn run_server(
             .unwrap_or_default(),
     ));
 
+    // 结构化输出配置
+    let structured_output = Arc::new(RwLock::new(
+        config
+            .as_ref()
+            .map(|c| c.structured_output.clone())
+            .unwrap_or_default(),
+    ));
+
+    // 工具调用模拟配置
+    let tool_emulation = Arc::new(RwLock::new(
+        config
+            .as_ref()
+            .map(|c| c.tool_emulation.clone())
+            .unwrap_or_default(),
+    ));
+
+    // 出站防护
+    let guardrails = Arc::new(RwLock::new(Guardrails::new(
+        &config
+            .as_ref()
+            .map(|c| c.guardrails.clone())
+            .unwrap_or_default(),
+    )));
+
+    // MCP 网关
+    let mcp_gateway = Arc::new(McpGateway::new(
+        config
+            .as_ref()
+            .map(|c| c.mcp_gateway.clone())
+            .unwrap_or_default(),
+        db.clone(),
+        flow_monitor.clone(),
+    ));
+    mcp_gateway.start();
+
+    // 内置 MCP 服务器
+    let flow_query = flow_monitor.file_store().map(|file_store| {
+        Arc::new(FlowQueryService::new(
+            flow_monitor.memory_store(),
+            file_store,
+        ))
+    });
+    let mcp_server = Arc::new(ProxyCastMcpServer::new(
+        flow_query,
+        pool_service.clone(),
+        db.clone(),
+        processor.quota.clone(),
+        processor.stats.clone(),
+        default_provider.clone(),
+        processor.router.clone(),
+    ));
+
+    // 批处理队列
+    let batch_queue = Arc::new(BatchQueue::new(
+        config.as_ref().map(|c| c.batch.clone()).unwrap_or_default(),
+        db.clone(),
+    ));
+
+    // 定期清理过期的配额超限记录
+    let quota_cleanup = start_quota_cleanup_task(processor.quota.clone(), 60);
+
     // 创建 Kiro 事件服务
     let kiro_event_service = Arc::new(KiroEventService::new());
 
@@ -893,10 +1118,23 @@ async fn run_server(
         flow_monitor,
         flow_interceptor,
         endpoint_providers,
+        structured_output,
+        tool_emulation,
+        guardrails,
+        mcp_gateway: mcp_gateway.clone(),
+        mcp_server,
+        batch_queue,
         kiro_event_service,
         api_key_service,
     };
 
+    // 批处理执行器（经由常规处理器调用上游）
+    let batch_runner = state.batch_queue.is_enabled().then(|| {
+        state
+            .batch_queue
+            .start(Arc::new(handlers::HandlerBatchExecutor::new(state.clone())))
+    });
+
     // ========== 开发模式：启动独立的 HTTP 桥接服务器 ==========
     // 仅在 debug 模式下，启动一个独立的开发服务器在端口 3030
     // 允许浏览器 dev server 通过 HTTP 调用 Tauri 命令
@@ -961,6 +1199,16 @@ async fn run_server(
             "/v0/management/config",
             axum::routing::put(handlers::management_update_config),
         )
+        // 内置 MCP 服务器（Flow 内容与凭证状态同样需要管理 API 认证）
+        .route(
+            "/mcp/proxycast",
+            post(handlers::proxycast_mcp_post).get(handlers::proxycast_mcp_get),
+        )
+        .route("/v0/management/mcp", post(handlers::management_mcp_post))
+        .route(
+            "/v0/management/routing/explain",
+            post(handlers::management_routing_explain),
+        )
         .layer(crate::middleware::ManagementAuthLayer::new(
             management_config,
         ));
@@ -1011,6 +1259,47 @@ async fn run_server(
             }
         ))
         .route("/v1/messages/count_tokens", post(count_tokens))
+        // MCP 网关
+        .route(
+            "/mcp",
+            post(handlers::mcp_gateway_post)
+                .get(handlers::mcp_gateway_get)
+                .delete(handlers::mcp_gateway_delete),
+        )
+        .route("/mcp/status", get(handlers::mcp_gateway_status))
+        // Batch API（OpenAI Files / Batches）
+        .route(
+            "/v1/files",
+            post(handlers::batch_files_create).get(handlers::batch_files_list),
+        )
+        .route(
+            "/v1/files/{id}",
+            get(handlers::batch_files_get).delete(handlers::batch_files_delete),
+        )
+        .route("/v1/files/{id}/content", get(handlers::batch_files_content))
+        .route(
+            "/v1/batches",
+            post(handlers::batches_create).get(handlers::batches_list),
+        )
+        .route("/v1/batches/{id}", get(handlers::batches_get))
+        .route("/v1/batches/{id}/cancel", post(handlers::batches_cancel))
+        // Batch API（Anthropic Message Batches）
+        .route(
+            "/v1/messages/batches",
+            post(handlers::message_batches_create).get(handlers::message_batches_list),
+        )
+        .route(
+            "/v1/messages/batches/{id}",
+            get(handlers::message_batches_get).delete(handlers::message_batches_delete),
+        )
+        .route(
+            "/v1/messages/batches/{id}/cancel",
+            post(handlers::message_batches_cancel),
+        )
+        .route(
+            "/v1/messages/batches/{id}/results",
+            get(handlers::message_batches_results),
+        )
         // 图像生成 API 路由
         .route(
             "/v1/images/generations",
@@ -1037,6 +1326,27 @@ async fn run_server(
         .layer(DefaultBodyLimit::max(body_limit))
         .with_state(state);
 
+    // 正向代理（MITM），与 API 服务器共用同一个 Router
+    let forward_proxy = match config
+        .as_ref()
+        .map(|c| c.forward_proxy.clone())
+        .filter(|c| c.enabled)
+    {
+        Some(proxy_config) => {
+            match ForwardProxy::new(proxy_config, app.clone(), api_key.to_string()) {
+                Ok(proxy) => Arc::new(proxy).start().await.map_err(|e| {
+                    tracing::warn!("[FORWARD_PROXY] 启动失败: {}", e);
+                }),
+                Err(e) => {
+                    tracing::warn!("[FORWARD_PROXY] 初始化失败: {}", e);
+                    Err(())
+                }
+            }
+            .ok()
+        }
+        None => None,
+    };
+
     let addr: std::net::SocketAddr = format!("{host}:{port}")
         .parse()
         .map_err(|e| format!("无效的监听地址 {}:{} - {}", host, port, e))?;
@@ -1050,12 +1360,23 @@ async fn run_server(
 
     tracing::info!("Server listening on {}", addr);
 
-    axum::serve(listener, app)
+    let served = axum::serve(listener, app)
         .with_graceful_shutdown(async move {
             let _ = shutdown.await;
         })
-        .await?;
+        .await;
+
+    // 关闭 MCP 网关托管的子进程与远程连接
+    mcp_gateway.shutdown().await;
+    quota_cleanup.abort();
+    if let Some(batch_runner) = batch_runner {
+        batch_runner.abort();
+    }
+    if let Some(forward_proxy) = forward_proxy {
+        forward_proxy.abort();
+    }
 
+    served?;
     Ok(())
 }
 
@@ -1612,7 +1933,7 @@ async fn anthropic_messages_with_selector(
 
             // 根据凭证类型调用相应的 Provider
             // 注意：这里没有 Flow 捕获，因为是通过 selector 路由的请求
-            handlers::call_provider_anthropic(&state, &cred, &request, None).await
+            handlers::call_provider_anthropic_guarded(&state, &cred, &request, None).await
         }
         None => {
             // 不再回退到默认 provider，直接返回错误
@@ -1693,7 +2014,7 @@ async fn chat_completions_with_selector(
             );
 
             // 注意：这里没有 Flow 捕获，因为是通过 selector 路由的请求
-            handlers::call_provider_openai(&state, &cred, &request, None).await
+            handlers::call_provider_openai_guarded(&state, &cred, &request, None).await
         }
         None => {
             // 不再回退到默认 provider，直接返回错误
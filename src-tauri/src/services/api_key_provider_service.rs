@@ -1426,6 +1426,7 @@ impl ApiKeyProviderService {
             tools: None,
             tool_choice: None,
             reasoning_effort: None,
+            ..Default::default()
         };
 
         let response = provider
@@ -585,6 +585,18 @@ impl ModelRegistryService {
             .collect()
     }
 
+    /// 获取模型的上下文长度（token）
+    ///
+    /// 优先精确匹配模型 ID，其次忽略大小写匹配
+    pub async fn get_context_length(&self, model_id: &str) -> Option<u32> {
+        let models = self.models_cache.read().await;
+        models
+            .iter()
+            .find(|m| m.id == model_id)
+            .or_else(|| models.iter().find(|m| m.id.eq_ignore_ascii_case(model_id)))
+            .and_then(|m| m.limits.context_length)
+    }
+
     /// 按服务等级获取模型
     pub async fn get_models_by_tier(&self, tier: ModelTier) -> Vec<EnhancedModelMetadata> {
         self.models_cache
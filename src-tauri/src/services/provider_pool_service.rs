@@ -4,8 +4,12 @@
 
 #![allow(dead_code)]
 
+use crate::credential::{
+    get_global_unified_manager, CredentialPermit, CredentialScheduler, PriorityClass, ScheduleError,
+};
 use crate::database::dao::provider_pool::ProviderPoolDao;
 use crate::database::DbConnection;
+use crate::flow_monitor::{CandidateExclusion, CandidateTrace};
 use crate::models::provider_pool_model::{
     get_default_check_model, get_oauth_creds_path, CredentialData, CredentialDisplay,
     HealthCheckResult, OAuthStatus, PoolProviderType, PoolStats, ProviderCredential,
@@ -20,6 +24,7 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// 凭证健康信息
@@ -66,6 +71,8 @@ pub struct ProviderPoolService {
     max_error_count: u32,
     /// 健康检查超时时间
     health_check_timeout: Duration,
+    /// 凭证并发调度器
+    scheduler: Arc<CredentialScheduler>,
 }
 
 impl Default for ProviderPoolService {
@@ -84,6 +91,7 @@ impl ProviderPoolService {
             round_robin_index: std::sync::RwLock::new(HashMap::new()),
             max_error_count: 3,
             health_check_timeout: Duration::from_secs(30),
+            scheduler: Arc::new(CredentialScheduler::default()),
         }
     }
 
@@ -236,122 +244,93 @@ impl ProviderPoolService {
         model: Option<&str>,
         client_type: Option<&crate::server::client_detector::ClientType>,
     ) -> Result<Option<ProviderCredential>, String> {
-        // 对于未知的 provider_type，直接返回 None（不是错误）
-        // 这样可以让 select_credential_with_fallback 继续尝试智能降级
-        let pt: PoolProviderType = match provider_type.parse() {
-            Ok(pt) => pt,
-            Err(_) => {
-                eprintln!(
-                    "[SELECT_CREDENTIAL] 未知的 provider_type '{}', 返回 None 以便智能降级",
-                    provider_type
-                );
-                return Ok(None);
-            }
-        };
-        let conn = db.lock().map_err(|e| e.to_string())?;
+        self.select_credential_filtered(db, provider_type, model, client_type, &|_| true)
+    }
 
-        // 获取凭证，对于 AI Provider 类型，也查找 Assistant 类型的凭证
-        let mut credentials =
-            ProviderPoolDao::get_by_type(&conn, &pt).map_err(|e| e.to_string())?;
-        eprintln!(
-            "[SELECT_CREDENTIAL] provider_type={}, pt={:?}, initial_count={}",
-            provider_type,
-            pt,
-            credentials.len()
-        );
+    /// 通过并发调度器获取凭证
+    ///
+    /// 跳过已达到并发上限或处于冷却期的凭证；所有凭证都忙时按优先级排队等待。
+    /// 返回的 `CredentialPermit` 需要持有到请求结束（包括流式响应）。
+    ///
+    /// # 返回
+    /// - `Ok(Some((credential, permit)))`: 获取成功
+    /// - `Ok(None)`: 该 Provider 没有可用凭证（调用方可继续降级）
+    /// - `Err(ScheduleError)`: 排队已满或排队超时
+    pub async fn acquire_credential(
+        &self,
+        db: &DbConnection,
+        provider_type: &str,
+        model: Option<&str>,
+        client_type: Option<&crate::server::client_detector::ClientType>,
+        priority: PriorityClass,
+    ) -> Result<Option<(ProviderCredential, CredentialPermit)>, ScheduleError> {
+        self.acquire_credential_filtered(db, provider_type, model, client_type, priority, &|_| true)
+            .await
+    }
 
-        // AI Provider 和 Assistant 共享凭证（都使用 AI Provider API）
-        if pt == PoolProviderType::Anthropic {
-            let assistant_creds = ProviderPoolDao::get_by_type(&conn, &PoolProviderType::Claude)
-                .map_err(|e| e.to_string())?;
-            eprintln!(
-                "[SELECT_CREDENTIAL] AI Provider: adding {} Assistant credentials",
-                assistant_creds.len()
-            );
-            credentials.extend(assistant_creds);
-        } else if pt == PoolProviderType::Claude {
-            let ai_provider_creds =
-                ProviderPoolDao::get_by_type(&conn, &PoolProviderType::Anthropic)
-                    .map_err(|e| e.to_string())?;
-            eprintln!(
-                "[SELECT_CREDENTIAL] Assistant: adding {} AI Provider credentials",
-                ai_provider_creds.len()
-            );
-            credentials.extend(ai_provider_creds);
-        }
+    /// 通过并发调度器获取满足附加过滤条件的凭证
+    ///
+    /// 用于对冲请求等需要限定凭证范围的场景，排队和许可语义同 `acquire_credential`
+    pub async fn acquire_credential_filtered(
+        &self,
+        db: &DbConnection,
+        provider_type: &str,
+        model: Option<&str>,
+        client_type: Option<&crate::server::client_detector::ClientType>,
+        priority: PriorityClass,
+        filter: &(dyn Fn(&ProviderCredential) -> bool + Sync),
+    ) -> Result<Option<(ProviderCredential, CredentialPermit)>, ScheduleError> {
+        self.scheduler
+            .acquire(provider_type, model, priority, |available| {
+                self.select_credential_filtered(
+                    db,
+                    provider_type,
+                    model,
+                    client_type,
+                    &|c: &ProviderCredential| available(c) && filter(c),
+                )
+            })
+            .await
+    }
 
-        drop(conn);
+    /// 获取并发调度器
+    pub fn scheduler(&self) -> &Arc<CredentialScheduler> {
+        &self.scheduler
+    }
 
-        eprintln!(
-            "[SELECT_CREDENTIAL] total_credentials={}, model={:?}",
-            credentials.len(),
-            model
-        );
+    /// 选择凭证（附加自定义过滤条件）
+    pub fn select_credential_filtered(
+        &self,
+        db: &DbConnection,
+        provider_type: &str,
+        model: Option<&str>,
+        client_type: Option<&crate::server::client_detector::ClientType>,
+        filter: &dyn Fn(&ProviderCredential) -> bool,
+    ) -> Result<Option<ProviderCredential>, String> {
+        // 对于未知的 provider_type，直接返回 None（不是错误）
+        // 这样可以让 select_credential_with_fallback 继续尝试智能降级
+        let Some(credentials) = self.load_candidates(db, provider_type)? else {
+            return Ok(None);
+        };
 
-        // 过滤可用的凭证
         let mut available: Vec<_> = credentials
             .into_iter()
             .filter(|c| {
-                let is_avail = c.is_available();
-                if !is_avail {
-                    eprintln!(
-                        "[SELECT_CREDENTIAL] credential {} (type={}) is_available={} (is_healthy={}, is_disabled={}, error_count={}, last_error={:?})",
-                        c.name.as_deref().unwrap_or("unnamed"),
-                        c.provider_type,
-                        is_avail,
-                        c.is_healthy,
-                        c.is_disabled,
-                        c.error_count,
-                        c.last_error_message
-                    );
-                } else {
-                    eprintln!(
-                        "[SELECT_CREDENTIAL] credential {} (type={}) is_available={}",
+                let exclusions = Self::candidate_exclusions(c, model, client_type);
+                if !exclusions.is_empty() {
+                    tracing::debug!(
+                        "[SELECT_CREDENTIAL] 排除凭证 {} (type={}): {:?}",
                         c.name.as_deref().unwrap_or("unnamed"),
                         c.provider_type,
-                        is_avail
+                        exclusions
                     );
                 }
-                is_avail
+                exclusions.is_empty()
             })
             .collect();
 
-        eprintln!(
-            "[SELECT_CREDENTIAL] after is_available filter: {}",
-            available.len()
-        );
-
-        // 如果指定了模型，进一步过滤支持该模型的凭证
-        if let Some(m) = model {
-            available.retain(|c| {
-                let supports = c.supports_model(m);
-                eprintln!(
-                    "[SELECT_CREDENTIAL] credential {} supports_model({})={}",
-                    c.name.as_deref().unwrap_or("unnamed"),
-                    m,
-                    supports
-                );
-                supports
-            });
-        }
-
-        // 过滤客户端兼容的凭证
-        available.retain(|c| {
-            let compatible = c.is_compatible_with_client(client_type);
-            if !compatible {
-                eprintln!(
-                    "[SELECT_CREDENTIAL] credential {} 不兼容客户端类型 {:?}",
-                    c.name.as_deref().unwrap_or("unnamed"),
-                    client_type
-                );
-            }
-            compatible
-        });
-
-        eprintln!(
-            "[SELECT_CREDENTIAL] after client compatibility filter: {}",
-            available.len()
-        );
+        // 自定义过滤（如并发调度器跳过已满或冷却中的凭证）
+        available.retain(filter);
 
         if available.is_empty() {
             return Ok(None);
@@ -368,6 +347,119 @@ impl ProviderPoolService {
         Ok(Some(selected))
     }
 
+    /// 解释凭证选择过程（不占用并发许可，不修改任何状态）
+    ///
+    /// 返回该 Provider 下每个候选凭证的筛选结果，包含调度器的冷却、并发上限
+    /// 以及风控风险等级；通过筛选的凭证附带权重分数，分数最高者即会被选中。
+    pub fn explain_selection(
+        &self,
+        db: &DbConnection,
+        provider_type: &str,
+        model: Option<&str>,
+        client_type: Option<&crate::server::client_detector::ClientType>,
+    ) -> Result<Vec<CandidateTrace>, String> {
+        let Some(credentials) = self.load_candidates(db, provider_type)? else {
+            return Ok(Vec::new());
+        };
+        let unified = get_global_unified_manager();
+
+        let mut traces: Vec<CandidateTrace> = credentials
+            .iter()
+            .map(|c| {
+                let mut excluded_by = Self::candidate_exclusions(c, model, client_type);
+                if self.scheduler.is_cooling(&c.uuid) {
+                    excluded_by.push(CandidateExclusion::CoolingDown);
+                }
+                if self.scheduler.is_at_capacity(&c.uuid) {
+                    excluded_by.push(CandidateExclusion::ConcurrencyLimit);
+                }
+                CandidateTrace {
+                    credential_id: c.uuid.clone(),
+                    name: c.name.clone(),
+                    provider_type: c.provider_type.to_string(),
+                    accepted: excluded_by.is_empty(),
+                    excluded_by,
+                    risk_level: unified
+                        .as_ref()
+                        .map(|m| m.get_risk_level(&c.uuid).as_str().to_string()),
+                    score: None,
+                }
+            })
+            .collect();
+
+        let accepted: Vec<ProviderCredential> = credentials
+            .iter()
+            .zip(&traces)
+            .filter(|(_, t)| t.accepted)
+            .map(|(c, _)| c.clone())
+            .collect();
+        let now = Utc::now();
+        for trace in traces.iter_mut().filter(|t| t.accepted) {
+            if let Some(c) = accepted.iter().find(|c| c.uuid == trace.credential_id) {
+                trace.score = Some(self.calculate_credential_score(c, now, &accepted));
+            }
+        }
+
+        Ok(traces)
+    }
+
+    /// 加载候选凭证（AI Provider 与 Assistant 共享凭证）
+    ///
+    /// 未知的 provider_type 返回 `Ok(None)`。
+    fn load_candidates(
+        &self,
+        db: &DbConnection,
+        provider_type: &str,
+    ) -> Result<Option<Vec<ProviderCredential>>, String> {
+        let pt: PoolProviderType = match provider_type.parse() {
+            Ok(pt) => pt,
+            Err(_) => {
+                tracing::debug!(
+                    "[SELECT_CREDENTIAL] 未知的 provider_type '{}', 返回 None 以便智能降级",
+                    provider_type
+                );
+                return Ok(None);
+            }
+        };
+        let conn = db.lock().map_err(|e| e.to_string())?;
+
+        let mut credentials =
+            ProviderPoolDao::get_by_type(&conn, &pt).map_err(|e| e.to_string())?;
+        let shared = match pt {
+            PoolProviderType::Anthropic => Some(PoolProviderType::Claude),
+            PoolProviderType::Claude => Some(PoolProviderType::Anthropic),
+            _ => None,
+        };
+        if let Some(shared) = shared {
+            credentials
+                .extend(ProviderPoolDao::get_by_type(&conn, &shared).map_err(|e| e.to_string())?);
+        }
+
+        Ok(Some(credentials))
+    }
+
+    /// 凭证自身属性导致的排除原因（不含调度器状态）
+    fn candidate_exclusions(
+        cred: &ProviderCredential,
+        model: Option<&str>,
+        client_type: Option<&crate::server::client_detector::ClientType>,
+    ) -> Vec<CandidateExclusion> {
+        let mut exclusions = Vec::new();
+        if cred.is_disabled {
+            exclusions.push(CandidateExclusion::Disabled);
+        }
+        if !cred.is_healthy {
+            exclusions.push(CandidateExclusion::Unhealthy);
+        }
+        if model.is_some_and(|m| !cred.supports_model(m)) {
+            exclusions.push(CandidateExclusion::ModelNotSupported);
+        }
+        if !cred.is_compatible_with_client(client_type) {
+            exclusions.push(CandidateExclusion::ClientIncompatible);
+        }
+        exclusions
+    }
+
     /// 带智能降级的凭证选择
     ///
     /// 当 Provider Pool 无可用凭证时，自动从 API Key Provider 降级查找
@@ -2087,6 +2179,35 @@ mod tests {
         assert!(!info_normal_error.requires_reauth);
     }
 
+    #[test]
+    fn test_candidate_exclusions() {
+        let mut cred = ProviderCredential::new(
+            PoolProviderType::OpenAI,
+            CredentialData::OpenAIKey {
+                api_key: "sk-test".to_string(),
+                base_url: None,
+            },
+        );
+        assert!(ProviderPoolService::candidate_exclusions(&cred, Some("gpt-4o"), None).is_empty());
+
+        cred.is_disabled = true;
+        cred.is_healthy = false;
+        cred.not_supported_models = vec!["gpt-4o".to_string()];
+        assert_eq!(
+            ProviderPoolService::candidate_exclusions(&cred, Some("gpt-4o"), None),
+            vec![
+                CandidateExclusion::Disabled,
+                CandidateExclusion::Unhealthy,
+                CandidateExclusion::ModelNotSupported,
+            ]
+        );
+        // 未指定模型时不检查模型排除
+        assert!(
+            !ProviderPoolService::candidate_exclusions(&cred, None, None)
+                .contains(&CandidateExclusion::ModelNotSupported)
+        );
+    }
+
     #[test]
     fn test_credential_health_info_serialization() {
         let info = CredentialHealthInfo {
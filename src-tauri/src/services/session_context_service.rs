@@ -2,6 +2,7 @@
 //!
 //! 提供会话上下文的持久化、恢复和智能管理功能，解决 AI 对话中的上下文丢失问题
 
+use crate::agent::compaction::estimate_tokens;
 use crate::database::dao::general_chat::GeneralChatDao;
 use crate::services::general_chat::{ChatMessage, ChatSession, MessageRole};
 use rusqlite::Connection;
@@ -36,6 +37,8 @@ pub struct ContextWindowConfig {
     pub max_messages: usize,
     /// 最大字符数
     pub max_characters: usize,
+    /// 最大 token 数（通常取自模型注册表中的上下文长度，None 表示不限制）
+    pub max_tokens: Option<u32>,
     /// 是否启用智能摘要
     pub enable_smart_summary: bool,
     /// 摘要触发阈值（消息数量）
@@ -47,6 +50,7 @@ impl Default for ContextWindowConfig {
         Self {
             max_messages: 50,
             max_characters: 100000,
+            max_tokens: None,
             enable_smart_summary: true,
             summary_threshold: 30,
         }
@@ -90,7 +94,14 @@ impl SessionContextService {
         // 检查是否需要应用上下文窗口限制
         if all_messages.len() <= self.config.max_messages {
             let total_chars: usize = all_messages.iter().map(|m| m.content.len()).sum();
-            if total_chars <= self.config.max_characters {
+            let within_tokens = self.config.max_tokens.is_none_or(|max_tokens| {
+                all_messages
+                    .iter()
+                    .map(|m| estimate_tokens(&m.content))
+                    .sum::<u32>()
+                    <= max_tokens
+            });
+            if total_chars <= self.config.max_characters && within_tokens {
                 return Ok(all_messages);
             }
         }
@@ -163,19 +174,29 @@ impl SessionContextService {
     ) -> Result<Vec<ChatMessage>, String> {
         let mut selected = Vec::new();
         let mut char_count = 0;
+        let mut token_count = 0;
 
         // 从最新的消息开始选择
         for message in messages.into_iter().rev() {
             let message_chars = message.content.len();
+            let message_tokens = self
+                .config
+                .max_tokens
+                .map_or(0, |_| estimate_tokens(&message.content));
 
             // 检查是否会超过限制
             if selected.len() >= self.config.max_messages
                 || char_count + message_chars > self.config.max_characters
+                || self
+                    .config
+                    .max_tokens
+                    .is_some_and(|max_tokens| token_count + message_tokens > max_tokens)
             {
                 break;
             }
 
             char_count += message_chars;
+            token_count += message_tokens;
             selected.push(message);
         }
 
@@ -492,6 +513,7 @@ mod tests {
         let config = ContextWindowConfig {
             max_messages: 50,
             max_characters: 100000,
+            max_tokens: None,
             enable_smart_summary: true,
             summary_threshold: 30,
         };
@@ -522,6 +544,38 @@ mod tests {
         assert_eq!(context.len(), 5);
     }
 
+    #[test]
+    fn test_get_effective_context_respects_token_limit() {
+        let conn = Arc::new(Mutex::new(setup_test_db()));
+        let messages = create_test_messages("test-session", 5);
+        let last_tokens = estimate_tokens(&messages[4].content);
+        let config = ContextWindowConfig {
+            max_tokens: Some(last_tokens * 2),
+            enable_smart_summary: false,
+            ..Default::default()
+        };
+        let service = SessionContextService::new(conn.clone(), config);
+
+        {
+            let conn_guard = conn.lock().unwrap();
+            let session = ChatSession {
+                id: "test-session".to_string(),
+                name: "测试会话".to_string(),
+                created_at: chrono::Utc::now().timestamp_millis(),
+                updated_at: chrono::Utc::now().timestamp_millis(),
+                metadata: None,
+            };
+            GeneralChatDao::create_session(&conn_guard, &session).unwrap();
+            for msg in &messages {
+                GeneralChatDao::add_message(&conn_guard, msg).unwrap();
+            }
+        }
+
+        let context = service.get_effective_context("test-session").unwrap();
+        assert_eq!(context.len(), 2);
+        assert_eq!(context[1].id, "msg-5");
+    }
+
     #[test]
     fn test_session_stats() {
         let conn = Arc::new(Mutex::new(setup_test_db()));
@@ -1,13 +1,36 @@
 //! 工具钩子管理服务
 //!
-//! 提供工具执行前后的钩子机制，用于自动化上下文记忆管理
+//! 提供工具执行前后的钩子机制，用于自动化上下文记忆管理，
+//! 以及在工具执行前阻止或修改调用、运行外部脚本、调用本地 Webhook、向模型注入反馈。
+//!
+//! ## 外部钩子协议
+//! 外部命令从 stdin 读取 JSON 格式的钩子输入（`session_id`、`hook_event_name`、
+//! `tool_name`、`tool_input`、`tool_response`），Webhook 以同样的 JSON 作为 POST 请求体。
+//! - 退出码 0：stdout 若为 JSON 则按 `decision`（`block`）、`reason`、`tool_input`、
+//!   `feedback` 处理
+//! - 退出码 2：阻止工具调用（PostToolUse 时作为反馈），stderr 作为原因反馈给模型
+//! - 其他退出码或超时：记录错误并跳过同一规则的后续动作，不影响其他规则和工具执行。
+//!   超时时结束钩子启动的整个进程树（Unix 为进程组，Windows 为作业对象）
 
 use crate::services::context_memory_service::{ContextMemoryService, MemoryEntry, MemoryFileType};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::process::Stdio;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
 use tracing::{debug, error, info, warn};
 
+/// 外部钩子默认超时（秒）
+const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 60;
+
+/// 外部命令表示阻止的退出码
+const HOOK_EXIT_BLOCK: i32 = 2;
+
+fn default_hook_timeout_secs() -> u64 {
+    DEFAULT_HOOK_TIMEOUT_SECS
+}
+
 /// 钩子触发时机
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -22,6 +45,18 @@ pub enum HookTrigger {
     Stop,
 }
 
+impl HookTrigger {
+    /// 外部钩子输入中的事件名
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            HookTrigger::SessionStart => "SessionStart",
+            HookTrigger::PreToolUse => "PreToolUse",
+            HookTrigger::PostToolUse => "PostToolUse",
+            HookTrigger::Stop => "Stop",
+        }
+    }
+}
+
 /// 钩子动作类型
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -51,6 +86,51 @@ pub enum HookAction {
         action_type: String,
         parameters: HashMap<String, String>,
     },
+    /// 阻止工具调用（PostToolUse 时作为反馈）
+    Block { reason: String },
+    /// 修改工具输入，浅合并到参数对象（仅 PreToolUse）
+    ModifyInput {
+        set: HashMap<String, serde_json::Value>,
+    },
+    /// 向下一轮模型输入注入反馈
+    InjectFeedback { message: String },
+    /// 运行外部命令（通过 shell 执行），stdin 为钩子输入 JSON
+    RunCommand {
+        command: String,
+        #[serde(default = "default_hook_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// 调用本地 Webhook（POST 钩子输入 JSON，仅允许回环地址）
+    Webhook {
+        url: String,
+        #[serde(default = "default_hook_timeout_secs")]
+        timeout_secs: u64,
+    },
+}
+
+/// 钩子执行结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HookOutcome {
+    /// 阻止原因（存在时不执行工具）
+    pub blocked: Option<String>,
+    /// 修改后的工具输入
+    pub tool_input: Option<serde_json::Value>,
+    /// 注入下一轮模型输入的反馈
+    pub feedback: Vec<String>,
+}
+
+/// 外部钩子的 JSON 输出（stdout 或 Webhook 响应体）
+#[derive(Debug, Default, Deserialize)]
+struct ExternalHookResponse {
+    /// `block` 表示阻止
+    #[serde(default)]
+    decision: Option<String>,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default, alias = "updatedInput")]
+    tool_input: Option<serde_json::Value>,
+    #[serde(default, alias = "additionalContext")]
+    feedback: Option<String>,
 }
 
 /// 钩子规则
@@ -106,6 +186,8 @@ pub struct HookContext {
     pub tool_name: Option<String>,
     /// 工具参数（如果适用）
     pub tool_parameters: Option<HashMap<String, String>>,
+    /// 工具输入 JSON（如果适用，优先于 tool_parameters 传给外部钩子）
+    pub tool_input: Option<serde_json::Value>,
     /// 工具结果（如果适用）
     pub tool_result: Option<String>,
     /// 消息内容
@@ -251,6 +333,163 @@ impl ToolHooksService {
         info!("已注册 {} 个默认钩子规则", rules.len());
     }
 
+    /// 运行钩子并汇总阻止、输入修改与反馈
+    ///
+    /// 记忆类动作与 `execute_hooks` 相同；外部命令与 Webhook 带超时执行。
+    /// 动作失败时记录错误并跳过该规则的后续动作，继续执行其他规则。
+    /// 某条规则阻止后不再执行后续规则，后续规则可以看到前面修改后的工具输入。
+    pub async fn run_hooks(
+        &self,
+        trigger: HookTrigger,
+        context: &HookContext,
+    ) -> Result<HookOutcome, String> {
+        let matching_rules: Vec<HookRule> = {
+            let rules = self.rules.lock().map_err(|e| e.to_string())?;
+            let mut matching: Vec<_> = rules
+                .iter()
+                .filter(|rule| rule.enabled && rule.trigger == trigger)
+                .filter(|rule| self.evaluate_conditions(rule, context))
+                .cloned()
+                .collect();
+            matching.sort_by_key(|rule| rule.priority);
+            matching
+        };
+
+        let mut context = context.clone();
+        let mut outcome = HookOutcome::default();
+        for rule in matching_rules {
+            let start_time = std::time::Instant::now();
+            let mut result = Ok(());
+            for action in &rule.actions {
+                result = self
+                    .run_action(trigger, action, &context, &mut outcome)
+                    .await;
+                if result.is_err() || outcome.blocked.is_some() {
+                    break;
+                }
+            }
+
+            let execution_time = start_time.elapsed().as_millis() as f64;
+            if let Err(e) = &result {
+                error!("执行钩子规则失败 {}: {}", rule.name, e);
+            }
+            self.update_execution_stats(&rule.id, result.is_ok(), execution_time);
+
+            if let Some(reason) = &outcome.blocked {
+                info!("钩子规则 {} 阻止了工具调用: {}", rule.name, reason);
+                break;
+            }
+            if let Some(input) = &outcome.tool_input {
+                context.tool_input = Some(input.clone());
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// 执行单个动作（支持外部钩子）
+    async fn run_action(
+        &self,
+        trigger: HookTrigger,
+        action: &HookAction,
+        context: &HookContext,
+        outcome: &mut HookOutcome,
+    ) -> Result<(), String> {
+        match action {
+            HookAction::Block { reason } => {
+                let reason = self.interpolate_template(reason, context);
+                Self::apply_block(trigger, reason, outcome);
+            }
+            HookAction::ModifyInput { set } => {
+                if trigger != HookTrigger::PreToolUse {
+                    debug!("ModifyInput 仅在 PreToolUse 时生效");
+                    return Ok(());
+                }
+                let mut input = context
+                    .tool_input
+                    .clone()
+                    .filter(|input| input.is_object())
+                    .unwrap_or_else(|| serde_json::json!({}));
+                if let Some(object) = input.as_object_mut() {
+                    for (key, value) in set {
+                        object.insert(key.clone(), value.clone());
+                    }
+                }
+                outcome.tool_input = Some(input);
+            }
+            HookAction::InjectFeedback { message } => {
+                outcome
+                    .feedback
+                    .push(self.interpolate_template(message, context));
+            }
+            HookAction::RunCommand {
+                command,
+                timeout_secs,
+            } => {
+                let payload = Self::hook_payload(trigger, context);
+                let response =
+                    run_command_hook(command, &payload, &context.session_id, *timeout_secs).await?;
+                Self::apply_external(trigger, response, outcome);
+            }
+            HookAction::Webhook { url, timeout_secs } => {
+                let payload = Self::hook_payload(trigger, context);
+                let response = run_webhook_hook(url, &payload, *timeout_secs).await?;
+                Self::apply_external(trigger, response, outcome);
+            }
+            _ => self.execute_action(action, context)?,
+        }
+        Ok(())
+    }
+
+    /// 外部钩子输入
+    fn hook_payload(trigger: HookTrigger, context: &HookContext) -> serde_json::Value {
+        let tool_input = context.tool_input.clone().or_else(|| {
+            context
+                .tool_parameters
+                .as_ref()
+                .and_then(|params| serde_json::to_value(params).ok())
+        });
+        serde_json::json!({
+            "session_id": context.session_id,
+            "hook_event_name": trigger.event_name(),
+            "tool_name": context.tool_name,
+            "tool_input": tool_input,
+            "tool_response": context.tool_result,
+            "error": context.error_info,
+            "metadata": context.metadata,
+        })
+    }
+
+    /// 阻止：PreToolUse 时阻止工具执行，其他时机作为反馈
+    fn apply_block(trigger: HookTrigger, reason: String, outcome: &mut HookOutcome) {
+        if trigger == HookTrigger::PreToolUse {
+            outcome.blocked = Some(reason);
+        } else {
+            outcome.feedback.push(reason);
+        }
+    }
+
+    fn apply_external(
+        trigger: HookTrigger,
+        response: ExternalHookResponse,
+        outcome: &mut HookOutcome,
+    ) {
+        if let Some(feedback) = response.feedback.filter(|f| !f.trim().is_empty()) {
+            outcome.feedback.push(feedback);
+        }
+        if trigger == HookTrigger::PreToolUse {
+            if let Some(input) = response.tool_input {
+                outcome.tool_input = Some(input);
+            }
+        }
+        if response.decision.as_deref() == Some("block") {
+            let reason = response
+                .reason
+                .unwrap_or_else(|| "外部钩子阻止了此操作".to_string());
+            Self::apply_block(trigger, reason, outcome);
+        }
+    }
+
     /// 执行钩子
     pub fn execute_hooks(&self, trigger: HookTrigger, context: &HookContext) -> Result<(), String> {
         let rules = self.rules.lock().map_err(|e| e.to_string())?;
@@ -429,6 +668,14 @@ impl ToolHooksService {
                 // 自定义动作的实现
                 debug!("执行自定义钩子动作");
             }
+
+            HookAction::Block { .. }
+            | HookAction::ModifyInput { .. }
+            | HookAction::InjectFeedback { .. }
+            | HookAction::RunCommand { .. }
+            | HookAction::Webhook { .. } => {
+                debug!("钩子动作需通过 run_hooks 执行，已跳过");
+            }
         }
 
         Ok(())
@@ -567,6 +814,192 @@ impl ToolHooksService {
     }
 }
 
+/// 运行外部命令钩子
+async fn run_command_hook(
+    command: &str,
+    payload: &serde_json::Value,
+    session_id: &str,
+    timeout_secs: u64,
+) -> Result<ExternalHookResponse, String> {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        cmd
+    };
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    // 独立进程组，超时时可以结束命令启动的所有子进程
+    #[cfg(unix)]
+    cmd.process_group(0);
+    cmd.env("PROXYCAST_SESSION_ID", session_id)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("启动钩子命令失败: {}", e))?;
+    let tree = ProcessTree::attach(&child);
+    let input = payload.to_string();
+    let run = async {
+        if let Some(mut stdin) = child.stdin.take() {
+            // 命令可能不读取 stdin，写入失败不影响执行
+            let _ = stdin.write_all(input.as_bytes()).await;
+        }
+        child.wait_with_output().await
+    };
+    let output = match tokio::time::timeout(Duration::from_secs(timeout_secs), run).await {
+        Ok(output) => output.map_err(|e| format!("钩子命令执行失败: {}", e))?,
+        Err(_) => {
+            tree.kill();
+            return Err(format!("钩子命令超时（{}s）: {}", timeout_secs, command));
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match output.status.code() {
+        Some(0) => Ok(parse_external_response(&stdout)),
+        Some(HOOK_EXIT_BLOCK) => Ok(ExternalHookResponse {
+            decision: Some("block".to_string()),
+            reason: Some(stderr.trim().to_string()).filter(|s| !s.is_empty()),
+            ..Default::default()
+        }),
+        code => Err(format!("钩子命令退出码 {:?}: {}", code, stderr.trim())),
+    }
+}
+
+/// 钩子命令启动的进程树
+///
+/// `kill_on_drop` 只结束 `sh -c` / `cmd /C` 外壳，超时时需要结束外壳启动的所有进程
+#[cfg(unix)]
+struct ProcessTree(Option<i32>);
+
+#[cfg(unix)]
+impl ProcessTree {
+    fn attach(child: &tokio::process::Child) -> Self {
+        // 以 process_group(0) 启动，进程组 ID 即子进程 ID
+        Self(child.id().map(|pid| pid as i32))
+    }
+
+    fn kill(&self) {
+        if let Some(pgid) = self.0 {
+            // SAFETY: killpg 只发送信号，不涉及内存
+            unsafe {
+                libc::killpg(pgid, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// 钩子命令启动的进程树
+///
+/// `kill_on_drop` 只结束 `sh -c` / `cmd /C` 外壳，超时时通过作业对象结束外壳启动的所有进程
+#[cfg(windows)]
+struct ProcessTree(Option<windows::Win32::Foundation::HANDLE>);
+
+#[cfg(windows)]
+impl ProcessTree {
+    fn attach(child: &tokio::process::Child) -> Self {
+        use windows::core::PCWSTR;
+        use windows::Win32::Foundation::{CloseHandle, HANDLE};
+        use windows::Win32::System::JobObjects::{AssignProcessToJobObject, CreateJobObjectW};
+
+        let Some(process) = child.raw_handle() else {
+            return Self(None);
+        };
+        // SAFETY: 进程句柄在 child 存活期间有效，作业对象句柄由本结构持有并在 Drop 时关闭
+        unsafe {
+            let Ok(job) = CreateJobObjectW(None, PCWSTR::null()) else {
+                return Self(None);
+            };
+            if AssignProcessToJobObject(job, HANDLE(process as isize)).is_err() {
+                let _ = CloseHandle(job);
+                return Self(None);
+            }
+            Self(Some(job))
+        }
+    }
+
+    fn kill(&self) {
+        if let Some(job) = self.0 {
+            // SAFETY: 作业对象句柄在 Drop 前有效
+            unsafe {
+                let _ = windows::Win32::System::JobObjects::TerminateJobObject(job, 1);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ProcessTree {
+    fn drop(&mut self) {
+        if let Some(job) = self.0.take() {
+            // SAFETY: 句柄由 CreateJobObjectW 创建且只关闭一次
+            unsafe {
+                let _ = windows::Win32::Foundation::CloseHandle(job);
+            }
+        }
+    }
+}
+
+/// 调用本地 Webhook 钩子
+async fn run_webhook_hook(
+    url: &str,
+    payload: &serde_json::Value,
+    timeout_secs: u64,
+) -> Result<ExternalHookResponse, String> {
+    let parsed = url::Url::parse(url).map_err(|e| format!("无效的 Webhook 地址: {}", e))?;
+    let is_loopback = match parsed.host() {
+        Some(url::Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false,
+    };
+    if !is_loopback {
+        return Err(format!("Webhook 钩子仅允许本地地址: {}", url));
+    }
+
+    let response = reqwest::Client::new()
+        .post(parsed)
+        .timeout(Duration::from_secs(timeout_secs))
+        .json(payload)
+        .send()
+        .await
+        .map_err(|e| format!("调用 Webhook 失败: {}", e))?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("读取 Webhook 响应失败: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("Webhook 返回 {}: {}", status, body.trim()));
+    }
+    Ok(parse_external_response(&body))
+}
+
+/// 解析外部钩子输出，非 JSON 输出视为无操作
+fn parse_external_response(output: &str) -> ExternalHookResponse {
+    let output = output.trim();
+    if !output.starts_with('{') {
+        if !output.is_empty() {
+            debug!("钩子输出: {}", output);
+        }
+        return ExternalHookResponse::default();
+    }
+    serde_json::from_str(output).unwrap_or_else(|e| {
+        warn!("解析钩子输出失败: {}", e);
+        ExternalHookResponse::default()
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -602,6 +1035,7 @@ mod tests {
             session_id: "test-session".to_string(),
             tool_name: None,
             tool_parameters: None,
+            tool_input: None,
             tool_result: None,
             message_content: None,
             message_count: 0,
@@ -628,6 +1062,7 @@ mod tests {
             session_id: "test-session".to_string(),
             tool_name: Some("test_tool".to_string()),
             tool_parameters: None,
+            tool_input: None,
             tool_result: Some("发生了一个错误".to_string()),
             message_content: Some("这里有一个错误需要处理".to_string()),
             message_count: 5,
@@ -671,6 +1106,7 @@ mod tests {
             session_id: "test-session".to_string(),
             tool_name: Some("custom_tool".to_string()),
             tool_parameters: None,
+            tool_input: None,
             tool_result: None,
             message_content: None,
             message_count: 0,
@@ -697,6 +1133,7 @@ mod tests {
             session_id: "test-session-123".to_string(),
             tool_name: Some("test_tool".to_string()),
             tool_parameters: None,
+            tool_input: None,
             tool_result: None,
             message_content: Some("这是测试消息".to_string()),
             message_count: 42,
@@ -716,4 +1153,206 @@ mod tests {
         assert!(result.contains("42"));
         assert!(result.contains("custom_value"));
     }
+
+    fn tool_rule(id: &str, trigger: HookTrigger, actions: Vec<HookAction>) -> HookRule {
+        HookRule {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            trigger,
+            conditions: vec![HookCondition::ToolNameEquals("write_file".to_string())],
+            actions,
+            enabled: true,
+            priority: 0,
+            created_at: 0,
+        }
+    }
+
+    fn tool_context(input: serde_json::Value) -> HookContext {
+        HookContext {
+            session_id: "test-session".to_string(),
+            tool_name: Some("write_file".to_string()),
+            tool_parameters: None,
+            tool_input: Some(input),
+            tool_result: None,
+            message_content: None,
+            message_count: 0,
+            error_info: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_block_and_modify_input() {
+        let (_memory_service, hooks_service, _temp_dir) = create_test_services();
+        hooks_service
+            .add_hook_rule(tool_rule(
+                "modify",
+                HookTrigger::PreToolUse,
+                vec![
+                    HookAction::ModifyInput {
+                        set: HashMap::from([("mode".to_string(), serde_json::json!("0644"))]),
+                    },
+                    HookAction::InjectFeedback {
+                        message: "{tool_name} 已设置权限".to_string(),
+                    },
+                ],
+            ))
+            .unwrap();
+
+        let outcome = hooks_service
+            .run_hooks(
+                HookTrigger::PreToolUse,
+                &tool_context(serde_json::json!({"path": "a.txt"})),
+            )
+            .await
+            .unwrap();
+        assert!(outcome.blocked.is_none());
+        assert_eq!(
+            outcome.tool_input,
+            Some(serde_json::json!({"path": "a.txt", "mode": "0644"}))
+        );
+        assert_eq!(outcome.feedback, vec!["write_file 已设置权限"]);
+
+        let mut block = tool_rule(
+            "block",
+            HookTrigger::PreToolUse,
+            vec![HookAction::Block {
+                reason: "禁止写入".to_string(),
+            }],
+        );
+        block.priority = 1;
+        hooks_service.add_hook_rule(block).unwrap();
+        let outcome = hooks_service
+            .run_hooks(
+                HookTrigger::PreToolUse,
+                &tool_context(serde_json::json!({})),
+            )
+            .await
+            .unwrap();
+        assert_eq!(outcome.blocked.as_deref(), Some("禁止写入"));
+    }
+
+    #[cfg(unix)]
+    async fn run_command(
+        hooks_service: &ToolHooksService,
+        context: &HookContext,
+        command: &str,
+        timeout_secs: u64,
+    ) -> HookOutcome {
+        let _ = hooks_service.remove_hook_rule("command");
+        hooks_service
+            .add_hook_rule(tool_rule(
+                "command",
+                HookTrigger::PreToolUse,
+                vec![HookAction::RunCommand {
+                    command: command.to_string(),
+                    timeout_secs,
+                }],
+            ))
+            .unwrap();
+        hooks_service
+            .run_hooks(HookTrigger::PreToolUse, context)
+            .await
+            .unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_command_exit_codes() {
+        let (_memory_service, hooks_service, _temp_dir) = create_test_services();
+        let context = tool_context(serde_json::json!({"path": "secret.env"}));
+        // 退出码 2：stderr 作为阻止原因，stdin 为钩子输入
+        let outcome = run_command(
+            &hooks_service,
+            &context,
+            r#"grep -q '"tool_input":{"path":"secret.env"}' && echo "不允许写入 .env" >&2 && exit 2"#,
+            10,
+        )
+        .await;
+        assert_eq!(outcome.blocked.as_deref(), Some("不允许写入 .env"));
+
+        // 退出码 0：解析 stdout JSON
+        let outcome = run_command(
+            &hooks_service,
+            &context,
+            r#"cat >/dev/null; echo '{"tool_input":{"path":"safe.txt"},"feedback":"已改写路径"}'"#,
+            10,
+        )
+        .await;
+        assert_eq!(
+            outcome.tool_input,
+            Some(serde_json::json!({"path": "safe.txt"}))
+        );
+        assert_eq!(outcome.feedback, vec!["已改写路径"]);
+
+        // 其他退出码与超时不阻止工具执行
+        let outcome = run_command(&hooks_service, &context, "exit 1", 10).await;
+        assert_eq!(outcome, HookOutcome::default());
+        let outcome = run_command(&hooks_service, &context, "sleep 5", 1).await;
+        assert_eq!(outcome, HookOutcome::default());
+        let stats = hooks_service.get_execution_stats().unwrap();
+        assert_eq!(stats["command"].failure_count, 2);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_run_command_timeout_kills_process_group() {
+        let (_memory_service, hooks_service, temp_dir) = create_test_services();
+        let context = tool_context(serde_json::json!({}));
+        let pid_file = temp_dir.path().join("child.pid");
+
+        // 外壳启动的后台子进程在超时后也应被结束
+        let command = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let outcome = run_command(&hooks_service, &context, &command, 1).await;
+        assert_eq!(outcome, HookOutcome::default());
+
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = format!("/proc/{}/stat", pid.trim());
+        let mut alive = true;
+        for _ in 0..50 {
+            // 已结束的进程可能短暂保留为僵尸进程（状态 Z）
+            alive = std::fs::read_to_string(&stat)
+                .map(|s| !s.contains(") Z "))
+                .unwrap_or(false);
+            if !alive {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!alive, "超时后钩子子进程仍在运行");
+    }
+
+    #[tokio::test]
+    async fn test_post_tool_block_becomes_feedback() {
+        let (_memory_service, hooks_service, _temp_dir) = create_test_services();
+        hooks_service
+            .add_hook_rule(tool_rule(
+                "lint",
+                HookTrigger::PostToolUse,
+                vec![
+                    HookAction::Block {
+                        reason: "请先运行格式化".to_string(),
+                    },
+                    HookAction::Webhook {
+                        url: "https://example.com/hook".to_string(),
+                        timeout_secs: 1,
+                    },
+                ],
+            ))
+            .unwrap();
+
+        let outcome = hooks_service
+            .run_hooks(
+                HookTrigger::PostToolUse,
+                &tool_context(serde_json::json!({})),
+            )
+            .await
+            .unwrap();
+        assert!(outcome.blocked.is_none());
+        assert_eq!(outcome.feedback, vec!["请先运行格式化"]);
+        // 非本地 Webhook 被拒绝，记为失败
+        let stats = hooks_service.get_execution_stats().unwrap();
+        assert_eq!(stats["lint"].failure_count, 1);
+    }
 }
@@ -217,6 +217,17 @@ impl RateLimitTracker {
         self.model_limits.retain(|_, record| record.reset_at > now);
     }
 
+    /// 获取最早的限流结束时间（包含模型级别限流）
+    pub fn earliest_recovery(&self) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        self.account_limits
+            .iter()
+            .chain(self.model_limits.iter())
+            .map(|entry| entry.value().reset_at)
+            .filter(|reset_at| *reset_at > now)
+            .min()
+    }
+
     /// 获取所有被限流的账号
     pub fn get_rate_limited_accounts(&self) -> Vec<String> {
         let now = Utc::now();
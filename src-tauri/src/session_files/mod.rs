@@ -11,7 +11,9 @@
 //! │   │   ├── article.md
 //! │   │   ├── song-spec.md
 //! │   │   └── ...
-//! │   └── canvas/             # 画布状态快照
+//! │   ├── canvas/             # 画布状态快照
+//! │   └── checkpoints/        # 检查点文件快照
+//! │       └── {checkpoint-id}/
 //! └── ...
 //! ```
 
@@ -3,7 +3,7 @@
 //! 提供会话文件的 CRUD 操作和生命周期管理。
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use chrono::Utc;
 
@@ -52,6 +52,13 @@ impl SessionFileStorage {
         self.get_session_dir(session_id).join("files")
     }
 
+    /// 获取文件快照目录路径
+    fn get_snapshot_dir(&self, session_id: &str, snapshot_id: &str) -> PathBuf {
+        self.get_session_dir(session_id)
+            .join("checkpoints")
+            .join(snapshot_id)
+    }
+
     // ========================================================================
     // 会话管理
     // ========================================================================
@@ -295,6 +302,55 @@ impl SessionFileStorage {
         Ok(SessionDetail { meta, files })
     }
 
+    // ========================================================================
+    // 文件快照
+    // ========================================================================
+
+    /// 为会话文件创建快照，返回快照中的文件数量
+    pub fn create_snapshot(&self, session_id: &str, snapshot_id: &str) -> Result<u32, String> {
+        self.get_or_create_session(session_id)?;
+
+        let snapshot_dir = self.get_snapshot_dir(session_id, snapshot_id);
+        if snapshot_dir.exists() {
+            return Err(format!("快照已存在: {}", snapshot_id));
+        }
+        let file_count = Self::copy_dir(&self.get_files_dir(session_id), &snapshot_dir)?;
+
+        tracing::info!(
+            "[SessionFileStorage] 创建文件快照: {} ({} 个文件)",
+            snapshot_id,
+            file_count
+        );
+        Ok(file_count)
+    }
+
+    /// 将会话文件恢复到快照状态（快照之后新增的文件会被删除）
+    pub fn restore_snapshot(&self, session_id: &str, snapshot_id: &str) -> Result<(), String> {
+        let snapshot_dir = self.get_snapshot_dir(session_id, snapshot_id);
+        if !snapshot_dir.is_dir() {
+            return Err(format!("快照不存在: {}", snapshot_id));
+        }
+
+        let files_dir = self.get_files_dir(session_id);
+        if files_dir.exists() {
+            fs::remove_dir_all(&files_dir).map_err(|e| format!("清空文件目录失败: {}", e))?;
+        }
+        Self::copy_dir(&snapshot_dir, &files_dir)?;
+        self.refresh_meta_stats(session_id)?;
+
+        tracing::info!("[SessionFileStorage] 恢复文件快照: {}", snapshot_id);
+        Ok(())
+    }
+
+    /// 删除文件快照
+    pub fn delete_snapshot(&self, session_id: &str, snapshot_id: &str) -> Result<(), String> {
+        let snapshot_dir = self.get_snapshot_dir(session_id, snapshot_id);
+        if snapshot_dir.exists() {
+            fs::remove_dir_all(&snapshot_dir).map_err(|e| format!("删除快照失败: {}", e))?;
+        }
+        Ok(())
+    }
+
     // ========================================================================
     // 清理功能
     // ========================================================================
@@ -350,6 +406,28 @@ impl SessionFileStorage {
         self.save_meta(session_id, &meta)
     }
 
+    /// 递归复制目录，返回复制的文件数量
+    fn copy_dir(src: &Path, dst: &Path) -> Result<u32, String> {
+        fs::create_dir_all(dst).map_err(|e| format!("创建目录失败: {}", e))?;
+        if !src.exists() {
+            return Ok(0);
+        }
+
+        let mut count = 0;
+        let entries = fs::read_dir(src).map_err(|e| format!("读取目录失败: {}", e))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let target = dst.join(entry.file_name());
+            if path.is_dir() {
+                count += Self::copy_dir(&path, &target)?;
+            } else {
+                fs::copy(&path, &target).map_err(|e| format!("复制文件失败: {}", e))?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
     /// 根据文件扩展名检测文件类型
     fn detect_file_type(file_name: &str) -> String {
         let ext = file_name.rsplit('.').next().unwrap_or("").to_lowercase();
@@ -432,4 +510,33 @@ mod tests {
         storage.delete_session("test-session-4").unwrap();
         assert!(!storage.session_exists("test-session-4"));
     }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let (storage, _temp) = create_test_storage();
+        storage
+            .save_file("test-session-5", "draft.md", "v1")
+            .unwrap();
+
+        assert_eq!(storage.create_snapshot("test-session-5", "cp1").unwrap(), 1);
+        assert!(storage.create_snapshot("test-session-5", "cp1").is_err());
+
+        storage
+            .save_file("test-session-5", "draft.md", "v2")
+            .unwrap();
+        storage
+            .save_file("test-session-5", "extra.md", "new")
+            .unwrap();
+
+        storage.restore_snapshot("test-session-5", "cp1").unwrap();
+        assert_eq!(
+            storage.read_file("test-session-5", "draft.md").unwrap(),
+            "v1"
+        );
+        assert_eq!(storage.list_files("test-session-5").unwrap().len(), 1);
+        assert_eq!(storage.get_meta("test-session-5").unwrap().file_count, 1);
+
+        storage.delete_snapshot("test-session-5", "cp1").unwrap();
+        assert!(storage.restore_snapshot("test-session-5", "cp1").is_err());
+    }
 }
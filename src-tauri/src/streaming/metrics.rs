@@ -8,7 +8,9 @@
 //! - 需求 7.5: 记录流式指标（吞吐量、延迟、错误率）
 
 use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use tracing::info;
 
 /// 流式传输指标
@@ -322,6 +324,88 @@ impl StreamMetrics {
     }
 }
 
+// ============================================================================
+// TTFB 滑动窗口
+// ============================================================================
+
+/// TTFB 滑动窗口
+///
+/// 汇总最近若干次流式传输的首字节时间，用于计算分位数（如对冲请求的 p95 阈值）。
+#[derive(Debug)]
+pub struct TtfbWindow {
+    /// 最近的 TTFB 样本（毫秒）
+    samples: Mutex<VecDeque<u64>>,
+    /// 窗口容量
+    capacity: usize,
+}
+
+impl TtfbWindow {
+    /// 默认窗口容量
+    pub const DEFAULT_CAPACITY: usize = 200;
+
+    /// 计算分位数所需的最少样本数
+    pub const MIN_SAMPLES: usize = 20;
+
+    /// 创建指定容量的窗口
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// 记录一个 TTFB 样本
+    pub fn record(&self, ttfb_ms: u64) {
+        let mut samples = self.samples.lock();
+        if samples.len() >= self.capacity {
+            samples.pop_front();
+        }
+        samples.push_back(ttfb_ms);
+    }
+
+    /// 从流式指标中记录 TTFB（未收到首字节时忽略）
+    pub fn record_metrics(&self, metrics: &StreamMetrics) {
+        if let Some(ttfb) = metrics.ttfb_ms {
+            self.record(ttfb);
+        }
+    }
+
+    /// 当前样本数
+    pub fn len(&self) -> usize {
+        self.samples.lock().len()
+    }
+
+    /// 是否没有样本
+    pub fn is_empty(&self) -> bool {
+        self.samples.lock().is_empty()
+    }
+
+    /// 计算分位数（0.0 - 1.0）
+    ///
+    /// 样本数少于 `MIN_SAMPLES` 时返回 None，避免少量样本导致阈值抖动。
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        let samples = self.samples.lock();
+        if samples.len() < Self::MIN_SAMPLES {
+            return None;
+        }
+        let mut sorted: Vec<u64> = samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = (p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64).round() as usize;
+        Some(sorted[rank])
+    }
+
+    /// p95 TTFB
+    pub fn p95(&self) -> Option<u64> {
+        self.percentile(0.95)
+    }
+}
+
+impl Default for TtfbWindow {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
 // ============================================================================
 // 测试模块
 // ============================================================================
@@ -564,4 +648,41 @@ mod tests {
         metrics.log_metrics(Some("test-flow-id"));
         metrics.log_metrics(None);
     }
+
+    #[test]
+    fn test_ttfb_window_percentile() {
+        let window = TtfbWindow::new(100);
+        for ttfb in 1..=TtfbWindow::MIN_SAMPLES as u64 - 1 {
+            window.record(ttfb * 10);
+        }
+        // 样本不足时不计算分位数
+        assert_eq!(window.p95(), None);
+
+        for ttfb in TtfbWindow::MIN_SAMPLES as u64..=100 {
+            window.record(ttfb * 10);
+        }
+        assert_eq!(window.len(), 100);
+        assert_eq!(window.p95(), Some(950));
+        assert_eq!(window.percentile(0.0), Some(10));
+    }
+
+    #[test]
+    fn test_ttfb_window_evicts_oldest() {
+        let window = TtfbWindow::new(TtfbWindow::MIN_SAMPLES);
+        for _ in 0..TtfbWindow::MIN_SAMPLES {
+            window.record(5000);
+        }
+        for _ in 0..TtfbWindow::MIN_SAMPLES {
+            window.record(100);
+        }
+        assert_eq!(window.len(), TtfbWindow::MIN_SAMPLES);
+        assert_eq!(window.p95(), Some(100));
+
+        let mut metrics = StreamMetrics::new();
+        window.record_metrics(&metrics);
+        assert_eq!(window.len(), TtfbWindow::MIN_SAMPLES);
+        metrics.record_chunk(10);
+        window.record_metrics(&metrics);
+        assert_eq!(window.len(), TtfbWindow::MIN_SAMPLES);
+    }
 }
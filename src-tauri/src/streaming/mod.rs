@@ -37,7 +37,7 @@ pub use manager::{
     ManagedStream, ManagedStreamWithCallback, StreamConfig, StreamContext, StreamEvent,
     StreamManager, TimeoutStream,
 };
-pub use metrics::StreamMetrics;
+pub use metrics::{StreamMetrics, TtfbWindow};
 pub use traits::{
     reqwest_stream_to_stream_response, StreamFormat as TraitsStreamFormat, StreamResponse,
     StreamingProvider,
@@ -0,0 +1,275 @@
+//! 结构化输出（response_format）支持
+//!
+//! OpenAI 的 `response_format` 在不同后端的支持程度不同：
+//! - OpenAI 兼容后端原生支持 json_object / json_schema
+//! - Antigravity (Gemini) 映射为 responseMimeType + responseSchema
+//! - Claude API Key（非流式）通过强制调用单个工具实现
+//! - 其余后端由代理注入 schema 指令，校验输出并在预算内要求模型修正
+//!
+//! 本模块负责解析请求中的 schema、选择实现方式、注入指令以及校验输出。
+
+pub mod validator;
+
+use crate::flow_monitor::StructuredOutputMode;
+use crate::models::openai::{ChatCompletionRequest, ChatMessage, MessageContent};
+use crate::models::provider_pool_model::CredentialData;
+use serde_json::Value;
+
+/// 请求中声明的结构化输出要求
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredOutputSpec {
+    /// schema 名称（json_object 时为 None）
+    pub name: Option<String>,
+    /// JSON Schema（json_object 时为 None，只要求输出合法 JSON 对象）
+    pub schema: Option<Value>,
+}
+
+impl StructuredOutputSpec {
+    /// 从请求的 response_format 解析结构化输出要求
+    ///
+    /// 未设置 response_format 或类型为 text 时返回 None。
+    pub fn from_request(request: &ChatCompletionRequest) -> Option<Self> {
+        let format = request.response_format.as_ref()?;
+        match format.get("type").and_then(|t| t.as_str())? {
+            "json_object" => Some(Self {
+                name: None,
+                schema: None,
+            }),
+            "json_schema" => {
+                let spec = format.get("json_schema");
+                Some(Self {
+                    name: spec
+                        .and_then(|s| s.get("name"))
+                        .and_then(|n| n.as_str())
+                        .map(String::from),
+                    schema: spec.and_then(|s| s.get("schema")).cloned(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// 校验模型输出
+    ///
+    /// 输出必须是 JSON（允许包裹在 ```json 代码块中）；json_object 要求为对象，
+    /// json_schema 要求符合 schema。成功时返回解析后的值。
+    pub fn check(&self, text: &str) -> Result<Value, Vec<String>> {
+        let value: Value = serde_json::from_str(extract_json(text))
+            .map_err(|e| vec![format!("输出不是合法 JSON: {e}")])?;
+
+        let errors = match &self.schema {
+            Some(schema) => validator::validate(schema, &value),
+            None if !value.is_object() => vec!["$: 输出应为 JSON 对象".to_string()],
+            None => Vec::new(),
+        };
+
+        if errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// 代理注入到 system 消息中的输出要求
+    fn instructions(&self) -> String {
+        match &self.schema {
+            Some(schema) => format!(
+                "You must respond with a single JSON value that conforms to the following JSON Schema. \
+                 Output only the JSON, without markdown fences or any other text.\n\nJSON Schema:\n{}",
+                serde_json::to_string_pretty(schema).unwrap_or_default()
+            ),
+            None => "You must respond with a single valid JSON object. \
+                     Output only the JSON, without markdown fences or any other text."
+                .to_string(),
+        }
+    }
+}
+
+/// 根据凭证类型选择结构化输出的实现方式
+pub fn mode_for_credential(credential: &CredentialData, stream: bool) -> StructuredOutputMode {
+    match credential {
+        CredentialData::OpenAIKey { .. }
+        | CredentialData::VertexKey { .. }
+        | CredentialData::AntigravityOAuth { .. } => StructuredOutputMode::Native,
+        // 仅自定义 base_url（OpenAI 兼容代理）时原样透传
+        CredentialData::AnthropicKey {
+            base_url: Some(_), ..
+        } => StructuredOutputMode::Native,
+        // 流式路径直接转发 Anthropic SSE，无法强制工具调用
+        CredentialData::ClaudeKey { .. } if !stream => StructuredOutputMode::ToolForcing,
+        _ => StructuredOutputMode::PromptFallback,
+    }
+}
+
+/// 为不支持 response_format 的后端注入输出要求
+///
+/// 追加到已有 system 消息末尾，没有则在开头插入一条；同时移除 response_format，
+/// 避免上游因不认识该参数而报错。
+pub fn inject_instructions(request: &mut ChatCompletionRequest, spec: &StructuredOutputSpec) {
+    let instructions = spec.instructions();
+    request.response_format = None;
+
+    if let Some(system) = request.messages.iter_mut().find(|m| m.role == "system") {
+        let existing = system.get_content_text();
+        system.content = Some(MessageContent::Text(if existing.is_empty() {
+            instructions
+        } else {
+            format!("{existing}\n\n{instructions}")
+        }));
+    } else {
+        request
+            .messages
+            .insert(0, text_message("system", instructions));
+    }
+}
+
+/// 追加一轮修正对话：模型上一次的输出 + 列出校验错误的用户消息
+pub fn push_repair_messages(request: &mut ChatCompletionRequest, output: &str, errors: &[String]) {
+    request
+        .messages
+        .push(text_message("assistant", output.to_string()));
+    request.messages.push(text_message(
+        "user",
+        format!(
+            "Your previous response did not satisfy the required JSON format:\n- {}\n\n\
+             Respond again with only the corrected JSON.",
+            errors.join("\n- ")
+        ),
+    ));
+}
+
+/// 去掉模型常见的 ```json 代码块包裹
+pub fn extract_json(text: &str) -> &str {
+    let trimmed = text.trim();
+    let Some(rest) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    let body = rest.split_once('\n').map(|(_, body)| body).unwrap_or("");
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+fn text_message(role: &str, text: String) -> ChatMessage {
+    ChatMessage {
+        role: role.to_string(),
+        content: Some(MessageContent::Text(text)),
+        tool_calls: None,
+        tool_call_id: None,
+        reasoning_content: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request_with_format(format: Value) -> ChatCompletionRequest {
+        serde_json::from_value(json!({
+            "model": "m",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": "Who?"}
+            ],
+            "response_format": format
+        }))
+        .unwrap()
+    }
+
+    fn person_format() -> Value {
+        json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": "person",
+                "schema": {
+                    "type": "object",
+                    "properties": {"name": {"type": "string"}},
+                    "required": ["name"]
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_spec_from_request() {
+        let spec = StructuredOutputSpec::from_request(&request_with_format(person_format()));
+        assert_eq!(spec.unwrap().name.as_deref(), Some("person"));
+
+        let spec = StructuredOutputSpec::from_request(&request_with_format(
+            json!({"type": "json_object"}),
+        ))
+        .unwrap();
+        assert!(spec.schema.is_none());
+
+        assert!(
+            StructuredOutputSpec::from_request(&request_with_format(json!({"type": "text"})))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_check_output() {
+        let spec =
+            StructuredOutputSpec::from_request(&request_with_format(person_format())).unwrap();
+
+        assert_eq!(
+            spec.check("```json\n{\"name\": \"Ann\"}\n```").unwrap(),
+            json!({"name": "Ann"})
+        );
+        assert!(spec.check("{\"age\": 3}").unwrap_err()[0].contains("name"));
+        assert!(spec.check("Sure! Here it is").unwrap_err()[0].contains("JSON"));
+
+        let object_only = StructuredOutputSpec {
+            name: None,
+            schema: None,
+        };
+        assert!(object_only.check("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn test_inject_and_repair_messages() {
+        let mut request = request_with_format(person_format());
+        let spec = StructuredOutputSpec::from_request(&request).unwrap();
+
+        inject_instructions(&mut request, &spec);
+        assert!(request.response_format.is_none());
+        assert_eq!(request.messages.len(), 2);
+        let system = request.messages[0].get_content_text();
+        assert!(system.starts_with("Be brief.\n\n"));
+        assert!(system.contains("\"required\""));
+
+        push_repair_messages(
+            &mut request,
+            "{}",
+            &["$: 缺少必填字段 \"name\"".to_string()],
+        );
+        assert_eq!(request.messages.len(), 4);
+        assert_eq!(request.messages[2].role, "assistant");
+        assert!(request.messages[3]
+            .get_content_text()
+            .contains("缺少必填字段"));
+    }
+
+    #[test]
+    fn test_mode_for_credential() {
+        let claude = CredentialData::ClaudeKey {
+            api_key: "k".to_string(),
+            base_url: None,
+        };
+        assert_eq!(
+            mode_for_credential(&claude, false),
+            StructuredOutputMode::ToolForcing
+        );
+        assert_eq!(
+            mode_for_credential(&claude, true),
+            StructuredOutputMode::PromptFallback
+        );
+
+        let kiro = CredentialData::KiroOAuth {
+            creds_file_path: "x".to_string(),
+        };
+        assert_eq!(
+            mode_for_credential(&kiro, false),
+            StructuredOutputMode::PromptFallback
+        );
+    }
+}
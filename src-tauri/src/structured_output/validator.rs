@@ -0,0 +1,284 @@
+//! JSON Schema 校验
+//!
+//! 实现结构化输出常用的 JSON Schema 子集：type、enum、const、properties、required、
+//! additionalProperties、items、anyOf / oneOf / allOf、长度与数值范围，以及指向
+//! `#/$defs` / `#/definitions` 的本地 $ref。未识别的关键字会被忽略。
+
+use serde_json::Value;
+
+/// $ref 最大解析深度，防止循环引用
+const MAX_REF_DEPTH: usize = 32;
+
+/// 校验 JSON 值是否符合 schema
+///
+/// 返回所有校验错误，每条错误以 JSONPath 风格的位置开头（如 `$.items[0].name`）。
+/// 返回空列表表示校验通过。
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(schema, schema, value, "$", 0, &mut errors);
+    errors
+}
+
+fn validate_at(
+    root: &Value,
+    schema: &Value,
+    value: &Value,
+    path: &str,
+    depth: usize,
+    errors: &mut Vec<String>,
+) {
+    let obj = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => {
+            errors.push(format!("{path}: schema 不允许任何值"));
+            return;
+        }
+        Value::Object(obj) => obj,
+        _ => return,
+    };
+
+    if let Some(reference) = obj.get("$ref").and_then(|r| r.as_str()) {
+        if depth >= MAX_REF_DEPTH {
+            errors.push(format!("{path}: $ref 嵌套过深: {reference}"));
+            return;
+        }
+        match resolve_ref(root, reference) {
+            Some(target) => validate_at(root, target, value, path, depth + 1, errors),
+            None => errors.push(format!("{path}: 无法解析 $ref: {reference}")),
+        }
+        return;
+    }
+
+    if let Some(expected) = obj.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(arr) => arr.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| matches_type(t, value)) {
+            errors.push(format!(
+                "{path}: 类型应为 {}，实际为 {}",
+                types.join(" | "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(options)) = obj.get("enum") {
+        if !options.contains(value) {
+            errors.push(format!("{path}: 值 {value} 不在 enum 允许范围内"));
+        }
+    }
+
+    if let Some(expected) = obj.get("const") {
+        if expected != value {
+            errors.push(format!("{path}: 值应为 {expected}"));
+        }
+    }
+
+    if let Some(Value::Array(subschemas)) = obj.get("allOf") {
+        for sub in subschemas {
+            validate_at(root, sub, value, path, depth, errors);
+        }
+    }
+
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(Value::Array(subschemas)) = obj.get(keyword) {
+            let matched = subschemas.iter().any(|sub| {
+                let mut sub_errors = Vec::new();
+                validate_at(root, sub, value, path, depth, &mut sub_errors);
+                sub_errors.is_empty()
+            });
+            if !matched {
+                errors.push(format!("{path}: 不匹配 {keyword} 中的任何一个 schema"));
+            }
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            let properties = obj.get("properties").and_then(|p| p.as_object());
+
+            if let Some(Value::Array(required)) = obj.get("required") {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !map.contains_key(key) {
+                        errors.push(format!("{path}: 缺少必填字段 \"{key}\""));
+                    }
+                }
+            }
+
+            for (key, child) in map {
+                let child_path = format!("{path}.{key}");
+                match properties.and_then(|p| p.get(key)) {
+                    Some(sub) => validate_at(root, sub, child, &child_path, depth, errors),
+                    None => match obj.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{path}: 不允许额外字段 \"{key}\""));
+                        }
+                        Some(sub @ Value::Object(_)) => {
+                            validate_at(root, sub, child, &child_path, depth, errors)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = obj.get("minItems").and_then(|m| m.as_u64()) {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{path}: 至少需要 {min} 个元素"));
+                }
+            }
+            if let Some(max) = obj.get("maxItems").and_then(|m| m.as_u64()) {
+                if items.len() as u64 > max {
+                    errors.push(format!("{path}: 最多允许 {max} 个元素"));
+                }
+            }
+            if let Some(item_schema) = obj.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(
+                        root,
+                        item_schema,
+                        item,
+                        &format!("{path}[{i}]"),
+                        depth,
+                        errors,
+                    );
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = obj.get("minLength").and_then(|m| m.as_u64()) {
+                if len < min {
+                    errors.push(format!("{path}: 长度至少为 {min}"));
+                }
+            }
+            if let Some(max) = obj.get("maxLength").and_then(|m| m.as_u64()) {
+                if len > max {
+                    errors.push(format!("{path}: 长度最多为 {max}"));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = obj.get("minimum").and_then(|m| m.as_f64()) {
+                if n < min {
+                    errors.push(format!("{path}: 不能小于 {min}"));
+                }
+            }
+            if let Some(max) = obj.get("maximum").and_then(|m| m.as_f64()) {
+                if n > max {
+                    errors.push(format!("{path}: 不能大于 {max}"));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 解析本地 $ref（`#`、`#/$defs/...`、`#/definitions/...` 等 JSON Pointer）
+fn resolve_ref<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    let pointer = reference.strip_prefix('#')?;
+    if pointer.is_empty() {
+        return Some(root);
+    }
+    root.pointer(pointer)
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => match value {
+            Value::Number(n) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+            }
+            _ => false,
+        },
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn person_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "role": {"enum": ["admin", "user"]},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2}
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_value_passes() {
+        let value = json!({"name": "Ann", "age": 30, "role": "admin", "tags": ["a"]});
+        assert!(validate(&person_schema(), &value).is_empty());
+    }
+
+    #[test]
+    fn test_reports_each_violation_with_path() {
+        let value = json!({"age": 1.5, "role": "guest", "tags": ["a", 2, "c"], "extra": true});
+        let errors = validate(&person_schema(), &value);
+
+        assert!(errors.iter().any(|e| e.contains("缺少必填字段 \"name\"")));
+        assert!(errors.iter().any(|e| e.starts_with("$.age:")));
+        assert!(errors.iter().any(|e| e.starts_with("$.role:")));
+        assert!(errors.iter().any(|e| e.starts_with("$.tags:")));
+        assert!(errors.iter().any(|e| e.starts_with("$.tags[1]:")));
+        assert!(errors.iter().any(|e| e.contains("\"extra\"")));
+    }
+
+    #[test]
+    fn test_any_of_and_ref() {
+        let schema = json!({
+            "$defs": {"id": {"type": "integer"}},
+            "type": "object",
+            "properties": {
+                "id": {"$ref": "#/$defs/id"},
+                "value": {"anyOf": [{"type": "string"}, {"type": "null"}]}
+            }
+        });
+
+        assert!(validate(&schema, &json!({"id": 3, "value": null})).is_empty());
+        assert_eq!(validate(&schema, &json!({"id": "x"})).len(), 1);
+        assert_eq!(validate(&schema, &json!({"value": 1})).len(), 1);
+        assert_eq!(
+            validate(&json!({"$ref": "#/$defs/missing"}), &json!(1)),
+            vec!["$: 无法解析 $ref: #/$defs/missing".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_integer_accepts_whole_floats() {
+        let schema = json!({"type": "integer"});
+        assert!(validate(&schema, &json!(2.0)).is_empty());
+        assert!(!validate(&schema, &json!(2.5)).is_empty());
+        assert!(!validate(&schema, &json!("2")).is_empty());
+    }
+}
@@ -143,7 +143,7 @@ impl ShellProc {
             shutdown_flag.clone(),
             exit_code.clone(),
             exited.clone(),
-            block_file,
+            block_file.clone(),
         );
 
         // 启动输入处理任务
@@ -153,6 +153,7 @@ impl ShellProc {
             master.clone(),
             input_rx,
             shutdown_flag.clone(),
+            block_file,
         );
 
         tracing::info!("[ShellProc] 进程已创建: block_id={}", block_id);
@@ -346,6 +347,13 @@ impl ShellProc {
                                     e
                                 );
                             }
+                            if let Err(e) = bf.record_output(output_data) {
+                                tracing::warn!(
+                                    "[ShellProc] 录制输出失败: block_id={}, error={}",
+                                    block_id,
+                                    e
+                                );
+                            }
                         }
 
                         // 发送输出事件
@@ -392,6 +400,7 @@ impl ShellProc {
         master: Arc<Mutex<Box<dyn portable_pty::MasterPty + Send>>>,
         mut input_rx: mpsc::Receiver<BlockInputUnion>,
         shutdown_flag: Arc<AtomicBool>,
+        block_file: Option<Arc<BlockFile>>,
     ) {
         tokio::spawn(async move {
             while let Some(input) = input_rx.recv().await {
@@ -414,6 +423,15 @@ impl ShellProc {
                             e
                         );
                     }
+                    if let Some(ref bf) = block_file {
+                        if let Err(e) = bf.record_input(data) {
+                            tracing::warn!(
+                                "[ShellProc] 录制输入失败: block_id={}, error={}",
+                                block_id,
+                                e
+                            );
+                        }
+                    }
                 }
 
                 // 处理终端大小调整
@@ -438,6 +456,15 @@ impl ShellProc {
                             size.rows
                         );
                     }
+                    if let Some(ref bf) = block_file {
+                        if let Err(e) = bf.record_resize(size.cols, size.rows) {
+                            tracing::warn!(
+                                "[ShellProc] 录制大小调整失败: block_id={}, error={}",
+                                block_id,
+                                e
+                            );
+                        }
+                    }
                 }
 
                 // 处理信号
@@ -6,6 +6,7 @@
 //! - `local_pty` - 本地 PTY 连接
 //! - `ssh_connection` - SSH 远程连接
 //! - `ssh_shell_proc` - SSH 远程 Shell 进程
+//! - `ssh_tunnel` - SSH 端口转发
 //! - `wsl_connection` - WSL 连接（仅 Windows）
 //! - `connection_router` - 连接类型路由
 //! - `connection_config` - 连接配置持久化
@@ -14,6 +15,7 @@
 //! - 本地 PTY 进程管理
 //! - SSH 远程连接和认证
 //! - SSH 远程 PTY 创建和数据转发
+//! - SSH 本地、远程和动态端口转发
 //! - WSL 发行版连接
 //! - 连接类型自动路由
 //! - 连接配置存储和管理
@@ -23,6 +25,7 @@ pub mod connection_router;
 pub mod local_pty;
 pub mod ssh_connection;
 pub mod ssh_shell_proc;
+pub mod ssh_tunnel;
 pub mod wsl_connection;
 
 pub use connection_config::{
@@ -38,6 +41,10 @@ pub use ssh_connection::{
     SSHConfigParser, SSHConn, SSHOpts, DEFAULT_SSH_PORT, MAX_PROXY_JUMP_DEPTH,
 };
 pub use ssh_shell_proc::SSHShellProc;
+pub use ssh_tunnel::{
+    ForwardKind, ForwardSpec, SessionTransport, TunnelManager, TunnelState, TunnelStatus,
+    TunnelTransport,
+};
 pub use wsl_connection::{
     is_wsl_conn_name, WSLConn, WSLDistro, WSLDistroState, WSLOpts, WSLShellProc,
     DEFAULT_WSL_DISTRO, WSL_CONN_PREFIX,
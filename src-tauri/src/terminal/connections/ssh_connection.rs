@@ -9,6 +9,7 @@
 //! - 远程 PTY 创建和数据转发
 //! - SSH 配置文件解析
 //! - known_hosts 验证
+//! - ssh_config 端口转发（见 `ssh_tunnel`）
 //!
 //! ## Requirements
 //! - 4.1: 解析连接字符串
@@ -26,11 +27,13 @@ use std::fmt;
 use std::net::TcpStream;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicI32, AtomicI64, Ordering};
+use std::sync::Arc;
 
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use ssh2::{KeyboardInteractivePrompt as SshKeyboardInteractivePrompt, Session};
 
+use super::ssh_tunnel::{ForwardSpec, SessionTransport, TunnelManager, TunnelStatus};
 use crate::terminal::error::TerminalError;
 
 /// 默认 SSH 端口
@@ -348,6 +351,9 @@ pub struct ConnStatus {
     pub no_wsh_reason: Option<String>,
     /// wsh 版本
     pub wsh_version: Option<String>,
+    /// 端口转发隧道
+    #[serde(default)]
+    pub tunnels: Vec<TunnelStatus>,
 }
 
 impl Default for ConnStatus {
@@ -363,6 +369,7 @@ impl Default for ConnStatus {
             wsh_error: None,
             no_wsh_reason: None,
             wsh_version: None,
+            tunnels: Vec::new(),
         }
     }
 }
@@ -579,6 +586,12 @@ pub struct SSHConn {
     no_wsh_reason: RwLock<Option<String>>,
     /// Tauri 应用句柄（用于事件广播）
     app_handle: RwLock<Option<tauri::AppHandle>>,
+    /// ssh_config 中配置的端口转发（认证成功后启动）
+    configured_forwards: RwLock<Vec<ForwardSpec>>,
+    /// 端口转发隧道
+    tunnels: RwLock<Option<Arc<TunnelManager>>>,
+    /// 隧道在后台出错时通知
+    tunnel_changed: Arc<tokio::sync::Notify>,
 }
 
 impl SSHConn {
@@ -598,6 +611,9 @@ impl SSHConn {
             wsh_error: RwLock::new(None),
             no_wsh_reason: RwLock::new(None),
             app_handle: RwLock::new(None),
+            configured_forwards: RwLock::new(Vec::new()),
+            tunnels: RwLock::new(None),
+            tunnel_changed: Arc::new(tokio::sync::Notify::new()),
         }
     }
 
@@ -695,13 +711,14 @@ impl SSHConn {
             wsh_error: self.wsh_error.read().clone(),
             no_wsh_reason: self.no_wsh_reason.read().clone(),
             wsh_version: self.wsh_version.read().clone(),
+            tunnels: self.list_forwards(),
         }
     }
 
     /// 连接到远程服务器
     ///
     /// _Requirements: 4.10, 7.2_
-    pub async fn connect(&self, _conn_flags: &ConnKeywords) -> Result<(), TerminalError> {
+    pub async fn connect(&self, conn_flags: &ConnKeywords) -> Result<(), TerminalError> {
         // 检查状态转换
         let current_state = self.state();
         if !current_state.can_transition_to(ConnectionState::Connecting) {
@@ -772,6 +789,9 @@ impl SSHConn {
             *stream = Some(tcp);
         }
 
+        // 端口转发在认证成功后启动
+        *self.configured_forwards.write() = ForwardSpec::from_keywords(conn_flags);
+
         // 注意：认证将在 authenticate 方法中完成
         // 这里只完成连接建立
 
@@ -799,6 +819,7 @@ impl SSHConn {
                     self.last_connect_time
                         .store(chrono::Utc::now().timestamp(), Ordering::SeqCst);
                     self.active_conn_num.fetch_add(1, Ordering::SeqCst);
+                    self.start_configured_forwards(session);
                     self.broadcast_conn_change();
                     return Ok(());
                 }
@@ -1067,6 +1088,12 @@ impl SSHConn {
     pub async fn close(&self) -> Result<(), TerminalError> {
         tracing::info!("[SSHConn] 断开连接: {}", self.opts);
 
+        // 停止端口转发
+        let tunnels = self.tunnels.write().take();
+        if let Some(tunnels) = tunnels {
+            tunnels.close_all();
+        }
+
         // 断开 SSH 会话
         {
             let mut session = self.session.write();
@@ -1084,6 +1111,7 @@ impl SSHConn {
         self.set_state(ConnectionState::Disconnected);
         self.active_conn_num.fetch_sub(1, Ordering::SeqCst);
         self.broadcast_conn_change();
+        self.tunnel_changed.notify_one();
 
         Ok(())
     }
@@ -1133,6 +1161,7 @@ impl SSHConn {
                     self.last_connect_time
                         .store(chrono::Utc::now().timestamp(), Ordering::SeqCst);
                     self.active_conn_num.fetch_add(1, Ordering::SeqCst);
+                    self.start_configured_forwards(session);
                     self.broadcast_conn_change();
                     return Ok(());
                 }
@@ -1254,6 +1283,90 @@ impl SSHConn {
         }
     }
 
+    /// 启动 ssh_config 中配置的端口转发
+    ///
+    /// 与 OpenSSH 默认的 `ExitOnForwardFailure no` 一致，单条转发失败不影响连接，
+    /// 失败原因记录在隧道状态中。
+    fn start_configured_forwards(&self, session: &Session) {
+        let specs = self.configured_forwards.read().clone();
+        if specs.is_empty() {
+            return;
+        }
+
+        let manager = self.tunnel_manager_for(session);
+        for spec in specs {
+            if let Err(e) = manager.add(spec.clone()) {
+                tracing::warn!("[SSHConn] {}", e);
+                manager.record_failure(spec, e.to_string());
+            }
+        }
+    }
+
+    /// 获取（必要时创建）会话上的隧道管理器
+    fn tunnel_manager_for(&self, session: &Session) -> Arc<TunnelManager> {
+        self.tunnels
+            .write()
+            .get_or_insert_with(|| {
+                let changed = self.tunnel_changed.clone();
+                Arc::new(TunnelManager::new(
+                    Arc::new(SessionTransport::new(session.clone())),
+                    Arc::new(move || changed.notify_one()),
+                ))
+            })
+            .clone()
+    }
+
+    /// 获取已连接会话上的隧道管理器
+    fn tunnel_manager(&self) -> Result<Arc<TunnelManager>, TerminalError> {
+        if !self.is_connected() {
+            return Err(TerminalError::SSHConnectionFailed("连接未建立".to_string()));
+        }
+        if let Some(manager) = self.tunnels.read().as_ref() {
+            return Ok(manager.clone());
+        }
+        let session = self
+            .get_session()
+            .ok_or_else(|| TerminalError::SSHConnectionFailed("未建立 SSH 会话".to_string()))?;
+        Ok(self.tunnel_manager_for(&session))
+    }
+
+    /// 添加端口转发
+    pub fn add_forward(&self, spec: ForwardSpec) -> Result<TunnelStatus, TerminalError> {
+        let status = self.tunnel_manager()?.add(spec)?;
+        self.broadcast_conn_change();
+        Ok(status)
+    }
+
+    /// 移除端口转发
+    pub fn remove_forward(&self, id: &str) -> Result<(), TerminalError> {
+        self.tunnel_manager()?.remove(id)?;
+        self.broadcast_conn_change();
+        Ok(())
+    }
+
+    /// 列出端口转发
+    pub fn list_forwards(&self) -> Vec<TunnelStatus> {
+        self.tunnels
+            .read()
+            .as_ref()
+            .map(|manager| manager.list())
+            .unwrap_or_default()
+    }
+
+    /// 隧道在后台出错时广播连接状态，连接断开后退出
+    ///
+    /// _Requirements: 7.3_
+    pub async fn watch_forward_changes(self: Arc<Self>) {
+        let changed = self.tunnel_changed.clone();
+        loop {
+            changed.notified().await;
+            if !self.is_connected() {
+                break;
+            }
+            self.broadcast_conn_change();
+        }
+    }
+
     /// 完整的连接和认证流程
     ///
     /// 包括连接、主机密钥验证和认证。
@@ -14,7 +14,7 @@
 //! - 4.7: 支持 ProxyJump 配置
 //! - 4.11: 用户调整终端大小时同步调整远程 PTY 大小
 
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
 use std::sync::Arc;
 
@@ -32,6 +32,7 @@ use crate::terminal::events::{
 use crate::terminal::persistence::BlockFile;
 
 use super::ssh_connection::SSHConn;
+use super::ssh_tunnel::retry_would_block;
 
 /// SSH Shell 进程封装
 ///
@@ -93,35 +94,7 @@ impl SSHShellProc {
             rows
         );
 
-        // 创建 SSH Channel
-        let mut channel = session.channel_session().map_err(|e| {
-            TerminalError::SSHConnectionFailed(format!("创建 SSH Channel 失败: {}", e))
-        })?;
-
-        // 请求 PTY
-        // 使用 xterm-256color 终端类型
-        channel
-            .request_pty(
-                "xterm-256color",
-                None,
-                Some((cols as u32, rows as u32, 0, 0)),
-            )
-            .map_err(|e| TerminalError::SSHConnectionFailed(format!("请求远程 PTY 失败: {}", e)))?;
-
-        // 根据控制器类型启动 Shell 或执行命令
-        if controller_type == "cmd" {
-            // 命令执行模式
-            let cmd = Self::build_remote_command(&block_meta)?;
-            tracing::info!("[SSHShellProc] 执行远程命令: {}", cmd);
-            channel.exec(&cmd).map_err(|e| {
-                TerminalError::SSHConnectionFailed(format!("执行远程命令失败: {}", e))
-            })?;
-        } else {
-            // Shell 模式 - 启动交互式 Shell
-            channel.shell().map_err(|e| {
-                TerminalError::SSHConnectionFailed(format!("启动远程 Shell 失败: {}", e))
-            })?;
-        }
+        let channel = Self::open_channel(session, &controller_type, &block_meta, rows, cols)?;
 
         // 设置非阻塞模式
         session.set_blocking(false);
@@ -141,7 +114,7 @@ impl SSHShellProc {
             shutdown_flag.clone(),
             exit_code.clone(),
             exited.clone(),
-            block_file,
+            block_file.clone(),
         );
 
         // 启动输入处理任务
@@ -151,6 +124,7 @@ impl SSHShellProc {
             term_size.clone(),
             input_rx,
             shutdown_flag.clone(),
+            block_file,
         );
 
         tracing::info!("[SSHShellProc] 远程进程已创建: block_id={}", block_id);
@@ -213,6 +187,50 @@ impl SSHShellProc {
         .await
     }
 
+    /// 打开远程 PTY 通道并启动 Shell 或命令
+    ///
+    /// 会话可能已被端口转发或先前的 Shell 切换为非阻塞模式，每个通道操作都重试 `WouldBlock`。
+    fn open_channel(
+        session: &Session,
+        controller_type: &str,
+        block_meta: &BlockMeta,
+        rows: u16,
+        cols: u16,
+    ) -> Result<Channel, TerminalError> {
+        // 创建 SSH Channel
+        let mut channel = retry_would_block(|| session.channel_session()).map_err(|e| {
+            TerminalError::SSHConnectionFailed(format!("创建 SSH Channel 失败: {}", e))
+        })?;
+
+        // 请求 PTY
+        // 使用 xterm-256color 终端类型
+        retry_would_block(|| {
+            channel.request_pty(
+                "xterm-256color",
+                None,
+                Some((cols as u32, rows as u32, 0, 0)),
+            )
+        })
+        .map_err(|e| TerminalError::SSHConnectionFailed(format!("请求远程 PTY 失败: {}", e)))?;
+
+        // 根据控制器类型启动 Shell 或执行命令
+        if controller_type == "cmd" {
+            // 命令执行模式
+            let cmd = Self::build_remote_command(block_meta)?;
+            tracing::info!("[SSHShellProc] 执行远程命令: {}", cmd);
+            retry_would_block(|| channel.exec(&cmd)).map_err(|e| {
+                TerminalError::SSHConnectionFailed(format!("执行远程命令失败: {}", e))
+            })?;
+        } else {
+            // Shell 模式 - 启动交互式 Shell
+            retry_would_block(|| channel.shell()).map_err(|e| {
+                TerminalError::SSHConnectionFailed(format!("启动远程 Shell 失败: {}", e))
+            })?;
+        }
+
+        Ok(channel)
+    }
+
     /// 构建远程命令
     ///
     /// 根据块元数据构建要在远程执行的命令。
@@ -349,6 +367,13 @@ impl SSHShellProc {
                                     e
                                 );
                             }
+                            if let Err(e) = bf.record_output(output_data) {
+                                tracing::warn!(
+                                    "[SSHShellProc] 录制输出失败: block_id={}, error={}",
+                                    block_id,
+                                    e
+                                );
+                            }
                         }
 
                         // 发送输出事件
@@ -406,6 +431,7 @@ impl SSHShellProc {
         term_size: Arc<Mutex<TermSize>>,
         mut input_rx: mpsc::Receiver<BlockInputUnion>,
         shutdown_flag: Arc<AtomicBool>,
+        block_file: Option<Arc<BlockFile>>,
     ) {
         tokio::spawn(async move {
             while let Some(input) = input_rx.recv().await {
@@ -417,7 +443,7 @@ impl SSHShellProc {
                 // 处理输入数据
                 if let Some(data) = &input.input_data {
                     let mut ch = channel.lock();
-                    if let Err(e) = ch.write_all(data) {
+                    if let Err(e) = write_channel(&mut ch, data) {
                         tracing::error!(
                             "[SSHShellProc] 写入失败: block_id={}, error={}",
                             block_id,
@@ -425,12 +451,14 @@ impl SSHShellProc {
                         );
                         continue;
                     }
-                    if let Err(e) = ch.flush() {
-                        tracing::error!(
-                            "[SSHShellProc] Flush 失败: block_id={}, error={}",
-                            block_id,
-                            e
-                        );
+                    if let Some(ref bf) = block_file {
+                        if let Err(e) = bf.record_input(data) {
+                            tracing::warn!(
+                                "[SSHShellProc] 录制输入失败: block_id={}, error={}",
+                                block_id,
+                                e
+                            );
+                        }
                     }
                 }
 
@@ -444,9 +472,9 @@ impl SSHShellProc {
 
                     // 发送 PTY 大小调整请求到远程
                     let mut ch = channel.lock();
-                    if let Err(e) =
+                    if let Err(e) = retry_would_block(|| {
                         ch.request_pty_size(size.cols as u32, size.rows as u32, Some(0), Some(0))
-                    {
+                    }) {
                         tracing::error!(
                             "[SSHShellProc] 调整远程 PTY 大小失败: block_id={}, error={}",
                             block_id,
@@ -460,6 +488,15 @@ impl SSHShellProc {
                             size.rows
                         );
                     }
+                    if let Some(ref bf) = block_file {
+                        if let Err(e) = bf.record_resize(size.cols, size.rows) {
+                            tracing::warn!(
+                                "[SSHShellProc] 录制大小调整失败: block_id={}, error={}",
+                                block_id,
+                                e
+                            );
+                        }
+                    }
                 }
 
                 // 处理信号
@@ -477,7 +514,7 @@ impl SSHShellProc {
                         "SIGINT" => {
                             // 发送 Ctrl+C
                             let mut ch = channel.lock();
-                            if let Err(e) = ch.write_all(&[0x03]) {
+                            if let Err(e) = write_channel(&mut ch, &[0x03]) {
                                 tracing::warn!(
                                     "[SSHShellProc] 发送 Ctrl+C 失败: block_id={}, error={}",
                                     block_id,
@@ -488,7 +525,7 @@ impl SSHShellProc {
                         "SIGQUIT" => {
                             // 发送 Ctrl+\ (0x1C)
                             let mut ch = channel.lock();
-                            if let Err(e) = ch.write_all(&[0x1C]) {
+                            if let Err(e) = write_channel(&mut ch, &[0x1C]) {
                                 tracing::warn!(
                                     "[SSHShellProc] 发送 Ctrl+\\ 失败: block_id={}, error={}",
                                     block_id,
@@ -541,13 +578,7 @@ impl SSHShellProc {
     /// _Requirements: 4.2_
     pub fn write(&self, data: &[u8]) -> Result<(), TerminalError> {
         let mut channel = self.channel.lock();
-        channel
-            .write_all(data)
-            .map_err(|e| TerminalError::WriteFailed(e.to_string()))?;
-        channel
-            .flush()
-            .map_err(|e| TerminalError::WriteFailed(e.to_string()))?;
-        Ok(())
+        write_channel(&mut channel, data).map_err(|e| TerminalError::WriteFailed(e.to_string()))
     }
 
     /// 调整远程 PTY 大小
@@ -562,8 +593,7 @@ impl SSHShellProc {
 
         // 发送到远程
         let mut channel = self.channel.lock();
-        channel
-            .request_pty_size(cols as u32, rows as u32, Some(0), Some(0))
+        retry_would_block(|| channel.request_pty_size(cols as u32, rows as u32, Some(0), Some(0)))
             .map_err(|e| TerminalError::ResizeFailed(e.to_string()))?;
 
         tracing::debug!(
@@ -593,7 +623,7 @@ impl SSHShellProc {
         // 发送 Ctrl+C 尝试中断进程
         {
             let mut channel = self.channel.lock();
-            let _ = channel.write_all(&[0x03]); // Ctrl+C
+            let _ = write_channel(&mut channel, &[0x03]); // Ctrl+C
         }
 
         // 等待一小段时间让进程响应
@@ -656,6 +686,20 @@ impl Drop for SSHShellProc {
     }
 }
 
+/// 向非阻塞通道写入全部数据
+///
+/// 发送窗口已满时 libssh2 返回 `WouldBlock`，`write_all` 会直接失败，这里重试直到写完。
+fn write_channel(channel: &mut Channel, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        let written = retry_would_block(|| channel.write(data))?;
+        if written == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        data = &data[written..];
+    }
+    retry_would_block(|| channel.flush())
+}
+
 /// Shell 转义辅助函数
 ///
 /// 对字符串进行 Shell 转义，防止命令注入。
@@ -697,4 +741,40 @@ mod tests {
         assert_eq!(size.rows, 24);
         assert_eq!(size.cols, 80);
     }
+
+    /// 端口转发启动后（会话已切换为非阻塞模式）仍能打开远程 Shell
+    ///
+    /// 需要可访问的 sshd，通过 `PROXYCAST_TEST_SSH=user@host:port` 指定，使用 ssh-agent 认证。
+    #[test]
+    #[ignore = "需要 sshd，通过 PROXYCAST_TEST_SSH 指定"]
+    fn test_open_shell_after_forward_started() {
+        use super::super::ssh_tunnel::{ForwardKind, ForwardSpec, SessionTransport, TunnelManager};
+        use std::net::{TcpListener, TcpStream};
+
+        let target = std::env::var("PROXYCAST_TEST_SSH").expect("未设置 PROXYCAST_TEST_SSH");
+        let (user, addr) = target.split_once('@').expect("格式应为 user@host:port");
+
+        let mut session = Session::new().unwrap();
+        session.set_tcp_stream(TcpStream::connect(addr).unwrap());
+        session.handshake().unwrap();
+        session.userauth_agent(user).unwrap();
+
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let manager = TunnelManager::new(
+            Arc::new(SessionTransport::new(session.clone())),
+            Arc::new(|| {}),
+        );
+        let spec =
+            ForwardSpec::parse(ForwardKind::Local, &format!("{} localhost:22", port)).unwrap();
+        manager.add(spec).unwrap();
+        assert!(!session.is_blocking());
+
+        let mut channel =
+            SSHShellProc::open_channel(&session, "shell", &BlockMeta::default(), 24, 80).unwrap();
+        write_channel(&mut channel, b"exit\n").unwrap();
+    }
 }
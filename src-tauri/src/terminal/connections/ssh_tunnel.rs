@@ -0,0 +1,1260 @@
+//! SSH 端口转发模块
+//!
+//! 在已认证的 libssh2 会话上实现 ssh_config 中的端口转发。
+//!
+//! ## 功能
+//! - `LocalForward`：本地监听，经 `direct-tcpip` 通道连接远程目标
+//! - `RemoteForward`：远程监听（`tcpip-forward`），接入的连接转发到本地目标
+//! - `DynamicForward`：本地 SOCKS5 代理，按请求的目标地址打开 `direct-tcpip` 通道
+//! - 按连接管理隧道生命周期，支持运行时添加和移除
+//!
+//! ## 实现说明
+//! 转发时会话切换为非阻塞模式：阻塞读会在等待数据期间持有会话锁，
+//! 使同一会话上的其他通道停顿。会话与远程 Shell 共享，因此其他使用者
+//! 必须通过 [`retry_would_block`] 重试 `WouldBlock`，而不能假定会话处于阻塞模式。
+//! 每条隧道由一个线程轮询监听端与全部已建立的连接，
+//! 握手和建立通道等可能耗时的操作放在独立线程中完成。
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use ssh2::{Channel, Listener, Session};
+
+use super::ssh_connection::ConnKeywords;
+use crate::terminal::error::TerminalError;
+
+/// 空闲时的轮询间隔
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// 打开通道或建立远程监听的超时时间
+const OPEN_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 连接转发目标的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// SOCKS5 握手超时时间
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 单次读取的缓冲区大小
+const BUFFER_SIZE: usize = 32 * 1024;
+
+// ============================================================================
+// 转发规则
+// ============================================================================
+
+/// 端口转发类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardKind {
+    /// 本地端口转发（LocalForward / -L）
+    Local,
+    /// 远程端口转发（RemoteForward / -R）
+    Remote,
+    /// 动态端口转发（DynamicForward / -D）
+    Dynamic,
+}
+
+impl ForwardKind {
+    /// 对应的 ssh 命令行选项字母
+    fn flag(&self) -> char {
+        match self {
+            Self::Local => 'L',
+            Self::Remote => 'R',
+            Self::Dynamic => 'D',
+        }
+    }
+}
+
+impl fmt::Display for ForwardKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::Remote => write!(f, "remote"),
+            Self::Dynamic => write!(f, "dynamic"),
+        }
+    }
+}
+
+/// 端口转发规则
+///
+/// 对应 ssh_config 中的一条 `LocalForward`、`RemoteForward` 或 `DynamicForward`。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForwardSpec {
+    /// 转发类型
+    pub kind: ForwardKind,
+    /// 监听地址（None 表示仅本机，`*` 表示所有地址）
+    pub bind_address: Option<String>,
+    /// 监听端口（远程转发为 0 时由服务器分配）
+    pub bind_port: u16,
+    /// 目标主机（动态转发为 None）
+    pub target_host: Option<String>,
+    /// 目标端口（动态转发为 None）
+    pub target_port: Option<u16>,
+}
+
+impl ForwardSpec {
+    /// 解析 ssh_config 格式的转发规则
+    ///
+    /// 支持以下格式：
+    /// - `LocalForward` / `RemoteForward`：`[bind_address:]port host:hostport`
+    /// - `DynamicForward`：`[bind_address:]port`
+    ///
+    /// 地址可以使用 `[::1]:8080` 或 `::1/8080` 表示 IPv6。
+    pub fn parse(kind: ForwardKind, value: &str) -> Result<Self, TerminalError> {
+        let invalid = |reason: &str| {
+            TerminalError::SSHForwardFailed(format!("无效的转发规则 '{}': {}", value, reason))
+        };
+
+        let mut parts = value.split_whitespace();
+        let listen = parts.next().ok_or_else(|| invalid("缺少监听端口"))?;
+        let target = parts.next();
+        if parts.next().is_some() {
+            return Err(invalid("参数过多"));
+        }
+
+        let (bind_address, bind_port) = split_host_port(listen).map_err(|e| invalid(&e))?;
+        let (target_host, target_port) = match (kind, target) {
+            (ForwardKind::Dynamic, None) => (None, None),
+            (ForwardKind::Dynamic, Some(_)) => return Err(invalid("动态转发不需要目标地址")),
+            (_, None) => return Err(invalid("缺少目标地址")),
+            (_, Some(target)) => match split_host_port(target).map_err(|e| invalid(&e))? {
+                (Some(host), port) => (Some(host), Some(port)),
+                (None, _) => return Err(invalid("目标地址缺少主机名")),
+            },
+        };
+
+        if bind_port == 0 && kind != ForwardKind::Remote {
+            return Err(invalid("监听端口不能为 0"));
+        }
+        if target_port == Some(0) {
+            return Err(invalid("目标端口不能为 0"));
+        }
+
+        Ok(Self {
+            kind,
+            bind_address,
+            bind_port,
+            target_host,
+            target_port,
+        })
+    }
+
+    /// 从 SSH 配置关键字中收集转发规则
+    ///
+    /// 无法解析的规则会记录警告并跳过。
+    pub fn from_keywords(keywords: &ConnKeywords) -> Vec<Self> {
+        let groups = [
+            (ForwardKind::Local, &keywords.local_forward),
+            (ForwardKind::Remote, &keywords.remote_forward),
+            (ForwardKind::Dynamic, &keywords.dynamic_forward),
+        ];
+
+        let mut specs = Vec::new();
+        for (kind, values) in groups {
+            for value in values.iter().flatten() {
+                match Self::parse(kind, value) {
+                    Ok(spec) => specs.push(spec),
+                    Err(e) => tracing::warn!("[SSHTunnel] 跳过转发规则: {}", e),
+                }
+            }
+        }
+        specs
+    }
+
+    /// 隧道 ID
+    ///
+    /// 与 OpenSSH 一致，同一类型下以监听地址和端口区分隧道。
+    pub fn id(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.kind.flag(),
+            self.bind_address.as_deref().unwrap_or("localhost"),
+            self.bind_port
+        )
+    }
+
+    /// 本地监听使用的地址
+    fn local_bind_host(&self) -> &str {
+        match self.bind_address.as_deref() {
+            None | Some("localhost") => "127.0.0.1",
+            Some("*") | Some("") => "0.0.0.0",
+            Some(host) => host,
+        }
+    }
+
+    /// 请求服务器监听的地址
+    ///
+    /// 未指定时与 OpenSSH 一致只监听回环地址，`*` 表示所有地址（需服务器开启 GatewayPorts）。
+    fn remote_bind_host(&self) -> &str {
+        match self.bind_address.as_deref() {
+            None => "localhost",
+            Some("*") => "",
+            Some(host) => host,
+        }
+    }
+}
+
+impl fmt::Display for ForwardSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "-{} ", self.kind.flag())?;
+        if let Some(ref bind) = self.bind_address {
+            write!(f, "{}:", format_host(bind))?;
+        }
+        write!(f, "{}", self.bind_port)?;
+        if let (Some(host), Some(port)) = (&self.target_host, self.target_port) {
+            write!(f, ":{}:{}", format_host(host), port)?;
+        }
+        Ok(())
+    }
+}
+
+/// 拆分 `[host:]port`
+fn split_host_port(value: &str) -> Result<(Option<String>, u16), String> {
+    let (host, port) = if let Some(rest) = value.strip_prefix('[') {
+        let (host, rest) = rest.split_once(']').ok_or_else(|| "缺少 ']'".to_string())?;
+        let port = rest
+            .strip_prefix(':')
+            .or_else(|| rest.strip_prefix('/'))
+            .ok_or_else(|| "缺少端口".to_string())?;
+        (Some(host), port)
+    } else if let Some((host, port)) = value.rsplit_once('/') {
+        (Some(host), port)
+    } else if let Some((host, port)) = value.rsplit_once(':') {
+        (Some(host), port)
+    } else {
+        (None, value)
+    };
+
+    let port = port
+        .parse::<u16>()
+        .map_err(|_| format!("无效的端口 '{}'", port))?;
+    Ok((host.map(|h| h.to_string()), port))
+}
+
+/// 格式化主机名（IPv6 地址加方括号）
+fn format_host(host: &str) -> String {
+    if host.contains(':') {
+        format!("[{}]", host)
+    } else {
+        host.to_string()
+    }
+}
+
+// ============================================================================
+// 隧道状态
+// ============================================================================
+
+/// 隧道状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TunnelState {
+    /// 正在监听
+    Active,
+    /// 启动失败或运行中出错
+    Error,
+}
+
+/// 隧道状态详情
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TunnelStatus {
+    /// 隧道 ID
+    pub id: String,
+    /// 转发规则（远程转发的端口为服务器实际分配的端口）
+    pub spec: ForwardSpec,
+    /// 隧道状态
+    pub state: TunnelState,
+    /// 错误信息
+    pub error: Option<String>,
+    /// 当前活跃的连接数
+    pub active_connections: usize,
+}
+
+impl TunnelStatus {
+    fn new(spec: ForwardSpec) -> Self {
+        Self {
+            id: spec.id(),
+            spec,
+            state: TunnelState::Active,
+            error: None,
+            active_connections: 0,
+        }
+    }
+
+    fn failed(spec: ForwardSpec, error: impl Into<String>) -> Self {
+        Self {
+            state: TunnelState::Error,
+            error: Some(error.into()),
+            ..Self::new(spec)
+        }
+    }
+}
+
+// ============================================================================
+// 传输层抽象
+// ============================================================================
+
+/// 隧道一端的数据流
+///
+/// 读写在无数据或缓冲区已满时返回 `WouldBlock`。
+pub trait TunnelStream: Read + Write + Send {
+    /// 通知对端不再发送数据
+    fn send_eof(&mut self) -> io::Result<()>;
+
+    /// 关闭数据流
+    fn close(&mut self);
+}
+
+/// 远程监听器
+pub trait TunnelListener: Send {
+    /// 接受一个转发进来的连接，没有待处理的连接时返回 `WouldBlock`
+    fn accept(&mut self) -> io::Result<Box<dyn TunnelStream>>;
+}
+
+/// SSH 传输层
+///
+/// 抽象出转发所需的两种会话操作，测试中以本地 TCP 替身代替 sshd。
+pub trait TunnelTransport: Send + Sync {
+    /// 打开到目标地址的 `direct-tcpip` 通道
+    fn open_direct(
+        &self,
+        host: &str,
+        port: u16,
+        origin: SocketAddr,
+    ) -> io::Result<Box<dyn TunnelStream>>;
+
+    /// 请求服务器在指定地址监听，返回监听器和实际端口
+    fn listen_remote(&self, host: &str, port: u16) -> io::Result<(Box<dyn TunnelListener>, u16)>;
+}
+
+/// 基于 libssh2 会话的传输层
+pub struct SessionTransport {
+    session: Session,
+}
+
+impl SessionTransport {
+    /// 创建传输层，并将会话切换为非阻塞模式
+    pub fn new(session: Session) -> Self {
+        session.set_blocking(false);
+        Self { session }
+    }
+}
+
+impl TunnelTransport for SessionTransport {
+    fn open_direct(
+        &self,
+        host: &str,
+        port: u16,
+        origin: SocketAddr,
+    ) -> io::Result<Box<dyn TunnelStream>> {
+        let origin_ip = origin.ip().to_string();
+        let channel = retry_would_block(|| {
+            self.session
+                .channel_direct_tcpip(host, port, Some((&origin_ip, origin.port())))
+        })?;
+        Ok(Box::new(channel))
+    }
+
+    fn listen_remote(&self, host: &str, port: u16) -> io::Result<(Box<dyn TunnelListener>, u16)> {
+        let (listener, bound_port) =
+            retry_would_block(|| self.session.channel_forward_listen(port, Some(host), None))?;
+        Ok((Box::new(listener), bound_port))
+    }
+}
+
+impl TunnelStream for Channel {
+    fn send_eof(&mut self) -> io::Result<()> {
+        Channel::send_eof(self).map_err(io::Error::from)
+    }
+
+    fn close(&mut self) {
+        let _ = Channel::close(self);
+    }
+}
+
+impl TunnelListener for Listener {
+    fn accept(&mut self) -> io::Result<Box<dyn TunnelStream>> {
+        let channel = Listener::accept(self).map_err(io::Error::from)?;
+        Ok(Box::new(channel))
+    }
+}
+
+/// 在非阻塞会话上重试操作，直到完成或超时
+pub(crate) fn retry_would_block<T, E: Into<io::Error>>(
+    mut op: impl FnMut() -> Result<T, E>,
+) -> io::Result<T> {
+    let deadline = Instant::now() + OPEN_TIMEOUT;
+    loop {
+        match op().map_err(Into::into) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "等待 SSH 服务器响应超时",
+                    ));
+                }
+                std::thread::sleep(IDLE_POLL_INTERVAL);
+            }
+            result => return result,
+        }
+    }
+}
+
+// ============================================================================
+// 隧道管理器
+// ============================================================================
+
+/// 隧道变更通知回调
+pub type TunnelNotifier = Arc<dyn Fn() + Send + Sync>;
+
+/// 单条运行中的隧道
+struct Tunnel {
+    status: Arc<Mutex<TunnelStatus>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Tunnel {
+    fn stop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// 隧道管理器
+///
+/// 管理单个 SSH 连接上的全部端口转发。
+pub struct TunnelManager {
+    transport: Arc<dyn TunnelTransport>,
+    tunnels: Mutex<Vec<Tunnel>>,
+    /// 隧道在后台出错时调用
+    notify: TunnelNotifier,
+}
+
+impl TunnelManager {
+    /// 创建隧道管理器
+    pub fn new(transport: Arc<dyn TunnelTransport>, notify: TunnelNotifier) -> Self {
+        Self {
+            transport,
+            tunnels: Mutex::new(Vec::new()),
+            notify,
+        }
+    }
+
+    /// 添加并启动隧道
+    ///
+    /// 监听失败时返回错误；同 ID 的失败隧道会被替换。
+    pub fn add(&self, spec: ForwardSpec) -> Result<TunnelStatus, TerminalError> {
+        let id = spec.id();
+        {
+            let mut tunnels = self.tunnels.lock();
+            if let Some(pos) = tunnels.iter().position(|t| t.status.lock().id == id) {
+                if tunnels[pos].status.lock().state == TunnelState::Active {
+                    return Err(TerminalError::SSHForwardFailed(format!(
+                        "隧道已存在: {}",
+                        id
+                    )));
+                }
+                tunnels.remove(pos);
+            }
+        }
+
+        let (acceptor, spec) = self.bind(spec)?;
+        let status = Arc::new(Mutex::new(TunnelStatus::new(spec.clone())));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let worker = TunnelWorker {
+            spec: spec.clone(),
+            transport: self.transport.clone(),
+            status: status.clone(),
+            shutdown: shutdown.clone(),
+            notify: self.notify.clone(),
+        };
+        let handle = std::thread::Builder::new()
+            .name(format!("ssh-tunnel-{}", id))
+            .spawn(move || worker.run(acceptor))
+            .map_err(|e| TerminalError::SSHForwardFailed(format!("启动隧道线程失败: {}", e)))?;
+
+        tracing::info!("[SSHTunnel] 隧道已启动: {}", spec);
+        let snapshot = status.lock().clone();
+        self.tunnels.lock().push(Tunnel {
+            status,
+            shutdown,
+            handle: Some(handle),
+        });
+        Ok(snapshot)
+    }
+
+    /// 记录启动失败的隧道，便于在状态中展示
+    pub fn record_failure(&self, spec: ForwardSpec, error: impl Into<String>) {
+        let status = TunnelStatus::failed(spec, error);
+        let mut tunnels = self.tunnels.lock();
+        tunnels.retain(|t| t.status.lock().id != status.id);
+        tunnels.push(Tunnel {
+            status: Arc::new(Mutex::new(status)),
+            shutdown: Arc::new(AtomicBool::new(true)),
+            handle: None,
+        });
+    }
+
+    /// 停止并移除隧道
+    pub fn remove(&self, id: &str) -> Result<(), TerminalError> {
+        let mut tunnel = {
+            let mut tunnels = self.tunnels.lock();
+            let pos = tunnels
+                .iter()
+                .position(|t| t.status.lock().id == id)
+                .ok_or_else(|| TerminalError::SSHForwardFailed(format!("隧道不存在: {}", id)))?;
+            tunnels.remove(pos)
+        };
+
+        tunnel.stop();
+        tracing::info!("[SSHTunnel] 隧道已移除: {}", id);
+        Ok(())
+    }
+
+    /// 列出所有隧道
+    pub fn list(&self) -> Vec<TunnelStatus> {
+        self.tunnels
+            .lock()
+            .iter()
+            .map(|t| t.status.lock().clone())
+            .collect()
+    }
+
+    /// 停止所有隧道
+    pub fn close_all(&self) {
+        let tunnels: Vec<Tunnel> = self.tunnels.lock().drain(..).collect();
+        for mut tunnel in tunnels {
+            tunnel.stop();
+        }
+    }
+
+    /// 建立监听
+    fn bind(&self, mut spec: ForwardSpec) -> Result<(Acceptor, ForwardSpec), TerminalError> {
+        let label = spec.to_string();
+        let failed =
+            |e: io::Error| TerminalError::SSHForwardFailed(format!("{} 监听失败: {}", label, e));
+
+        match spec.kind {
+            ForwardKind::Local | ForwardKind::Dynamic => {
+                let listener =
+                    TcpListener::bind((spec.local_bind_host(), spec.bind_port)).map_err(failed)?;
+                listener.set_nonblocking(true).map_err(failed)?;
+                Ok((Acceptor::Local(listener), spec))
+            }
+            ForwardKind::Remote => {
+                let (listener, bound_port) = self
+                    .transport
+                    .listen_remote(spec.remote_bind_host(), spec.bind_port)
+                    .map_err(failed)?;
+                spec.bind_port = bound_port;
+                Ok((Acceptor::Remote(listener), spec))
+            }
+        }
+    }
+}
+
+impl Drop for TunnelManager {
+    fn drop(&mut self) {
+        self.close_all();
+    }
+}
+
+// ============================================================================
+// 隧道工作线程
+// ============================================================================
+
+/// 隧道的监听端
+enum Acceptor {
+    Local(TcpListener),
+    Remote(Box<dyn TunnelListener>),
+}
+
+/// 隧道工作线程上下文
+struct TunnelWorker {
+    spec: ForwardSpec,
+    transport: Arc<dyn TunnelTransport>,
+    status: Arc<Mutex<TunnelStatus>>,
+    shutdown: Arc<AtomicBool>,
+    notify: TunnelNotifier,
+}
+
+impl TunnelWorker {
+    fn run(self, mut acceptor: Acceptor) {
+        let (ready_tx, ready_rx) = mpsc::channel::<Pipe>();
+        let mut pipes: Vec<Pipe> = Vec::new();
+
+        while !self.shutdown.load(Ordering::SeqCst) {
+            let mut progress = false;
+
+            match self.accept(&mut acceptor, &ready_tx) {
+                Ok(accepted) => progress |= accepted,
+                Err(e) => {
+                    tracing::warn!("[SSHTunnel] 隧道 {} 已停止: {}", self.spec, e);
+                    {
+                        let mut status = self.status.lock();
+                        status.state = TunnelState::Error;
+                        status.error = Some(e.to_string());
+                        status.active_connections = 0;
+                    }
+                    (self.notify)();
+                    return;
+                }
+            }
+
+            while let Ok(pipe) = ready_rx.try_recv() {
+                pipes.push(pipe);
+                progress = true;
+            }
+
+            pipes.retain_mut(|pipe| match pipe.pump() {
+                Ok(moved) => {
+                    progress |= moved;
+                    !pipe.finished()
+                }
+                Err(e) => {
+                    tracing::debug!("[SSHTunnel] 连接中断: {}", e);
+                    false
+                }
+            });
+            self.status.lock().active_connections = pipes.len();
+
+            if !progress {
+                std::thread::sleep(IDLE_POLL_INTERVAL);
+            }
+        }
+    }
+
+    /// 接受新连接并交给独立线程完成建立
+    ///
+    /// 返回是否接受了连接；远程监听器失效时返回错误。
+    fn accept(&self, acceptor: &mut Acceptor, ready: &mpsc::Sender<Pipe>) -> io::Result<bool> {
+        match acceptor {
+            Acceptor::Local(listener) => match TcpListener::accept(listener) {
+                Ok((stream, peer)) => {
+                    let spec = self.spec.clone();
+                    let transport = self.transport.clone();
+                    let ready = ready.clone();
+                    std::thread::spawn(move || {
+                        match open_local(stream, peer, &spec, transport.as_ref()) {
+                            Ok(pipe) => {
+                                let _ = ready.send(pipe);
+                            }
+                            Err(e) => tracing::warn!("[SSHTunnel] {} 转发失败: {}", spec, e),
+                        }
+                    });
+                    Ok(true)
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+                Err(e) => {
+                    tracing::warn!("[SSHTunnel] 接受本地连接失败: {}", e);
+                    Ok(false)
+                }
+            },
+            Acceptor::Remote(listener) => match listener.accept() {
+                Ok(channel) => {
+                    let spec = self.spec.clone();
+                    let ready = ready.clone();
+                    std::thread::spawn(move || match open_remote(channel, &spec) {
+                        Ok(pipe) => {
+                            let _ = ready.send(pipe);
+                        }
+                        Err(e) => tracing::warn!("[SSHTunnel] {} 转发失败: {}", spec, e),
+                    });
+                    Ok(true)
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+                Err(e) => Err(e),
+            },
+        }
+    }
+}
+
+/// 为本地接入的连接打开 SSH 通道（本地转发与动态转发）
+fn open_local(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    spec: &ForwardSpec,
+    transport: &dyn TunnelTransport,
+) -> io::Result<Pipe> {
+    // 部分平台上接受的连接会继承监听器的非阻塞模式
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+
+    let channel = match spec.kind {
+        ForwardKind::Dynamic => {
+            let (host, port) = socks5::handshake(&mut stream)?;
+            let channel = transport.open_direct(&host, port, peer);
+            let reply = if channel.is_ok() {
+                socks5::REPLY_SUCCEEDED
+            } else {
+                socks5::REPLY_CONNECTION_REFUSED
+            };
+            socks5::reply(&mut stream, reply)?;
+            channel?
+        }
+        _ => {
+            let (host, port) = spec
+                .target_host
+                .as_deref()
+                .zip(spec.target_port)
+                .ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "转发规则缺少目标地址")
+                })?;
+            transport.open_direct(host, port, peer)?
+        }
+    };
+
+    stream.set_read_timeout(None)?;
+    Pipe::new(stream, channel)
+}
+
+/// 为远程转发进来的连接连接本地目标
+fn open_remote(mut channel: Box<dyn TunnelStream>, spec: &ForwardSpec) -> io::Result<Pipe> {
+    let (host, port) = spec
+        .target_host
+        .as_deref()
+        .zip(spec.target_port)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "转发规则缺少目标地址"))?;
+
+    match connect_any(host, port) {
+        Ok(stream) => Pipe::new(stream, channel),
+        Err(e) => {
+            channel.close();
+            Err(e)
+        }
+    }
+}
+
+/// 依次尝试目标主机解析出的地址
+fn connect_any(host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, format!("无法解析 {}", host));
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+// ============================================================================
+// 数据转发
+// ============================================================================
+
+/// 单向数据缓冲
+struct Direction {
+    buffer: Box<[u8]>,
+    start: usize,
+    end: usize,
+    /// 读端已结束
+    eof: bool,
+    /// 已向写端发送结束
+    closed: bool,
+}
+
+impl Default for Direction {
+    fn default() -> Self {
+        Self {
+            buffer: vec![0u8; BUFFER_SIZE].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            eof: false,
+            closed: false,
+        }
+    }
+}
+
+impl Direction {
+    /// 从 `src` 读取并写入 `dst`，返回是否有数据移动
+    ///
+    /// 写端返回 `WouldBlock` 时保留未写完的数据，下次以相同内容重试。
+    fn transfer(&mut self, src: &mut dyn Read, dst: &mut dyn Write) -> io::Result<bool> {
+        let mut progress = false;
+
+        if self.start == self.end && !self.eof {
+            self.start = 0;
+            self.end = 0;
+            match src.read(&mut self.buffer) {
+                Ok(0) => {
+                    self.eof = true;
+                    progress = true;
+                }
+                Ok(n) => {
+                    self.end = n;
+                    progress = true;
+                }
+                Err(e) if is_retryable(&e) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        while self.start < self.end {
+            match dst.write(&self.buffer[self.start..self.end]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.start += n;
+                    progress = true;
+                }
+                Err(e) if is_retryable(&e) => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(progress)
+    }
+
+    /// 读端已结束且缓冲区已写完
+    fn drained(&self) -> bool {
+        self.eof && self.start == self.end
+    }
+}
+
+fn is_retryable(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+    )
+}
+
+/// 一条已建立的转发连接
+struct Pipe {
+    local: TcpStream,
+    remote: Box<dyn TunnelStream>,
+    /// 本地 → 远程
+    upstream: Direction,
+    /// 远程 → 本地
+    downstream: Direction,
+}
+
+impl Pipe {
+    fn new(local: TcpStream, remote: Box<dyn TunnelStream>) -> io::Result<Self> {
+        local.set_nonblocking(true)?;
+        let _ = local.set_nodelay(true);
+        Ok(Self {
+            local,
+            remote,
+            upstream: Direction::default(),
+            downstream: Direction::default(),
+        })
+    }
+
+    /// 双向搬运数据，一侧结束后向另一侧传递半关闭
+    fn pump(&mut self) -> io::Result<bool> {
+        let mut progress = self
+            .upstream
+            .transfer(&mut self.local, self.remote.as_mut())?;
+        if self.upstream.drained() && !self.upstream.closed {
+            match self.remote.send_eof() {
+                Ok(()) => self.upstream.closed = true,
+                Err(e) if is_retryable(&e) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        progress |= self
+            .downstream
+            .transfer(self.remote.as_mut(), &mut self.local)?;
+        if self.downstream.drained() && !self.downstream.closed {
+            let _ = self.local.shutdown(Shutdown::Write);
+            self.downstream.closed = true;
+        }
+
+        Ok(progress)
+    }
+
+    fn finished(&self) -> bool {
+        self.upstream.closed && self.downstream.closed
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        self.remote.close();
+        let _ = self.local.shutdown(Shutdown::Both);
+    }
+}
+
+// ============================================================================
+// SOCKS5
+// ============================================================================
+
+/// SOCKS5 服务端握手（RFC 1928，仅支持无认证的 CONNECT）
+mod socks5 {
+    use std::io::{self, Read, Write};
+
+    const VERSION: u8 = 0x05;
+    const METHOD_NO_AUTH: u8 = 0x00;
+    const METHOD_NOT_ACCEPTABLE: u8 = 0xff;
+    const CMD_CONNECT: u8 = 0x01;
+    const ATYP_IPV4: u8 = 0x01;
+    const ATYP_DOMAIN: u8 = 0x03;
+    const ATYP_IPV6: u8 = 0x04;
+
+    pub const REPLY_SUCCEEDED: u8 = 0x00;
+    pub const REPLY_CONNECTION_REFUSED: u8 = 0x05;
+    const REPLY_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+    const REPLY_ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+    fn invalid(message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+    }
+
+    /// 完成方法协商并读取 CONNECT 请求，返回目标地址
+    pub fn handshake<S: Read + Write>(stream: &mut S) -> io::Result<(String, u16)> {
+        let mut header = [0u8; 2];
+        stream.read_exact(&mut header)?;
+        if header[0] != VERSION {
+            return Err(invalid("不支持的 SOCKS 版本"));
+        }
+        let mut methods = vec![0u8; header[1] as usize];
+        stream.read_exact(&mut methods)?;
+        if !methods.contains(&METHOD_NO_AUTH) {
+            stream.write_all(&[VERSION, METHOD_NOT_ACCEPTABLE])?;
+            return Err(invalid("客户端不支持无认证方式"));
+        }
+        stream.write_all(&[VERSION, METHOD_NO_AUTH])?;
+
+        let mut request = [0u8; 4];
+        stream.read_exact(&mut request)?;
+        if request[0] != VERSION {
+            return Err(invalid("不支持的 SOCKS 版本"));
+        }
+        if request[1] != CMD_CONNECT {
+            reply(stream, REPLY_COMMAND_NOT_SUPPORTED)?;
+            return Err(invalid("仅支持 CONNECT 命令"));
+        }
+
+        let host = match request[3] {
+            ATYP_IPV4 => {
+                let mut addr = [0u8; 4];
+                stream.read_exact(&mut addr)?;
+                std::net::Ipv4Addr::from(addr).to_string()
+            }
+            ATYP_IPV6 => {
+                let mut addr = [0u8; 16];
+                stream.read_exact(&mut addr)?;
+                std::net::Ipv6Addr::from(addr).to_string()
+            }
+            ATYP_DOMAIN => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len)?;
+                let mut name = vec![0u8; len[0] as usize];
+                stream.read_exact(&mut name)?;
+                String::from_utf8(name).map_err(|_| invalid("无效的域名"))?
+            }
+            _ => {
+                reply(stream, REPLY_ADDRESS_NOT_SUPPORTED)?;
+                return Err(invalid("不支持的地址类型"));
+            }
+        };
+
+        let mut port = [0u8; 2];
+        stream.read_exact(&mut port)?;
+        Ok((host, u16::from_be_bytes(port)))
+    }
+
+    /// 发送应答（绑定地址固定为 0.0.0.0:0）
+    pub fn reply<S: Write>(stream: &mut S, code: u8) -> io::Result<()> {
+        stream.write_all(&[VERSION, code, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::connections::ssh_connection::SSHConfigParser;
+
+    /// 充当 sshd 的本地替身
+    ///
+    /// `direct-tcpip` 直接连接目标，`tcpip-forward` 在本机监听，
+    /// 转发逻辑与真实会话走同一套代码。
+    struct LoopbackTransport;
+
+    impl TunnelStream for TcpStream {
+        fn send_eof(&mut self) -> io::Result<()> {
+            self.shutdown(Shutdown::Write)
+        }
+
+        fn close(&mut self) {
+            let _ = self.shutdown(Shutdown::Both);
+        }
+    }
+
+    impl TunnelListener for TcpListener {
+        fn accept(&mut self) -> io::Result<Box<dyn TunnelStream>> {
+            let (stream, _) = TcpListener::accept(self)?;
+            stream.set_nonblocking(true)?;
+            Ok(Box::new(stream))
+        }
+    }
+
+    impl TunnelTransport for LoopbackTransport {
+        fn open_direct(
+            &self,
+            host: &str,
+            port: u16,
+            _origin: SocketAddr,
+        ) -> io::Result<Box<dyn TunnelStream>> {
+            let stream = connect_any(host, port)?;
+            stream.set_nonblocking(true)?;
+            Ok(Box::new(stream))
+        }
+
+        fn listen_remote(
+            &self,
+            host: &str,
+            port: u16,
+        ) -> io::Result<(Box<dyn TunnelListener>, u16)> {
+            let host = if host == "localhost" {
+                "127.0.0.1"
+            } else {
+                host
+            };
+            let listener = TcpListener::bind((host, port))?;
+            listener.set_nonblocking(true)?;
+            let port = listener.local_addr()?.port();
+            Ok((Box::new(listener), port))
+        }
+    }
+
+    fn manager() -> TunnelManager {
+        TunnelManager::new(Arc::new(LoopbackTransport), Arc::new(|| {}))
+    }
+
+    /// 启动回显服务，返回端口
+    fn echo_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                std::thread::spawn(move || {
+                    let mut reader = stream.try_clone().unwrap();
+                    let mut writer = stream;
+                    let _ = io::copy(&mut reader, &mut writer);
+                });
+            }
+        });
+        port
+    }
+
+    fn free_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn assert_echo(stream: &mut TcpStream, message: &[u8]) {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream.write_all(message).unwrap();
+        let mut buf = vec![0u8; message.len()];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, message);
+    }
+
+    #[test]
+    fn test_retry_would_block() {
+        let mut attempts = 0;
+        let result = retry_would_block(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(io::Error::from(io::ErrorKind::WouldBlock))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let err = retry_would_block(|| Err::<(), _>(io::Error::from(io::ErrorKind::BrokenPipe)));
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_parse_forward_specs() {
+        let spec = ForwardSpec::parse(ForwardKind::Local, "8080 localhost:80").unwrap();
+        assert_eq!(spec.bind_address, None);
+        assert_eq!(spec.bind_port, 8080);
+        assert_eq!(spec.target_host.as_deref(), Some("localhost"));
+        assert_eq!(spec.target_port, Some(80));
+        assert_eq!(spec.id(), "L:localhost:8080");
+        assert_eq!(spec.to_string(), "-L 8080:localhost:80");
+
+        let spec = ForwardSpec::parse(ForwardKind::Remote, "0.0.0.0:9000 [::1]:9000").unwrap();
+        assert_eq!(spec.bind_address.as_deref(), Some("0.0.0.0"));
+        assert_eq!(spec.target_host.as_deref(), Some("::1"));
+        assert_eq!(spec.to_string(), "-R 0.0.0.0:9000:[::1]:9000");
+
+        let spec = ForwardSpec::parse(ForwardKind::Dynamic, "[::1]:1080").unwrap();
+        assert_eq!(spec.bind_address.as_deref(), Some("::1"));
+        assert_eq!(spec.bind_port, 1080);
+        assert_eq!(spec.target_host, None);
+
+        let spec = ForwardSpec::parse(ForwardKind::Local, "*/8443 db.internal/5432").unwrap();
+        assert_eq!(spec.local_bind_host(), "0.0.0.0");
+        assert_eq!(spec.target_port, Some(5432));
+
+        assert!(ForwardSpec::parse(ForwardKind::Local, "8080").is_err());
+        assert!(ForwardSpec::parse(ForwardKind::Local, "abc localhost:80").is_err());
+        assert!(ForwardSpec::parse(ForwardKind::Local, "8080 80").is_err());
+        assert!(ForwardSpec::parse(ForwardKind::Dynamic, "1080 localhost:80").is_err());
+        assert!(ForwardSpec::parse(ForwardKind::Dynamic, "0").is_err());
+    }
+
+    #[test]
+    fn test_forward_specs_from_keywords() {
+        let keywords = SSHConfigParser::get_host_config_from_content(
+            r#"
+Host tunnel
+    LocalForward 8080 localhost:80
+    RemoteForward 9000 localhost:9000
+    DynamicForward 1080
+    LocalForward bogus
+"#,
+            "tunnel",
+        )
+        .unwrap();
+
+        let specs = ForwardSpec::from_keywords(&keywords);
+        let kinds: Vec<_> = specs.iter().map(|s| s.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ForwardKind::Local,
+                ForwardKind::Remote,
+                ForwardKind::Dynamic
+            ]
+        );
+    }
+
+    #[test]
+    fn test_local_forward() {
+        let target = echo_server();
+        let port = free_port();
+        let manager = manager();
+        let spec = ForwardSpec::parse(
+            ForwardKind::Local,
+            &format!("{} 127.0.0.1:{}", port, target),
+        )
+        .unwrap();
+        let status = manager.add(spec).unwrap();
+        assert_eq!(status.state, TunnelState::Active);
+
+        let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        assert_echo(&mut client, b"hello through -L");
+        let large = vec![b'x'; BUFFER_SIZE * 4 + 17];
+        assert_echo(&mut client, &large);
+
+        // 同一监听地址不能重复添加
+        assert!(manager
+            .add(ForwardSpec::parse(ForwardKind::Local, &format!("{} localhost:1", port)).unwrap())
+            .is_err());
+
+        manager.remove(&status.id).unwrap();
+        assert!(manager.list().is_empty());
+        assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+    }
+
+    #[test]
+    fn test_remote_forward() {
+        let target = echo_server();
+        let manager = manager();
+        let spec =
+            ForwardSpec::parse(ForwardKind::Remote, &format!("0 127.0.0.1:{}", target)).unwrap();
+        let status = manager.add(spec).unwrap();
+        // 端口 0 由"服务器"分配，状态中记录实际端口
+        assert_ne!(status.spec.bind_port, 0);
+
+        let mut client = TcpStream::connect(("127.0.0.1", status.spec.bind_port)).unwrap();
+        assert_echo(&mut client, b"hello through -R");
+    }
+
+    #[test]
+    fn test_dynamic_forward() {
+        let target = echo_server();
+        let port = free_port();
+        let manager = manager();
+        manager
+            .add(ForwardSpec::parse(ForwardKind::Dynamic, &port.to_string()).unwrap())
+            .unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client.write_all(&[0x05, 0x01, 0x00]).unwrap();
+        let mut method = [0u8; 2];
+        client.read_exact(&mut method).unwrap();
+        assert_eq!(method, [0x05, 0x00]);
+
+        let mut request = vec![0x05, 0x01, 0x00, 0x03, 9];
+        request.extend_from_slice(b"localhost");
+        request.extend_from_slice(&target.to_be_bytes());
+        client.write_all(&request).unwrap();
+        let mut reply = [0u8; 10];
+        client.read_exact(&mut reply).unwrap();
+        assert_eq!(reply[1], socks5::REPLY_SUCCEEDED);
+
+        assert_echo(&mut client, b"hello through -D");
+        assert_eq!(manager.list()[0].active_connections, 1);
+    }
+
+    #[test]
+    fn test_half_close_propagates() {
+        // 目标读到 EOF 后才回复，验证本地半关闭会传递到远端
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            stream.read_to_end(&mut request).unwrap();
+            stream.write_all(&request).unwrap();
+        });
+
+        let port = free_port();
+        let manager = manager();
+        manager
+            .add(
+                ForwardSpec::parse(
+                    ForwardKind::Local,
+                    &format!("{} 127.0.0.1:{}", port, target),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let mut client = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        client.write_all(b"request").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).unwrap();
+        assert_eq!(response, b"request");
+    }
+
+    #[test]
+    fn test_record_failure() {
+        let manager = manager();
+        let spec = ForwardSpec::parse(ForwardKind::Local, "8080 localhost:80").unwrap();
+        manager.record_failure(spec, "Address already in use");
+
+        let tunnels = manager.list();
+        assert_eq!(tunnels.len(), 1);
+        assert_eq!(tunnels[0].state, TunnelState::Error);
+        assert_eq!(tunnels[0].error.as_deref(), Some("Address already in use"));
+    }
+}
@@ -406,6 +406,7 @@ impl WSLConn {
             wsh_error: self.wsh_error.read().clone(),
             no_wsh_reason: self.no_wsh_reason.read().clone(),
             wsh_version: self.wsh_version.read().clone(),
+            tunnels: Vec::new(),
         }
     }
 
@@ -695,7 +696,7 @@ impl WSLShellProc {
             shutdown_flag.clone(),
             exit_code.clone(),
             exited.clone(),
-            block_file,
+            block_file.clone(),
         );
 
         // 启动输入处理任务
@@ -705,6 +706,7 @@ impl WSLShellProc {
             master.clone(),
             input_rx,
             shutdown_flag.clone(),
+            block_file,
         );
 
         tracing::info!("[WSLShellProc] WSL 进程已创建: block_id={}", block_id);
@@ -849,6 +851,13 @@ impl WSLShellProc {
                                     e
                                 );
                             }
+                            if let Err(e) = bf.record_output(output_data) {
+                                tracing::warn!(
+                                    "[WSLShellProc] 录制输出失败: block_id={}, error={}",
+                                    block_id,
+                                    e
+                                );
+                            }
                         }
 
                         let data = BASE64.encode(output_data);
@@ -896,6 +905,7 @@ impl WSLShellProc {
         master: Arc<parking_lot::Mutex<Box<dyn portable_pty::MasterPty + Send>>>,
         mut input_rx: mpsc::Receiver<BlockInputUnion>,
         shutdown_flag: Arc<AtomicBool>,
+        block_file: Option<Arc<BlockFile>>,
     ) {
         use portable_pty::PtySize;
 
@@ -923,6 +933,15 @@ impl WSLShellProc {
                             e
                         );
                     }
+                    if let Some(ref bf) = block_file {
+                        if let Err(e) = bf.record_input(data) {
+                            tracing::warn!(
+                                "[WSLShellProc] 录制输入失败: block_id={}, error={}",
+                                block_id,
+                                e
+                            );
+                        }
+                    }
                 }
 
                 // 处理终端大小调整
@@ -947,6 +966,15 @@ impl WSLShellProc {
                             size.rows
                         );
                     }
+                    if let Some(ref bf) = block_file {
+                        if let Err(e) = bf.record_resize(size.cols, size.rows) {
+                            tracing::warn!(
+                                "[WSLShellProc] 录制大小调整失败: block_id={}, error={}",
+                                block_id,
+                                e
+                            );
+                        }
+                    }
                 }
 
                 // 处理信号
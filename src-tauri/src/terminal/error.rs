@@ -42,6 +42,10 @@ pub enum TerminalError {
     #[error("块文件错误: {0}")]
     BlockFileError(String),
 
+    /// 录制错误
+    #[error("录制错误: {0}")]
+    RecordingError(String),
+
     /// 数据库错误
     #[error("数据库错误: {0}")]
     DatabaseError(String),
@@ -58,6 +62,10 @@ pub enum TerminalError {
     #[error("SSH 认证失败: {0}")]
     SSHAuthFailed(String),
 
+    /// SSH 端口转发失败
+    #[error("SSH 端口转发失败: {0}")]
+    SSHForwardFailed(String),
+
     /// WSL 连接失败
     #[error("WSL 连接失败: {0}")]
     WSLConnectionFailed(String),
@@ -53,10 +53,12 @@ pub enum OSCSequence {
     },
 
     /// OSC 133 - 命令提示符标记（Shell Integration）
-    /// 格式: OSC 133 ; type ST
+    /// 格式: OSC 133 ; type [; exit_code] ST
     PromptMark {
         /// 标记类型
         mark_type: PromptMarkType,
+        /// 退出码（仅 D 标记携带）
+        exit_code: Option<i32>,
     },
 
     /// OSC 16162 - Wave 特定命令
@@ -314,14 +316,22 @@ impl OSCParser {
 
     /// 解析 OSC 133 - 命令提示符标记
     ///
-    /// 格式: type (A/B/C/D)
+    /// 格式: type (A/B/C/D)，D 可附带退出码 `D;exit_code`
     ///
     /// _Requirements: 6.3_
     fn parse_osc_133(params: &str) -> Option<OSCSequence> {
-        let mark_char = params.chars().next()?;
+        let mut parts = params.split(';');
+        let mark_char = parts.next()?.chars().next()?;
         let mark_type = PromptMarkType::from_char(mark_char);
+        let exit_code = match mark_type {
+            PromptMarkType::CommandFinished => parts.next().and_then(|c| c.trim().parse().ok()),
+            _ => None,
+        };
 
-        Some(OSCSequence::PromptMark { mark_type })
+        Some(OSCSequence::PromptMark {
+            mark_type,
+            exit_code,
+        })
     }
 
     /// 解析 OSC 16162 - Wave 命令
@@ -533,7 +543,7 @@ mod tests {
             let results = OSCParser::parse(data);
             assert_eq!(results.len(), 1);
             match &results[0].sequence {
-                OSCSequence::PromptMark { mark_type } => {
+                OSCSequence::PromptMark { mark_type, .. } => {
                     assert_eq!(*mark_type, expected_type);
                 }
                 _ => panic!("Expected PromptMark"),
@@ -541,6 +551,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_osc_133_exit_code() {
+        let results = OSCParser::parse(b"\x1b]133;D;127\x1b\\");
+        assert_eq!(results.len(), 1);
+        match &results[0].sequence {
+            OSCSequence::PromptMark {
+                mark_type,
+                exit_code,
+            } => {
+                assert_eq!(*mark_type, PromptMarkType::CommandFinished);
+                assert_eq!(*exit_code, Some(127));
+            }
+            _ => panic!("Expected PromptMark"),
+        }
+    }
+
     #[test]
     fn test_parse_osc_16162() {
         let data = b"\x1b]16162;setcwd /home/user\x07";
@@ -652,7 +678,7 @@ mod tests {
 
         assert_eq!(results.len(), 1);
         match &results[0].sequence {
-            OSCSequence::PromptMark { mark_type } => {
+            OSCSequence::PromptMark { mark_type, .. } => {
                 assert_eq!(*mark_type, PromptMarkType::PromptStart);
             }
             _ => panic!("Expected PromptMark"),
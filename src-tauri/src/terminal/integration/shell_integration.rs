@@ -4,13 +4,14 @@
 //! - 当前工作目录跟踪
 //! - 命令执行状态管理
 //! - 命令时间记录
+//! - 命令行、退出码和输出片段捕获
 //! - OSC 序列处理
 //!
 //! ## 功能
 //! - 处理 OSC 7 更新当前目录
 //! - 处理 OSC 52 剪贴板操作
 //! - 处理 OSC 133 命令提示符标记
-//! - 处理 OSC 16162 Wave 命令
+//! - 处理 OSC 16162 Wave 命令（`setcwd`、`setshell`、`setcmd`）
 //!
 //! ## Requirements
 //! - 6.5: 支持 bash、zsh、fish、pwsh 四种 Shell 类型
@@ -28,6 +29,9 @@ use super::osc_parser::{OSCParser, OSCSequence, PromptMarkType};
 use crate::terminal::error::TerminalError;
 use crate::terminal::events::event_names;
 
+/// 单条命令保留的最大输出字节数（超出时保留末尾）
+pub const MAX_COMMAND_OUTPUT_BYTES: usize = 8 * 1024;
+
 /// Shell 类型
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -106,6 +110,15 @@ pub struct CommandInfo {
     pub end_time: Option<i64>,
     /// 命令持续时间（毫秒）
     pub duration_ms: Option<i64>,
+    /// 命令行（由集成脚本通过 OSC 16162 `setcmd` 上报）
+    #[serde(default)]
+    pub command: Option<String>,
+    /// 命令执行时的工作目录
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// 退出码（由 OSC 133 D 标记携带）
+    #[serde(default)]
+    pub exit_code: Option<i32>,
 }
 
 impl CommandInfo {
@@ -115,6 +128,9 @@ impl CommandInfo {
             start_time: current_timestamp_ms(),
             end_time: None,
             duration_ms: None,
+            command: None,
+            cwd: None,
+            exit_code: None,
         }
     }
 
@@ -155,10 +171,16 @@ pub struct ShellIntegration {
     shell_type: RwLock<ShellType>,
     /// 当前工作目录
     current_dir: RwLock<Option<String>>,
+    /// 当前主机名（OSC 7 上报）
+    current_host: RwLock<Option<String>>,
     /// 集成状态
     status: RwLock<ShellIntegrationStatus>,
     /// 当前命令信息
     current_command: RwLock<Option<CommandInfo>>,
+    /// 待关联到下一条命令的命令行
+    pending_command: RwLock<Option<String>>,
+    /// 当前命令的输出（仅保留末尾）
+    command_output: RwLock<Vec<u8>>,
     /// 上次命令开始时间
     last_command_start: AtomicI64,
     /// Tauri 应用句柄（可选）
@@ -175,8 +197,11 @@ impl ShellIntegration {
             block_id,
             shell_type: RwLock::new(ShellType::Unknown),
             current_dir: RwLock::new(None),
+            current_host: RwLock::new(None),
             status: RwLock::new(ShellIntegrationStatus::Unknown),
             current_command: RwLock::new(None),
+            pending_command: RwLock::new(None),
+            command_output: RwLock::new(Vec::new()),
             last_command_start: AtomicI64::new(0),
             app_handle: None,
         }
@@ -192,8 +217,11 @@ impl ShellIntegration {
             block_id,
             shell_type: RwLock::new(ShellType::Unknown),
             current_dir: RwLock::new(None),
+            current_host: RwLock::new(None),
             status: RwLock::new(ShellIntegrationStatus::Unknown),
             current_command: RwLock::new(None),
+            pending_command: RwLock::new(None),
+            command_output: RwLock::new(Vec::new()),
             last_command_start: AtomicI64::new(0),
             app_handle: Some(app_handle),
         }
@@ -226,6 +254,11 @@ impl ShellIntegration {
         self.current_dir.read().unwrap().clone()
     }
 
+    /// 获取当前主机名（OSC 7 上报）
+    pub fn get_current_host(&self) -> Option<String> {
+        self.current_host.read().unwrap().clone()
+    }
+
     /// 获取集成状态
     pub fn get_status(&self) -> ShellIntegrationStatus {
         *self.status.read().unwrap()
@@ -236,6 +269,14 @@ impl ShellIntegration {
         self.current_command.read().unwrap().clone()
     }
 
+    /// 获取当前（或最近一条）命令的输出
+    ///
+    /// 只包含命令执行期间（OSC 133 C 与 D 之间）的输出，最多保留
+    /// `MAX_COMMAND_OUTPUT_BYTES` 字节，不做 ANSI 过滤。
+    pub fn get_command_output(&self) -> String {
+        String::from_utf8_lossy(&self.command_output.read().unwrap()).into_owned()
+    }
+
     /// 处理 PTY 输出数据
     ///
     /// 解析数据中的 OSC 序列并更新状态。
@@ -248,8 +289,12 @@ impl ShellIntegration {
     pub fn process_output(&self, data: &[u8]) -> usize {
         let parsed = OSCParser::parse(data);
         let count = parsed.len();
+        let mut last_end = 0;
 
         for osc in parsed {
+            self.capture_output(&data[last_end..osc.range.start]);
+            last_end = osc.range.end;
+
             if let Err(e) = self.process_osc(&osc.sequence) {
                 tracing::warn!(
                     "[ShellIntegration] 处理 OSC 序列失败: block_id={}, error={}",
@@ -258,10 +303,25 @@ impl ShellIntegration {
                 );
             }
         }
+        self.capture_output(&data[last_end..]);
 
         count
     }
 
+    /// 捕获命令执行期间的输出
+    fn capture_output(&self, data: &[u8]) {
+        if data.is_empty() || self.get_status() != ShellIntegrationStatus::RunningCommand {
+            return;
+        }
+
+        let mut output = self.command_output.write().unwrap();
+        output.extend_from_slice(data);
+        if output.len() > MAX_COMMAND_OUTPUT_BYTES {
+            let excess = output.len() - MAX_COMMAND_OUTPUT_BYTES;
+            output.drain(..excess);
+        }
+    }
+
     /// 处理单个 OSC 序列
     ///
     /// # 参数
@@ -270,14 +330,20 @@ impl ShellIntegration {
     /// _Requirements: 6.1, 6.2, 6.3, 6.4_
     pub fn process_osc(&self, sequence: &OSCSequence) -> Result<(), TerminalError> {
         match sequence {
-            OSCSequence::CurrentDirectory { hostname: _, path } => {
+            OSCSequence::CurrentDirectory { hostname, path } => {
+                if let Some(host) = hostname.as_ref().filter(|h| !h.is_empty()) {
+                    *self.current_host.write().unwrap() = Some(host.clone());
+                }
                 self.update_current_dir(path.clone());
             }
             OSCSequence::Clipboard { selection, data } => {
                 self.handle_clipboard(selection, data)?;
             }
-            OSCSequence::PromptMark { mark_type } => {
-                self.handle_prompt_mark(*mark_type);
+            OSCSequence::PromptMark {
+                mark_type,
+                exit_code,
+            } => {
+                self.handle_prompt_mark(*mark_type, *exit_code);
             }
             OSCSequence::WaveCommand { command } => {
                 self.handle_wave_command(command)?;
@@ -360,11 +426,11 @@ impl ShellIntegration {
     /// 处理命令提示符标记
     ///
     /// _Requirements: 6.3, 6.6, 6.8_
-    fn handle_prompt_mark(&self, mark_type: PromptMarkType) {
+    fn handle_prompt_mark(&self, mark_type: PromptMarkType, exit_code: Option<i32>) {
         match mark_type {
             PromptMarkType::PromptStart => {
                 // 提示符开始，命令已结束
-                self.finish_command();
+                self.finish_command(None);
                 self.set_status(ShellIntegrationStatus::Ready);
             }
             PromptMarkType::CommandStart => {
@@ -378,7 +444,7 @@ impl ShellIntegration {
             }
             PromptMarkType::CommandFinished => {
                 // 命令执行完成
-                self.finish_command();
+                self.finish_command(exit_code);
                 self.set_status(ShellIntegrationStatus::Ready);
             }
             PromptMarkType::Unknown(c) => {
@@ -413,6 +479,13 @@ impl ShellIntegration {
                     self.update_current_dir(args.to_string());
                 }
             }
+            "setcmd" => {
+                // 设置当前命令行
+                let line = args.trim_end_matches(['\r', '\n']);
+                if !line.is_empty() {
+                    self.set_command_line(line.to_string());
+                }
+            }
             "setshell" => {
                 // 设置 Shell 类型
                 if !args.is_empty() {
@@ -453,15 +526,35 @@ impl ShellIntegration {
         }
     }
 
+    /// 设置命令行
+    ///
+    /// 命令已开始执行且尚无命令行时直接关联，否则留给下一条命令。
+    fn set_command_line(&self, line: String) {
+        if self.get_status() == ShellIntegrationStatus::RunningCommand {
+            let mut guard = self.current_command.write().unwrap();
+            if let Some(cmd) = guard.as_mut().filter(|c| c.command.is_none()) {
+                cmd.command = Some(line);
+                return;
+            }
+        }
+        *self.pending_command.write().unwrap() = Some(line);
+    }
+
     /// 开始命令
     ///
     /// _Requirements: 6.8_
     fn start_command(&self) {
         let now = current_timestamp_ms();
         self.last_command_start.store(now, Ordering::SeqCst);
+        self.command_output.write().unwrap().clear();
 
+        let command = CommandInfo {
+            command: self.pending_command.write().unwrap().take(),
+            cwd: self.get_current_dir(),
+            ..CommandInfo::new()
+        };
         let mut guard = self.current_command.write().unwrap();
-        *guard = Some(CommandInfo::new());
+        *guard = Some(command);
 
         tracing::debug!(
             "[ShellIntegration] 命令开始: block_id={}, time={}",
@@ -473,10 +566,11 @@ impl ShellIntegration {
     /// 结束命令
     ///
     /// _Requirements: 6.8_
-    fn finish_command(&self) {
+    fn finish_command(&self, exit_code: Option<i32>) {
         let mut guard = self.current_command.write().unwrap();
-        if let Some(ref mut cmd) = *guard {
+        if let Some(cmd) = guard.as_mut().filter(|c| c.end_time.is_none()) {
             cmd.finish();
+            cmd.exit_code = exit_code;
             tracing::debug!(
                 "[ShellIntegration] 命令结束: block_id={}, duration_ms={:?}",
                 self.block_id,
@@ -521,6 +615,9 @@ impl ShellIntegration {
             let mut guard = self.current_command.write().unwrap();
             *guard = None;
         }
+        *self.current_host.write().unwrap() = None;
+        *self.pending_command.write().unwrap() = None;
+        self.command_output.write().unwrap().clear();
         self.last_command_start.store(0, Ordering::SeqCst);
 
         tracing::debug!("[ShellIntegration] 状态重置: block_id={}", self.block_id);
@@ -582,6 +679,7 @@ mod tests {
         // 先设置为 RunningCommand
         let osc_exec = OSCSequence::PromptMark {
             mark_type: PromptMarkType::CommandExecuted,
+            exit_code: None,
         };
         integration.process_osc(&osc_exec).unwrap();
         assert_eq!(
@@ -592,6 +690,7 @@ mod tests {
         // 然后 PromptStart 应该切换到 Ready
         let osc_prompt = OSCSequence::PromptMark {
             mark_type: PromptMarkType::PromptStart,
+            exit_code: None,
         };
         integration.process_osc(&osc_prompt).unwrap();
         assert_eq!(integration.get_status(), ShellIntegrationStatus::Ready);
@@ -603,6 +702,7 @@ mod tests {
 
         let osc = OSCSequence::PromptMark {
             mark_type: PromptMarkType::CommandExecuted,
+            exit_code: None,
         };
 
         integration.process_osc(&osc).unwrap();
@@ -620,6 +720,7 @@ mod tests {
         // 先执行命令
         let osc_exec = OSCSequence::PromptMark {
             mark_type: PromptMarkType::CommandExecuted,
+            exit_code: None,
         };
         integration.process_osc(&osc_exec).unwrap();
 
@@ -629,6 +730,7 @@ mod tests {
         // 命令结束
         let osc_finish = OSCSequence::PromptMark {
             mark_type: PromptMarkType::CommandFinished,
+            exit_code: None,
         };
         integration.process_osc(&osc_finish).unwrap();
 
@@ -696,6 +798,7 @@ mod tests {
 
         let osc_exec = OSCSequence::PromptMark {
             mark_type: PromptMarkType::CommandExecuted,
+            exit_code: None,
         };
         integration.process_osc(&osc_exec).unwrap();
 
@@ -721,4 +824,27 @@ mod tests {
         assert!(cmd.duration_ms.is_some());
         assert!(cmd.duration_ms.unwrap() >= 5);
     }
+
+    #[test]
+    fn test_command_capture_from_output() {
+        let integration = ShellIntegration::new("test-block".to_string());
+
+        integration.process_output(
+            b"\x1b]7;file://devbox/srv/app\x1b\\\x1b]133;A\x1b\\$ \
+              \x1b]16162;setcmd make test\x1b\\\x1b]133;C\x1b\\",
+        );
+        integration.process_output(b"running tests\r\n");
+        integration.process_output(b"1 failed\r\n\x1b]133;D;2\x1b\\\x1b]133;A\x1b\\$ ");
+
+        let cmd = integration.get_current_command().unwrap();
+        assert_eq!(cmd.command.as_deref(), Some("make test"));
+        assert_eq!(cmd.cwd.as_deref(), Some("/srv/app"));
+        assert_eq!(cmd.exit_code, Some(2));
+        assert!(cmd.end_time.is_some());
+        assert_eq!(integration.get_current_host().as_deref(), Some("devbox"));
+        assert_eq!(
+            integration.get_command_output(),
+            "running tests\r\n1 failed\r\n"
+        );
+    }
 }
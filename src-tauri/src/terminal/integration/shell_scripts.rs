@@ -59,19 +59,33 @@ __proxycast_command_executed() {
 }
 
 __proxycast_command_finished() {
-    printf '\033]133;D;%s\033\\' "$?"
+    printf '\033]133;D;%s\033\\' "${1:-$?}"
+}
+
+# OSC 16162 - 上报命令行（用于命令历史）
+__proxycast_report_command() {
+    printf '\033]16162;setcmd %s\033\\' "$1"
 }
 
 # 设置 PROMPT_COMMAND
 __proxycast_precmd() {
     local exit_code=$?
-    __proxycast_command_finished
+    __proxycast_command_finished "$exit_code"
     __proxycast_osc7
     __proxycast_prompt_start
     return $exit_code
 }
 
+# 提示符就绪，下一条命令触发 preexec
+__proxycast_prompt_ready() {
+    __proxycast_at_prompt=1
+}
+
 __proxycast_preexec() {
+    local cmd
+    cmd=$(HISTTIMEFORMAT= builtin history 1)
+    [[ $cmd =~ ^\ *[0-9]+\*?\ +(.*)$ ]] && cmd=${BASH_REMATCH[1]}
+    __proxycast_report_command "$cmd"
     __proxycast_command_executed
 }
 
@@ -82,9 +96,11 @@ if [ -n "$BASH_VERSION" ]; then
         if [ -n "$COMP_LINE" ]; then
             return
         fi
-        if [ "$BASH_COMMAND" = "$PROMPT_COMMAND" ]; then
+        # 每个提示符只触发一次（管道和 PROMPT_COMMAND 中的命令不重复触发）
+        if [ -z "$__proxycast_at_prompt" ]; then
             return
         fi
+        __proxycast_at_prompt=
         __proxycast_preexec
     }
     
@@ -93,9 +109,9 @@ fi
 
 # 设置 PROMPT_COMMAND
 if [ -z "$PROMPT_COMMAND" ]; then
-    PROMPT_COMMAND="__proxycast_precmd"
+    PROMPT_COMMAND="__proxycast_precmd;__proxycast_prompt_ready"
 else
-    PROMPT_COMMAND="__proxycast_precmd;$PROMPT_COMMAND"
+    PROMPT_COMMAND="__proxycast_precmd;$PROMPT_COMMAND;__proxycast_prompt_ready"
 fi
 
 # 加载用户的 .bashrc（如果存在且我们是通过 --rcfile 启动的）
@@ -130,20 +146,21 @@ __proxycast_command_executed() {
 }
 
 __proxycast_command_finished() {
-    printf '\033]133;D;%s\033\\' "$?"
+    printf '\033]133;D;%s\033\\' "${1:-$?}"
 }
 
 # precmd 钩子 - 命令执行后
 __proxycast_precmd() {
     local exit_code=$?
-    __proxycast_command_finished
+    __proxycast_command_finished "$exit_code"
     __proxycast_osc7
     __proxycast_prompt_start
     return $exit_code
 }
 
-# preexec 钩子 - 命令执行前
+# preexec 钩子 - 命令执行前（$1 为输入的命令行）
 __proxycast_preexec() {
+    printf '\033]16162;setcmd %s\033\\' "$1"
     __proxycast_command_executed
 }
 
@@ -208,6 +225,7 @@ function __proxycast_fish_prompt --on-event fish_prompt
 end
 
 function __proxycast_fish_preexec --on-event fish_preexec
+    printf '\033]16162;setcmd %s\033\\' "$argv[1]"
     __proxycast_command_executed
 end
 
@@ -267,6 +285,7 @@ if (Get-Module -ListAvailable -Name PSReadLine) {
     $existingHandler = (Get-PSReadLineOption).AddToHistoryHandler
     Set-PSReadLineOption -AddToHistoryHandler {
         param([string]$line)
+        Write-Host -NoNewline "`e]16162;setcmd $line`e\"
         Send-ProxyCastCommandExecuted
         if ($existingHandler) {
             return & $existingHandler $line
@@ -7,7 +7,7 @@
 //! - `events` - Tauri 事件定义
 //! - `pty_session` - PTY 会话封装
 //! - `session_manager` - 会话管理器
-//! - `persistence` - 持久化存储（块文件、会话元数据）
+//! - `persistence` - 持久化存储（块文件、会话元数据、录制、命令历史）
 //! - `block_controller` - 块控制器抽象层
 //! - `connections` - 连接模块（本地 PTY、SSH、WSL）
 //! - `integration` - 集成模块（Shell 集成、OSC 解析、状态重同步）
@@ -45,6 +45,9 @@ pub use integration::{
     resync_controller, ResyncController, ResyncOptions, ResyncResult, TERMINAL_RESET_SEQUENCE,
     TERMINAL_SOFT_RESET_SEQUENCE,
 };
-pub use persistence::{BlockFile, SessionMetadataStore, SessionRecord};
+pub use persistence::{
+    BlockFile, CommandHistoryEntry, CommandHistoryQuery, CommandHistoryStore, Recording,
+    RecordingChapter, SessionMetadataStore, SessionRecord,
+};
 pub use pty_session::{PtySession, DEFAULT_COLS, DEFAULT_ROWS};
 pub use session_manager::{SessionMetadata, TerminalSessionManager};
@@ -6,6 +6,8 @@
 //! - 循环缓冲写入（超过最大大小时覆盖旧数据）
 //! - 文件读取和截断
 //! - 可配置最大文件大小
+//! - 可选的带时间戳录制（见 `recording` 模块）
+//! - 可选的命令历史提取（见 `command_history` 模块）
 //!
 //! ## 设计说明
 //! 采用简单的循环缓冲策略：当文件大小超过配置的最大值时，
@@ -18,8 +20,10 @@ use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 
+use super::command_history::CommandHistoryTracker;
+use super::recording::TerminalRecorder;
 use crate::terminal::error::TerminalError;
 
 /// 默认终端块文件最大大小 (256KB)
@@ -43,6 +47,10 @@ pub struct BlockFile {
     is_wrapped: RwLock<bool>,
     /// 文件句柄（用于写入）
     file: RwLock<Option<File>>,
+    /// 录制器（录制模式开启时存在）
+    recorder: Mutex<Option<TerminalRecorder>>,
+    /// 命令历史跟踪器（启用命令历史时存在）
+    command_history: Mutex<Option<CommandHistoryTracker>>,
 }
 
 impl BlockFile {
@@ -102,6 +110,8 @@ impl BlockFile {
             current_size: AtomicUsize::new(current_size),
             is_wrapped: RwLock::new(is_wrapped),
             file: RwLock::new(Some(file)),
+            recorder: Mutex::new(None),
+            command_history: Mutex::new(None),
         })
     }
 
@@ -321,10 +331,98 @@ impl BlockFile {
     pub fn is_wrapped(&self) -> bool {
         *self.is_wrapped.read()
     }
+
+    /// 获取录制文件路径（与块文件同目录，扩展名为 `.cast`）
+    pub fn recording_path(&self) -> PathBuf {
+        self.file_path.with_extension("cast")
+    }
+
+    /// 检查是否正在录制
+    pub fn is_recording(&self) -> bool {
+        self.recorder.lock().is_some()
+    }
+
+    /// 开始录制（覆盖该块之前的录制）
+    ///
+    /// # 参数
+    /// - `cols`: 当前列数
+    /// - `rows`: 当前行数
+    ///
+    /// # 返回
+    /// - `Ok(PathBuf)`: 录制文件路径
+    /// - `Err(TerminalError)`: 已在录制中或创建失败
+    pub fn start_recording(&self, cols: u16, rows: u16) -> Result<PathBuf, TerminalError> {
+        let mut guard = self.recorder.lock();
+        if guard.is_some() {
+            return Err(TerminalError::RecordingError(format!(
+                "块 {} 已在录制中",
+                self.block_id
+            )));
+        }
+
+        let path = self.recording_path();
+        *guard = Some(TerminalRecorder::create(&self.block_id, &path, cols, rows)?);
+        Ok(path)
+    }
+
+    /// 停止录制
+    ///
+    /// # 返回
+    /// - `Ok(Some(PathBuf))`: 录制文件路径
+    /// - `Ok(None)`: 当前未在录制
+    pub fn stop_recording(&self) -> Result<Option<PathBuf>, TerminalError> {
+        match self.recorder.lock().take() {
+            Some(recorder) => recorder.finish().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// 启用命令历史
+    ///
+    /// 之后通过 `record_output` 传入的输出会被解析，已完成的命令写入命令历史。
+    pub fn enable_command_history(&self, tracker: CommandHistoryTracker) {
+        *self.command_history.lock() = Some(tracker);
+    }
+
+    /// 记录终端输出（同时供录制器和命令历史使用，两者都未启用时忽略）
+    pub fn record_output(&self, data: &[u8]) -> Result<(), TerminalError> {
+        if let Some(tracker) = self.command_history.lock().as_mut() {
+            // 命令历史写入失败不影响录制和终端输出
+            if let Err(e) = tracker.process_output(data) {
+                tracing::warn!("[BlockFile] 写入命令历史失败 {}: {}", self.block_id, e);
+            }
+        }
+
+        match self.recorder.lock().as_mut() {
+            Some(recorder) => recorder.record_output(data),
+            None => Ok(()),
+        }
+    }
+
+    /// 记录用户输入（未录制时忽略）
+    pub fn record_input(&self, data: &[u8]) -> Result<(), TerminalError> {
+        match self.recorder.lock().as_mut() {
+            Some(recorder) => recorder.record_input(data),
+            None => Ok(()),
+        }
+    }
+
+    /// 记录终端大小调整（未录制时忽略）
+    pub fn record_resize(&self, cols: u16, rows: u16) -> Result<(), TerminalError> {
+        match self.recorder.lock().as_mut() {
+            Some(recorder) => recorder.record_resize(cols, rows),
+            None => Ok(()),
+        }
+    }
 }
 
 impl Drop for BlockFile {
     fn drop(&mut self) {
+        // 结束未停止的录制
+        if let Some(recorder) = self.recorder.lock().take() {
+            let _ = recorder.finish();
+        }
+
         // 确保文件句柄被正确关闭
         let mut file_guard = self.file.write();
         if let Some(ref mut file) = *file_guard {
@@ -0,0 +1,627 @@
+//! 命令历史存储
+//!
+//! 基于 Shell 集成（OSC 133 / OSC 7 / OSC 16162 `setcmd`）记录每条命令，
+//! 存储到 SQLite 并提供全文搜索。
+//!
+//! ## 功能
+//! - 跨本地、SSH、WSL 会话的命令历史
+//! - 命令行和输出片段的全文搜索（FTS5）
+//! - 按主机、工作目录、退出状态、时间过滤
+//! - 从终端输出流中自动提取已完成的命令
+
+use std::sync::Arc;
+
+use chrono::Utc;
+use rusqlite::types::ToSql;
+use rusqlite::{params, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use super::recording::strip_ansi;
+use crate::database::DbConnection;
+use crate::terminal::connections::{ConnectionRouter, ConnectionType};
+use crate::terminal::error::TerminalError;
+use crate::terminal::integration::ShellIntegration;
+
+/// 默认查询条数
+const DEFAULT_QUERY_LIMIT: usize = 50;
+
+/// 最大查询条数
+const MAX_QUERY_LIMIT: usize = 500;
+
+/// 存储的输出片段最大字符数（保留末尾）
+const MAX_OUTPUT_SNIPPET_CHARS: usize = 2000;
+
+/// 命令历史记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandHistoryEntry {
+    /// 记录 ID
+    pub id: i64,
+    /// 会话 ID
+    pub session_id: String,
+    /// 主机（本地主机名、SSH 主机或 WSL 发行版）
+    pub host: String,
+    /// 连接类型（local/ssh/wsl）
+    pub connection_type: String,
+    /// 工作目录
+    pub cwd: Option<String>,
+    /// 命令行
+    pub command: String,
+    /// 输出片段（已去除 ANSI 控制序列）
+    pub output: String,
+    /// 退出码
+    pub exit_code: Option<i32>,
+    /// 开始时间（Unix 时间戳，毫秒）
+    pub started_at: i64,
+    /// 结束时间（Unix 时间戳，毫秒）
+    pub finished_at: Option<i64>,
+    /// 持续时间（毫秒）
+    pub duration_ms: Option<i64>,
+}
+
+/// 退出状态过滤
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExitStatusFilter {
+    /// 退出码为 0
+    Success,
+    /// 退出码非 0
+    Failure,
+}
+
+/// 命令历史查询条件
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandHistoryQuery {
+    /// 全文搜索关键词（匹配命令行和输出）
+    pub text: Option<String>,
+    /// 主机
+    pub host: Option<String>,
+    /// 工作目录（包含子目录）
+    pub cwd: Option<String>,
+    /// 会话 ID
+    pub session_id: Option<String>,
+    /// 退出状态
+    pub exit_status: Option<ExitStatusFilter>,
+    /// 开始时间下限（毫秒）
+    pub since: Option<i64>,
+    /// 开始时间上限（毫秒）
+    pub until: Option<i64>,
+    /// 最大返回条数（默认 50，最大 500）
+    pub limit: Option<usize>,
+    /// 偏移量
+    pub offset: Option<usize>,
+}
+
+/// 命令历史查询结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandHistoryHit {
+    /// 命令记录
+    #[serde(flatten)]
+    pub entry: CommandHistoryEntry,
+    /// 匹配片段（仅全文搜索时存在）
+    pub snippet: Option<String>,
+}
+
+/// 命令历史存储服务
+pub struct CommandHistoryStore {
+    db: DbConnection,
+}
+
+impl CommandHistoryStore {
+    /// 创建新的命令历史存储服务
+    pub fn new(db: DbConnection) -> Self {
+        Self { db }
+    }
+
+    /// 初始化数据库表
+    ///
+    /// 创建 terminal_command_history 表和对应的 FTS5 索引表。
+    pub fn init_tables(&self) -> Result<(), TerminalError> {
+        let conn = self
+            .db
+            .lock()
+            .map_err(|e| TerminalError::DatabaseError(format!("无法获取数据库锁: {}", e)))?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS terminal_command_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id TEXT NOT NULL,
+                host TEXT NOT NULL,
+                connection_type TEXT NOT NULL,
+                cwd TEXT,
+                command TEXT NOT NULL,
+                output TEXT NOT NULL DEFAULT '',
+                exit_code INTEGER,
+                started_at INTEGER NOT NULL,
+                finished_at INTEGER,
+                duration_ms INTEGER
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_terminal_command_history_started_at
+                ON terminal_command_history(started_at);
+            CREATE INDEX IF NOT EXISTS idx_terminal_command_history_host
+                ON terminal_command_history(host);
+            CREATE INDEX IF NOT EXISTS idx_terminal_command_history_session
+                ON terminal_command_history(session_id);
+
+            -- 全文搜索表（FTS5），rowid 与 terminal_command_history.id 一致
+            CREATE VIRTUAL TABLE IF NOT EXISTS terminal_command_history_fts USING fts5(
+                command,
+                output
+            );",
+        )
+        .map_err(|e| TerminalError::DatabaseError(format!("创建命令历史表失败: {}", e)))?;
+
+        tracing::debug!("[CommandHistory] 数据库表初始化完成");
+        Ok(())
+    }
+
+    /// 插入命令记录
+    ///
+    /// # 返回
+    /// 新记录 ID（忽略 `entry.id`）
+    pub fn insert(&self, entry: &CommandHistoryEntry) -> Result<i64, TerminalError> {
+        let conn = self
+            .db
+            .lock()
+            .map_err(|e| TerminalError::DatabaseError(format!("无法获取数据库锁: {}", e)))?;
+
+        conn.execute(
+            "INSERT INTO terminal_command_history
+             (session_id, host, connection_type, cwd, command, output, exit_code,
+              started_at, finished_at, duration_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                entry.session_id,
+                entry.host,
+                entry.connection_type,
+                entry.cwd,
+                entry.command,
+                entry.output,
+                entry.exit_code,
+                entry.started_at,
+                entry.finished_at,
+                entry.duration_ms,
+            ],
+        )
+        .map_err(|e| TerminalError::DatabaseError(format!("保存命令记录失败: {}", e)))?;
+        let id = conn.last_insert_rowid();
+
+        conn.execute(
+            "INSERT INTO terminal_command_history_fts (rowid, command, output)
+             VALUES (?1, ?2, ?3)",
+            params![id, entry.command, entry.output],
+        )
+        .map_err(|e| TerminalError::DatabaseError(format!("更新命令索引失败: {}", e)))?;
+
+        Ok(id)
+    }
+
+    /// 根据 ID 获取命令记录
+    pub fn get(&self, id: i64) -> Result<Option<CommandHistoryEntry>, TerminalError> {
+        let conn = self
+            .db
+            .lock()
+            .map_err(|e| TerminalError::DatabaseError(format!("无法获取数据库锁: {}", e)))?;
+
+        conn.query_row(
+            "SELECT id, session_id, host, connection_type, cwd, command, output, exit_code,
+                    started_at, finished_at, duration_ms
+             FROM terminal_command_history WHERE id = ?1",
+            params![id],
+            row_to_entry,
+        )
+        .optional()
+        .map_err(|e| TerminalError::DatabaseError(format!("查询命令记录失败: {}", e)))
+    }
+
+    /// 查询命令历史
+    ///
+    /// 结果按开始时间倒序排列。
+    pub fn search(
+        &self,
+        query: &CommandHistoryQuery,
+    ) -> Result<Vec<CommandHistoryHit>, TerminalError> {
+        let text = query
+            .text
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty());
+
+        let mut sql = String::from(
+            "SELECT h.id, h.session_id, h.host, h.connection_type, h.cwd, h.command, h.output,
+                    h.exit_code, h.started_at, h.finished_at, h.duration_ms",
+        );
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut args: Vec<Box<dyn ToSql>> = Vec::new();
+
+        if let Some(text) = text {
+            sql.push_str(
+                ", snippet(terminal_command_history_fts, -1, '<mark>', '</mark>', '...', 16)
+                 FROM terminal_command_history h
+                 JOIN terminal_command_history_fts f ON f.rowid = h.id",
+            );
+            conditions.push("terminal_command_history_fts MATCH ?");
+            args.push(Box::new(escape_fts_query(text)));
+        } else {
+            sql.push_str(", NULL FROM terminal_command_history h");
+        }
+
+        if let Some(host) = &query.host {
+            conditions.push("h.host = ?");
+            args.push(Box::new(host.clone()));
+        }
+        if let Some(cwd) = &query.cwd {
+            let cwd = cwd.trim_end_matches('/');
+            conditions.push("(h.cwd = ? OR h.cwd LIKE ? ESCAPE '\\')");
+            args.push(Box::new(cwd.to_string()));
+            args.push(Box::new(format!("{}/%", escape_like(cwd))));
+        }
+        if let Some(session_id) = &query.session_id {
+            conditions.push("h.session_id = ?");
+            args.push(Box::new(session_id.clone()));
+        }
+        match query.exit_status {
+            Some(ExitStatusFilter::Success) => conditions.push("h.exit_code = 0"),
+            Some(ExitStatusFilter::Failure) => conditions.push("h.exit_code <> 0"),
+            None => {}
+        }
+        if let Some(since) = query.since {
+            conditions.push("h.started_at >= ?");
+            args.push(Box::new(since));
+        }
+        if let Some(until) = query.until {
+            conditions.push("h.started_at <= ?");
+            args.push(Box::new(until));
+        }
+
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY h.started_at DESC, h.id DESC LIMIT ? OFFSET ?");
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_QUERY_LIMIT)
+            .clamp(1, MAX_QUERY_LIMIT);
+        args.push(Box::new(limit as i64));
+        args.push(Box::new(query.offset.unwrap_or(0) as i64));
+
+        let conn = self
+            .db
+            .lock()
+            .map_err(|e| TerminalError::DatabaseError(format!("无法获取数据库锁: {}", e)))?;
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| TerminalError::DatabaseError(format!("查询命令历史失败: {}", e)))?;
+        let params: Vec<&dyn ToSql> = args.iter().map(|a| a.as_ref()).collect();
+        let rows = stmt
+            .query_map(params.as_slice(), |row| {
+                Ok(CommandHistoryHit {
+                    entry: row_to_entry(row)?,
+                    snippet: row.get(11)?,
+                })
+            })
+            .map_err(|e| TerminalError::DatabaseError(format!("查询命令历史失败: {}", e)))?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| TerminalError::DatabaseError(format!("读取命令历史失败: {}", e)))
+    }
+
+    /// 删除命令记录
+    pub fn delete(&self, id: i64) -> Result<(), TerminalError> {
+        let conn = self
+            .db
+            .lock()
+            .map_err(|e| TerminalError::DatabaseError(format!("无法获取数据库锁: {}", e)))?;
+
+        conn.execute(
+            "DELETE FROM terminal_command_history WHERE id = ?1",
+            params![id],
+        )
+        .map_err(|e| TerminalError::DatabaseError(format!("删除命令记录失败: {}", e)))?;
+        conn.execute(
+            "DELETE FROM terminal_command_history_fts WHERE rowid = ?1",
+            params![id],
+        )
+        .map_err(|e| TerminalError::DatabaseError(format!("删除命令索引失败: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// 清空命令历史
+    pub fn clear(&self) -> Result<(), TerminalError> {
+        let conn = self
+            .db
+            .lock()
+            .map_err(|e| TerminalError::DatabaseError(format!("无法获取数据库锁: {}", e)))?;
+
+        conn.execute_batch(
+            "DELETE FROM terminal_command_history;
+             DELETE FROM terminal_command_history_fts;",
+        )
+        .map_err(|e| TerminalError::DatabaseError(format!("清空命令历史失败: {}", e)))?;
+
+        Ok(())
+    }
+}
+
+/// 命令历史跟踪器
+///
+/// 解析单个块的终端输出，在命令结束（OSC 133 D）时写入命令历史。
+pub struct CommandHistoryTracker {
+    /// 命令历史存储
+    store: Arc<CommandHistoryStore>,
+    /// Shell 集成处理器
+    shell_integration: ShellIntegration,
+    /// 会话 ID
+    session_id: String,
+    /// 主机
+    host: String,
+    /// 连接类型
+    connection_type: ConnectionType,
+    /// 最近写入的命令开始时间（避免重复写入）
+    last_recorded_start: Option<i64>,
+}
+
+impl CommandHistoryTracker {
+    /// 根据连接名称创建跟踪器
+    ///
+    /// # 参数
+    /// - `store`: 命令历史存储
+    /// - `session_id`: 会话 ID
+    /// - `conn_name`: 连接名称（None 或 "local" 为本地）
+    pub fn for_connection(
+        store: Arc<CommandHistoryStore>,
+        session_id: &str,
+        conn_name: Option<&str>,
+    ) -> Self {
+        let conn_name = conn_name.unwrap_or("").trim();
+        let connection_type = ConnectionRouter::route(conn_name);
+        let host = match connection_type {
+            ConnectionType::Local => {
+                whoami::fallible::hostname().unwrap_or_else(|_| "localhost".to_string())
+            }
+            ConnectionType::WSL => match conn_name.trim_start_matches("wsl://") {
+                "" => "wsl".to_string(),
+                distro => distro.to_string(),
+            },
+            ConnectionType::SSH => conn_name.trim_start_matches("ssh://").to_string(),
+        };
+
+        Self {
+            store,
+            shell_integration: ShellIntegration::new(session_id.to_string()),
+            session_id: session_id.to_string(),
+            host,
+            connection_type,
+            last_recorded_start: None,
+        }
+    }
+
+    /// 获取主机
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// 处理终端输出
+    ///
+    /// # 返回
+    /// 本次写入的命令记录 ID（没有命令结束时为 None）
+    pub fn process_output(&mut self, data: &[u8]) -> Result<Option<i64>, TerminalError> {
+        self.shell_integration.process_output(data);
+
+        let command = match self.shell_integration.get_current_command() {
+            Some(cmd) if cmd.end_time.is_some() => cmd,
+            _ => return Ok(None),
+        };
+        if self.last_recorded_start == Some(command.start_time) {
+            return Ok(None);
+        }
+        self.last_recorded_start = Some(command.start_time);
+
+        // 没有命令行的记录（例如未加载集成脚本的 setcmd）无法搜索和重跑
+        let Some(command_line) = command.command.clone().filter(|c| !c.trim().is_empty()) else {
+            return Ok(None);
+        };
+
+        let entry = CommandHistoryEntry {
+            id: 0,
+            session_id: self.session_id.clone(),
+            host: self.host.clone(),
+            connection_type: self.connection_type.to_string(),
+            cwd: command.cwd.clone(),
+            command: command_line,
+            output: output_snippet(&self.shell_integration.get_command_output()),
+            exit_code: command.exit_code,
+            started_at: command.start_time,
+            finished_at: command
+                .end_time
+                .or_else(|| Some(Utc::now().timestamp_millis())),
+            duration_ms: command.duration_ms,
+        };
+        self.store.insert(&entry).map(Some)
+    }
+}
+
+/// 从数据库行构建命令记录
+fn row_to_entry(row: &Row<'_>) -> rusqlite::Result<CommandHistoryEntry> {
+    Ok(CommandHistoryEntry {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        host: row.get(2)?,
+        connection_type: row.get(3)?,
+        cwd: row.get(4)?,
+        command: row.get(5)?,
+        output: row.get(6)?,
+        exit_code: row.get(7)?,
+        started_at: row.get(8)?,
+        finished_at: row.get(9)?,
+        duration_ms: row.get(10)?,
+    })
+}
+
+/// 生成输出片段：去除控制序列和回车，保留末尾
+fn output_snippet(raw: &str) -> String {
+    let text = strip_ansi(raw).replace("\r\n", "\n").replace('\r', "\n");
+    let text = text.trim();
+    let count = text.chars().count();
+    if count <= MAX_OUTPUT_SNIPPET_CHARS {
+        return text.to_string();
+    }
+    text.chars()
+        .skip(count - MAX_OUTPUT_SNIPPET_CHARS)
+        .collect()
+}
+
+/// 构建 FTS5 查询：每个词按前缀匹配，多个词之间为 AND
+fn escape_fts_query(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 转义 LIKE 模式中的特殊字符
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+    use std::sync::Mutex;
+
+    fn create_store() -> Arc<CommandHistoryStore> {
+        let conn = Connection::open_in_memory().unwrap();
+        let store = CommandHistoryStore::new(Arc::new(Mutex::new(conn)));
+        store.init_tables().unwrap();
+        Arc::new(store)
+    }
+
+    fn entry(
+        host: &str,
+        cwd: &str,
+        command: &str,
+        exit_code: i32,
+        started_at: i64,
+    ) -> CommandHistoryEntry {
+        CommandHistoryEntry {
+            id: 0,
+            session_id: "session-1".to_string(),
+            host: host.to_string(),
+            connection_type: "local".to_string(),
+            cwd: Some(cwd.to_string()),
+            command: command.to_string(),
+            output: format!("output of {}", command),
+            exit_code: Some(exit_code),
+            started_at,
+            finished_at: Some(started_at + 10),
+            duration_ms: Some(10),
+        }
+    }
+
+    #[test]
+    fn test_search_filters() {
+        let store = create_store();
+        store
+            .insert(&entry("devbox", "/srv/app", "cargo build", 0, 1_000))
+            .unwrap();
+        store
+            .insert(&entry("devbox", "/srv/app/web", "npm test", 1, 2_000))
+            .unwrap();
+        store
+            .insert(&entry("laptop", "/home/me", "cargo test", 101, 3_000))
+            .unwrap();
+
+        let all = store.search(&CommandHistoryQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].entry.command, "cargo test");
+
+        let hits = store
+            .search(&CommandHistoryQuery {
+                text: Some("carg".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].snippet.is_some());
+
+        let hits = store
+            .search(&CommandHistoryQuery {
+                host: Some("devbox".to_string()),
+                exit_status: Some(ExitStatusFilter::Failure),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entry.command, "npm test");
+
+        let hits = store
+            .search(&CommandHistoryQuery {
+                cwd: Some("/srv/app/".to_string()),
+                since: Some(1_500),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entry.cwd.as_deref(), Some("/srv/app/web"));
+    }
+
+    #[test]
+    fn test_search_output_and_delete() {
+        let store = create_store();
+        let id = store
+            .insert(&entry("devbox", "/tmp", "make", 2, 1_000))
+            .unwrap();
+
+        let query = CommandHistoryQuery {
+            text: Some("output".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(store.search(&query).unwrap().len(), 1);
+
+        store.delete(id).unwrap();
+        assert!(store.get(id).unwrap().is_none());
+        assert!(store.search(&query).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tracker_records_finished_commands() {
+        let store = create_store();
+        let mut tracker =
+            CommandHistoryTracker::for_connection(store.clone(), "s1", Some("wsl://Ubuntu"));
+        assert_eq!(tracker.host(), "Ubuntu");
+
+        tracker
+            .process_output(b"\x1b]7;file://box/work\x07\x1b]16162;setcmd ls -l\x07\x1b]133;C\x07")
+            .unwrap();
+        assert_eq!(
+            tracker.process_output(b"\x1b[1mfile\x1b[0m\r\n").unwrap(),
+            None
+        );
+        let id = tracker
+            .process_output(b"\x1b]133;D;0\x07\x1b]133;A\x07")
+            .unwrap()
+            .unwrap();
+        // 后续提示符不会重复写入
+        assert_eq!(tracker.process_output(b"\x1b]133;A\x07$ ").unwrap(), None);
+
+        let saved = store.get(id).unwrap().unwrap();
+        assert_eq!(saved.command, "ls -l");
+        assert_eq!(saved.cwd.as_deref(), Some("/work"));
+        assert_eq!(saved.output, "file");
+        assert_eq!(saved.exit_code, Some(0));
+        assert_eq!(saved.connection_type, "wsl");
+        assert_eq!(saved.host, "Ubuntu");
+    }
+
+    #[test]
+    fn test_escape_fts_query() {
+        assert_eq!(escape_fts_query("git  push"), "\"git\"* \"push\"*");
+        assert_eq!(escape_fts_query("a\"b"), "\"a\"\"b\"*");
+    }
+}
@@ -4,15 +4,28 @@
 //!
 //! ## 模块结构
 //! - `block_file` - 块文件循环缓冲存储
+//! - `command_history` - 命令历史 SQLite 存储与全文搜索
+//! - `recording` - 终端会话录制（asciicast v2）
 //! - `session_store` - 会话元数据 SQLite 存储
 //!
 //! ## 功能
 //! - 终端输出历史的文件存储（循环缓冲）
 //! - 会话元数据的数据库存储
+//! - 跨会话的命令历史检索
+//! - 带时间戳的会话录制、导出与回放
 //! - 会话恢复支持
 
 pub mod block_file;
+pub mod command_history;
+pub mod recording;
 pub mod session_store;
 
 pub use block_file::BlockFile;
+pub use command_history::{
+    CommandHistoryEntry, CommandHistoryHit, CommandHistoryQuery, CommandHistoryStore,
+    CommandHistoryTracker, ExitStatusFilter,
+};
+pub use recording::{
+    strip_ansi, Recording, RecordingChapter, RecordingEvent, RecordingEventKind, TerminalRecorder,
+};
 pub use session_store::{SessionMetadataStore, SessionRecord};
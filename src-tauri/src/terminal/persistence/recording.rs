@@ -0,0 +1,668 @@
+//! 终端会话录制
+//!
+//! 以带时间戳的事件流记录终端会话，支持导出和回放。
+//!
+//! ## 功能
+//! - 记录输出、输入和终端大小调整事件（asciicast v2 格式增量写入）
+//! - 基于 Shell 集成（OSC 133）的命令边界生成章节标记
+//! - 导出为 asciicast v2 或去除 ANSI 控制序列的纯文本记录
+//! - 生成回放帧序列，用于回放到新的块中
+//!
+//! ## 文件格式
+//! 录制文件即 asciicast v2 文件：首行为头部 JSON，之后每行一个事件
+//! `[时间(秒), 类型, 数据]`，类型为 `o`（输出）、`i`（输入）、
+//! `r`（大小调整，数据为 `列x行`）或 `m`（章节标记）。
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::terminal::error::TerminalError;
+use crate::terminal::integration::ShellIntegration;
+
+/// asciicast 格式版本
+pub const ASCIICAST_VERSION: u8 = 2;
+
+/// 录制事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingEventKind {
+    /// 终端输出
+    Output,
+    /// 用户输入
+    Input,
+    /// 终端大小调整
+    Resize,
+    /// 章节标记
+    Marker,
+}
+
+impl RecordingEventKind {
+    /// asciicast 事件类型代码
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Output => "o",
+            Self::Input => "i",
+            Self::Resize => "r",
+            Self::Marker => "m",
+        }
+    }
+
+    /// 从 asciicast 事件类型代码解析
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "o" => Some(Self::Output),
+            "i" => Some(Self::Input),
+            "r" => Some(Self::Resize),
+            "m" => Some(Self::Marker),
+            _ => None,
+        }
+    }
+}
+
+/// 录制文件头部
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    /// 格式版本（固定为 2）
+    pub version: u8,
+    /// 初始列数
+    pub width: u16,
+    /// 初始行数
+    pub height: u16,
+    /// 录制开始时间（Unix 时间戳，秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    /// 标题
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// 录制事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingEvent {
+    /// 相对录制开始的时间（秒）
+    pub time: f64,
+    /// 事件类型
+    pub kind: RecordingEventKind,
+    /// 事件数据
+    pub data: String,
+}
+
+/// 录制章节（对应一条命令）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingChapter {
+    /// 章节序号（从 1 开始）
+    pub index: usize,
+    /// 章节开始时间（秒）
+    pub time: f64,
+    /// 章节结束时间（下一章节开始或录制结束，秒）
+    pub end_time: f64,
+    /// 章节标题（通常是命令行）
+    pub label: String,
+}
+
+/// 终端录制器
+///
+/// 将事件增量写入 asciicast v2 文件，每个事件写入后立即落盘。
+pub struct TerminalRecorder {
+    /// 录制文件路径
+    path: PathBuf,
+    /// 文件句柄
+    file: File,
+    /// 录制开始时刻
+    started_at: Instant,
+    /// 输出中未完成的 UTF-8 字节
+    output_pending: Vec<u8>,
+    /// 输入中未完成的 UTF-8 字节
+    input_pending: Vec<u8>,
+    /// 用于识别命令边界的 Shell 集成处理器
+    shell_integration: ShellIntegration,
+    /// 最近一条命令的开始时间（CommandInfo.start_time）
+    last_command_start: Option<i64>,
+    /// 正在输入的命令行
+    input_line: String,
+    /// 最近提交的命令行
+    last_command_line: Option<String>,
+    /// 已生成的章节数
+    chapter_count: usize,
+}
+
+impl TerminalRecorder {
+    /// 创建录制器（覆盖已存在的录制文件）
+    ///
+    /// # 参数
+    /// - `block_id`: 块 ID
+    /// - `path`: 录制文件路径
+    /// - `cols`: 初始列数
+    /// - `rows`: 初始行数
+    pub fn create(
+        block_id: &str,
+        path: &Path,
+        cols: u16,
+        rows: u16,
+    ) -> Result<Self, TerminalError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| {
+                TerminalError::RecordingError(format!("无法创建目录 {:?}: {}", parent, e))
+            })?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|e| TerminalError::RecordingError(format!("无法创建录制文件: {}", e)))?;
+
+        let header = RecordingHeader {
+            version: ASCIICAST_VERSION,
+            width: cols,
+            height: rows,
+            timestamp: Some(Utc::now().timestamp()),
+            title: Some(block_id.to_string()),
+        };
+        let line = serde_json::to_string(&header)
+            .map_err(|e| TerminalError::RecordingError(format!("序列化头部失败: {}", e)))?;
+        writeln!(file, "{}", line)
+            .map_err(|e| TerminalError::RecordingError(format!("写入失败: {}", e)))?;
+
+        tracing::debug!("[Recording] 开始录制: {} -> {:?}", block_id, path);
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            started_at: Instant::now(),
+            output_pending: Vec::new(),
+            input_pending: Vec::new(),
+            shell_integration: ShellIntegration::new(block_id.to_string()),
+            last_command_start: None,
+            input_line: String::new(),
+            last_command_line: None,
+            chapter_count: 0,
+        })
+    }
+
+    /// 获取录制文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 记录终端输出
+    ///
+    /// 同时解析 Shell 集成序列，命令开始时写入章节标记。
+    pub fn record_output(&mut self, data: &[u8]) -> Result<(), TerminalError> {
+        let text = decode_utf8(&mut self.output_pending, data);
+        if !text.is_empty() {
+            self.write_event(RecordingEventKind::Output, &text)?;
+        }
+
+        self.shell_integration.process_output(data);
+        if let Some(command) = self.shell_integration.get_current_command() {
+            if self.last_command_start != Some(command.start_time) {
+                self.last_command_start = Some(command.start_time);
+                self.chapter_count += 1;
+                let label = self
+                    .last_command_line
+                    .take()
+                    .unwrap_or_else(|| format!("命令 {}", self.chapter_count));
+                self.write_event(RecordingEventKind::Marker, &label)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 记录用户输入
+    pub fn record_input(&mut self, data: &[u8]) -> Result<(), TerminalError> {
+        let text = decode_utf8(&mut self.input_pending, data);
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        for c in strip_ansi(&text).chars() {
+            match c {
+                '\r' | '\n' => {
+                    let line = self.input_line.trim();
+                    if !line.is_empty() {
+                        self.last_command_line = Some(line.to_string());
+                    }
+                    self.input_line.clear();
+                }
+                '\x7f' | '\x08' => {
+                    self.input_line.pop();
+                }
+                '\x03' | '\x15' => self.input_line.clear(),
+                c if c.is_control() => {}
+                c => self.input_line.push(c),
+            }
+        }
+
+        self.write_event(RecordingEventKind::Input, &text)
+    }
+
+    /// 记录终端大小调整
+    pub fn record_resize(&mut self, cols: u16, rows: u16) -> Result<(), TerminalError> {
+        self.write_event(RecordingEventKind::Resize, &format!("{}x{}", cols, rows))
+    }
+
+    /// 写入章节标记
+    pub fn add_marker(&mut self, label: &str) -> Result<(), TerminalError> {
+        self.chapter_count += 1;
+        self.write_event(RecordingEventKind::Marker, label)
+    }
+
+    /// 结束录制，返回录制文件路径
+    pub fn finish(mut self) -> Result<PathBuf, TerminalError> {
+        let tail = String::from_utf8_lossy(&std::mem::take(&mut self.output_pending)).into_owned();
+        if !tail.is_empty() {
+            self.write_event(RecordingEventKind::Output, &tail)?;
+        }
+        self.file
+            .flush()
+            .map_err(|e| TerminalError::RecordingError(format!("Flush 失败: {}", e)))?;
+
+        tracing::debug!("[Recording] 结束录制: {:?}", self.path);
+        Ok(self.path)
+    }
+
+    /// 写入单个事件
+    fn write_event(&mut self, kind: RecordingEventKind, data: &str) -> Result<(), TerminalError> {
+        let time = (self.started_at.elapsed().as_secs_f64() * 1_000_000.0).round() / 1_000_000.0;
+        let line = serde_json::to_string(&(time, kind.code(), data))
+            .map_err(|e| TerminalError::RecordingError(format!("序列化事件失败: {}", e)))?;
+        writeln!(self.file, "{}", line)
+            .map_err(|e| TerminalError::RecordingError(format!("写入失败: {}", e)))
+    }
+}
+
+/// 已保存的录制
+#[derive(Debug, Clone)]
+pub struct Recording {
+    /// 头部信息
+    pub header: RecordingHeader,
+    /// 事件列表（按时间排序）
+    pub events: Vec<RecordingEvent>,
+}
+
+impl Recording {
+    /// 从文件加载录制
+    pub fn load(path: &Path) -> Result<Self, TerminalError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| TerminalError::RecordingError(format!("无法读取录制文件: {}", e)))?;
+        Self::parse(&content)
+    }
+
+    /// 解析 asciicast v2 内容
+    ///
+    /// 未知类型的事件和无法解析的尾行（例如写入中断）会被忽略。
+    pub fn parse(content: &str) -> Result<Self, TerminalError> {
+        let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+        let header_line = lines
+            .next()
+            .ok_or_else(|| TerminalError::RecordingError("录制文件为空".to_string()))?;
+        let header: RecordingHeader = serde_json::from_str(header_line)
+            .map_err(|e| TerminalError::RecordingError(format!("无效的录制头部: {}", e)))?;
+        if header.version != ASCIICAST_VERSION {
+            return Err(TerminalError::RecordingError(format!(
+                "不支持的录制版本: {}",
+                header.version
+            )));
+        }
+
+        let events = lines
+            .filter_map(|line| serde_json::from_str::<(f64, String, String)>(line).ok())
+            .filter_map(|(time, code, data)| {
+                RecordingEventKind::from_code(&code).map(|kind| RecordingEvent { time, kind, data })
+            })
+            .collect();
+
+        Ok(Self { header, events })
+    }
+
+    /// 录制总时长（秒）
+    pub fn duration(&self) -> f64 {
+        self.events.last().map(|e| e.time).unwrap_or(0.0)
+    }
+
+    /// 导出为 asciicast v2
+    ///
+    /// # 参数
+    /// - `include_input`: 是否包含输入事件（可能包含密码等敏感内容）
+    pub fn to_asciicast(&self, include_input: bool) -> String {
+        let mut out = serde_json::to_string(&self.header).unwrap_or_default();
+        out.push('\n');
+        for event in &self.events {
+            if event.kind == RecordingEventKind::Input && !include_input {
+                continue;
+            }
+            if let Ok(line) = serde_json::to_string(&(event.time, event.kind.code(), &event.data)) {
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// 导出为纯文本记录
+    ///
+    /// 去除 ANSI 控制序列并处理回车和退格，章节标记输出为分隔标题行。
+    pub fn to_transcript(&self) -> String {
+        let mut renderer = TranscriptRenderer::default();
+        let mut segment = String::new();
+
+        for event in &self.events {
+            match event.kind {
+                RecordingEventKind::Output => segment.push_str(&event.data),
+                RecordingEventKind::Marker => {
+                    renderer.feed(&strip_ansi(&segment));
+                    segment.clear();
+                    renderer.heading(&format!(
+                        "── [{}] {} ──",
+                        format_timestamp(event.time),
+                        event.data
+                    ));
+                }
+                RecordingEventKind::Input | RecordingEventKind::Resize => {}
+            }
+        }
+        renderer.feed(&strip_ansi(&segment));
+
+        renderer.finish()
+    }
+
+    /// 获取章节列表
+    pub fn chapters(&self) -> Vec<RecordingChapter> {
+        let duration = self.duration();
+        let markers: Vec<&RecordingEvent> = self
+            .events
+            .iter()
+            .filter(|e| e.kind == RecordingEventKind::Marker)
+            .collect();
+
+        markers
+            .iter()
+            .enumerate()
+            .map(|(i, marker)| RecordingChapter {
+                index: i + 1,
+                time: marker.time,
+                end_time: markers.get(i + 1).map(|m| m.time).unwrap_or(duration),
+                label: marker.data.clone(),
+            })
+            .collect()
+    }
+
+    /// 生成回放帧序列
+    ///
+    /// 每帧包含距上一帧的等待时间和输出数据。
+    ///
+    /// # 参数
+    /// - `speed`: 回放速度倍率（小于等于 0 时按 1 处理）
+    /// - `idle_limit`: 最长空闲等待（秒），超过时压缩为该值
+    pub fn replay_frames(&self, speed: f64, idle_limit: Option<f64>) -> Vec<(Duration, &str)> {
+        let speed = if speed > 0.0 { speed } else { 1.0 };
+        let mut last_time = 0.0;
+
+        self.events
+            .iter()
+            .filter(|e| e.kind == RecordingEventKind::Output)
+            .map(|event| {
+                let mut gap = (event.time - last_time).max(0.0);
+                if let Some(limit) = idle_limit {
+                    gap = gap.min(limit);
+                }
+                last_time = event.time;
+                (Duration::from_secs_f64(gap / speed), event.data.as_str())
+            })
+            .collect()
+    }
+}
+
+/// 纯文本记录渲染器
+///
+/// 维护当前行和光标位置，模拟回车覆盖和退格。
+#[derive(Default)]
+struct TranscriptRenderer {
+    lines: Vec<String>,
+    current: Vec<char>,
+    cursor: usize,
+}
+
+impl TranscriptRenderer {
+    fn feed(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\n' => self.newline(),
+                '\r' => self.cursor = 0,
+                '\x08' => self.cursor = self.cursor.saturating_sub(1),
+                '\t' => self.put(c),
+                c if c.is_control() => {}
+                c => self.put(c),
+            }
+        }
+    }
+
+    fn put(&mut self, c: char) {
+        if self.cursor < self.current.len() {
+            self.current[self.cursor] = c;
+        } else {
+            self.current.push(c);
+        }
+        self.cursor += 1;
+    }
+
+    fn newline(&mut self) {
+        let line: String = self.current.iter().collect();
+        self.lines.push(line.trim_end().to_string());
+        self.current.clear();
+        self.cursor = 0;
+    }
+
+    fn heading(&mut self, title: &str) {
+        if !self.current.is_empty() {
+            self.newline();
+        }
+        if self.lines.last().is_some_and(|l| !l.is_empty()) {
+            self.lines.push(String::new());
+        }
+        self.lines.push(title.to_string());
+    }
+
+    fn finish(mut self) -> String {
+        if !self.current.is_empty() {
+            self.newline();
+        }
+        let mut out = self.lines.join("\n");
+        out.push('\n');
+        out
+    }
+}
+
+/// 去除 ANSI 控制序列（CSI、OSC、DCS 等）
+pub fn strip_ansi(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: ESC [ 参数 中间字节 结束字节(0x40-0x7E)
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC / DCS / SOS / PM / APC: 以 BEL 或 ST (ESC \) 结束
+                Some(']') | Some('P') | Some('X') | Some('^') | Some('_') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // 字符集选择: ESC ( X 等
+                Some('(') | Some(')') | Some('*') | Some('+') => {
+                    chars.next();
+                }
+                _ => {}
+            },
+            '\u{9b}' => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// 解码 UTF-8，末尾不完整的字节保留到下次
+fn decode_utf8(pending: &mut Vec<u8>, data: &[u8]) -> String {
+    pending.extend_from_slice(data);
+    let mut out = String::new();
+
+    loop {
+        match std::str::from_utf8(pending) {
+            Ok(s) => {
+                out.push_str(s);
+                pending.clear();
+                break;
+            }
+            Err(e) => {
+                let valid = e.valid_up_to();
+                out.push_str(std::str::from_utf8(&pending[..valid]).unwrap_or_default());
+                match e.error_len() {
+                    // 无效字节：替换后继续
+                    Some(len) => {
+                        out.push(char::REPLACEMENT_CHARACTER);
+                        pending.drain(..valid + len);
+                    }
+                    // 不完整的多字节序列：等待更多数据
+                    None => {
+                        pending.drain(..valid);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// 格式化时间为 mm:ss
+fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    format!("{:02}:{:02}", total / 60, total % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;32mok\x1b[0m"), "ok");
+        assert_eq!(
+            strip_ansi("\x1b]0;title\x07a\x1b]7;file:///tmp\x1b\\b"),
+            "ab"
+        );
+        assert_eq!(strip_ansi("\x1b(Bplain\x1b=x"), "plainx");
+    }
+
+    #[test]
+    fn test_decode_utf8_split_sequence() {
+        let mut pending = Vec::new();
+        let bytes = "你好".as_bytes();
+        assert_eq!(decode_utf8(&mut pending, &bytes[..2]), "");
+        assert_eq!(decode_utf8(&mut pending, &bytes[2..]), "你好");
+        assert!(pending.is_empty());
+        assert_eq!(decode_utf8(&mut pending, b"a\xffb"), "a\u{fffd}b");
+    }
+
+    #[test]
+    fn test_record_and_load_with_chapters() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("block-1.cast");
+
+        let mut recorder = TerminalRecorder::create("block-1", &path, 80, 24).unwrap();
+        recorder.record_output(b"\x1b]133;A\x07$ ").unwrap();
+        recorder.record_input(b"ls -la\r").unwrap();
+        recorder.record_output(b"\x1b]133;C\x07").unwrap();
+        recorder.record_output(b"file.txt\r\n").unwrap();
+        recorder.record_resize(100, 30).unwrap();
+        recorder.record_output(b"\x1b]133;D;0\x07").unwrap();
+        let path = recorder.finish().unwrap();
+
+        let recording = Recording::load(&path).unwrap();
+        assert_eq!(recording.header.width, 80);
+        assert_eq!(recording.header.height, 24);
+        assert!(recording
+            .events
+            .iter()
+            .any(|e| e.kind == RecordingEventKind::Resize && e.data == "100x30"));
+
+        let chapters = recording.chapters();
+        assert_eq!(chapters.len(), 1);
+        assert_eq!(chapters[0].label, "ls -la");
+
+        let cast = recording.to_asciicast(false);
+        assert!(cast.starts_with("{\"version\":2"));
+        assert!(!cast.contains("\"i\""));
+        assert!(recording.to_asciicast(true).contains("\"i\""));
+    }
+
+    #[test]
+    fn test_transcript_renders_overwrites_and_headings() {
+        let content = concat!(
+            "{\"version\":2,\"width\":80,\"height\":24}\n",
+            "[0.1, \"o\", \"$ \"]\n",
+            "[0.5, \"m\", \"make\"]\n",
+            "[0.6, \"o\", \"\\u001b[32m10%\\r50%\\r100%\\u001b[0m\\r\\n\"]\n",
+            "[0.7, \"o\", \"abc\\b\\bX\\r\\n\"]\n",
+        );
+        let recording = Recording::parse(content).unwrap();
+
+        assert_eq!(
+            recording.to_transcript(),
+            "$\n\n── [00:00] make ──\n100%\naXc\n"
+        );
+        assert_eq!(recording.chapters()[0].end_time, 0.7);
+    }
+
+    #[test]
+    fn test_replay_frames_speed_and_idle_limit() {
+        let content = concat!(
+            "{\"version\":2,\"width\":80,\"height\":24}\n",
+            "[1.0, \"o\", \"a\"]\n",
+            "[1.5, \"i\", \"x\"]\n",
+            "[11.0, \"o\", \"b\"]\n",
+        );
+        let recording = Recording::parse(content).unwrap();
+
+        let frames = recording.replay_frames(2.0, Some(2.0));
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0], (Duration::from_millis(500), "a"));
+        assert_eq!(frames[1], (Duration::from_secs(1), "b"));
+    }
+
+    #[test]
+    fn test_parse_rejects_other_versions() {
+        assert!(Recording::parse("{\"version\":1,\"width\":80,\"height\":24}").is_err());
+        assert!(Recording::parse("").is_err());
+    }
+}
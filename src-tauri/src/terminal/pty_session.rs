@@ -8,6 +8,7 @@
 //! - 处理 PTY 输入写入
 //! - 监控进程退出状态
 //! - 保存输出历史（循环缓冲区）
+//! - 可选地将输出写入块文件录制
 //!
 //! ## 架构说明
 //! PTY 在后端预创建，使用默认大小 (24x80)。前端连接后通过 resize 同步实际大小。
@@ -25,6 +26,7 @@ use tokio::sync::RwLock;
 
 use super::error::TerminalError;
 use super::events::{event_names, SessionStatus, TerminalOutputEvent, TerminalStatusEvent};
+use super::persistence::BlockFile;
 
 /// 默认终端行数
 pub const DEFAULT_ROWS: u16 = 24;
@@ -85,6 +87,8 @@ pub struct PtySession {
     shutdown_flag: Arc<AtomicBool>,
     /// 输出历史缓冲区
     output_buffer: Arc<Mutex<CircularBuffer>>,
+    /// 录制目标块文件
+    recording_target: Arc<Mutex<Option<Arc<BlockFile>>>>,
 }
 
 impl PtySession {
@@ -231,6 +235,10 @@ impl PtySession {
         let output_buffer = Arc::new(Mutex::new(CircularBuffer::new(OUTPUT_BUFFER_MAX_SIZE)));
         let output_buffer_clone = output_buffer.clone();
 
+        // 录制目标（会话管理器设置后生效）
+        let recording_target: Arc<Mutex<Option<Arc<BlockFile>>>> = Arc::new(Mutex::new(None));
+        let recording_target_clone = recording_target.clone();
+
         // 获取当前 tokio runtime handle（在主线程中获取）
         let runtime_handle = tokio::runtime::Handle::current();
 
@@ -272,6 +280,13 @@ impl PtySession {
                         // 保存到输出缓冲区
                         output_buffer_clone.lock().append(output_data);
 
+                        // 录制输出
+                        if let Some(bf) = recording_target_clone.lock().as_ref() {
+                            if let Err(e) = bf.record_output(output_data) {
+                                tracing::warn!("[终端] 会话 {} 录制输出失败: {}", id_clone, e);
+                            }
+                        }
+
                         // 发送输出事件
                         let data = BASE64.encode(output_data);
                         let _ = app_handle.emit(
@@ -317,6 +332,7 @@ impl PtySession {
             status,
             shutdown_flag,
             output_buffer,
+            recording_target,
         })
     }
 
@@ -325,6 +341,13 @@ impl PtySession {
         &self.id
     }
 
+    /// 设置录制目标块文件
+    ///
+    /// 设置后 PTY 输出会交给块文件的录制器（仅在录制开启时写入）。
+    pub fn set_recording_target(&self, block_file: Arc<BlockFile>) {
+        *self.recording_target.lock() = Some(block_file);
+    }
+
     /// 写入数据到 PTY
     pub fn write(&self, data: &[u8]) -> Result<(), TerminalError> {
         let mut writer = self.writer.lock();
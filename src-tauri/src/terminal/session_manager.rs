@@ -8,6 +8,8 @@
 //! - 集成 BlockFile 进行输出持久化
 //! - 集成 SessionMetadataStore 进行元数据存储
 //! - 支持会话状态生命周期管理
+//! - 会话录制、导出与回放
+//! - 命令历史检索与重新执行
 //!
 //! ## Requirements
 //! - 3.1: 终端会话创建时创建对应的 Block_File
@@ -19,10 +21,12 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use tauri::Emitter;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
@@ -30,10 +34,16 @@ use crate::database::DbConnection;
 
 use super::block_controller::ControllerRegistry;
 use super::error::TerminalError;
-use super::events::SessionStatus;
-use super::persistence::{BlockFile, SessionMetadataStore, SessionRecord};
+use super::events::{event_names, SessionStatus, TerminalOutputEvent, TerminalStatusEvent};
+use super::persistence::{
+    BlockFile, CommandHistoryEntry, CommandHistoryHit, CommandHistoryQuery, CommandHistoryStore,
+    CommandHistoryTracker, Recording, RecordingChapter, SessionMetadataStore, SessionRecord,
+};
 use super::pty_session::{PtySession, DEFAULT_COLS, DEFAULT_ROWS};
 
+/// 录制回放时的最长空闲等待（秒）
+const REPLAY_IDLE_LIMIT_SECS: f64 = 2.0;
+
 /// 会话元数据（用于前端展示）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionMetadata {
@@ -112,6 +122,8 @@ pub struct TerminalSessionManager {
     controller_registry: Arc<ControllerRegistry>,
     /// 会话元数据存储
     session_store: Option<Arc<SessionMetadataStore>>,
+    /// 命令历史存储
+    command_history: Option<Arc<CommandHistoryStore>>,
     /// 块文件基础目录
     block_file_base_dir: PathBuf,
     /// Tauri 应用句柄
@@ -136,6 +148,7 @@ impl TerminalSessionManager {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             controller_registry: Arc::new(ControllerRegistry::new()),
             session_store: None,
+            command_history: None,
             block_file_base_dir,
             app_handle,
         }
@@ -174,6 +187,42 @@ impl TerminalSessionManager {
         self.session_store.as_ref()
     }
 
+    /// 启用命令历史
+    ///
+    /// 启用后新建和恢复的会话会把已完成的命令写入命令历史。
+    ///
+    /// # 参数
+    /// - `db`: 数据库连接
+    pub fn enable_command_history(&mut self, db: DbConnection) -> Result<(), TerminalError> {
+        let store = CommandHistoryStore::new(db);
+        store.init_tables()?;
+        self.command_history = Some(Arc::new(store));
+
+        tracing::info!("[终端] 命令历史已启用");
+        Ok(())
+    }
+
+    /// 获取命令历史存储
+    pub fn command_history(&self) -> Option<&Arc<CommandHistoryStore>> {
+        self.command_history.as_ref()
+    }
+
+    /// 为块文件挂载命令历史跟踪器（未启用命令历史时忽略）
+    fn attach_command_history(
+        &self,
+        block_file: &BlockFile,
+        session_id: &str,
+        connection: Option<&str>,
+    ) {
+        if let Some(store) = &self.command_history {
+            block_file.enable_command_history(CommandHistoryTracker::for_connection(
+                store.clone(),
+                session_id,
+                connection,
+            ));
+        }
+    }
+
     /// 创建新的终端会话
     ///
     /// 使用默认大小 (24x80) 创建 PTY 会话。
@@ -239,6 +288,7 @@ impl TerminalSessionManager {
 
         // 创建块文件
         let block_file = BlockFile::with_default_size(&block_id, &self.block_file_base_dir)?;
+        self.attach_command_history(&block_file, &session_id, None);
         let block_file = Arc::new(block_file);
 
         // 创建旧版 PTY 会话（兼容模式）
@@ -249,6 +299,7 @@ impl TerminalSessionManager {
             cwd,
             self.app_handle.clone(),
         )?;
+        pty_session.set_recording_target(block_file.clone());
 
         // 创建会话元数据
         let metadata = SessionMetadata {
@@ -338,6 +389,11 @@ impl TerminalSessionManager {
         // 同时写入块文件（用于持久化）
         session.block_file.append_data(data)?;
 
+        // 录制输入（未录制时忽略）
+        if let Err(e) = session.block_file.record_input(data) {
+            tracing::warn!("[终端] 会话 {} 录制输入失败: {}", session_id, e);
+        }
+
         Ok(())
     }
 
@@ -367,6 +423,10 @@ impl TerminalSessionManager {
         session.metadata.rows = rows;
         session.metadata.cols = cols;
 
+        if let Err(e) = session.block_file.record_resize(cols, rows) {
+            tracing::warn!("[终端] 会话 {} 录制大小调整失败: {}", session_id, e);
+        }
+
         tracing::debug!("[终端] 会话 {} 调整大小为 {}x{}", session_id, cols, rows);
 
         Ok(())
@@ -382,6 +442,11 @@ impl TerminalSessionManager {
         let mut sessions = self.sessions.write().await;
 
         if let Some(mut session) = sessions.remove(session_id) {
+            // 结束录制
+            if let Err(e) = session.block_file.stop_recording() {
+                tracing::warn!("[终端] 会话 {} 结束录制失败: {}", session_id, e);
+            }
+
             // 关闭旧版 PTY 会话
             if let Some(pty) = session.legacy_pty.take() {
                 pty.close().await?;
@@ -446,6 +511,7 @@ impl TerminalSessionManager {
 
         // 创建块文件引用
         let block_file = BlockFile::with_default_size(&record.block_id, &self.block_file_base_dir)?;
+        self.attach_command_history(&block_file, session_id, record.connection.as_deref());
         let block_file = Arc::new(block_file);
 
         // 读取历史数据
@@ -456,6 +522,7 @@ impl TerminalSessionManager {
         let cols = DEFAULT_COLS;
         let pty_session =
             PtySession::with_size(session_id.to_string(), rows, cols, self.app_handle.clone())?;
+        pty_session.set_recording_target(block_file.clone());
 
         // 创建会话元数据
         let metadata = SessionMetadata::from_record(&record, rows, cols);
@@ -478,6 +545,229 @@ impl TerminalSessionManager {
         Ok(metadata)
     }
 
+    /// 开始录制会话
+    ///
+    /// # 参数
+    /// - `session_id`: 会话 ID
+    ///
+    /// # 返回
+    /// 录制文件路径
+    pub async fn start_recording(&self, session_id: &str) -> Result<PathBuf, TerminalError> {
+        let sessions = self.sessions.read().await;
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        let path = session
+            .block_file
+            .start_recording(session.metadata.cols, session.metadata.rows)?;
+        tracing::info!("[终端] 会话 {} 开始录制: {:?}", session_id, path);
+        Ok(path)
+    }
+
+    /// 停止录制会话
+    ///
+    /// # 返回
+    /// 录制文件路径（未在录制时为 None）
+    pub async fn stop_recording(&self, session_id: &str) -> Result<Option<PathBuf>, TerminalError> {
+        let sessions = self.sessions.read().await;
+        let session = sessions
+            .get(session_id)
+            .ok_or_else(|| TerminalError::SessionNotFound(session_id.to_string()))?;
+
+        let path = session.block_file.stop_recording()?;
+        tracing::info!("[终端] 会话 {} 停止录制: {:?}", session_id, path);
+        Ok(path)
+    }
+
+    /// 检查会话是否正在录制
+    pub async fn is_recording(&self, session_id: &str) -> bool {
+        let sessions = self.sessions.read().await;
+        sessions
+            .get(session_id)
+            .is_some_and(|s| s.block_file.is_recording())
+    }
+
+    /// 加载会话录制（会话关闭后仍可读取）
+    ///
+    /// # 参数
+    /// - `session_id`: 会话 ID
+    pub async fn load_recording(&self, session_id: &str) -> Result<Recording, TerminalError> {
+        let path = {
+            let sessions = self.sessions.read().await;
+            match sessions.get(session_id) {
+                Some(session) => session.block_file.recording_path(),
+                None => self
+                    .block_file_base_dir
+                    .join(format!("{}.cast", session_id)),
+            }
+        };
+
+        if !path.exists() {
+            return Err(TerminalError::RecordingError(format!(
+                "会话 {} 没有录制",
+                session_id
+            )));
+        }
+        Recording::load(&path)
+    }
+
+    /// 导出会话录制为 asciicast v2
+    ///
+    /// # 参数
+    /// - `session_id`: 会话 ID
+    /// - `include_input`: 是否包含输入事件
+    pub async fn export_recording_asciicast(
+        &self,
+        session_id: &str,
+        include_input: bool,
+    ) -> Result<String, TerminalError> {
+        Ok(self
+            .load_recording(session_id)
+            .await?
+            .to_asciicast(include_input))
+    }
+
+    /// 导出会话录制为纯文本记录（去除 ANSI 控制序列）
+    pub async fn export_recording_transcript(
+        &self,
+        session_id: &str,
+    ) -> Result<String, TerminalError> {
+        Ok(self.load_recording(session_id).await?.to_transcript())
+    }
+
+    /// 获取会话录制的章节列表
+    pub async fn list_recording_chapters(
+        &self,
+        session_id: &str,
+    ) -> Result<Vec<RecordingChapter>, TerminalError> {
+        Ok(self.load_recording(session_id).await?.chapters())
+    }
+
+    /// 将会话录制回放到新的块中
+    ///
+    /// 按录制时间间隔推送输出事件到新块，并写入新块的块文件。
+    /// 回放块只用于展示，不关联任何进程。
+    ///
+    /// # 参数
+    /// - `session_id`: 会话 ID
+    /// - `speed`: 回放速度倍率（默认 1.0）
+    ///
+    /// # 返回
+    /// 新块 ID
+    pub async fn replay_recording(
+        &self,
+        session_id: &str,
+        speed: Option<f64>,
+    ) -> Result<String, TerminalError> {
+        let recording = self.load_recording(session_id).await?;
+        let block_id = Uuid::new_v4().to_string();
+        let block_file = BlockFile::with_default_size(&block_id, &self.block_file_base_dir)?;
+        let app_handle = self.app_handle.clone();
+        let replay_id = block_id.clone();
+
+        tracing::info!(
+            "[终端] 回放会话 {} 的录制到块 {} ({} 个事件)",
+            session_id,
+            block_id,
+            recording.events.len()
+        );
+
+        tokio::spawn(async move {
+            // 等待前端挂载新块
+            tokio::time::sleep(Duration::from_millis(200)).await;
+
+            for (delay, data) in
+                recording.replay_frames(speed.unwrap_or(1.0), Some(REPLAY_IDLE_LIMIT_SECS))
+            {
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+                if let Err(e) = block_file.append_data(data.as_bytes()) {
+                    tracing::warn!("[终端] 回放块 {} 写入块文件失败: {}", replay_id, e);
+                }
+                let _ = app_handle.emit(
+                    event_names::TERMINAL_OUTPUT,
+                    TerminalOutputEvent {
+                        session_id: replay_id.clone(),
+                        data: BASE64.encode(data),
+                    },
+                );
+            }
+
+            let _ = app_handle.emit(
+                event_names::TERMINAL_STATUS,
+                TerminalStatusEvent {
+                    session_id: replay_id.clone(),
+                    status: SessionStatus::Done,
+                    exit_code: None,
+                    error: None,
+                },
+            );
+            tracing::debug!("[终端] 回放块 {} 完成", replay_id);
+        });
+
+        Ok(block_id)
+    }
+
+    /// 搜索命令历史
+    ///
+    /// # 参数
+    /// - `query`: 查询条件
+    pub fn search_command_history(
+        &self,
+        query: &CommandHistoryQuery,
+    ) -> Result<Vec<CommandHistoryHit>, TerminalError> {
+        self.command_history_store()?.search(query)
+    }
+
+    /// 删除命令历史记录
+    ///
+    /// # 参数
+    /// - `entry_id`: 记录 ID
+    pub fn delete_command_history(&self, entry_id: i64) -> Result<(), TerminalError> {
+        self.command_history_store()?.delete(entry_id)
+    }
+
+    /// 在会话中重新执行历史命令
+    ///
+    /// # 参数
+    /// - `entry_id`: 命令历史记录 ID
+    /// - `session_id`: 目标会话 ID
+    ///
+    /// # 返回
+    /// 被执行的命令记录
+    pub async fn rerun_command(
+        &self,
+        entry_id: i64,
+        session_id: &str,
+    ) -> Result<CommandHistoryEntry, TerminalError> {
+        let entry = self
+            .command_history_store()?
+            .get(entry_id)?
+            .ok_or_else(|| {
+                TerminalError::DatabaseError(format!("命令历史记录不存在: {}", entry_id))
+            })?;
+
+        tracing::info!(
+            "[终端] 在会话 {} 中重新执行命令 #{}: {}",
+            session_id,
+            entry_id,
+            entry.command
+        );
+        self.write_to_session(session_id, format!("{}\r", entry.command).as_bytes())
+            .await?;
+
+        Ok(entry)
+    }
+
+    /// 获取命令历史存储（未启用时返回错误）
+    fn command_history_store(&self) -> Result<&Arc<CommandHistoryStore>, TerminalError> {
+        self.command_history
+            .as_ref()
+            .ok_or_else(|| TerminalError::DatabaseError("命令历史未启用".to_string()))
+    }
+
     /// 加载所有已保存的会话（应用启动时调用）
     ///
     /// _Requirements: 3.5_
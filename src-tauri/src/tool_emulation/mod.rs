@@ -0,0 +1,629 @@
+//! 工具调用模拟
+//!
+//! 部分模型不支持原生函数调用，或会直接拒绝带 `tools` 的请求。对 `tool_emulation.models`
+//! 命中的模型，代理：
+//! - 把工具定义渲染进 system 提示词（XML 或 JSON 格式），并移除 tools / tool_choice
+//! - 把历史中的工具调用与工具结果改写为同样格式的文本
+//! - 从模型输出（含流式分片）中解析工具调用，还原为 OpenAI `tool_calls` 或
+//!   Anthropic `tool_use` 块
+
+mod parser;
+pub mod stream;
+
+pub use parser::{merge_text, EmulatedToolCall, Segment, ToolCallParser};
+
+use crate::config::{ToolCallFormat, ToolEmulationConfig};
+use crate::models::anthropic::AnthropicMessagesRequest;
+use crate::models::openai::{ChatCompletionRequest, ChatMessage, MessageContent, Tool};
+use crate::models::provider_pool_model::pattern_matches;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// 模型是否需要代理模拟工具调用
+pub fn requires_emulation(config: &ToolEmulationConfig, model: &str) -> bool {
+    config
+        .models
+        .iter()
+        .any(|pattern| pattern_matches(pattern, model))
+}
+
+/// 提示词中的工具定义
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+    /// 参数 JSON Schema
+    pub parameters: Value,
+}
+
+/// 工具选择约束
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolChoice {
+    Auto,
+    None,
+    /// 至少调用一个工具（OpenAI `required` / Anthropic `any`）
+    Required,
+    /// 必须调用指定工具
+    Named(String),
+}
+
+impl ToolChoice {
+    fn from_openai(value: Option<&Value>) -> Self {
+        match value {
+            Some(Value::String(s)) if s == "none" => Self::None,
+            Some(Value::String(s)) if s == "required" => Self::Required,
+            Some(Value::Object(obj)) => obj
+                .get("function")
+                .and_then(|f| f.get("name"))
+                .and_then(|n| n.as_str())
+                .map(|n| Self::Named(n.to_string()))
+                .unwrap_or(Self::Auto),
+            _ => Self::Auto,
+        }
+    }
+
+    fn from_anthropic(value: Option<&Value>) -> Self {
+        let Some(choice) = value else {
+            return Self::Auto;
+        };
+        match choice.get("type").and_then(|t| t.as_str()) {
+            Some("none") => Self::None,
+            Some("any") => Self::Required,
+            Some("tool") => choice
+                .get("name")
+                .and_then(|n| n.as_str())
+                .map(|n| Self::Named(n.to_string()))
+                .unwrap_or(Self::Required),
+            _ => Self::Auto,
+        }
+    }
+}
+
+/// 渲染注入 system 提示词的工具说明
+pub fn render_tools_prompt(
+    tools: &[ToolSpec],
+    format: ToolCallFormat,
+    choice: &ToolChoice,
+) -> String {
+    let mut prompt = String::from(
+        "You can call tools to help answer the user. To call a tool, output a block in exactly this format:\n\n",
+    );
+    prompt.push_str(&render_call_example(format));
+    prompt.push_str(
+        "\n\nThe arguments must be a single JSON object that matches the tool's parameters. \
+         You may write text before the tool calls and emit several blocks to call several tools. \
+         After the last tool call, stop and wait: the results will be provided in <tool_result> blocks.",
+    );
+    match choice {
+        ToolChoice::Required => {
+            prompt.push_str("\nYou must call at least one tool in your response.");
+        }
+        ToolChoice::Named(name) => {
+            prompt.push_str(&format!(
+                "\nYou must call the tool `{name}` in your response."
+            ));
+        }
+        ToolChoice::Auto | ToolChoice::None => {}
+    }
+
+    prompt.push_str("\n\nAvailable tools:\n");
+    match format {
+        ToolCallFormat::Xml => {
+            prompt.push_str("<tools>\n");
+            for tool in tools {
+                prompt.push_str(&tool_to_xml(tool));
+                prompt.push('\n');
+            }
+            prompt.push_str("</tools>");
+        }
+        ToolCallFormat::Json => {
+            let defs: Vec<Value> = tools
+                .iter()
+                .map(|t| json!({"name": t.name, "description": t.description, "parameters": t.parameters}))
+                .collect();
+            prompt.push_str("```json\n");
+            prompt.push_str(&serde_json::to_string_pretty(&defs).unwrap_or_else(|_| "[]".into()));
+            prompt.push_str("\n```");
+        }
+    }
+    prompt
+}
+
+fn render_call_example(format: ToolCallFormat) -> String {
+    render_call(format, "TOOL_NAME", r#"{"param": "value"}"#)
+}
+
+/// 按模拟格式渲染一次工具调用（用于提示词示例和历史消息）
+pub fn render_call(format: ToolCallFormat, name: &str, arguments: &str) -> String {
+    match format {
+        ToolCallFormat::Xml => format!(
+            "<tool_call>\n<name>{}</name>\n<arguments>{}</arguments>\n</tool_call>",
+            escape_xml(name),
+            arguments
+        ),
+        ToolCallFormat::Json => {
+            let arguments: Value = serde_json::from_str(arguments).unwrap_or_else(|_| json!({}));
+            format!(
+                "<tool_call>{{\"name\": {}, \"arguments\": {}}}</tool_call>",
+                Value::from(name),
+                arguments
+            )
+        }
+    }
+}
+
+fn render_result(tool_call_id: &str, name: Option<&str>, content: &str) -> String {
+    match name {
+        Some(name) => format!(
+            "<tool_result id=\"{}\" name=\"{}\">\n{}\n</tool_result>",
+            escape_xml(tool_call_id),
+            escape_xml(name),
+            content
+        ),
+        None => format!(
+            "<tool_result id=\"{}\">\n{}\n</tool_result>",
+            escape_xml(tool_call_id),
+            content
+        ),
+    }
+}
+
+/// 单个工具定义转换为 XML（与 Agent 工具提示词的格式保持一致）
+fn tool_to_xml(tool: &ToolSpec) -> String {
+    let mut xml = format!("<tool name=\"{}\">\n", escape_xml(&tool.name));
+    xml.push_str(&format!(
+        "  <description>{}</description>\n",
+        escape_xml(&tool.description)
+    ));
+    xml.push_str("  <parameters>\n");
+
+    let required: Vec<&str> = tool.parameters["required"]
+        .as_array()
+        .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+    if let Some(properties) = tool.parameters["properties"].as_object() {
+        for (name, prop) in properties {
+            let prop_type = prop["type"].as_str().unwrap_or("any");
+            xml.push_str(&format!(
+                "    <parameter name=\"{}\" type=\"{}\"{}>\n",
+                escape_xml(name),
+                escape_xml(prop_type),
+                if required.contains(&name.as_str()) {
+                    " required=\"true\""
+                } else {
+                    ""
+                }
+            ));
+            if let Some(description) = prop["description"].as_str() {
+                xml.push_str(&format!(
+                    "      <description>{}</description>\n",
+                    escape_xml(description)
+                ));
+            }
+            if let Some(values) = prop["enum"].as_array() {
+                xml.push_str("      <enum>\n");
+                for value in values {
+                    xml.push_str(&format!(
+                        "        <value>{}</value>\n",
+                        escape_xml(&value.to_string())
+                    ));
+                }
+                xml.push_str("      </enum>\n");
+            }
+            // 嵌套结构无法用属性列表表达，直接附上子 schema
+            if matches!(prop_type, "object" | "array") {
+                xml.push_str(&format!(
+                    "      <schema>{}</schema>\n",
+                    escape_xml(&prop.to_string())
+                ));
+            }
+            xml.push_str("    </parameter>\n");
+        }
+    }
+
+    xml.push_str("  </parameters>\n</tool>");
+    xml
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// 改写 OpenAI 请求：工具写入提示词，历史中的工具调用 / 结果改为文本
+pub fn emulate_openai_request(request: &mut ChatCompletionRequest, format: ToolCallFormat) {
+    let tools: Vec<ToolSpec> = request
+        .tools
+        .take()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|tool| match tool {
+            Tool::Function { function } => Some(ToolSpec {
+                name: function.name,
+                description: function.description.unwrap_or_default(),
+                parameters: function
+                    .parameters
+                    .unwrap_or_else(|| json!({"type": "object", "properties": {}})),
+            }),
+            _ => None,
+        })
+        .collect();
+    let choice = ToolChoice::from_openai(request.tool_choice.take().as_ref());
+    request.parallel_tool_calls = None;
+
+    let mut call_names: HashMap<String, String> = HashMap::new();
+    let mut messages: Vec<ChatMessage> = Vec::with_capacity(request.messages.len());
+    let mut after_tool_result = false;
+    for mut message in std::mem::take(&mut request.messages) {
+        let is_tool_result = message.role == "tool";
+        if let Some(calls) = message.tool_calls.take() {
+            let mut text = message.get_content_text();
+            for call in calls {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&render_call(
+                    format,
+                    &call.function.name,
+                    &call.function.arguments,
+                ));
+                call_names.insert(call.id, call.function.name);
+            }
+            message.content = Some(MessageContent::Text(text));
+            messages.push(message);
+        } else if message.role == "tool" {
+            let id = message.tool_call_id.take().unwrap_or_default();
+            let result = render_result(
+                &id,
+                call_names.get(&id).map(String::as_str),
+                &message.get_content_text(),
+            );
+            // 连续的工具结果合并为一条 user 消息
+            match messages.last_mut() {
+                Some(prev) if after_tool_result => {
+                    let merged = format!("{}\n{}", prev.get_content_text(), result);
+                    prev.content = Some(MessageContent::Text(merged));
+                }
+                _ => messages.push(ChatMessage {
+                    role: "user".to_string(),
+                    content: Some(MessageContent::Text(result)),
+                    tool_calls: None,
+                    tool_call_id: None,
+                    reasoning_content: None,
+                }),
+            }
+        } else {
+            messages.push(message);
+        }
+        after_tool_result = is_tool_result;
+    }
+    request.messages = messages;
+
+    if tools.is_empty() || choice == ToolChoice::None {
+        return;
+    }
+    let prompt = render_tools_prompt(&tools, format, &choice);
+    if let Some(system) = request.messages.iter_mut().find(|m| m.role == "system") {
+        let existing = system.get_content_text();
+        system.content = Some(MessageContent::Text(if existing.is_empty() {
+            prompt
+        } else {
+            format!("{existing}\n\n{prompt}")
+        }));
+    } else {
+        request.messages.insert(
+            0,
+            ChatMessage {
+                role: "system".to_string(),
+                content: Some(MessageContent::Text(prompt)),
+                tool_calls: None,
+                tool_call_id: None,
+                reasoning_content: None,
+            },
+        );
+    }
+}
+
+/// 改写 Anthropic 请求：工具写入 system，历史中的 tool_use / tool_result 块改为文本
+pub fn emulate_anthropic_request(request: &mut AnthropicMessagesRequest, format: ToolCallFormat) {
+    let tools: Vec<ToolSpec> = request
+        .tools
+        .take()
+        .unwrap_or_default()
+        .into_iter()
+        // 服务端工具（web_search 等）没有 input_schema，无法模拟
+        .filter(|tool| tool.input_schema.is_some())
+        .map(|tool| ToolSpec {
+            name: tool.name,
+            description: tool.description.unwrap_or_default(),
+            parameters: tool.input_schema.unwrap_or_default(),
+        })
+        .collect();
+    let choice = ToolChoice::from_anthropic(request.tool_choice.take().as_ref());
+
+    let mut call_names: HashMap<String, String> = HashMap::new();
+    for message in &mut request.messages {
+        let Value::Array(blocks) = &mut message.content else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            let text = match block["type"].as_str() {
+                Some("tool_use") => {
+                    let id = block["id"].as_str().unwrap_or_default().to_string();
+                    let name = block["name"].as_str().unwrap_or_default().to_string();
+                    let text = render_call(format, &name, &block["input"].to_string());
+                    call_names.insert(id, name);
+                    text
+                }
+                Some("tool_result") => {
+                    let id = block["tool_use_id"].as_str().unwrap_or_default();
+                    let mut content = tool_result_text(&block["content"]);
+                    if block["is_error"].as_bool() == Some(true) {
+                        content = format!("Error: {content}");
+                    }
+                    render_result(id, call_names.get(id).map(String::as_str), &content)
+                }
+                _ => continue,
+            };
+            *block = json!({"type": "text", "text": text});
+        }
+    }
+
+    if tools.is_empty() || choice == ToolChoice::None {
+        return;
+    }
+    let prompt = render_tools_prompt(&tools, format, &choice);
+    request.system = Some(match request.system.take() {
+        Some(Value::String(existing)) if !existing.is_empty() => {
+            Value::String(format!("{existing}\n\n{prompt}"))
+        }
+        Some(Value::Array(mut blocks)) => {
+            blocks.push(json!({"type": "text", "text": prompt}));
+            Value::Array(blocks)
+        }
+        _ => Value::String(prompt),
+    });
+}
+
+/// tool_result.content 可以是字符串或内容块数组
+fn tool_result_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b["text"].as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// 生成 OpenAI 工具调用 ID
+pub fn openai_call_id() -> String {
+    format!("call_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// 生成 Anthropic tool_use ID
+pub fn anthropic_call_id() -> String {
+    format!("toolu_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// 改写非流式 OpenAI 响应：从 message.content 中解析工具调用
+///
+/// 返回解析出的工具调用数量。
+pub fn rewrite_openai_response(json: &mut Value) -> usize {
+    let mut total = 0;
+    let Some(choices) = json.get_mut("choices").and_then(|c| c.as_array_mut()) else {
+        return 0;
+    };
+    for choice in choices {
+        let Some(content) = choice["message"]["content"].as_str() else {
+            continue;
+        };
+        let mut text = String::new();
+        let mut calls = Vec::new();
+        for segment in ToolCallParser::parse_all(content) {
+            match segment {
+                Segment::Text(t) => text.push_str(&t),
+                Segment::ToolCall(call) => calls.push(json!({
+                    "id": openai_call_id(),
+                    "type": "function",
+                    "function": {"name": call.name, "arguments": call.arguments}
+                })),
+            }
+        }
+        if calls.is_empty() {
+            continue;
+        }
+        total += calls.len();
+        let text = text.trim();
+        choice["message"]["content"] = if text.is_empty() {
+            Value::Null
+        } else {
+            Value::String(text.to_string())
+        };
+        choice["message"]["tool_calls"] = Value::Array(calls);
+        choice["finish_reason"] = json!("tool_calls");
+    }
+    total
+}
+
+/// 改写非流式 Anthropic 响应：把 text 块中的工具调用拆分为 tool_use 块
+///
+/// 返回解析出的工具调用数量。
+pub fn rewrite_anthropic_response(json: &mut Value) -> usize {
+    let Some(blocks) = json.get_mut("content").and_then(|c| c.as_array_mut()) else {
+        return 0;
+    };
+    let mut total = 0;
+    let mut rewritten = Vec::with_capacity(blocks.len());
+    for block in blocks.drain(..) {
+        let Some(text) = block["text"].as_str().filter(|_| block["type"] == "text") else {
+            rewritten.push(block);
+            continue;
+        };
+        for segment in ToolCallParser::parse_all(text) {
+            match segment {
+                Segment::Text(t) if t.trim().is_empty() => {}
+                Segment::Text(t) => rewritten.push(json!({"type": "text", "text": t})),
+                Segment::ToolCall(call) => {
+                    total += 1;
+                    rewritten.push(json!({
+                        "type": "tool_use",
+                        "id": anthropic_call_id(),
+                        "name": call.name,
+                        "input": call.input()
+                    }));
+                }
+            }
+        }
+    }
+    *blocks = rewritten;
+    if total > 0 {
+        json["stop_reason"] = json!("tool_use");
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weather_tool() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "city": {"type": "string", "description": "City name"},
+                "unit": {"type": "string", "enum": ["c", "f"]}
+            },
+            "required": ["city"]
+        })
+    }
+
+    #[test]
+    fn test_requires_emulation_matches_patterns() {
+        let config = ToolEmulationConfig {
+            models: vec!["deepseek-r1*".to_string(), "gemma-3".to_string()],
+            format: ToolCallFormat::Xml,
+        };
+        assert!(requires_emulation(&config, "deepseek-r1-distill"));
+        assert!(requires_emulation(&config, "gemma-3"));
+        assert!(!requires_emulation(&config, "gpt-4o"));
+        assert!(!requires_emulation(
+            &ToolEmulationConfig::default(),
+            "gemma-3"
+        ));
+    }
+
+    #[test]
+    fn test_emulate_openai_request_rewrites_tools_and_history() {
+        let mut request: ChatCompletionRequest = serde_json::from_value(json!({
+            "model": "m",
+            "messages": [
+                {"role": "user", "content": "Weather in Paris?"},
+                {"role": "assistant", "content": null, "tool_calls": [
+                    {"id": "call_1", "type": "function", "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}}
+                ]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "18C"}
+            ],
+            "tools": [{"type": "function", "function": {"name": "get_weather", "description": "Get weather", "parameters": weather_tool()}}],
+            "tool_choice": "required"
+        }))
+        .unwrap();
+
+        emulate_openai_request(&mut request, ToolCallFormat::Xml);
+
+        assert!(request.tools.is_none() && request.tool_choice.is_none());
+        let system = request.messages[0].get_content_text();
+        assert_eq!(request.messages[0].role, "system");
+        assert!(system.contains("<tool name=\"get_weather\">"));
+        assert!(system.contains("required=\"true\""));
+        assert!(system.contains("must call at least one tool"));
+
+        assert!(request.messages[2].tool_calls.is_none());
+        assert!(request.messages[2]
+            .get_content_text()
+            .contains("<name>get_weather</name>"));
+        assert_eq!(request.messages[3].role, "user");
+        assert!(request.messages[3]
+            .get_content_text()
+            .contains("<tool_result id=\"call_1\" name=\"get_weather\">\n18C"));
+    }
+
+    #[test]
+    fn test_emulate_anthropic_request_rewrites_blocks() {
+        let mut request: AnthropicMessagesRequest = serde_json::from_value(json!({
+            "model": "m",
+            "max_tokens": 100,
+            "system": [{"type": "text", "text": "Be brief."}],
+            "messages": [
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {"city": "Paris"}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "toolu_1", "content": [{"type": "text", "text": "18C"}]}
+                ]}
+            ],
+            "tools": [{"name": "get_weather", "input_schema": weather_tool()}]
+        }))
+        .unwrap();
+
+        emulate_anthropic_request(&mut request, ToolCallFormat::Json);
+
+        assert!(request.tools.is_none());
+        let system = request.system.unwrap();
+        assert_eq!(system.as_array().unwrap().len(), 2);
+        assert!(system[1]["text"].as_str().unwrap().contains("```json"));
+        assert_eq!(
+            request.messages[0].content[0]["text"],
+            r#"<tool_call>{"name": "get_weather", "arguments": {"city":"Paris"}}</tool_call>"#
+        );
+        assert!(request.messages[1].content[0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("name=\"get_weather\">\n18C"));
+    }
+
+    #[test]
+    fn test_rewrite_openai_response() {
+        let mut response = json!({
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": "Checking.\n<tool_call><name>get_weather</name><arguments>{\"city\":\"Paris\"}</arguments></tool_call>"},
+                "finish_reason": "stop"
+            }]
+        });
+
+        assert_eq!(rewrite_openai_response(&mut response), 1);
+        let choice = &response["choices"][0];
+        assert_eq!(choice["message"]["content"], "Checking.");
+        assert_eq!(choice["finish_reason"], "tool_calls");
+        assert_eq!(
+            choice["message"]["tool_calls"][0]["function"]["arguments"],
+            r#"{"city":"Paris"}"#
+        );
+    }
+
+    #[test]
+    fn test_rewrite_anthropic_response() {
+        let mut response = json!({
+            "content": [{"type": "text", "text": "<tool_call>{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Paris\"}}</tool_call>"}],
+            "stop_reason": "end_turn"
+        });
+
+        assert_eq!(rewrite_anthropic_response(&mut response), 1);
+        assert_eq!(response["stop_reason"], "tool_use");
+        assert_eq!(response["content"].as_array().unwrap().len(), 1);
+        assert_eq!(response["content"][0]["type"], "tool_use");
+        assert_eq!(response["content"][0]["input"]["city"], "Paris");
+
+        let mut plain =
+            json!({"content": [{"type": "text", "text": "Hi"}], "stop_reason": "end_turn"});
+        assert_eq!(rewrite_anthropic_response(&mut plain), 0);
+        assert_eq!(plain["stop_reason"], "end_turn");
+    }
+}
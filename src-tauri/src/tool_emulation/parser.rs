@@ -0,0 +1,422 @@
+//! 模拟工具调用的流式解析
+//!
+//! 从模型输出的文本中识别 `<tool_call>...</tool_call>` 块，块内可以是 XML
+//! （`<name>` + `<arguments>`）或 JSON（`{"name": .., "arguments": ..}`）。
+//! 文本按任意边界分片输入，工具调用参数中的 JSON 通过 `PartialJsonAccumulator`
+//! 累积，字符串内出现的标签不会被误判为结束。
+
+use crate::streaming::PartialJsonAccumulator;
+use serde_json::Value;
+
+const OPEN_TAG: &str = "<tool_call>";
+const CLOSE_TAG: &str = "</tool_call>";
+
+/// 单个工具调用块的最大长度，超过后按普通文本处理
+const MAX_CALL_BYTES: usize = 256 * 1024;
+
+/// 从文本中解析出的工具调用
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmulatedToolCall {
+    /// 工具名称
+    pub name: String,
+    /// 参数（JSON 对象的字符串形式）
+    pub arguments: String,
+}
+
+impl EmulatedToolCall {
+    /// 参数解析为 JSON 值（Anthropic tool_use.input 使用）
+    pub fn input(&self) -> Value {
+        serde_json::from_str(&self.arguments).unwrap_or_else(|_| Value::Object(Default::default()))
+    }
+}
+
+/// 解析输出的片段
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// 普通文本
+    Text(String),
+    /// 工具调用
+    ToolCall(EmulatedToolCall),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// 普通文本
+    Text,
+    /// 已读到 `<tool_call>`，等待判断块内格式
+    CallStart,
+    /// JSON 格式的调用体
+    JsonBody,
+    /// XML 格式的调用体（`<arguments>` 之外的部分）
+    XmlBody,
+    /// XML 格式 `<arguments>` 内的 JSON
+    XmlArguments,
+    /// 调用体已完整，等待 `</tool_call>`
+    CallEnd,
+}
+
+/// 流式工具调用解析器
+#[derive(Debug)]
+pub struct ToolCallParser {
+    mode: Mode,
+    /// 尚未输出的文本（可能包含不完整的开始标签）
+    text: String,
+    /// 当前调用块的原始内容，格式错误时作为文本输出
+    raw: String,
+    /// XML 调用体
+    markup: String,
+    /// 调用参数 / JSON 调用体
+    json: PartialJsonAccumulator,
+    /// 已完整、等待结束标签的调用
+    pending: Option<EmulatedToolCall>,
+    /// `</tool_call>` 之前的内容
+    tail: String,
+}
+
+impl Default for ToolCallParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToolCallParser {
+    pub fn new() -> Self {
+        Self {
+            mode: Mode::Text,
+            text: String::new(),
+            raw: String::new(),
+            markup: String::new(),
+            json: PartialJsonAccumulator::new(),
+            pending: None,
+            tail: String::new(),
+        }
+    }
+
+    /// 输入一段文本，返回可以确定的片段
+    pub fn push(&mut self, chunk: &str) -> Vec<Segment> {
+        let mut out = Vec::new();
+        for ch in chunk.chars() {
+            self.push_char(ch, &mut out);
+        }
+        if self.mode == Mode::Text {
+            self.flush_safe_text(&mut out);
+        }
+        out
+    }
+
+    /// 输入结束，输出剩余内容
+    ///
+    /// 缺少结束标签但调用体已完整的调用仍会输出；其余未完成的块作为文本输出。
+    pub fn finish(&mut self) -> Vec<Segment> {
+        let mut out = Vec::new();
+        match self.mode {
+            Mode::Text => {}
+            Mode::CallEnd => {
+                if let Some(call) = self.pending.take() {
+                    out.push(Segment::ToolCall(call));
+                }
+                let tail = std::mem::take(&mut self.tail);
+                self.text.push_str(&tail);
+            }
+            Mode::XmlBody => match self.xml_call() {
+                Some(call) => out.push(Segment::ToolCall(call)),
+                None => self.abort_call(),
+            },
+            Mode::CallStart | Mode::JsonBody | Mode::XmlArguments => self.abort_call(),
+        }
+        self.reset_call();
+        if !self.text.is_empty() {
+            out.push(Segment::Text(std::mem::take(&mut self.text)));
+        }
+        out
+    }
+
+    /// 一次性解析完整文本
+    pub fn parse_all(text: &str) -> Vec<Segment> {
+        let mut parser = Self::new();
+        let mut segments = parser.push(text);
+        segments.extend(parser.finish());
+        merge_text(segments)
+    }
+
+    fn push_char(&mut self, ch: char, out: &mut Vec<Segment>) {
+        if self.mode != Mode::Text && self.mode != Mode::CallEnd {
+            self.raw.push(ch);
+            if self.raw.len() > MAX_CALL_BYTES {
+                self.abort_call();
+                return;
+            }
+        }
+
+        match self.mode {
+            Mode::Text => {
+                self.text.push(ch);
+                if self.text.ends_with(OPEN_TAG) {
+                    self.text.truncate(self.text.len() - OPEN_TAG.len());
+                    if !self.text.is_empty() {
+                        out.push(Segment::Text(std::mem::take(&mut self.text)));
+                    }
+                    self.raw = OPEN_TAG.to_string();
+                    self.mode = Mode::CallStart;
+                }
+            }
+            Mode::CallStart => match ch {
+                c if c.is_whitespace() => {}
+                '{' => {
+                    self.json.append("{");
+                    self.mode = Mode::JsonBody;
+                }
+                '<' => {
+                    self.markup.push('<');
+                    self.mode = Mode::XmlBody;
+                }
+                _ => self.abort_call(),
+            },
+            Mode::JsonBody => {
+                if self.json.append(ch.encode_utf8(&mut [0; 4])) {
+                    match json_call(self.json.get_json()) {
+                        Some(call) => {
+                            self.pending = Some(call);
+                            self.mode = Mode::CallEnd;
+                        }
+                        None => self.abort_call(),
+                    }
+                }
+            }
+            Mode::XmlBody => {
+                self.markup.push(ch);
+                if self.markup.ends_with("<arguments>") {
+                    self.mode = Mode::XmlArguments;
+                } else if self.markup.ends_with(CLOSE_TAG) {
+                    match self.xml_call() {
+                        Some(call) => {
+                            out.push(Segment::ToolCall(call));
+                            self.reset_call();
+                        }
+                        None => self.abort_call(),
+                    }
+                }
+            }
+            Mode::XmlArguments => {
+                if self.json.is_empty() {
+                    match ch {
+                        c if c.is_whitespace() => return,
+                        // 空参数：<arguments></arguments>
+                        '<' => {
+                            self.markup.push('<');
+                            self.mode = Mode::XmlBody;
+                            return;
+                        }
+                        '{' => {}
+                        _ => {
+                            self.abort_call();
+                            return;
+                        }
+                    }
+                }
+                if self.json.append(ch.encode_utf8(&mut [0; 4])) {
+                    self.mode = Mode::XmlBody;
+                }
+            }
+            Mode::CallEnd => {
+                self.tail.push(ch);
+                let trimmed = self.tail.trim_start();
+                if trimmed == CLOSE_TAG {
+                    if let Some(call) = self.pending.take() {
+                        out.push(Segment::ToolCall(call));
+                    }
+                    self.reset_call();
+                } else if !CLOSE_TAG.starts_with(trimmed) {
+                    // 模型漏写了结束标签：调用照常输出，后续内容按文本重新处理
+                    if let Some(call) = self.pending.take() {
+                        out.push(Segment::ToolCall(call));
+                    }
+                    let rest = std::mem::take(&mut self.tail);
+                    self.reset_call();
+                    for c in rest.chars() {
+                        self.push_char(c, out);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 输出不可能是开始标签一部分的文本
+    fn flush_safe_text(&mut self, out: &mut Vec<Segment>) {
+        let keep = (1..OPEN_TAG.len())
+            .rev()
+            .find(|&n| self.text.ends_with(&OPEN_TAG[..n]))
+            .unwrap_or(0);
+        let split = self.text.len() - keep;
+        if split > 0 {
+            let rest = self.text.split_off(split);
+            out.push(Segment::Text(std::mem::replace(&mut self.text, rest)));
+        }
+    }
+
+    fn xml_call(&self) -> Option<EmulatedToolCall> {
+        let name = between(&self.markup, "<name>", "</name>")?.trim();
+        if name.is_empty() || (!self.json.is_empty() && !self.json.is_complete()) {
+            return None;
+        }
+        let arguments = if self.json.is_empty() {
+            "{}".to_string()
+        } else {
+            normalize_arguments(serde_json::from_str(self.json.get_json()).ok()?)?
+        };
+        Some(EmulatedToolCall {
+            name: unescape_xml(name),
+            arguments,
+        })
+    }
+
+    /// 当前块不是合法的工具调用，原样作为文本
+    fn abort_call(&mut self) {
+        self.text.push_str(&std::mem::take(&mut self.raw));
+        self.reset_call();
+    }
+
+    fn reset_call(&mut self) {
+        self.mode = Mode::Text;
+        self.raw.clear();
+        self.markup.clear();
+        self.json.reset();
+        self.pending = None;
+        self.tail.clear();
+    }
+}
+
+/// 合并相邻的文本片段
+pub fn merge_text(segments: Vec<Segment>) -> Vec<Segment> {
+    let mut merged: Vec<Segment> = Vec::with_capacity(segments.len());
+    for segment in segments {
+        match (merged.last_mut(), segment) {
+            (Some(Segment::Text(last)), Segment::Text(text)) => last.push_str(&text),
+            (_, segment) => merged.push(segment),
+        }
+    }
+    merged
+}
+
+fn json_call(body: &str) -> Option<EmulatedToolCall> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    let name = value.get("name")?.as_str()?.trim().to_string();
+    if name.is_empty() {
+        return None;
+    }
+    let arguments = match value.get("arguments").or_else(|| value.get("parameters")) {
+        Some(arguments) => normalize_arguments(arguments.clone())?,
+        None => "{}".to_string(),
+    };
+    Some(EmulatedToolCall { name, arguments })
+}
+
+/// 参数统一为 JSON 对象字符串（兼容模型把参数写成 JSON 字符串的情况）
+fn normalize_arguments(arguments: Value) -> Option<String> {
+    match arguments {
+        Value::Object(_) => Some(arguments.to_string()),
+        Value::String(s) => match serde_json::from_str::<Value>(&s).ok()? {
+            parsed @ Value::Object(_) => Some(parsed.to_string()),
+            _ => None,
+        },
+        Value::Null => Some("{}".to_string()),
+        _ => None,
+    }
+}
+
+fn between<'a>(text: &'a str, start: &str, end: &str) -> Option<&'a str> {
+    let from = text.find(start)? + start.len();
+    let to = from + text[from..].find(end)?;
+    Some(&text[from..to])
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: &str) -> Segment {
+        Segment::ToolCall(EmulatedToolCall {
+            name: name.to_string(),
+            arguments: arguments.to_string(),
+        })
+    }
+
+    fn text(s: &str) -> Segment {
+        Segment::Text(s.to_string())
+    }
+
+    /// 逐字符输入，模拟最碎的流式分片
+    fn parse_by_char(input: &str) -> Vec<Segment> {
+        let mut parser = ToolCallParser::new();
+        let mut segments = Vec::new();
+        for ch in input.chars() {
+            segments.extend(parser.push(&ch.to_string()));
+        }
+        segments.extend(parser.finish());
+        merge_text(segments)
+    }
+
+    #[test]
+    fn test_xml_call_with_surrounding_text() {
+        let input = "Let me check.\n<tool_call>\n<name>get_weather</name>\n<arguments>{\"city\": \"Paris\"}</arguments>\n</tool_call>";
+        let expected = vec![
+            text("Let me check.\n"),
+            call("get_weather", r#"{"city":"Paris"}"#),
+        ];
+        assert_eq!(ToolCallParser::parse_all(input), expected);
+        assert_eq!(parse_by_char(input), expected);
+    }
+
+    #[test]
+    fn test_json_call_and_multiple_calls() {
+        let input =
+            "<tool_call>{\"name\": \"a\", \"arguments\": {\"q\": \"</tool_call>\"}}</tool_call>\
+                     <tool_call>{\"name\": \"b\", \"arguments\": \"{\\\"n\\\": 1}\"}</tool_call>";
+        let expected = vec![
+            call("a", r#"{"q":"</tool_call>"}"#),
+            call("b", r#"{"n":1}"#),
+        ];
+        assert_eq!(ToolCallParser::parse_all(input), expected);
+        assert_eq!(parse_by_char(input), expected);
+    }
+
+    #[test]
+    fn test_partial_tag_held_until_resolved() {
+        let mut parser = ToolCallParser::new();
+        assert_eq!(parser.push("Hi <tool"), vec![text("Hi ")]);
+        assert_eq!(parser.push("box> there"), vec![text("<toolbox> there")]);
+        assert!(parser.finish().is_empty());
+    }
+
+    #[test]
+    fn test_missing_close_tag_and_empty_arguments() {
+        assert_eq!(
+            ToolCallParser::parse_all("<tool_call>{\"name\": \"ls\"} done"),
+            vec![call("ls", "{}"), text(" done")]
+        );
+        assert_eq!(
+            ToolCallParser::parse_all("<tool_call><name>ls</name><arguments></arguments>"),
+            vec![call("ls", "{}")]
+        );
+    }
+
+    #[test]
+    fn test_malformed_call_falls_back_to_text() {
+        let input = "<tool_call>not a call</tool_call> ok";
+        assert_eq!(ToolCallParser::parse_all(input), vec![text(input)]);
+
+        let unterminated = "<tool_call>{\"name\": \"a\", \"arguments\": {";
+        assert_eq!(
+            ToolCallParser::parse_all(unterminated),
+            vec![text(unterminated)]
+        );
+    }
+}
@@ -0,0 +1,501 @@
+//! 流式响应中的工具调用还原
+//!
+//! 按 SSE 事件改写上游响应：文本增量先经过 `ToolCallParser`，普通文本照常下发，
+//! 解析出的工具调用改为 OpenAI `tool_calls` 增量或 Anthropic `tool_use` 内容块。
+
+use super::{anthropic_call_id, openai_call_id, Segment, ToolCallParser};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+/// 按行切分 SSE 字节流
+#[derive(Debug, Default)]
+struct LineBuffer {
+    buffer: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            lines.push(
+                String::from_utf8_lossy(&line)
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string(),
+            );
+        }
+        lines
+    }
+
+    fn take_rest(&mut self) -> Option<String> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        let rest = String::from_utf8_lossy(&self.buffer).trim().to_string();
+        self.buffer.clear();
+        (!rest.is_empty()).then_some(rest)
+    }
+}
+
+fn sse_data(value: &Value) -> String {
+    format!("data: {}\n\n", value)
+}
+
+fn sse_event(value: &Value) -> String {
+    let event = value["type"].as_str().unwrap_or("message");
+    format!("event: {}\ndata: {}\n\n", event, value)
+}
+
+/// OpenAI Chat Completions SSE 改写
+#[derive(Debug, Default)]
+pub struct OpenAiToolStream {
+    lines: LineBuffer,
+    parser: ToolCallParser,
+    /// 最近一个 chunk 的公共字段（id / model / created 等），用于构造新 chunk
+    envelope: Option<Value>,
+    /// 已下发的工具调用数量
+    calls: usize,
+    finished: bool,
+}
+
+impl OpenAiToolStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 已还原的工具调用数量
+    pub fn call_count(&self) -> usize {
+        self.calls
+    }
+
+    /// 处理一段上游字节，返回改写后的字节
+    pub fn transform(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let mut out = String::new();
+        for line in self.lines.push(chunk) {
+            self.process_line(&line, &mut out);
+        }
+        out.into_bytes()
+    }
+
+    /// 上游结束，输出剩余内容
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut out = String::new();
+        if let Some(rest) = self.lines.take_rest() {
+            self.process_line(&rest, &mut out);
+        }
+        self.flush(&mut out);
+        out.into_bytes()
+    }
+
+    fn process_line(&mut self, line: &str, out: &mut String) {
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            if !line.is_empty() {
+                out.push_str(line);
+                out.push('\n');
+            }
+            return;
+        };
+        if data == "[DONE]" {
+            self.flush(out);
+            out.push_str("data: [DONE]\n\n");
+            return;
+        }
+        let Ok(mut chunk) = serde_json::from_str::<Value>(data) else {
+            out.push_str(line);
+            out.push_str("\n\n");
+            return;
+        };
+
+        let mut envelope = chunk.clone();
+        envelope["choices"] = json!([]);
+        if let Some(obj) = envelope.as_object_mut() {
+            obj.remove("usage");
+        }
+        self.envelope = Some(envelope);
+
+        let Some(choice) = chunk["choices"].get_mut(0) else {
+            out.push_str(&sse_data(&chunk));
+            return;
+        };
+
+        if let Some(content) = choice["delta"]["content"].as_str().map(String::from) {
+            let segments = self.parser.push(&content);
+            let text = self.emit_segments(segments, out);
+            match text {
+                Some(text) => choice["delta"]["content"] = Value::String(text),
+                None => {
+                    if let Some(delta) = choice["delta"].as_object_mut() {
+                        delta.remove("content");
+                    }
+                }
+            }
+        }
+
+        if choice["finish_reason"].is_string() {
+            // 结束前输出解析器中剩余的内容
+            let segments = self.parser.finish();
+            if let Some(text) = self.emit_segments(segments, out) {
+                out.push_str(&sse_data(&self.chunk_with_delta(json!({"content": text}))));
+            }
+            self.finished = true;
+            if self.calls > 0 {
+                choice["finish_reason"] = json!("tool_calls");
+            }
+        }
+
+        let delta_empty = choice["delta"].as_object().is_none_or(|d| d.is_empty());
+        if delta_empty && choice["finish_reason"].is_null() && chunk.get("usage").is_none() {
+            return;
+        }
+        out.push_str(&sse_data(&chunk));
+    }
+
+    /// 工具调用直接写入输出，返回需要随当前 chunk 下发的文本
+    fn emit_segments(&mut self, segments: Vec<Segment>, out: &mut String) -> Option<String> {
+        let mut text = String::new();
+        for segment in segments {
+            match segment {
+                Segment::Text(t) => text.push_str(&t),
+                Segment::ToolCall(call) => {
+                    if !text.is_empty() {
+                        let delta = json!({"content": std::mem::take(&mut text)});
+                        out.push_str(&sse_data(&self.chunk_with_delta(delta)));
+                    }
+                    let delta = json!({"tool_calls": [{
+                        "index": self.calls,
+                        "id": openai_call_id(),
+                        "type": "function",
+                        "function": {"name": call.name, "arguments": call.arguments}
+                    }]});
+                    out.push_str(&sse_data(&self.chunk_with_delta(delta)));
+                    self.calls += 1;
+                }
+            }
+        }
+        (!text.is_empty()).then_some(text)
+    }
+
+    fn chunk_with_delta(&self, delta: Value) -> Value {
+        let mut chunk = self
+            .envelope
+            .clone()
+            .unwrap_or_else(|| json!({"object": "chat.completion.chunk"}));
+        chunk["choices"] = json!([{"index": 0, "delta": delta, "finish_reason": null}]);
+        chunk
+    }
+
+    fn flush(&mut self, out: &mut String) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        let segments = self.parser.finish();
+        if let Some(text) = self.emit_segments(segments, out) {
+            out.push_str(&sse_data(&self.chunk_with_delta(json!({"content": text}))));
+        }
+    }
+}
+
+/// Anthropic Messages SSE 改写
+///
+/// 上游的 text 块被拆分为由本结构重新编号的 text / tool_use 块；其他块（如 thinking）
+/// 原样转发，只调整 index。
+#[derive(Debug, Default)]
+pub struct AnthropicToolStream {
+    lines: LineBuffer,
+    parser: ToolCallParser,
+    /// 下一个输出块的 index
+    next_index: u64,
+    /// 当前打开的输出 text 块
+    open_text: Option<u64>,
+    /// 上游 text 块的 index
+    upstream_text: HashSet<u64>,
+    /// 其他上游块 index -> 输出 index
+    passthrough: HashMap<u64, u64>,
+    calls: usize,
+    finished: bool,
+}
+
+impl AnthropicToolStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 已还原的工具调用数量
+    pub fn call_count(&self) -> usize {
+        self.calls
+    }
+
+    /// 处理一段上游字节，返回改写后的字节
+    pub fn transform(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let mut out = String::new();
+        for line in self.lines.push(chunk) {
+            self.process_line(&line, &mut out);
+        }
+        out.into_bytes()
+    }
+
+    /// 上游结束，输出剩余内容
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut out = String::new();
+        if let Some(rest) = self.lines.take_rest() {
+            self.process_line(&rest, &mut out);
+        }
+        self.flush(&mut out);
+        out.into_bytes()
+    }
+
+    fn process_line(&mut self, line: &str, out: &mut String) {
+        // event 行由 data 中的 type 重新生成
+        let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+            if !line.is_empty() && !line.starts_with("event:") {
+                out.push_str(line);
+                out.push('\n');
+            }
+            return;
+        };
+        let Ok(mut event) = serde_json::from_str::<Value>(data) else {
+            out.push_str(line);
+            out.push_str("\n\n");
+            return;
+        };
+        let upstream_index = event["index"].as_u64();
+
+        match event["type"].as_str().unwrap_or_default() {
+            "content_block_start" => {
+                let index = upstream_index.unwrap_or_default();
+                if event["content_block"]["type"] == "text" {
+                    self.upstream_text.insert(index);
+                    if let Some(text) = event["content_block"]["text"].as_str() {
+                        let segments = self.parser.push(text);
+                        self.emit_segments(segments, out);
+                    }
+                    return;
+                }
+                self.close_text(out);
+                let mapped = self.next_index;
+                self.next_index += 1;
+                self.passthrough.insert(index, mapped);
+                event["index"] = json!(mapped);
+            }
+            "content_block_delta" | "content_block_stop" => {
+                let index = upstream_index.unwrap_or_default();
+                if self.upstream_text.contains(&index) {
+                    if let Some(text) = event["delta"]["text"].as_str() {
+                        let segments = self.parser.push(text);
+                        self.emit_segments(segments, out);
+                    }
+                    return;
+                }
+                if let Some(mapped) = self.passthrough.get(&index) {
+                    event["index"] = json!(mapped);
+                }
+            }
+            "message_delta" => {
+                self.flush(out);
+                if self.calls > 0 {
+                    event["delta"]["stop_reason"] = json!("tool_use");
+                }
+            }
+            "message_stop" => self.flush(out),
+            _ => {}
+        }
+        out.push_str(&sse_event(&event));
+    }
+
+    fn emit_segments(&mut self, segments: Vec<Segment>, out: &mut String) {
+        for segment in segments {
+            match segment {
+                Segment::Text(text) => {
+                    let index = match self.open_text {
+                        Some(index) => index,
+                        None => {
+                            let index = self.next_index;
+                            self.next_index += 1;
+                            self.open_text = Some(index);
+                            out.push_str(&sse_event(&json!({
+                                "type": "content_block_start",
+                                "index": index,
+                                "content_block": {"type": "text", "text": ""}
+                            })));
+                            index
+                        }
+                    };
+                    out.push_str(&sse_event(&json!({
+                        "type": "content_block_delta",
+                        "index": index,
+                        "delta": {"type": "text_delta", "text": text}
+                    })));
+                }
+                Segment::ToolCall(call) => {
+                    self.close_text(out);
+                    let index = self.next_index;
+                    self.next_index += 1;
+                    out.push_str(&sse_event(&json!({
+                        "type": "content_block_start",
+                        "index": index,
+                        "content_block": {
+                            "type": "tool_use",
+                            "id": anthropic_call_id(),
+                            "name": call.name,
+                            "input": {}
+                        }
+                    })));
+                    out.push_str(&sse_event(&json!({
+                        "type": "content_block_delta",
+                        "index": index,
+                        "delta": {"type": "input_json_delta", "partial_json": call.arguments}
+                    })));
+                    out.push_str(&sse_event(
+                        &json!({"type": "content_block_stop", "index": index}),
+                    ));
+                    self.calls += 1;
+                }
+            }
+        }
+    }
+
+    fn close_text(&mut self, out: &mut String) {
+        if let Some(index) = self.open_text.take() {
+            out.push_str(&sse_event(
+                &json!({"type": "content_block_stop", "index": index}),
+            ));
+        }
+    }
+
+    fn flush(&mut self, out: &mut String) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        let segments = self.parser.finish();
+        self.emit_segments(segments, out);
+        self.close_text(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_events(output: &[u8]) -> Vec<Value> {
+        String::from_utf8_lossy(output)
+            .lines()
+            .filter_map(|l| l.strip_prefix("data: "))
+            .filter(|d| *d != "[DONE]")
+            .map(|d| serde_json::from_str(d).unwrap())
+            .collect()
+    }
+
+    fn openai_chunk(delta: Value, finish_reason: Value) -> String {
+        sse_data(&json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion.chunk",
+            "model": "m",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+        }))
+    }
+
+    #[test]
+    fn test_openai_stream_splits_tool_calls() {
+        let mut stream = OpenAiToolStream::new();
+        let mut output = Vec::new();
+
+        let upstream = [
+            openai_chunk(
+                json!({"role": "assistant", "content": "Sure.<tool"}),
+                Value::Null,
+            ),
+            openai_chunk(
+                json!({"content": "_call>{\"name\": \"ls\", \"argu"}),
+                Value::Null,
+            ),
+            openai_chunk(
+                json!({"content": "ments\": {\"path\": \"/\"}}</tool_call>"}),
+                Value::Null,
+            ),
+            openai_chunk(json!({}), json!("stop")),
+            "data: [DONE]\n\n".to_string(),
+        ];
+        // 按不对齐的字节边界切分
+        let bytes = upstream.concat().into_bytes();
+        for piece in bytes.chunks(7) {
+            output.extend(stream.transform(piece));
+        }
+        output.extend(stream.finish());
+
+        let events = data_events(&output);
+        let text: String = events
+            .iter()
+            .filter_map(|e| e["choices"][0]["delta"]["content"].as_str())
+            .collect();
+        assert_eq!(text, "Sure.");
+
+        let calls: Vec<&Value> = events
+            .iter()
+            .filter_map(|e| e["choices"][0]["delta"]["tool_calls"].get(0))
+            .collect();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0]["function"]["name"], "ls");
+        assert_eq!(calls[0]["function"]["arguments"], r#"{"path":"/"}"#);
+        assert_eq!(
+            events.last().unwrap()["choices"][0]["finish_reason"],
+            "tool_calls"
+        );
+        assert!(String::from_utf8_lossy(&output).ends_with("data: [DONE]\n\n"));
+    }
+
+    #[test]
+    fn test_anthropic_stream_splits_tool_use_blocks() {
+        let mut stream = AnthropicToolStream::new();
+        let upstream = [
+            json!({"type": "message_start", "message": {"id": "msg_1", "usage": {"input_tokens": 5}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "thinking", "thinking": ""}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "text_delta", "text": "Hi <tool_call><name>ls</name>"}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "text_delta", "text": "<arguments>{\"path\": \"/\"}</arguments></tool_call>"}}),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "end_turn"}, "usage": {"output_tokens": 9}}),
+            json!({"type": "message_stop"}),
+        ];
+        let mut output = Vec::new();
+        for event in &upstream {
+            output.extend(stream.transform(sse_event(event).as_bytes()));
+        }
+        output.extend(stream.finish());
+
+        let events = data_events(&output);
+        let starts: Vec<(u64, &str)> = events
+            .iter()
+            .filter(|e| e["type"] == "content_block_start")
+            .map(|e| {
+                (
+                    e["index"].as_u64().unwrap(),
+                    e["content_block"]["type"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(starts, vec![(0, "thinking"), (1, "text"), (2, "tool_use")]);
+
+        let input = events
+            .iter()
+            .find(|e| e["delta"]["type"] == "input_json_delta")
+            .unwrap();
+        assert_eq!(input["delta"]["partial_json"], r#"{"path":"/"}"#);
+        assert_eq!(stream.call_count(), 1);
+
+        let message_delta = events
+            .iter()
+            .find(|e| e["type"] == "message_delta")
+            .unwrap();
+        assert_eq!(message_delta["delta"]["stop_reason"], "tool_use");
+        // 每个打开的块都被关闭
+        let stops = events
+            .iter()
+            .filter(|e| e["type"] == "content_block_stop")
+            .count();
+        assert_eq!(stops, 3);
+    }
+}
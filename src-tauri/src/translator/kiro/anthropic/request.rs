@@ -682,6 +682,18 @@ fn is_tool_choice_required(tool_choice: &Option<serde_json::Value>) -> bool {
     }
 }
 
+/// CodeWhisperer 能承载的 Anthropic 参数
+const CW_SUPPORTED_ANTHROPIC_PARAMS: &[&str] = &["system", "tools", "tool_choice"];
+
+/// 返回转换到 CodeWhisperer 时会被丢弃的 Anthropic 参数
+pub fn unsupported_anthropic_params(request: &AnthropicMessagesRequest) -> Vec<String> {
+    request
+        .present_params()
+        .into_iter()
+        .filter(|p| !CW_SUPPORTED_ANTHROPIC_PARAMS.contains(&p.as_str()))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -700,6 +712,7 @@ mod tests {
             temperature: None,
             tools: None,
             tool_choice: None,
+            ..Default::default()
         };
 
         let translator = AnthropicRequestTranslator::new();
@@ -733,4 +746,19 @@ mod tests {
         let text = extract_system_text(&system);
         assert_eq!(text, "Line 1\nLine 2");
     }
+
+    #[test]
+    fn test_unsupported_anthropic_params() {
+        let request: AnthropicMessagesRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 1024,
+            "system": "You are a helpful assistant.",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "thinking": {"type": "enabled", "budget_tokens": 2048}
+        }))
+        .unwrap();
+
+        let params = unsupported_anthropic_params(&request);
+        assert_eq!(params, vec!["max_tokens", "thinking"]);
+    }
 }
@@ -611,6 +611,20 @@ fn is_tool_choice_required(tool_choice: &Option<serde_json::Value>) -> bool {
     }
 }
 
+/// CodeWhisperer 能承载的 OpenAI 参数
+///
+/// CodeWhisperer 没有采样参数，只能携带工具定义和强制工具调用。
+const CW_SUPPORTED_OPENAI_PARAMS: &[&str] = &["tools", "tool_choice"];
+
+/// 返回转换到 CodeWhisperer 时会被丢弃的 OpenAI 参数
+pub fn unsupported_openai_params(request: &ChatCompletionRequest) -> Vec<String> {
+    request
+        .present_params()
+        .into_iter()
+        .filter(|p| !CW_SUPPORTED_OPENAI_PARAMS.contains(&p.as_str()))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -643,6 +657,7 @@ mod tests {
             top_p: None,
             tool_choice: None,
             reasoning_effort: None,
+            ..Default::default()
         };
 
         let translator = OpenAiRequestTranslator::new();
@@ -659,4 +674,19 @@ mod tests {
             "CLAUDE_SONNET_4_5_20250929_V1_0"
         );
     }
+
+    #[test]
+    fn test_unsupported_openai_params() {
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{"role": "user", "content": "Hello"}],
+            "temperature": 0.2,
+            "seed": 7,
+            "tools": [{"type": "function", "function": {"name": "f"}}]
+        }))
+        .unwrap();
+
+        let params = unsupported_openai_params(&request);
+        assert_eq!(params, vec!["temperature", "seed"]);
+    }
 }
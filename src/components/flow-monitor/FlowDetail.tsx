@@ -22,6 +22,7 @@ import {
   Bot,
   Settings,
   Zap,
+  Shield,
 } from "lucide-react";
 import {
   flowMonitorApi,
@@ -30,6 +31,8 @@ import {
   type ToolCall,
   type FlowState,
   type ExportFormat,
+  type StructuredOutputValidation,
+  type GuardrailEvent,
   formatFlowState,
   formatFlowType,
   formatErrorType,
@@ -42,6 +45,22 @@ import { useFlowActions } from "@/hooks/useFlowActions";
 import { FlowTimeline } from "./FlowTimeline";
 import { cn } from "@/lib/utils";
 
+const STRUCTURED_OUTPUT_MODE_LABELS: Record<
+  StructuredOutputValidation["mode"],
+  string
+> = {
+  native: "后端原生",
+  tool_forcing: "强制工具调用",
+  prompt_fallback: "代理模拟",
+};
+
+const GUARDRAIL_ACTION_LABELS: Record<GuardrailEvent["action"], string> = {
+  blocked: "已拦截",
+  masked: "已打码",
+  tokenized: "已替换占位符",
+  restored: "已还原",
+};
+
 interface FlowDetailProps {
   flowId: string;
   onBack?: () => void;
@@ -1209,6 +1228,106 @@ function MetadataTab({ flow, onCopy }: MetadataTabProps) {
           </div>
         )}
 
+      {/* 未支持的参数 */}
+      {metadata.unsupported_params &&
+        metadata.unsupported_params.length > 0 && (
+          <div className="rounded-lg border bg-card p-4">
+            <h3 className="text-sm font-medium mb-3 flex items-center gap-2">
+              <AlertCircle className="h-4 w-4 text-yellow-500" />
+              未转发的参数
+            </h3>
+            <p className="text-xs text-muted-foreground mb-2">
+              目标 Provider 不支持以下参数，已在协议转换时丢弃
+            </p>
+            <div className="flex flex-wrap gap-1">
+              {metadata.unsupported_params.map((param) => (
+                <span
+                  key={param}
+                  className="text-xs font-mono px-2 py-0.5 rounded-full bg-muted"
+                >
+                  {param}
+                </span>
+              ))}
+            </div>
+          </div>
+        )}
+
+      {/* 结构化输出 */}
+      {metadata.structured_output && (
+        <div className="rounded-lg border bg-card p-4">
+          <h3 className="text-sm font-medium mb-3 flex items-center gap-2">
+            <Code className="h-4 w-4" />
+            结构化输出
+          </h3>
+          <div className="grid grid-cols-2 gap-2 text-sm">
+            <span className="text-muted-foreground">实现方式</span>
+            <span>
+              {STRUCTURED_OUTPUT_MODE_LABELS[metadata.structured_output.mode]}
+            </span>
+            {metadata.structured_output.schema_name && (
+              <>
+                <span className="text-muted-foreground">Schema</span>
+                <span className="font-mono">
+                  {metadata.structured_output.schema_name}
+                </span>
+              </>
+            )}
+            <span className="text-muted-foreground">校验结果</span>
+            <span
+              className={cn(
+                metadata.structured_output.valid === true && "text-green-600",
+                metadata.structured_output.valid === false && "text-red-500",
+              )}
+            >
+              {metadata.structured_output.valid === undefined
+                ? "未校验"
+                : metadata.structured_output.valid
+                  ? "通过"
+                  : "未通过"}
+            </span>
+            <span className="text-muted-foreground">调用次数</span>
+            <span>{metadata.structured_output.attempts}</span>
+          </div>
+          {metadata.structured_output.errors &&
+            metadata.structured_output.errors.length > 0 && (
+              <ul className="mt-2 text-xs font-mono text-red-500 space-y-1">
+                {metadata.structured_output.errors.map((error) => (
+                  <li key={error}>{error}</li>
+                ))}
+              </ul>
+            )}
+        </div>
+      )}
+
+      {/* 出站防护 */}
+      {metadata.guardrail_events && metadata.guardrail_events.length > 0 && (
+        <div className="rounded-lg border bg-card p-4">
+          <h3 className="text-sm font-medium mb-3 flex items-center gap-2">
+            <Shield className="h-4 w-4" />
+            出站防护
+          </h3>
+          <div className="space-y-1 text-sm">
+            {metadata.guardrail_events.map((event, index) => (
+              <div key={index} className="flex items-center gap-2">
+                <span
+                  className={cn(
+                    "text-xs px-2 py-0.5 rounded-full bg-muted",
+                    event.action === "blocked" && "text-red-500",
+                  )}
+                >
+                  {GUARDRAIL_ACTION_LABELS[event.action]}
+                </span>
+                <span className="font-mono">{event.detector}</span>
+                <span className="text-muted-foreground">×{event.count}</span>
+                <span className="text-xs font-mono text-muted-foreground truncate">
+                  {event.location}
+                </span>
+              </div>
+            ))}
+          </div>
+        </div>
+      )}
+
       {/* Flow ID */}
       <div className="rounded-lg border bg-card p-4">
         <h3 className="text-sm font-medium mb-3">Flow ID</h3>
@@ -522,8 +522,8 @@ export function useTerminalAI(
           pendingCommandsRef.current.set(pendingCommand.id, pendingCommand);
           setPendingCommands(Array.from(pendingCommandsRef.current.values()));
 
-          // 如果开启了自动执行，直接批准命令
-          if (configRef.current.autoExecute) {
+          // 开启了自动执行或已由审批策略批准时，直接批准命令
+          if (configRef.current.autoExecute || request.auto_approved) {
             console.log("[useTerminalAI] 自动执行模式已启用，自动批准命令");
             // 使用 setTimeout 确保状态更新后再执行
             setTimeout(() => {
@@ -33,6 +33,10 @@ export interface ToolExecutionResult {
   output: string;
   /** 错误信息（如果失败） */
   error?: string;
+  /** 开始执行时间（RFC 3339） */
+  started_at?: string;
+  /** 执行耗时（毫秒） */
+  duration_ms?: number;
 }
 
 /**
@@ -47,7 +51,9 @@ export type StreamEvent =
   | StreamEventActionRequired
   | StreamEventDone
   | StreamEventFinalDone
-  | StreamEventError;
+  | StreamEventError
+  | StreamEventContextCompacted
+  | StreamEventSubAgent;
 
 /**
  * 文本增量事件
@@ -152,6 +158,40 @@ export interface StreamEventError {
   message: string;
 }
 
+/**
+ * 上下文压缩事件
+ * 会话接近模型上下文上限时，较早的消息被摘要、工具输出被截断
+ */
+export interface StreamEventContextCompacted {
+  type: "context_compacted";
+  /** 摘要内容（仅截断工具输出时为空） */
+  summary?: string;
+  /** 被摘要的消息数量 */
+  summarized_messages: number;
+  /** 原样保留的最近消息数量 */
+  kept_messages: number;
+  /** 压缩前估算的 token 数 */
+  tokens_before: number;
+  /** 压缩后估算的 token 数 */
+  tokens_after: number;
+}
+
+/**
+ * 子 Agent 事件
+ * task 工具启动的子 Agent 的进度，event 为子 Agent 自身的事件
+ */
+export interface StreamEventSubAgent {
+  type: "sub_agent";
+  /** 启动子 Agent 的 task 工具调用 ID */
+  parent_tool_id: string;
+  /** 子 Agent 会话 ID */
+  agent_id: string;
+  /** 子任务描述 */
+  description: string;
+  /** 子 Agent 事件 */
+  event: StreamEvent;
+}
+
 /**
  * 工具调用状态（用于 UI 显示）
  */
@@ -192,6 +232,7 @@ export function parseStreamEvent(data: unknown): StreamEvent | null {
         text: (event.text as string) || "",
       };
     case "reasoning_delta":
+    case "thinking_delta":
       return {
         type: "thinking_delta",
         text: (event.text as string) || "",
@@ -251,6 +292,26 @@ export function parseStreamEvent(data: unknown): StreamEvent | null {
         type: "error",
         message: (event.message as string) || "Unknown error",
       };
+    case "context_compacted":
+      return {
+        type: "context_compacted",
+        summary: event.summary as string | undefined,
+        summarized_messages: (event.summarized_messages as number) || 0,
+        kept_messages: (event.kept_messages as number) || 0,
+        tokens_before: (event.tokens_before as number) || 0,
+        tokens_after: (event.tokens_after as number) || 0,
+      };
+    case "sub_agent": {
+      const nested = parseStreamEvent(event.event);
+      if (!nested) return null;
+      return {
+        type: "sub_agent",
+        parent_tool_id: (event.parent_tool_id as string) || "",
+        agent_id: (event.agent_id as string) || "",
+        description: (event.description as string) || "",
+        event: nested,
+      };
+    }
     default:
       return null;
   }
@@ -455,6 +516,8 @@ export interface AgentMessage {
   timestamp: string;
   tool_calls?: AgentToolCall[];
   tool_call_id?: string;
+  /** 是否置顶（上下文压缩时原样保留） */
+  pinned?: boolean;
 }
 
 /**
@@ -468,6 +531,188 @@ export async function getAgentSessionMessages(
   });
 }
 
+/**
+ * 消息树节点
+ */
+export interface AgentMessageNode extends AgentMessage {
+  id: number;
+  parent_id: number | null;
+}
+
+/**
+ * 会话分支（以末端消息标识）
+ */
+export interface AgentBranch {
+  leaf_id: number;
+  message_count: number;
+  preview: string;
+  updated_at: string;
+  is_active: boolean;
+}
+
+/**
+ * 会话检查点
+ */
+export interface AgentCheckpoint {
+  id: string;
+  session_id: string;
+  message_id: number | null;
+  label: string | null;
+  file_count: number;
+  created_at: string;
+}
+
+/**
+ * 获取会话的完整消息树
+ */
+export async function getAgentMessageTree(
+  sessionId: string,
+): Promise<AgentMessageNode[]> {
+  return await safeInvoke("agent_get_message_tree", { sessionId });
+}
+
+/**
+ * 列出会话的所有分支
+ */
+export async function listAgentBranches(
+  sessionId: string,
+): Promise<AgentBranch[]> {
+  return await safeInvoke("agent_list_branches", { sessionId });
+}
+
+/**
+ * 切换激活分支（切换到中间消息即回退到该位置）
+ */
+export async function switchAgentBranch(
+  sessionId: string,
+  messageId: number,
+): Promise<AgentMessageNode[]> {
+  return await safeInvoke("agent_switch_branch", { sessionId, messageId });
+}
+
+/**
+ * 编辑用户消息：回退到其父消息，随后发送的消息成为新分支
+ */
+export async function editAgentMessage(
+  sessionId: string,
+  messageId: number,
+): Promise<AgentMessageNode[]> {
+  return await safeInvoke("agent_edit_message", { sessionId, messageId });
+}
+
+/**
+ * 在指定消息处分叉出新会话，返回新会话 ID
+ */
+export async function forkAgentSession(
+  sessionId: string,
+  messageId: number,
+): Promise<string> {
+  return await safeInvoke("agent_fork_session", { sessionId, messageId });
+}
+
+/**
+ * 创建检查点（同时快照会话文件）
+ */
+export async function createAgentCheckpoint(
+  sessionId: string,
+  label?: string,
+): Promise<AgentCheckpoint> {
+  return await safeInvoke("agent_create_checkpoint", { sessionId, label });
+}
+
+/**
+ * 列出会话的检查点
+ */
+export async function listAgentCheckpoints(
+  sessionId: string,
+): Promise<AgentCheckpoint[]> {
+  return await safeInvoke("agent_list_checkpoints", { sessionId });
+}
+
+/**
+ * 回退到检查点，恢复对话分支和会话文件
+ */
+export async function rewindAgentCheckpoint(
+  sessionId: string,
+  checkpointId: string,
+): Promise<AgentMessageNode[]> {
+  return await safeInvoke("agent_rewind_to_checkpoint", {
+    sessionId,
+    checkpointId,
+  });
+}
+
+/**
+ * 删除检查点
+ */
+export async function deleteAgentCheckpoint(
+  sessionId: string,
+  checkpointId: string,
+): Promise<void> {
+  return await safeInvoke("agent_delete_checkpoint", {
+    sessionId,
+    checkpointId,
+  });
+}
+
+/**
+ * 上下文压缩记录
+ */
+export interface AgentCompaction {
+  id: string;
+  session_id: string;
+  message_id: number | null;
+  summary: string;
+  summarized_messages: number;
+  kept_messages: number;
+  tokens_before: number;
+  tokens_after: number;
+  created_at: string;
+}
+
+/**
+ * 被截断的工具输出
+ */
+export interface CompactedToolOutput {
+  handle: string;
+  tool_call_id: string | null;
+  content: string;
+}
+
+/**
+ * 置顶或取消置顶消息（置顶消息在上下文压缩时原样保留）
+ */
+export async function pinAgentMessage(
+  sessionId: string,
+  messageId: number,
+  pinned: boolean,
+): Promise<AgentMessageNode[]> {
+  return await safeInvoke("agent_pin_message", {
+    sessionId,
+    messageId,
+    pinned,
+  });
+}
+
+/**
+ * 列出会话的上下文压缩记录
+ */
+export async function listAgentCompactions(
+  sessionId: string,
+): Promise<AgentCompaction[]> {
+  return await safeInvoke("agent_list_compactions", { sessionId });
+}
+
+/**
+ * 获取被截断的工具输出
+ */
+export async function getAgentToolOutput(
+  sessionId: string,
+  handle: string,
+): Promise<CompactedToolOutput | null> {
+  return await safeInvoke("agent_get_tool_output", { sessionId, handle });
+}
+
 // ============================================================
 // aster Agent API (基于 aster 框架的完整 Agent 实现)
 // ============================================================
@@ -734,6 +979,10 @@ export interface TerminalCommandRequest {
   working_dir?: string;
   /** 超时时间（秒） */
   timeout_secs: number;
+  /** 是否已由审批策略自动批准 */
+  auto_approved?: boolean;
+  /** 审批策略的判定原因 */
+  policy_reason?: string;
 }
 
 /**
@@ -752,6 +1001,8 @@ export interface TerminalCommandResponse {
   exit_code?: number;
   /** 是否被用户拒绝 */
   rejected: boolean;
+  /** 在当前会话中始终允许该命令 */
+  always_allow?: boolean;
 }
 
 /**
@@ -769,6 +1020,7 @@ export async function sendTerminalCommandResponse(
     error: response.error,
     exitCode: response.exit_code,
     rejected: response.rejected,
+    alwaysAllow: response.always_allow,
   });
 }
 
@@ -326,6 +326,77 @@ export interface RoutingInfo {
   target_url?: string;
   route_rule?: string;
   load_balance_strategy?: string;
+  trace?: RoutingTrace;
+}
+
+/**
+ * 候选凭证被排除的原因
+ */
+export type CandidateExclusion =
+  | "disabled"
+  | "unhealthy"
+  | "model_not_supported"
+  | "client_incompatible"
+  | "cooling_down"
+  | "concurrency_limit";
+
+/**
+ * 候选凭证筛选结果
+ */
+export interface CandidateTrace {
+  credential_id: string;
+  name?: string;
+  provider_type: string;
+  accepted: boolean;
+  excluded_by?: CandidateExclusion[];
+  risk_level?: "low" | "medium" | "high" | "critical";
+  score?: number;
+}
+
+/**
+ * 路由决策过程
+ */
+export interface RoutingTrace {
+  requested_model: string;
+  fallback_chain?: string[];
+  resolved_model: string;
+  client_type: string;
+  endpoint_provider?: string;
+  default_provider: string;
+  provider_id_header?: string;
+  selected_provider: string;
+  provider_source?:
+    | "provider_id_header"
+    | "fallback_hop"
+    | "endpoint_override"
+    | "default";
+  candidates?: CandidateTrace[];
+  selected_credential?: string;
+  credential_source?: "pool" | "api_key_provider";
+  session_id?: string;
+  conversion?: string;
+}
+
+/**
+ * 结构化输出校验结果
+ */
+export interface StructuredOutputValidation {
+  mode: "native" | "tool_forcing" | "prompt_fallback";
+  schema_name?: string;
+  valid?: boolean; // 流式响应无法校验时为空
+  attempts: number;
+  errors?: string[];
+}
+
+/**
+ * 出站防护审计记录
+ */
+export interface GuardrailEvent {
+  detector: string;
+  action: "blocked" | "masked" | "tokenized" | "restored";
+  location: string; // 命中位置，如 messages[2].content
+  count: number;
+  timestamp: string;
 }
 
 /**
@@ -341,6 +412,9 @@ export interface FlowMetadata {
   routing_info: RoutingInfo;
   injected_params?: Record<string, unknown>;
   context_usage_percentage?: number;
+  unsupported_params?: string[]; // 目标协议无法映射而被丢弃的请求参数
+  structured_output?: StructuredOutputValidation;
+  guardrail_events?: GuardrailEvent[];
 }
 
 /**
@@ -160,6 +160,72 @@ export interface OrchestratorConfig {
   load_balancing: boolean;
   /** 模型池刷新间隔（秒） */
   pool_refresh_interval: number;
+  /** 自适应策略配置 */
+  adaptive?: AdaptiveConfig;
+}
+
+/** 自适应策略探索方式 */
+export type ExplorationMode = "epsilon_greedy" | "ucb";
+
+/** 自适应策略配置 */
+export interface AdaptiveConfig {
+  /** 使用自适应策略的服务等级 */
+  tiers: ServiceTier[];
+  /** 探索方式 */
+  exploration: ExplorationMode;
+  /** ε-greedy 的探索概率 */
+  epsilon: number;
+  /** UCB 探索系数 */
+  ucb_c: number;
+  /** 遥测滚动窗口（秒） */
+  window_secs: number;
+  /** 错误率权重 */
+  error_weight: number;
+  /** TTFB 权重 */
+  ttfb_weight: number;
+  /** 吞吐权重 */
+  throughput_weight: number;
+  /** TTFB 参考值（毫秒） */
+  ttfb_reference_ms: number;
+  /** 吞吐参考值（tokens/s） */
+  throughput_reference_tps: number;
+  /** 保留的决策记录数 */
+  max_decisions: number;
+}
+
+/** 自适应策略候选评分 */
+export interface ArmScore {
+  model_id: string;
+  credential_id: string;
+  provider_type: string;
+  /** 窗口内样本数 */
+  samples: number;
+  error_rate?: number | null;
+  ttfb_ms?: number | null;
+  throughput_tps?: number | null;
+  /** 是否处于冷却期 */
+  cooling: boolean;
+  /** 利用得分 */
+  score: number;
+  /** 探索奖励（仅 UCB） */
+  bonus: number;
+  /** 排除原因 */
+  excluded?: string;
+}
+
+/** 自适应策略决策记录 */
+export interface AdaptiveDecision {
+  timestamp: string;
+  tier: ServiceTier;
+  mode: ExplorationMode;
+  /** 是否为探索选择 */
+  explored: boolean;
+  selected_model?: string | null;
+  selected_credential?: string | null;
+  /** 选择原因 */
+  reason: string;
+  /** 所有候选的评分 */
+  candidates: ArmScore[];
 }
 
 // ============================================================================
@@ -243,6 +309,16 @@ export const orchestratorApi = {
   /** 列出所有可用策略 */
   listStrategies: (): Promise<StrategyInfo[]> => safeInvoke("list_strategies"),
 
+  /** 获取自适应策略最近的决策记录 */
+  getAdaptiveDecisions: (limit?: number): Promise<AdaptiveDecision[]> =>
+    safeInvoke("get_adaptive_decisions", { limit }),
+
+  /** 解释自适应策略的选择 */
+  explainAdaptiveSelection: (
+    request: SelectionRequest,
+  ): Promise<AdaptiveDecision> =>
+    safeInvoke("explain_adaptive_selection", { request }),
+
   /** 获取服务等级列表 */
   listServiceTiers: (): Promise<ServiceTierInfo[]> =>
     safeInvoke("list_service_tiers"),
@@ -79,6 +79,8 @@ export interface TokenStatsSummary {
   estimated_count: number;
   avg_input_tokens: number;
   avg_output_tokens: number;
+  total_cache_creation_tokens?: number;
+  total_cache_read_tokens?: number;
 }
 
 export interface ProviderTokenStats {
@@ -44,7 +44,22 @@ export type HookAction =
   | { update_task_plan: { title: string; content: string; priority: number } }
   | { log_progress: { title: string; content: string } }
   | { record_error: { error_description: string; attempted_solution: string } }
-  | { custom: { action_type: string; parameters: Record<string, string> } };
+  | { custom: { action_type: string; parameters: Record<string, string> } }
+  | { block: { reason: string } }
+  | { modify_input: { set: Record<string, unknown> } }
+  | { inject_feedback: { message: string } }
+  | { run_command: { command: string; timeout_secs?: number } }
+  | { webhook: { url: string; timeout_secs?: number } };
+
+/** 钩子执行结果 */
+export interface HookOutcome {
+  /** 阻止原因（存在时不执行工具） */
+  blocked?: string | null;
+  /** 修改后的工具输入 */
+  tool_input?: unknown;
+  /** 注入下一轮模型输入的反馈 */
+  feedback: string[];
+}
 
 export interface HookExecutionStats {
   execution_count: number;
@@ -58,6 +73,7 @@ export interface HookContextData {
   session_id: string;
   tool_name?: string;
   tool_parameters?: Record<string, string>;
+  tool_input?: unknown;
   tool_result?: string;
   message_content?: string;
   message_count: number;
@@ -77,7 +93,9 @@ export class ToolHooksAPI {
   /**
    * 执行钩子
    */
-  static async executeHooks(request: ExecuteHooksRequest): Promise<void> {
+  static async executeHooks(
+    request: ExecuteHooksRequest,
+  ): Promise<HookOutcome> {
     return invoke("execute_hooks", { request });
   }
 
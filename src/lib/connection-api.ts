@@ -187,6 +187,112 @@ export async function importSSHHost(
   });
 }
 
+// ============================================================================
+// SSH 端口转发
+// ============================================================================
+
+/**
+ * 端口转发类型
+ */
+export type ForwardKind = "local" | "remote" | "dynamic";
+
+/**
+ * 端口转发规则
+ */
+export interface ForwardSpec {
+  kind: ForwardKind;
+  /** 监听地址（未设置表示仅本机） */
+  bind_address?: string | null;
+  /** 监听端口 */
+  bind_port: number;
+  /** 目标主机（动态转发为空） */
+  target_host?: string | null;
+  /** 目标端口（动态转发为空） */
+  target_port?: number | null;
+}
+
+/**
+ * 隧道状态
+ */
+export interface TunnelStatus {
+  /** 隧道 ID，如 `L:localhost:8080` */
+  id: string;
+  spec: ForwardSpec;
+  state: "active" | "error";
+  error?: string | null;
+  /** 当前活跃的连接数 */
+  active_connections: number;
+}
+
+/**
+ * 端口转发连接状态
+ */
+export interface SSHTunnelConnStatus {
+  status: string;
+  connected: boolean;
+  connection: string;
+  error?: string | null;
+  tunnels: TunnelStatus[];
+}
+
+/**
+ * 建立 SSH 连接并启动 ssh_config 中配置的端口转发
+ *
+ * @param connection - 连接字符串或 ssh_config 中的 Host 别名
+ */
+export async function connectSSHTunnel(
+  connection: string,
+  password?: string,
+): Promise<SSHTunnelConnStatus> {
+  return safeInvoke<SSHTunnelConnStatus>("ssh_tunnel_connect", {
+    connection,
+    password,
+  });
+}
+
+/**
+ * 断开连接并停止全部端口转发
+ */
+export async function disconnectSSHTunnel(connection: string): Promise<void> {
+  return safeInvoke("ssh_tunnel_disconnect", { connection });
+}
+
+/**
+ * 获取连接上的隧道
+ */
+export async function listSSHTunnels(
+  connection: string,
+): Promise<TunnelStatus[]> {
+  return safeInvoke<TunnelStatus[]>("ssh_tunnel_list", { connection });
+}
+
+/**
+ * 添加隧道
+ *
+ * @param spec - ssh_config 格式的规则，如 `8080 localhost:80` 或 `1080`
+ */
+export async function addSSHTunnel(
+  connection: string,
+  kind: ForwardKind,
+  spec: string,
+): Promise<TunnelStatus> {
+  return safeInvoke<TunnelStatus>("ssh_tunnel_add", {
+    connection,
+    kind,
+    spec,
+  });
+}
+
+/**
+ * 移除隧道
+ */
+export async function removeSSHTunnel(
+  connection: string,
+  id: string,
+): Promise<void> {
+  return safeInvoke("ssh_tunnel_remove", { connection, id });
+}
+
 /**
  * 将连接名称转换为 terminal 会话的 connection 字符串
  *
@@ -317,6 +317,8 @@ const defaultMocks: Record<string, any> = {
   quick_select_model: () => ({ model: "" }),
   select_model_for_task: () => ({ model: "" }),
   list_strategies: () => [],
+  get_adaptive_decisions: () => [],
+  explain_adaptive_selection: () => ({ candidates: [], reason: "" }),
   list_service_tiers: () => [],
   list_task_hints: () => [],
 
@@ -13,6 +13,7 @@
  * - 发送输入到终端
  * - 调整终端大小
  * - 监听终端输出和状态事件
+ * - 会话录制、导出（asciicast v2 / 纯文本）与回放
  *
  * ## 使用示例
  * ```typescript
@@ -77,6 +78,63 @@ export interface TerminalStatusEvent {
   error?: string;
 }
 
+/** 录制导出格式 */
+export type RecordingExportFormat = "asciicast" | "transcript";
+
+/** 录制章节（对应一条命令） */
+export interface RecordingChapter {
+  /** 章节序号（从 1 开始） */
+  index: number;
+  /** 章节开始时间（秒） */
+  time: number;
+  /** 章节结束时间（秒） */
+  end_time: number;
+  /** 章节标题（通常是命令行） */
+  label: string;
+}
+
+/** 命令历史记录 */
+export interface CommandHistoryEntry {
+  id: number;
+  session_id: string;
+  /** 主机（本地主机名、SSH 主机或 WSL 发行版） */
+  host: string;
+  connection_type: "local" | "ssh" | "wsl";
+  cwd: string | null;
+  command: string;
+  /** 输出片段（已去除 ANSI 控制序列） */
+  output: string;
+  exit_code: number | null;
+  /** 开始时间（毫秒时间戳） */
+  started_at: number;
+  finished_at: number | null;
+  duration_ms: number | null;
+}
+
+/** 命令历史查询结果 */
+export interface CommandHistoryHit extends CommandHistoryEntry {
+  /** 匹配片段（仅全文搜索时存在，匹配词以 <mark> 包裹） */
+  snippet: string | null;
+}
+
+/** 命令历史查询条件 */
+export interface CommandHistoryQuery {
+  /** 全文搜索关键词（匹配命令行和输出） */
+  text?: string;
+  host?: string;
+  /** 工作目录（包含子目录） */
+  cwd?: string;
+  session_id?: string;
+  exit_status?: "success" | "failure";
+  /** 开始时间下限（毫秒时间戳） */
+  since?: number;
+  /** 开始时间上限（毫秒时间戳） */
+  until?: number;
+  /** 最大返回条数（默认 50） */
+  limit?: number;
+  offset?: number;
+}
+
 // ============================================================================
 // 事件名称
 // ============================================================================
@@ -194,6 +252,121 @@ export async function getTerminalSession(
   });
 }
 
+/**
+ * 开始录制终端会话
+ *
+ * @param sessionId - 会话 ID
+ * @returns 录制文件路径
+ */
+export async function startTerminalRecording(
+  sessionId: string,
+): Promise<string> {
+  return safeInvoke<string>("terminal_start_recording", { sessionId });
+}
+
+/**
+ * 停止录制终端会话
+ *
+ * @param sessionId - 会话 ID
+ * @returns 录制文件路径，未在录制时返回 null
+ */
+export async function stopTerminalRecording(
+  sessionId: string,
+): Promise<string | null> {
+  return safeInvoke<string | null>("terminal_stop_recording", { sessionId });
+}
+
+/**
+ * 导出终端会话录制
+ *
+ * @param sessionId - 会话 ID
+ * @param format - 导出格式（默认 asciicast）
+ * @param includeInput - asciicast 是否包含输入事件
+ * @returns 导出内容
+ */
+export async function exportTerminalRecording(
+  sessionId: string,
+  format: RecordingExportFormat = "asciicast",
+  includeInput = false,
+): Promise<string> {
+  return safeInvoke<string>("terminal_export_recording", {
+    sessionId,
+    format,
+    includeInput,
+  });
+}
+
+/**
+ * 获取终端会话录制的章节列表
+ *
+ * @param sessionId - 会话 ID
+ * @returns 章节列表
+ */
+export async function listTerminalRecordingChapters(
+  sessionId: string,
+): Promise<RecordingChapter[]> {
+  return safeInvoke<RecordingChapter[]>("terminal_list_recording_chapters", {
+    sessionId,
+  });
+}
+
+/**
+ * 将终端会话录制回放到新的块
+ *
+ * @param sessionId - 会话 ID
+ * @param speed - 回放速度倍率（默认 1.0）
+ * @returns 新块 ID（输出事件的 session_id）
+ */
+export async function replayTerminalRecording(
+  sessionId: string,
+  speed?: number,
+): Promise<string> {
+  return safeInvoke<string>("terminal_replay_recording", { sessionId, speed });
+}
+
+// ============================================================================
+// 命令历史
+// ============================================================================
+
+/**
+ * 搜索命令历史
+ *
+ * @param query - 查询条件
+ * @returns 按开始时间倒序排列的命令记录
+ */
+export async function searchCommandHistory(
+  query: CommandHistoryQuery = {},
+): Promise<CommandHistoryHit[]> {
+  return safeInvoke<CommandHistoryHit[]>("terminal_search_command_history", {
+    query,
+  });
+}
+
+/**
+ * 在会话中重新执行历史命令
+ *
+ * @param entryId - 命令历史记录 ID
+ * @param sessionId - 目标会话 ID
+ */
+export async function rerunCommand(
+  entryId: number,
+  sessionId: string,
+): Promise<CommandHistoryEntry> {
+  return safeInvoke<CommandHistoryEntry>("terminal_rerun_command", {
+    entryId,
+    sessionId,
+  });
+}
+
+/**
+ * 删除命令历史记录
+ *
+ * @param entryId - 命令历史记录 ID
+ */
+export async function deleteCommandHistory(entryId: number): Promise<void> {
+  await safeInvoke("terminal_delete_command_history", { entryId });
+}
+
 // ============================================================================
 // 事件监听
 // ============================================================================